@@ -0,0 +1,132 @@
+//! Allocations and memory held by the attributes of a log
+//!
+//! Counts the allocations it takes to build the attributes of all events of an example log and
+//! the bytes they hold on to, once with a string allocated for every key and value and once with
+//! keys shared by a `KeyTable` and values stored as `SmallString`. Run with
+//! `cargo bench --bench attributes`.
+//!
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use promi::stream::xes::XesReader;
+use promi::stream::{AttributeValue, Component, Event, Key, KeyTable, SmallString, Stream};
+
+/// Global allocator that keeps track of the number of allocations and the bytes in use
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        IN_USE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Allocations made and bytes retained while building a value
+struct Usage {
+    allocations: usize,
+    bytes: usize,
+}
+
+fn measure<T, F: FnOnce() -> T>(build: F) -> (T, Usage) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let in_use = IN_USE.load(Ordering::Relaxed);
+    let value = build();
+    let usage = Usage {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        bytes: IN_USE.load(Ordering::Relaxed).saturating_sub(in_use),
+    };
+    (value, usage)
+}
+
+fn read_events(path: &PathBuf) -> Vec<Event> {
+    let mut reader = XesReader::from(BufReader::new(File::open(path).unwrap()));
+    let mut events = Vec::new();
+    while let Some(component) = reader.next().unwrap() {
+        if let Component::Trace(trace) = component {
+            events.extend(trace.events);
+        }
+    }
+    events
+}
+
+fn report(name: &str, usage: &Usage, events: usize) {
+    println!(
+        "{:<28} {:>10.1} allocations/event {:>10.1} bytes/event",
+        name,
+        usage.allocations as f64 / events as f64,
+        usage.bytes as f64 / events as f64
+    );
+}
+
+fn main() {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "static",
+        "xes",
+        "book",
+        "bigger-example.xes",
+    ]
+    .iter()
+    .collect();
+
+    let (events, usage) = measure(|| read_events(&path));
+    let n = events.len();
+    println!("{} events read from {:?}", n, path);
+    report("XES reader", &usage, n);
+
+    // the string attributes of each event, e.g. activity and resource
+    let attributes: Vec<Vec<(String, String)>> = events
+        .iter()
+        .map(|event| {
+            event
+                .attributes
+                .iter()
+                .filter_map(|(key, value, _)| match value {
+                    AttributeValue::String(value) => Some((key.to_string(), value.to_string())),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    drop(events);
+
+    let (strings, usage) = measure(|| {
+        attributes
+            .iter()
+            .map(|event| event.iter().cloned().collect::<BTreeMap<String, String>>())
+            .collect::<Vec<_>>()
+    });
+    report("string keys and values", &usage, n);
+    drop(strings);
+
+    let (maps, usage) = measure(|| {
+        let mut keys = KeyTable::default();
+        attributes
+            .iter()
+            .map(|event| {
+                event
+                    .iter()
+                    .map(|(key, value)| (keys.intern(key), SmallString::from(value.as_str())))
+                    .collect::<BTreeMap<Key, SmallString>>()
+            })
+            .collect::<Vec<_>>()
+    });
+    report("key table and small strings", &usage, n);
+    drop(maps);
+}
@@ -0,0 +1,143 @@
+//! Public event logs for the examples, downloaded once into a local cache
+//!
+//! The logs of the BPI Challenges and related datasets are published under a DOI at the 4TU
+//! research data repository. Since the repository doesn't offer stable direct links to the files,
+//! the download URL of a dataset is taken from the environment variable
+//! `PROMI_DATASET_URL_<KEY>`, e.g. `PROMI_DATASET_URL_BPI2012`. Alternatively, a manually
+//! downloaded file is picked up if it is placed in the cache as `<key>.xes.gz`. The cache is
+//! located at `$PROMI_DATASET_CACHE`, or `target/datasets` by default.
+//!
+//! Datasets that are neither cached nor downloadable are replaced by a small log shipped with
+//! promi, such that the examples run offline, too.
+//!
+
+use std::env;
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use promi::stream::xes::{XesReader, XesReaderOptions};
+use promi::{Error, Result};
+
+/// Public event log
+pub struct Dataset {
+    pub key: &'static str,
+    pub title: &'static str,
+    pub doi: &'static str,
+}
+
+pub const DATASETS: [Dataset; 4] = [
+    Dataset {
+        key: "bpi2012",
+        title: "BPI Challenge 2012",
+        doi: "10.4121/uuid:3926db30-f712-4394-aebc-75976070e91f",
+    },
+    Dataset {
+        key: "bpi2017",
+        title: "BPI Challenge 2017",
+        doi: "10.4121/uuid:5f3067df-f10b-45da-b98b-86ae4c7a310b",
+    },
+    Dataset {
+        key: "roadfines",
+        title: "Road Traffic Fine Management Process",
+        doi: "10.4121/uuid:270fd440-1057-4fb9-89a9-b699b47990f5",
+    },
+    Dataset {
+        key: "sepsis",
+        title: "Sepsis Cases",
+        doi: "10.4121/uuid:915d2bfb-7e84-49ad-a286-dc35f063a460",
+    },
+];
+
+/// Log that stands in for datasets that are not available
+const FALLBACK: &[&str] = &["static", "xes", "book", "bigger-example.xes"];
+
+impl Dataset {
+    /// Look up a dataset by its key
+    pub fn find(key: &str) -> Result<&'static Dataset> {
+        DATASETS.iter().find(|d| d.key == key).ok_or_else(|| {
+            let keys: Vec<_> = DATASETS.iter().map(|d| d.key).collect();
+            Error::KeyError(format!("dataset {:?}, choose one of {:?}", key, keys))
+        })
+    }
+
+    /// Dataset selected by the first command line argument, BPI Challenge 2012 by default
+    pub fn from_args() -> Result<&'static Dataset> {
+        Dataset::find(&env::args().nth(1).unwrap_or_else(|| "bpi2012".to_string()))
+    }
+
+    fn cache() -> PathBuf {
+        match env::var_os("PROMI_DATASET_CACHE") {
+            Some(cache) => PathBuf::from(cache),
+            None => Path::new(env!("CARGO_MANIFEST_DIR")).join("target/datasets"),
+        }
+    }
+
+    fn cached(&self) -> PathBuf {
+        Dataset::cache().join(format!("{}.xes.gz", self.key))
+    }
+
+    /// Path in the cache for results derived from the dataset
+    #[allow(dead_code)]
+    pub fn output(&self, extension: &str) -> Result<PathBuf> {
+        fs::create_dir_all(Dataset::cache())?;
+        Ok(Dataset::cache().join(format!("{}.{}", self.key, extension)))
+    }
+
+    fn download(&self, url: &str, target: &Path) -> Result<()> {
+        fs::create_dir_all(target.parent().unwrap())?;
+        let partial = target.with_extension("partial");
+
+        println!("downloading {} from {}", self.title, url);
+        let status = Command::new("curl")
+            .args([
+                "--fail",
+                "--location",
+                "--silent",
+                "--show-error",
+                "--output",
+            ])
+            .arg(&partial)
+            .arg(url)
+            .status()?;
+        if !status.success() {
+            let _ = fs::remove_file(&partial);
+            return Err(Error::IOError(format!("download of {} failed", url)));
+        }
+
+        // only complete downloads enter the cache
+        fs::rename(&partial, target)?;
+        Ok(())
+    }
+
+    /// Local path of the dataset, downloaded if necessary
+    pub fn fetch(&self) -> Result<PathBuf> {
+        let cached = self.cached();
+        if cached.exists() {
+            return Ok(cached);
+        }
+
+        let variable = format!("PROMI_DATASET_URL_{}", self.key.to_uppercase());
+        match env::var(&variable) {
+            Ok(url) => {
+                self.download(&url, &cached)?;
+                Ok(cached)
+            }
+            Err(_) => {
+                let fallback: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join(FALLBACK.iter().collect::<PathBuf>());
+                println!(
+                    "{} (doi:{}) is not cached at {:?} and {} is not set, using {:?} instead",
+                    self.title, self.doi, cached, variable, fallback
+                );
+                Ok(fallback)
+            }
+        }
+    }
+}
+
+/// Fresh stream of a fetched dataset
+pub fn open(path: &Path) -> Result<XesReader<impl BufRead>> {
+    XesReaderOptions::default().open(path)
+}
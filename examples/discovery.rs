@@ -0,0 +1,87 @@
+//! Discovery and conformance checking on a public event log
+//!
+//! ```text
+//! cargo run --release --example discovery -- bpi2012
+//! ```
+//!
+//! The first pass over the log checks its quality and collects statistics, variants, the
+//! footprint and the heuristic net. The second pass replays the log on the net that the
+//! α-algorithm discovers from the footprint.
+//!
+
+mod common;
+
+use promi::model::alpha::alpha;
+use promi::model::footprint::{Footprint, FootprintGenerator};
+use promi::model::heuristic::{HeuristicMiner, HeuristicNet};
+use promi::model::replay::{ReplayStatistics, TokenReplay};
+use promi::stream::observer::Handler;
+use promi::stream::quality::{Issue, QualityChecker, QualityReport};
+use promi::stream::stats::{Statistics, StatsCollector};
+use promi::stream::variant::{VariantCollector, Variants};
+use promi::stream::void::consume;
+use promi::stream::AnyArtifact;
+use promi::Result;
+
+use common::Dataset;
+
+fn main() -> Result<()> {
+    let dataset = Dataset::from_args()?;
+    let path = dataset.fetch()?;
+
+    // file > XesReader > QualityChecker > Statistics > Variants > Footprint > HeuristicMiner
+    let observer = QualityChecker::default().into_observer(common::open(&path)?);
+    let observer = StatsCollector::default().into_observer(observer);
+    let observer = VariantCollector::default().into_observer(observer);
+    let observer = FootprintGenerator::default().into_observer(observer);
+    let mut observer = HeuristicMiner::default().into_observer(observer);
+    let artifacts = consume(&mut observer)?;
+    let mut artifacts = artifacts.iter().flatten();
+
+    println!("# {}\n", dataset.title);
+    let statistics = AnyArtifact::find::<Statistics>(&mut artifacts.clone()).unwrap();
+    println!("{}\n", statistics);
+
+    let quality = AnyArtifact::find::<QualityReport>(&mut artifacts.clone()).unwrap();
+    for issue in Issue::ALL.iter() {
+        println!("{:<24}{:>10}", issue, quality.count(*issue));
+    }
+
+    let variants = AnyArtifact::find::<Variants>(&mut artifacts.clone()).unwrap();
+    println!(
+        "\n{} variants, the top 10 cover {:.1}% of {} traces, 80% are covered by {}",
+        variants.variants.len(),
+        variants.cumulative_coverage(9),
+        variants.traces,
+        variants.covering(80.)
+    );
+
+    let heuristic = AnyArtifact::find::<HeuristicNet>(&mut artifacts.clone()).unwrap();
+    let arcs: usize = heuristic.dependencies.values().map(|t| t.len()).sum();
+    println!(
+        "heuristic net: {} activities, {} dependencies",
+        heuristic.activities.len(),
+        arcs
+    );
+
+    // file > XesReader > TokenReplay
+    let footprint = AnyArtifact::find::<Footprint>(&mut artifacts).unwrap();
+    let net = alpha(footprint)?;
+    println!(
+        "α net: {} places, {} transitions",
+        net.places.len(),
+        net.transitions.len()
+    );
+
+    let mut observer = TokenReplay::new(net)?.into_observer(common::open(&path)?);
+    let artifacts = consume(&mut observer)?;
+    let replay = AnyArtifact::find::<ReplayStatistics>(&mut artifacts.iter().flatten()).unwrap();
+    println!(
+        "token replay: fitness {:.3}, {} of {} traces fit",
+        replay.fitness(),
+        replay.fitting_traces,
+        replay.traces
+    );
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! Process report of a public event log
+//!
+//! ```text
+//! cargo run --release --example report -- sepsis
+//! ```
+//!
+//! The log is streamed through a bottleneck analysis into a `ReportSink`, which renders
+//! statistics, variants, the directly-follows graph and the bottlenecks into a single HTML file
+//! in the dataset cache.
+//!
+
+mod common;
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use promi::stream::bottleneck::{BottleneckAnalysis, Bottlenecks};
+use promi::stream::observer::Handler;
+use promi::stream::report::{DocumentFormat, ReportSink};
+use promi::stream::void::consume;
+use promi::stream::{AnyArtifact, Sink};
+use promi::Result;
+
+use common::Dataset;
+
+fn main() -> Result<()> {
+    let dataset = Dataset::from_args()?;
+    let path = dataset.fetch()?;
+
+    // file > XesReader > BottleneckAnalysis > void
+    let mut observer = BottleneckAnalysis::default().into_observer(common::open(&path)?);
+    let artifacts = consume(&mut observer)?;
+    let bottlenecks = AnyArtifact::find::<Bottlenecks>(&mut artifacts.iter().flatten())
+        .unwrap()
+        .clone();
+
+    // file > XesReader > ReportSink > HTML file
+    let output = dataset.output("html")?;
+    let mut sink = ReportSink::new(BufWriter::new(File::create(&output)?), DocumentFormat::Html)
+        .title(dataset.title)
+        .max_variants(20)
+        .artifact(bottlenecks.into());
+    sink.consume(&mut common::open(&path)?)?;
+
+    println!("report of {} written to {:?}", dataset.title, output);
+    Ok(())
+}
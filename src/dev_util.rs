@@ -13,8 +13,7 @@ use simple_logger::SimpleLogger;
 
 use crate::stream::buffer::Buffer;
 use crate::stream::xes::XesReader;
-use crate::stream::{AnyArtifact, ResOpt, Sink, Stream};
-use crate::{Error, Result};
+use crate::stream::Sink;
 
 static LOGGER: Once = Once::new();
 
@@ -77,63 +76,6 @@ macro_rules! join_static_reader {
     };
 }
 
-/// Stream that fails on purpose after any number of components or while emitting artifacts
-struct FailingStream<T: Stream> {
-    stream: T,
-    count: i64,
-    fails: i64,
-}
-
-impl<T: Stream> FailingStream<T> {
-    /// Create a new failing stream
-    ///
-    /// If _fails_ is set to a non negative value the stream will turn into the error state after
-    /// this number of components returned or the very last one. In the case _fails_ is negative,
-    /// the stream succeeds but fails on emitting artifacts.
-    ///
-    pub fn new(stream: T, fails: i64) -> Self {
-        Self {
-            stream,
-            count: 0,
-            fails,
-        }
-    }
-}
-
-impl<T: Stream> Stream for FailingStream<T> {
-    fn inner_ref(&self) -> Option<&dyn Stream> {
-        Some(&self.stream)
-    }
-
-    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
-        Some(&mut self.stream)
-    }
-
-    fn next(&mut self) -> ResOpt {
-        self.count += 1;
-
-        match (
-            self.stream.next()?,
-            self.count >= self.fails - 1,
-            self.fails >= 0,
-        ) {
-            (Some(next), _, false) | (Some(next), false, true) => Ok(Some(next)),
-            (None, _, false) => Ok(None),
-            (Some(_), true, true) | (None, _, true) => Err(Error::StreamError(format!(
-                "[{}/{}] stream failed on purpose on component",
-                self.count, self.fails
-            ))),
-        }
-    }
-
-    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
-        Err(Error::ArtifactError(format!(
-            "[{}/{}] stream failed on purpose on emitting artifacts",
-            self.count, self.fails
-        )))
-    }
-}
-
 lazy_static! {
     /// Cache for example event streams
     static ref CACHE: Mutex<HashMap<String, Buffer>> = Mutex::new(HashMap::new());
@@ -197,8 +139,6 @@ where
 
 #[cfg(test)]
 pub mod tests {
-    use crate::stream::void::consume;
-
     use super::*;
 
     #[test]
@@ -207,23 +147,6 @@ pub mod tests {
         info!("logging enabled!");
     }
 
-    #[test]
-    fn test_failing_stream() {
-        for i in 0..10 {
-            let mut failing = FailingStream::new(load_example(&["book", "L1.xes"]), i);
-            match consume(&mut failing) {
-                Err(Error::StreamError(_)) => (),
-                other => panic!("expected stream error, got {:?}", other),
-            }
-        }
-
-        let mut failing = FailingStream::new(load_example(&["book", "L1.xes"]), -1);
-        match consume(&mut failing) {
-            Err(Error::ArtifactError(_)) => (),
-            other => panic!("expected artifact error, got {:?}", other),
-        }
-    }
-
     #[test]
     fn test_pass_m_of_n_success() {
         retry_up_to(1, || ());
@@ -41,6 +41,9 @@ pub enum Error {
     #[error("{0}")]
     XesError(String),
 
+    #[error("Parse Error: {0}")]
+    ParseError(Box<crate::stream::xes::ParseDiagnostic>),
+
     #[error("{0}")]
     ChannelError(String),
 
@@ -55,12 +58,87 @@ pub enum Error {
 
     #[error("{0}")]
     FlowError(String),
+
+    #[error("{0}")]
+    NotificationError(String),
+
+    #[error("IO Error: {0}")]
+    IOError(String),
+
+    #[error("Resource Limit: {0}")]
+    ResourceLimit(String),
+
+    #[error("Model Error: {0}")]
+    ModelError(String),
+
+    #[error("Vault Error: {0}")]
+    VaultError(String),
+
+    #[error("CSV Error: {0}")]
+    CsvError(String),
+
+    #[error("JSON Error: {0}")]
+    JsonError(String),
+
+    #[error("Arrow Error: {0}")]
+    ArrowError(String),
+
+    #[error("Expression Error: {0}")]
+    ExpressionError(String),
+
+    #[error("Unit Error: {0}")]
+    UnitError(String),
+
+    #[error("Data Quality Error: {0}")]
+    QualityError(Box<crate::stream::quality::Finding>),
+
+    #[error("{1} (at {0})")]
+    ComponentError(String, Box<Error>),
+}
+
+impl Error {
+    /// Whether the error is likely to vanish if the failed operation is repeated
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::IOError(_) => true,
+            Error::ComponentError(_, error) => error.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// Data quality finding that caused the error, if any
+    pub fn finding(&self) -> Option<&crate::stream::quality::Finding> {
+        match self {
+            Error::QualityError(finding) => Some(finding),
+            Error::ComponentError(_, error) => error.finding(),
+            _ => None,
+        }
+    }
+
+    /// Location in the parsed document that caused the error, if any
+    pub fn diagnostic(&self) -> Option<&crate::stream::xes::ParseDiagnostic> {
+        match self {
+            Error::ParseError(diagnostic) => Some(diagnostic),
+            Error::ComponentError(_, error) => error.diagnostic(),
+            _ => None,
+        }
+    }
 }
 
 // Manual conversion as quick-xml errors don't support cloning
 impl From<quick_xml::Error> for Error {
     fn from(error: quick_xml::Error) -> Self {
-        Error::XMLError(format!("{:?}", error))
+        match error {
+            quick_xml::Error::Io(error) => error.into(),
+            error => Error::XMLError(format!("{:?}", error)),
+        }
+    }
+}
+
+// Manual conversion as io errors don't support cloning
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IOError(format!("{:?}", error))
     }
 }
 
@@ -71,6 +149,26 @@ impl From<std::string::FromUtf8Error> for Error {
     }
 }
 
+impl From<std::str::Utf8Error> for Error {
+    fn from(error: std::str::Utf8Error) -> Self {
+        Error::FromUtf8Error(format!("{:?}", error))
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(error: arrow_schema::ArrowError) -> Self {
+        Error::ArrowError(error.to_string())
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        Error::ArrowError(error.to_string())
+    }
+}
+
 // Manual conversion to prevent recursion
 impl From<std::sync::mpsc::SendError<crate::stream::ResOpt>> for Error {
     fn from(error: std::sync::mpsc::SendError<crate::stream::ResOpt>) -> Self {
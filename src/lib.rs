@@ -54,6 +54,10 @@ pub use error::{Error, Result};
 #[macro_use]
 pub mod dev_util;
 pub mod error;
+pub mod model;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod render;
 pub mod stream;
 
 /// promi's datetime type
@@ -0,0 +1,232 @@
+//! The α-algorithm
+//!
+//! The α-algorithm discovers a workflow net from the footprint of a log. Each activity becomes a
+//! transition. Places connect sets of activities `A` and `B` such that each activity of `A` causes
+//! each activity of `B` while the activities within `A` and within `B` exclude each other. Only
+//! maximal such pairs are kept. Additionally, the source place `i` leads to all start activities
+//! and all end activities lead to the sink place `o`.
+//!
+//! The algorithm is known to fail on short loops, i.e. loops of length one or two, as well as on
+//! non-local dependencies. See van der Aalst, _Process Mining: Data Science in Action_, 2016.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::model::footprint::{Footprint, Relation};
+use crate::model::petri::PetriNet;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, ResOpt, Stream};
+use crate::{Error, Result};
+
+type Activities = BTreeSet<String>;
+
+fn label(activities: &Activities) -> String {
+    activities.iter().cloned().collect::<Vec<_>>().join(",")
+}
+
+/// Maximal pairs of activity sets that make up the inner places of the net
+pub fn place_pairs(footprint: &Footprint) -> Vec<(Activities, Activities)> {
+    let causal = |a: &str, b: &str| footprint.relation(a, b) == Relation::Causal;
+    let choice = |a: &str, b: &str| footprint.relation(a, b) == Relation::Choice;
+    let independent = |set: &Activities, x: &str| set.iter().all(|y| choice(x, y));
+
+    // grow pairs from single causal relations until no activity can be added
+    let mut pairs: BTreeSet<(Activities, Activities)> = BTreeSet::new();
+    let mut queue: Vec<(Activities, Activities)> = Vec::new();
+    for a in footprint.activities.iter() {
+        for b in footprint.activities.iter() {
+            if causal(a, b) {
+                let pair = (
+                    std::iter::once(a.clone()).collect(),
+                    std::iter::once(b.clone()).collect(),
+                );
+                if pairs.insert(pair.clone()) {
+                    queue.push(pair);
+                }
+            }
+        }
+    }
+
+    while let Some((inputs, outputs)) = queue.pop() {
+        for x in footprint.activities.iter() {
+            let mut candidates = Vec::new();
+            if !inputs.contains(x)
+                && choice(x, x)
+                && independent(&inputs, x)
+                && outputs.iter().all(|b| causal(x, b))
+            {
+                let mut grown = inputs.clone();
+                grown.insert(x.clone());
+                candidates.push((grown, outputs.clone()));
+            }
+            if !outputs.contains(x)
+                && choice(x, x)
+                && independent(&outputs, x)
+                && inputs.iter().all(|a| causal(a, x))
+            {
+                let mut grown = outputs.clone();
+                grown.insert(x.clone());
+                candidates.push((inputs.clone(), grown));
+            }
+
+            for candidate in candidates {
+                if pairs.insert(candidate.clone()) {
+                    queue.push(candidate);
+                }
+            }
+        }
+    }
+
+    pairs
+        .iter()
+        .filter(|(a, b)| {
+            !pairs
+                .iter()
+                .any(|(c, d)| (c, d) != (a, b) && a.is_subset(c) && b.is_subset(d))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Discover a workflow net from a footprint
+pub fn alpha(footprint: &Footprint) -> Result<PetriNet> {
+    if footprint.start.is_empty() || footprint.end.is_empty() {
+        return Err(Error::ModelError(
+            "footprint lacks start or end activities".to_string(),
+        ));
+    }
+
+    let mut net = PetriNet::default();
+    let transitions: BTreeMap<&str, usize> = footprint
+        .activities
+        .iter()
+        .map(|a| (a.as_str(), net.add_transition(a.as_str(), Some(a))))
+        .collect();
+    let transition = |activity: &str| {
+        transitions
+            .get(activity)
+            .copied()
+            .ok_or_else(|| Error::ModelError(format!("unknown activity {:?}", activity)))
+    };
+
+    let source = net.add_place("i");
+    for activity in footprint.start.iter() {
+        net.add_input_arc(source, transition(activity)?)?;
+    }
+
+    for (inputs, outputs) in place_pairs(footprint) {
+        let place = net.add_place(format!("({{{}}},{{{}}})", label(&inputs), label(&outputs)));
+        for activity in inputs.iter() {
+            net.add_output_arc(transition(activity)?, place)?;
+        }
+        for activity in outputs.iter() {
+            net.add_input_arc(place, transition(activity)?)?;
+        }
+    }
+
+    let sink = net.add_place("o");
+    for activity in footprint.end.iter() {
+        net.add_output_arc(transition(activity)?, sink)?;
+    }
+
+    Ok(net)
+}
+
+/// Mine a Petri net from a footprint artifact
+///
+/// The miner is a stream without components that emits the net as its only artifact.
+///
+pub struct AlphaMiner {
+    footprint: Footprint,
+}
+
+impl AlphaMiner {
+    pub fn new(footprint: Footprint) -> Self {
+        AlphaMiner { footprint }
+    }
+}
+
+impl Stream for AlphaMiner {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        Ok(None)
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![alpha(&self.footprint)?.into()])
+    }
+}
+
+impl PluginProvider for AlphaMiner {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "AlphaMiner",
+            "Discover a Petri net from a footprint artifact with the α-algorithm",
+            Factory::new(
+                Declaration::default()
+                    .typed_artifact::<Footprint, _, _>("footprint", "The footprint of the log"),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let artifact = parameters.acquire_artifact("footprint")?;
+                    match artifact.downcast_ref::<Footprint>() {
+                        Some(footprint) => Ok(AlphaMiner::new(footprint.clone()).into_boxed()),
+                        None => Err(Error::ArtifactError(format!(
+                            "expected footprint artifact, got {:?}",
+                            artifact
+                        ))),
+                    }
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::model::footprint::FootprintGenerator;
+    use crate::model::soundness::check_soundness;
+    use crate::stream::observer::Handler;
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    fn footprint(path: &[&str]) -> Footprint {
+        let mut observer = FootprintGenerator::default().into_observer(load_example(path));
+        consume(&mut observer).unwrap();
+        observer.release().unwrap().footprint()
+    }
+
+    #[test]
+    fn test_alpha() {
+        let footprint = footprint(&["book", "L1.xes"]);
+        let mut miner = AlphaMiner::new(footprint);
+        let artifacts = consume(&mut miner).unwrap();
+        let net = AnyArtifact::find::<PetriNet>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(
+            net.places,
+            [
+                "i",
+                "({a},{b,e})",
+                "({a},{c,e})",
+                "({b,e},{d})",
+                "({c,e},{d})",
+                "o"
+            ]
+        );
+        assert_eq!(net.transitions.len(), 5);
+        assert!(check_soundness(net, 1000).unwrap().is_sound());
+
+        assert!(alpha(&Footprint::default()).is_err());
+    }
+}
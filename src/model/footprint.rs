@@ -0,0 +1,253 @@
+//! Footprints of event logs
+//!
+//! A footprint condenses a log into the ordering relations between its activities, derived from
+//! the directly-follows relation `>`:
+//! * causality `a → b` iff `a > b` and not `b > a`,
+//! * its reverse `a ← b` iff `b > a` and not `a > b`,
+//! * parallelism `a || b` iff `a > b` and `b > a` and
+//! * choice `a # b` iff neither `a > b` nor `b > a`.
+//!
+//! Along with the activities that start and end cases, this is all the α-algorithm needs, see
+//! `model::alpha`. The `FootprintGenerator` handler releases the footprint of a stream as artifact.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::dfg::{ActivitySource, Dfg, DfgGenerator};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeType, Event, Meta, Stream, Trace};
+use crate::Result;
+
+/// Ordering relation of two activities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Causal,
+    Reverse,
+    Parallel,
+    Choice,
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Relation::Causal => "→",
+            Relation::Reverse => "←",
+            Relation::Parallel => "||",
+            Relation::Choice => "#",
+        })
+    }
+}
+
+/// Ordering relations between the activities of a log
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Footprint {
+    pub activities: BTreeSet<String>,
+    /// Activities that start a case
+    pub start: BTreeSet<String>,
+    /// Activities that end a case
+    pub end: BTreeSet<String>,
+    /// Directly-follows relation, indexed by source activity
+    pub follows: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Footprint {
+    /// Whether `a > b`, i.e. `b` directly follows `a` at least once
+    pub fn directly_follows(&self, a: &str, b: &str) -> bool {
+        self.follows
+            .get(a)
+            .is_some_and(|targets| targets.contains(b))
+    }
+
+    pub fn relation(&self, a: &str, b: &str) -> Relation {
+        match (self.directly_follows(a, b), self.directly_follows(b, a)) {
+            (true, false) => Relation::Causal,
+            (false, true) => Relation::Reverse,
+            (true, true) => Relation::Parallel,
+            (false, false) => Relation::Choice,
+        }
+    }
+}
+
+impl From<&Dfg> for Footprint {
+    fn from(dfg: &Dfg) -> Self {
+        let positive = |weights: &BTreeMap<String, f64>| -> BTreeSet<String> {
+            weights
+                .iter()
+                .filter(|(_, weight)| **weight > 0.0)
+                .map(|(activity, _)| activity.clone())
+                .collect()
+        };
+
+        Footprint {
+            activities: positive(&dfg.activities),
+            start: positive(&dfg.start),
+            end: positive(&dfg.end),
+            follows: dfg
+                .edges
+                .iter()
+                .map(|(from, targets)| (from.clone(), positive(targets)))
+                .filter(|(_, targets)| !targets.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// Render the footprint matrix, rows are the first, columns the second operand of each relation
+impl fmt::Display for Footprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .activities
+            .iter()
+            .map(|a| a.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(2);
+
+        write!(f, "{:width$}", "", width = width)?;
+        for column in self.activities.iter() {
+            write!(f, " {:width$}", column, width = width)?;
+        }
+        for row in self.activities.iter() {
+            write!(f, "\n{:width$}", row, width = width)?;
+            for column in self.activities.iter() {
+                let relation = self.relation(row, column).to_string();
+                write!(f, " {:width$}", relation, width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Footprint {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Derive the footprint of all traces of a stream
+///
+/// Activities are taken from an attribute or a classifier just like by the `DfgGenerator`.
+///
+#[derive(Default)]
+pub struct FootprintGenerator {
+    dfg: DfgGenerator,
+}
+
+impl FootprintGenerator {
+    pub fn new(source: ActivitySource) -> Self {
+        FootprintGenerator {
+            dfg: DfgGenerator::new(source),
+        }
+    }
+
+    /// The footprint derived so far
+    pub fn footprint(&self) -> Footprint {
+        Footprint::from(self.dfg.dfg())
+    }
+}
+
+impl Handler for FootprintGenerator {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.dfg.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        self.dfg.on_trace(trace)
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        self.dfg.on_event(event, in_trace)
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        self.dfg.on_unfolded_event(event)
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.footprint().into()])
+    }
+}
+
+impl PluginProvider for FootprintGenerator {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "FootprintGenerator",
+            "Derive the footprint of all traces by activity attribute or classifier",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be observed")
+                    .default_attr("activity", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    })
+                    .optional_attr(
+                        "classifier",
+                        "Classifier that takes precedence over the activity attribute",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let activity = parameters
+                        .acquire_attribute("activity")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let source = match parameters.acquire_attribute("classifier") {
+                        Ok(classifier) => {
+                            ActivitySource::Classifier(classifier.value.try_string()?.to_string())
+                        }
+                        Err(_) => ActivitySource::Attribute(activity),
+                    };
+
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        FootprintGenerator::new(source),
+                    ))
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    #[test]
+    fn test_footprint() {
+        let mut observer =
+            FootprintGenerator::default().into_observer(load_example(&["book", "L1.xes"]));
+        let artifacts = consume(&mut observer).unwrap();
+        let footprint = AnyArtifact::find::<Footprint>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(
+            footprint.start,
+            ["a"].iter().map(|a| a.to_string()).collect()
+        );
+        assert_eq!(footprint.end, ["d"].iter().map(|a| a.to_string()).collect());
+        assert_eq!(footprint.relation("a", "b"), Relation::Causal);
+        assert_eq!(footprint.relation("d", "c"), Relation::Reverse);
+        assert_eq!(footprint.relation("b", "c"), Relation::Parallel);
+        assert_eq!(footprint.relation("b", "e"), Relation::Choice);
+        assert_eq!(footprint.relation("a", "a"), Relation::Choice);
+
+        let matrix = footprint.to_string();
+        assert_eq!(matrix.lines().next(), Some("   a  b  c  d  e "));
+        assert_eq!(matrix.lines().nth(2), Some("b  ←  #  || →  # "));
+    }
+}
@@ -0,0 +1,573 @@
+//! The heuristic miner
+//!
+//! Unlike the α-algorithm, the heuristic miner takes frequencies into account and is thus robust
+//! against noise and infrequent behavior. It derives the dependency measure of each pair of
+//! activities from how often they directly follow each other:
+//! * `a ⇒ b = (|a > b| - |b > a|) / (|a > b| + |b > a| + 1)` for `a ≠ b`,
+//! * `a ⇒ a = |a > a| / (|a > a| + 1)` for loops of length one and
+//! * `a ⇒2 b = (|a >> b| + |b >> a|) / (|a >> b| + |b >> a| + 1)` for loops of length two, where
+//!   `|a >> b|` counts the occurrences of `a b a`.
+//!
+//! Dependencies that pass the thresholds make up the dependency graph. Outputs of an activity are
+//! pairwise bound by an AND split if `(|b > c| + |c > b|) / (|a > b| + |a > c| + 1)` reaches the AND
+//! threshold and by an XOR split otherwise, inputs are bound by joins likewise. See Weijters and
+//! Ribeiro, _Flexible Heuristics Miner (FHM)_, 2011.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::dfg::{ActivitySource, DfgGenerator};
+use crate::stream::graphml::{GraphMl, KeyScope, KeyType, ToGraphMl};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeType, Event, Meta, Stream, Trace};
+use crate::{Error, Result};
+
+/// Thresholds that decide which dependencies make it into the heuristic net
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Minimal dependency measure of an arc
+    pub dependency: f64,
+    /// Arcs within this distance of the best in- or output of an activity are accepted
+    pub relative_to_best: f64,
+    /// Minimal number of times `b` directly follows `a` for an arc `a → b`
+    pub positive_observations: usize,
+    /// Minimal dependency measure of loops of length one
+    pub loop_length_one: f64,
+    /// Minimal dependency measure of loops of length two
+    pub loop_length_two: f64,
+    /// Minimal measure of pairs of outputs (inputs) to form an AND split (join)
+    pub and: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            dependency: 0.9,
+            relative_to_best: 0.05,
+            positive_observations: 1,
+            loop_length_one: 0.9,
+            loop_length_two: 0.9,
+            and: 0.1,
+        }
+    }
+}
+
+/// Kind of split or join
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gate {
+    And,
+    Xor,
+}
+
+/// Pair of outputs (inputs) of an activity with their split (join) kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub first: String,
+    pub second: String,
+    pub gate: Gate,
+}
+
+/// Dependency graph with splits and joins
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeuristicNet {
+    /// Number of occurrences per activity
+    pub activities: BTreeMap<String, usize>,
+    pub start: BTreeSet<String>,
+    pub end: BTreeSet<String>,
+    /// Accepted arcs with their dependency measure, indexed by source activity
+    pub dependencies: BTreeMap<String, BTreeMap<String, f64>>,
+    /// Pairwise bindings of outputs per activity
+    pub splits: BTreeMap<String, Vec<Binding>>,
+    /// Pairwise bindings of inputs per activity
+    pub joins: BTreeMap<String, Vec<Binding>>,
+}
+
+impl HeuristicNet {
+    /// Dependency measure of the arc `a → b`, if accepted
+    pub fn dependency(&self, a: &str, b: &str) -> Option<f64> {
+        self.dependencies.get(a)?.get(b).copied()
+    }
+
+    /// Gate that binds the outputs `b` and `c` of `a`
+    pub fn split(&self, a: &str, b: &str, c: &str) -> Option<Gate> {
+        Self::gate(self.splits.get(a)?, b, c)
+    }
+
+    /// Gate that binds the inputs `a` and `b` of `c`
+    pub fn join(&self, a: &str, b: &str, c: &str) -> Option<Gate> {
+        Self::gate(self.joins.get(c)?, a, b)
+    }
+
+    fn gate(bindings: &[Binding], a: &str, b: &str) -> Option<Gate> {
+        bindings
+            .iter()
+            .find(|x| (x.first == a && x.second == b) || (x.first == b && x.second == a))
+            .map(|x| x.gate)
+    }
+}
+
+impl ToGraphMl for HeuristicNet {
+    fn to_graphml(&self) -> Result<GraphMl> {
+        let mut graph = GraphMl::new(true)
+            .key("label", KeyScope::Node, KeyType::String)
+            .key("occurrences", KeyScope::Node, KeyType::Int)
+            .key("start", KeyScope::Node, KeyType::Boolean)
+            .key("end", KeyScope::Node, KeyType::Boolean)
+            .key("dependency", KeyScope::Edge, KeyType::Double);
+
+        // activity names are not necessarily valid ids
+        let ids: BTreeMap<_, _> = self
+            .activities
+            .keys()
+            .enumerate()
+            .map(|(i, activity)| (activity.as_str(), format!("n{}", i)))
+            .collect();
+
+        for (activity, occurrences) in self.activities.iter() {
+            graph.node(
+                &ids[activity.as_str()],
+                vec![
+                    ("label", activity.clone()),
+                    ("occurrences", occurrences.to_string()),
+                    ("start", self.start.contains(activity).to_string()),
+                    ("end", self.end.contains(activity).to_string()),
+                ],
+            )?;
+        }
+
+        for (a, targets) in self.dependencies.iter() {
+            for (b, dependency) in targets.iter() {
+                let id = |activity: &str| {
+                    ids.get(activity)
+                        .ok_or_else(|| Error::KeyError(format!("activity {:?} of arc", activity)))
+                };
+                graph.edge(id(a)?, id(b)?, vec![("dependency", dependency)])?;
+            }
+        }
+
+        Ok(graph)
+    }
+}
+
+#[typetag::serde]
+impl Artifact for HeuristicNet {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// All unordered pairs of distinct items
+fn pairs<'a>(items: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+    let mut pairs = Vec::new();
+    for (i, a) in items.iter().enumerate() {
+        for b in items[i + 1..].iter() {
+            pairs.push((*a, *b));
+        }
+    }
+    pairs
+}
+
+/// Mine a heuristic net from the dependency frequencies of all traces of a stream
+///
+/// Directly-follows frequencies are collected by a `DfgGenerator`, activities are thus taken from
+/// an attribute or a classifier alike. The net is released as artifact.
+///
+#[derive(Default)]
+pub struct HeuristicMiner {
+    dfg: DfgGenerator,
+    /// Occurrences of `a b a`, indexed by `a` and `b`
+    loops: BTreeMap<String, BTreeMap<String, f64>>,
+    thresholds: Thresholds,
+}
+
+impl HeuristicMiner {
+    pub fn new(source: ActivitySource) -> Self {
+        HeuristicMiner {
+            dfg: DfgGenerator::new(source),
+            loops: BTreeMap::new(),
+            thresholds: Thresholds::default(),
+        }
+    }
+
+    pub fn thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    fn follows(&self, a: &str, b: &str) -> f64 {
+        self.dfg.dfg().weight(a, b)
+    }
+
+    fn loops(&self, a: &str, b: &str) -> f64 {
+        self.loops
+            .get(a)
+            .and_then(|targets| targets.get(b))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Dependency measure `a ⇒ b`
+    pub fn dependency(&self, a: &str, b: &str) -> f64 {
+        let ab = self.follows(a, b);
+        if a == b {
+            ab / (ab + 1.0)
+        } else {
+            let ba = self.follows(b, a);
+            (ab - ba) / (ab + ba + 1.0)
+        }
+    }
+
+    /// Dependency measure `a ⇒2 b` of the loop of length two
+    pub fn loop_dependency(&self, a: &str, b: &str) -> f64 {
+        let loops = self.loops(a, b) + self.loops(b, a);
+        loops / (loops + 1.0)
+    }
+
+    /// The net mined from the frequencies collected so far
+    pub fn mine(&self) -> HeuristicNet {
+        let t = &self.thresholds;
+        let dfg = self.dfg.dfg();
+        let activities: Vec<&String> = dfg.activities.keys().collect();
+        let positive = |weights: &BTreeMap<String, f64>| -> BTreeSet<String> {
+            weights
+                .iter()
+                .filter(|(_, weight)| **weight > 0.0)
+                .map(|(activity, _)| activity.clone())
+                .collect()
+        };
+
+        let mut net = HeuristicNet {
+            activities: dfg
+                .activities
+                .iter()
+                .map(|(a, count)| (a.clone(), *count as usize))
+                .collect(),
+            start: positive(&dfg.start),
+            end: positive(&dfg.end),
+            ..HeuristicNet::default()
+        };
+
+        // loops of length one
+        let mut self_loops = BTreeSet::new();
+        for a in activities.iter() {
+            let dependency = self.dependency(a, a);
+            if dependency >= t.loop_length_one {
+                self_loops.insert(a.to_string());
+                net.dependencies
+                    .entry(a.to_string())
+                    .or_default()
+                    .insert(a.to_string(), dependency);
+            }
+        }
+
+        // loops of length two, which would otherwise cancel out each other's dependencies
+        for a in activities.iter() {
+            for b in activities.iter() {
+                if a >= b || self_loops.contains(*a) || self_loops.contains(*b) {
+                    continue;
+                }
+                let dependency = self.loop_dependency(a, b);
+                if dependency >= t.loop_length_two {
+                    for (x, y) in [(a, b), (b, a)].iter() {
+                        net.dependencies
+                            .entry(x.to_string())
+                            .or_default()
+                            .insert(y.to_string(), dependency);
+                    }
+                }
+            }
+        }
+
+        // the best dependency of each activity's in- and outputs
+        let mut best_out: BTreeMap<&str, f64> = BTreeMap::new();
+        let mut best_in: BTreeMap<&str, f64> = BTreeMap::new();
+        for a in activities.iter() {
+            for b in activities.iter() {
+                if a == b {
+                    continue;
+                }
+                let dependency = self.dependency(a, b);
+                let out = best_out.entry(a.as_str()).or_insert(f64::MIN);
+                *out = out.max(dependency);
+                let into = best_in.entry(b.as_str()).or_insert(f64::MIN);
+                *into = into.max(dependency);
+            }
+        }
+
+        for a in activities.iter() {
+            for b in activities.iter() {
+                if a == b || self.follows(a, b) < t.positive_observations as f64 {
+                    continue;
+                }
+                let dependency = self.dependency(a, b);
+                let near_best = best_out[a.as_str()] - dependency <= t.relative_to_best
+                    || best_in[b.as_str()] - dependency <= t.relative_to_best;
+                if dependency >= t.dependency && near_best {
+                    net.dependencies
+                        .entry(a.to_string())
+                        .or_default()
+                        .insert(b.to_string(), dependency);
+                }
+            }
+        }
+
+        // pairwise bindings of outputs and inputs
+        let mut inputs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (a, targets) in net.dependencies.iter() {
+            for b in targets.keys().filter(|b| *b != a) {
+                inputs.entry(b.as_str()).or_default().push(a.as_str());
+            }
+        }
+        let bindings = |pairs: Vec<(&str, &str)>, measure: &dyn Fn(&str, &str) -> f64| {
+            pairs
+                .into_iter()
+                .map(|(b, c)| Binding {
+                    first: b.to_string(),
+                    second: c.to_string(),
+                    gate: if measure(b, c) >= t.and {
+                        Gate::And
+                    } else {
+                        Gate::Xor
+                    },
+                })
+                .collect::<Vec<_>>()
+        };
+        let parallel = |b: &str, c: &str| self.follows(b, c) + self.follows(c, b);
+
+        let mut splits = BTreeMap::new();
+        for (a, targets) in net.dependencies.iter() {
+            let outputs: Vec<&str> = targets
+                .keys()
+                .map(|b| b.as_str())
+                .filter(|b| b != a)
+                .collect();
+            let measure =
+                |b: &str, c: &str| parallel(b, c) / (self.follows(a, b) + self.follows(a, c) + 1.0);
+            let split = bindings(pairs(&outputs), &measure);
+            if !split.is_empty() {
+                splits.insert(a.clone(), split);
+            }
+        }
+
+        let mut joins = BTreeMap::new();
+        for (c, sources) in inputs {
+            let measure =
+                |a: &str, b: &str| parallel(a, b) / (self.follows(a, c) + self.follows(b, c) + 1.0);
+            let join = bindings(pairs(&sources), &measure);
+            if !join.is_empty() {
+                joins.insert(c.to_string(), join);
+            }
+        }
+
+        net.splits = splits;
+        net.joins = joins;
+        net
+    }
+}
+
+impl Handler for HeuristicMiner {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.dfg.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let activities: Vec<String> = trace
+            .events
+            .iter()
+            .filter_map(|event| self.dfg.activity(event))
+            .collect();
+        for window in activities.windows(3) {
+            if window[0] == window[2] && window[0] != window[1] {
+                *self
+                    .loops
+                    .entry(window[0].clone())
+                    .or_default()
+                    .entry(window[1].clone())
+                    .or_default() += 1.0;
+            }
+        }
+
+        self.dfg.on_trace(trace)
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        self.dfg.on_event(event, in_trace)
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        self.dfg.on_unfolded_event(event)
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.mine().into()])
+    }
+}
+
+impl PluginProvider for HeuristicMiner {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        let defaults = Thresholds::default();
+
+        vec![Entry::new(
+            "HeuristicMiner",
+            "Mine a heuristic net from the dependency frequencies of all traces",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be observed")
+                    .default_attr("activity", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    })
+                    .default_attr("dependency", "Minimal dependency measure", |k| {
+                        (k, defaults.dependency).into()
+                    })
+                    .default_attr(
+                        "relative_to_best",
+                        "Maximal distance to the best dependency of an activity",
+                        |k| (k, defaults.relative_to_best).into(),
+                    )
+                    .default_attr(
+                        "positive_observations",
+                        "Minimal number of directly-follows observations",
+                        |k| (k, defaults.positive_observations as i64).into(),
+                    )
+                    .default_attr(
+                        "loop_length_one",
+                        "Minimal dependency measure of loops of length one",
+                        |k| (k, defaults.loop_length_one).into(),
+                    )
+                    .default_attr(
+                        "loop_length_two",
+                        "Minimal dependency measure of loops of length two",
+                        |k| (k, defaults.loop_length_two).into(),
+                    )
+                    .default_attr("and", "Minimal measure of AND splits and joins", |k| {
+                        (k, defaults.and).into()
+                    })
+                    .optional_attr(
+                        "classifier",
+                        "Classifier that takes precedence over the activity attribute",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut measure = |key: &str| -> Result<f64> {
+                        let value = *parameters.acquire_attribute(key)?.value.try_float()?;
+                        if !(0.0..=1.0).contains(&value) {
+                            return Err(Error::AttributeError(format!(
+                                "{} is expected to be within [0, 1], got {}",
+                                key, value
+                            )));
+                        }
+                        Ok(value)
+                    };
+                    let mut thresholds = Thresholds {
+                        dependency: measure("dependency")?,
+                        relative_to_best: measure("relative_to_best")?,
+                        loop_length_one: measure("loop_length_one")?,
+                        loop_length_two: measure("loop_length_two")?,
+                        and: measure("and")?,
+                        ..Thresholds::default()
+                    };
+                    thresholds.positive_observations = (*parameters
+                        .acquire_attribute("positive_observations")?
+                        .value
+                        .try_int()?)
+                    .max(0) as usize;
+
+                    let activity = parameters
+                        .acquire_attribute("activity")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let source = match parameters.acquire_attribute("classifier") {
+                        Ok(classifier) => {
+                            ActivitySource::Classifier(classifier.value.try_string()?.to_string())
+                        }
+                        Err(_) => ActivitySource::Attribute(activity),
+                    };
+
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        HeuristicMiner::new(source).thresholds(thresholds),
+                    ))
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    #[test]
+    fn test_heuristic_miner() {
+        let thresholds = Thresholds {
+            dependency: 0.5,
+            ..Thresholds::default()
+        };
+        let mut observer = HeuristicMiner::default()
+            .thresholds(thresholds)
+            .into_observer(load_example(&["book", "L1.xes"]));
+        let artifacts = consume(&mut observer).unwrap();
+        let net = AnyArtifact::find::<HeuristicNet>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(net.activities["a"], 6);
+        assert_eq!(net.dependency("a", "b"), Some(0.75));
+        assert_eq!(net.dependency("e", "d"), Some(0.5));
+        assert_eq!(net.dependency("b", "c"), None);
+        let arcs: usize = net.dependencies.values().map(|targets| targets.len()).sum();
+        assert_eq!(arcs, 6);
+
+        assert_eq!(net.split("a", "b", "c"), Some(Gate::And));
+        assert_eq!(net.split("a", "c", "e"), Some(Gate::Xor));
+        assert_eq!(net.join("c", "b", "d"), Some(Gate::And));
+        assert_eq!(net.join("b", "e", "d"), Some(Gate::Xor));
+
+        // defaults are too strict for the few cases of L1
+        let mut observer =
+            HeuristicMiner::default().into_observer(load_example(&["book", "L1.xes"]));
+        consume(&mut observer).unwrap();
+        assert!(observer.release().unwrap().mine().dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_graphml() {
+        let mut net = HeuristicNet::default();
+        net.activities.insert("a".into(), 2);
+        net.activities.insert("b".into(), 1);
+        net.start.insert("a".into());
+        net.dependencies
+            .entry("a".into())
+            .or_default()
+            .insert("b".into(), 0.5);
+
+        let xml = net.to_graphml().unwrap().to_xml().unwrap();
+        assert!(xml.contains(r#"<data key="node_occurrences">2</data>"#));
+        assert_eq!(
+            xml.matches(r#"<data key="node_start">true</data>"#).count(),
+            1
+        );
+        assert!(xml.contains(r#"<edge source="n0" target="n1">"#));
+        assert!(xml.contains(r#"<data key="edge_dependency">0.5</data>"#));
+
+        net.dependencies
+            .entry("b".into())
+            .or_default()
+            .insert("c".into(), 0.9);
+        assert!(net.to_graphml().is_err());
+    }
+}
@@ -0,0 +1,13 @@
+//! Process models
+//!
+//! Models are usually the result of process discovery and serve as input for conformance checking.
+//! All models implement `Artifact` such that they can be passed between pipes of a flow graph.
+//!
+
+pub mod alpha;
+pub mod footprint;
+pub mod heuristic;
+pub mod petri;
+pub mod process_tree;
+pub mod replay;
+pub mod soundness;
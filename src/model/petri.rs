@@ -0,0 +1,730 @@
+//! Place/transition nets
+//!
+//! A Petri net consists of places that hold tokens and transitions that consume tokens from their
+//! input places and produce tokens in their output places. Transitions may carry an activity label
+//! or be silent (τ).
+//!
+//! Nets are exchanged with other tools, e.g. ProM or PM4Py, in the Petri Net Markup Language
+//! (PNML). The `PnmlReader` reads the first net of a document, flattening its pages, along with
+//! its initial and final marking. The `PnmlWriter` writes a net, by default marked as a workflow
+//! net with one token in each source place initially and in each sink place finally. Silent
+//! transitions are marked as invisible the way ProM does, and labelled transitions are named after
+//! their label.
+//!
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+
+use quick_xml::events::{
+    BytesDecl as QxBytesDecl, BytesEnd as QxBytesEnd, BytesStart as QxBytesStart,
+    BytesText as QxBytesText, Event as QxEvent,
+};
+use quick_xml::{Reader as QxReader, Writer as QxWriter};
+use serde::{Deserialize, Serialize};
+
+use crate::stream::graphml::{GraphMl, KeyScope, KeyType, ToGraphMl};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{Artifact, AttributeType, Sink};
+use crate::{Error, Result};
+
+const PNML_NET_TYPE: &str = "http://www.pnml.org/version-2009/grammar/pnmlcoremodel";
+const INVISIBLE: &str = "$invisible$";
+
+/// A (possibly silent) transition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    pub name: String,
+    /// Activity label, `None` for silent transitions
+    pub label: Option<String>,
+    /// Indices of input places
+    pub inputs: Vec<usize>,
+    /// Indices of output places
+    pub outputs: Vec<usize>,
+}
+
+impl Transition {
+    pub fn is_silent(&self) -> bool {
+        self.label.is_none()
+    }
+}
+
+/// Number of tokens per place, indexed like the places of the net
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Marking(pub Vec<u32>);
+
+impl Marking {
+    /// Whether each place holds at least as many tokens as in `other`
+    pub fn covers(&self, other: &Marking) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a >= b)
+    }
+
+    /// Total number of tokens
+    pub fn tokens(&self) -> u32 {
+        self.0.iter().sum()
+    }
+}
+
+/// Place/transition net with unit arc weights
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PetriNet {
+    pub places: Vec<String>,
+    pub transitions: Vec<Transition>,
+}
+
+impl PetriNet {
+    /// Add a place and return its index
+    pub fn add_place<N: Into<String>>(&mut self, name: N) -> usize {
+        self.places.push(name.into());
+        self.places.len() - 1
+    }
+
+    /// Add a transition and return its index
+    pub fn add_transition<N: Into<String>>(&mut self, name: N, label: Option<&str>) -> usize {
+        self.transitions.push(Transition {
+            name: name.into(),
+            label: label.map(|l| l.to_string()),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        });
+        self.transitions.len() - 1
+    }
+
+    /// Connect a place to the input of a transition
+    pub fn add_input_arc(&mut self, place: usize, transition: usize) -> Result<()> {
+        self.check_place(place)?;
+        self.transition_mut(transition)?.inputs.push(place);
+        Ok(())
+    }
+
+    /// Connect the output of a transition to a place
+    pub fn add_output_arc(&mut self, transition: usize, place: usize) -> Result<()> {
+        self.check_place(place)?;
+        self.transition_mut(transition)?.outputs.push(place);
+        Ok(())
+    }
+
+    fn check_place(&self, place: usize) -> Result<()> {
+        if place < self.places.len() {
+            Ok(())
+        } else {
+            Err(Error::ModelError(format!("there's no place {}", place)))
+        }
+    }
+
+    fn transition_mut(&mut self, transition: usize) -> Result<&mut Transition> {
+        self.transitions
+            .get_mut(transition)
+            .ok_or_else(|| Error::ModelError(format!("there's no transition {}", transition)))
+    }
+
+    /// Index of the place with the given name
+    pub fn place(&self, name: &str) -> Option<usize> {
+        self.places.iter().position(|p| p == name)
+    }
+
+    /// Marking with tokens in the given places and none elsewhere
+    pub fn marking(&self, tokens: &[(usize, u32)]) -> Marking {
+        let mut marking = vec![0; self.places.len()];
+        for (place, n) in tokens.iter() {
+            marking[*place] += n;
+        }
+        Marking(marking)
+    }
+
+    /// Whether the transition is enabled in the given marking
+    pub fn is_enabled(&self, marking: &Marking, transition: usize) -> bool {
+        let mut required = vec![0; self.places.len()];
+        for place in self.transitions[transition].inputs.iter() {
+            required[*place] += 1;
+        }
+        marking.covers(&Marking(required))
+    }
+
+    /// Fire an enabled transition
+    pub fn fire(&self, marking: &Marking, transition: usize) -> Option<Marking> {
+        if !self.is_enabled(marking, transition) {
+            return None;
+        }
+
+        let mut next = marking.clone();
+        for place in self.transitions[transition].inputs.iter() {
+            next.0[*place] -= 1;
+        }
+        for place in self.transitions[transition].outputs.iter() {
+            next.0[*place] += 1;
+        }
+        Some(next)
+    }
+
+    /// Places without incoming arcs
+    pub fn source_places(&self) -> Vec<usize> {
+        (0..self.places.len())
+            .filter(|p| !self.transitions.iter().any(|t| t.outputs.contains(p)))
+            .collect()
+    }
+
+    /// Places without outgoing arcs
+    pub fn sink_places(&self) -> Vec<usize> {
+        (0..self.places.len())
+            .filter(|p| !self.transitions.iter().any(|t| t.inputs.contains(p)))
+            .collect()
+    }
+}
+
+impl ToGraphMl for PetriNet {
+    fn to_graphml(&self) -> Result<GraphMl> {
+        let mut graph = GraphMl::new(true)
+            .key("kind", KeyScope::Node, KeyType::String)
+            .key("name", KeyScope::Node, KeyType::String)
+            .key("label", KeyScope::Node, KeyType::String)
+            .key("weight", KeyScope::Edge, KeyType::Int);
+
+        for (i, place) in self.places.iter().enumerate() {
+            graph.node(
+                &format!("p{}", i),
+                vec![("kind", "place"), ("name", place.as_str())],
+            )?;
+        }
+
+        for (i, transition) in self.transitions.iter().enumerate() {
+            let mut data = vec![("kind", "transition"), ("name", transition.name.as_str())];
+            if let Some(label) = transition.label.as_ref() {
+                data.push(("label", label.as_str()));
+            }
+            graph.node(&format!("t{}", i), data)?;
+        }
+
+        // arcs are listed once per unit of weight
+        for (i, transition) in self.transitions.iter().enumerate() {
+            let mut arcs: Vec<(String, String, usize)> = Vec::new();
+            let endpoints = transition
+                .inputs
+                .iter()
+                .map(|p| (format!("p{}", p), format!("t{}", i)))
+                .chain(
+                    transition
+                        .outputs
+                        .iter()
+                        .map(|p| (format!("t{}", i), format!("p{}", p))),
+                );
+            for (source, target) in endpoints {
+                match arcs
+                    .iter_mut()
+                    .find(|(s, t, _)| s == &source && t == &target)
+                {
+                    Some((_, _, weight)) => *weight += 1,
+                    None => arcs.push((source, target, 1)),
+                }
+            }
+
+            for (source, target, weight) in arcs {
+                graph.edge(&source, &target, vec![("weight", weight)])?;
+            }
+        }
+
+        Ok(graph)
+    }
+}
+
+#[typetag::serde]
+impl Artifact for PetriNet {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// node of the net that is currently read
+enum PnmlNode {
+    Place(usize),
+    Transition(usize),
+    Arc(usize),
+    FinalPlace(usize),
+    None,
+}
+
+/// Read a Petri net from PNML
+pub struct PnmlReader<R: io::BufRead> {
+    reader: QxReader<R>,
+    path: Vec<Vec<u8>>,
+}
+
+impl<R: io::BufRead> PnmlReader<R> {
+    pub fn new(reader: R) -> Self {
+        let mut reader = QxReader::from_reader(reader);
+        reader.trim_text(true);
+
+        PnmlReader {
+            reader,
+            path: Vec::new(),
+        }
+    }
+
+    fn within(&self, path: &[&[u8]]) -> bool {
+        self.path.len() >= path.len()
+            && self.path[self.path.len() - path.len()..]
+                .iter()
+                .zip(path.iter())
+                .all(|(a, b)| a == b)
+    }
+
+    fn parse_count(text: &str) -> Result<u32> {
+        text.trim()
+            .parse::<u32>()
+            .map_err(|_| Error::ModelError(format!("invalid token count {:?}", text)))
+    }
+
+    /// Read the net, its initial marking and, if given, its final marking
+    pub fn read(mut self) -> Result<(PetriNet, Marking, Option<Marking>)> {
+        // (id, name, tokens) of places, (id, name, silent) of transitions, (source, target, weight)
+        let mut places: Vec<(String, Option<String>, u32)> = Vec::new();
+        let mut transitions: Vec<(String, Option<String>, bool)> = Vec::new();
+        let mut arcs: Vec<(String, String, u32)> = Vec::new();
+        let mut final_marking: Option<Vec<(String, u32)>> = None;
+
+        let mut node = PnmlNode::None;
+        let mut nets = 0;
+        let mut buffer = Vec::new();
+
+        loop {
+            let (element, open) = match self.reader.read_event(&mut buffer)? {
+                QxEvent::Start(element) => (element, true),
+                QxEvent::Empty(element) => (element, false),
+                QxEvent::Text(text) => {
+                    let text = String::from_utf8(text.unescaped()?.to_vec())?;
+                    match node {
+                        PnmlNode::Place(i) if self.within(&[b"place", b"name", b"text"]) => {
+                            places[i].1 = Some(text)
+                        }
+                        PnmlNode::Place(i) if self.within(&[b"initialMarking", b"text"]) => {
+                            places[i].2 = Self::parse_count(&text)?
+                        }
+                        PnmlNode::Transition(i)
+                            if self.within(&[b"transition", b"name", b"text"]) =>
+                        {
+                            transitions[i].1 = Some(text)
+                        }
+                        PnmlNode::Arc(i) if self.within(&[b"inscription", b"text"]) => {
+                            arcs[i].2 = Self::parse_count(&text)?
+                        }
+                        PnmlNode::FinalPlace(i) if self.within(&[b"place", b"text"]) => {
+                            if let Some(marking) = final_marking.as_mut() {
+                                marking[i].1 = Self::parse_count(&text)?;
+                            }
+                        }
+                        _ => (),
+                    }
+                    buffer.clear();
+                    continue;
+                }
+                QxEvent::End(element) => {
+                    if self.path.pop().as_deref() == Some(b"net") && nets == 1 {
+                        break;
+                    }
+                    if matches!(element.name(), b"place" | b"transition" | b"arc") {
+                        node = PnmlNode::None;
+                    }
+                    buffer.clear();
+                    continue;
+                }
+                QxEvent::Eof => break,
+                _ => {
+                    buffer.clear();
+                    continue;
+                }
+            };
+
+            let mut attributes: HashMap<Vec<u8>, String> = HashMap::new();
+            for attribute in element.attributes() {
+                let attribute = attribute?;
+                attributes.insert(
+                    attribute.key.to_vec(),
+                    String::from_utf8(attribute.unescaped_value()?.to_vec())?,
+                );
+            }
+            let attribute = |key: &[u8]| {
+                attributes.get(key).cloned().ok_or_else(|| {
+                    Error::ModelError(format!(
+                        "missing {:?} attribute in {:?}",
+                        String::from_utf8_lossy(key),
+                        String::from_utf8_lossy(element.name())
+                    ))
+                })
+            };
+
+            match element.name() {
+                b"net" => nets += 1,
+                b"place" if self.within(&[b"finalmarkings", b"marking"]) => {
+                    if let Some(marking) = final_marking.as_mut() {
+                        marking.push((attribute(b"idref")?, 1));
+                        node = PnmlNode::FinalPlace(marking.len() - 1);
+                    }
+                }
+                b"place" => {
+                    places.push((attribute(b"id")?, None, 0));
+                    node = PnmlNode::Place(places.len() - 1);
+                }
+                b"transition" => {
+                    transitions.push((attribute(b"id")?, None, false));
+                    node = PnmlNode::Transition(transitions.len() - 1);
+                }
+                b"arc" => {
+                    arcs.push((attribute(b"source")?, attribute(b"target")?, 1));
+                    node = PnmlNode::Arc(arcs.len() - 1);
+                }
+                b"toolspecific" => {
+                    if let PnmlNode::Transition(i) = node {
+                        if attributes.get(b"activity".as_ref()).map(|a| a.as_str())
+                            == Some(INVISIBLE)
+                        {
+                            transitions[i].2 = true;
+                        }
+                    }
+                }
+                b"finalmarkings" => final_marking = Some(Vec::new()),
+                _ => (),
+            }
+
+            if open {
+                self.path.push(element.name().to_vec());
+            }
+            buffer.clear();
+        }
+
+        if nets == 0 {
+            return Err(Error::ModelError("no net in PNML document".into()));
+        }
+
+        let mut net = PetriNet::default();
+        let mut place_ids = HashMap::new();
+        for (id, name, _) in places.iter() {
+            place_ids.insert(
+                id.as_str(),
+                net.add_place(name.as_ref().unwrap_or(id).as_str()),
+            );
+        }
+        let mut transition_ids = HashMap::new();
+        for (id, name, silent) in transitions.iter() {
+            let name = name.as_ref().unwrap_or(id);
+            let label = if *silent { None } else { Some(name.as_str()) };
+            transition_ids.insert(id.as_str(), net.add_transition(name.as_str(), label));
+        }
+
+        for (source, target, weight) in arcs.iter() {
+            for _ in 0..*weight {
+                match (
+                    place_ids.get(source.as_str()),
+                    transition_ids.get(source.as_str()),
+                    place_ids.get(target.as_str()),
+                    transition_ids.get(target.as_str()),
+                ) {
+                    (Some(place), _, _, Some(transition)) => {
+                        net.add_input_arc(*place, *transition)?
+                    }
+                    (_, Some(transition), Some(place), _) => {
+                        net.add_output_arc(*transition, *place)?
+                    }
+                    _ => {
+                        return Err(Error::ModelError(format!(
+                            "invalid arc from {:?} to {:?}",
+                            source, target
+                        )))
+                    }
+                }
+            }
+        }
+
+        let initial = Marking(places.iter().map(|(_, _, tokens)| *tokens).collect());
+        let final_marking = match final_marking {
+            Some(tokens) => {
+                let tokens = tokens
+                    .iter()
+                    .map(|(id, n)| match place_ids.get(id.as_str()) {
+                        Some(place) => Ok((*place, *n)),
+                        None => Err(Error::ModelError(format!("there's no place {:?}", id))),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Some(net.marking(&tokens))
+            }
+            None => None,
+        };
+
+        Ok((net, initial, final_marking))
+    }
+}
+
+/// Write a Petri net as PNML once the stream is closed
+pub struct PnmlWriter<W: io::Write> {
+    writer: W,
+    net: PetriNet,
+    initial: Marking,
+    final_marking: Marking,
+}
+
+impl<W: io::Write> PnmlWriter<W> {
+    pub fn new(writer: W, net: PetriNet) -> Self {
+        let initial: Vec<_> = net.source_places().into_iter().map(|p| (p, 1)).collect();
+        let final_marking: Vec<_> = net.sink_places().into_iter().map(|p| (p, 1)).collect();
+
+        PnmlWriter {
+            writer,
+            initial: net.marking(&initial),
+            final_marking: net.marking(&final_marking),
+            net,
+        }
+    }
+
+    /// Set the initial marking instead of one token in each source place
+    pub fn initial_marking(mut self, marking: Marking) -> Self {
+        self.initial = marking;
+        self
+    }
+
+    /// Set the final marking instead of one token in each sink place
+    pub fn final_marking(mut self, marking: Marking) -> Self {
+        self.final_marking = marking;
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_text<T: io::Write>(
+        writer: &mut QxWriter<T>,
+        element: &[u8],
+        text: &str,
+    ) -> Result<()> {
+        writer.write_event(QxEvent::Start(QxBytesStart::borrowed_name(element)))?;
+        writer.write_event(QxEvent::Start(QxBytesStart::borrowed_name(b"text")))?;
+        writer.write_event(QxEvent::Text(QxBytesText::from_plain_str(text)))?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"text")))?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(element)))?;
+        Ok(())
+    }
+
+    /// Serialize the net
+    pub fn write(&mut self) -> Result<()> {
+        let mut writer = QxWriter::new_with_indent(&mut self.writer, b' ', 2);
+
+        let declaration = QxBytesDecl::new(b"1.0", Some(b"UTF-8"), None);
+        writer.write_event(QxEvent::Decl(declaration))?;
+        writer.write_event(QxEvent::Start(QxBytesStart::borrowed_name(b"pnml")))?;
+
+        let mut event = QxBytesStart::owned_name(b"net".to_vec());
+        event.push_attribute(("id", "net"));
+        event.push_attribute(("type", PNML_NET_TYPE));
+        writer.write_event(QxEvent::Start(event))?;
+        let mut event = QxBytesStart::owned_name(b"page".to_vec());
+        event.push_attribute(("id", "page"));
+        writer.write_event(QxEvent::Start(event))?;
+
+        for (i, name) in self.net.places.iter().enumerate() {
+            let mut event = QxBytesStart::owned_name(b"place".to_vec());
+            event.push_attribute(("id", format!("p{}", i).as_str()));
+            writer.write_event(QxEvent::Start(event))?;
+            Self::write_text(&mut writer, b"name", name)?;
+            match self.initial.0.get(i) {
+                Some(tokens) if *tokens > 0 => {
+                    Self::write_text(&mut writer, b"initialMarking", &tokens.to_string())?
+                }
+                _ => (),
+            }
+            writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"place")))?;
+        }
+
+        for (i, transition) in self.net.transitions.iter().enumerate() {
+            let mut event = QxBytesStart::owned_name(b"transition".to_vec());
+            event.push_attribute(("id", format!("t{}", i).as_str()));
+            writer.write_event(QxEvent::Start(event))?;
+            let name = transition.label.as_ref().unwrap_or(&transition.name);
+            Self::write_text(&mut writer, b"name", name)?;
+            if transition.is_silent() {
+                let mut event = QxBytesStart::owned_name(b"toolspecific".to_vec());
+                event.push_attribute(("tool", "ProM"));
+                event.push_attribute(("version", "6.4"));
+                event.push_attribute(("activity", INVISIBLE));
+                writer.write_event(QxEvent::Empty(event))?;
+            }
+            writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"transition")))?;
+        }
+
+        // parallel arcs are merged into a weighted one
+        let mut arcs: Vec<((String, String), u32)> = Vec::new();
+        for (i, transition) in self.net.transitions.iter().enumerate() {
+            let inputs = transition
+                .inputs
+                .iter()
+                .map(|p| (format!("p{}", p), format!("t{}", i)));
+            let outputs = transition
+                .outputs
+                .iter()
+                .map(|p| (format!("t{}", i), format!("p{}", p)));
+            for arc in inputs.chain(outputs) {
+                match arcs.iter_mut().find(|(a, _)| *a == arc) {
+                    Some((_, weight)) => *weight += 1,
+                    None => arcs.push((arc, 1)),
+                }
+            }
+        }
+        for (i, ((source, target), weight)) in arcs.iter().enumerate() {
+            let mut event = QxBytesStart::owned_name(b"arc".to_vec());
+            event.push_attribute(("id", format!("a{}", i).as_str()));
+            event.push_attribute(("source", source.as_str()));
+            event.push_attribute(("target", target.as_str()));
+            if *weight > 1 {
+                writer.write_event(QxEvent::Start(event))?;
+                Self::write_text(&mut writer, b"inscription", &weight.to_string())?;
+                writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"arc")))?;
+            } else {
+                writer.write_event(QxEvent::Empty(event))?;
+            }
+        }
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"page")))?;
+
+        writer.write_event(QxEvent::Start(QxBytesStart::borrowed_name(
+            b"finalmarkings",
+        )))?;
+        writer.write_event(QxEvent::Start(QxBytesStart::borrowed_name(b"marking")))?;
+        for (i, tokens) in self.final_marking.0.iter().enumerate() {
+            if *tokens > 0 {
+                let mut event = QxBytesStart::owned_name(b"place".to_vec());
+                event.push_attribute(("idref", format!("p{}", i).as_str()));
+                writer.write_event(QxEvent::Start(event))?;
+                Self::write_text(&mut writer, b"text", &tokens.to_string())?;
+                writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"place")))?;
+            }
+        }
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"marking")))?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"finalmarkings")))?;
+
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"net")))?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"pnml")))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<W: io::Write + Send> Sink for PnmlWriter<W> {
+    fn on_close(&mut self) -> Result<()> {
+        self.write()
+    }
+}
+
+impl PluginProvider for PnmlWriter<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "PnmlWriter",
+            "Write a Petri net artifact as PNML, e.g. for ProM or PM4Py",
+            Factory::new(
+                Declaration::default()
+                    .typed_artifact::<PetriNet, _, _>("net", "The Petri net to be written")
+                    .typed_attr("path", "Location of the PNML file", AttributeType::String),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let artifact = parameters.acquire_artifact("net")?;
+                    let net = match artifact.downcast_ref::<PetriNet>() {
+                        Some(net) => net.clone(),
+                        None => {
+                            return Err(Error::ArtifactError(format!(
+                                "expected Petri net artifact, got {:?}",
+                                artifact
+                            )))
+                        }
+                    };
+                    let path = parameters.acquire_attribute("path")?;
+                    let file = File::create(path.value.try_string()?)?;
+                    Ok(PnmlWriter::new(file, net).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fire() {
+        let mut net = PetriNet::default();
+        let (p0, p1) = (net.add_place("p0"), net.add_place("p1"));
+        let t = net.add_transition("t", Some("a"));
+        net.add_input_arc(p0, t).unwrap();
+        net.add_input_arc(p0, t).unwrap();
+        net.add_output_arc(t, p1).unwrap();
+        assert!(net.add_output_arc(t, 42).is_err());
+
+        assert!(!net.is_enabled(&net.marking(&[(p0, 1)]), t));
+        assert_eq!(
+            net.fire(&net.marking(&[(p0, 3)]), t),
+            Some(net.marking(&[(p0, 1), (p1, 1)]))
+        );
+        assert_eq!(net.source_places(), vec![p0]);
+        assert_eq!(net.sink_places(), vec![p1]);
+    }
+
+    #[test]
+    fn test_pnml() {
+        let mut net = PetriNet::default();
+        let (i, p, o) = (
+            net.add_place("i"),
+            net.add_place("a & b"),
+            net.add_place("o"),
+        );
+        let a = net.add_transition("a", Some("a"));
+        let tau = net.add_transition("tau", None);
+        net.add_input_arc(i, a).unwrap();
+        net.add_output_arc(a, p).unwrap();
+        net.add_output_arc(a, p).unwrap();
+        net.add_input_arc(p, tau).unwrap();
+        net.add_input_arc(p, tau).unwrap();
+        net.add_output_arc(tau, o).unwrap();
+
+        let xml = net.to_graphml().unwrap().to_xml().unwrap();
+        assert_eq!(xml.matches("<node ").count(), 5);
+        assert!(xml.contains(r#"<edge source="t0" target="p1">"#));
+        assert!(xml.contains(r#"<data key="edge_weight">2</data>"#));
+        assert_eq!(xml.matches(r#"<data key="node_label">"#).count(), 1);
+
+        let mut writer = PnmlWriter::new(Vec::new(), net.clone());
+        writer.write().unwrap();
+        let pnml = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(pnml.contains(r#"activity="$invisible$""#));
+        assert!(pnml.contains("<text>a &amp; b</text>"));
+
+        let (read, initial, final_marking) = PnmlReader::new(pnml.as_bytes()).read().unwrap();
+        assert_eq!(read, net);
+        assert_eq!(initial, net.marking(&[(i, 1)]));
+        assert_eq!(final_marking, Some(net.marking(&[(o, 1)])));
+
+        // documents of other tools may use ids and nested pages
+        let pnml = r#"<?xml version="1.0"?>
+            <pnml><net id="n" type="http://www.pnml.org/version-2009/grammar/pnmlcoremodel">
+              <name><text>net</text></name>
+              <page id="p"><page id="nested">
+                <place id="start"><initialMarking><text>2</text></initialMarking></place>
+                <transition id="t1"><name><text>register</text></name></transition>
+                <arc id="x" source="start" target="t1"/>
+              </page></page>
+            </net></pnml>"#;
+        let (read, initial, final_marking) = PnmlReader::new(pnml.as_bytes()).read().unwrap();
+        assert_eq!(read.places, vec!["start"]);
+        assert_eq!(read.transitions[0].label.as_deref(), Some("register"));
+        assert_eq!(read.transitions[0].inputs, vec![0]);
+        assert_eq!(initial, Marking(vec![2]));
+        assert!(final_marking.is_none());
+
+        let pnml = r#"<pnml><net id="n"><arc id="x" source="a" target="b"/></net></pnml>"#;
+        assert!(PnmlReader::new(pnml.as_bytes()).read().is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! Process trees
+//!
+//! A process tree is a block-structured process model: its leaves are activities or silent steps
+//! (τ), its inner nodes are operators that combine the behaviour of their children. Process trees
+//! are sound by construction and are the output of the inductive miner family.
+//!
+//! The operators are
+//! - sequence, i.e. the children are executed in order,
+//! - exclusive choice, i.e. exactly one child is executed,
+//! - parallel, i.e. all children are executed in any interleaving, and
+//! - loop, i.e. the first child is executed, then, any number of times, one of the others followed
+//!   by the first one again.
+//!
+//! Trees are converted into workflow nets with `to_petri_net` to feed token replay or alignments.
+//!
+
+use std::any::Any;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::petri::PetriNet;
+use crate::stream::graphml::{GraphMl, KeyScope, KeyType, ToGraphMl};
+use crate::stream::Artifact;
+use crate::{Error, Result};
+
+/// Operator of an inner node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+    Sequence,
+    Xor,
+    Parallel,
+    Loop,
+}
+
+impl Operator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Sequence => "->",
+            Operator::Xor => "X",
+            Operator::Parallel => "+",
+            Operator::Loop => "*",
+        }
+    }
+}
+
+/// Block-structured process model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProcessTree {
+    Activity(String),
+    Silent,
+    Node(Operator, Vec<ProcessTree>),
+}
+
+impl ProcessTree {
+    pub fn activity<A: Into<String>>(activity: A) -> Self {
+        ProcessTree::Activity(activity.into())
+    }
+
+    pub fn sequence(children: Vec<ProcessTree>) -> Self {
+        ProcessTree::Node(Operator::Sequence, children)
+    }
+
+    pub fn xor(children: Vec<ProcessTree>) -> Self {
+        ProcessTree::Node(Operator::Xor, children)
+    }
+
+    pub fn parallel(children: Vec<ProcessTree>) -> Self {
+        ProcessTree::Node(Operator::Parallel, children)
+    }
+
+    /// Loop of a body, i.e. the first child, and any number of redo children
+    pub fn looped(children: Vec<ProcessTree>) -> Self {
+        ProcessTree::Node(Operator::Loop, children)
+    }
+
+    /// Activities of all leaves, in depth-first order
+    pub fn activities(&self) -> Vec<&str> {
+        match self {
+            ProcessTree::Activity(activity) => vec![activity.as_str()],
+            ProcessTree::Silent => vec![],
+            ProcessTree::Node(_, children) => {
+                children.iter().flat_map(|c| c.activities()).collect()
+            }
+        }
+    }
+
+    /// Convert the tree into a workflow net with a single source and sink place
+    ///
+    /// Fails for operators without children and loops without a redo part.
+    ///
+    pub fn to_petri_net(&self) -> Result<PetriNet> {
+        let mut net = PetriNet::default();
+        let source = net.add_place("source");
+        let sink = net.add_place("sink");
+        self.translate(&mut net, source, sink)?;
+        Ok(net)
+    }
+
+    fn tau(net: &mut PetriNet) -> usize {
+        net.add_transition(format!("tau{}", net.transitions.len()), None)
+    }
+
+    fn silent(net: &mut PetriNet, from: usize, to: usize) -> Result<()> {
+        let transition = Self::tau(net);
+        net.add_input_arc(from, transition)?;
+        net.add_output_arc(transition, to)
+    }
+
+    fn place(net: &mut PetriNet) -> usize {
+        net.add_place(format!("p{}", net.places.len()))
+    }
+
+    // behaviour of the tree between the places `from` and `to`
+    fn translate(&self, net: &mut PetriNet, from: usize, to: usize) -> Result<()> {
+        match self {
+            ProcessTree::Activity(activity) => {
+                let transition = net.add_transition(activity.as_str(), Some(activity));
+                net.add_input_arc(from, transition)?;
+                net.add_output_arc(transition, to)?;
+            }
+            ProcessTree::Silent => {
+                Self::silent(net, from, to)?;
+            }
+            ProcessTree::Node(operator, children) => {
+                let minimum = if *operator == Operator::Loop { 2 } else { 1 };
+                if children.len() < minimum {
+                    return Err(Error::ModelError(format!(
+                        "{:?} requires at least {} children, got {}",
+                        operator,
+                        minimum,
+                        children.len()
+                    )));
+                }
+
+                match operator {
+                    Operator::Sequence => {
+                        let mut current = from;
+                        for (i, child) in children.iter().enumerate() {
+                            let next = if i + 1 == children.len() {
+                                to
+                            } else {
+                                Self::place(net)
+                            };
+                            child.translate(net, current, next)?;
+                            current = next;
+                        }
+                    }
+                    Operator::Xor => {
+                        for child in children.iter() {
+                            child.translate(net, from, to)?;
+                        }
+                    }
+                    Operator::Parallel => {
+                        let (split, join) = (Self::tau(net), Self::tau(net));
+                        net.add_input_arc(from, split)?;
+                        net.add_output_arc(join, to)?;
+                        for child in children.iter() {
+                            let (start, end) = (Self::place(net), Self::place(net));
+                            net.add_output_arc(split, start)?;
+                            net.add_input_arc(end, join)?;
+                            child.translate(net, start, end)?;
+                        }
+                    }
+                    Operator::Loop => {
+                        // silent entry and exit keep the body from interfering with `from` and `to`
+                        let (start, end) = (Self::place(net), Self::place(net));
+                        Self::silent(net, from, start)?;
+                        children[0].translate(net, start, end)?;
+                        for redo in children[1..].iter() {
+                            redo.translate(net, end, start)?;
+                        }
+                        Self::silent(net, end, to)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProcessTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessTree::Activity(activity) => write!(f, "{:?}", activity),
+            ProcessTree::Silent => write!(f, "tau"),
+            ProcessTree::Node(operator, children) => {
+                write!(f, "{}(", operator.symbol())?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", child)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl ProcessTree {
+    // add the nodes of the subtree in pre-order and return the id of its root
+    fn add_graphml_nodes(&self, graph: &mut GraphMl, next: &mut usize) -> Result<String> {
+        let id = format!("n{}", next);
+        *next += 1;
+
+        match self {
+            ProcessTree::Activity(activity) => graph.node(
+                &id,
+                vec![("kind", "activity"), ("label", activity.as_str())],
+            )?,
+            ProcessTree::Silent => graph.node(&id, vec![("kind", "silent"), ("label", "tau")])?,
+            ProcessTree::Node(operator, children) => {
+                graph.node(
+                    &id,
+                    vec![("kind", "operator"), ("label", operator.symbol())],
+                )?;
+                for (i, child) in children.iter().enumerate() {
+                    let child = child.add_graphml_nodes(graph, next)?;
+                    graph.edge(&id, &child, vec![("order", i)])?;
+                }
+            }
+        }
+
+        Ok(id)
+    }
+}
+
+impl ToGraphMl for ProcessTree {
+    fn to_graphml(&self) -> Result<GraphMl> {
+        let mut graph = GraphMl::new(true)
+            .key("kind", KeyScope::Node, KeyType::String)
+            .key("label", KeyScope::Node, KeyType::String)
+            .key("order", KeyScope::Edge, KeyType::Int);
+        self.add_graphml_nodes(&mut graph, &mut 0)?;
+        Ok(graph)
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ProcessTree {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::soundness::check_soundness;
+
+    use super::*;
+
+    #[test]
+    fn test_to_petri_net() {
+        let tree = ProcessTree::sequence(vec![
+            ProcessTree::activity("a"),
+            ProcessTree::xor(vec![ProcessTree::activity("b"), ProcessTree::Silent]),
+            ProcessTree::parallel(vec![ProcessTree::activity("c"), ProcessTree::activity("d")]),
+            ProcessTree::looped(vec![ProcessTree::activity("e"), ProcessTree::activity("f")]),
+        ]);
+        assert_eq!(
+            tree.to_string(),
+            r#"->("a", X("b", tau), +("c", "d"), *("e", "f"))"#
+        );
+        assert_eq!(tree.activities(), vec!["a", "b", "c", "d", "e", "f"]);
+
+        let xml = tree.to_graphml().unwrap().to_xml().unwrap();
+        assert_eq!(xml.matches("<node ").count(), 11);
+        assert_eq!(xml.matches("<edge ").count(), 10);
+        assert!(xml.contains(r#"<data key="node_label">tau</data>"#));
+        assert!(xml.contains(r#"<edge source="n2" target="n4">"#));
+
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(serde_json::from_str::<ProcessTree>(&json).unwrap(), tree);
+
+        let net = tree.to_petri_net().unwrap();
+        assert_eq!(net.source_places(), vec![net.place("source").unwrap()]);
+        assert_eq!(net.sink_places(), vec![net.place("sink").unwrap()]);
+        assert_eq!(net.transitions.iter().filter(|t| !t.is_silent()).count(), 6);
+        assert!(check_soundness(&net, 1000).unwrap().is_sound());
+
+        assert!(ProcessTree::looped(vec![ProcessTree::Silent])
+            .to_petri_net()
+            .is_err());
+    }
+}
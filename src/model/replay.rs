@@ -0,0 +1,271 @@
+//! Token-based replay
+//!
+//! Conformance of a log and a Petri net is assessed by replaying each trace on the net. Replay
+//! starts with a token in each source place. For each event, a transition labelled with its
+//! activity is fired; if none of them is enabled, the missing tokens of the first one are created
+//! artificially. Finally, a token is consumed from each sink place, and whatever remains in the
+//! net is counted. Fitness then is
+//!
+//! `½ (1 - missing / consumed) + ½ (1 - remaining / produced)`.
+//!
+//! Silent transitions are never fired, replay is thus exact for nets without τ-transitions only.
+//! Events whose activity no transition is labelled with are counted but otherwise skipped.
+//!
+
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::petri::PetriNet;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeContainer, Stream, Trace};
+use crate::{Error, Result};
+
+/// Token counts of a replay
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayStatistics {
+    pub traces: usize,
+    /// Traces replayed without missing or remaining tokens
+    pub fitting_traces: usize,
+    pub produced: u64,
+    pub consumed: u64,
+    pub missing: u64,
+    pub remaining: u64,
+    /// Events without matching transition
+    pub unmatched_events: usize,
+}
+
+impl ReplayStatistics {
+    /// Token-based fitness in `[0, 1]`, one if nothing was replayed
+    pub fn fitness(&self) -> f64 {
+        let ratio = |a: u64, b: u64| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+        0.5 * (1.0 - ratio(self.missing, self.consumed))
+            + 0.5 * (1.0 - ratio(self.remaining, self.produced))
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ReplayStatistics {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Replay all traces of a stream on a Petri net
+pub struct TokenReplay {
+    net: PetriNet,
+    activity_key: String,
+    /// Transitions per activity label
+    transitions: BTreeMap<String, Vec<usize>>,
+    sources: Vec<usize>,
+    sinks: Vec<usize>,
+    statistics: ReplayStatistics,
+}
+
+impl TokenReplay {
+    /// Replay on the given net, which needs source and sink places
+    pub fn new(net: PetriNet) -> Result<Self> {
+        let sources = net.source_places();
+        let sinks = net.sink_places();
+        if sources.is_empty() || sinks.is_empty() {
+            return Err(Error::ModelError(
+                "net lacks source or sink places".to_string(),
+            ));
+        }
+
+        let mut transitions: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, transition) in net.transitions.iter().enumerate() {
+            if let Some(label) = &transition.label {
+                transitions.entry(label.clone()).or_default().push(i);
+            }
+        }
+
+        Ok(TokenReplay {
+            net,
+            activity_key: "concept:name".to_string(),
+            transitions,
+            sources,
+            sinks,
+            statistics: ReplayStatistics::default(),
+        })
+    }
+
+    /// Take activities from the given event attribute
+    pub fn activity_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.activity_key = key.into();
+        self
+    }
+
+    /// Statistics of the traces replayed so far
+    pub fn statistics(&self) -> &ReplayStatistics {
+        &self.statistics
+    }
+
+    fn replay(&mut self, trace: &Trace) -> Result<()> {
+        let mut marking = self
+            .net
+            .marking(&self.sources.iter().map(|p| (*p, 1)).collect::<Vec<_>>());
+        let (mut produced, mut consumed, mut missing) = (self.sources.len() as u64, 0, 0);
+
+        for event in trace.events.iter() {
+            let activity = match event.get_value(&self.activity_key) {
+                Some(value) => value.try_string()?,
+                None => continue,
+            };
+            let candidates = match self.transitions.get(activity) {
+                Some(candidates) => candidates,
+                None => {
+                    self.statistics.unmatched_events += 1;
+                    continue;
+                }
+            };
+
+            let transition = candidates
+                .iter()
+                .copied()
+                .find(|t| self.net.is_enabled(&marking, *t))
+                .unwrap_or(candidates[0]);
+            let inputs = &self.net.transitions[transition].inputs;
+            for place in inputs.iter() {
+                if marking.0[*place] == 0 {
+                    marking.0[*place] += 1;
+                    missing += 1;
+                }
+                marking.0[*place] -= 1;
+            }
+            consumed += inputs.len() as u64;
+
+            let outputs = &self.net.transitions[transition].outputs;
+            for place in outputs.iter() {
+                marking.0[*place] += 1;
+            }
+            produced += outputs.len() as u64;
+        }
+
+        for place in self.sinks.iter() {
+            if marking.0[*place] == 0 {
+                missing += 1;
+            } else {
+                marking.0[*place] -= 1;
+            }
+            consumed += 1;
+        }
+        let remaining = marking.tokens() as u64;
+
+        let statistics = &mut self.statistics;
+        statistics.traces += 1;
+        if missing == 0 && remaining == 0 {
+            statistics.fitting_traces += 1;
+        }
+        statistics.produced += produced;
+        statistics.consumed += consumed;
+        statistics.missing += missing;
+        statistics.remaining += remaining;
+        Ok(())
+    }
+}
+
+impl Handler for TokenReplay {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        self.replay(&trace)?;
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.statistics.clone().into()])
+    }
+}
+
+impl PluginProvider for TokenReplay {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "TokenReplay",
+            "Replay all traces on a Petri net and count produced, consumed, missing and remaining tokens",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be replayed")
+                    .typed_artifact::<PetriNet, _, _>("net", "The Petri net to replay on")
+                    .default_attr("activity", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    }),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let artifact = parameters.acquire_artifact("net")?;
+                    let net = match artifact.downcast_ref::<PetriNet>() {
+                        Some(net) => net.clone(),
+                        None => {
+                            return Err(Error::ArtifactError(format!(
+                                "expected Petri net artifact, got {:?}",
+                                artifact
+                            )))
+                        }
+                    };
+                    let activity = parameters
+                        .acquire_attribute("activity")?
+                        .value
+                        .try_string()?
+                        .to_string();
+
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        TokenReplay::new(net)?.activity_key(activity),
+                    ))
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::model::alpha::alpha;
+    use crate::model::footprint::FootprintGenerator;
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    #[test]
+    fn test_token_replay() {
+        let mut observer =
+            FootprintGenerator::default().into_observer(load_example(&["book", "L1.xes"]));
+        consume(&mut observer).unwrap();
+        let net = alpha(&observer.release().unwrap().footprint()).unwrap();
+
+        // the net discovered from L1 fits L1 perfectly
+        let mut observer = TokenReplay::new(net.clone())
+            .unwrap()
+            .into_observer(load_example(&["book", "L1.xes"]));
+        let artifacts = consume(&mut observer).unwrap();
+        let statistics =
+            AnyArtifact::find::<ReplayStatistics>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(statistics.traces, 6);
+        assert_eq!(statistics.fitting_traces, 6);
+        assert_eq!(statistics.missing, 0);
+        assert_eq!(statistics.remaining, 0);
+        assert_eq!(statistics.fitness(), 1.0);
+
+        // L2 contains activities and loops unknown to the net
+        let mut observer = TokenReplay::new(net)
+            .unwrap()
+            .into_observer(load_example(&["book", "L2.xes"]));
+        consume(&mut observer).unwrap();
+        let replay = observer.release().unwrap();
+        let statistics = replay.statistics();
+        assert_eq!(statistics.traces, 13);
+        assert!(statistics.fitting_traces < 13);
+        assert!(statistics.missing > 0);
+        assert!(statistics.fitness() < 1.0);
+
+        assert!(TokenReplay::new(PetriNet::default()).is_err());
+    }
+}
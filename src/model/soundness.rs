@@ -0,0 +1,478 @@
+//! Behavioural analysis of Petri nets
+//!
+//! A workflow net has a single source place `i` and a single sink place `o` and each node lies on a
+//! path from `i` to `o`, i.e. the net becomes strongly connected if it's short-circuited by a
+//! transition from `o` back to `i`. Such a net is sound iff the short-circuited net is live and
+//! bounded. Equivalently, starting from `[i]`,
+//! * the final marking `[o]` can always be reached (option to complete),
+//! * `[o]` is the only reachable marking that marks `o` (proper completion) and
+//! * each transition can be fired in some reachable marking (no dead transitions).
+//!
+//! These properties are decided on the reachability graph, which is only finite for bounded nets.
+//! Boundedness is detected along the way: a net is unbounded iff some firing sequence leads to a
+//! marking that strictly covers a marking passed before. Dead transitions of unbounded nets are
+//! still found on the (finite) Karp-Miller coverability tree, where places that can be pumped hold
+//! arbitrarily many tokens.
+//!
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::petri::{Marking, PetriNet};
+use crate::stream::Artifact;
+use crate::{Error, Result};
+
+// token count of places that can be pumped in the coverability tree
+const OMEGA: u32 = u32::MAX;
+
+/// Markings reachable from an initial marking
+#[derive(Debug, Clone)]
+pub struct ReachabilityGraph {
+    /// Reachable markings, starting with the initial one
+    pub markings: Vec<Marking>,
+    /// Firings as (source marking, transition, target marking)
+    pub edges: Vec<(usize, usize, usize)>,
+    /// If not, exploration was stopped and the graph is incomplete
+    pub bounded: bool,
+}
+
+impl ReachabilityGraph {
+    /// Explore the state space of a net breadth-first
+    ///
+    /// Fails if there are more than `limit` reachable markings.
+    ///
+    pub fn new(net: &PetriNet, initial: Marking, limit: usize) -> Result<Self> {
+        let mut graph = ReachabilityGraph {
+            markings: vec![initial.clone()],
+            edges: Vec::new(),
+            bounded: true,
+        };
+        let mut index: HashMap<Marking, usize> = vec![(initial, 0)].into_iter().collect();
+        let mut parent: Vec<Option<usize>> = vec![None];
+        let mut queue: VecDeque<usize> = vec![0].into_iter().collect();
+
+        while let Some(current) = queue.pop_front() {
+            for transition in 0..net.transitions.len() {
+                let next = match net.fire(&graph.markings[current], transition) {
+                    Some(next) => next,
+                    None => continue,
+                };
+
+                let target = match index.get(&next) {
+                    Some(target) => *target,
+                    None => {
+                        // an ancestor that is strictly covered can be pumped indefinitely
+                        let mut ancestor = Some(current);
+                        while let Some(a) = ancestor {
+                            if next.covers(&graph.markings[a]) {
+                                graph.bounded = false;
+                                return Ok(graph);
+                            }
+                            ancestor = parent[a];
+                        }
+
+                        if graph.markings.len() >= limit {
+                            return Err(Error::ModelError(format!(
+                                "state space exceeds the limit of {} markings",
+                                limit
+                            )));
+                        }
+
+                        graph.markings.push(next.clone());
+                        parent.push(Some(current));
+                        index.insert(next, graph.markings.len() - 1);
+                        queue.push_back(graph.markings.len() - 1);
+                        graph.markings.len() - 1
+                    }
+                };
+
+                graph.edges.push((current, transition, target));
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Transitions that are not enabled in any reachable marking
+    pub fn dead_transitions(&self, net: &PetriNet) -> Vec<usize> {
+        (0..net.transitions.len())
+            .filter(|t| !self.edges.iter().any(|(_, e, _)| e == t))
+            .collect()
+    }
+
+    /// Markings from which the given one can be reached
+    pub fn co_reachable(&self, target: usize) -> Vec<bool> {
+        let mut predecessors = vec![Vec::new(); self.markings.len()];
+        for (source, _, target) in self.edges.iter() {
+            predecessors[*target].push(*source);
+        }
+
+        let mut reached = vec![false; self.markings.len()];
+        let mut queue: VecDeque<usize> = vec![target].into_iter().collect();
+        reached[target] = true;
+
+        while let Some(current) = queue.pop_front() {
+            for source in predecessors[current].iter() {
+                if !reached[*source] {
+                    reached[*source] = true;
+                    queue.push_back(*source);
+                }
+            }
+        }
+
+        reached
+    }
+}
+
+/// Fire a transition in a marking of the coverability tree, pumped places stay pumped
+fn fire_covering(net: &PetriNet, marking: &Marking, transition: usize) -> Option<Marking> {
+    let mut next = marking.clone();
+    for place in net.transitions[transition].inputs.iter() {
+        match next.0[*place] {
+            0 => return None,
+            OMEGA => (),
+            _ => next.0[*place] -= 1,
+        }
+    }
+    for place in net.transitions[transition].outputs.iter() {
+        if next.0[*place] != OMEGA {
+            next.0[*place] += 1;
+        }
+    }
+    Some(next)
+}
+
+/// Transitions that are enabled in some marking of the coverability tree, i.e. that are not dead
+///
+/// Fails if the tree exceeds `limit` nodes.
+///
+fn coverable_transitions(net: &PetriNet, initial: Marking, limit: usize) -> Result<Vec<bool>> {
+    let mut enabled = vec![false; net.transitions.len()];
+    let mut nodes: Vec<(Marking, Option<usize>)> = vec![(initial.clone(), None)];
+    let mut seen: HashSet<Marking> = vec![initial].into_iter().collect();
+    let mut stack = vec![0];
+
+    while let Some(current) = stack.pop() {
+        for (transition, enabled) in enabled.iter_mut().enumerate() {
+            let mut next = match fire_covering(net, &nodes[current].0, transition) {
+                Some(next) => next,
+                None => continue,
+            };
+            *enabled = true;
+
+            // places that grew beyond a strictly covered ancestor can be pumped indefinitely
+            let mut ancestor = Some(current);
+            while let Some(a) = ancestor {
+                let (marking, parent) = &nodes[a];
+                if next.covers(marking) && next != *marking {
+                    for (n, m) in next.0.iter_mut().zip(marking.0.iter()) {
+                        if *n > *m {
+                            *n = OMEGA;
+                        }
+                    }
+                }
+                ancestor = *parent;
+            }
+
+            if seen.insert(next.clone()) {
+                if nodes.len() >= limit {
+                    return Err(Error::ModelError(format!(
+                        "coverability tree exceeds the limit of {} markings",
+                        limit
+                    )));
+                }
+                nodes.push((next, Some(current)));
+                stack.push(nodes.len() - 1);
+            }
+        }
+    }
+
+    Ok(enabled)
+}
+
+/// Outcome of a soundness check
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SoundnessReport {
+    pub workflow_net: bool,
+    /// Only known for workflow nets
+    pub bounded: Option<bool>,
+    /// Number of explored markings
+    pub markings: usize,
+    pub option_to_complete: bool,
+    pub proper_completion: bool,
+    /// Names of dead transitions
+    pub dead_transitions: Vec<String>,
+    /// Human readable reasons why the net is not sound
+    pub issues: Vec<String>,
+}
+
+impl SoundnessReport {
+    pub fn is_sound(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for SoundnessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.is_sound() {
+            "sound"
+        } else {
+            "not sound"
+        };
+        writeln!(f, "{} ({} markings explored)", verdict, self.markings)?;
+        for issue in self.issues.iter() {
+            writeln!(f, "  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Artifact for SoundnessReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Source and sink place if the net is a workflow net
+fn workflow_places(net: &PetriNet, issues: &mut Vec<String>) -> Option<(usize, usize)> {
+    let (sources, sinks) = (net.source_places(), net.sink_places());
+    if sources.len() != 1 || sinks.len() != 1 {
+        issues.push(format!(
+            "not a workflow net: expected one source and one sink place, found {} and {}",
+            sources.len(),
+            sinks.len()
+        ));
+        return None;
+    }
+    let (source, sink) = (sources[0], sinks[0]);
+
+    // nodes are places followed by transitions
+    let n_places = net.places.len();
+    let mut successors = vec![Vec::new(); n_places + net.transitions.len()];
+    for (t, transition) in net.transitions.iter().enumerate() {
+        for p in transition.inputs.iter() {
+            successors[*p].push(n_places + t);
+        }
+        for p in transition.outputs.iter() {
+            successors[n_places + t].push(*p);
+        }
+    }
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (node, targets) in successors.iter().enumerate() {
+        for target in targets.iter() {
+            predecessors[*target].push(node);
+        }
+    }
+
+    let reach = |start: usize, edges: &[Vec<usize>]| {
+        let mut reached = vec![false; edges.len()];
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            if !reached[node] {
+                reached[node] = true;
+                stack.extend(edges[node].iter().copied());
+            }
+        }
+        reached
+    };
+    let (forward, backward) = (reach(source, &successors), reach(sink, &predecessors));
+
+    let disconnected: Vec<_> = (0..successors.len())
+        .filter(|n| !(forward[*n] && backward[*n]))
+        .map(|n| match n.checked_sub(n_places) {
+            Some(t) => net.transitions[t].name.clone(),
+            None => net.places[n].clone(),
+        })
+        .collect();
+
+    if disconnected.is_empty() {
+        Some((source, sink))
+    } else {
+        issues.push(format!(
+            "not a workflow net: {:?} not on a path from source to sink",
+            disconnected
+        ));
+        None
+    }
+}
+
+/// Check whether a net is a sound workflow net
+///
+/// Fails if the state space of a bounded net exceeds `limit` markings.
+///
+pub fn check_soundness(net: &PetriNet, limit: usize) -> Result<SoundnessReport> {
+    let mut report = SoundnessReport::default();
+
+    let (source, sink) = match workflow_places(net, &mut report.issues) {
+        Some(places) => places,
+        None => return Ok(report),
+    };
+    report.workflow_net = true;
+
+    let graph = ReachabilityGraph::new(net, net.marking(&[(source, 1)]), limit)?;
+    report.markings = graph.markings.len();
+    report.bounded = Some(graph.bounded);
+    if !graph.bounded {
+        report.issues.push("the net is unbounded".to_string());
+
+        // the reachability graph is incomplete, but the coverability tree is finite
+        let enabled = coverable_transitions(net, net.marking(&[(source, 1)]), limit)?;
+        report.dead_transitions = net
+            .transitions
+            .iter()
+            .zip(enabled)
+            .filter(|(_, enabled)| !enabled)
+            .map(|(t, _)| t.name.clone())
+            .collect();
+        if !report.dead_transitions.is_empty() {
+            report
+                .issues
+                .push(format!("dead transitions: {:?}", report.dead_transitions));
+        }
+        return Ok(report);
+    }
+
+    let final_marking = net.marking(&[(sink, 1)]);
+    let co_reachable = match graph.markings.iter().position(|m| m == &final_marking) {
+        Some(target) => graph.co_reachable(target),
+        None => vec![false; graph.markings.len()],
+    };
+    let stuck = co_reachable.iter().filter(|r| !**r).count();
+    report.option_to_complete = stuck == 0;
+    if !report.option_to_complete {
+        report
+            .issues
+            .push(format!("{} reachable markings cannot complete", stuck));
+    }
+
+    let improper = graph
+        .markings
+        .iter()
+        .filter(|m| m.0[sink] > 0 && **m != final_marking)
+        .count();
+    report.proper_completion = improper == 0;
+    if !report.proper_completion {
+        report.issues.push(format!(
+            "{} reachable markings mark the sink place but leave tokens behind",
+            improper
+        ));
+    }
+
+    report.dead_transitions = graph
+        .dead_transitions(net)
+        .into_iter()
+        .map(|t| net.transitions[t].name.clone())
+        .collect();
+    if !report.dead_transitions.is_empty() {
+        report
+            .issues
+            .push(format!("dead transitions: {:?}", report.dead_transitions));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// i > a > (b | c) > d > o, where `split` decides whether b and c are concurrent or exclusive
+    fn net(split: bool) -> PetriNet {
+        let mut net = PetriNet::default();
+        let places: Vec<_> = ["i", "p1", "p2", "p3", "p4", "o"]
+            .iter()
+            .map(|p| net.add_place(*p))
+            .collect();
+        let (i, p1, p2, p3, p4, o) = (
+            places[0], places[1], places[2], places[3], places[4], places[5],
+        );
+
+        let a = net.add_transition("a", Some("a"));
+        net.add_input_arc(i, a).unwrap();
+        net.add_output_arc(a, p1).unwrap();
+        if split {
+            net.add_output_arc(a, p2).unwrap();
+        }
+
+        let b = net.add_transition("b", Some("b"));
+        net.add_input_arc(p1, b).unwrap();
+        net.add_output_arc(b, p3).unwrap();
+
+        // without split, b and c compete for the token in p1
+        let c = net.add_transition("c", Some("c"));
+        net.add_input_arc(if split { p2 } else { p1 }, c).unwrap();
+        net.add_output_arc(c, if split { p4 } else { p2 }).unwrap();
+        if !split {
+            let e = net.add_transition("e", None);
+            net.add_input_arc(p2, e).unwrap();
+            net.add_output_arc(e, p4).unwrap();
+        }
+
+        let d = net.add_transition("d", None);
+        net.add_input_arc(p3, d).unwrap();
+        net.add_input_arc(p4, d).unwrap();
+        net.add_output_arc(d, o).unwrap();
+
+        net
+    }
+
+    #[test]
+    fn test_sound() {
+        let report = check_soundness(&net(true), 1000).unwrap();
+        assert!(report.is_sound(), "{}", report);
+        assert_eq!(report.bounded, Some(true));
+        assert_eq!(report.markings, 6);
+    }
+
+    #[test]
+    fn test_unsound() {
+        // exclusive choice followed by a synchronization deadlocks
+        let mut net = net(false);
+        let report = check_soundness(&net, 1000).unwrap();
+        assert!(!report.option_to_complete);
+        assert!(report.proper_completion);
+        assert_eq!(report.dead_transitions, vec!["d"]);
+        assert_eq!(report.issues.len(), 2);
+
+        // a transition that pumps tokens renders the net unbounded
+        let (p1, p2) = (net.place("p1").unwrap(), net.place("p2").unwrap());
+        let pump = net.add_transition("pump", None);
+        net.add_input_arc(p1, pump).unwrap();
+        net.add_output_arc(pump, p1).unwrap();
+        net.add_output_arc(pump, p2).unwrap();
+        let c = net.add_transition("c'", None);
+        net.add_input_arc(p2, c).unwrap();
+        net.add_output_arc(c, net.place("p4").unwrap()).unwrap();
+
+        let report = check_soundness(&net, 1000).unwrap();
+        assert_eq!(report.bounded, Some(false));
+        assert!(!report.is_sound());
+        assert!(report.dead_transitions.is_empty());
+        assert!(check_soundness(&net, 2).is_err());
+
+        // p3 never holds more than one token, no matter how often p2 is pumped
+        let z = net.add_transition("z", None);
+        net.add_input_arc(net.place("p3").unwrap(), z).unwrap();
+        net.add_input_arc(net.place("p3").unwrap(), z).unwrap();
+        net.add_output_arc(z, net.place("o").unwrap()).unwrap();
+        let report = check_soundness(&net, 1000).unwrap();
+        assert_eq!(report.bounded, Some(false));
+        assert_eq!(report.dead_transitions, vec!["z"]);
+        assert_eq!(report.issues.len(), 2);
+
+        // a place that cannot be marked is not on a path from source to sink
+        let mut net = self::net(true);
+        net.add_place("orphan");
+        let report = check_soundness(&net, 1000).unwrap();
+        assert!(!report.workflow_net);
+        assert_eq!(report.bounded, None);
+    }
+}
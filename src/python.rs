@@ -0,0 +1,588 @@
+//! Python bindings
+//!
+//! The `python` feature exposes promi's XES parsing, logs, buffers, filters, statistics and flow
+//! graphs as Python extension module, such that it can be used from notebooks. The module is built
+//! with [maturin](https://www.maturin.rs), see `pyproject.toml`.
+//!
+//! ```python
+//! import pandas as pd
+//! import pm4py
+//! import promi
+//!
+//! log = promi.read_xes("static/xes/book/L1.xes")
+//! log = log.filter('concept:name in ["a", "b", "c"]', scope="event")
+//! print(log.statistics())
+//!
+//! # hand over to PM4Py via a data frame with the usual `case:` prefixed trace attributes
+//! df = pm4py.format_dataframe(pd.DataFrame(log.to_records()))
+//! log = promi.Log.from_records(df.to_dict("records"))
+//!
+//! graph = promi.Graph.from_json(open("graph.json").read())
+//! graph.set_log("log", log)
+//! graph.execute(workers=4)
+//! print(graph.artifacts())
+//! ```
+//!
+//! Attribute values are mapped onto their natural Python counterparts, i.e. `str`, `int`, `float`,
+//! `bool` and timezone aware `datetime`. Lists become lists of key-value tuples, nested attributes
+//! are dropped. Artifacts other than logs and buffers are handed over as their JSON representation.
+//! Errors are raised as `RuntimeError`.
+//!
+
+// the wrappers generated by pyo3 convert errors that already are `PyErr`s
+#![allow(clippy::useless_conversion)]
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+
+use chrono::NaiveDateTime;
+use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList};
+
+use crate::stream::buffer::Buffer;
+use crate::stream::filter::from_cnf;
+use crate::stream::flow::{Graph, PoolExecutor, ThreadExecutor};
+use crate::stream::log::Log;
+use crate::stream::observer::Handler;
+use crate::stream::plugin;
+use crate::stream::predicate::Predicate;
+use crate::stream::stats::{Statistics, StatsCollector};
+use crate::stream::xes::{XesReader, XesReaderOptions, XesWriter};
+use crate::stream::{
+    AnyArtifact, Attribute, AttributeMap, AttributeValue, Component, Event, KeyTable, Scope, Sink,
+    Trace,
+};
+use crate::{DateTime, Error};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        PyRuntimeError::new_err(error.to_string())
+    }
+}
+
+fn value_to_py(py: Python<'_>, value: &AttributeValue) -> PyObject {
+    match value {
+        AttributeValue::String(string) | AttributeValue::Id(string) => string.as_str().into_py(py),
+        AttributeValue::Date(date) => date.into_py(py),
+        AttributeValue::Int(int) => int.into_py(py),
+        AttributeValue::Float(float) => float.into_py(py),
+        AttributeValue::Boolean(boolean) => boolean.into_py(py),
+        AttributeValue::List(list) => list
+            .iter()
+            .map(|a| (a.key.as_str(), value_to_py(py, &a.value)))
+            .collect::<Vec<_>>()
+            .into_py(py),
+    }
+}
+
+/// Convert a Python value, `None` and NaN (i.e. missing values in data frames) are skipped
+fn value_from_py(value: &Bound<'_, PyAny>) -> PyResult<Option<AttributeValue>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    if let Ok(boolean) = value.downcast::<PyBool>() {
+        return Ok(Some(AttributeValue::Boolean(boolean.is_true())));
+    }
+    if let Ok(int) = value.extract::<i64>() {
+        return Ok(Some(AttributeValue::Int(int)));
+    }
+    if let Ok(float) = value.extract::<f64>() {
+        return Ok((!float.is_nan()).then_some(AttributeValue::Float(float)));
+    }
+    if let Ok(string) = value.extract::<String>() {
+        return Ok(Some(AttributeValue::String(string.into())));
+    }
+    if let Ok(date) = value.extract::<DateTime>() {
+        return Ok(Some(AttributeValue::Date(date)));
+    }
+    // naive timestamps are taken as UTC
+    if let Ok(date) = value.extract::<NaiveDateTime>() {
+        return Ok(Some(AttributeValue::Date(date.and_utc().fixed_offset())));
+    }
+
+    Err(PyTypeError::new_err(format!(
+        "unsupported attribute value {}",
+        value.repr()?
+    )))
+}
+
+fn attributes_to_py<'py>(
+    py: Python<'py>,
+    attributes: &AttributeMap,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    for (key, value, _) in attributes.iter() {
+        dict.set_item(key, value_to_py(py, value))?;
+    }
+    Ok(dict)
+}
+
+fn trace_to_py<'py>(py: Python<'py>, trace: &Trace) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("attributes", attributes_to_py(py, &trace.attributes)?)?;
+    let events = trace
+        .events
+        .iter()
+        .map(|e| attributes_to_py(py, &e.attributes))
+        .collect::<PyResult<Vec<_>>>()?;
+    dict.set_item("events", events)?;
+    Ok(dict)
+}
+
+fn artifact_to_py(py: Python<'_>, artifact: &AnyArtifact) -> PyResult<PyObject> {
+    if let Some(log) = artifact.downcast_ref::<Log>() {
+        return Ok(PyLog { log: log.clone() }.into_py(py));
+    }
+    if let Some(buffer) = artifact.downcast_ref::<Buffer>() {
+        return Ok(PyBuffer {
+            buffer: buffer.clone(),
+        }
+        .into_py(py));
+    }
+
+    // hand over the artifact itself, i.e. without the `AnyArtifact` wrapper
+    let json = serde_json::to_value(artifact)
+        .map_err(|e| Error::ArtifactError(format!("unable to serialize artifact: {}", e)))?;
+    Ok(py
+        .import_bound("json")?
+        .call_method1("loads", (json["artifact"].to_string(),))?
+        .unbind())
+}
+
+/// In-memory event log
+#[pyclass(name = "Log", module = "promi")]
+#[derive(Clone, Default)]
+pub struct PyLog {
+    log: Log,
+}
+
+#[pymethods]
+impl PyLog {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of traces
+    fn __len__(&self) -> usize {
+        self.log.traces.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Log(traces={}, events={})",
+            self.log.traces.len(),
+            self.log
+                .traces
+                .iter()
+                .map(|t| t.events.len())
+                .sum::<usize>()
+                + self.log.events.len()
+        )
+    }
+
+    /// Log-level attributes
+    fn meta<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        attributes_to_py(py, &self.log.meta.attributes)
+    }
+
+    /// Traces as dictionaries with `attributes` and `events`
+    fn traces<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.log.traces.iter().map(|t| trace_to_py(py, t)).collect()
+    }
+
+    /// Standalone events
+    fn events<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.log
+            .events
+            .iter()
+            .map(|e| attributes_to_py(py, &e.attributes))
+            .collect()
+    }
+
+    /// One record per event, trace attributes are prefixed with `case:`
+    ///
+    /// The records can be turned into a pandas data frame as it is used by PM4Py.
+    ///
+    fn to_records<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let records = PyList::empty_bound(py);
+        for trace in self.log.traces.iter() {
+            for event in trace.events.iter() {
+                let record = PyDict::new_bound(py);
+                for (key, value, _) in trace.attributes.iter() {
+                    record.set_item(format!("case:{}", key), value_to_py(py, value))?;
+                }
+                for (key, value, _) in event.attributes.iter() {
+                    record.set_item(key, value_to_py(py, value))?;
+                }
+                records.append(record)?;
+            }
+        }
+        for event in self.log.events.iter() {
+            records.append(attributes_to_py(py, &event.attributes)?)?;
+        }
+        Ok(records)
+    }
+
+    /// Build a log from records as created by `to_records`
+    ///
+    /// Records are grouped into traces by the case column in order of appearance, records without
+    /// one become standalone events. Attributes prefixed with `case:` are assigned to the trace.
+    ///
+    #[staticmethod]
+    #[pyo3(signature = (records, case = "case:concept:name"))]
+    fn from_records(records: &Bound<'_, PyAny>, case: &str) -> PyResult<Self> {
+        let mut log = Log::default();
+        let mut index = std::collections::HashMap::new();
+        let mut keys = KeyTable::default();
+
+        for record in records.iter()? {
+            let record = record?;
+            let record = record.downcast::<PyDict>()?;
+
+            let case_id = match record.get_item(case)? {
+                Some(value) => value_from_py(&value)?,
+                None => None,
+            };
+            let mut event = Event::default();
+            let mut trace_attributes = Vec::new();
+            for (key, value) in record.iter() {
+                let key: String = key.extract()?;
+                let value = match value_from_py(&value)? {
+                    Some(value) => value,
+                    None => continue,
+                };
+                match key.strip_prefix("case:") {
+                    Some(key) => trace_attributes.push(Attribute::new(keys.intern(key), value)),
+                    None => event
+                        .attributes
+                        .insert(Attribute::new(keys.intern(&key), value)),
+                }
+            }
+
+            let case_id = match case_id {
+                Some(AttributeValue::String(id)) | Some(AttributeValue::Id(id)) => id.to_string(),
+                Some(other) => format!("{:?}", other),
+                None => {
+                    log.events.push(event);
+                    continue;
+                }
+            };
+            let position = *index.entry(case_id).or_insert_with(|| {
+                let mut trace = Trace::default();
+                trace_attributes
+                    .drain(..)
+                    .for_each(|a| trace.attributes.insert(a));
+                log.traces.push(trace);
+                log.traces.len() - 1
+            });
+            log.traces[position].events.push(event);
+        }
+
+        Ok(PyLog { log })
+    }
+
+    /// Keep traces or events that match a predicate, e.g. `concept:name in ["a", "b"]`
+    #[pyo3(signature = (predicate, scope = "event"))]
+    fn filter(&self, py: Python<'_>, predicate: &str, scope: &str) -> PyResult<Self> {
+        let log = self.log.clone();
+        let (predicate, scope) = (predicate.to_string(), scope.to_string());
+        let log = py.allow_threads(move || -> crate::Result<Log> {
+            let predicate = Predicate::parse(&predicate)?;
+            let buffer = Buffer::from(log);
+            let mut filtered = match Scope::try_from(Some(scope))? {
+                Scope::Trace => from_cnf(buffer, predicate.cnf()?, Vec::new()),
+                Scope::Event => from_cnf(buffer, Vec::new(), predicate.cnf()?),
+            };
+            let mut log = Log::default();
+            log.consume(&mut filtered)?;
+            Ok(log)
+        })?;
+        Ok(PyLog { log })
+    }
+
+    /// Number of traces, events in traces and events in total
+    fn statistics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mut observer = StatsCollector::default().into_observer(Buffer::from(self.log.clone()));
+        let artifacts = crate::stream::void::consume(&mut observer)?;
+        let statistics = AnyArtifact::find::<Statistics>(&mut artifacts.iter().flatten())
+            .cloned()
+            .unwrap_or_default();
+
+        let [traces, events, total] = statistics.counts();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("traces", traces)?;
+        dict.set_item("events", events)?;
+        dict.set_item("total_events", total)?;
+        Ok(dict)
+    }
+
+    fn write_xes(&self, py: Python<'_>, path: &str) -> PyResult<()> {
+        let log = self.log.clone();
+        py.allow_threads(move || -> crate::Result<()> {
+            let mut writer = XesWriter::new(File::create(path)?);
+            writer.consume(&mut Buffer::from(log))?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        Ok(serde_json::to_string(&self.log)
+            .map_err(|e| Error::ArtifactError(format!("unable to serialize log: {}", e)))?)
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let log = serde_json::from_str(json)
+            .map_err(|e| Error::ArtifactError(format!("unable to deserialize log: {}", e)))?;
+        Ok(PyLog { log })
+    }
+}
+
+/// Read a XES file into memory, compressed files are detected by their extension
+#[pyfunction]
+fn read_xes(py: Python<'_>, path: &str) -> PyResult<PyLog> {
+    let log = py.allow_threads(|| -> crate::Result<Log> {
+        let mut reader = XesReaderOptions::default().open(path)?;
+        let mut log = Log::default();
+        log.consume(&mut reader)?;
+        Ok(log)
+    })?;
+    Ok(PyLog { log })
+}
+
+/// Fifo queue of stream components
+#[pyclass(name = "Buffer", module = "promi")]
+#[derive(Clone, Default)]
+pub struct PyBuffer {
+    buffer: Buffer,
+}
+
+#[pymethods]
+impl PyBuffer {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    #[staticmethod]
+    fn from_log(log: &PyLog) -> Self {
+        PyBuffer {
+            buffer: Buffer::from(log.log.clone()),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Collect the buffered components into a log, fails on buffered errors
+    fn to_log(&self) -> PyResult<PyLog> {
+        let mut log = Log::default();
+        log.consume(&mut self.buffer.clone())?;
+        Ok(PyLog { log })
+    }
+}
+
+/// Iterate the components of a XES file lazily
+///
+/// Each component is a dictionary with its `type` (`meta`, `trace` or `event`) and `attributes`,
+/// traces also hold their `events`.
+///
+#[pyclass(name = "XesReader", module = "promi", unsendable)]
+pub struct PyXesReader {
+    reader: XesReader<BufReader<Box<dyn std::io::Read + Send>>>,
+}
+
+#[pymethods]
+impl PyXesReader {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        Ok(PyXesReader {
+            reader: XesReaderOptions::default().open(path)?,
+        })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        use crate::stream::Stream;
+
+        let component = match self.reader.next()? {
+            Some(component) => component,
+            None => return Ok(None),
+        };
+        let dict = match &component {
+            Component::Meta(meta) => {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("attributes", attributes_to_py(py, &meta.attributes)?)?;
+                dict.set_item("type", "meta")?;
+                dict
+            }
+            Component::Trace(trace) => {
+                let dict = trace_to_py(py, trace)?;
+                dict.set_item("type", "trace")?;
+                dict
+            }
+            Component::Event(event) => {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("attributes", attributes_to_py(py, &event.attributes)?)?;
+                dict.set_item("type", "event")?;
+                dict
+            }
+        };
+        Ok(Some(dict))
+    }
+}
+
+/// Flow graph, see `promi::stream::flow`
+#[pyclass(name = "Graph", module = "promi")]
+pub struct PyGraph {
+    graph: Graph,
+}
+
+#[pymethods]
+impl PyGraph {
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let graph = serde_json::from_str(json)
+            .map_err(|e| Error::FlowError(format!("unable to deserialize graph: {}", e)))?;
+        Ok(PyGraph { graph })
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        Ok(serde_json::to_string(&self.graph)
+            .map_err(|e| Error::FlowError(format!("unable to serialize graph: {}", e)))?)
+    }
+
+    /// Pass a log into the graph, e.g. to be acquired by a `LogArtifactSource`
+    fn set_log(&mut self, name: String, log: &PyLog) {
+        self.graph
+            .artifacts
+            .insert(name, AnyArtifact::from(log.log.clone()));
+    }
+
+    /// Execute the graph with a thread per pipe or on a pool of workers
+    #[pyo3(signature = (workers = None))]
+    fn execute(&mut self, py: Python<'_>, workers: Option<usize>) -> PyResult<()> {
+        let graph = &mut self.graph;
+        py.allow_threads(|| match workers {
+            Some(workers) => graph.execute(&mut PoolExecutor::new(workers)).map(|_| ()),
+            None => graph.execute(&mut ThreadExecutor::default()).map(|_| ()),
+        })?;
+        Ok(())
+    }
+
+    /// Named artifacts, internal ones (e.g. pipe definitions) are left out
+    fn artifacts<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (name, artifact) in self.graph.artifacts.iter() {
+            if !name.starts_with("__") {
+                dict.set_item(name, artifact_to_py(py, artifact)?)?;
+            }
+        }
+        Ok(dict)
+    }
+
+    fn artifact(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        match self.graph.artifacts.get(name) {
+            Some(artifact) => artifact_to_py(py, artifact),
+            None => Err(PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    /// Report of the most recent run
+    fn run_report(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.graph
+            .run_report()
+            .map(|report| artifact_to_py(py, &AnyArtifact::from(report.clone())))
+            .transpose()
+    }
+}
+
+/// Metadata of all installed plugins as JSON
+#[pyfunction]
+fn plugins() -> PyResult<String> {
+    Ok(serde_json::to_string(&plugin::describe()?)
+        .map_err(|e| Error::StreamError(format!("unable to serialize plugins: {}", e)))?)
+}
+
+#[pymodule]
+fn promi(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", crate::VERSION)?;
+    m.add_class::<PyLog>()?;
+    m.add_class::<PyBuffer>()?;
+    m.add_class::<PyXesReader>()?;
+    m.add_class::<PyGraph>()?;
+    m.add_function(wrap_pyfunction!(read_xes, m)?)?;
+    m.add_function(wrap_pyfunction!(plugins, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "promi").unwrap();
+            promi(&module).unwrap();
+
+            let path: String = join_static_str!("xes", "book", "L1.xes");
+            let log = read_xes(py, &path).unwrap();
+            assert_eq!(log.__len__(), 6);
+            assert_eq!(log.__repr__(), "Log(traces=6, events=23)");
+
+            let statistics = log.statistics(py).unwrap();
+            let total: usize = statistics
+                .get_item("total_events")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(total, 23);
+
+            // records round trip as used for PM4Py interop
+            let records = log.to_records(py).unwrap();
+            assert_eq!(records.len(), 23);
+            let restored = PyLog::from_records(records.as_any(), "case:concept:name").unwrap();
+            assert_eq!(restored.__len__(), 6);
+            assert_eq!(restored.to_records(py).unwrap().len(), 23);
+
+            let filtered = log.filter(py, r#"concept:name == "a""#, "event").unwrap();
+            assert_eq!(filtered.__repr__(), "Log(traces=6, events=6)");
+
+            let mut reader = PyXesReader::new(&path).unwrap();
+            let mut components = 0;
+            while reader.__next__(py).unwrap().is_some() {
+                components += 1;
+            }
+            assert_eq!(components, 1 + 6);
+
+            let json = r#"{"generation": 0, "artifacts": {}, "staging": null, "pipes": [{
+                "name": "Foo",
+                "source": {"name": "LogArtifactSource", "artifact_receiver": ["log"]},
+                "streams": [{"name": "Statistics", "artifact_sender": ["stats"]}],
+                "sink": {"name": "BufferSink", "artifact_sender": ["buffer"]}
+            }]}"#;
+            let mut graph = PyGraph::from_json(json).unwrap();
+            graph.set_log("log".into(), &filtered);
+            graph.execute(py, Some(2)).unwrap();
+
+            let artifacts = graph.artifacts(py).unwrap();
+            let buffer = artifacts.get_item("buffer").unwrap().unwrap();
+            assert_eq!(buffer.extract::<PyBuffer>().unwrap().__len__(), 1 + 6);
+            let stats = artifacts.get_item("stats").unwrap().unwrap();
+            assert!(stats.get_item("__type").is_ok());
+            assert!(graph.artifact(py, "missing").is_err());
+            assert!(graph.run_report(py).unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,499 @@
+//! Graphviz DOT rendering
+//!
+//! Directly-follows graphs, Petri nets and process trees implement `ToDot` to be rendered into
+//! [DOT](https://graphviz.org/doc/info/lang.html) documents, e.g. to be laid out by `dot -Tsvg`.
+//! The appearance is configured by a `DotStyle`: the direction of the layout, fonts, colors and,
+//! for directly-follows graphs, whether edges are labelled with their frequency or mean duration
+//! and scaled by their weight. The `DotWriter` sink writes such an artifact to a file.
+//!
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::str::FromStr;
+
+use crate::model::petri::PetriNet;
+use crate::model::process_tree::{Operator, ProcessTree};
+use crate::stream::dfg::Dfg;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, AttributeType, Sink};
+use crate::{Error, Result};
+
+/// Annotation of the edges of a directly-follows graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeLabel {
+    #[default]
+    Frequency,
+    /// Mean duration of the relation, if known
+    Performance,
+    None,
+}
+
+impl FromStr for EdgeLabel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "frequency" => Ok(EdgeLabel::Frequency),
+            "performance" => Ok(EdgeLabel::Performance),
+            "none" => Ok(EdgeLabel::None),
+            _ => Err(Error::AttributeError(format!(
+                "invalid edge label {:?}, expected \"frequency\", \"performance\" or \"none\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// Appearance of rendered graphs
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotStyle {
+    rankdir: String,
+    font: String,
+    fill: String,
+    silent: String,
+    labels: EdgeLabel,
+    scale_edges: bool,
+}
+
+impl Default for DotStyle {
+    fn default() -> Self {
+        DotStyle {
+            rankdir: "LR".to_string(),
+            font: "Helvetica".to_string(),
+            fill: "#dbe9f6".to_string(),
+            silent: "#333333".to_string(),
+            labels: EdgeLabel::default(),
+            scale_edges: true,
+        }
+    }
+}
+
+impl DotStyle {
+    /// Set the direction of the layout, i.e. one of `LR`, `RL`, `TB` or `BT`
+    pub fn rankdir<D: Into<String>>(mut self, rankdir: D) -> Self {
+        self.rankdir = rankdir.into();
+        self
+    }
+
+    /// Set the font of all labels
+    pub fn font<F: Into<String>>(mut self, font: F) -> Self {
+        self.font = font.into();
+        self
+    }
+
+    /// Set the fill color of activities
+    pub fn fill<C: Into<String>>(mut self, color: C) -> Self {
+        self.fill = color.into();
+        self
+    }
+
+    /// Set the fill color of silent transitions and steps
+    pub fn silent<C: Into<String>>(mut self, color: C) -> Self {
+        self.silent = color.into();
+        self
+    }
+
+    /// Set the annotation of the edges of directly-follows graphs
+    pub fn labels(mut self, labels: EdgeLabel) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Scale the edges of directly-follows graphs by their weight
+    pub fn scale_edges(mut self, scale_edges: bool) -> Self {
+        self.scale_edges = scale_edges;
+        self
+    }
+
+    fn header(&self, dot: &mut String) {
+        dot.push_str("digraph {\n");
+        let _ = writeln!(dot, "  rankdir={};", quote(&self.rankdir));
+        let _ = writeln!(dot, "  node [fontname={}];", quote(&self.font));
+        let _ = writeln!(dot, "  edge [fontname={}];", quote(&self.font));
+    }
+}
+
+/// Quote and escape a DOT identifier
+fn quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Human readable duration, e.g. `1.5h`
+fn format_duration(seconds: f64) -> String {
+    let (value, unit) = match seconds.abs() {
+        s if s >= 86400.0 => (seconds / 86400.0, "d"),
+        s if s >= 3600.0 => (seconds / 3600.0, "h"),
+        s if s >= 60.0 => (seconds / 60.0, "m"),
+        _ => (seconds, "s"),
+    };
+    format!("{}{}", (value * 10.0).round() / 10.0, unit)
+}
+
+/// Artifacts that can be rendered into DOT
+pub trait ToDot {
+    fn to_dot(&self, style: &DotStyle) -> String;
+}
+
+impl ToDot for Dfg {
+    fn to_dot(&self, style: &DotStyle) -> String {
+        let mut dot = String::new();
+        style.header(&mut dot);
+        let _ = writeln!(
+            dot,
+            "  node [shape=box, style=\"rounded,filled\", fillcolor={}];",
+            quote(&style.fill)
+        );
+
+        // activity names are not necessarily valid ids
+        let ids: HashMap<&str, usize> = self
+            .activities
+            .keys()
+            .enumerate()
+            .map(|(i, activity)| (activity.as_str(), i))
+            .collect();
+        let id = |activity: &str| ids.get(activity).copied();
+        for (i, (activity, weight)) in self.activities.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "  n{} [label={}];",
+                i,
+                quote(&format!("{}\n{}", activity, weight))
+            );
+        }
+
+        let max_weight = self
+            .edges
+            .values()
+            .flat_map(|t| t.values())
+            .chain(self.start.values())
+            .chain(self.end.values())
+            .fold(0.0f64, |a, b| a.max(*b));
+        let edge = |dot: &mut String, from: String, to: String, label: Option<String>, w: f64| {
+            let mut attributes = Vec::new();
+            if let Some(label) = label {
+                attributes.push(format!("label={}", quote(&label)));
+            }
+            if style.scale_edges && max_weight > 0.0 {
+                attributes.push(format!("penwidth={:.2}", 1.0 + 4.0 * w / max_weight));
+            }
+            let _ = writeln!(dot, "  {} -> {} [{}];", from, to, attributes.join(", "));
+        };
+        let frequency = |weight: f64| match style.labels {
+            EdgeLabel::Frequency => Some(weight.to_string()),
+            _ => None,
+        };
+
+        if !self.start.is_empty() {
+            dot.push_str(
+                "  start [shape=circle, label=\"\", style=filled, fillcolor=\"#8fbc8f\"];\n",
+            );
+        }
+        if !self.end.is_empty() {
+            dot.push_str(
+                "  end [shape=doublecircle, label=\"\", style=filled, fillcolor=\"#f08080\"];\n",
+            );
+        }
+        for (activity, weight) in self.start.iter() {
+            if let Some(i) = id(activity) {
+                edge(
+                    &mut dot,
+                    "start".into(),
+                    format!("n{}", i),
+                    frequency(*weight),
+                    *weight,
+                );
+            }
+        }
+        for (from, targets) in self.edges.iter() {
+            for (to, weight) in targets.iter() {
+                let label = match style.labels {
+                    EdgeLabel::Performance => self.mean_duration(from, to).map(format_duration),
+                    _ => frequency(*weight),
+                };
+                if let (Some(i), Some(j)) = (id(from), id(to)) {
+                    edge(
+                        &mut dot,
+                        format!("n{}", i),
+                        format!("n{}", j),
+                        label,
+                        *weight,
+                    );
+                }
+            }
+        }
+        for (activity, weight) in self.end.iter() {
+            if let Some(i) = id(activity) {
+                edge(
+                    &mut dot,
+                    format!("n{}", i),
+                    "end".into(),
+                    frequency(*weight),
+                    *weight,
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl ToDot for PetriNet {
+    fn to_dot(&self, style: &DotStyle) -> String {
+        let mut dot = String::new();
+        style.header(&mut dot);
+
+        // source places are marked as in the initial marking of a workflow net
+        let sources = self.source_places();
+        for (i, name) in self.places.iter().enumerate() {
+            let label = if sources.contains(&i) { "●" } else { "" };
+            let _ = writeln!(
+                dot,
+                "  p{} [shape=circle, label={}, xlabel={}];",
+                i,
+                quote(label),
+                quote(name)
+            );
+        }
+
+        for (i, transition) in self.transitions.iter().enumerate() {
+            match &transition.label {
+                Some(label) => {
+                    let _ = writeln!(
+                        dot,
+                        "  t{} [shape=box, style=filled, fillcolor={}, label={}];",
+                        i,
+                        quote(&style.fill),
+                        quote(label)
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        dot,
+                        "  t{} [shape=box, style=filled, fillcolor={}, label=\"\", width=0.15];",
+                        i,
+                        quote(&style.silent)
+                    );
+                }
+            }
+        }
+
+        for (i, transition) in self.transitions.iter().enumerate() {
+            for place in transition.inputs.iter() {
+                let _ = writeln!(dot, "  p{} -> t{};", place, i);
+            }
+            for place in transition.outputs.iter() {
+                let _ = writeln!(dot, "  t{} -> p{};", i, place);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl ToDot for ProcessTree {
+    fn to_dot(&self, style: &DotStyle) -> String {
+        fn node(tree: &ProcessTree, style: &DotStyle, dot: &mut String, next: &mut usize) -> usize {
+            let id = *next;
+            *next += 1;
+
+            match tree {
+                ProcessTree::Activity(activity) => {
+                    let _ = writeln!(
+                        dot,
+                        "  n{} [shape=box, style=filled, fillcolor={}, label={}];",
+                        id,
+                        quote(&style.fill),
+                        quote(activity)
+                    );
+                }
+                ProcessTree::Silent => {
+                    let _ = writeln!(
+                        dot,
+                        "  n{} [shape=box, style=filled, fillcolor={}, label=\"τ\", fontcolor=white];",
+                        id,
+                        quote(&style.silent)
+                    );
+                }
+                ProcessTree::Node(operator, children) => {
+                    let symbol = match operator {
+                        Operator::Sequence => "→",
+                        Operator::Xor => "×",
+                        Operator::Parallel => "∧",
+                        Operator::Loop => "↺",
+                    };
+                    let _ = writeln!(dot, "  n{} [shape=circle, label={}];", id, quote(symbol));
+                    for child in children.iter() {
+                        let child = node(child, style, dot, next);
+                        let _ = writeln!(dot, "  n{} -> n{};", id, child);
+                    }
+                }
+            }
+
+            id
+        }
+
+        let mut dot = String::new();
+        style.header(&mut dot);
+        dot.push_str("  ordering=out;\n");
+        node(self, style, &mut dot, &mut 0);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Render an artifact into DOT, if it's a graph or model
+pub fn artifact_to_dot(artifact: &AnyArtifact, style: &DotStyle) -> Result<String> {
+    if let Some(dfg) = artifact.downcast_ref::<Dfg>() {
+        Ok(dfg.to_dot(style))
+    } else if let Some(net) = artifact.downcast_ref::<PetriNet>() {
+        Ok(net.to_dot(style))
+    } else if let Some(tree) = artifact.downcast_ref::<ProcessTree>() {
+        Ok(tree.to_dot(style))
+    } else {
+        Err(Error::ArtifactError(format!(
+            "expected graph or model artifact, got {:?}",
+            artifact
+        )))
+    }
+}
+
+/// Write a rendered artifact as DOT once the stream is closed
+pub struct DotWriter<W: io::Write> {
+    writer: W,
+    dot: String,
+}
+
+impl<W: io::Write> DotWriter<W> {
+    pub fn new(writer: W, dot: String) -> Self {
+        DotWriter { writer, dot }
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write + Send> Sink for DotWriter<W> {
+    fn on_close(&mut self) -> Result<()> {
+        self.writer.write_all(self.dot.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl PluginProvider for DotWriter<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "DotWriter",
+            "Render a DFG, Petri net or process tree artifact into a Graphviz DOT file",
+            Factory::new(
+                Declaration::default()
+                    .artifact("graph", "The graph or model to be rendered")
+                    .typed_attr("path", "Location of the DOT file", AttributeType::String)
+                    .default_attr("labels", "Edge labels of DFGs", |k| (k, "frequency").into())
+                    .default_attr("rankdir", "Direction of the layout", |k| (k, "LR").into()),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let style = DotStyle::default()
+                        .labels(
+                            parameters
+                                .acquire_attribute("labels")?
+                                .value
+                                .try_string()?
+                                .parse()?,
+                        )
+                        .rankdir(
+                            parameters
+                                .acquire_attribute("rankdir")?
+                                .value
+                                .try_string()?,
+                        );
+                    let dot = artifact_to_dot(parameters.acquire_artifact("graph")?, &style)?;
+                    let path = parameters.acquire_attribute("path")?;
+                    let file = File::create(path.value.try_string()?)?;
+                    Ok(DotWriter::new(file, dot).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::dfg::Performance;
+
+    use super::*;
+
+    #[test]
+    fn test_dot() {
+        let mut dfg = Dfg::default();
+        dfg.activities.insert("a \"quoted\"".into(), 2.0);
+        dfg.activities.insert("b".into(), 2.0);
+        dfg.start.insert("a \"quoted\"".into(), 2.0);
+        dfg.end.insert("b".into(), 2.0);
+        dfg.edges
+            .entry("a \"quoted\"".into())
+            .or_default()
+            .insert("b".into(), 1.0);
+        dfg.performance
+            .entry("a \"quoted\"".into())
+            .or_default()
+            .insert(
+                "b".into(),
+                Performance {
+                    weight: 1.0,
+                    seconds: 5400.0,
+                },
+            );
+
+        let dot = dfg.to_dot(&DotStyle::default());
+        assert!(dot.starts_with("digraph {\n  rankdir=\"LR\";"));
+        assert!(dot.contains(r#"n0 [label="a \"quoted\"\n2"];"#));
+        assert!(dot.contains(r#"n0 -> n1 [label="1", penwidth=3.00];"#));
+        assert!(dot.contains(r#"start -> n0 [label="2", penwidth=5.00];"#));
+        let dot = dfg.to_dot(
+            &DotStyle::default()
+                .labels(EdgeLabel::Performance)
+                .scale_edges(false),
+        );
+        assert!(dot.contains(r#"n0 -> n1 [label="1.5h"];"#));
+        assert!(dot.contains("start -> n0 [];"));
+
+        let tree = ProcessTree::sequence(vec![
+            ProcessTree::activity("a"),
+            ProcessTree::xor(vec![ProcessTree::activity("b"), ProcessTree::Silent]),
+        ]);
+        let dot = tree.to_dot(&DotStyle::default().rankdir("TB"));
+        assert!(dot.contains(r#"n0 [shape=circle, label="→"];"#));
+        assert!(dot.contains("n2 -> n4;"));
+
+        let net = tree.to_petri_net().unwrap();
+        let dot = artifact_to_dot(&net.into(), &DotStyle::default()).unwrap();
+        assert!(dot.contains(r#"p0 [shape=circle, label="●", xlabel="source"];"#));
+        assert!(dot.contains(r#"label="", width=0.15];"#));
+        assert!(dot.contains("p0 -> t0;"));
+
+        let artifact: AnyArtifact = Buffer::default().into();
+        assert!(matches!(
+            artifact_to_dot(&artifact, &DotStyle::default()),
+            Err(Error::ArtifactError(_))
+        ));
+
+        let mut sink = DotWriter::new(Vec::new(), dot.clone());
+        sink.consume(&mut Buffer::default()).unwrap();
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(), dot);
+    }
+}
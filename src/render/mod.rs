@@ -0,0 +1,7 @@
+//! Rendering of artifacts
+//!
+//! Models and graphs are rendered into formats of external visualization tools, such that results
+//! can be inspected without leaving the crate.
+//!
+
+pub mod dot;
@@ -0,0 +1,540 @@
+//! Anonymization of event logs for sharing
+//!
+//! The `Anonymize` handler prepares logs, e.g. of hospitals or banks, to be handed to third
+//! parties. Values of selected attributes, e.g. `org:resource` or case identifiers, are replaced
+//! by tokens, and timestamps may be shifted and generalized.
+//!
+//! Tokens are either derived by a keyed HMAC-SHA256, so that the same value yields the same token
+//! in every run with the same key, or drawn from a consistent dictionary that numbers values in
+//! order of appearance, e.g. `org:resource#1`. Unlike `Pseudonymize`, no state has to be kept
+//! across runs to obtain consistent HMAC tokens. Note that HMAC tokens do not depend on the
+//! attribute key, i.e. a case identifier referenced by several attributes maps to the same token.
+//!
+//! HMAC tokens are truncated to 8 bytes (16 hex digits) by default, which keeps them readable.
+//! Distinct values collide with a probability of about n²/2⁶⁵ for n values, i.e. collisions become
+//! likely once there are billions of values. Since a collision merges unrelated values, use
+//! longer tokens for large value domains, see `Anonymize::token_bytes`.
+//!
+//! Optionally, the mapping from original values to tokens is emitted as a `ProtectedMapping`
+//! artifact, encrypted as a vault, that can only be opened with the key it was protected with.
+//!
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::Duration;
+use hmac::{Hmac, Mac};
+use rand::{random, Rng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::pseudonymize::{Vault, VaultKey};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeMap, AttributeType, AttributeValue, Event, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// Bytes of the HMAC that are kept in a token by default, i.e. 16 hex digits
+pub const TOKEN_BYTES: usize = 8;
+
+/// HMAC-SHA256 of a message (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// How attribute values are turned into tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TokenMethod {
+    /// Keyed HMAC-SHA256, truncated to [`Anonymize::token_bytes`] bytes
+    #[default]
+    Hmac,
+    /// Values are numbered per attribute in order of appearance
+    Dictionary,
+}
+
+impl FromStr for TokenMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hmac" => Ok(TokenMethod::Hmac),
+            "dictionary" => Ok(TokenMethod::Dictionary),
+            _ => Err(Error::AttributeError(format!(
+                "invalid token method {:?}, expected \"hmac\" or \"dictionary\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// Encrypted mapping from original values to tokens
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProtectedMapping {
+    data: Vec<u8>,
+}
+
+impl ProtectedMapping {
+    /// Encrypt a mapping under the given key
+    pub fn protect(vault: &Vault, key: &VaultKey) -> Result<Self> {
+        let mut data = Vec::new();
+        vault.write(&mut data, key)?;
+        Ok(ProtectedMapping { data })
+    }
+
+    /// Decrypt the mapping
+    pub fn open(&self, key: &VaultKey) -> Result<Vault> {
+        Vault::read(self.data.as_slice(), key)
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ProtectedMapping {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Replace attribute values by tokens and disguise timestamps
+///
+/// String and integer values of the selected attributes are replaced by string tokens. All date
+/// values, not only `time:timestamp`, are first shifted by a fixed offset and a random offset that
+/// is drawn per trace, preserving durations within a trace, and then truncated to a multiple of
+/// the generalization granularity. Standalone events are treated as traces of their own.
+///
+pub struct Anonymize {
+    keys: Vec<String>,
+    method: TokenMethod,
+    token_bytes: usize,
+    key: VaultKey,
+    dictionary: BTreeMap<String, BTreeMap<String, String>>,
+    mapping: Vault,
+    protect: Option<VaultKey>,
+    shift: Duration,
+    jitter: Duration,
+    generalize: Option<Duration>,
+    rng: Pcg64,
+}
+
+impl Anonymize {
+    pub fn new<K: Into<String>, I: IntoIterator<Item = K>>(keys: I) -> Self {
+        Anonymize {
+            keys: keys.into_iter().map(|k| k.into()).collect(),
+            method: TokenMethod::default(),
+            token_bytes: TOKEN_BYTES,
+            key: VaultKey::generate(),
+            dictionary: BTreeMap::new(),
+            mapping: Vault::default(),
+            protect: None,
+            shift: Duration::zero(),
+            jitter: Duration::zero(),
+            generalize: None,
+            rng: Pcg64::new(random(), 0),
+        }
+    }
+
+    /// Set how tokens are derived
+    pub fn method(mut self, method: TokenMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set how many bytes of the HMAC are kept in a token, [`TOKEN_BYTES`] by default
+    ///
+    /// Shorter tokens are more readable, longer ones are less likely to collide.
+    ///
+    /// # Panics
+    /// If `bytes` is not in the range [1, 32].
+    ///
+    pub fn token_bytes(mut self, bytes: usize) -> Self {
+        assert!(
+            (1..=32).contains(&bytes),
+            "token size has to be between 1 and 32 bytes, got {}",
+            bytes
+        );
+        self.token_bytes = bytes;
+        self
+    }
+
+    /// Set the HMAC key, a random key is used by default
+    pub fn key(mut self, key: VaultKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Emit the mapping as artifact, encrypted under the given key
+    pub fn protect(mut self, key: VaultKey) -> Self {
+        self.protect = Some(key);
+        self
+    }
+
+    /// Shift all timestamps by a fixed offset
+    pub fn shift(mut self, shift: Duration) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    /// Shift the timestamps of each trace by a random offset of at most the given magnitude
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Truncate timestamps to a multiple of the given granularity, e.g. one day
+    pub fn generalize(mut self, granularity: Duration) -> Self {
+        self.generalize = Some(granularity);
+        self
+    }
+
+    /// Make random offsets reproducible
+    pub fn random_state(mut self, random_state: u128) -> Self {
+        self.rng = Pcg64::new(random_state, 0);
+        self
+    }
+
+    fn token(&mut self, key: &str, value: &str) -> Result<String> {
+        let token = match self.method {
+            TokenMethod::Hmac => hmac_sha256(self.key.as_bytes(), value.as_bytes())
+                [..self.token_bytes]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            TokenMethod::Dictionary => {
+                let tokens = self.dictionary.entry(key.to_string()).or_default();
+                let next = tokens.len() + 1;
+                tokens
+                    .entry(value.to_string())
+                    .or_insert_with(|| format!("{}#{}", key, next))
+                    .clone()
+            }
+        };
+
+        if self.protect.is_some() && self.mapping.pseudonym(key, value).is_none() {
+            self.mapping.insert(key, value, &token)?;
+        }
+        Ok(token)
+    }
+
+    fn offset(&mut self) -> Duration {
+        let jitter = self.jitter.num_seconds().abs();
+        if jitter == 0 {
+            return self.shift;
+        }
+        self.shift + Duration::seconds(self.rng.gen_range(-jitter..=jitter))
+    }
+
+    fn disguise(&self, timestamp: &DateTime, offset: Duration) -> DateTime {
+        let shifted = *timestamp + offset;
+        match self.generalize.map(|g| g.num_seconds()) {
+            Some(granularity) if granularity > 0 => {
+                shifted
+                    - Duration::seconds(shifted.timestamp().rem_euclid(granularity))
+                    - Duration::nanoseconds(shifted.timestamp_subsec_nanos() as i64)
+            }
+            _ => shifted,
+        }
+    }
+
+    fn apply(&mut self, attributes: &mut AttributeMap, offset: Duration) -> Result<()> {
+        for key in self.keys.clone().iter() {
+            let value = match attributes.get_value(key) {
+                Some(AttributeValue::String(value)) => value.to_string(),
+                Some(AttributeValue::Int(value)) => value.to_string(),
+                _ => continue,
+            };
+            let token = self.token(key, &value)?;
+            *attributes.get_value_mut(key).unwrap() = AttributeValue::String(token.into());
+        }
+
+        let dates: Vec<_> = attributes
+            .iter()
+            .filter(|(_, value, _)| matches!(value, AttributeValue::Date(_)))
+            .map(|(key, _, _)| key.to_string())
+            .collect();
+        for key in dates.iter() {
+            if let Some(AttributeValue::Date(timestamp)) = attributes.get_value_mut(key) {
+                *timestamp = self.disguise(timestamp, offset);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for Anonymize {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        let offset = self.offset();
+        self.apply(&mut trace.attributes, offset)?;
+        for event in trace.events.iter_mut() {
+            self.apply(&mut event.attributes, offset)?;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            let offset = self.offset();
+            self.apply(&mut event.attributes, offset)?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        match &self.protect {
+            Some(key) => Ok(vec![ProtectedMapping::protect(&self.mapping, key)?.into()]),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+impl PluginProvider for Anonymize {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Anonymize",
+            "Replace attribute values by tokens and disguise timestamps",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be anonymized")
+                    .typed_attr(
+                        "keys",
+                        "List of attributes to be tokenized",
+                        AttributeType::List,
+                    )
+                    .default_attr("method", "Either \"hmac\" or \"dictionary\"", |k| {
+                        (k, "hmac").into()
+                    })
+                    .default_attr(
+                        "token_bytes",
+                        "Bytes of the HMAC kept in a token, between 1 and 32",
+                        |k| (k, TOKEN_BYTES as i64).into(),
+                    )
+                    .optional_attr(
+                        "key",
+                        "HMAC key as 64 hex digits, random if omitted",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "protect",
+                        "Key as 64 hex digits that protects the dictionary in the released mapping",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "shift",
+                        "Seconds all timestamps are shifted by",
+                        AttributeType::Int,
+                    )
+                    .optional_attr(
+                        "jitter",
+                        "Maximal seconds of random noise added to timestamps",
+                        AttributeType::Int,
+                    )
+                    .optional_attr(
+                        "generalize",
+                        "Granularity in seconds timestamps are truncated to",
+                        AttributeType::Int,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let keys = parameters
+                        .acquire_attribute("keys")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(|a| Ok(a.value.try_string()?.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+                    let method = parameters
+                        .acquire_attribute("method")?
+                        .value
+                        .try_string()?
+                        .parse()?;
+                    let token_bytes = *parameters
+                        .acquire_attribute("token_bytes")?
+                        .value
+                        .try_int()?;
+                    if !(1..=32).contains(&token_bytes) {
+                        return Err(Error::AttributeError(format!(
+                            "token_bytes is expected to be between 1 and 32, got {}",
+                            token_bytes
+                        )));
+                    }
+                    let mut anonymize = Anonymize::new(keys)
+                        .method(method)
+                        .token_bytes(token_bytes as usize);
+
+                    // optional HMAC key and key protecting the mapping, 64 hex digits each
+                    if let Ok(key) = parameters.acquire_attribute("key") {
+                        anonymize = anonymize.key(VaultKey::from_hex(key.value.try_string()?)?);
+                    }
+                    if let Ok(key) = parameters.acquire_attribute("protect") {
+                        anonymize = anonymize.protect(VaultKey::from_hex(key.value.try_string()?)?);
+                    }
+
+                    // optional shift, jitter and generalization granularity in seconds
+                    if let Ok(shift) = parameters.acquire_attribute("shift") {
+                        anonymize = anonymize.shift(Duration::seconds(*shift.value.try_int()?));
+                    }
+                    if let Ok(jitter) = parameters.acquire_attribute("jitter") {
+                        anonymize = anonymize.jitter(Duration::seconds(*jitter.value.try_int()?));
+                    }
+                    if let Ok(granularity) = parameters.acquire_attribute("generalize") {
+                        anonymize =
+                            anonymize.generalize(Duration::seconds(*granularity.value.try_int()?));
+                    }
+
+                    Ok(
+                        Observer::from((parameters.acquire_stream("inner")?, anonymize))
+                            .into_boxed(),
+                    )
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeContainer, Component, Sink};
+
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn anonymized(anonymize: Anonymize) -> (Log, Vec<AnyArtifact>) {
+        let mut buffer = Buffer::default();
+        for (case, resources) in [("c1", ["alice", "bob"]), ("c2", ["bob", "carol"])].iter() {
+            let mut trace = Trace::default();
+            trace.attributes.insert(("concept:name", *case));
+            for (i, resource) in resources.iter().enumerate() {
+                let timestamp =
+                    DateTime::parse_from_rfc3339(&format!("2020-01-0{}T10:3{}:00+01:00", i + 1, i))
+                        .unwrap();
+                let mut event = Event::default();
+                event.attributes.insert(("org:resource", *resource));
+                event.attributes.insert(("time:timestamp", timestamp));
+                trace.events.push(event);
+            }
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+
+        let mut observer = anonymize.into_observer(buffer);
+        let mut log = Log::default();
+        log.consume(&mut observer).unwrap();
+        let artifacts = observer.release().unwrap().release_artifacts().unwrap();
+        (log, artifacts)
+    }
+
+    fn values(log: &Log, key: &str) -> Vec<String> {
+        log.traces
+            .iter()
+            .flat_map(|t| t.events.iter())
+            .map(|e| match e.get_value(key).unwrap() {
+                AttributeValue::Date(timestamp) => timestamp.to_rfc3339(),
+                value => value.try_string().unwrap().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_anonymize() {
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // HMAC tokens are consistent across runs with the same key
+        let key = VaultKey::from_hex(&"ab".repeat(32)).unwrap();
+        let protect = VaultKey::generate();
+        let (first, artifacts) = anonymized(
+            Anonymize::new(vec!["org:resource", "concept:name"])
+                .key(key.clone())
+                .protect(protect.clone()),
+        );
+        let (second, _) = anonymized(Anonymize::new(vec!["org:resource"]).key(key.clone()));
+        let tokens = values(&first, "org:resource");
+        assert_eq!(tokens, values(&second, "org:resource"));
+        assert_eq!(tokens[1], tokens[2]);
+        assert_eq!(tokens[0].len(), 16);
+
+        // the token size is configurable, shorter tokens are prefixes of longer ones
+        let (full, _) = anonymized(
+            Anonymize::new(vec!["org:resource"])
+                .key(key)
+                .token_bytes(32),
+        );
+        let full = values(&full, "org:resource");
+        assert_eq!(full[0].len(), 64);
+        assert!(full[0].starts_with(&tokens[0]));
+        assert!(std::panic::catch_unwind(|| Anonymize::new(vec!["foo"]).token_bytes(33)).is_err());
+        assert_ne!(
+            first.traces[0].get_value("concept:name").unwrap(),
+            &AttributeValue::from("c1")
+        );
+
+        let mapping = AnyArtifact::find::<ProtectedMapping>(&mut artifacts.iter())
+            .unwrap()
+            .open(&protect)
+            .unwrap();
+        assert_eq!(mapping.len(), 5);
+        assert_eq!(mapping.original("org:resource", &tokens[0]), Some("alice"));
+
+        // dictionary tokens, shifted and generalized timestamps
+        let (log, artifacts) = anonymized(
+            Anonymize::new(vec!["org:resource"])
+                .method(TokenMethod::Dictionary)
+                .shift(Duration::days(-1))
+                .generalize(Duration::hours(1)),
+        );
+        assert!(artifacts.is_empty());
+        assert_eq!(
+            values(&log, "org:resource"),
+            vec![
+                "org:resource#1",
+                "org:resource#2",
+                "org:resource#2",
+                "org:resource#3"
+            ]
+        );
+        assert_eq!(
+            values(&log, "time:timestamp")[..2],
+            [
+                "2019-12-31T10:00:00+01:00".to_string(),
+                "2020-01-01T10:00:00+01:00".to_string()
+            ]
+        );
+
+        // jitter preserves durations within traces
+        let (log, _) = anonymized(
+            Anonymize::new(Vec::<String>::new())
+                .jitter(Duration::days(30))
+                .random_state(42),
+        );
+        for trace in log.traces.iter() {
+            let timestamps: Vec<_> = trace
+                .events
+                .iter()
+                .map(|e| *e.get_value("time:timestamp").unwrap().try_date().unwrap())
+                .collect();
+            assert_eq!(
+                timestamps[1] - timestamps[0],
+                Duration::days(1) + Duration::minutes(1)
+            );
+        }
+    }
+}
@@ -0,0 +1,829 @@
+//! Columnar event data in Apache Parquet format
+//!
+//! Data lakes store event data in Parquet files, one event per row. `ParquetWriter` flattens a
+//! stream into such files and `ParquetReader` turns them back into an event stream, reading the
+//! file batch by batch instead of as a whole.
+//!
+//! The columns holding the case, the activity and the timestamp are configurable and default to
+//! `case:concept:name`, `concept:name` and `time:timestamp`. Like for CSV, further attributes of
+//! the enclosing trace are prefixed with `case:`, all other columns hold event attributes.
+//! Consecutive rows of the same case are grouped into a trace, rows without case become standalone
+//! events.
+//!
+//! The writer infers the schema from the first batch of rows: strings and ids become `Utf8`,
+//! dates `Timestamp(Microsecond, "UTC")`, integers `Int64`, floats `Float64` and booleans
+//! `Boolean`. Columns with mixed types are written as strings, lists are skipped. Writing fails
+//! if an attribute first occurs after the schema is fixed or doesn't fit the type of its column,
+//! such columns need to be declared upfront or the batch size needs to be increased.
+//!
+//! This module requires the `arrow` feature.
+//!
+
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, TimestampMicrosecondBuilder,
+};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Date32Type, Date64Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{Array, ArrayRef, RecordBatch, RecordBatchReader};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::ChunkReader;
+
+use crate::stream::csv::{format_value, CASE_PREFIX};
+use crate::stream::extension::{Concept, Extension, Time};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::{
+    Attribute, AttributeType, AttributeValue, Component, Event, Meta, ResOpt, Sink, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+const TIMEZONE: &str = "UTC";
+
+/// Names of the columns holding case, activity and timestamp
+#[derive(Debug, Clone)]
+struct Columns {
+    case: String,
+    activity: String,
+    timestamp: String,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            case: format!("{}concept:name", CASE_PREFIX),
+            activity: "concept:name".into(),
+            timestamp: "time:timestamp".into(),
+        }
+    }
+}
+
+impl Columns {
+    /// Configure columns from optional plugin attributes
+    fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        let mut columns = Columns::default();
+        for (key, column) in [
+            ("case", &mut columns.case),
+            ("activity", &mut columns.activity),
+            ("timestamp", &mut columns.timestamp),
+        ] {
+            if let Ok(attribute) = parameters.acquire_attribute(key) {
+                *column = attribute.value.try_string()?.to_string();
+            }
+        }
+        Ok(columns)
+    }
+}
+
+// case identifier, trace attributes and event of a row
+type Row = (Option<String>, Vec<Attribute>, Event);
+
+/// Read events from a Parquet file, batch by batch
+///
+/// ```no_run
+/// use promi::stream::arrow::ParquetReader;
+/// use promi::stream::log::Log;
+/// use promi::stream::Sink;
+///
+/// let mut reader = ParquetReader::open("events.parquet").unwrap().case("order_id");
+/// let mut log = Log::default();
+/// log.consume(&mut reader).unwrap();
+/// ```
+pub struct ParquetReader {
+    batches: ParquetRecordBatchReader,
+    columns: Columns,
+    batch: Option<RecordBatch>,
+    row: usize,
+    trace: Option<(String, Trace)>,
+    queue: VecDeque<Component>,
+    meta: bool,
+    done: bool,
+}
+
+impl ParquetReader {
+    /// Read Parquet data from any source that supports random access, e.g. a file
+    pub fn new<R: ChunkReader + 'static>(reader: R) -> Result<Self> {
+        Ok(ParquetReader {
+            batches: ParquetRecordBatchReaderBuilder::try_new(reader)?.build()?,
+            columns: Columns::default(),
+            batch: None,
+            row: 0,
+            trace: None,
+            queue: VecDeque::new(),
+            meta: false,
+            done: false,
+        })
+    }
+
+    /// Open a Parquet file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Column holding the case identifier, `case:concept:name` by default
+    pub fn case<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.case = column.into();
+        self
+    }
+
+    /// Column holding the activity, `concept:name` by default
+    pub fn activity<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.activity = column.into();
+        self
+    }
+
+    /// Column holding the timestamp, `time:timestamp` by default
+    pub fn timestamp<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.timestamp = column.into();
+        self
+    }
+
+    fn read_meta(&mut self) -> Result<Meta> {
+        let schema = self.batches.schema();
+        if schema.field_with_name(&self.columns.activity).is_err() {
+            return Err(Error::ArrowError(format!(
+                "no column {:?}",
+                self.columns.activity
+            )));
+        }
+
+        let mut meta = Meta::default();
+        meta.extensions.push(Concept::declare());
+        if schema.field_with_name(&self.columns.timestamp).is_ok() {
+            meta.extensions.push(Time::declare());
+        }
+        Ok(meta)
+    }
+
+    /// Turn the next row into a case identifier, trace attributes and an event
+    fn read_row(&mut self) -> Result<Option<Row>> {
+        while self.batch.as_ref().is_none_or(|b| self.row >= b.num_rows()) {
+            match self.batches.next() {
+                Some(batch) => {
+                    self.batch = Some(batch?);
+                    self.row = 0;
+                }
+                None => return Ok(None),
+            }
+        }
+
+        let batch = self.batch.as_ref().unwrap();
+        let schema = batch.schema();
+        let mut case = None;
+        let mut attributes = Vec::new();
+        let mut event = Event::default();
+        for (field, array) in schema.fields().iter().zip(batch.columns()) {
+            let value = match read_value(array, self.row)? {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let column = field.name();
+            if column == &self.columns.case {
+                case = Some(format_value(&value));
+            } else if column == &self.columns.activity {
+                event.attributes.insert(("concept:name", value));
+            } else if column == &self.columns.timestamp {
+                event.attributes.insert(("time:timestamp", value));
+            } else if let Some(key) = column.strip_prefix(CASE_PREFIX) {
+                attributes.push(Attribute::new(key, value));
+            } else {
+                event.attributes.insert((column.as_str(), value));
+            }
+        }
+        self.row += 1;
+
+        Ok(Some((case, attributes, event)))
+    }
+
+    fn read(&mut self) -> ResOpt {
+        if !self.meta {
+            self.meta = true;
+            return Ok(Some(Component::Meta(self.read_meta()?)));
+        }
+
+        while self.queue.is_empty() {
+            let (case, attributes, event) = match self.read_row()? {
+                Some(row) => row,
+                None => {
+                    return Ok(self.trace.take().map(|(_, t)| Component::Trace(t)));
+                }
+            };
+
+            match case {
+                Some(case) if self.trace.as_ref().is_some_and(|(c, _)| *c == case) => {
+                    self.trace.as_mut().unwrap().1.events.push(event);
+                }
+                Some(case) => {
+                    let mut trace = Trace::default();
+                    trace.attributes.insert(("concept:name", case.as_str()));
+                    for attribute in attributes {
+                        trace.attributes.insert(attribute);
+                    }
+                    trace.events.push(event);
+                    if let Some((_, previous)) = self.trace.replace((case, trace)) {
+                        self.queue.push_back(Component::Trace(previous));
+                    }
+                }
+                None => {
+                    if let Some((_, previous)) = self.trace.take() {
+                        self.queue.push_back(Component::Trace(previous));
+                    }
+                    self.queue.push_back(Component::Event(event));
+                }
+            }
+        }
+
+        Ok(self.queue.pop_front())
+    }
+}
+
+/// Convert a cell to an attribute value, unsupported types and nulls are skipped
+fn read_value(array: &ArrayRef, row: usize) -> Result<Option<AttributeValue>> {
+    if array.is_null(row) {
+        return Ok(None);
+    }
+
+    let timestamp = |value: Option<NaiveDateTime>| -> Result<AttributeValue> {
+        let value = value
+            .ok_or_else(|| Error::ArrowError(format!("timestamp out of range in row {}", row)))?;
+        Ok(AttributeValue::Date(DateTime::from(
+            Utc.from_utc_datetime(&value),
+        )))
+    };
+
+    let value = match array.data_type() {
+        DataType::Utf8 => AttributeValue::String(array.as_string::<i32>().value(row).into()),
+        DataType::LargeUtf8 => AttributeValue::String(array.as_string::<i64>().value(row).into()),
+        DataType::Boolean => AttributeValue::Boolean(array.as_boolean().value(row)),
+        DataType::Int8 => AttributeValue::Int(array.as_primitive::<Int8Type>().value(row).into()),
+        DataType::Int16 => AttributeValue::Int(array.as_primitive::<Int16Type>().value(row).into()),
+        DataType::Int32 => AttributeValue::Int(array.as_primitive::<Int32Type>().value(row).into()),
+        DataType::Int64 => AttributeValue::Int(array.as_primitive::<Int64Type>().value(row)),
+        DataType::UInt8 => AttributeValue::Int(array.as_primitive::<UInt8Type>().value(row).into()),
+        DataType::UInt16 => {
+            AttributeValue::Int(array.as_primitive::<UInt16Type>().value(row).into())
+        }
+        DataType::UInt32 => {
+            AttributeValue::Int(array.as_primitive::<UInt32Type>().value(row).into())
+        }
+        DataType::UInt64 => {
+            let value = array.as_primitive::<UInt64Type>().value(row);
+            AttributeValue::Int(i64::try_from(value).map_err(|_| {
+                Error::ArrowError(format!("integer {} out of range in row {}", value, row))
+            })?)
+        }
+        DataType::Float32 => {
+            AttributeValue::Float(array.as_primitive::<Float32Type>().value(row).into())
+        }
+        DataType::Float64 => AttributeValue::Float(array.as_primitive::<Float64Type>().value(row)),
+        // timestamps are stored relative to UTC, regardless of their timezone
+        DataType::Timestamp(TimeUnit::Second, _) => timestamp(
+            chrono::DateTime::from_timestamp(
+                array.as_primitive::<TimestampSecondType>().value(row),
+                0,
+            )
+            .map(|t| t.naive_utc()),
+        )?,
+        DataType::Timestamp(TimeUnit::Millisecond, _) => timestamp(
+            chrono::DateTime::from_timestamp_millis(
+                array.as_primitive::<TimestampMillisecondType>().value(row),
+            )
+            .map(|t| t.naive_utc()),
+        )?,
+        DataType::Timestamp(TimeUnit::Microsecond, _) => timestamp(
+            chrono::DateTime::from_timestamp_micros(
+                array.as_primitive::<TimestampMicrosecondType>().value(row),
+            )
+            .map(|t| t.naive_utc()),
+        )?,
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => timestamp(Some(
+            chrono::DateTime::from_timestamp_nanos(
+                array.as_primitive::<TimestampNanosecondType>().value(row),
+            )
+            .naive_utc(),
+        ))?,
+        DataType::Date32 => timestamp(
+            NaiveDate::from_ymd_opt(1970, 1, 1)
+                .and_then(|d| {
+                    d.checked_add_signed(chrono::Duration::days(
+                        array.as_primitive::<Date32Type>().value(row).into(),
+                    ))
+                })
+                .and_then(|d| d.and_hms_opt(0, 0, 0)),
+        )?,
+        DataType::Date64 => timestamp(
+            chrono::DateTime::from_timestamp_millis(array.as_primitive::<Date64Type>().value(row))
+                .map(|t| t.naive_utc()),
+        )?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+impl Stream for ParquetReader {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.done {
+            return Ok(None);
+        }
+
+        let component = self.read();
+        if component.is_err() {
+            self.done = true;
+        }
+        component
+    }
+}
+
+impl PluginProvider for ParquetReader {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "ParquetReader",
+            "Read events from a Parquet file, one event per row, with optional case, activity and timestamp columns",
+            Factory::new(
+                Declaration::default().typed_attr(
+                    "path",
+                    "Location of the Parquet file",
+                    AttributeType::String,
+                ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let mut reader = ParquetReader::open(path)?;
+                    reader.columns = Columns::from_parameters(parameters)?;
+                    Ok(reader.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+/// Write a stream as Parquet file, one row per event
+///
+/// Rows are collected and written in batches, see the module documentation for how the schema is
+/// derived.
+pub struct ParquetWriter<W: Write + Send> {
+    writer: Option<W>,
+    arrow: Option<(ArrowWriter<W>, SchemaRef)>,
+    columns: Columns,
+    batch_size: usize,
+    rows: Vec<BTreeMap<String, AttributeValue>>,
+    declared: Vec<String>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Write Parquet data to the given writer
+    pub fn new(writer: W) -> Self {
+        ParquetWriter {
+            writer: Some(writer),
+            arrow: None,
+            columns: Columns::default(),
+            batch_size: 10_000,
+            rows: Vec::new(),
+            declared: Vec::new(),
+        }
+    }
+
+    /// Column to write the case identifier to, `case:concept:name` by default
+    pub fn case<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.case = column.into();
+        self
+    }
+
+    /// Column to write the activity to, `concept:name` by default
+    pub fn activity<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.activity = column.into();
+        self
+    }
+
+    /// Column to write the timestamp to, `time:timestamp` by default
+    pub fn timestamp<S: Into<String>>(mut self, column: S) -> Self {
+        self.columns.timestamp = column.into();
+        self
+    }
+
+    /// Declare columns upfront, in case they don't occur within the first batch
+    ///
+    /// Declared columns take their type from the first batch, or are written as strings if they
+    /// don't occur in it. Trace attributes are prefixed with `case:`.
+    pub fn columns<C: Into<String>, I: IntoIterator<Item = C>>(mut self, columns: I) -> Self {
+        self.declared = columns.into_iter().map(|c| c.into()).collect();
+        self
+    }
+
+    /// Number of rows per batch, 10000 by default
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn row(&self, event: &Event, trace: Option<&Trace>) -> BTreeMap<String, AttributeValue> {
+        let mut row = BTreeMap::new();
+        let mut add = |column: String, value: &AttributeValue| {
+            if !matches!(value, AttributeValue::List(_)) {
+                row.insert(column, value.clone());
+            }
+        };
+
+        if let Some(trace) = trace {
+            for (key, value, _) in trace.attributes.iter() {
+                if key == "concept:name" {
+                    add(self.columns.case.clone(), value);
+                } else {
+                    add(format!("{}{}", CASE_PREFIX, key), value);
+                }
+            }
+        }
+        for (key, value, _) in event.attributes.iter() {
+            match key {
+                "concept:name" => add(self.columns.activity.clone(), value),
+                "time:timestamp" => add(self.columns.timestamp.clone(), value),
+                key => add(key.to_string(), value),
+            }
+        }
+        row
+    }
+
+    /// Derive the schema from the pending rows
+    fn infer_schema(&self) -> Schema {
+        let mut types: BTreeMap<&str, Option<DataType>> = BTreeMap::new();
+        types.insert(&self.columns.activity, None);
+        for column in self.declared.iter() {
+            types.insert(column, None);
+        }
+        for row in self.rows.iter() {
+            for (column, value) in row.iter() {
+                let data_type = match value {
+                    AttributeValue::String(_) | AttributeValue::Id(_) => DataType::Utf8,
+                    AttributeValue::Date(_) => {
+                        DataType::Timestamp(TimeUnit::Microsecond, Some(TIMEZONE.into()))
+                    }
+                    AttributeValue::Int(_) => DataType::Int64,
+                    AttributeValue::Float(_) => DataType::Float64,
+                    AttributeValue::Boolean(_) => DataType::Boolean,
+                    AttributeValue::List(_) => continue,
+                };
+
+                let entry = types.entry(column).or_insert(None);
+                match entry {
+                    Some(known) if *known != data_type => *entry = Some(DataType::Utf8),
+                    Some(_) => (),
+                    None => *entry = Some(data_type),
+                }
+            }
+        }
+
+        // case, activity and timestamp first, then trace attributes and event attributes
+        let rank = |column: &str| {
+            if column == self.columns.case {
+                0
+            } else if column == self.columns.activity {
+                1
+            } else if column == self.columns.timestamp {
+                2
+            } else if column.starts_with(CASE_PREFIX) {
+                3
+            } else {
+                4
+            }
+        };
+        let mut fields: Vec<Field> = types
+            .into_iter()
+            .map(|(column, data_type)| {
+                Field::new(column, data_type.unwrap_or(DataType::Utf8), true)
+            })
+            .collect();
+        fields.sort_by_key(|f| rank(f.name()));
+        Schema::new(fields)
+    }
+
+    fn batch(&self, schema: &SchemaRef) -> Result<RecordBatch> {
+        for row in self.rows.iter() {
+            if let Some(column) = row.keys().find(|c| schema.field_with_name(c).is_err()) {
+                return Err(Error::ArrowError(format!(
+                    "column {:?} is missing in the schema derived from the first batch, \
+                    declare it upfront or increase the batch size",
+                    column
+                )));
+            }
+        }
+
+        let rows = &self.rows;
+        let mismatch = |column: &str, value: &AttributeValue| {
+            Error::ArrowError(format!(
+                "value {:?} does not fit the type of column {:?}",
+                value, column
+            ))
+        };
+        let arrays = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let column = field.name();
+                let values = rows.iter().map(|r| r.get(column));
+                let array: ArrayRef = match field.data_type() {
+                    DataType::Int64 => {
+                        let mut builder = Int64Builder::with_capacity(rows.len());
+                        for value in values {
+                            builder.append_option(match value {
+                                Some(AttributeValue::Int(value)) => Some(*value),
+                                Some(value) => return Err(mismatch(column, value)),
+                                None => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    DataType::Float64 => {
+                        let mut builder = Float64Builder::with_capacity(rows.len());
+                        for value in values {
+                            builder.append_option(match value {
+                                Some(AttributeValue::Float(value)) => Some(*value),
+                                Some(AttributeValue::Int(value)) => Some(*value as f64),
+                                Some(value) => return Err(mismatch(column, value)),
+                                None => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    DataType::Boolean => {
+                        let mut builder = BooleanBuilder::with_capacity(rows.len());
+                        for value in values {
+                            builder.append_option(match value {
+                                Some(AttributeValue::Boolean(value)) => Some(*value),
+                                Some(value) => return Err(mismatch(column, value)),
+                                None => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    DataType::Timestamp(_, _) => {
+                        let mut builder = TimestampMicrosecondBuilder::with_capacity(rows.len())
+                            .with_timezone(TIMEZONE);
+                        for value in values {
+                            builder.append_option(match value {
+                                Some(AttributeValue::Date(value)) => Some(value.timestamp_micros()),
+                                Some(value) => return Err(mismatch(column, value)),
+                                None => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    _ => {
+                        let mut builder = StringBuilder::new();
+                        for value in values {
+                            builder.append_option(value.map(format_value));
+                        }
+                        Arc::new(builder.finish())
+                    }
+                };
+                Ok(array)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.arrow.is_none() {
+            let schema = Arc::new(self.infer_schema());
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let writer = self.writer.take().unwrap();
+            let arrow = ArrowWriter::try_new(writer, schema.clone(), Some(properties))?;
+            self.arrow = Some((arrow, schema));
+        }
+
+        if !self.rows.is_empty() {
+            let schema = self.arrow.as_ref().unwrap().1.clone();
+            let batch = self.batch(&schema)?;
+            self.rows.clear();
+            self.arrow.as_mut().unwrap().0.write(&batch)?;
+        }
+        Ok(())
+    }
+
+    fn on_row(&mut self, row: BTreeMap<String, AttributeValue>) -> Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> Sink for ParquetWriter<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        match component {
+            Component::Meta(_) => Ok(()),
+            Component::Trace(trace) => trace
+                .events
+                .iter()
+                .try_for_each(|event| self.on_row(self.row(event, Some(&trace)))),
+            Component::Event(event) => self.on_row(self.row(&event, None)),
+        }
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.flush()?;
+        if let Some((arrow, _)) = self.arrow.take() {
+            arrow.close()?;
+        }
+        Ok(())
+    }
+}
+
+impl PluginProvider for ParquetWriter<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "ParquetWriter",
+            "Write the stream as Parquet file with one row per event, with optional case, activity and timestamp columns",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the Parquet file", AttributeType::String)
+                    .default_attr("batch_size", "Number of rows per batch", |k| {
+                        (k, 10_000).into()
+                    })
+                    .default_attr(
+                        "columns",
+                        "Columns to declare upfront, trace attributes are prefixed with case:",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    ),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let batch_size = *parameters.acquire_attribute("batch_size")?.value.try_int()?;
+                    if batch_size < 1 {
+                        return Err(Error::AttributeError(format!(
+                            "batch_size is expected to be positive, got {}",
+                            batch_size
+                        )));
+                    }
+
+                    let columns = parameters
+                        .acquire_attribute("columns")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(|c| c.value.try_string().map(|c| c.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    let mut writer = ParquetWriter::new(File::create(path)?)
+                        .batch_size(batch_size as usize)
+                        .columns(columns);
+                    writer.columns = Columns::from_parameters(parameters)?;
+                    Ok(writer.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("promi_parquet_{}", std::process::id()));
+
+        let mut expected = Log::default();
+        expected
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+
+        // the schema is inferred from the first batch, which needs to contain all columns
+        let time = DateTime::parse_from_rfc3339("2021-03-04T10:00:00.123456+00:00").unwrap();
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(
+            Trace::builder()
+                .case_id("typed")
+                .attribute(("priority", 2))
+                .event(
+                    Event::builder()
+                        .name("x")
+                        .timestamp(time)
+                        .attribute(("cost", 2.5))
+                        .attribute(("valid", true))
+                        .attribute(("mixed", 1)),
+                )
+                .event(Event::builder().name("y").attribute(("mixed", "one")))
+                .build(),
+        ))));
+        for trace in expected.traces.iter() {
+            buffer.push(Ok(Some(Component::Trace(trace.clone()))));
+        }
+        buffer.push(Ok(Some(Component::Event(
+            Event::builder().name("z").build(),
+        ))));
+
+        let mut writer = ParquetWriter::new(File::create(&path).unwrap()).batch_size(7);
+        writer.consume(&mut buffer).unwrap();
+
+        let mut log = Log::default();
+        log.consume(&mut ParquetReader::open(&path).unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(log.traces.len(), expected.traces.len() + 1);
+        for (trace, original) in log.traces[1..].iter().zip(expected.traces.iter()) {
+            assert_eq!(trace.case_id(), original.case_id());
+            let names = |t: &Trace| {
+                t.events
+                    .iter()
+                    .map(|e| e.name().map(String::from))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(names(trace), names(original));
+        }
+
+        let typed = &log.traces[0];
+        assert_eq!(typed.case_id(), Some("typed"));
+        assert_eq!(typed.attributes.get_value("priority"), Some(&2.into()));
+        let event = &typed.events[0];
+        assert_eq!(event.timestamp(), Some(&time));
+        assert_eq!(event.attributes.get_value("cost"), Some(&2.5.into()));
+        assert_eq!(event.attributes.get_value("valid"), Some(&true.into()));
+        assert_eq!(event.attributes.get_value("mixed"), Some(&"1".into()));
+        assert_eq!(
+            typed.events[1].attributes.get_value("mixed"),
+            Some(&"one".into())
+        );
+
+        assert_eq!(log.events.len(), 1);
+        assert_eq!(log.events[0].name(), Some("z"));
+
+        // late columns and values that don't fit their column fail unless declared upfront
+        let late = |declared: &[&str]| {
+            let mut buffer = Buffer::default();
+            for (i, value) in [AttributeValue::Int(1), AttributeValue::Int(2), "x".into()]
+                .iter()
+                .enumerate()
+            {
+                let mut event = Event::builder().name("a");
+                if i > 0 {
+                    event = event.attribute(("late", value.clone()));
+                }
+                buffer.push(Ok(Some(Component::Event(event.build()))));
+            }
+            ParquetWriter::new(File::create(&path).unwrap())
+                .batch_size(1)
+                .columns(declared.iter().copied())
+                .consume(&mut buffer)
+        };
+        match late(&[]) {
+            Err(Error::ArrowError(message)) => assert!(message.contains("\"late\"")),
+            other => panic!("expected arrow error, got {:?}", other),
+        }
+        assert!(late(&["late"]).is_ok());
+        let mut log = Log::default();
+        log.consume(&mut ParquetReader::open(&path).unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let values: Vec<_> = log
+            .events
+            .iter()
+            .map(|e| e.attributes.get_value("late").cloned())
+            .collect();
+        assert_eq!(values, [None, Some("2".into()), Some("x".into())]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Adapters for async applications
+//!
+//! Streams and sinks are blocking by design. The adapters of this module, available with the
+//! `async` feature, run them on the blocking thread pool of a tokio runtime and connect them to
+//! async code through bounded channels:
+//! - `AsyncStream` pulls the components of a stream, e.g. to send them over the network,
+//! - `AsyncSink` pushes components into a sink, e.g. as they are received from the network.
+//!
+//! Flow graphs are executed by `execute_graph` with the `AsyncExecutor`. A pipe occupies a thread
+//! of the pool while it runs, but threads are shared with the application and reused instead of
+//! being spawned per pipe. All adapters have to be created within a runtime.
+//!
+
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+
+use crate::stream::flow::{AsyncExecutor, Graph};
+use crate::stream::{AnyArtifact, Component, ResOpt, Sink, Stream};
+use crate::{Error, Result};
+
+/// Pull the components of a blocking stream asynchronously
+pub struct AsyncStream {
+    receiver: mpsc::Receiver<ResOpt>,
+    task: JoinHandle<Result<Vec<Vec<AnyArtifact>>>>,
+}
+
+impl AsyncStream {
+    /// Read the stream ahead on the blocking thread pool, buffering up to `bound` components
+    pub fn new<T: Stream + 'static>(mut stream: T, bound: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(bound.max(1));
+        let task = task::spawn_blocking(move || {
+            loop {
+                let component = stream.next();
+                let done = !matches!(component, Ok(Some(_)));
+                if sender.blocking_send(component).is_err() {
+                    return Err(Error::ChannelError(
+                        "async stream dropped before the end of stream".to_string(),
+                    ));
+                }
+                if done {
+                    break;
+                }
+            }
+            stream.emit_artifacts()
+        });
+
+        AsyncStream { receiver, task }
+    }
+
+    /// Return the next stream component
+    pub async fn next(&mut self) -> ResOpt {
+        match self.receiver.recv().await {
+            Some(component) => component,
+            None => Ok(None),
+        }
+    }
+
+    /// Wait for the stream to terminate and release its artifacts
+    pub async fn artifacts(self) -> Result<Vec<Vec<AnyArtifact>>> {
+        drop(self.receiver);
+        self.task
+            .await
+            .map_err(|e| Error::StreamError(e.to_string()))?
+    }
+}
+
+/// Stream of the components sent through an `AsyncSink`
+struct ChannelStream {
+    receiver: mpsc::Receiver<ResOpt>,
+}
+
+impl Stream for ChannelStream {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        match self.receiver.blocking_recv() {
+            Some(component) => component,
+            None => Err(Error::ChannelError(
+                "async sink dropped before the end of stream".to_string(),
+            )),
+        }
+    }
+}
+
+/// Push components into a blocking sink asynchronously
+pub struct AsyncSink {
+    sender: mpsc::Sender<ResOpt>,
+    task: JoinHandle<Result<Vec<Vec<AnyArtifact>>>>,
+}
+
+impl AsyncSink {
+    /// Consume the components sent on the blocking thread pool, buffering up to `bound` components
+    pub fn new<T: Sink + 'static>(mut sink: T, bound: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(bound.max(1));
+        let task = task::spawn_blocking(move || sink.consume(&mut ChannelStream { receiver }));
+
+        AsyncSink { sender, task }
+    }
+
+    async fn push(&mut self, component: ResOpt) -> Result<()> {
+        self.sender
+            .send(component)
+            .await
+            .map_err(|_| Error::ChannelError("async sink terminated".to_string()))
+    }
+
+    /// Send a component, fails if the sink terminated, see `close` for the cause
+    pub async fn send(&mut self, component: Component) -> Result<()> {
+        self.push(Ok(Some(component))).await
+    }
+
+    /// Pass an error to the sink, which terminates it
+    pub async fn error(&mut self, error: Error) -> Result<()> {
+        self.push(Err(error)).await
+    }
+
+    /// Close the stream and wait for the sink to release its artifacts
+    pub async fn close(mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        // the sink may have terminated already, its result tells why
+        let closed = self.push(Ok(None)).await;
+        drop(self.sender);
+        let artifacts = self
+            .task
+            .await
+            .map_err(|e| Error::StreamError(e.to_string()))??;
+        closed.map(|_| artifacts)
+    }
+}
+
+/// Execute a flow graph from within a runtime
+pub async fn execute_graph(mut graph: Graph) -> Result<Graph> {
+    let mut executor = AsyncExecutor::current();
+    task::spawn_blocking(move || {
+        graph.execute(&mut executor)?;
+        Ok(graph)
+    })
+    .await
+    .map_err(|e| Error::FlowError(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Runtime;
+
+    use crate::dev_util::load_example;
+    use crate::stream::flow::Segment;
+    use crate::stream::report::{DocumentFormat, ReportSink};
+    use crate::stream::stats::Statistics;
+
+    use super::*;
+
+    #[test]
+    fn test_async_adapters() {
+        let runtime = Runtime::new().unwrap();
+
+        runtime.block_on(async {
+            let mut stream = AsyncStream::new(load_example(&["book", "L1.xes"]), 2);
+            let mut sink = AsyncSink::new(ReportSink::new(Vec::new(), DocumentFormat::Markdown), 2);
+            let mut components = 0;
+            while let Some(component) = stream.next().await.unwrap() {
+                sink.send(component).await.unwrap();
+                components += 1;
+            }
+            assert_eq!(components, 1 + 6);
+            assert!(stream.artifacts().await.is_ok());
+
+            let artifacts = sink.close().await.unwrap();
+            let statistics =
+                AnyArtifact::find::<Statistics>(&mut artifacts.iter().flatten()).unwrap();
+            assert_eq!(statistics.counts(), [6, 23, 23]);
+
+            // errors terminate the sink
+            let mut sink = AsyncSink::new(ReportSink::new(Vec::new(), DocumentFormat::Html), 2);
+            sink.error(Error::StreamError("broken".to_string()))
+                .await
+                .unwrap();
+            assert!(sink.close().await.is_err());
+
+            let path: String = join_static_str!("xes", "book", "L1.xes");
+            let mut graph = Graph::default();
+            graph
+                .source("Foo", Segment::new("XesReader").attribute(("path", path)))
+                .stream(Segment::new("Statistics").emit_artifact("stats"))
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            let graph = execute_graph(graph).await.unwrap();
+            let statistics = graph.artifacts["stats"].downcast_ref::<Statistics>();
+            assert_eq!(statistics.unwrap().counts(), [6, 23, 23]);
+        });
+    }
+}
@@ -0,0 +1,583 @@
+//! Rank the bottlenecks of a process
+//!
+//! Where does a process lose time? `BottleneckAnalysis` combines three perspectives on the traces
+//! of a stream:
+//! - the throughput time of each directly-follows transition between activities,
+//! - the decomposition of the time spent per activity into _waiting_ time (since the previous
+//!   event of the case) and _service_ time (from `start` to `complete` in `lifecycle:transition`)
+//!   and
+//! - the workload of each resource in `org:resource`, i.e. its events, cases and service time.
+//!
+//! Each perspective is ranked and cut to the top-k entries, together with the number of cases
+//! affected. The resulting `Bottlenecks` artifact renders into Markdown and JSON, the
+//! `BottleneckReport` sink writes it into a file once the stream is closed.
+//!
+//! Events without `time:timestamp` are skipped. Events without lifecycle information are
+//! considered complete, their time is waiting time only.
+//!
+
+use std::any::Any;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::Handler;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, AttributeValue, Component, Event,
+    Sink, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// Aggregated durations
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Measure {
+    pub observations: usize,
+    /// Number of cases with at least one observation
+    pub cases: usize,
+    /// Sum of durations in seconds
+    pub seconds: f64,
+    /// Longest duration in seconds
+    pub max_seconds: f64,
+    #[serde(skip)]
+    last_case: Option<usize>,
+}
+
+impl Measure {
+    fn observe(&mut self, case: usize, seconds: f64) {
+        if self.last_case != Some(case) {
+            self.last_case = Some(case);
+            self.cases += 1;
+        }
+        self.observations += 1;
+        self.seconds += seconds;
+        self.max_seconds = self.max_seconds.max(seconds);
+    }
+
+    /// Mean duration in seconds
+    pub fn mean(&self) -> f64 {
+        if self.observations > 0 {
+            self.seconds / self.observations as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Throughput time of a directly-follows transition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionTime {
+    pub from: String,
+    pub to: String,
+    pub time: Measure,
+}
+
+/// Decomposed time spent per activity
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActivityTime {
+    pub activity: String,
+    pub waiting: Measure,
+    pub service: Measure,
+}
+
+impl ActivityTime {
+    /// Mean waiting time plus mean service time in seconds
+    pub fn mean(&self) -> f64 {
+        self.waiting.mean() + self.service.mean()
+    }
+}
+
+/// Workload of a resource
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Workload {
+    pub resource: String,
+    pub events: usize,
+    pub cases: usize,
+    pub service_seconds: f64,
+    #[serde(skip)]
+    last_case: Option<usize>,
+}
+
+/// Top-k bottlenecks of a stream
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bottlenecks {
+    pub cases: usize,
+    /// Slowest transitions by mean throughput time
+    pub transitions: Vec<TransitionTime>,
+    /// Slowest activities by mean waiting plus service time
+    pub activities: Vec<ActivityTime>,
+    /// Busiest resources by number of events
+    pub resources: Vec<Workload>,
+}
+
+/// Human readable duration, e.g. `2d 3h` or `5m 12s`
+fn format_seconds(seconds: f64) -> String {
+    const UNITS: [(&str, i64); 4] = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let seconds = seconds.round() as i64;
+
+    let i = UNITS
+        .iter()
+        .position(|(_, size)| seconds >= *size)
+        .unwrap_or(UNITS.len() - 1);
+    let (unit, size) = UNITS[i];
+    match UNITS.get(i + 1) {
+        Some((minor, minor_size)) if seconds % size >= *minor_size => format!(
+            "{}{} {}{}",
+            seconds / size,
+            unit,
+            seconds % size / minor_size,
+            minor
+        ),
+        _ => format!("{}{}", seconds / size, unit),
+    }
+}
+
+impl Bottlenecks {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::ArtifactError(e.to_string()))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        writeln!(
+            md,
+            "# Bottleneck Report\n\n{} cases analyzed.\n",
+            self.cases
+        )
+        .unwrap();
+
+        md.push_str("## Slowest Transitions\n\n");
+        md.push_str("| Transition | Mean | Max | Observations | Cases |\n");
+        md.push_str("|---|---:|---:|---:|---:|\n");
+        for t in self.transitions.iter() {
+            writeln!(
+                md,
+                "| {} → {} | {} | {} | {} | {} |",
+                t.from,
+                t.to,
+                format_seconds(t.time.mean()),
+                format_seconds(t.time.max_seconds),
+                t.time.observations,
+                t.time.cases
+            )
+            .unwrap();
+        }
+
+        md.push_str("\n## Slowest Activities\n\n");
+        md.push_str("| Activity | Mean Waiting | Mean Service | Observations | Cases |\n");
+        md.push_str("|---|---:|---:|---:|---:|\n");
+        for a in self.activities.iter() {
+            writeln!(
+                md,
+                "| {} | {} | {} | {} | {} |",
+                a.activity,
+                format_seconds(a.waiting.mean()),
+                format_seconds(a.service.mean()),
+                a.waiting.observations.max(a.service.observations),
+                a.waiting.cases.max(a.service.cases)
+            )
+            .unwrap();
+        }
+
+        md.push_str("\n## Busiest Resources\n\n");
+        md.push_str("| Resource | Events | Cases | Service |\n");
+        md.push_str("|---|---:|---:|---:|\n");
+        for r in self.resources.iter() {
+            writeln!(
+                md,
+                "| {} | {} | {} | {} |",
+                r.resource,
+                r.events,
+                r.cases,
+                format_seconds(r.service_seconds)
+            )
+            .unwrap();
+        }
+
+        md
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Bottlenecks {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn string(event: &Event, key: &str) -> Option<String> {
+    match event.get_value(key) {
+        Some(AttributeValue::String(value)) | Some(AttributeValue::Id(value)) => {
+            Some(value.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn seconds(from: DateTime, to: DateTime) -> f64 {
+    to.signed_duration_since(from).num_milliseconds() as f64 / 1000.0
+}
+
+fn descending(a: f64, b: f64) -> Ordering {
+    b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+}
+
+/// Collect the durations of transitions, activities and resources from traces
+#[derive(Debug)]
+pub struct BottleneckAnalysis {
+    top_k: usize,
+    cases: usize,
+    transitions: BTreeMap<(String, String), Measure>,
+    activities: BTreeMap<String, ActivityTime>,
+    resources: BTreeMap<String, Workload>,
+}
+
+impl Default for BottleneckAnalysis {
+    fn default() -> Self {
+        BottleneckAnalysis {
+            top_k: 10,
+            cases: 0,
+            transitions: BTreeMap::new(),
+            activities: BTreeMap::new(),
+            resources: BTreeMap::new(),
+        }
+    }
+}
+
+impl BottleneckAnalysis {
+    /// Keep the `top_k` entries of each ranking
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    fn workload(&mut self, case: usize, event: &Event, service: f64) {
+        if let Some(resource) = string(event, "org:resource") {
+            let workload = self
+                .resources
+                .entry(resource.clone())
+                .or_insert_with(|| Workload {
+                    resource,
+                    ..Workload::default()
+                });
+            if workload.last_case != Some(case) {
+                workload.last_case = Some(case);
+                workload.cases += 1;
+            }
+            workload.events += 1;
+            workload.service_seconds += service;
+        }
+    }
+
+    fn activity(&mut self, activity: &str) -> &mut ActivityTime {
+        self.activities
+            .entry(activity.to_string())
+            .or_insert_with(|| ActivityTime {
+                activity: activity.to_string(),
+                ..ActivityTime::default()
+            })
+    }
+
+    /// The ranked bottlenecks observed so far
+    pub fn bottlenecks(&self) -> Bottlenecks {
+        let mut transitions: Vec<_> = self
+            .transitions
+            .iter()
+            .map(|((from, to), time)| TransitionTime {
+                from: from.clone(),
+                to: to.clone(),
+                time: time.clone(),
+            })
+            .collect();
+        transitions.sort_by(|a, b| descending(a.time.mean(), b.time.mean()));
+        transitions.truncate(self.top_k);
+
+        let mut activities: Vec<_> = self.activities.values().cloned().collect();
+        activities.sort_by(|a, b| descending(a.mean(), b.mean()));
+        activities.truncate(self.top_k);
+
+        let mut resources: Vec<_> = self.resources.values().cloned().collect();
+        resources.sort_by_key(|r| Reverse(r.events));
+        resources.truncate(self.top_k);
+
+        Bottlenecks {
+            cases: self.cases,
+            transitions,
+            activities,
+            resources,
+        }
+    }
+}
+
+impl Handler for BottleneckAnalysis {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let case = self.cases;
+        self.cases += 1;
+
+        let mut previous: Option<(String, DateTime)> = None;
+        let mut started: HashMap<String, VecDeque<DateTime>> = HashMap::new();
+
+        for event in trace.events.iter() {
+            let (activity, timestamp) = match (
+                string(event, "concept:name"),
+                event.get_value("time:timestamp"),
+            ) {
+                (Some(activity), Some(timestamp)) => (activity, *timestamp.try_date()?),
+                _ => continue,
+            };
+            let last = previous.as_ref().map(|(_, t)| *t);
+
+            match string(event, "lifecycle:transition").as_deref() {
+                Some("start") => {
+                    if let Some(last) = last {
+                        self.activity(&activity)
+                            .waiting
+                            .observe(case, seconds(last, timestamp));
+                    }
+                    started
+                        .entry(activity.clone())
+                        .or_default()
+                        .push_back(timestamp);
+                    self.workload(case, event, 0.0);
+                }
+                Some("complete") | None => {
+                    let start = started.get_mut(&activity).and_then(|s| s.pop_front());
+                    let service = match (start, last) {
+                        (Some(start), _) => {
+                            let service = seconds(start, timestamp);
+                            self.activity(&activity).service.observe(case, service);
+                            service
+                        }
+                        (None, Some(last)) => {
+                            self.activity(&activity)
+                                .waiting
+                                .observe(case, seconds(last, timestamp));
+                            0.0
+                        }
+                        (None, None) => 0.0,
+                    };
+                    self.workload(case, event, service);
+
+                    if let Some((from, last)) = &previous {
+                        self.transitions
+                            .entry((from.clone(), activity.clone()))
+                            .or_default()
+                            .observe(case, seconds(*last, timestamp));
+                    }
+                    previous = Some((activity, timestamp));
+                }
+                Some(_) => continue,
+            }
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.bottlenecks().into()])
+    }
+}
+
+/// Output format of the bottleneck report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Json,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "json" => Ok(ReportFormat::Json),
+            other => Err(Error::AttributeError(format!(
+                "invalid report format {:?}, expected \"markdown\" or \"json\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Write the bottlenecks of the stream into a report once it is closed
+///
+/// Additionally, the bottlenecks are emitted as an artifact.
+///
+pub struct BottleneckReport<W: io::Write> {
+    writer: W,
+    format: ReportFormat,
+    analysis: BottleneckAnalysis,
+}
+
+impl<W: io::Write> BottleneckReport<W> {
+    pub fn new(writer: W, format: ReportFormat) -> Self {
+        BottleneckReport {
+            writer,
+            format,
+            analysis: BottleneckAnalysis::default(),
+        }
+    }
+
+    /// Keep the `top_k` entries of each ranking
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.analysis = self.analysis.top_k(top_k);
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write + Send> Sink for BottleneckReport<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        if let Component::Trace(trace) = component {
+            self.analysis.on_trace(trace)?;
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        let bottlenecks = self.analysis.bottlenecks();
+        let report = match self.format {
+            ReportFormat::Markdown => bottlenecks.to_markdown(),
+            ReportFormat::Json => bottlenecks.to_json()?,
+        };
+        self.writer.write_all(report.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        self.analysis.release_artifacts()
+    }
+}
+
+impl PluginProvider for BottleneckReport<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "BottleneckReport",
+            "Rank the slowest transitions and activities and the busiest resources into a Markdown or JSON report",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the report, JSON if it ends with .json", AttributeType::String)
+                    .default_attr("top_k", "Number of entries per ranking", |k| {
+                        (k, 10).into()
+                    })
+                    .optional_attr(
+                        "format",
+                        "Either \"markdown\" or \"json\", derived from the path if omitted",
+                        AttributeType::String,
+                    ),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let format = match parameters.acquire_attribute("format") {
+                        Ok(format) => format.value.try_string()?.parse()?,
+                        Err(_) if path.ends_with(".json") => ReportFormat::Json,
+                        Err(_) => ReportFormat::Markdown,
+                    };
+                    let top_k = *parameters.acquire_attribute("top_k")?.value.try_int()?;
+                    if top_k < 1 {
+                        return Err(Error::AttributeError(format!(
+                            "top_k is expected to be positive, got {}",
+                            top_k
+                        )));
+                    }
+
+                    Ok(BottleneckReport::new(File::create(&path)?, format)
+                        .top_k(top_k as usize)
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+
+    use super::*;
+
+    fn event(activity: &str, lifecycle: &str, resource: &str, minute: u32) -> Event {
+        let mut event = Event::default();
+        event.attributes.insert(("concept:name", activity));
+        event.attributes.insert(("lifecycle:transition", lifecycle));
+        event.attributes.insert(("org:resource", resource));
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    #[test]
+    fn test_bottleneck_report() {
+        let mut buffer = Buffer::default();
+        for offset in [0, 30].iter() {
+            let trace = Trace {
+                events: vec![
+                    event("register", "complete", "ann", *offset),
+                    event("check", "start", "bob", offset + 10),
+                    event("check", "complete", "bob", offset + 12),
+                    event("pay", "complete", "ann", offset + 13),
+                ],
+                ..Trace::default()
+            };
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+
+        let mut report = BottleneckReport::new(Vec::new(), ReportFormat::Markdown).top_k(2);
+        let artifacts = report.consume(&mut buffer).unwrap();
+        let bottlenecks =
+            AnyArtifact::find::<Bottlenecks>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(bottlenecks.cases, 2);
+        let transition = &bottlenecks.transitions[0];
+        assert_eq!(
+            (transition.from.as_str(), transition.to.as_str()),
+            ("register", "check")
+        );
+        assert_eq!(transition.time.mean(), 720.0);
+        assert_eq!(transition.time.cases, 2);
+        assert_eq!(bottlenecks.transitions.len(), 2);
+
+        let check = &bottlenecks.activities[0];
+        assert_eq!(check.activity, "check");
+        assert_eq!((check.waiting.mean(), check.service.mean()), (600.0, 120.0));
+
+        let resources: Vec<_> = bottlenecks
+            .resources
+            .iter()
+            .map(|r| (r.resource.as_str(), r.events, r.cases, r.service_seconds))
+            .collect();
+        assert_eq!(resources, vec![("ann", 4, 2, 0.0), ("bob", 4, 2, 240.0)]);
+
+        let markdown = String::from_utf8(report.into_inner()).unwrap();
+        assert!(markdown.contains("| register → check | 12m | 12m | 2 | 2 |"));
+        assert!(bottlenecks
+            .to_json()
+            .unwrap()
+            .contains("\"max_seconds\": 720.0"));
+
+        assert_eq!(format_seconds(0.0), "0s");
+        assert_eq!(format_seconds(3_725.0), "1h 2m");
+        assert_eq!(format_seconds(90_000.0), "1d 1h");
+    }
+}
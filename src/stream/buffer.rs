@@ -4,17 +4,66 @@
 //! stream sink. Apart from that, a buffer is a pretty dumb data structure. If you're interested in
 //! a thread safe way of buffering an event stream, have a look at channels.
 //!
+//! A `SpillBuffer` holds up to a given number of components in memory and spills all others to
+//! temporary files, framed like the binary codec of TCP streams. Components are read back
+//! transparently in order, so buffering huge logs doesn't exhaust memory. Each file is removed once
+//! it's been read or the buffer is dropped.
+//!
 
+use std::any::Any;
 use std::collections::VecDeque;
 use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, Result};
 use crate::stream::log::Log;
-use crate::stream::{Component, ResOpt, Sink, Stream};
+use crate::stream::net::{Codec, Frame};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, Component, ResOpt, Sink, Stream};
+
+/// Serializable representation of a buffer, errors are reduced to their message
+#[derive(Serialize, Deserialize)]
+struct BufferRepr {
+    buffer: Vec<std::result::Result<Option<Component>, String>>,
+}
+
+impl From<Buffer> for BufferRepr {
+    fn from(buffer: Buffer) -> Self {
+        BufferRepr {
+            buffer: buffer
+                .buffer
+                .into_iter()
+                .map(|c| c.map_err(|e| e.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl From<BufferRepr> for Buffer {
+    fn from(repr: BufferRepr) -> Self {
+        Buffer {
+            buffer: repr
+                .buffer
+                .into_iter()
+                .map(|c| c.map_err(Error::StreamError))
+                .collect(),
+        }
+    }
+}
 
 /// Consumes a stream and stores it in memory for further processing.
 ///
-#[derive(Debug, Clone)]
+/// A buffer is an artifact, too. Hence, a pipe may publish a materialized sub-log. Note that, when
+/// serialized, errors are reduced to their message and restored as [`Error::StreamError`].
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "BufferRepr", into = "BufferRepr")]
 pub struct Buffer {
     buffer: VecDeque<ResOpt>,
 }
@@ -56,6 +105,17 @@ impl Sink for Buffer {
     }
 }
 
+#[typetag::serde]
+impl Artifact for Buffer {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 impl From<Log> for Buffer {
     fn from(log: Log) -> Self {
         let mut buffer = Buffer {
@@ -90,6 +150,222 @@ impl Buffer {
     }
 }
 
+// distinguishes spill files of a process
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Size in bytes after which a new spill file is started
+const SPILL_FILE_SIZE: usize = 1 << 26;
+
+// temporary file that components are spilled to, removed on drop
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    size: usize,
+    written: usize,
+    read: usize,
+}
+
+impl SpillFile {
+    fn create(directory: &Path) -> Result<Self> {
+        let path = directory.join(format!(
+            "promi_spill_{}_{}",
+            std::process::id(),
+            SPILL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let writer = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?,
+        );
+        let reader = BufReader::new(File::open(&path)?);
+
+        Ok(SpillFile {
+            path,
+            writer,
+            reader,
+            size: 0,
+            written: 0,
+            read: 0,
+        })
+    }
+
+    // frames of the binary stream codec, errors are reduced to their message
+    fn write(&mut self, component: ResOpt) -> Result<()> {
+        let frame = match component {
+            Ok(Some(component)) => Frame::Component(component),
+            Ok(None) => Frame::End,
+            Err(error) => Frame::Error(error.to_string()),
+        };
+        self.size += Codec::Binary.write_frame(&mut self.writer, &frame)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn read(&mut self) -> ResOpt {
+        self.writer.flush()?;
+        let frame = Codec::Binary.read_frame(&mut self.reader)?;
+        self.read += 1;
+
+        match frame {
+            Frame::Component(component) => Ok(Some(component)),
+            Frame::End => Ok(None),
+            Frame::Error(error) => Err(Error::StreamError(error)),
+        }
+    }
+
+    fn is_drained(&self) -> bool {
+        self.read == self.written
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Buffer that holds a bounded number of components in memory and spills the rest to disk
+///
+/// Once a component was spilled, all subsequent ones are spilled as well until the files are read
+/// to their end, such that the order of components is preserved. A new spill file is started every
+/// 64 MiB and removed once it's read, so disk usage stays bounded while components are pushed and
+/// read alternately.
+///
+#[derive(Debug)]
+pub struct SpillBuffer {
+    memory: VecDeque<ResOpt>,
+    capacity: usize,
+    directory: PathBuf,
+    files: VecDeque<SpillFile>,
+    file_size: usize,
+    spilled: usize,
+}
+
+impl SpillBuffer {
+    /// Create a buffer that holds up to `capacity` components in memory
+    pub fn new(capacity: usize) -> Self {
+        SpillBuffer {
+            memory: VecDeque::new(),
+            capacity,
+            directory: std::env::temp_dir(),
+            files: VecDeque::new(),
+            file_size: SPILL_FILE_SIZE,
+            spilled: 0,
+        }
+    }
+
+    /// Set the directory of the spill files, the system's temporary directory by default
+    pub fn directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = directory.into();
+        self
+    }
+
+    /// Total number of buffered components
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of components on disk
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    pub fn push(&mut self, component: ResOpt) -> Result<()> {
+        if self.spilled == 0 && self.memory.len() < self.capacity {
+            self.memory.push_back(component);
+            return Ok(());
+        }
+
+        if self.files.back().is_none_or(|f| f.size >= self.file_size) {
+            self.files.push_back(SpillFile::create(&self.directory)?);
+        }
+        self.files.back_mut().unwrap().write(component)?;
+        self.spilled += 1;
+        Ok(())
+    }
+}
+
+impl Stream for SpillBuffer {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if let Some(component) = self.memory.pop_front() {
+            return component;
+        }
+
+        match self.files.front_mut() {
+            Some(file) if self.spilled > 0 => {
+                let component = file.read();
+                self.spilled -= 1;
+                if file.is_drained() {
+                    self.files.pop_front();
+                }
+                component
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Sink for SpillBuffer {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.push(Ok(Some(component)))
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.push(Err(error))
+    }
+}
+
+/// Sink that publishes the consumed stream as [`Buffer`] artifact
+#[derive(Debug, Default)]
+pub struct BufferSink {
+    buffer: Buffer,
+}
+
+impl Sink for BufferSink {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.buffer.on_component(component)
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.buffer.on_error(error)
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![mem::take(&mut self.buffer).into()])
+    }
+}
+
+impl PluginProvider for BufferSink {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "BufferSink",
+            "A sink that publishes the consumed stream as buffer artifact",
+            Factory::new(
+                Declaration::default(),
+                FactoryType::Sink(Box::new(|_| Ok(Box::new(BufferSink::default())))),
+            ),
+        )]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dev_util::load_example;
@@ -134,4 +410,85 @@ mod tests {
 
         assert!(buffer_b.consume(&mut buffer_a).is_err());
     }
+
+    #[test]
+    fn test_buffer_artifact() {
+        let mut buffer = load_example(&["book", "L1.xes"]);
+        buffer.push(Err(Error::StreamError("broken".into())));
+
+        let artifact: stream::AnyArtifact = buffer.into();
+        let json = serde_json::to_string(&artifact).unwrap();
+        let artifact: stream::AnyArtifact = serde_json::from_str(&json).unwrap();
+
+        let mut buffer = artifact.downcast_ref::<Buffer>().unwrap().clone();
+        assert_eq!(buffer.len(), 8);
+
+        let mut log = Log::default();
+        assert!(log.consume(&mut buffer).is_err());
+        assert_eq!(log.traces.len(), 6);
+
+        let artifacts = BufferSink::default()
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let buffer = stream::AnyArtifact::find::<Buffer>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(buffer.len(), 7);
+    }
+
+    #[test]
+    fn test_spill_buffer() {
+        let json = |c: Result<stream::Component>| serde_json::to_string(&c.unwrap()).unwrap();
+        let expected: Vec<_> = load_example(&["book", "L1.xes"]).iter().map(json).collect();
+
+        let mut buffer = SpillBuffer::new(2);
+        buffer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        assert_eq!((buffer.len(), buffer.spilled()), (7, 5));
+        let path = buffer.files[0].path.clone();
+        assert!(path.exists());
+
+        // components are read back in order, the file is removed once drained
+        let mut components: Vec<_> = buffer.iter().take(3).map(json).collect();
+        buffer
+            .push(Err(Error::StreamError("broken".into())))
+            .unwrap();
+        assert_eq!(buffer.len(), 5);
+        for _ in 0..4 {
+            components.push(json(buffer.next().transpose().unwrap()));
+        }
+        assert_eq!(components, expected);
+        assert!(matches!(buffer.next(), Err(Error::StreamError(m)) if m.contains("broken")));
+        assert!(buffer.next().unwrap().is_none());
+        assert!(!path.exists());
+
+        // in memory only
+        let mut buffer = SpillBuffer::new(10);
+        buffer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        assert_eq!(buffer.spilled(), 0);
+        assert!(buffer.files.is_empty());
+
+        // dropping the buffer removes the file
+        let mut buffer = SpillBuffer::new(0);
+        buffer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let path = buffer.files[0].path.clone();
+        drop(buffer);
+        assert!(!path.exists());
+
+        // files are rotated, such that read components don't occupy disk space
+        let mut buffer = SpillBuffer::new(0);
+        buffer.file_size = 1;
+        let mut stream = load_example(&["book", "L1.xes"]);
+        buffer.push(stream.next()).unwrap();
+        let mut components = Vec::new();
+        while let Some(component) = buffer.next().unwrap() {
+            buffer.push(stream.next()).unwrap();
+            assert!(buffer.files.len() <= 1);
+            components.push(json(Ok(component)));
+        }
+        assert_eq!(components, expected);
+    }
 }
@@ -0,0 +1,281 @@
+//! Canonicalization of activity labels
+//!
+//! Labels that were typed by hand or exported by different systems often differ in details that
+//! don't carry meaning, e.g. `"Approve "` and `"approve"`. Discovery treats them as different
+//! activities, which splits variants and directly-follows relations spuriously. The `Canonicalize`
+//! handler rewrites the activity attribute of each event into a canonical form before such
+//! analyses. Each step can be switched off:
+//! - trimming of leading and trailing whitespace,
+//! - collapsing inner whitespace into a single space,
+//! - case folding into lower case and
+//! - unicode normalization into NFC, such that composed and decomposed characters match.
+//!
+//! The mapping from raw to canonical labels is released as an `ActivityMapping` artifact, such that
+//! merged labels can be reviewed.
+//!
+
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeValue, Event, Stream};
+use crate::Result;
+
+/// Steps of the canonicalization, all enabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Canonicalization {
+    pub trim: bool,
+    pub collapse_whitespace: bool,
+    pub case_fold: bool,
+    pub nfc: bool,
+}
+
+impl Default for Canonicalization {
+    fn default() -> Self {
+        Canonicalization {
+            trim: true,
+            collapse_whitespace: true,
+            case_fold: true,
+            nfc: true,
+        }
+    }
+}
+
+impl Canonicalization {
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> Self {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+
+    pub fn case_fold(mut self, case_fold: bool) -> Self {
+        self.case_fold = case_fold;
+        self
+    }
+
+    pub fn nfc(mut self, nfc: bool) -> Self {
+        self.nfc = nfc;
+        self
+    }
+
+    /// Canonical form of a label
+    pub fn apply(&self, label: &str) -> String {
+        let mut label = if self.nfc {
+            label.nfc().collect()
+        } else {
+            label.to_string()
+        };
+
+        if self.trim {
+            label = label.trim().to_string();
+        }
+        if self.collapse_whitespace {
+            let mut collapsed = String::with_capacity(label.len());
+            let mut whitespace = false;
+            for c in label.chars() {
+                if c.is_whitespace() {
+                    whitespace = true;
+                    continue;
+                }
+                if whitespace && !collapsed.is_empty() {
+                    collapsed.push(' ');
+                }
+                whitespace = false;
+                collapsed.push(c);
+            }
+            // whitespace at the ends is kept unless trimmed
+            if whitespace {
+                collapsed.push(' ');
+            }
+            if label.starts_with(char::is_whitespace) {
+                collapsed.insert(0, ' ');
+            }
+            label = collapsed;
+        }
+        if self.case_fold {
+            label = label.to_lowercase();
+        }
+
+        label
+    }
+}
+
+/// Raw activity labels and their canonical form
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActivityMapping {
+    /// Canonical form by raw label
+    pub labels: BTreeMap<String, String>,
+}
+
+impl ActivityMapping {
+    /// Raw labels by canonical form, for canonical labels that merge several raw labels
+    pub fn merged(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (raw, canonical) in self.labels.iter() {
+            groups.entry(canonical).or_default().push(raw);
+        }
+        groups.retain(|_, raw| raw.len() > 1);
+        groups
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ActivityMapping {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Rewrite the activity attribute of events into its canonical form
+pub struct Canonicalize {
+    key: String,
+    canonicalization: Canonicalization,
+    mapping: ActivityMapping,
+}
+
+impl Default for Canonicalize {
+    fn default() -> Self {
+        Self::new(Canonicalization::default())
+    }
+}
+
+impl Canonicalize {
+    pub fn new(canonicalization: Canonicalization) -> Self {
+        Canonicalize {
+            key: "concept:name".to_string(),
+            canonicalization,
+            mapping: ActivityMapping::default(),
+        }
+    }
+
+    /// Take activities from the given attribute
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Mapping recorded so far
+    pub fn mapping(&self) -> &ActivityMapping {
+        &self.mapping
+    }
+}
+
+impl Handler for Canonicalize {
+    fn on_event(&mut self, mut event: Event, _in_trace: bool) -> Result<Option<Event>> {
+        if let Some(AttributeValue::String(label)) = event.attributes.get_value_mut(&self.key) {
+            let canonical = match self.mapping.labels.get(label.as_str()) {
+                Some(canonical) => canonical.clone(),
+                None => {
+                    let canonical = self.canonicalization.apply(label);
+                    self.mapping
+                        .labels
+                        .insert(label.to_string(), canonical.clone());
+                    canonical
+                }
+            };
+            *label = canonical.into();
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.mapping.clone().into()])
+    }
+}
+
+impl PluginProvider for Canonicalize {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Canonicalize",
+            "Canonicalize activity labels by trimming, collapsing whitespace, case folding and NFC normalization",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream whose activities are canonicalized")
+                    .default_attr("key", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    }),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut canonicalization = Canonicalization::default();
+                    for (name, step) in [
+                        ("trim", &mut canonicalization.trim),
+                        ("collapse_whitespace", &mut canonicalization.collapse_whitespace),
+                        ("case_fold", &mut canonicalization.case_fold),
+                        ("nfc", &mut canonicalization.nfc),
+                    ] {
+                        if let Ok(attribute) = parameters.acquire_attribute(name) {
+                            *step = *attribute.value.try_boolean()?;
+                        }
+                    }
+
+                    let canonicalize = Canonicalize::new(canonicalization)
+                        .key(parameters.acquire_attribute("key")?.value.try_string()?);
+                    Ok(
+                        Observer::from((parameters.acquire_stream("inner")?, canonicalize))
+                            .into_boxed(),
+                    )
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::variant::{VariantCollector, Variants};
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let all = Canonicalization::default();
+        assert_eq!(all.apply("  Approve\t  Order "), "approve order");
+        assert_eq!(all.apply("Cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(all.trim(false).apply(" a  b "), " a b ");
+        assert_eq!(all.collapse_whitespace(false).apply(" A  b "), "a  b");
+        assert_eq!(all.case_fold(false).apply("A"), "A");
+        assert_eq!(all.nfc(false).apply("e\u{301}"), "e\u{301}");
+
+        let mut buffer = Buffer::default();
+        for labels in [["register", "Approve "], ["Register", "approve"]].iter() {
+            let mut trace = Trace::default();
+            for label in labels.iter() {
+                let mut event = Event::default();
+                event.attributes.insert(("concept:name", *label));
+                trace.events.push(event);
+            }
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+
+        let mut observer = VariantCollector::default()
+            .into_observer(Canonicalize::default().into_observer(buffer));
+        let artifacts = consume(&mut observer).unwrap();
+
+        let variants = AnyArtifact::find::<Variants>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(variants.variants.len(), 1);
+        assert_eq!(variants.variants[0].activities, ["register", "approve"]);
+
+        let mapping =
+            AnyArtifact::find::<ActivityMapping>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(mapping.labels.len(), 4);
+        assert_eq!(mapping.merged()["approve"], ["Approve ", "approve"]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Self-test against a XES certification corpus
+//!
+//! The XES standard defines certification levels (A1, B1, ...) for tools that read and write XES.
+//! The corpus of test files is not shipped with promi, `certify` runs against a local copy that is
+//! expected to be laid out as one directory per level:
+//!
+//! ```text
+//! corpus/
+//! ├── A1/
+//! │   ├── basic.xes          documents that have to be accepted
+//! │   └── invalid/
+//! │       └── broken.xes     documents that have to be rejected
+//! └── B1/
+//!     └── ...
+//! ```
+//!
+//! A document is accepted if it is parsed and validated without error and survives a round trip
+//! through `XesWriter` and `XesReader` without semantic difference (timestamps are compared with
+//! millisecond precision). A document is rejected if either parsing or validation fails. A level
+//! is passed if all of its cases pass; the resulting `CertificationReport` lists all cases such
+//! that regressions can be tracked over time.
+//!
+
+use std::any::Any;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::compare::{compare_streams, CompareOptions};
+use crate::stream::observer::Handler;
+use crate::stream::validator::Validator;
+use crate::stream::void::consume;
+use crate::stream::xes::{XesReader, XesWriter};
+use crate::stream::{Artifact, Sink};
+use crate::Result;
+
+/// Outcome of a single test document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub path: String,
+    /// Whether the document has to be accepted
+    pub valid: bool,
+    pub passed: bool,
+    /// Reason of the failure or rejection, if any
+    pub message: Option<String>,
+}
+
+/// Outcomes of all documents of a level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelReport {
+    pub level: String,
+    pub cases: Vec<CaseReport>,
+}
+
+impl LevelReport {
+    /// Number of cases passed
+    pub fn passed(&self) -> usize {
+        self.cases.iter().filter(|c| c.passed).count()
+    }
+
+    /// Whether all cases are passed
+    pub fn is_passed(&self) -> bool {
+        self.passed() == self.cases.len()
+    }
+}
+
+/// Conformance of promi by certification level
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CertificationReport {
+    /// Levels in lexicographical order
+    pub levels: Vec<LevelReport>,
+}
+
+impl CertificationReport {
+    /// Levels whose cases are all passed
+    pub fn passed_levels(&self) -> Vec<&str> {
+        self.levels
+            .iter()
+            .filter(|l| l.is_passed())
+            .map(|l| l.level.as_str())
+            .collect()
+    }
+
+    /// Cases that failed, over all levels
+    pub fn failures(&self) -> Vec<&CaseReport> {
+        self.levels
+            .iter()
+            .flat_map(|l| l.cases.iter())
+            .filter(|c| !c.passed)
+            .collect()
+    }
+}
+
+impl fmt::Display for CertificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for level in self.levels.iter() {
+            writeln!(
+                f,
+                "{}: {}/{} passed{}",
+                level.level,
+                level.passed(),
+                level.cases.len(),
+                if level.is_passed() { "" } else { " (failed)" }
+            )?;
+            for case in level.cases.iter().filter(|c| !c.passed) {
+                writeln!(
+                    f,
+                    "  {}: {}",
+                    case.path,
+                    case.message.as_deref().unwrap_or("unexpectedly accepted")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Artifact for CertificationReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Parse, validate and round trip a document
+fn accept(path: &Path) -> Result<()> {
+    let reader = XesReader::from(BufReader::new(File::open(path)?));
+    consume(&mut Validator::default().into_observer(reader))?;
+
+    let mut writer = XesWriter::new(Vec::new());
+    writer.consume(&mut XesReader::from(BufReader::new(File::open(path)?)))?;
+    let serialized = writer.into_inner();
+
+    compare_streams(
+        &mut XesReader::from(BufReader::new(File::open(path)?)),
+        &mut XesReader::from(&serialized[..]),
+        &CompareOptions::default().timestamp_precision(Duration::milliseconds(1)),
+    )
+}
+
+/// XES documents in a directory, sorted by name
+fn documents(directory: &Path) -> Result<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut documents = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "xes") {
+            documents.push(path);
+        }
+    }
+    documents.sort();
+    Ok(documents)
+}
+
+/// Run all test documents of a level directory
+pub fn certify_level(directory: &Path) -> Result<LevelReport> {
+    let mut cases = Vec::new();
+
+    for (valid, path) in documents(directory)?.into_iter().map(|p| (true, p)).chain(
+        documents(&directory.join("invalid"))?
+            .into_iter()
+            .map(|p| (false, p)),
+    ) {
+        let outcome = accept(&path);
+        cases.push(CaseReport {
+            path: path.to_string_lossy().to_string(),
+            valid,
+            passed: outcome.is_ok() == valid,
+            message: outcome.err().map(|e| e.to_string()),
+        });
+    }
+
+    Ok(LevelReport {
+        level: directory
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        cases,
+    })
+}
+
+/// Run the corpus rooted at the given directory, each subdirectory is a level
+pub fn certify<P: AsRef<Path>>(root: P) -> Result<CertificationReport> {
+    let mut directories = Vec::new();
+    for entry in fs::read_dir(root.as_ref())? {
+        let path = entry?.path();
+        if path.is_dir() {
+            directories.push(path);
+        }
+    }
+    directories.sort();
+
+    Ok(CertificationReport {
+        levels: directories
+            .iter()
+            .map(|d| certify_level(d))
+            .collect::<Result<Vec<_>>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certify() {
+        let root = std::env::temp_dir().join(format!("promi_certify_{}", std::process::id()));
+        let copy = |source: &[&str], target: &[&str]| {
+            let target = target.iter().fold(root.clone(), |p, s| p.join(s));
+            fs::create_dir_all(target.parent().unwrap()).unwrap();
+            fs::copy(join_static!("xes", source[0], source[1]), target).unwrap();
+        };
+
+        copy(
+            &["correct", "log_correct_attributes.xes"],
+            &["A1", "log.xes"],
+        );
+        copy(&["correct", "int_correct_value.xes"], &["A1", "int.xes"]);
+        copy(
+            &["non_parsing", "broken_xml.xes"],
+            &["A1", "invalid", "broken.xes"],
+        );
+        copy(
+            &["correct", "extension_standard.xes"],
+            &["B1", "extension.xes"],
+        );
+        copy(
+            &["correct", "date_correct_value.xes"],
+            &["B1", "invalid", "date.xes"],
+        );
+
+        let report = certify(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let levels: Vec<_> = report
+            .levels
+            .iter()
+            .map(|l| (l.level.as_str(), l.passed(), l.cases.len()))
+            .collect();
+        assert_eq!(levels, vec![("A1", 3, 3), ("B1", 1, 2)]);
+        assert_eq!(report.passed_levels(), vec!["A1"]);
+        assert_eq!(report.failures().len(), 1);
+        assert!(report.failures()[0].path.ends_with("date.xes"));
+        assert!(report.to_string().contains("B1: 1/2 passed (failed)"));
+    }
+}
@@ -1,9 +1,11 @@
 //! Thread safe channels to enable secure, concurrent communication
 //!
 
+use std::any::Any;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
+use std::marker::PhantomData;
 use std::mem;
 use std::sync::mpsc::{
     channel as async_channel, sync_channel, Receiver, Sender as AsyncSender, SyncSender,
@@ -158,6 +160,91 @@ pub fn stream_channel(bound: Option<usize>) -> StreamChannel {
     channel(bound)
 }
 
+/// Type erased payload of a data channel
+pub type Payload = Box<dyn Any + Send>;
+
+/// Sending endpoint of a data channel
+pub type DataSender = Sender<Payload>;
+
+/// Receiving endpoint of a data channel
+pub type DataReceiver = Receiver<Payload>;
+
+/// Data channel endpoints that are handed over to a plugin
+///
+/// Data channels let plugins exchange arbitrary side-data (e.g. a model that is updated
+/// incrementally) while a flow graph is executed. Unlike artifacts, payloads may be sent at any
+/// time and any number of times.
+///
+#[derive(Default)]
+pub struct DataEndpoints {
+    pub senders: Vec<DataSender>,
+    pub receivers: Vec<DataReceiver>,
+}
+
+/// Data channel sender that only accepts payloads of type `T`
+pub struct TypedSender<T> {
+    sender: DataSender,
+    _type: PhantomData<T>,
+}
+
+impl<T: Send + 'static> TypedSender<T> {
+    /// Send item to channel
+    pub fn send(&self, t: T) -> Result<()> {
+        self.sender.send(Box::new(t))
+    }
+}
+
+impl<T> From<DataSender> for TypedSender<T> {
+    fn from(sender: DataSender) -> Self {
+        TypedSender {
+            sender,
+            _type: PhantomData,
+        }
+    }
+}
+
+/// Data channel receiver that expects payloads of type `T`
+pub struct TypedReceiver<T> {
+    receiver: DataReceiver,
+    _type: PhantomData<T>,
+}
+
+impl<T: Send + 'static> TypedReceiver<T> {
+    fn downcast(payload: Payload) -> Result<T> {
+        payload.downcast::<T>().map(|t| *t).map_err(|_| {
+            Error::ChannelError(format!(
+                "payload is not of type {}",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+
+    /// Block until an item is received, returns `None` if all senders are gone
+    pub fn recv(&self) -> Result<Option<T>> {
+        match self.receiver.recv() {
+            Ok(payload) => Ok(Some(Self::downcast(payload)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Receive an item if available without blocking
+    pub fn try_recv(&self) -> Result<Option<T>> {
+        match self.receiver.try_recv() {
+            Ok(payload) => Ok(Some(Self::downcast(payload)?)),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl<T> From<DataReceiver> for TypedReceiver<T> {
+    fn from(receiver: DataReceiver) -> Self {
+        TypedReceiver {
+            receiver,
+            _type: PhantomData,
+        }
+    }
+}
+
 enum NameSpaceEntry<T, G> {
     Entry(T),
     Generation(G),
@@ -290,6 +377,31 @@ impl<T: Send + 'static, G: Copy + Eq + Hash> ChannelNameSpace<T, G> {
             .map_err(|_| Error::ChannelError(format!("receiver {:?} was already acquired", key)))
     }
 
+    /// Provide the sender of a channel whose receiver is held elsewhere
+    ///
+    /// The receiver counts as acquired by the current generation.
+    ///
+    pub fn provide_sender(&mut self, key: &str, sender: Sender<T>) -> Result<()> {
+        let generation = self
+            .generation
+            .ok_or_else(|| Error::ChannelError("no generation set".into()))?;
+        if self.channels.contains_key(key) {
+            return Err(Error::ChannelError(format!(
+                "channel {:?} already exists",
+                key
+            )));
+        }
+
+        self.channels.insert(
+            key.to_string(),
+            (
+                NameSpaceEntry::Entry(sender),
+                NameSpaceEntry::Generation(generation),
+            ),
+        );
+        Ok(())
+    }
+
     /// Acquire an iterator over all remaining receivers
     pub fn acquire_remaining_receivers(
         &mut self,
@@ -308,6 +420,11 @@ impl<T: Send + 'static, G: Copy + Eq + Hash> ChannelNameSpace<T, G> {
         Ok(receivers.into_iter())
     }
 
+    /// Bound of the channels created, `None` if unbounded
+    pub fn bound(&self) -> Option<usize> {
+        self.bound
+    }
+
     /// Increment generation
     pub fn set_generation(&mut self, generation: G) {
         self.generation = Some(generation);
@@ -546,4 +663,22 @@ mod tests {
 
         assert_eq!(receiver.recv().unwrap(), 1337);
     }
+
+    #[test]
+    fn test_typed_channel() {
+        let (sender, receiver) = channel::<Payload>(None);
+        let sender = TypedSender::<Vec<usize>>::from(sender);
+        let receiver = TypedReceiver::<Vec<usize>>::from(receiver);
+
+        assert!(receiver.try_recv().unwrap().is_none());
+
+        sender.send(vec![1, 2, 3]).unwrap();
+        assert_eq!(receiver.recv().unwrap(), Some(vec![1, 2, 3]));
+
+        sender.sender.send(Box::new("foo")).unwrap();
+        assert!(receiver.recv().is_err());
+
+        drop(sender);
+        assert!(receiver.recv().unwrap().is_none());
+    }
 }
@@ -0,0 +1,228 @@
+//! Inject failures into event streams
+//!
+//! Pipelines are supposed to deal with broken inputs, lost connections and the like. The
+//! `ChaosStream` allows to test that: it passes its inner stream through, but fails after a given
+//! number of components (or at the end of a shorter stream), at random with a given probability
+//! per component, or when emitting artifacts. All failures are `StreamError`s or `ArtifactError`s that name the component at which
+//! they occurred.
+//!
+
+use rand::{random, Rng};
+use rand_pcg::Pcg64;
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, AttributeType, ResOpt, Stream};
+use crate::{Error, Result};
+
+/// Stream that fails on purpose
+pub struct ChaosStream<T: Stream> {
+    stream: T,
+    count: usize,
+    fail_after: Option<usize>,
+    probability: f64,
+    rng: Pcg64,
+    fail_on_artifacts: bool,
+}
+
+impl<T: Stream> ChaosStream<T> {
+    /// Create a stream that passes its inner stream through until told otherwise
+    pub fn new(stream: T) -> Self {
+        ChaosStream {
+            stream,
+            count: 0,
+            fail_after: None,
+            probability: 0.0,
+            rng: Pcg64::new(random(), 0),
+            fail_on_artifacts: false,
+        }
+    }
+
+    /// Fail once `n` components were passed through, streams that end earlier fail at their end
+    pub fn fail_after(mut self, n: usize) -> Self {
+        self.fail_after = Some(n);
+        self
+    }
+
+    /// Fail on each component with the given probability
+    ///
+    /// # Panics
+    ///
+    /// Panics if `probability` is not within [0, 1].
+    pub fn random(mut self, probability: f64, random_state: Option<u128>) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability is expected to be within [0, 1], got {}",
+            probability
+        );
+        self.probability = probability;
+        self.rng = Pcg64::new(random_state.unwrap_or_else(random), 0);
+        self
+    }
+
+    /// Fail when emitting artifacts
+    pub fn fail_on_artifacts(mut self, fail: bool) -> Self {
+        self.fail_on_artifacts = fail;
+        self
+    }
+}
+
+impl<T: Stream> Stream for ChaosStream<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        let component = match (self.stream.next()?, self.fail_after) {
+            (Some(component), _) => component,
+            (None, None) => return Ok(None),
+            (None, Some(_)) => {
+                return Err(Error::StreamError(format!(
+                    "failed on purpose at the end of the stream after {} components",
+                    self.count
+                )))
+            }
+        };
+
+        if self.fail_after.is_some_and(|n| self.count >= n) {
+            return Err(Error::StreamError(format!(
+                "failed on purpose after {} components",
+                self.count
+            )));
+        }
+        if self.probability > 0.0 && self.rng.gen_bool(self.probability) {
+            return Err(Error::StreamError(format!(
+                "failed on purpose at random on component {}",
+                self.count + 1
+            )));
+        }
+
+        self.count += 1;
+        Ok(Some(component))
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        if self.fail_on_artifacts {
+            return Err(Error::ArtifactError(format!(
+                "failed on purpose on emitting artifacts after {} components",
+                self.count
+            )));
+        }
+        Ok(vec![])
+    }
+}
+
+impl PluginProvider for ChaosStream<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "ChaosStream",
+            "Fail on purpose after a number of components, at random or on emitting artifacts",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to pass through")
+                    .default_attr(
+                        "probability",
+                        "Probability to fail on each component",
+                        |k| (k, 0.0).into(),
+                    )
+                    .default_attr(
+                        "fail_on_artifacts",
+                        "Whether to fail when emitting artifacts",
+                        |k| (k, false).into(),
+                    )
+                    .optional_attr("seed", "Seed of the random failures", AttributeType::Int)
+                    .optional_attr(
+                        "fail_after",
+                        "Fail after this number of components or at the end of the stream",
+                        AttributeType::Int,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let probability = parameters
+                        .acquire_attribute("probability")?
+                        .value
+                        .try_number()?;
+                    if !(0.0..=1.0).contains(&probability) {
+                        return Err(Error::AttributeError(format!(
+                            "probability is expected to be within [0, 1], got {}",
+                            probability
+                        )));
+                    }
+                    let random_state = match parameters.acquire_attribute("seed") {
+                        Ok(s) => Some(*(s.value.try_int()?) as u128),
+                        _ => None,
+                    };
+
+                    let mut chaos = ChaosStream::new(parameters.acquire_stream("inner")?)
+                        .random(probability, random_state)
+                        .fail_on_artifacts(
+                            *parameters
+                                .acquire_attribute("fail_on_artifacts")?
+                                .value
+                                .try_boolean()?,
+                        );
+                    if let Ok(n) = parameters.acquire_attribute("fail_after") {
+                        chaos = chaos.fail_after((*n.value.try_int()?).max(0) as usize);
+                    }
+
+                    Ok(chaos.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    #[test]
+    fn test_fail_after() {
+        // L1 consists of a meta component and six traces, it fails at its end at the latest
+        for n in 0..10 {
+            let mut chaos = ChaosStream::new(load_example(&["book", "L1.xes"])).fail_after(n);
+            match consume(&mut chaos) {
+                Err(Error::StreamError(_)) => assert_eq!(chaos.count, n.min(7)),
+                other => panic!("expected stream error, got {:?}", other),
+            }
+        }
+
+        let mut chaos = ChaosStream::new(load_example(&["book", "L1.xes"]));
+        assert!(consume(&mut chaos).is_ok());
+
+        let mut chaos = ChaosStream::new(load_example(&["book", "L1.xes"])).fail_on_artifacts(true);
+        match consume(&mut chaos) {
+            Err(Error::ArtifactError(_)) => (),
+            other => panic!("expected artifact error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_random() {
+        let failures = |seed| {
+            let mut chaos =
+                ChaosStream::new(load_example(&["book", "L1.xes"])).random(0.5, Some(seed));
+            consume(&mut chaos).err().map(|_| chaos.count)
+        };
+
+        // seeded failures are reproducible
+        let outcomes: Vec<_> = (0..20).map(failures).collect();
+        assert_eq!(outcomes, (0..20).map(failures).collect::<Vec<_>>());
+        assert!(outcomes.iter().any(|o| o.is_some()));
+
+        let mut chaos = ChaosStream::new(load_example(&["book", "L1.xes"])).random(0.0, None);
+        assert!(consume(&mut chaos).is_ok());
+        assert!(std::panic::catch_unwind(|| {
+            ChaosStream::new(load_example(&["book", "L1.xes"])).random(-0.1, None)
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Sources of the current time
+//!
+//! Components that depend on time, e.g. the timeout of open cases or the export time recorded as
+//! provenance, ask a `Clock` instead of the system, which makes the notion of time explicit:
+//! - `SystemClock` tells the wall-clock time,
+//! - `EventClock` tells the event time, i.e. the latest `time:timestamp` observed so far and
+//! - `MockClock` tells whatever it was set to, which keeps tests deterministic.
+//!
+//! Clocks are injected as `SharedClock`, such that a test keeps control over a mock clock that a
+//! component holds. Components feed the events they see into `Clock::observe`, which only moves
+//! event clocks.
+//!
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, FixedOffset, Utc};
+
+use crate::stream::{AttributeContainer, Event};
+use crate::{DateTime, Result};
+
+/// Tells the current time
+pub trait Clock: Debug + Send + Sync {
+    /// Current time, if known
+    fn now(&self) -> Option<DateTime>;
+
+    /// Take note of an event that passed
+    fn observe(&self, _event: &Event) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Handle of a clock that is shared among components
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Wall-clock time in UTC
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Option<DateTime> {
+        Some(Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap()))
+    }
+}
+
+/// Event time, i.e. the latest timestamp observed
+///
+/// The time is unknown until the first event with a `time:timestamp` is observed and never goes
+/// back, such that late events don't set back the clock.
+///
+#[derive(Debug, Default)]
+pub struct EventClock {
+    latest: Mutex<Option<DateTime>>,
+}
+
+impl EventClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(EventClock::default())
+    }
+}
+
+impl Clock for EventClock {
+    fn now(&self) -> Option<DateTime> {
+        *self.latest.lock().unwrap()
+    }
+
+    fn observe(&self, event: &Event) -> Result<()> {
+        if let Some(timestamp) = event.get_value("time:timestamp") {
+            let timestamp = *timestamp.try_date()?;
+            let mut latest = self.latest.lock().unwrap();
+            if latest.is_none_or(|latest| latest < timestamp) {
+                *latest = Some(timestamp);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Manually controlled time
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Mutex<Option<DateTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime) -> Self {
+        MockClock {
+            now: Mutex::new(Some(now)),
+        }
+    }
+
+    /// Set the current time
+    pub fn set(&self, now: DateTime) {
+        *self.now.lock().unwrap() = Some(now);
+    }
+
+    /// Move the current time, if known, by the given duration
+    pub fn advance(&self, duration: Duration) {
+        if let Some(now) = self.now.lock().unwrap().as_mut() {
+            *now += duration;
+        }
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Option<DateTime> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(minute: u32) -> Event {
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        let mut event = Event::default();
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    #[test]
+    fn test_clocks() {
+        assert!(SystemClock.now().is_some());
+
+        let clock = EventClock::default();
+        assert_eq!(clock.now(), None);
+        clock.observe(&Event::default()).unwrap();
+        assert_eq!(clock.now(), None);
+        for minute in [3, 7, 5].iter() {
+            clock.observe(&event(*minute)).unwrap();
+        }
+        assert_eq!(
+            clock.now(),
+            event(7)
+                .get_value("time:timestamp")
+                .map(|t| *t.try_date().unwrap())
+        );
+
+        let start = DateTime::parse_from_rfc3339("2020-01-01T00:00:00+00:00").unwrap();
+        let clock = Arc::new(MockClock::new(start));
+        let shared: SharedClock = clock.clone();
+        clock.observe(&event(30)).unwrap();
+        clock.advance(Duration::minutes(5));
+        assert_eq!(shared.now(), Some(start + Duration::minutes(5)));
+        clock.set(start);
+        assert_eq!(shared.now(), Some(start));
+    }
+}
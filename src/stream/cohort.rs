@@ -0,0 +1,343 @@
+//! Compare segments of an event stream
+//!
+//! A cohort is a group of traces that share the same value of a trace attribute, e.g. a region or
+//! a sales channel. The `Cohort` handler assigns each trace to its cohort and feeds it to a
+//! dedicated handler per cohort. Once the stream ends, the artifacts of all cohort handlers are
+//! released as a single, keyed artifact that allows for cross-segment comparison.
+//!
+//! The `Cohort` plugin either collects statistics or a directly-follows graph per cohort, as
+//! selected by its attribute `collector`. Activities of the directly-follows graphs are resolved
+//! the same way as by the `DfgGenerator` plugin, i.e. by attribute or classifier.
+//!
+
+use std::any::Any;
+use std::collections::{btree_map, BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::dfg::{ActivitySource, DfgGenerator};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::stats::StatsCollector;
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, AttributeValue, Event, Meta, Stream,
+    Trace,
+};
+use crate::{Error, Result};
+
+/// Function that creates a fresh handler for each cohort
+pub type HandlerFactory = Box<dyn Fn() -> Box<dyn Handler> + Send>;
+
+/// Artifacts of all cohorts, keyed by the cohort's attribute value
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CohortArtifacts {
+    pub key: String,
+    pub cohorts: BTreeMap<String, Vec<AnyArtifact>>,
+}
+
+impl CohortArtifacts {
+    /// Find the first artifact of the given type for each cohort
+    pub fn find<T: 'static>(&self) -> BTreeMap<&str, &T> {
+        self.cohorts
+            .iter()
+            .filter_map(|(k, a)| AnyArtifact::find::<T>(&mut a.iter()).map(|a| (k.as_str(), a)))
+            .collect()
+    }
+}
+
+#[typetag::serde]
+impl Artifact for CohortArtifacts {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Split a stream by a trace attribute and analyze each cohort separately
+///
+/// Traces that lack the attribute as well as standalone events are not assigned to any cohort. The
+/// stream itself is forwarded unaltered. Cohort handlers are created on the first trace of their
+/// cohort and receive the meta data of the stream beforehand.
+///
+pub struct Cohort {
+    key: String,
+    factory: HandlerFactory,
+    meta: Option<Meta>,
+    cohorts: BTreeMap<String, Box<dyn Handler>>,
+    /// Cohort of the open unfolded trace
+    open: Option<String>,
+}
+
+impl Cohort {
+    /// Create a new cohort handler that uses the given factory for each cohort
+    pub fn new<K: Into<String>>(key: K, factory: HandlerFactory) -> Self {
+        Self {
+            key: key.into(),
+            factory,
+            meta: None,
+            open: None,
+            cohorts: BTreeMap::new(),
+        }
+    }
+
+    /// Create a new cohort handler that collects statistics for each cohort
+    pub fn statistics<K: Into<String>>(key: K) -> Self {
+        Self::new(key, Box::new(|| Box::new(StatsCollector::default())))
+    }
+
+    /// Create a new cohort handler that discovers a directly-follows graph for each cohort
+    pub fn dfg<K: Into<String>>(key: K, source: ActivitySource) -> Self {
+        Self::new(
+            key,
+            Box::new(move || Box::new(DfgGenerator::new(source.clone()))),
+        )
+    }
+
+    fn cohort_name(value: &AttributeValue) -> String {
+        match value {
+            AttributeValue::String(value) | AttributeValue::Id(value) => value.to_string(),
+            AttributeValue::Date(value) => value.to_rfc3339(),
+            AttributeValue::Int(value) => value.to_string(),
+            AttributeValue::Float(value) => value.to_string(),
+            AttributeValue::Boolean(value) => value.to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+}
+
+impl Handler for Cohort {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.meta = Some(meta.clone());
+        Ok(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        self.open = None;
+        let name = match trace.get_value(&self.key) {
+            Some(value) => Self::cohort_name(value),
+            None => return Ok(Some(trace)),
+        };
+        self.open = Some(name.clone());
+
+        let handler = match self.cohorts.entry(name) {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            btree_map::Entry::Vacant(entry) => {
+                let mut handler = (self.factory)();
+                if let Some(meta) = self.meta.clone() {
+                    handler.on_meta(meta)?;
+                }
+                entry.insert(handler)
+            }
+        };
+
+        match handler.on_trace(trace.clone())? {
+            Some(mut copy) => {
+                for event in copy.events.drain(..) {
+                    handler.on_event(event, true)?;
+                }
+            }
+            None => self.open = None,
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        let cohorts = &mut self.cohorts;
+        if let Some(handler) = self.open.as_ref().and_then(|n| cohorts.get_mut(n)) {
+            handler.on_unfolded_event(event.clone())?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        let mut cohorts = BTreeMap::new();
+
+        for (name, handler) in self.cohorts.iter_mut() {
+            cohorts.insert(name.clone(), handler.release_artifacts()?);
+        }
+
+        Ok(vec![CohortArtifacts {
+            key: self.key.clone(),
+            cohorts,
+        }
+        .into()])
+    }
+}
+
+impl PluginProvider for Cohort {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Cohort",
+            "Compute statistics or a directly-follows graph per cohort, i.e. per value of a trace attribute",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be analyzed")
+                    .typed_attr(
+                        "key",
+                        "Trace attribute that determines the cohort",
+                        AttributeType::String,
+                    )
+                    .default_attr(
+                        "collector",
+                        "What to collect per cohort (statistics or dfg)",
+                        |k| (k, "statistics").into(),
+                    )
+                    .default_attr("activity", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    })
+                    .optional_attr(
+                        "classifier",
+                        "Classifier that takes precedence over the activity attribute",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let key = parameters
+                        .acquire_attribute("key")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let cohort = match parameters
+                        .acquire_attribute("collector")?
+                        .value
+                        .try_string()?
+                    {
+                        "statistics" => Cohort::statistics(key),
+                        "dfg" => {
+                            // a classifier takes precedence over the activity attribute
+                            let source = match parameters.acquire_attribute("classifier") {
+                                Ok(classifier) => ActivitySource::Classifier(
+                                    classifier.value.try_string()?.to_string(),
+                                ),
+                                Err(_) => ActivitySource::Attribute(
+                                    parameters
+                                        .acquire_attribute("activity")?
+                                        .value
+                                        .try_string()?
+                                        .to_string(),
+                                ),
+                            };
+                            Cohort::dfg(key, source)
+                        }
+                        other => {
+                            return Err(Error::AttributeError(format!(
+                                "unknown cohort collector: {:?}",
+                                other
+                            )))
+                        }
+                    };
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, cohort)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::dfg::Dfg;
+    use crate::stream::plugin::REGISTRY;
+    use crate::stream::stats::Statistics;
+    use crate::stream::void::consume;
+    use crate::stream::AttributeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_cohort() {
+        let buffer = load_example(&["book", "L1.xes"]);
+        let mut observer = Cohort::statistics("concept:name").into_observer(buffer);
+
+        let artifacts = consume(&mut observer).unwrap();
+        let cohorts = AnyArtifact::find::<CohortArtifacts>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .find::<Statistics>();
+
+        assert_eq!(cohorts.len(), 6);
+        assert_eq!(cohorts["Case3.0"].counts(), [1, 3, 3]);
+        assert_eq!(cohorts["Case1.0"].counts(), [1, 4, 4]);
+
+        let buffer = load_example(&["book", "L1.xes"]);
+        let mut observer = Cohort::statistics("foo").into_observer(buffer);
+
+        let artifacts = consume(&mut observer).unwrap();
+        let cohorts =
+            AnyArtifact::find::<CohortArtifacts>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert!(cohorts.cohorts.is_empty());
+    }
+
+    #[test]
+    fn test_cohort_dfg() {
+        let cohorts = |attributes: Vec<(&str, &str)>| {
+            let registry = REGISTRY.lock().unwrap();
+            let mut stream = registry.get("Cohort").unwrap().factory.build_stream(
+                AttributeMap::from(vec![("key", "concept:name")].into_iter().chain(attributes)),
+                &mut [],
+                vec![Box::new(load_example(&["book", "L1.xes"]))],
+                vec![],
+            )?;
+            let artifacts = consume(&mut stream)?;
+            AnyArtifact::find::<CohortArtifacts>(&mut artifacts.iter().flatten())
+                .and_then(|c| {
+                    let dfgs = c.find::<Dfg>();
+                    let dfg = dfgs.get("Case1.0")?;
+                    Some((dfgs.len(), dfg.activities.keys().next()?.clone()))
+                })
+                .ok_or_else(|| Error::StreamError("no cohorts".into()))
+        };
+        assert_eq!(
+            cohorts(vec![("collector", "dfg")]).unwrap(),
+            (6, "a".to_string())
+        );
+        // statistics hold no directly-follows graphs
+        assert!(cohorts(vec![("collector", "statistics")]).is_err());
+        assert!(cohorts(vec![("collector", "foo")]).is_err());
+
+        // activities are resolved like those of the DfgGenerator
+        assert_eq!(
+            cohorts(vec![("collector", "dfg"), ("activity", "org:resource")])
+                .unwrap()
+                .1,
+            "UNDEFINED"
+        );
+        assert_eq!(
+            cohorts(vec![
+                ("collector", "dfg"),
+                ("activity", "org:resource"),
+                ("classifier", "MXMLLegacyClassifier"),
+            ])
+            .unwrap()
+            .1,
+            "a+complete"
+        );
+        assert!(cohorts(vec![("collector", "dfg"), ("classifier", "missing")]).is_err());
+
+        let buffer = load_example(&["book", "L1.xes"]);
+        let mut observer = Cohort::dfg(
+            "concept:name",
+            ActivitySource::Attribute("concept:name".into()),
+        )
+        .into_observer(buffer);
+
+        let artifacts = consume(&mut observer).unwrap();
+        let cohorts = AnyArtifact::find::<CohortArtifacts>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .find::<Dfg>();
+
+        assert_eq!(cohorts.len(), 6);
+        let dfg = cohorts["Case1.0"];
+        assert_eq!(dfg.activities.values().sum::<f64>(), 4.0);
+        assert_eq!(dfg.start.values().sum::<f64>(), 1.0);
+        assert_eq!(dfg.end.values().sum::<f64>(), 1.0);
+    }
+}
@@ -0,0 +1,335 @@
+//! Compare event streams semantically
+//!
+//! Comparing serialized logs byte by byte is brittle: an exporter may reorder nested attributes,
+//! round timestamps to milliseconds or print floats with fewer digits, none of which changes the
+//! meaning of a log. `compare_streams` consumes two streams and reports the first semantic
+//! difference, subject to the tolerances of `CompareOptions`. In tests, `assert_stream_eq` panics
+//! with that difference instead.
+//!
+//! ```
+//! use promi::stream::buffer::Buffer;
+//! use promi::stream::compare::{assert_stream_eq, CompareOptions};
+//! use promi::stream::{Component, Event};
+//!
+//! let mut a = Buffer::default();
+//! let mut b = Buffer::default();
+//! let mut event = Event::default();
+//! event.attributes.insert(("cost", 0.1 + 0.2));
+//! a.push(Ok(Some(Component::Event(event))));
+//! let mut event = Event::default();
+//! event.attributes.insert(("cost", 0.3));
+//! b.push(Ok(Some(Component::Event(event))));
+//!
+//! assert_stream_eq(&mut a, &mut b, &CompareOptions::default().float_epsilon(1e-9));
+//! ```
+//!
+
+use chrono::Duration;
+
+use crate::stream::{
+    Attribute, AttributeContainer, AttributeMap, AttributeValue, ClassifierDecl, Component,
+    ExtensionDecl, Global, Meta, Stream,
+};
+use crate::{Error, Result};
+
+/// Tolerances for comparing streams
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    /// Whether the order of nested attributes, list items and meta declarations is irrelevant
+    pub ignore_order: bool,
+    /// Maximal difference of timestamps considered equal
+    pub timestamp_precision: Duration,
+    /// Maximal difference of floats considered equal
+    pub float_epsilon: f64,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            ignore_order: false,
+            timestamp_precision: Duration::zero(),
+            float_epsilon: 0.0,
+        }
+    }
+}
+
+impl CompareOptions {
+    pub fn ignore_order(mut self, ignore_order: bool) -> Self {
+        self.ignore_order = ignore_order;
+        self
+    }
+
+    pub fn timestamp_precision(mut self, precision: Duration) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    pub fn float_epsilon(mut self, epsilon: f64) -> Self {
+        self.float_epsilon = epsilon;
+        self
+    }
+}
+
+fn differ<T>(path: &str, message: String) -> Result<T> {
+    Err(Error::ValidationError(format!("{}: {}", path, message)))
+}
+
+/// Compare two lists item by item or, if order is ignored, find a distinct match for each item
+fn compare_lists<T, F>(a: &[T], b: &[T], options: &CompareOptions, path: &str, cmp: F) -> Result<()>
+where
+    F: Fn(&T, &T, &str) -> Result<()>,
+{
+    if a.len() != b.len() {
+        return differ(path, format!("{} items vs. {} items", a.len(), b.len()));
+    }
+
+    if !options.ignore_order {
+        return a
+            .iter()
+            .zip(b.iter())
+            .enumerate()
+            .try_for_each(|(i, (x, y))| cmp(x, y, &format!("{}[{}]", path, i)));
+    }
+
+    let mut matched = vec![false; b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let item_path = format!("{}[{}]", path, i);
+        match (0..b.len()).find(|j| !matched[*j] && cmp(x, &b[*j], &item_path).is_ok()) {
+            Some(j) => matched[j] = true,
+            None => return differ(&item_path, "no matching item".to_string()),
+        }
+    }
+
+    Ok(())
+}
+
+impl CompareOptions {
+    fn values(&self, a: &AttributeValue, b: &AttributeValue, path: &str) -> Result<()> {
+        let equal = match (a, b) {
+            (AttributeValue::Date(x), AttributeValue::Date(y)) => {
+                (*x - *y).abs() <= self.timestamp_precision
+            }
+            (AttributeValue::Float(x), AttributeValue::Float(y)) => {
+                (x.is_nan() && y.is_nan()) || (x - y).abs() <= self.float_epsilon
+            }
+            (AttributeValue::List(x), AttributeValue::List(y)) => {
+                return compare_lists(x, y, self, path, |x, y, p| self.attribute(x, y, p))
+            }
+            (x, y) => x == y,
+        };
+
+        if equal {
+            Ok(())
+        } else {
+            differ(path, format!("{:?} vs. {:?}", a, b))
+        }
+    }
+
+    fn attribute(&self, a: &Attribute, b: &Attribute, path: &str) -> Result<()> {
+        if a.key != b.key {
+            return differ(path, format!("key {:?} vs. {:?}", a.key, b.key));
+        }
+        let path = format!("{}/{}", path, a.key);
+        self.values(&a.value, &b.value, &path)?;
+        compare_lists(&a.children, &b.children, self, &path, |x, y, p| {
+            self.attribute(x, y, p)
+        })
+    }
+
+    fn attributes(&self, a: &AttributeMap, b: &AttributeMap, path: &str) -> Result<()> {
+        for (key, _, _) in b.iter() {
+            if a.get_value(key).is_none() {
+                return differ(path, format!("missing attribute {:?} on the left", key));
+            }
+        }
+
+        for (key, value, children) in a.iter() {
+            let attribute_path = format!("{}/{}", path, key);
+            match b.get_value(key) {
+                Some(other) => self.values(value, other, &attribute_path)?,
+                None => return differ(path, format!("missing attribute {:?} on the right", key)),
+            }
+            let other_children = b.get_children(key).unwrap_or(&[]);
+            compare_lists(
+                children,
+                other_children,
+                self,
+                &attribute_path,
+                |x, y, p| self.attribute(x, y, p),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn meta(&self, a: &Meta, b: &Meta, path: &str) -> Result<()> {
+        let extension = |x: &ExtensionDecl, y: &ExtensionDecl, p: &str| {
+            if (&x.name, &x.prefix, &x.uri) == (&y.name, &y.prefix, &y.uri) {
+                Ok(())
+            } else {
+                differ(p, format!("extension {:?} vs. {:?}", x, y))
+            }
+        };
+        let classifier = |x: &ClassifierDecl, y: &ClassifierDecl, p: &str| {
+            if (&x.name, &x.scope, &x.keys) == (&y.name, &y.scope, &y.keys) {
+                Ok(())
+            } else {
+                differ(p, format!("classifier {:?} vs. {:?}", x, y))
+            }
+        };
+        let global = |x: &Global, y: &Global, p: &str| {
+            if x.scope != y.scope {
+                return differ(p, format!("scope {:?} vs. {:?}", x.scope, y.scope));
+            }
+            compare_lists(&x.attributes, &y.attributes, self, p, |x, y, p| {
+                self.attribute(x, y, p)
+            })
+        };
+
+        let path = |section: &str| format!("{}/{}", path, section);
+        compare_lists(
+            &a.extensions,
+            &b.extensions,
+            self,
+            &path("extensions"),
+            extension,
+        )?;
+        compare_lists(&a.globals, &b.globals, self, &path("globals"), global)?;
+        compare_lists(
+            &a.classifiers,
+            &b.classifiers,
+            self,
+            &path("classifiers"),
+            classifier,
+        )?;
+        self.attributes(&a.attributes, &b.attributes, &path("attributes"))
+    }
+
+    fn component(&self, a: &Component, b: &Component, path: &str) -> Result<()> {
+        match (a, b) {
+            (Component::Meta(x), Component::Meta(y)) => self.meta(x, y, path),
+            (Component::Event(x), Component::Event(y)) => {
+                self.attributes(&x.attributes, &y.attributes, path)
+            }
+            (Component::Trace(x), Component::Trace(y)) => {
+                self.attributes(&x.attributes, &y.attributes, path)?;
+                // events are ordered by definition, regardless of the options
+                if x.events.len() != y.events.len() {
+                    return differ(
+                        path,
+                        format!("{} events vs. {} events", x.events.len(), y.events.len()),
+                    );
+                }
+                x.events
+                    .iter()
+                    .zip(y.events.iter())
+                    .enumerate()
+                    .try_for_each(|(i, (x, y))| {
+                        self.attributes(&x.attributes, &y.attributes, &format!("{}/{}", path, i))
+                    })
+            }
+            (x, y) => differ(path, format!("{:?} vs. {:?}", x.hint(), y.hint())),
+        }
+    }
+}
+
+/// Consume both streams and return a `ValidationError` describing the first difference
+pub fn compare_streams(
+    a: &mut dyn Stream,
+    b: &mut dyn Stream,
+    options: &CompareOptions,
+) -> Result<()> {
+    for i in 0.. {
+        match (a.next()?, b.next()?) {
+            (Some(x), Some(y)) => options.component(&x, &y, &format!("component {}", i))?,
+            (None, None) => break,
+            (Some(_), None) => return differ(&format!("component {}", i), "right ended".into()),
+            (None, Some(_)) => return differ(&format!("component {}", i), "left ended".into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Panic if two streams differ semantically
+pub fn assert_stream_eq(a: &mut dyn Stream, b: &mut dyn Stream, options: &CompareOptions) {
+    if let Err(error) = compare_streams(a, b, options) {
+        panic!("streams differ, {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::buffer::Buffer;
+    use crate::stream::{Event, Trace};
+    use crate::DateTime;
+
+    use super::*;
+
+    fn buffer(timestamp: &str, cost: f64, children: Vec<Attribute>) -> Buffer {
+        let mut event = Event::default();
+        event.attributes.insert((
+            "time:timestamp",
+            DateTime::parse_from_rfc3339(timestamp).unwrap(),
+        ));
+        event.attributes.insert(("cost", cost, children));
+        let mut trace = Trace::default();
+        trace.events.push(event);
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        buffer.push(Ok(Some(Component::Trace(trace))));
+        buffer
+    }
+
+    #[test]
+    fn test_compare_streams() {
+        let a = buffer(
+            "2021-03-04T10:30:00.1234+00:00",
+            0.1 + 0.2,
+            vec![Attribute::new("a", 1), Attribute::new("b", 2)],
+        );
+        let b = buffer(
+            "2021-03-04T11:30:00.123+01:00",
+            0.3,
+            vec![Attribute::new("b", 2), Attribute::new("a", 1)],
+        );
+        let strict = CompareOptions::default();
+        let tolerant = CompareOptions::default()
+            .ignore_order(true)
+            .timestamp_precision(Duration::milliseconds(1))
+            .float_epsilon(1e-9);
+
+        assert!(compare_streams(&mut a.clone(), &mut b.clone(), &strict).is_err());
+        assert_stream_eq(&mut a.clone(), &mut b.clone(), &tolerant);
+        assert!(compare_streams(
+            &mut a.clone(),
+            &mut b.clone(),
+            &tolerant.clone().ignore_order(false)
+        )
+        .is_err());
+
+        let c = buffer(
+            "2021-03-04T10:30:00.2+00:00",
+            0.3,
+            vec![Attribute::new("a", 1), Attribute::new("b", 2)],
+        );
+        match compare_streams(&mut a.clone(), &mut c.clone(), &tolerant) {
+            Err(Error::ValidationError(message)) => assert!(message.contains("time:timestamp")),
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let mut shorter = a.clone();
+        shorter.push(Ok(None));
+        let mut longer = a.clone();
+        longer.push(Ok(Some(Component::Event(Event::default()))));
+        assert!(compare_streams(&mut shorter, &mut longer, &tolerant).is_err());
+
+        assert_stream_eq(
+            &mut load_example(&["book", "L1.xes"]),
+            &mut load_example(&["book", "L1.xes"]),
+            &strict,
+        );
+    }
+}
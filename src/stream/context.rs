@@ -0,0 +1,263 @@
+//! Trace stream components back to their origin
+//!
+//! A [`Sequencer`] assigns a monotonically increasing sequence id to each component that is
+//! pulled from a stream and records it, along with the originating pipe and segment, as the
+//! current [`ComponentContext`] of the thread. Handlers further down the stream may access it via
+//! [`current`] and errors can be tagged with it via [`ComponentContext::wrap`].
+//!
+//! Components that are passed from one pipe to another by a stream channel keep their origin: the
+//! flow graph [`forward`]s the context of the sending pipe to the sequencer of the receiving one,
+//! where it becomes the upstream context of the received component.
+//!
+
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::stream::{AnyArtifact, ResOpt, Stream};
+
+thread_local! {
+    static CURRENT: RefCell<Option<ComponentContext>> = const { RefCell::new(None) };
+    static FORWARDED: RefCell<Option<ComponentContext>> = const { RefCell::new(None) };
+}
+
+/// Origin of a stream component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentContext {
+    /// Position of the component in the stream, starting at 1
+    pub sequence: u64,
+    /// Name of the pipe the component originates from
+    pub pipe: Option<String>,
+    /// Name of the segment the component originates from
+    pub segment: Option<String>,
+    /// Context of the component in the pipe it was received from
+    pub upstream: Option<Box<ComponentContext>>,
+}
+
+impl ComponentContext {
+    /// Tag an error with this context
+    ///
+    /// Errors that are already tagged are returned unchanged, i.e. the innermost context wins.
+    ///
+    pub fn wrap(&self, error: Error) -> Error {
+        match error {
+            Error::ComponentError(..) => error,
+            error => Error::ComponentError(self.to_string(), Box::new(error)),
+        }
+    }
+}
+
+impl fmt::Display for ComponentContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "component #{}", self.sequence)?;
+        if let Some(pipe) = &self.pipe {
+            write!(f, " of pipe {:?}", pipe)?;
+        }
+        if let Some(segment) = &self.segment {
+            write!(f, " from segment {:?}", segment)?;
+        }
+        if let Some(upstream) = &self.upstream {
+            write!(f, ", received as {}", upstream)?;
+        }
+        Ok(())
+    }
+}
+
+/// Context of the component most recently pulled by a sequencer on this thread
+pub fn current() -> Option<ComponentContext> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Reset the context of this thread
+pub fn clear() {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+    FORWARDED.with(|forwarded| *forwarded.borrow_mut() = None);
+}
+
+/// Hand the context of a component received from another pipe to the next sequenced component
+pub fn forward(context: Option<ComponentContext>) {
+    FORWARDED.with(|forwarded| *forwarded.borrow_mut() = context)
+}
+
+fn set(context: ComponentContext) {
+    CURRENT.with(|current| *current.borrow_mut() = Some(context))
+}
+
+/// Assigns sequence ids to components of a stream
+///
+/// Errors of the inner stream are tagged with the context of the component that failed to be
+/// produced.
+///
+pub struct Sequencer<T: Stream> {
+    stream: T,
+    pipe: Option<String>,
+    segment: Option<String>,
+    sequence: u64,
+}
+
+impl<T: Stream> Sequencer<T> {
+    pub fn new(stream: T) -> Self {
+        Sequencer {
+            stream,
+            pipe: None,
+            segment: None,
+            sequence: 0,
+        }
+    }
+
+    /// Name the originating pipe
+    pub fn pipe<S: Into<String>>(mut self, pipe: S) -> Self {
+        self.pipe = Some(pipe.into());
+        self
+    }
+
+    /// Name the originating segment
+    pub fn segment<S: Into<String>>(mut self, segment: S) -> Self {
+        self.segment = Some(segment.into());
+        self
+    }
+
+    fn context(&self, sequence: u64) -> ComponentContext {
+        ComponentContext {
+            sequence,
+            pipe: self.pipe.clone(),
+            segment: self.segment.clone(),
+            upstream: FORWARDED
+                .with(|forwarded| forwarded.borrow_mut().take())
+                .map(Box::new),
+        }
+    }
+}
+
+impl<T: Stream> Stream for Sequencer<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        match self.stream.next() {
+            Ok(Some(component)) => {
+                self.sequence += 1;
+                set(self.context(self.sequence));
+                Ok(Some(component))
+            }
+            Ok(None) => Ok(None),
+            Err(error) => Err(self.context(self.sequence + 1).wrap(error)),
+        }
+    }
+
+    // the sequencer is transparent, i.e. it must not add a level of artifacts
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        self.stream.emit_artifacts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::observer::Handler;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Trace};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        sequences: Vec<u64>,
+        fail_at: u64,
+    }
+
+    impl Handler for Recorder {
+        fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+            let context = current().unwrap();
+            self.sequences.push(context.sequence);
+
+            if context.sequence == self.fail_at {
+                Err(context.wrap(Error::StreamError("boom".to_string())))
+            } else {
+                Ok(Some(trace))
+            }
+        }
+    }
+
+    fn traces(n: usize) -> Buffer {
+        let mut buffer = Buffer::default();
+        for _ in 0..n {
+            buffer.push(Ok(Some(Component::Trace(Trace::default()))));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_sequencer() {
+        clear();
+        let sequencer = Sequencer::new(traces(3)).pipe("Foo").segment("Bar");
+        let mut observer = Recorder::default().into_observer(sequencer);
+
+        consume(&mut observer).unwrap();
+
+        assert_eq!(observer.release().unwrap().sequences, vec![1, 2, 3]);
+        assert_eq!(
+            current(),
+            Some(ComponentContext {
+                sequence: 3,
+                pipe: Some("Foo".to_string()),
+                segment: Some("Bar".to_string()),
+                upstream: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        let sequencer = Sequencer::new(traces(5)).pipe("Foo").segment("Bar");
+        let mut observer = Recorder {
+            fail_at: 3,
+            ..Recorder::default()
+        }
+        .into_observer(sequencer);
+
+        let error = consume(&mut observer).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Stream Error: boom (at component #3 of pipe "Foo" from segment "Bar")"#
+        );
+
+        // errors of the inner stream refer to the component that failed to be produced
+        let mut buffer = traces(2);
+        buffer.push(Err(Error::StreamError("bad".to_string())));
+        let mut sequencer = Sequencer::new(buffer);
+
+        let error = consume(&mut sequencer).unwrap_err();
+        assert_eq!(error.to_string(), "Stream Error: bad (at component #3)");
+    }
+
+    #[test]
+    fn test_forward() {
+        clear();
+        let upstream = ComponentContext {
+            sequence: 7,
+            pipe: Some("Foo".to_string()),
+            segment: None,
+            upstream: None,
+        };
+        forward(Some(upstream.clone()));
+
+        let mut sequencer = Sequencer::new(traces(2)).pipe("Bar");
+        sequencer.next().unwrap();
+        let context = current().unwrap();
+        assert_eq!(context.upstream, Some(Box::new(upstream)));
+        assert_eq!(
+            context.to_string(),
+            r#"component #1 of pipe "Bar", received as component #7 of pipe "Foo""#
+        );
+
+        // the forwarded context only applies to a single component
+        sequencer.next().unwrap();
+        assert_eq!(current().unwrap().upstream, None);
+    }
+}
@@ -20,6 +20,11 @@ pub trait Artifact: Any + Send + Debug {
 }
 
 /// Container for arbitrary artifacts a stream processing pipeline may create
+///
+/// Artifacts serialize with their type name in `__type`, such that any artifact written by one
+/// process can be deserialized by another, as long as the artifact type is compiled into it.
+/// Deserializing an unknown type is an error.
+///
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnyArtifact {
     artifact: Box<dyn Artifact>,
@@ -63,3 +68,40 @@ impl<T: Artifact> From<T> for AnyArtifact {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::dfg::{Dfg, DfgGenerator};
+    use crate::stream::observer::Handler;
+    use crate::stream::stats::{Statistics, StatsCollector};
+    use crate::stream::void::consume;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let buffer = crate::dev_util::load_example(&["book", "L1.xes"]);
+        let observer = StatsCollector::default().into_observer(buffer);
+        let mut observer = DfgGenerator::default().into_observer(observer);
+        let artifacts: Vec<AnyArtifact> = consume(&mut observer)
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let json = serde_json::to_string(&artifacts).unwrap();
+        let binary = rmp_serde::to_vec(&artifacts).unwrap();
+        for restored in [
+            serde_json::from_str::<Vec<AnyArtifact>>(&json).unwrap(),
+            rmp_serde::from_slice::<Vec<AnyArtifact>>(&binary).unwrap(),
+        ] {
+            let statistics = AnyArtifact::find::<Statistics>(&mut restored.iter()).unwrap();
+            assert_eq!(statistics.counts(), [6, 23, 23]);
+            let dfg = AnyArtifact::find::<Dfg>(&mut restored.iter()).unwrap();
+            assert_eq!(Some(dfg), AnyArtifact::find::<Dfg>(&mut artifacts.iter()));
+        }
+
+        let unknown = r#"{"artifact":{"__type":"Unknown"}}"#;
+        assert!(serde_json::from_str::<AnyArtifact>(unknown).is_err());
+    }
+}
@@ -1,9 +1,14 @@
 use std::any::Any;
+use std::borrow::Borrow;
 use std::collections::btree_map::Iter;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
 
-use serde::{Deserialize, Serialize};
+use compact_str::CompactString;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::stream::{Artifact, ComponentType};
 use crate::{DateTime, Error, Result};
@@ -20,15 +25,22 @@ pub enum AttributeType {
     List,
 }
 
+/// String that is stored inline if it is no longer than 24 bytes
+///
+/// Values of string attributes are mostly short, e.g. activity names or resources, such that
+/// they don't need an allocation of their own.
+///
+pub type SmallString = CompactString;
+
 /// Attribute value type
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AttributeValue {
-    String(String),
+    String(SmallString),
     Date(DateTime),
     Int(i64),
     Float(f64),
     Boolean(bool),
-    Id(String),
+    Id(SmallString),
     List(Vec<Attribute>),
 }
 
@@ -65,6 +77,15 @@ impl AttributeValue {
         }
     }
 
+    /// Try to cast attribute to float, integers are converted
+    pub fn try_number(&self) -> Result<f64> {
+        match self {
+            AttributeValue::Float(float) => Ok(*float),
+            AttributeValue::Int(integer) => Ok(*integer as f64),
+            other => Err(Error::AttributeError(format!("{:?} is no number", other))),
+        }
+    }
+
     /// Try to cast attribute to boolean
     pub fn try_boolean(&self) -> Result<&bool> {
         match self {
@@ -114,6 +135,12 @@ impl From<&str> for AttributeValue {
 
 impl From<String> for AttributeValue {
     fn from(value: String) -> Self {
+        AttributeValue::String(value.into())
+    }
+}
+
+impl From<SmallString> for AttributeValue {
+    fn from(value: SmallString) -> Self {
         AttributeValue::String(value)
     }
 }
@@ -169,7 +196,7 @@ impl Artifact for AttributeValue {
 ///
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Attribute {
-    pub key: String,
+    pub key: Key,
     pub value: AttributeValue,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<Attribute>,
@@ -179,7 +206,7 @@ impl Attribute {
     /// Instantiate a new attribute without children
     pub fn new<K, V>(key: K, attribute: V) -> Attribute
     where
-        K: Into<String>,
+        K: Into<Key>,
         V: Into<AttributeValue>,
     {
         Attribute {
@@ -192,7 +219,7 @@ impl Attribute {
     /// Instantiate a new attribute with children
     pub fn with_children<K, V, C>(key: K, attribute: V, children: C) -> Attribute
     where
-        K: Into<String>,
+        K: Into<Key>,
         V: Into<AttributeValue>,
         C: IntoIterator<Item = Attribute>,
     {
@@ -210,7 +237,7 @@ impl Attribute {
 
 impl<K, V> From<(K, V)> for Attribute
 where
-    K: Into<String>,
+    K: Into<Key>,
     V: Into<AttributeValue>,
 {
     fn from(tuple: (K, V)) -> Self {
@@ -221,7 +248,7 @@ where
 
 impl<K, V, C> From<(K, V, C)> for Attribute
 where
-    K: Into<String>,
+    K: Into<Key>,
     V: Into<AttributeValue>,
     C: IntoIterator<Item = Attribute>,
 {
@@ -242,6 +269,133 @@ impl Artifact for Attribute {
     }
 }
 
+/// Attribute key
+///
+/// Keys are reference counted, such that attributes read from the same [`KeyTable`] share them
+/// instead of each allocating a string of its own.
+///
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(Arc<str>);
+
+impl Key {
+    /// Allocate a key that is not shared with any other key
+    pub fn new(key: &str) -> Self {
+        Key(Arc::from(key))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Key {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Key {
+    fn from(key: &str) -> Self {
+        Key::new(key)
+    }
+}
+
+impl From<&String> for Key {
+    fn from(key: &String) -> Self {
+        Key::new(key)
+    }
+}
+
+impl From<String> for Key {
+    fn from(key: String) -> Self {
+        Key(Arc::from(key))
+    }
+}
+
+impl From<&Key> for Key {
+    fn from(key: &Key) -> Self {
+        key.clone()
+    }
+}
+
+impl PartialEq<str> for Key {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Key {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(Key::from(String::deserialize(deserializer)?))
+    }
+}
+
+/// Table of keys to share among the attributes of a stream
+///
+/// Logs repeat the same few keys, e.g. `concept:name`, for millions of events. Readers intern the
+/// keys they parse, such that all attributes of a stream refer to a single allocation per key. The
+/// table lives as long as the reader that owns it and grows with the number of distinct keys.
+///
+#[derive(Debug, Clone, Default)]
+pub struct KeyTable {
+    keys: HashSet<Key>,
+}
+
+impl KeyTable {
+    /// Look up the key in the table, add it if missing
+    pub fn intern(&mut self, key: &str) -> Key {
+        if let Some(key) = self.keys.get(key) {
+            return key.clone();
+        }
+
+        let key = Key::new(key);
+        self.keys.insert(key.clone());
+        key
+    }
+
+    /// Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check whether the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
 /// Helper struct that represents an attribute without its key
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct AttributeMapEntry {
@@ -266,10 +420,13 @@ impl AttributeMapEntry {
 }
 
 /// `BTreeMap` based container for attributes that allows for efficient access
+///
+/// Keys are shared with the attributes the map is built from, see [`Key`].
+///
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AttributeMap {
     #[serde(flatten)]
-    inner: BTreeMap<String, AttributeMapEntry>,
+    inner: BTreeMap<Key, AttributeMapEntry>,
 }
 
 impl Default for AttributeMap {
@@ -382,7 +539,7 @@ impl AttributeMap {
 
 /// An iterator over tuple representations of attributes
 pub struct AttributeMapIterator<'a> {
-    inner: Iter<'a, String, AttributeMapEntry>,
+    inner: Iter<'a, Key, AttributeMapEntry>,
 }
 
 impl<'a> Iterator for AttributeMapIterator<'a> {
@@ -427,3 +584,73 @@ pub trait AttributeContainer {
     /// Tell the caller what kind of object this view refers to
     fn hint(&self) -> ComponentType;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_table() {
+        let mut table = KeyTable::default();
+        let maps: Vec<AttributeMap> = (0..2)
+            .map(|i| {
+                AttributeMap::from(
+                    vec![
+                        (table.intern("concept:name"), i),
+                        (table.intern("org:resource"), i),
+                    ]
+                    .into_iter(),
+                )
+            })
+            .collect();
+        assert_eq!(table.len(), 2);
+
+        let keys: Vec<Vec<&str>> = maps
+            .iter()
+            .map(|m| m.iter().map(|a| a.0).collect())
+            .collect();
+        assert_eq!(keys[0], ["concept:name", "org:resource"]);
+        assert!(keys[0]
+            .iter()
+            .zip(keys[1].iter())
+            .all(|(a, b)| a.as_ptr() == b.as_ptr()));
+
+        // keys that don't come from a table are allocated on their own
+        assert_ne!(Key::new("concept:name").as_ptr(), keys[0][0].as_ptr());
+        assert_eq!(
+            Key::new("org:resource"),
+            Key::from("org:resource".to_string())
+        );
+
+        // keys are serialized as plain strings
+        let json = serde_json::to_string(&maps[1]).unwrap();
+        assert_eq!(
+            json,
+            r#"{"concept:name":{"Int":1},"org:resource":{"Int":1}}"#
+        );
+        let map: AttributeMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(map, maps[1]);
+        assert_eq!(
+            map.clone().remove("concept:name").unwrap().key,
+            "concept:name"
+        );
+    }
+
+    #[test]
+    fn test_small_string() {
+        let short = AttributeValue::from("register request");
+        let long = AttributeValue::from("a".repeat(25));
+        assert!(!short.try_string().unwrap().is_empty());
+        match (&short, &long) {
+            (AttributeValue::String(short), AttributeValue::String(long)) => {
+                assert!(!short.is_heap_allocated());
+                assert!(long.is_heap_allocated());
+            }
+            other => panic!("unexpected values: {:?}", other),
+        }
+        assert_eq!(
+            serde_json::to_string(&short).unwrap(),
+            r#"{"String":"register request"}"#
+        );
+    }
+}
@@ -3,8 +3,10 @@ use std::fmt::Debug;
 
 use serde::{Deserialize, Serialize};
 
+use crate::stream::extension::time::TimeType;
+use crate::stream::extension::{Concept, Extension, Org, Time};
 use crate::stream::{Attribute, AttributeContainer, AttributeMap, AttributeValue};
-use crate::{Error, Result};
+use crate::{DateTime, Error, Result};
 
 /// Tells whether global/classifier target events or traces
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -80,6 +82,134 @@ pub struct ClassifierDecl {
     pub keys: String,
 }
 
+impl ClassifierDecl {
+    /// Attribute keys of the classifier
+    ///
+    /// Keys are separated by whitespace, keys that contain whitespace are enclosed in single quotes.
+    ///
+    pub fn attribute_keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        let mut key = String::new();
+        let mut quoted = false;
+
+        for c in self.keys.chars() {
+            match (quoted, c) {
+                (_, '\'') => quoted = !quoted,
+                (false, c) if c.is_whitespace() => {
+                    if !key.is_empty() {
+                        keys.push(std::mem::take(&mut key));
+                    }
+                }
+                (_, c) => key.push(c),
+            }
+        }
+        if !key.is_empty() {
+            keys.push(key);
+        }
+
+        keys
+    }
+}
+
+/// Classifier that assigns classes to events or traces by the values of its attribute keys
+///
+/// The class identity is the string representation of all values joined by `+`, e.g.
+/// `approve+complete` for the keys `concept:name lifecycle:transition`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classifier {
+    pub name: String,
+    pub scope: Scope,
+    pub keys: Vec<String>,
+}
+
+impl From<&ClassifierDecl> for Classifier {
+    fn from(declaration: &ClassifierDecl) -> Self {
+        Classifier {
+            name: declaration.name.clone(),
+            scope: declaration.scope.clone(),
+            keys: declaration.attribute_keys(),
+        }
+    }
+}
+
+impl Classifier {
+    /// Classify events by a single attribute
+    pub fn attribute<K: Into<String>>(key: K) -> Self {
+        let key = key.into();
+        Classifier {
+            name: key.clone(),
+            scope: Scope::Event,
+            keys: vec![key],
+        }
+    }
+
+    /// Class identity of a trace or event, `None` if any key is missing or holds a list
+    pub fn class(&self, component: &dyn AttributeContainer) -> Option<String> {
+        let mut parts = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            parts.push(match component.get_value(key)? {
+                AttributeValue::String(value) | AttributeValue::Id(value) => value.to_string(),
+                AttributeValue::Date(value) => value.to_rfc3339(),
+                AttributeValue::Int(value) => value.to_string(),
+                AttributeValue::Float(value) => value.to_string(),
+                AttributeValue::Boolean(value) => value.to_string(),
+                AttributeValue::List(_) => return None,
+            });
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("+"))
+        }
+    }
+}
+
+/// Tells whether a stream is organized in traces or consists of standalone events only
+///
+/// The XES standard covers both, logs that group events by traces and event streams that don't. In
+/// the latter case, events usually refer to their case by the attribute `case:concept:name`.
+///
+/// An `Unfolded` stream is a log whose traces are emitted in pieces to bound memory: a trace
+/// without events carries the trace attributes and is followed by the events of the trace as
+/// standalone events, up to the next trace. Observers pass these events to
+/// `Handler::on_unfolded_event` and drop them along with their trace if a handler filters it.
+///
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Flavor {
+    #[default]
+    Log,
+    Stream,
+    Unfolded,
+}
+
+impl std::str::FromStr for Flavor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "log" => Ok(Flavor::Log),
+            "stream" => Ok(Flavor::Stream),
+            "unfolded" => Ok(Flavor::Unfolded),
+            other => Err(Error::AttributeError(format!(
+                "invalid flavor {:?}, expected \"log\", \"stream\" or \"unfolded\"",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Flavor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Flavor::Log => "log",
+            Flavor::Stream => "stream",
+            Flavor::Unfolded => "unfolded",
+        })
+    }
+}
+
 /// Container for meta information of an event stream
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Meta {
@@ -87,6 +217,9 @@ pub struct Meta {
     pub globals: Vec<Global>,
     pub classifiers: Vec<ClassifierDecl>,
     pub attributes: AttributeMap,
+    /// Streams of `Flavor::Stream` must not contain traces
+    #[serde(default)]
+    pub flavor: Flavor,
 }
 
 impl Default for Meta {
@@ -96,6 +229,20 @@ impl Default for Meta {
             globals: Vec::new(),
             classifiers: Vec::new(),
             attributes: AttributeMap::new(),
+            flavor: Flavor::default(),
+        }
+    }
+}
+
+impl Meta {
+    /// Resolve the classifier declared under the given name
+    pub fn classifier(&self, name: &str) -> Result<Classifier> {
+        match self.classifiers.iter().find(|c| c.name == name) {
+            Some(declaration) => Ok(declaration.into()),
+            None => Err(Error::KeyError(format!(
+                "no classifier {:?} declared",
+                name
+            ))),
         }
     }
 }
@@ -150,6 +297,116 @@ impl AttributeContainer for Event {
     }
 }
 
+/// Typed access to the attributes of the standard extensions
+///
+/// Getters return `None` if an attribute is missing or of the wrong type. Use the extension views,
+/// e.g. [`Concept::view`], to tell both apart.
+///
+impl Event {
+    /// Start building an event
+    pub fn builder() -> EventBuilder {
+        EventBuilder::default()
+    }
+
+    /// Activity, i.e. `concept:name`
+    pub fn name(&self) -> Option<&str> {
+        Concept::view(self).ok()?.name
+    }
+
+    /// Activity instance, i.e. `concept:instance`
+    pub fn instance(&self) -> Option<&str> {
+        Concept::view(self).ok()?.instance
+    }
+
+    /// Time of occurrence, i.e. `time:timestamp`
+    pub fn timestamp(&self) -> Option<&DateTime> {
+        match Time::view(self).ok()?.time {
+            TimeType::Timestamp(time) => Some(time),
+            TimeType::Interval(_) => None,
+        }
+    }
+
+    /// Executing resource, i.e. `org:resource`
+    pub fn resource(&self) -> Option<&str> {
+        Org::view(self).ok()?.resource
+    }
+
+    /// Role of the executing resource, i.e. `org:role`
+    pub fn role(&self) -> Option<&str> {
+        Org::view(self).ok()?.role
+    }
+
+    /// Group of the executing resource, i.e. `org:group`
+    pub fn group(&self) -> Option<&str> {
+        Org::view(self).ok()?.group
+    }
+
+    /// Lifecycle transition, i.e. `lifecycle:transition`
+    pub fn transition(&self) -> Option<&str> {
+        self.get_value("lifecycle:transition")?.try_string().ok()
+    }
+}
+
+/// Builder for events with the attributes of the standard extensions
+///
+/// ```
+/// use promi::stream::Event;
+///
+/// let event = Event::builder().name("a").resource("Alice").attribute(("cost", 42)).build();
+/// assert_eq!((event.name(), event.resource()), (Some("a"), Some("Alice")));
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct EventBuilder {
+    event: Event,
+}
+
+impl EventBuilder {
+    pub fn name<S: Into<String>>(self, name: S) -> Self {
+        self.attribute(("concept:name", name.into()))
+    }
+
+    pub fn instance<S: Into<String>>(self, instance: S) -> Self {
+        self.attribute(("concept:instance", instance.into()))
+    }
+
+    pub fn timestamp(self, timestamp: DateTime) -> Self {
+        self.attribute(("time:timestamp", timestamp))
+    }
+
+    pub fn resource<S: Into<String>>(self, resource: S) -> Self {
+        self.attribute(("org:resource", resource.into()))
+    }
+
+    pub fn role<S: Into<String>>(self, role: S) -> Self {
+        self.attribute(("org:role", role.into()))
+    }
+
+    pub fn group<S: Into<String>>(self, group: S) -> Self {
+        self.attribute(("org:group", group.into()))
+    }
+
+    pub fn transition<S: Into<String>>(self, transition: S) -> Self {
+        self.attribute(("lifecycle:transition", transition.into()))
+    }
+
+    /// Set an arbitrary attribute, replacing any previous one with the same key
+    pub fn attribute<A: Into<Attribute>>(mut self, attribute: A) -> Self {
+        self.event.attributes.insert(attribute);
+        self
+    }
+
+    pub fn build(self) -> Event {
+        self.event
+    }
+}
+
+impl From<EventBuilder> for Event {
+    fn from(builder: EventBuilder) -> Self {
+        builder.build()
+    }
+}
+
 /// Represents the execution of a single case
 ///
 /// From [IEEE Std 1849-2016](https://standards.ieee.org/standard/1849-2016.html):
@@ -194,6 +451,70 @@ impl AttributeContainer for Trace {
     }
 }
 
+impl Trace {
+    /// Start building a trace
+    pub fn builder() -> TraceBuilder {
+        TraceBuilder::default()
+    }
+
+    /// Case identifier, i.e. `concept:name`
+    pub fn case_id(&self) -> Option<&str> {
+        Concept::view(self).ok()?.name
+    }
+
+    /// Timestamps of the first and the last event, if they are ordered
+    pub fn interval(&self) -> Option<(&DateTime, &DateTime)> {
+        match Time::view(self).ok()?.time {
+            TimeType::Interval(interval) => Some(interval),
+            TimeType::Timestamp(_) => None,
+        }
+    }
+}
+
+/// Builder for traces, see [`EventBuilder`]
+#[derive(Debug, Default)]
+pub struct TraceBuilder {
+    trace: Trace,
+}
+
+impl TraceBuilder {
+    pub fn case_id<S: Into<String>>(self, case_id: S) -> Self {
+        self.attribute(("concept:name", case_id.into()))
+    }
+
+    /// Set an arbitrary attribute, replacing any previous one with the same key
+    pub fn attribute<A: Into<Attribute>>(mut self, attribute: A) -> Self {
+        self.trace.attributes.insert(attribute);
+        self
+    }
+
+    /// Append an event, either built or a builder
+    pub fn event<E: Into<Event>>(mut self, event: E) -> Self {
+        self.trace.events.push(event.into());
+        self
+    }
+
+    /// Append multiple events
+    pub fn events<I>(mut self, events: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Event>,
+    {
+        self.trace.events.extend(events.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn build(self) -> Trace {
+        self.trace
+    }
+}
+
+impl From<TraceBuilder> for Trace {
+    fn from(builder: TraceBuilder) -> Self {
+        builder.build()
+    }
+}
+
 /// State of an extensible event stream
 #[derive(Debug, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
 pub enum ComponentType {
@@ -254,3 +575,53 @@ impl AttributeContainer for Component {
 // ```
 /// Container for stream components that can express the empty components as well as errors
 pub type ResOpt = Result<Option<Component>>;
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let t1 = chrono::FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2020, 1, 1, 8, 0, 0)
+            .unwrap();
+        let t2 = t1 + chrono::Duration::hours(2);
+
+        let trace = Trace::builder()
+            .case_id("c1")
+            .attribute(("priority", 3))
+            .event(
+                Event::builder()
+                    .name("a")
+                    .instance("a1")
+                    .timestamp(t1)
+                    .resource("Alice")
+                    .role("clerk")
+                    .group("front office")
+                    .transition("complete"),
+            )
+            .events(vec![Event::builder().name("b").timestamp(t2).build()])
+            .build();
+
+        assert_eq!(trace.case_id(), Some("c1"));
+        assert_eq!(trace.get_value("priority"), Some(&AttributeValue::Int(3)));
+        assert_eq!(trace.interval(), Some((&t1, &t2)));
+
+        let event = &trace.events[0];
+        assert_eq!((event.name(), event.instance()), (Some("a"), Some("a1")));
+        assert_eq!(event.timestamp(), Some(&t1));
+        assert_eq!(
+            (event.resource(), event.role(), event.group()),
+            (Some("Alice"), Some("clerk"), Some("front office"))
+        );
+        assert_eq!(event.transition(), Some("complete"));
+
+        // missing and mistyped attributes
+        let event = Event::builder().attribute(("concept:name", 42)).build();
+        assert_eq!((event.name(), event.timestamp()), (None, None));
+        assert_eq!(Trace::default().interval(), None);
+    }
+}
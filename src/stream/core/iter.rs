@@ -0,0 +1,186 @@
+use std::iter::FromIterator;
+
+use crate::stream::buffer::Buffer;
+use crate::stream::{Component, Event, Stream, Trace};
+use crate::Result;
+
+/// Iterator over the components of a stream
+///
+/// Created by [`Stream::iter`] or [`Stream::components`]. The iterator is fused, i.e. it stops
+/// after the end of the stream or the first error. Artifacts can still be emitted by the stream
+/// afterwards, e.g. by taking it back with [`Iter::into_inner`].
+///
+pub struct Iter<S: Stream> {
+    stream: S,
+    done: bool,
+}
+
+impl<S: Stream> Iter<S> {
+    pub fn new(stream: S) -> Self {
+        Iter {
+            stream,
+            done: false,
+        }
+    }
+
+    /// Return the underlying stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Stream> Iterator for Iter<S> {
+    type Item = Result<Component>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let component = self.stream.next().transpose();
+        self.done = !matches!(component, Some(Ok(_)));
+        component
+    }
+}
+
+/// Iterator over the traces of a stream, see [`Stream::traces`]
+pub struct Traces<S: Stream> {
+    components: Iter<S>,
+}
+
+impl<S: Stream> Traces<S> {
+    pub fn new(stream: S) -> Self {
+        Traces {
+            components: Iter::new(stream),
+        }
+    }
+}
+
+impl<S: Stream> Iterator for Traces<S> {
+    type Item = Result<Trace>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.components.next()? {
+                Ok(Component::Trace(trace)) => return Some(Ok(trace)),
+                Ok(_) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Iterator over the events of a stream, see [`Stream::events`]
+pub struct Events<S: Stream> {
+    components: Iter<S>,
+    pending: std::vec::IntoIter<Event>,
+}
+
+impl<S: Stream> Events<S> {
+    pub fn new(stream: S) -> Self {
+        Events {
+            components: Iter::new(stream),
+            pending: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<S: Stream> Iterator for Events<S> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.next() {
+                return Some(Ok(event));
+            }
+
+            match self.components.next()? {
+                Ok(Component::Trace(trace)) => self.pending = trace.events.into_iter(),
+                Ok(Component::Event(event)) => return Some(Ok(event)),
+                Ok(Component::Meta(_)) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for Box<dyn Stream + 'a> {
+    type Item = Result<Component>;
+    type IntoIter = Iter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::new(self)
+    }
+}
+
+impl IntoIterator for Buffer {
+    type Item = Result<Component>;
+    type IntoIter = Iter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::new(self)
+    }
+}
+
+impl FromIterator<Component> for Buffer {
+    fn from_iter<I: IntoIterator<Item = Component>>(components: I) -> Self {
+        let mut buffer = Buffer::default();
+        for component in components {
+            buffer.push(Ok(Some(component)));
+        }
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::void::consume;
+    use crate::stream::AttributeContainer;
+    use crate::Error;
+
+    use super::*;
+
+    #[test]
+    fn test_iter() {
+        let mut stream = load_example(&["book", "L1.xes"]);
+        let components = stream.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(components.len(), 1 + 6);
+        assert!(stream.iter().next().is_none());
+
+        // round trip through a buffer
+        let buffer: Buffer = components.into_iter().collect();
+        assert_eq!(buffer.len(), 1 + 6);
+        let activities = buffer
+            .events()
+            .map(|e| Ok(e?.get_value_or("concept:name")?.try_string()?.to_string()))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(activities.len(), 23);
+        assert_eq!(activities[..4], ["a", "e", "d", "a"]);
+
+        let boxed = load_example(&["book", "L1.xes"]).into_boxed();
+        let lengths: Vec<_> = boxed
+            .traces()
+            .map(|t| t.map(|t| t.events.len()))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(lengths.iter().sum::<usize>(), 23);
+        assert_eq!(
+            load_example(&["book", "L1.xes"])
+                .into_boxed()
+                .into_iter()
+                .count(),
+            7
+        );
+
+        // iteration stops at the first error, artifacts are still available
+        let mut buffer = Buffer::default();
+        buffer.push(Err(Error::StreamError("broken".to_string())));
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+        let mut iter = buffer.into_iter();
+        assert!(matches!(iter.next(), Some(Err(_))));
+        assert!(iter.next().is_none());
+        assert!(consume(&mut iter.into_inner()).is_ok());
+    }
+}
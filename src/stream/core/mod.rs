@@ -2,6 +2,7 @@
 pub mod artifact;
 pub mod attribute;
 pub mod component;
+pub mod iter;
 pub mod sink;
 pub mod stream;
 
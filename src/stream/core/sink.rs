@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use crate::stream::progress::{Progress, ProgressReport};
 use crate::stream::{AnyArtifact, Component, Stream};
 use crate::{Error, Result};
 
@@ -63,6 +66,21 @@ pub trait Sink: Send {
         Ok(artifacts)
     }
 
+    /// Invokes a stream like `consume`, passing a progress report to the callback periodically
+    fn consume_with_progress<F>(
+        &mut self,
+        stream: &mut dyn Stream,
+        interval: Duration,
+        callback: F,
+    ) -> Result<Vec<Vec<AnyArtifact>>>
+    where
+        Self: Sized,
+        F: FnMut(&ProgressReport) + Send,
+    {
+        let mut progress = Progress::new(stream).interval(interval).callback(callback);
+        self.consume(&mut progress)
+    }
+
     /// Turn sink instance into trait object
     fn into_boxed<'a>(self) -> Box<dyn Sink + 'a>
     where
@@ -1,6 +1,17 @@
-use crate::stream::{AnyArtifact, ResOpt};
+use serde::{Deserialize, Serialize};
+
+use crate::stream::{AnyArtifact, Events, Iter, ResOpt, Traces};
 use crate::Result;
 
+/// Position of a stream in the source it reads from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadPosition {
+    /// Bytes read so far
+    pub bytes: u64,
+    /// Size of the source in bytes, if known
+    pub total: Option<u64>,
+}
+
 /// Extensible event stream
 ///
 /// Yields one stream component at a time. Usually, it either acts as a factory or forwards another
@@ -35,6 +46,46 @@ pub trait Stream: Send {
         Ok(artifacts)
     }
 
+    /// Position of the stream in its source, if it reads from one
+    ///
+    /// Streams that forward another stream report the position of their inner stream.
+    ///
+    fn read_position(&self) -> Option<ReadPosition> {
+        self.inner_ref().and_then(|inner| inner.read_position())
+    }
+
+    /// Iterate the components of the stream, see [`Iter`]
+    fn iter(&mut self) -> Iter<&mut Self>
+    where
+        Self: Sized,
+    {
+        Iter::new(self)
+    }
+
+    /// Turn the stream into an iterator over its components
+    fn components(self) -> Iter<Self>
+    where
+        Self: Sized,
+    {
+        Iter::new(self)
+    }
+
+    /// Iterate the traces of the stream, meta data and standalone events are skipped
+    fn traces(self) -> Traces<Self>
+    where
+        Self: Sized,
+    {
+        Traces::new(self)
+    }
+
+    /// Iterate all events of the stream in order, i.e. those in traces and standalone ones
+    fn events(self) -> Events<Self>
+    where
+        Self: Sized,
+    {
+        Events::new(self)
+    }
+
     /// Turn stream instance into trait object
     fn into_boxed<'a>(self) -> Box<dyn Stream + 'a>
     where
@@ -60,4 +111,38 @@ impl<'a> Stream for Box<dyn Stream + 'a> {
     fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
         self.as_mut().on_emit_artifacts()
     }
+
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        self.as_mut().emit_artifacts()
+    }
+
+    fn read_position(&self) -> Option<ReadPosition> {
+        self.as_ref().read_position()
+    }
+}
+
+impl<S: Stream + ?Sized> Stream for &mut S {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        (**self).inner_ref()
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        (**self).inner_mut()
+    }
+
+    fn next(&mut self) -> ResOpt {
+        (**self).next()
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        (**self).on_emit_artifacts()
+    }
+
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        (**self).emit_artifacts()
+    }
+
+    fn read_position(&self) -> Option<ReadPosition> {
+        (**self).read_position()
+    }
 }
@@ -0,0 +1,314 @@
+//! Correlate events by foreign-key attributes
+//!
+//! Events of different cases, or even different logs, often refer to the same business objects,
+//! e.g. an invoice event and a delivery event carrying the same order number. The `Correlator`
+//! handler indexes events by the values of selected attributes and releases a `Correlations`
+//! artifact. It tells which events refer to which object and which cases are linked through
+//! shared objects, a starting point for object-centric analysis or merging cases.
+//!
+//! Correlating multiple logs works by running a correlator with a distinct source label on each
+//! of them and merging the resulting artifacts.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::merge::join_value;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, Event, Stream, Trace,
+};
+use crate::Result;
+
+/// Reference to an event
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventRef {
+    /// Label of the log the event stems from
+    pub source: String,
+    /// Name of the enclosing trace, if any
+    pub case: Option<String>,
+    pub activity: Option<String>,
+    /// Position of the event within its source
+    pub position: usize,
+}
+
+/// Events indexed by attribute key and value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Correlations {
+    pub objects: BTreeMap<String, BTreeMap<String, Vec<EventRef>>>,
+}
+
+impl Correlations {
+    /// Events that refer to the given value
+    pub fn events(&self, key: &str, value: &str) -> &[EventRef] {
+        self.objects
+            .get(key)
+            .and_then(|values| values.get(value))
+            .map(|events| events.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Add the references of another artifact, e.g. one of a different log
+    pub fn merge(&mut self, other: Correlations) {
+        for (key, values) in other.objects {
+            let own = self.objects.entry(key).or_default();
+            for (value, events) in values {
+                own.entry(value).or_default().extend(events);
+            }
+        }
+    }
+
+    /// Groups of cases, as (source, case), that are transitively linked through shared values
+    ///
+    /// Cases that are not linked to any other case are omitted. Groups are sorted.
+    ///
+    pub fn linked_cases(&self) -> Vec<BTreeSet<(String, String)>> {
+        let mut cases: Vec<(String, String)> = Vec::new();
+        let mut indices: HashMap<(String, String), usize> = HashMap::new();
+        let mut parent: Vec<usize> = Vec::new();
+
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        for events in self.objects.values().flat_map(|values| values.values()) {
+            let mut first = None;
+            for event in events.iter() {
+                let case = match &event.case {
+                    Some(case) => (event.source.clone(), case.clone()),
+                    None => continue,
+                };
+                let index = *indices.entry(case.clone()).or_insert_with(|| {
+                    cases.push(case);
+                    parent.push(parent.len());
+                    parent.len() - 1
+                });
+
+                match first {
+                    Some(first) => {
+                        let (a, b) = (find(&mut parent, first), find(&mut parent, index));
+                        parent[a] = b;
+                    }
+                    None => first = Some(index),
+                }
+            }
+        }
+
+        let mut groups: BTreeMap<usize, BTreeSet<(String, String)>> = BTreeMap::new();
+        for (index, case) in cases.into_iter().enumerate() {
+            let root = find(&mut parent, index);
+            groups.entry(root).or_default().insert(case);
+        }
+
+        let mut groups: Vec<_> = groups.into_values().filter(|g| g.len() > 1).collect();
+        groups.sort();
+        groups
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Correlations {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Index events by the values of foreign-key attributes
+///
+/// Values are looked up in the event attributes first and in those of the enclosing trace
+/// otherwise. Only string, id and integer values are considered.
+///
+pub struct Correlator {
+    keys: Vec<String>,
+    source: String,
+    position: usize,
+    correlations: Correlations,
+}
+
+impl Correlator {
+    pub fn new<K: Into<String>, I: IntoIterator<Item = K>>(keys: I) -> Self {
+        Correlator {
+            keys: keys.into_iter().map(|k| k.into()).collect(),
+            source: String::new(),
+            position: 0,
+            correlations: Correlations::default(),
+        }
+    }
+
+    /// Label events with the name of their log
+    pub fn source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    fn index(&mut self, event: &Event, trace: Option<&Trace>) {
+        let reference = EventRef {
+            source: self.source.clone(),
+            case: trace
+                .and_then(|t| t.get_value("concept:name"))
+                .and_then(join_value),
+            activity: event.get_value("concept:name").and_then(join_value),
+            position: self.position,
+        };
+        self.position += 1;
+
+        for key in self.keys.iter() {
+            let value = event
+                .get_value(key)
+                .or_else(|| trace.and_then(|t| t.get_value(key)))
+                .and_then(join_value);
+
+            if let Some(value) = value {
+                self.correlations
+                    .objects
+                    .entry(key.clone())
+                    .or_default()
+                    .entry(value)
+                    .or_default()
+                    .push(reference.clone());
+            }
+        }
+    }
+}
+
+impl Handler for Correlator {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        for event in trace.events.iter() {
+            self.index(event, Some(&trace));
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.index(&event, None);
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.correlations.clone().into()])
+    }
+}
+
+impl PluginProvider for Correlator {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Correlator",
+            "Link events through shared values of foreign-key attributes",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be correlated")
+                    .typed_attr(
+                        "keys",
+                        "List of foreign-key attributes, e.g. order numbers",
+                        AttributeType::List,
+                    )
+                    .default_attr("source", "Label of the log", |k| (k, "").into()),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let keys = parameters
+                        .acquire_attribute("keys")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(|a| Ok(a.value.try_string()?.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+                    let source = parameters
+                        .acquire_attribute("source")?
+                        .value
+                        .try_string()?
+                        .to_string();
+
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        Correlator::new(keys).source(source),
+                    ))
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::Component;
+
+    use super::*;
+
+    fn correlate(source: &str, traces: &[(&str, &[(&str, &str)])]) -> Correlations {
+        let mut buffer = Buffer::default();
+        for (name, events) in traces.iter() {
+            let mut trace = Trace::default();
+            trace.attributes.insert(("concept:name", *name));
+            for (activity, order) in events.iter() {
+                let mut event = Event::default();
+                event.attributes.insert(("concept:name", *activity));
+                event.attributes.insert(("order", *order));
+                trace.events.push(event);
+            }
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+
+        let mut observer = Correlator::new(vec!["order"])
+            .source(source)
+            .into_observer(buffer);
+        let artifacts = consume(&mut observer).unwrap();
+        AnyArtifact::find::<Correlations>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_correlator() {
+        let mut correlations = correlate(
+            "erp",
+            &[
+                ("c1", &[("create", "o1"), ("change", "o2")]),
+                ("c2", &[("create", "o3")]),
+            ],
+        );
+        let events = correlations.events("order", "o1");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].case.as_deref(), Some("c1"));
+        assert_eq!(events[0].activity.as_deref(), Some("create"));
+        assert!(correlations.linked_cases().is_empty());
+
+        correlations.merge(correlate(
+            "shop",
+            &[("s1", &[("ship", "o2")]), ("s2", &[("pay", "o3")])],
+        ));
+        assert_eq!(correlations.events("order", "o2").len(), 2);
+
+        let case = |s: &str, c: &str| (s.to_string(), c.to_string());
+        assert_eq!(
+            correlations.linked_cases(),
+            vec![
+                vec![case("erp", "c1"), case("shop", "s1")]
+                    .into_iter()
+                    .collect(),
+                vec![case("erp", "c2"), case("shop", "s2")]
+                    .into_iter()
+                    .collect(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1389 @@
+//! Flat event data in CSV format
+//!
+//! A CSV export of an event log holds one event per row. Which columns identify the case, the
+//! activity and the time of an event is described by a `ColumnMapping`. Mappings can be written
+//! to and read from simple profile files, i.e. they need to be set up only once per data source:
+//!
+//! ```text
+//! # promi column mapping
+//! delimiter = ;
+//! case = order_id
+//! activity = step
+//! timestamp = created
+//! timestamp_format = %d.%m.%Y %H:%M
+//! locale = de
+//! ```
+//!
+//! For new data sources, `detect` guesses a mapping from a sample of rows, based on column names,
+//! on how many values parse as timestamps and on the number of distinct values per column.
+//!
+//! `CsvReader` turns CSV data into an event stream. Each row becomes an event named after the
+//! activity column, events of the same case are grouped into a trace named after the case column.
+//! Rows with an empty case become standalone events and are reported as `MissingCaseId` in a
+//! `QualityReport` artifact. All other columns become event attributes,
+//! whose types are either declared or inferred per value: integers, floats (in the conventions of
+//! the mapping's locale), booleans (`true`/`false`) and strings otherwise. Empty fields are
+//! skipped.
+//!
+//! ```
+//! use promi::stream::csv::{ColumnMapping, CsvReader};
+//! use promi::stream::log::Log;
+//! use promi::stream::Sink;
+//!
+//! let data = "case,activity,time,cost\n1,a,2021-03-04 10:00,5\n1,b,2021-03-04 11:00,2.5\n";
+//! let mapping = ColumnMapping::new("case", "activity").timestamp("time", None);
+//!
+//! let mut log = Log::default();
+//! log.consume(&mut CsvReader::new(data.as_bytes(), mapping)).unwrap();
+//! assert_eq!(log.traces[0].events.len(), 2);
+//! ```
+//!
+//! In turn, `CsvWriter` flattens a stream into one row per event. Attributes of the enclosing
+//! trace are repeated in each row, prefixed with `case:`, e.g. `case:concept:name`. Columns are
+//! either selected upfront or collected from the whole stream, sorted with trace attributes first.
+//!
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use regex::Regex;
+
+use crate::stream::extension::{Concept, Extension, Time};
+use crate::stream::locale::Locale;
+use crate::stream::output::FileOptions;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::provenance::ProvenanceWriter;
+use crate::stream::quality::{Finding, Issue, QualityReport};
+use crate::stream::rotate::{RotatingWriter, Rotation, SinkBuilder};
+use crate::stream::{
+    AnyArtifact, Attribute, AttributeContainer, AttributeType, AttributeValue, Component, Event,
+    KeyTable, Meta, ResOpt, Sink, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// Timestamp formats tried during detection, timestamps without offset are considered UTC
+pub const TIMESTAMP_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M:%S",
+    "%Y-%m-%d",
+    "%d.%m.%Y",
+];
+
+const DELIMITERS: &[char] = &[',', ';', '\t', '|'];
+
+lazy_static! {
+    static ref CRE_CASE: Regex =
+        Regex::new(r"(?i)(case|trace|process.?id|order.?(id|no|number)|ticket|^id$)").unwrap();
+    static ref CRE_ACTIVITY: Regex =
+        Regex::new(r"(?i)(activit|concept:name|event|action|task|step|operation)").unwrap();
+    static ref CRE_TIMESTAMP: Regex =
+        Regex::new(r"(?i)(time|date|timestamp|^ts$|created|completed|start|end)").unwrap();
+}
+
+/// Parse a timestamp with the given `chrono` format
+///
+/// Formats without offset are interpreted as UTC, formats without time as midnight.
+///
+pub fn parse_timestamp(value: &str, format: &str) -> Result<DateTime> {
+    let value = value.trim();
+    let utc = FixedOffset::east_opt(0).unwrap();
+
+    if let Ok(timestamp) = chrono::DateTime::parse_from_str(value, format) {
+        return Ok(timestamp);
+    }
+    if let Ok(timestamp) = NaiveDateTime::parse_from_str(value, format) {
+        return Ok(utc.from_utc_datetime(&timestamp));
+    }
+    let date = NaiveDate::parse_from_str(value, format)?;
+    Ok(utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+/// Reads records of a CSV file as specified in RFC 4180
+///
+/// Fields may be quoted, quoted fields may contain delimiters, line breaks and escaped quotes.
+/// Blank lines outside of quoted fields are skipped.
+///
+pub struct Records<R: BufRead> {
+    reader: R,
+    delimiter: char,
+    lines: usize,
+    start: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, delimiter: char) -> Self {
+        Records {
+            reader,
+            delimiter,
+            lines: 0,
+            start: 0,
+        }
+    }
+
+    /// Physical line the last record started at, counting from 1
+    pub fn line(&self) -> usize {
+        self.start
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) if fields.is_empty() && field.is_empty() && !quoted => return None,
+                Ok(0) if quoted => {
+                    return Some(Err(Error::CsvError("unterminated quoted field".into())))
+                }
+                Ok(0) => break,
+                Ok(_) => self.lines += 1,
+                Err(error) => return Some(Err(error.into())),
+            }
+
+            let blank = line.trim_end_matches(&['\r', '\n'][..]).is_empty();
+            if !quoted && fields.is_empty() && field.is_empty() {
+                if blank {
+                    continue;
+                }
+                self.start = self.lines;
+            }
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (true, c) => field.push(c),
+                    (false, '"') if field.is_empty() => quoted = true,
+                    (false, '\r') | (false, '\n') => (),
+                    (false, c) if c == self.delimiter => fields.push(std::mem::take(&mut field)),
+                    (false, c) => field.push(c),
+                }
+            }
+
+            if !quoted {
+                break;
+            }
+        }
+
+        fields.push(field);
+        Some(Ok(fields))
+    }
+}
+
+/// Which columns hold the case, activity and timestamp of an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping {
+    pub delimiter: char,
+    pub case: String,
+    pub activity: String,
+    pub timestamp: Option<String>,
+    /// `chrono` format of the timestamp column, common formats are tried if not given
+    pub timestamp_format: Option<String>,
+    /// Tag of the locale to parse numbers and timestamps with, see `Locale::from_tag`
+    pub locale: Option<String>,
+}
+
+impl ColumnMapping {
+    pub fn new<C: Into<String>, A: Into<String>>(case: C, activity: A) -> Self {
+        ColumnMapping {
+            delimiter: ',',
+            case: case.into(),
+            activity: activity.into(),
+            timestamp: None,
+            timestamp_format: None,
+            locale: None,
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn timestamp<T: Into<String>>(mut self, column: T, format: Option<&str>) -> Self {
+        self.timestamp = Some(column.into());
+        self.timestamp_format = format.map(|f| f.to_string());
+        self
+    }
+
+    pub fn locale<T: Into<String>>(mut self, tag: T) -> Self {
+        self.locale = Some(tag.into());
+        self
+    }
+
+    /// Locale to parse values with, defaults to English conventions
+    pub fn resolve_locale(&self) -> Result<Locale> {
+        match &self.locale {
+            Some(tag) => Locale::from_tag(tag),
+            None => Ok(Locale::default()),
+        }
+    }
+
+    /// Write the mapping as profile
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        let delimiter = match self.delimiter {
+            '\t' => "\\t".to_string(),
+            d => d.to_string(),
+        };
+
+        writeln!(writer, "# promi column mapping")?;
+        writeln!(writer, "delimiter = {}", delimiter)?;
+        writeln!(writer, "case = {}", self.case)?;
+        writeln!(writer, "activity = {}", self.activity)?;
+        if let Some(timestamp) = &self.timestamp {
+            writeln!(writer, "timestamp = {}", timestamp)?;
+        }
+        if let Some(format) = &self.timestamp_format {
+            writeln!(writer, "timestamp_format = {}", format)?;
+        }
+        if let Some(locale) = &self.locale {
+            writeln!(writer, "locale = {}", locale)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a mapping from a profile
+    pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut mapping = ColumnMapping::new("", "");
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().to_string()),
+                None => {
+                    return Err(Error::CsvError(format!(
+                        "malformed profile line {:?}",
+                        line
+                    )))
+                }
+            };
+            match key {
+                "delimiter" => {
+                    mapping.delimiter = match value.as_str() {
+                        "\\t" => '\t',
+                        v if v.chars().count() == 1 => v.chars().next().unwrap(),
+                        v => return Err(Error::CsvError(format!("invalid delimiter {:?}", v))),
+                    }
+                }
+                "case" => mapping.case = value,
+                "activity" => mapping.activity = value,
+                "timestamp" => mapping.timestamp = Some(value),
+                "timestamp_format" => mapping.timestamp_format = Some(value),
+                "locale" => mapping.locale = Some(value),
+                other => return Err(Error::CsvError(format!("unknown profile key {:?}", other))),
+            }
+        }
+
+        if mapping.case.is_empty() || mapping.activity.is_empty() {
+            return Err(Error::CsvError("profile requires case and activity".into()));
+        }
+        Ok(mapping)
+    }
+
+    /// Write the mapping as profile to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write(io::BufWriter::new(File::create(path)?))
+    }
+
+    /// Read a mapping from a profile file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(io::BufReader::new(File::open(path)?))
+    }
+}
+
+/// Statistics of a column, gathered from a sample of rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    pub name: String,
+    /// Number of non-empty values
+    pub filled: usize,
+    /// Number of distinct non-empty values
+    pub distinct: usize,
+    /// Share of non-empty values that parse as number
+    pub numeric_rate: f64,
+    /// Share of non-empty values that parse as timestamp with the best matching format
+    pub timestamp_rate: f64,
+    pub timestamp_format: Option<String>,
+}
+
+impl ColumnProfile {
+    pub fn new(name: &str, values: &[&str]) -> Self {
+        let values: Vec<&str> = values
+            .iter()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .collect();
+        let mut distinct = values.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        let rate = |n: usize| {
+            if values.is_empty() {
+                0.0
+            } else {
+                n as f64 / values.len() as f64
+            }
+        };
+
+        let numeric = values.iter().filter(|v| v.parse::<f64>().is_ok()).count();
+        let (timestamp_format, timestamps) = TIMESTAMP_FORMATS
+            .iter()
+            .map(|f| {
+                let n = values
+                    .iter()
+                    .filter(|v| parse_timestamp(v, f).is_ok())
+                    .count();
+                (Some(f.to_string()), n)
+            })
+            .filter(|(_, n)| *n > 0)
+            // prefer the first format on ties
+            .fold((None, 0), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+
+        ColumnProfile {
+            name: name.to_string(),
+            filled: values.len(),
+            distinct: distinct.len(),
+            numeric_rate: rate(numeric),
+            timestamp_rate: rate(timestamps),
+            timestamp_format,
+        }
+    }
+
+    fn timestamp_score(&self) -> f64 {
+        let name = if CRE_TIMESTAMP.is_match(&self.name) {
+            0.5
+        } else {
+            0.0
+        };
+        2.0 * self.timestamp_rate + name
+    }
+
+    fn case_score(&self) -> f64 {
+        let mut score = if CRE_CASE.is_match(&self.name) {
+            2.0
+        } else {
+            0.0
+        };
+        // a case usually comprises several events, but there are many cases
+        if self.distinct > 1 && self.distinct < self.filled {
+            score += 1.0;
+        }
+        score + self.cardinality() - 2.0 * self.timestamp_rate
+    }
+
+    fn activity_score(&self) -> f64 {
+        let mut score = if CRE_ACTIVITY.is_match(&self.name) {
+            2.0
+        } else {
+            0.0
+        };
+        // there are few activities, which are labels rather than numbers
+        if self.distinct > 1 && self.distinct * 2 <= self.filled {
+            score += 1.0;
+        }
+        score - self.cardinality() - 2.0 * self.timestamp_rate - self.numeric_rate
+    }
+
+    /// Share of distinct values
+    fn cardinality(&self) -> f64 {
+        if self.filled > 0 {
+            self.distinct as f64 / self.filled as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Guess the column mapping from a header and a sample of rows
+///
+/// Columns are assigned greedily in order timestamp, case and activity, each to the remaining
+/// column with the highest score. Fails if no plausible case or activity column is found.
+///
+pub fn detect(header: &[String], rows: &[Vec<String>], delimiter: char) -> Result<ColumnMapping> {
+    let profiles: Vec<ColumnProfile> = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let values: Vec<&str> = rows
+                .iter()
+                .filter_map(|r| r.get(i).map(|v| v.as_str()))
+                .collect();
+            ColumnProfile::new(name, &values)
+        })
+        .collect();
+
+    let mut taken = vec![false; profiles.len()];
+    let mut best = |score: &dyn Fn(&ColumnProfile) -> f64, threshold: f64| {
+        let candidate = profiles
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !taken[*i])
+            .map(|(i, p)| (i, score(p)))
+            .filter(|(_, s)| *s >= threshold)
+            .fold(None, |best: Option<(usize, f64)>, (i, s)| match best {
+                Some((_, b)) if b >= s => best,
+                _ => Some((i, s)),
+            });
+        candidate.map(|(i, _)| {
+            taken[i] = true;
+            &profiles[i]
+        })
+    };
+
+    let timestamp = best(&ColumnProfile::timestamp_score, 1.5).cloned();
+    let case = best(&ColumnProfile::case_score, 1.0)
+        .map(|p| p.name.clone())
+        .ok_or_else(|| Error::CsvError("cannot detect case column".into()))?;
+    let activity = best(&ColumnProfile::activity_score, 0.5)
+        .map(|p| p.name.clone())
+        .ok_or_else(|| Error::CsvError("cannot detect activity column".into()))?;
+
+    let mut mapping = ColumnMapping::new(case, activity).delimiter(delimiter);
+    if let Some(timestamp) = timestamp {
+        mapping = mapping.timestamp(timestamp.name, timestamp.timestamp_format.as_deref());
+    }
+    Ok(mapping)
+}
+
+/// Guess delimiter and column mapping from the first `sample` rows of a CSV file with header
+pub fn detect_from<R: BufRead>(mut reader: R, sample: usize) -> Result<ColumnMapping> {
+    let mut first = String::new();
+    reader.read_line(&mut first)?;
+
+    // the delimiter that splits the header into most columns
+    let delimiter = DELIMITERS
+        .iter()
+        .copied()
+        .fold((',', 0), |best, d| match first.matches(d).count() {
+            n if n > best.1 => (d, n),
+            _ => best,
+        })
+        .0;
+
+    let header = Records::new(first.as_bytes(), delimiter)
+        .next()
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+    let rows = Records::new(reader, delimiter)
+        .take(sample)
+        .collect::<Result<Vec<_>>>()?;
+
+    detect(&header, &rows, delimiter)
+}
+
+/// Prefix of trace attribute columns
+pub const CASE_PREFIX: &str = "case:";
+
+/// Render an attribute value as CSV field, lists are left empty
+pub fn format_value(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::String(value) | AttributeValue::Id(value) => value.to_string(),
+        AttributeValue::Date(value) => value.to_rfc3339(),
+        AttributeValue::Int(value) => value.to_string(),
+        AttributeValue::Float(value) => value.to_string(),
+        AttributeValue::Boolean(value) => value.to_string(),
+        AttributeValue::List(_) => String::new(),
+    }
+}
+
+/// Write a stream as CSV, one row per event
+///
+/// Without column selection, all rows are kept in memory until the stream ends since the header
+/// can't be written before all columns are known.
+///
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    delimiter: char,
+    header: bool,
+    columns: Option<Vec<String>>,
+    rows: Vec<HashMap<String, String>>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CsvWriter {
+            writer,
+            delimiter: ',',
+            header: true,
+            columns: None,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether to write a header row
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Write only the given columns, in order
+    pub fn columns<C: Into<String>>(mut self, columns: Vec<C>) -> Self {
+        self.columns = Some(columns.into_iter().map(|c| c.into()).collect());
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn escape(&self, field: &str) -> String {
+        if field.contains(self.delimiter) || field.contains(&['"', '\n', '\r'][..]) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    fn write_record<'a, I: Iterator<Item = &'a str>>(&mut self, fields: I) -> Result<()> {
+        let delimiter = self.delimiter.to_string();
+        let record: Vec<String> = fields.map(|f| self.escape(f)).collect();
+        writeln!(self.writer, "{}", record.join(&delimiter))?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &HashMap<String, String>) -> Result<()> {
+        let columns = self.columns.take().unwrap_or_default();
+        let result = self.write_record(
+            columns
+                .iter()
+                .map(|c| row.get(c).map(|v| v.as_str()).unwrap_or("")),
+        );
+        self.columns = Some(columns);
+        result
+    }
+
+    fn row(event: &Event, trace: Option<&Trace>) -> Vec<(String, String)> {
+        let mut row = Vec::new();
+        if let Some(trace) = trace {
+            for (key, value, _) in trace.attributes.iter() {
+                row.push((format!("{}{}", CASE_PREFIX, key), format_value(value)));
+            }
+        }
+        for (key, value, _) in event.attributes.iter() {
+            row.push((key.to_string(), format_value(value)));
+        }
+        row
+    }
+
+    fn on_row(&mut self, row: Vec<(String, String)>) -> Result<()> {
+        if self.columns.is_some() {
+            self.write_row(&row.into_iter().collect())
+        } else {
+            self.rows.push(row.into_iter().collect());
+            Ok(())
+        }
+    }
+}
+
+impl<W: Write + Send> Sink for CsvWriter<W> {
+    fn on_open(&mut self) -> Result<()> {
+        if let (true, Some(columns)) = (self.header, self.columns.clone()) {
+            self.write_record(columns.iter().map(|c| c.as_str()))?;
+        }
+        Ok(())
+    }
+
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        match component {
+            Component::Meta(_) => Ok(()),
+            Component::Trace(trace) => trace
+                .events
+                .iter()
+                .try_for_each(|event| self.on_row(Self::row(event, Some(&trace)))),
+            Component::Event(event) => self.on_row(Self::row(&event, None)),
+        }
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        if self.columns.is_none() {
+            let mut columns: Vec<String> = Vec::new();
+            let mut traced: Vec<String> = Vec::new();
+            for row in self.rows.iter() {
+                for key in row.keys() {
+                    let target = if key.starts_with(CASE_PREFIX) {
+                        &mut traced
+                    } else {
+                        &mut columns
+                    };
+                    if !target.contains(key) {
+                        target.push(key.clone());
+                    }
+                }
+            }
+            traced.sort();
+            columns.sort();
+            traced.extend(columns);
+
+            if self.header {
+                self.write_record(traced.iter().map(|c| c.as_str()))?;
+            }
+            self.columns = Some(traced);
+            for row in std::mem::take(&mut self.rows) {
+                self.write_row(&row)?;
+            }
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl PluginProvider for CsvWriter<io::BufWriter<File>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "CsvWriter",
+            "Write the stream as CSV file with one row per event",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the CSV file", AttributeType::String)
+                    .default_attr("delimiter", "Field delimiter", |k| (k, ",").into())
+                    .default_attr("header", "Whether to write a header row", |k| {
+                        (k, true).into()
+                    })
+                    .default_attr(
+                        "columns",
+                        "Columns to write, all if empty, trace attributes are prefixed with case:",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    )
+                    .with(FileOptions::declare)
+                    .with(Rotation::declare)
+                    .with(ProvenanceWriter::declare),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let delimiter = match parameters
+                        .acquire_attribute("delimiter")?
+                        .value
+                        .try_string()?
+                    {
+                        "\\t" => '\t',
+                        d if d.chars().count() == 1 => d.chars().next().unwrap(),
+                        d => return Err(Error::CsvError(format!("invalid delimiter {:?}", d))),
+                    };
+                    let header = *parameters
+                        .acquire_attribute("header")?
+                        .value
+                        .try_boolean()?;
+                    let columns = parameters
+                        .acquire_attribute("columns")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(|c| c.value.try_string().map(|c| c.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    let options = FileOptions::from_parameters(parameters)?;
+
+                    let build = move |writer: Box<dyn Write + Send>| {
+                        let writer = CsvWriter::new(writer).delimiter(delimiter).header(header);
+                        if columns.is_empty() {
+                            writer.into_boxed()
+                        } else {
+                            writer.columns(columns.clone()).into_boxed()
+                        }
+                    };
+
+                    let sink = if let Some(rotation) = Rotation::from_parameters(parameters)? {
+                        let builder: SinkBuilder = Box::new(move |file| Ok(build(Box::new(file))));
+                        RotatingWriter::new(&path, rotation, builder)
+                            .options(options)
+                            .into_boxed()
+                    } else {
+                        build(Box::new(options.create(&path)?))
+                    };
+                    ProvenanceWriter::from_parameters(sink, &path, parameters)
+                })),
+            ),
+        )]
+    }
+}
+
+/// Parse a field as the given type
+pub fn coerce(value: &str, hint: &AttributeType, locale: &Locale) -> Result<AttributeValue> {
+    Ok(match hint {
+        AttributeType::String => AttributeValue::String(value.into()),
+        AttributeType::Id => AttributeValue::Id(value.into()),
+        AttributeType::Int => AttributeValue::Int(locale.parse_int(value)?),
+        AttributeType::Float => AttributeValue::Float(locale.parse_float(value)?),
+        AttributeType::Boolean => match value.trim().to_lowercase().as_str() {
+            "true" | "1" => AttributeValue::Boolean(true),
+            "false" | "0" => AttributeValue::Boolean(false),
+            _ => return Err(Error::ParseBooleanError(value.to_string())),
+        },
+        AttributeType::Date => AttributeValue::Date(locale.parse_timestamp(value, None)?),
+        AttributeType::List => {
+            return Err(Error::CsvError(format!("cannot read {:?} as list", value)))
+        }
+    })
+}
+
+/// Parse a field as integer, float, boolean or, if all fails, string
+///
+/// Zero-padded numbers like `02134` are usually identifiers and stay strings.
+///
+pub fn infer(value: &str, locale: &Locale) -> AttributeValue {
+    let trimmed = value.trim();
+    let unsigned = trimmed.trim_start_matches(['+', '-']);
+    let padded = unsigned.starts_with('0')
+        && unsigned[1..].starts_with(|c: char| c.is_ascii_digit() || c == '\'' || c == ' ');
+    let numeric = !padded
+        && trimmed.chars().any(|c| c.is_ascii_digit())
+        && trimmed
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.,'eE ".contains(c));
+
+    if numeric {
+        if let Ok(int) = trimmed.parse::<i64>() {
+            return AttributeValue::Int(int);
+        }
+        if let Ok(float) = locale.parse_float(trimmed) {
+            return AttributeValue::Float(float);
+        }
+    }
+
+    match trimmed.to_lowercase().as_str() {
+        "true" => AttributeValue::Boolean(true),
+        "false" => AttributeValue::Boolean(false),
+        _ => AttributeValue::String(value.into()),
+    }
+}
+
+/// Read an event stream from CSV data with header
+///
+/// By default, the whole file is read before the first trace is emitted, since rows of a case may
+/// be scattered all over the file. Within each trace, events are ordered by timestamp, if mapped.
+/// If the rows are grouped by case already, `grouped` allows to emit each trace as soon as the
+/// next case starts, which keeps memory usage low.
+///
+pub struct CsvReader<R: BufRead> {
+    records: Records<R>,
+    mapping: ColumnMapping,
+    types: BTreeMap<String, AttributeType>,
+    grouped: bool,
+    header: Option<Vec<String>>,
+    pending: VecDeque<Component>,
+    current: Option<(String, Trace)>,
+    exhausted: bool,
+    quality: QualityReport,
+    keys: KeyTable,
+    /// Locale of the mapping, resolved along with the header
+    locale: Locale,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R, mapping: ColumnMapping) -> Self {
+        CsvReader {
+            records: Records::new(reader, mapping.delimiter),
+            mapping,
+            types: BTreeMap::new(),
+            grouped: false,
+            header: None,
+            pending: VecDeque::new(),
+            current: None,
+            exhausted: false,
+            quality: QualityReport::default().max_findings(1000),
+            keys: KeyTable::default(),
+            locale: Locale::default(),
+        }
+    }
+
+    /// Declare the type of an attribute column instead of inferring it per value
+    pub fn coerce<C: Into<String>>(mut self, column: C, hint: AttributeType) -> Self {
+        self.types.insert(column.into(), hint);
+        self
+    }
+
+    /// Whether rows of the same case are adjacent
+    pub fn grouped(mut self, grouped: bool) -> Self {
+        self.grouped = grouped;
+        self
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::CsvError(format!("line {}: {}", self.records.line(), message))
+    }
+
+    fn read_header(&mut self) -> Result<Meta> {
+        let header = match self.records.next() {
+            Some(header) => header?,
+            None => return Err(Error::CsvError("missing header".into())),
+        };
+
+        let columns = [
+            Some(&self.mapping.case),
+            Some(&self.mapping.activity),
+            self.mapping.timestamp.as_ref(),
+        ];
+        for column in columns.iter().flatten() {
+            if !header.contains(column) {
+                return Err(self.error(format!("no column {:?}", column)));
+            }
+        }
+        self.header = Some(header);
+        self.locale = self.mapping.resolve_locale()?;
+
+        let mut meta = Meta::default();
+        meta.extensions.push(Concept::declare());
+        if self.mapping.timestamp.is_some() {
+            meta.extensions.push(Time::declare());
+        }
+        Ok(meta)
+    }
+
+    /// Read the next row as case and event
+    fn read_row(&mut self) -> Result<Option<(String, Event)>> {
+        let record = match self.records.next() {
+            Some(record) => record?,
+            None => return Ok(None),
+        };
+
+        let header = self.header.as_ref().unwrap();
+        let locale = &self.locale;
+        if record.len() != header.len() {
+            return Err(self.error(format!(
+                "expected {} fields, got {}",
+                header.len(),
+                record.len()
+            )));
+        }
+
+        let mut case = String::new();
+        let mut event = Event::default();
+        for (column, value) in header.iter().zip(record) {
+            if value.is_empty() {
+                continue;
+            }
+
+            let attribute = if *column == self.mapping.case {
+                case = value;
+                continue;
+            } else if *column == self.mapping.activity {
+                Attribute::new(self.keys.intern("concept:name"), value)
+            } else if Some(column) == self.mapping.timestamp.as_ref() {
+                let format = self.mapping.timestamp_format.as_deref();
+                match locale.parse_timestamp(&value, format) {
+                    Ok(timestamp) => Attribute::new(self.keys.intern("time:timestamp"), timestamp),
+                    Err(error) => return Err(self.error(error.to_string())),
+                }
+            } else {
+                match self.types.get(column) {
+                    Some(hint) => match coerce(&value, hint, locale) {
+                        Ok(value) => Attribute::new(self.keys.intern(column), value),
+                        Err(error) => {
+                            return Err(self.error(format!("column {:?}: {}", column, error)))
+                        }
+                    },
+                    None => Attribute::new(self.keys.intern(column), infer(&value, locale)),
+                }
+            };
+            event.attributes.insert(attribute);
+        }
+
+        Ok(Some((case, event)))
+    }
+
+    fn missing_case(&mut self) {
+        let location = format!("line {}", self.records.line());
+        self.quality.record(Finding::new(
+            Issue::MissingCaseId,
+            location,
+            "row has no case, read as standalone event",
+        ));
+    }
+
+    fn trace(case: String) -> Trace {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", case));
+        trace
+    }
+
+    /// Order the events of a trace by timestamp, events without one come first
+    fn sort(trace: &mut Trace) {
+        trace.events.sort_by_key(|e| {
+            e.get_value("time:timestamp")
+                .and_then(|t| t.try_date().ok().copied())
+        });
+    }
+
+    /// Read the whole file and queue all traces and standalone events
+    fn read_all(&mut self) -> Result<()> {
+        let mut traces: Vec<Trace> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut events = Vec::new();
+
+        while let Some((case, event)) = self.read_row()? {
+            if case.is_empty() {
+                self.missing_case();
+                events.push(event);
+                continue;
+            }
+            let i = *index.entry(case.clone()).or_insert_with(|| {
+                traces.push(Self::trace(case));
+                traces.len() - 1
+            });
+            traces[i].events.push(event);
+        }
+
+        for mut trace in traces {
+            Self::sort(&mut trace);
+            self.pending.push_back(Component::Trace(trace));
+        }
+        self.pending
+            .extend(events.into_iter().map(Component::Event));
+        Ok(())
+    }
+
+    /// Read rows until a trace is complete or a standalone event is found
+    fn read_grouped(&mut self) -> Result<()> {
+        while self.pending.is_empty() {
+            match self.read_row()? {
+                Some((case, event)) if case.is_empty() => {
+                    self.missing_case();
+                    self.pending.push_back(Component::Event(event))
+                }
+                Some((case, event)) => match &mut self.current {
+                    Some((current, trace)) if *current == case => trace.events.push(event),
+                    _ => {
+                        let mut trace = Self::trace(case.clone());
+                        trace.events.push(event);
+                        if let Some((_, mut done)) = self.current.replace((case, trace)) {
+                            Self::sort(&mut done);
+                            self.pending.push_back(Component::Trace(done));
+                        }
+                    }
+                },
+                None => {
+                    if let Some((_, mut done)) = self.current.take() {
+                        Self::sort(&mut done);
+                        self.pending.push_back(Component::Trace(done));
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead + Send> Stream for CsvReader<R> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.header.is_none() {
+            return Ok(Some(Component::Meta(self.read_header()?)));
+        }
+
+        if self.pending.is_empty() && !self.exhausted {
+            if self.grouped {
+                self.read_grouped()?;
+                self.exhausted = self.pending.is_empty();
+            } else {
+                self.read_all()?;
+                self.exhausted = true;
+            }
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        if self.quality.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![std::mem::take(&mut self.quality).into()])
+    }
+}
+
+impl PluginProvider for CsvReader<io::BufReader<File>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "CsvReader",
+            "Read events from a CSV file, one event per row",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the CSV file", AttributeType::String)
+                    .default_attr(
+                        "types",
+                        "Types of attribute columns, keyed by column, e.g. int, float or date",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    )
+                    .default_attr(
+                        "grouped",
+                        "Whether rows of the same case are adjacent",
+                        |k| (k, false).into(),
+                    )
+                    .optional_attr(
+                        "profile",
+                        "Column mapping file, takes precedence over the columns given",
+                        AttributeType::String,
+                    )
+                    .optional_attr("case", "Column of the case", AttributeType::String)
+                    .optional_attr("activity", "Column of the activity", AttributeType::String)
+                    .optional_attr("delimiter", "Field delimiter", AttributeType::String)
+                    .optional_attr(
+                        "timestamp",
+                        "Column of the timestamp",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "timestamp_format",
+                        "Format of the timestamps, e.g. %d.%m.%Y %H:%M",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "locale",
+                        "Locale to parse numbers and timestamps with, e.g. de",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let mut string = |key: &str| -> Result<Option<String>> {
+                        match parameters.acquire_attribute(key) {
+                            Ok(attribute) => Ok(Some(attribute.value.try_string()?.to_string())),
+                            Err(_) => Ok(None),
+                        }
+                    };
+
+                    // the mapping is given by a profile, by attributes or detected from the data
+                    let mapping = match (string("profile")?, string("case")?, string("activity")?) {
+                        (Some(profile), _, _) => ColumnMapping::load(profile)?,
+                        (None, Some(case), Some(activity)) => {
+                            let mut mapping = ColumnMapping::new(case, activity);
+                            if let Some(delimiter) = string("delimiter")? {
+                                mapping =
+                                    mapping.delimiter(delimiter.chars().next().unwrap_or(','));
+                            }
+                            if let Some(timestamp) = string("timestamp")? {
+                                mapping = mapping
+                                    .timestamp(timestamp, string("timestamp_format")?.as_deref());
+                            }
+                            if let Some(locale) = string("locale")? {
+                                mapping = mapping.locale(locale);
+                            }
+                            mapping
+                        }
+                        _ => detect_from(io::BufReader::new(File::open(&path)?), 100)?,
+                    };
+
+                    let mut reader =
+                        CsvReader::new(io::BufReader::new(File::open(&path)?), mapping).grouped(
+                            *parameters
+                                .acquire_attribute("grouped")?
+                                .value
+                                .try_boolean()?,
+                        );
+                    for column in parameters.acquire_attribute("types")?.value.try_list()? {
+                        let hint = match column.value.try_string()? {
+                            "string" => AttributeType::String,
+                            "id" => AttributeType::Id,
+                            "int" => AttributeType::Int,
+                            "float" => AttributeType::Float,
+                            "boolean" => AttributeType::Boolean,
+                            "date" => AttributeType::Date,
+                            other => {
+                                return Err(Error::CsvError(format!("unknown type {:?}", other)))
+                            }
+                        };
+                        reader = reader.coerce(column.key.as_str(), hint);
+                    }
+
+                    Ok(reader.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::compare::{assert_stream_eq, CompareOptions};
+    use crate::stream::log::Log;
+
+    use super::*;
+
+    const DATA: &str = "Auftrag;Schritt;Zeit;Betrag\n\
+        A-1;Anlegen;01.02.2021 08:00;12,50\n\
+        A-1;\"Prüfen; manuell\";01.02.2021 09:30;12,50\n\
+        A-2;Anlegen;02.02.2021 10:00;3\n\
+        A-2;Freigeben;02.02.2021 11:00;3\n\
+        A-3;Anlegen;03.02.2021 12:00;7\n\
+        A-3;Freigeben;03.02.2021 12:05;7\n\
+        A-4;Anlegen;04.02.2021 07:00;7\n\
+        A-4;Freigeben;04.02.2021 07:10;7\n";
+
+    #[test]
+    fn test_records() {
+        let data = "a,\"b,\"\"c\"\"\nd\",e\r\n\r\n,\n\n";
+        let mut records = Records::new(data.as_bytes(), ',');
+        assert_eq!(
+            records.next().unwrap().unwrap(),
+            vec!["a".to_string(), "b,\"c\"\nd".to_string(), "e".to_string()]
+        );
+        assert_eq!(records.line(), 1);
+        assert_eq!(
+            records.next().unwrap().unwrap(),
+            vec!["".to_string(), "".to_string()]
+        );
+        assert_eq!(records.line(), 4);
+        assert!(records.next().is_none());
+
+        assert!(Records::new("\"open".as_bytes(), ',')
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = DateTime::parse_from_rfc3339("2021-02-01T08:00:00+00:00").unwrap();
+        assert_eq!(
+            parse_timestamp("01.02.2021 08:00", "%d.%m.%Y %H:%M").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_timestamp("2021-02-01", "%Y-%m-%d").unwrap(),
+            expected - chrono::Duration::hours(8)
+        );
+        assert_eq!(
+            parse_timestamp("2021-02-01T09:00:00+01:00", TIMESTAMP_FORMATS[0]).unwrap(),
+            expected
+        );
+        assert!(parse_timestamp("yesterday", "%Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn test_infer() {
+        let en = Locale::default();
+        let de = Locale::from_tag("de").unwrap();
+        assert_eq!(infer("42", &en), AttributeValue::Int(42));
+        assert_eq!(infer("1,234.5", &en), AttributeValue::Float(1234.5));
+        assert_eq!(infer("1.234,5", &de), AttributeValue::Float(1234.5));
+        assert_eq!(infer("1,2", &de), AttributeValue::Float(1.2));
+        assert_eq!(infer("0.5", &en), AttributeValue::Float(0.5));
+        assert_eq!(infer("0", &en), AttributeValue::Int(0));
+        for value in &["1,2", "1,2,3", "02134", "-007"] {
+            assert_eq!(infer(value, &en), AttributeValue::String((*value).into()));
+        }
+        assert_eq!(infer("TRUE", &en), AttributeValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_detect() {
+        // no header hints at the case column but its cardinality
+        let mapping = detect_from(DATA.as_bytes(), 100).unwrap();
+        assert_eq!(
+            mapping,
+            ColumnMapping::new("Auftrag", "Schritt")
+                .delimiter(';')
+                .timestamp("Zeit", Some("%d.%m.%Y %H:%M"))
+        );
+
+        let data = "case_id,activity,amount\n1,a,1\n1,b,2\n2,a,3\n";
+        let mapping = detect_from(data.as_bytes(), 100).unwrap();
+        assert_eq!(mapping.case, "case_id");
+        assert_eq!(mapping.activity, "activity");
+        assert_eq!(mapping.timestamp, None);
+
+        assert!(detect_from("x\n1\n2\n".as_bytes(), 100).is_err());
+    }
+
+    #[test]
+    fn test_profile() {
+        let mapping = ColumnMapping::new("case", "activity")
+            .delimiter('\t')
+            .timestamp("time", Some("%Y-%m-%d %H:%M"))
+            .locale("de");
+        let mut profile = Vec::new();
+        mapping.write(&mut profile).unwrap();
+        assert_eq!(ColumnMapping::read(profile.as_slice()).unwrap(), mapping);
+        assert!(mapping.resolve_locale().unwrap().decimal_comma);
+
+        assert!(ColumnMapping::read("case = c\n".as_bytes()).is_err());
+        assert!(ColumnMapping::read("case = c\nactivity = a\ncolour = red\n".as_bytes()).is_err());
+    }
+
+    fn reader(data: &'static str, grouped: bool) -> CsvReader<&'static [u8]> {
+        let mapping = ColumnMapping::new("Auftrag", "Schritt")
+            .delimiter(';')
+            .timestamp("Zeit", Some("%d.%m.%Y %H:%M"))
+            .locale("de");
+        CsvReader::new(data.as_bytes(), mapping).grouped(grouped)
+    }
+
+    #[test]
+    fn test_csv_reader() {
+        let mut log = Log::default();
+        log.consume(&mut reader(DATA, false)).unwrap();
+
+        assert_eq!(log.meta.extensions.len(), 2);
+        assert_eq!(log.traces.len(), 4);
+        let trace = &log.traces[0];
+        assert_eq!(trace.get_value("concept:name"), Some(&"A-1".into()));
+        assert_eq!(
+            trace.events[1].get_value("concept:name"),
+            Some(&"Prüfen; manuell".into())
+        );
+        assert_eq!(trace.events[0].get_value("Betrag"), Some(&12.5.into()));
+        assert_eq!(
+            log.traces[1].events[0].get_value("Betrag"),
+            Some(&AttributeValue::Int(3))
+        );
+        assert_eq!(
+            trace.events[0].get_value("time:timestamp"),
+            Some(&AttributeValue::Date(
+                DateTime::parse_from_rfc3339("2021-02-01T08:00:00+00:00").unwrap()
+            ))
+        );
+
+        // declared types, scattered cases and standalone events
+        let data = "Auftrag;Schritt;Zeit;Betrag\n\
+            A-1;Anlegen;01.02.2021 08:00;3\n\
+            A-2;Anlegen;01.02.2021 08:30;4\n\
+            ;Wartung;01.02.2021 08:45;\n\
+            A-1;Prüfen;01.02.2021 08:15;1,5\n";
+        let mut log = Log::default();
+        let artifacts = log
+            .consume(&mut reader(data, false).coerce("Betrag", AttributeType::Float))
+            .unwrap();
+        assert_eq!(log.traces.len(), 2);
+        assert_eq!(log.events.len(), 1);
+        let report = AnyArtifact::find::<QualityReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.findings[0].issue, Issue::MissingCaseId);
+        assert_eq!(report.findings[0].location, "line 4");
+        assert_eq!(
+            log.traces[0].events[0].get_value("Betrag"),
+            Some(&3.0.into())
+        );
+        assert!(log.events[0].get_value("Betrag").is_none());
+
+        // grouped reading splits scattered cases
+        let mut log = Log::default();
+        log.consume(&mut reader(data, true)).unwrap();
+        assert_eq!(log.traces.len(), 3);
+
+        // blank lines are skipped, errors refer to physical lines
+        let data = "Auftrag;Schritt;Zeit;Betrag\n\
+            A-1;\"Anlegen\nneu\";01.02.2021 08:00;3\n\
+            \n\
+            A-1;Prüfen;01.02.2021 08:15;1\n\n";
+        let mut log = Log::default();
+        log.consume(&mut reader(data, false)).unwrap();
+        assert_eq!(log.traces[0].events.len(), 2);
+
+        // events without timestamp come first
+        let data = "Auftrag;Schritt;Zeit;Betrag\n\
+            A-1;b;01.02.2021 09:00;1\n\
+            A-1;a;;1\n\
+            A-1;c;01.02.2021 08:00;1\n";
+        let mut log = Log::default();
+        log.consume(&mut reader(data, false)).unwrap();
+        let activities: Vec<_> = log.traces[0]
+            .events
+            .iter()
+            .map(|e| e.get_value("concept:name").unwrap().try_string().unwrap())
+            .collect();
+        assert_eq!(activities, ["a", "c", "b"]);
+
+        let invalid = "Auftrag;Schritt;Zeit;Betrag\nA-1;\"An\nlegen\";01.02.2021 08:00;3\n\n\
+            A-1;Anlegen;gestern;3\n";
+        match Log::default().consume(&mut reader(invalid, false)) {
+            Err(Error::CsvError(message)) => assert!(message.starts_with("line 5"), "{}", message),
+            other => panic!("expected csv error, got {:?}", other),
+        }
+        assert!(Log::default()
+            .consume(&mut reader("Fall;Schritt\n", false))
+            .is_err());
+    }
+
+    #[test]
+    fn test_csv_writer() {
+        let mut original = Buffer::default();
+        original.consume(&mut reader(DATA, false)).unwrap();
+
+        let mut writer = CsvWriter::new(Vec::new()).delimiter(';');
+        writer.consume(&mut original.clone()).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next(),
+            Some("case:concept:name;Betrag;concept:name;time:timestamp")
+        );
+        assert_eq!(
+            lines.nth(1),
+            Some("A-1;12.5;\"Prüfen; manuell\";2021-02-01T09:30:00+00:00")
+        );
+
+        // round trip
+        let mapping = ColumnMapping::new("case:concept:name", "concept:name")
+            .delimiter(';')
+            .timestamp("time:timestamp", None);
+        let mut copy = CsvReader::new(output.as_bytes(), mapping);
+        assert_stream_eq(&mut original.clone(), &mut copy, &CompareOptions::default());
+
+        let mut writer = CsvWriter::new(Vec::new())
+            .header(false)
+            .columns(vec!["concept:name", "missing"]);
+        writer.consume(&mut original).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(output.lines().next(), Some("Anlegen,"));
+    }
+}
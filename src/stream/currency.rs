@@ -0,0 +1,443 @@
+//! Convert costs into a common currency
+//!
+//! Logs that span several countries record costs in different currencies, which renders sums and
+//! averages meaningless. The `CurrencyConverter` handler normalizes the `cost:total` attribute of
+//! traces and events (see the cost extension) to a target currency and updates `cost:currency`
+//! accordingly.
+//!
+//! Rates are given by a `RateTable` that tells how many units of the target currency one unit of a
+//! source currency is worth. The amounts of the cost drivers listed by `cost:drivers` are converted
+//! along with the total, as they share its currency.
+//!
+//! A rate may be valid from a certain point in time on. Dated rates apply
+//! to components whose `time:timestamp` is at or after the respective date, the latest one wins.
+//! Undated rates apply otherwise. Rate tables can be read from CSV files with the columns
+//! `currency`, `rate` and optionally `valid_from`.
+//!
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::csv::Records;
+use crate::stream::extension::{Cost, Extension};
+use crate::stream::locale::Locale;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeContainer, AttributeMap, AttributeType,
+    AttributeValue, Event, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// List of the cost drivers of a trace or event
+const DRIVERS: &str = "cost:drivers";
+
+/// Amount of a cost driver, nested in the entries of `cost:drivers`
+const AMOUNT: &str = "cost:amount";
+
+/// Exchange rates into a single target currency
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateTable {
+    /// Rates per source currency, undated first, then ordered by date
+    rates: BTreeMap<String, Vec<(Option<DateTime>, f64)>>,
+}
+
+impl RateTable {
+    /// Add a rate, optionally valid from the given point in time on
+    pub fn rate<C: Into<String>>(mut self, currency: C, rate: f64, from: Option<DateTime>) -> Self {
+        let rates = self.rates.entry(currency.into()).or_default();
+        rates.retain(|(f, _)| *f != from);
+        rates.push((from, rate));
+        rates.sort_by_key(|(f, _)| *f);
+        self
+    }
+
+    /// Rate of a currency at the given point in time
+    pub fn lookup(&self, currency: &str, at: Option<&DateTime>) -> Option<f64> {
+        let rates = self.rates.get(currency)?;
+
+        at.and_then(|at| {
+            rates
+                .iter()
+                .rev()
+                .find(|(from, _)| from.as_ref().is_some_and(|f| f <= at))
+        })
+        .or_else(|| rates.iter().find(|(from, _)| from.is_none()))
+        .map(|(_, rate)| *rate)
+    }
+
+    /// Read a CSV rate table with header `currency,rate[,valid_from]`
+    pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut records = Records::new(reader, ',');
+        let header = records
+            .next()
+            .ok_or_else(|| Error::CsvError("empty rate table".into()))??;
+        let column = |name: &str| header.iter().position(|h| h.trim() == name);
+        let (currency, rate) = match (column("currency"), column("rate")) {
+            (Some(currency), Some(rate)) => (currency, rate),
+            _ => {
+                return Err(Error::CsvError(
+                    "rate table requires columns currency and rate".into(),
+                ))
+            }
+        };
+        let from = column("valid_from");
+        let locale = Locale::default();
+
+        records.try_fold(RateTable::default(), |table, record| {
+            let record = record?;
+            let field = |i: usize| {
+                record.get(i).map(|f| f.trim()).ok_or_else(|| {
+                    Error::CsvError(format!("incomplete rate table record {:?}", record))
+                })
+            };
+            let valid_from = match from.map(field).transpose()? {
+                Some(f) if !f.is_empty() => Some(locale.parse_timestamp(f, None)?),
+                _ => None,
+            };
+
+            Ok(table.rate(
+                field(currency)?,
+                locale.parse_float(field(rate)?)?,
+                valid_from,
+            ))
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+impl TryFrom<&[Attribute]> for RateTable {
+    type Error = Error;
+
+    /// Read attributes keyed by currency with the rate as value and an optional child `from`
+    fn try_from(attributes: &[Attribute]) -> Result<Self> {
+        attributes
+            .iter()
+            .try_fold(RateTable::default(), |table, a| {
+                let from = a
+                    .children
+                    .iter()
+                    .find(|c| c.key == "from")
+                    .map(|c| c.value.try_date().cloned())
+                    .transpose()?;
+                Ok(table.rate(a.key.as_str(), *a.value.try_float()?, from))
+            })
+    }
+}
+
+/// Number of converted and unconvertible costs per source currency
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversionReport {
+    pub converted: BTreeMap<String, usize>,
+    pub unconverted: BTreeMap<String, usize>,
+}
+
+#[typetag::serde]
+impl Artifact for ConversionReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Normalize costs to a target currency
+///
+/// Costs without currency are assumed to be in the target currency already. If no rate is known
+/// for a currency, the stream fails unless `strict` is disabled, in which case the cost remains
+/// as is and is reported as unconverted.
+///
+#[derive(Debug, Clone)]
+pub struct CurrencyConverter {
+    target: String,
+    rates: RateTable,
+    strict: bool,
+    report: ConversionReport,
+}
+
+impl CurrencyConverter {
+    pub fn new<T: Into<String>>(target: T, rates: RateTable) -> Self {
+        CurrencyConverter {
+            target: target.into(),
+            rates,
+            strict: true,
+            report: ConversionReport::default(),
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Rate to convert the costs of a trace or event with, if they need conversion
+    fn rate(&mut self, component: &dyn AttributeContainer) -> Result<Option<f64>> {
+        let cost = Cost::view(component)?;
+        let priced = cost.total.is_some() || component.get_value(DRIVERS).is_some();
+        let currency = match cost.currency {
+            Some(currency) if priced && currency != self.target => currency,
+            _ => return Ok(None),
+        };
+
+        let timestamp = component
+            .get_value("time:timestamp")
+            .and_then(|t| t.try_date().ok());
+        match self.rates.lookup(currency, timestamp) {
+            Some(rate) => {
+                *self
+                    .report
+                    .converted
+                    .entry(currency.to_string())
+                    .or_default() += 1;
+                Ok(Some(rate))
+            }
+            None if self.strict => Err(Error::AttributeError(format!(
+                "no rate from {} to {} at {:?}",
+                currency, self.target, timestamp
+            ))),
+            None => {
+                *self
+                    .report
+                    .unconverted
+                    .entry(currency.to_string())
+                    .or_default() += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Convert total and cost driver amounts at the given rate
+    fn apply(&self, attributes: &mut AttributeMap, rate: f64) -> Result<()> {
+        if let Some(total) = attributes.get_value_mut(Cost::TOTAL) {
+            *total = AttributeValue::Float(total.try_number()? * rate);
+        }
+        if let Some(AttributeValue::List(drivers)) = attributes.get_value_mut(DRIVERS) {
+            let amounts = drivers
+                .iter_mut()
+                .flat_map(|driver| driver.children.iter_mut())
+                .filter(|attribute| attribute.key.as_str() == AMOUNT);
+            for amount in amounts {
+                amount.value = AttributeValue::Float(amount.value.try_number()? * rate);
+            }
+        }
+        attributes.insert((Cost::CURRENCY, self.target.as_str()));
+        Ok(())
+    }
+
+    fn convert_event(&mut self, event: &mut Event) -> Result<()> {
+        if let Some(rate) = self.rate(event)? {
+            self.apply(&mut event.attributes, rate)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for CurrencyConverter {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        if let Some(rate) = self.rate(&trace)? {
+            self.apply(&mut trace.attributes, rate)?;
+        }
+        for event in trace.events.iter_mut() {
+            self.convert_event(event)?;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.convert_event(&mut event)?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.report.clone().into()])
+    }
+}
+
+impl PluginProvider for CurrencyConverter {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "CurrencyConverter",
+            "Convert cost:total and the cost driver amounts into a target currency using a rate table",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be converted")
+                    .typed_attr(
+                        "target",
+                        "Currency to convert into, e.g. EUR",
+                        AttributeType::String,
+                    )
+                    .default_attr(
+                        "rates",
+                        "Rates keyed by source currency, with an optional child `from` date",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    )
+                    .default_attr("strict", "Fail on currencies without rate", |k| {
+                        (k, true).into()
+                    })
+                    .optional_attr(
+                        "rate_file",
+                        "CSV file of rates, complemented by the rates attribute",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    // a rate file, if given, is complemented by rates given as attributes
+                    let mut rates = match parameters.acquire_attribute("rate_file") {
+                        Ok(path) => RateTable::load(path.value.try_string()?)?,
+                        Err(_) => RateTable::default(),
+                    };
+                    for (currency, entries) in RateTable::try_from(
+                        parameters.acquire_attribute("rates")?.value.try_list()?,
+                    )?
+                    .rates
+                    {
+                        for (from, rate) in entries {
+                            rates = rates.rate(currency.as_str(), rate, from);
+                        }
+                    }
+
+                    let converter = CurrencyConverter::new(
+                        parameters.acquire_attribute("target")?.value.try_string()?,
+                        rates,
+                    )
+                    .strict(
+                        *parameters
+                            .acquire_attribute("strict")?
+                            .value
+                            .try_boolean()?,
+                    );
+
+                    Ok(
+                        Observer::from((parameters.acquire_stream("inner")?, converter))
+                            .into_boxed(),
+                    )
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{Component, Sink};
+
+    use super::*;
+
+    fn timestamp(rfc3339: &str) -> DateTime {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap()
+    }
+
+    #[test]
+    fn test_rate_table() {
+        let csv = "currency,rate,valid_from\nUSD,n/a,\n";
+        assert!(RateTable::read(csv.as_bytes()).is_err());
+
+        let csv = "currency,rate,valid_from\nUSD,0.9,\nUSD,0.8,2021-01-01\nGBP,1.2,\n";
+        let table = RateTable::read(csv.as_bytes()).unwrap();
+        assert_eq!(table.lookup("USD", None), Some(0.9));
+        assert_eq!(
+            table.lookup("USD", Some(&timestamp("2020-12-31T23:00:00+00:00"))),
+            Some(0.9)
+        );
+        assert_eq!(
+            table.lookup("USD", Some(&timestamp("2021-06-01T00:00:00+00:00"))),
+            Some(0.8)
+        );
+        assert_eq!(table.lookup("GBP", None), Some(1.2));
+        assert_eq!(table.lookup("CHF", None), None);
+    }
+
+    #[test]
+    fn test_currency_converter() {
+        let mut trace = Trace::default();
+        trace.attributes.insert((Cost::TOTAL, 100.0));
+        trace.attributes.insert((Cost::CURRENCY, "GBP"));
+        let mut labour = Attribute::new("cost:driver", "labour");
+        labour.children.push(Attribute::new(AMOUNT, 75));
+        trace
+            .attributes
+            .insert((DRIVERS, AttributeValue::List(vec![labour])));
+        for (total, currency, time) in [
+            (10.0, "USD", "2020-06-01T00:00:00+00:00"),
+            (10.0, "USD", "2021-06-01T00:00:00+00:00"),
+            (10.0, "EUR", "2021-06-01T00:00:00+00:00"),
+        ] {
+            let mut event = Event::default();
+            event.attributes.insert((Cost::TOTAL, total));
+            event.attributes.insert((Cost::CURRENCY, currency));
+            event.attributes.insert(("time:timestamp", timestamp(time)));
+            trace.events.push(event);
+        }
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let rates = RateTable::default()
+            .rate("USD", 0.9, None)
+            .rate("USD", 0.8, Some(timestamp("2021-01-01T00:00:00+00:00")))
+            .rate("GBP", 1.2, None);
+        let converter = CurrencyConverter::new("EUR", rates.clone());
+        let mut log = Log::default();
+        let artifacts = log
+            .consume(&mut converter.into_observer(buffer.clone()))
+            .unwrap();
+
+        let trace = &log.traces[0];
+        assert!(is_close!(Cost::view(trace).unwrap().total.unwrap(), 120.0));
+        let drivers = trace
+            .attributes
+            .get_value(DRIVERS)
+            .unwrap()
+            .try_list()
+            .unwrap();
+        let amount = drivers[0].children[0].value.try_number().unwrap();
+        assert!(is_close!(amount, 90.0));
+        let totals: Vec<_> = trace
+            .events
+            .iter()
+            .map(|e| Cost::view(e).unwrap().total.unwrap())
+            .collect();
+        assert!(is_close!(totals[0], 9.0));
+        assert!(is_close!(totals[1], 8.0));
+        assert!(is_close!(totals[2], 10.0));
+        assert!(trace
+            .events
+            .iter()
+            .all(|e| Cost::view(e).unwrap().currency == Some("EUR")));
+
+        let report =
+            AnyArtifact::find::<ConversionReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.converted["USD"], 2);
+
+        // GBP is unknown when converting to USD
+        let converter = CurrencyConverter::new("USD", RateTable::default().rate("EUR", 1.1, None));
+        assert!(Log::default()
+            .consume(&mut converter.clone().into_observer(buffer.clone()))
+            .is_err());
+        let artifacts = Log::default()
+            .consume(&mut converter.strict(false).into_observer(buffer))
+            .unwrap();
+        let report =
+            AnyArtifact::find::<ConversionReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.unconverted["GBP"], 1);
+        assert_eq!(report.converted["EUR"], 1);
+    }
+}
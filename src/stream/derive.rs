@@ -0,0 +1,210 @@
+//! Add computed attributes
+//!
+//! The `Derive` handler evaluates expressions (see `stream::expression`) on traces or events and
+//! stores the results as new attributes, e.g. a gross price or the duration of an activity. In
+//! flow graphs, each derivation is an attribute whose key names the target attribute and whose
+//! value is the expression. The optional child `scope` is either `event` (default) or `trace`.
+//!
+//! Derivations are applied in order, so later ones may use the results of earlier ones. If an
+//! expression references a missing attribute, the target attribute is left untouched.
+//!
+
+use std::convert::TryFrom;
+
+use crate::stream::expression::Expression;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{Attribute, AttributeContainer, AttributeType, Event, Scope, Stream, Trace};
+use crate::{Error, Result};
+
+/// Compute a single attribute
+#[derive(Debug, Clone)]
+pub struct Derivation {
+    pub key: String,
+    pub expression: Expression,
+    pub scope: Scope,
+}
+
+impl Derivation {
+    pub fn new<K: Into<String>>(key: K, expression: &str, scope: Scope) -> Result<Self> {
+        Ok(Derivation {
+            key: key.into(),
+            expression: Expression::parse(expression)?,
+            scope,
+        })
+    }
+
+    /// Evaluate the derivation on a trace or event, `None` if an attribute is missing
+    pub fn evaluate(&self, container: &dyn AttributeContainer) -> Result<Option<Attribute>> {
+        match self.expression.evaluate(container) {
+            Ok(value) => Ok(value.map(|v| Attribute::new(self.key.as_str(), v))),
+            Err(error) => Err(Error::ExpressionError(format!(
+                "cannot derive {:?} from {}: {}",
+                self.key, self.expression, error
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&Attribute> for Derivation {
+    type Error = Error;
+
+    fn try_from(attribute: &Attribute) -> Result<Self> {
+        let scope = attribute
+            .children
+            .iter()
+            .find(|c| c.key == "scope")
+            .map(|c| -> Result<String> { Ok(c.value.try_string()?.to_string()) })
+            .transpose()?;
+
+        Derivation::new(
+            attribute.key.as_str(),
+            attribute.value.try_string()?,
+            Scope::try_from(scope)?,
+        )
+    }
+}
+
+/// Add attributes computed from expressions to traces and events
+#[derive(Debug, Clone, Default)]
+pub struct Derive {
+    derivations: Vec<Derivation>,
+}
+
+impl Derive {
+    /// Add a derivation, derivations are applied in order of registration
+    pub fn derivation(mut self, derivation: Derivation) -> Self {
+        self.derivations.push(derivation);
+        self
+    }
+
+    fn derive_event(&self, event: &mut Event) -> Result<()> {
+        for derivation in self.derivations.iter().filter(|d| d.scope == Scope::Event) {
+            if let Some(attribute) = derivation.evaluate(event)? {
+                event.attributes.insert(attribute);
+            }
+        }
+        Ok(())
+    }
+
+    fn derive_trace(&self, trace: &mut Trace) -> Result<()> {
+        for derivation in self.derivations.iter().filter(|d| d.scope == Scope::Trace) {
+            if let Some(attribute) = derivation.evaluate(trace)? {
+                trace.attributes.insert(attribute);
+            }
+        }
+        for event in trace.events.iter_mut() {
+            self.derive_event(event)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for Derive {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        self.derive_trace(&mut trace)?;
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.derive_event(&mut event)?;
+        }
+
+        Ok(Some(event))
+    }
+}
+
+impl PluginProvider for Derive {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Derive",
+            "Add attributes computed from expressions over other attributes",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be extended")
+                    .typed_attr(
+                        "derivations",
+                        "List of expressions, each keyed by the attribute to compute",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let derive = parameters
+                        .acquire_attribute("derivations")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(Derivation::try_from)
+                        .collect::<Result<Vec<_>>>()?
+                        .into_iter()
+                        .fold(Derive::default(), |derive, d| derive.derivation(d));
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, derive)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeValue, Component, Sink};
+
+    use super::*;
+
+    #[test]
+    fn test_derive() {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", "case"));
+        for cost in [100, 50] {
+            let mut event = Event::default();
+            event.attributes.insert(("cost:total", cost));
+            trace.events.push(event);
+        }
+        trace.events.push(Event::default());
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let derivations = [
+            Attribute::new("gross", r#""cost:total" * 1.19"#),
+            Attribute::new("expensive", r#""gross" > 100"#),
+            Attribute::with_children(
+                "label",
+                r#"'#' + "concept:name""#,
+                vec![Attribute::new("scope", "trace")],
+            ),
+        ];
+        let derive = derivations
+            .iter()
+            .map(|d| Derivation::try_from(d).unwrap())
+            .fold(Derive::default(), |derive, d| derive.derivation(d));
+
+        let mut log = Log::default();
+        log.consume(&mut derive.into_observer(buffer)).unwrap();
+
+        let trace = &log.traces[0];
+        assert_eq!(trace.get_value("label"), Some(&"#case".into()));
+        let expensive: Vec<_> = trace
+            .events
+            .iter()
+            .map(|e| e.get_value("expensive").cloned())
+            .collect();
+        assert_eq!(
+            expensive,
+            vec![
+                Some(AttributeValue::Boolean(true)),
+                Some(AttributeValue::Boolean(false)),
+                None
+            ]
+        );
+
+        let invalid = Attribute::new("x", "1 +");
+        assert!(Derivation::try_from(&invalid).is_err());
+    }
+}
@@ -0,0 +1,1449 @@
+//! Directly-follows graphs
+//!
+//! A directly-follows graph (DFG) counts how often an activity is directly followed by another one
+//! within the same case. The `OnlineDfg` handler maintains such a graph over an unbounded stream.
+//! With a forgetting factor below one, older observations decay exponentially such that the graph
+//! always reflects the recent behaviour of the process. The `IncrementalOnlineDfg` plugin
+//! additionally sends snapshots of the graph to a data channel while the stream is consumed.
+//!
+//! For logs of complete cases, the `DfgGenerator` handler counts plain frequencies, including the
+//! activities that end a case. Activities are given by an attribute or by the concatenated values
+//! of a classifier declared in the stream's meta data.
+//!
+//! In performance mode, the `DfgGenerator` additionally keeps all durations between directly
+//! following events and emits their distribution per relation, i.e. mean, median and
+//! percentiles, as a `PerformanceDfg` for bottleneck analysis.
+//!
+//! A DFG can be rendered into a standalone, interactive HTML page with frequency and performance
+//! annotations, see `Dfg::to_html` and the `HtmlProcessMap` sink.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::channel::TypedSender;
+use crate::stream::graphml::{GraphMl, KeyScope, KeyType, ToGraphMl};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, Classifier, Component, Event, Flavor,
+    Meta, Sink, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+// weights are stored unscaled, this is the point to fold the scale back into them
+const MIN_SCALE: f64 = 1e-100;
+
+// placeholders are `{{TITLE}}` (HTML escaped) and `{{DATA}}` (JSON)
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font-family: sans-serif; margin: 0; }
+header { padding: 8px 16px; background: #eee; display: flex; gap: 24px; align-items: center; }
+svg { display: block; }
+.node rect { fill: #dbe9f6; stroke: #3b6e9c; rx: 6; }
+.node.start rect { stroke-width: 3; }
+.edge path { fill: none; stroke: #666; }
+text { font-size: 12px; }
+</style>
+</head>
+<body>
+<header>
+<strong>{{TITLE}}</strong>
+<label><input type="radio" name="view" value="frequency" checked> frequency</label>
+<label><input type="radio" name="view" value="performance"> performance</label>
+<label>min. relative edge weight <input type="range" id="threshold" min="0" max="100" value="0"></label>
+</header>
+<svg id="map"></svg>
+<script>
+const DFG = {{DATA}};
+const NS = "http://www.w3.org/2000/svg", W = 160, H = 40, GX = 60, GY = 90;
+
+function duration(s) {
+  if (s === null) return "n/a";
+  const parts = [];
+  for (const [unit, f] of [["d", 86400], ["h", 3600], ["m", 60], ["s", 1]]) {
+    if (s >= f && parts.length < 2) { const n = Math.floor(s / f); parts.push(n + unit); s -= n * f; }
+  }
+  return parts.length ? parts.join(" ") : "0s";
+}
+
+function weight(w) { return Number.isInteger(w) ? String(w) : w.toFixed(2); }
+
+function element(tag, attributes, parent, text) {
+  const e = document.createElementNS(NS, tag);
+  for (const k in attributes) e.setAttribute(k, attributes[k]);
+  if (text !== undefined) e.textContent = text;
+  parent.appendChild(e);
+  return e;
+}
+
+// assign layers by breadth-first search from start activities
+function layers() {
+  const layer = {}, queue = DFG.activities.filter(a => a.start > 0).map(a => a.name);
+  queue.forEach(n => layer[n] = 0);
+  while (queue.length) {
+    const n = queue.shift();
+    DFG.edges.filter(e => e.from === n && !(e.to in layer)).forEach(e => { layer[e.to] = layer[n] + 1; queue.push(e.to); });
+  }
+  let max = Math.max(-1, ...Object.values(layer));
+  DFG.activities.filter(a => !(a.name in layer)).forEach(a => layer[a.name] = ++max);
+  return layer;
+}
+
+function render() {
+  const view = document.querySelector("input[name=view]:checked").value;
+  const threshold = document.getElementById("threshold").value / 100;
+  const svg = document.getElementById("map");
+  svg.innerHTML = "";
+
+  const layer = layers(), rows = {}, column = {};
+  DFG.activities.forEach(a => { const l = layer[a.name]; column[a.name] = rows[l] || 0; rows[l] = column[a.name] + 1; });
+  const columns = Math.max(1, ...Object.values(rows)), depth = Math.max(0, ...Object.values(layer)) + 1;
+  svg.setAttribute("width", columns * (W + GX) + GX);
+  svg.setAttribute("height", depth * (H + GY) + GY);
+  const center = n => [GX + W / 2 + (column[n] + (columns - rows[layer[n]]) / 2) * (W + GX), GY / 2 + H / 2 + layer[n] * (H + GY)];
+
+  const marker = element("marker", {id: "arrow", viewBox: "0 0 10 10", refX: 10, refY: 5, markerWidth: 6, markerHeight: 6, orient: "auto"}, element("defs", {}, svg));
+  element("path", {d: "M0,0 L10,5 L0,10 z", fill: "#666"}, marker);
+
+  const max = Math.max(0, ...DFG.edges.map(e => e.weight));
+  DFG.edges.filter(e => e.weight >= threshold * max).forEach(e => {
+    const [x1, y1] = center(e.from), [x2, y2] = center(e.to), g = element("g", {class: "edge"}, svg);
+    let d, lx, ly;
+    if (e.from === e.to) {
+      d = `M${x1 + W / 2},${y1 - 8} C${x1 + W / 2 + 50},${y1 - 30} ${x1 + W / 2 + 50},${y1 + 30} ${x1 + W / 2},${y1 + 8}`;
+      [lx, ly] = [x1 + W / 2 + 42, y1];
+    } else {
+      const back = layer[e.to] <= layer[e.from], sx = back ? W / 2 + 40 : 0;
+      const p = [[x1, y1 + H / 2], [x1 + sx, y1 + H / 2 + GY / 2], [x2 + sx, y2 - H / 2 - GY / 2], [x2, y2 - H / 2]];
+      d = `M${p[0]} C${p[1]} ${p[2]} ${p[3]}`;
+      [lx, ly] = [0, 1].map(i => (p[0][i] + 3 * p[1][i] + 3 * p[2][i] + p[3][i]) / 8);
+    }
+    element("path", {d, "stroke-width": 1 + 4 * e.weight / (max || 1), "marker-end": "url(#arrow)"}, g);
+    element("text", {x: lx + 4, y: ly}, g, view === "frequency" ? weight(e.weight) : duration(e.mean));
+    element("title", {}, g, `${e.from} > ${e.to}: ${weight(e.weight)}, mean ${duration(e.mean)}`);
+  });
+
+  DFG.activities.forEach(a => {
+    const [x, y] = center(a.name), g = element("g", {class: a.start > 0 ? "node start" : "node"}, svg);
+    element("rect", {x: x - W / 2, y: y - H / 2, width: W, height: H}, g);
+    element("text", {x, y: y - 2, "text-anchor": "middle"}, g, a.name);
+    element("text", {x, y: y + 13, "text-anchor": "middle"}, g, weight(a.weight));
+    element("title", {}, g, `${a.name}: ${weight(a.weight)} (start: ${weight(a.start)})`);
+  });
+}
+
+document.querySelectorAll("input").forEach(i => i.addEventListener("input", render));
+render();
+</script>
+</body>
+</html>
+"##;
+
+/// Escape text to be placed into HTML
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a JSON string literal that is safe to be embedded into a script tag
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+                json.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Throughput time of a directly-follows relation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Performance {
+    /// Weight of the observations with known duration
+    pub weight: f64,
+    /// Weighted sum of durations in seconds
+    pub seconds: f64,
+}
+
+impl Performance {
+    /// Mean duration in seconds
+    pub fn mean(&self) -> Option<f64> {
+        if self.weight > 0.0 {
+            Some(self.seconds / self.weight)
+        } else {
+            None
+        }
+    }
+}
+
+// percentiles reported in performance mode unless configured otherwise
+const DEFAULT_PERCENTILES: [f64; 4] = [25.0, 75.0, 90.0, 95.0];
+
+/// Distribution of the throughput times of a directly-follows relation, in seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DurationStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Pairs of percentile, between 0 and 100, and duration
+    pub percentiles: Vec<(f64, f64)>,
+}
+
+impl DurationStats {
+    /// Summarize durations, if any
+    pub fn new(durations: &[f64], percentiles: &[f64]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+
+        let mut sorted = durations.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        Some(DurationStats {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median: Self::interpolate(&sorted, 50.0),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            percentiles: percentiles
+                .iter()
+                .map(|p| (*p, Self::interpolate(&sorted, *p)))
+                .collect(),
+        })
+    }
+
+    /// Duration at the given percentile, if reported
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        self.percentiles
+            .iter()
+            .find(|(p, _)| *p == percentile)
+            .map(|(_, d)| *d)
+    }
+
+    // linear interpolation between closest ranks
+    fn interpolate(sorted: &[f64], percentile: f64) -> f64 {
+        let rank = percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+    }
+}
+
+/// Directly-follows graph annotated with the distribution of throughput times
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceDfg {
+    /// Throughput times of each directly-follows relation, indexed by source and target activity
+    pub edges: BTreeMap<String, BTreeMap<String, DurationStats>>,
+}
+
+impl PerformanceDfg {
+    /// Throughput times of the directly-follows relation `from` > `to`, if known
+    pub fn stats(&self, from: &str, to: &str) -> Option<&DurationStats> {
+        self.edges.get(from).and_then(|targets| targets.get(to))
+    }
+}
+
+#[typetag::serde]
+impl Artifact for PerformanceDfg {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Weighted directly-follows graph
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dfg {
+    /// Weight of each activity
+    pub activities: BTreeMap<String, f64>,
+    /// Weight of each activity starting a case
+    pub start: BTreeMap<String, f64>,
+    /// Weight of each activity ending a case, only known for complete cases
+    #[serde(default)]
+    pub end: BTreeMap<String, f64>,
+    /// Weight of each directly-follows relation, indexed by source and target activity
+    pub edges: BTreeMap<String, BTreeMap<String, f64>>,
+    /// Throughput time of each directly-follows relation, indexed like `edges`
+    #[serde(default)]
+    pub performance: BTreeMap<String, BTreeMap<String, Performance>>,
+}
+
+impl Dfg {
+    /// Weight of the directly-follows relation `from` > `to`
+    pub fn weight(&self, from: &str, to: &str) -> f64 {
+        self.edges
+            .get(from)
+            .and_then(|targets| targets.get(to))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Mean duration in seconds of the directly-follows relation `from` > `to`, if known
+    pub fn mean_duration(&self, from: &str, to: &str) -> Option<f64> {
+        self.performance
+            .get(from)
+            .and_then(|targets| targets.get(to))
+            .and_then(|performance| performance.mean())
+    }
+
+    /// Render the graph into a standalone, interactive HTML page
+    ///
+    /// The page embeds all data and scripts, i.e. it can be opened without a server. Edges can be
+    /// annotated with either their frequency or their mean duration and filtered by weight.
+    ///
+    pub fn to_html(&self, title: &str) -> String {
+        let activities = self
+            .activities
+            .iter()
+            .map(|(name, weight)| {
+                format!(
+                    r#"{{"name":{},"weight":{},"start":{}}}"#,
+                    json_string(name),
+                    weight,
+                    self.start.get(name).copied().unwrap_or(0.0)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .edges
+            .iter()
+            .flat_map(|(from, targets)| targets.iter().map(move |(to, w)| (from, to, w)))
+            .map(|(from, to, weight)| {
+                format!(
+                    r#"{{"from":{},"to":{},"weight":{},"mean":{}}}"#,
+                    json_string(from),
+                    json_string(to),
+                    weight,
+                    match self.mean_duration(from, to) {
+                        Some(mean) => mean.to_string(),
+                        None => "null".to_string(),
+                    }
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let data = format!(
+            r#"{{"activities":[{}],"edges":[{}]}}"#,
+            activities.join(","),
+            edges.join(",")
+        );
+
+        // substitute placeholders in a single pass so that inputs cannot inject any
+        let title = escape_html(title);
+        HTML_TEMPLATE
+            .split("{{DATA}}")
+            .map(|part| part.replace("{{TITLE}}", &title))
+            .collect::<Vec<_>>()
+            .join(&data)
+    }
+
+    fn scale(&mut self, factor: f64) {
+        let weights = self
+            .activities
+            .values_mut()
+            .chain(self.start.values_mut())
+            .chain(self.end.values_mut())
+            .chain(self.edges.values_mut().flat_map(|t| t.values_mut()));
+
+        for weight in weights {
+            *weight *= factor;
+        }
+
+        for performance in self.performance.values_mut().flat_map(|t| t.values_mut()) {
+            performance.weight *= factor;
+            performance.seconds *= factor;
+        }
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Dfg {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl ToGraphMl for Dfg {
+    fn to_graphml(&self) -> Result<GraphMl> {
+        let mut graph = GraphMl::new(true)
+            .key("label", KeyScope::Node, KeyType::String)
+            .key("weight", KeyScope::Node, KeyType::Double)
+            .key("start", KeyScope::Node, KeyType::Double)
+            .key("weight", KeyScope::Edge, KeyType::Double)
+            .key("mean_duration", KeyScope::Edge, KeyType::Double);
+
+        // activity names are not necessarily valid ids
+        let ids: HashMap<_, _> = self
+            .activities
+            .keys()
+            .enumerate()
+            .map(|(i, activity)| (activity.as_str(), format!("n{}", i)))
+            .collect();
+
+        for (activity, weight) in self.activities.iter() {
+            let start = self.start.get(activity).copied().unwrap_or(0.0);
+            graph.node(
+                &ids[activity.as_str()],
+                vec![
+                    ("label", activity.clone()),
+                    ("weight", weight.to_string()),
+                    ("start", start.to_string()),
+                ],
+            )?;
+        }
+
+        for (from, targets) in self.edges.iter() {
+            for (to, weight) in targets.iter() {
+                let mut data = vec![("weight", weight.to_string())];
+                if let Some(mean) = self.mean_duration(from, to) {
+                    data.push(("mean_duration", mean.to_string()));
+                }
+
+                let id = |activity: &str| {
+                    ids.get(activity)
+                        .ok_or_else(|| Error::KeyError(format!("activity {:?} of edge", activity)))
+                };
+                graph.edge(id(from)?, id(to)?, data)?;
+            }
+        }
+
+        Ok(graph)
+    }
+}
+
+/// Maintain a decaying directly-follows graph
+///
+/// Each observed event first decays all weights by the forgetting factor and then adds one to the
+/// weight of its activity and of the relation to the previous activity of the case. Traces are
+/// cases on their own, as are the unfolded traces of a `Flavor::Unfolded` stream, events of an
+/// event-only stream refer to their case by an attribute. To
+/// keep memory bounded, the least recently seen cases are forgotten once there are more than
+/// `max_cases` of them. If events carry a `time:timestamp`, the time passed between directly
+/// following events is recorded as well.
+///
+pub struct OnlineDfg {
+    case_key: String,
+    activity_key: String,
+    factor: f64,
+    max_cases: usize,
+    periodic: Option<(usize, TypedSender<Dfg>)>,
+    ct_component: usize,
+    ct_event: u64,
+    cases: HashMap<String, (String, Option<DateTime>, u64)>,
+    /// Cases by the event count they were last seen at, least recently seen first
+    seen: BTreeMap<u64, String>,
+    /// Last event with an activity of the open unfolded trace
+    open: Option<(String, Option<DateTime>)>,
+    scale: f64,
+    dfg: Dfg,
+}
+
+impl Default for OnlineDfg {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl OnlineDfg {
+    /// Create a new graph with the given forgetting factor
+    ///
+    /// A factor of one disables forgetting, i.e. weights are plain frequencies.
+    ///
+    pub fn new(factor: f64) -> Self {
+        Self {
+            case_key: "case:concept:name".to_string(),
+            activity_key: "concept:name".to_string(),
+            factor,
+            max_cases: 10_000,
+            periodic: None,
+            ct_component: 0,
+            ct_event: 0,
+            cases: HashMap::new(),
+            seen: BTreeMap::new(),
+            open: None,
+            scale: 1.0,
+            dfg: Dfg::default(),
+        }
+    }
+
+    /// Identify cases of events that are not part of a trace by the given attribute
+    pub fn case_key<K: Into<String>>(mut self, case_key: K) -> Self {
+        self.case_key = case_key.into();
+        self
+    }
+
+    /// Forget the least recently seen case if there are more than `max_cases`
+    pub fn max_cases(mut self, max_cases: usize) -> Self {
+        self.max_cases = max_cases;
+        self
+    }
+
+    /// Send a snapshot to the given channel every `every` components
+    pub fn periodic(mut self, every: usize, sender: TypedSender<Dfg>) -> Self {
+        self.periodic = Some((every, sender));
+        self
+    }
+
+    /// Current state of the graph
+    pub fn snapshot(&self) -> Dfg {
+        let mut dfg = self.dfg.clone();
+        dfg.scale(self.scale);
+        dfg
+    }
+
+    fn activity(&self, event: &Event) -> Result<Option<String>> {
+        Ok(match event.get_value(&self.activity_key) {
+            Some(activity) => Some(activity.try_string()?.to_string()),
+            None => None,
+        })
+    }
+
+    fn timestamp(event: &Event) -> Result<Option<DateTime>> {
+        Ok(match event.get_value("time:timestamp") {
+            Some(timestamp) => Some(*timestamp.try_date()?),
+            None => None,
+        })
+    }
+
+    fn duration(from: Option<DateTime>, to: Option<DateTime>) -> Option<f64> {
+        match (from, to) {
+            (Some(from), Some(to)) => {
+                Some(to.signed_duration_since(from).num_milliseconds() as f64 / 1000.0)
+            }
+            _ => None,
+        }
+    }
+
+    /// Decay all weights and record `activity`, following `previous` after `duration` if any
+    fn observe(&mut self, previous: Option<&str>, activity: &str, duration: Option<f64>) {
+        self.ct_event += 1;
+        self.scale *= self.factor;
+        if self.scale < MIN_SCALE {
+            self.dfg.scale(self.scale);
+            self.scale = 1.0;
+        }
+
+        let increment = 1.0 / self.scale;
+        *self.dfg.activities.entry(activity.to_string()).or_default() += increment;
+        match previous {
+            Some(previous) => {
+                *self
+                    .dfg
+                    .edges
+                    .entry(previous.to_string())
+                    .or_default()
+                    .entry(activity.to_string())
+                    .or_default() += increment;
+
+                if let Some(duration) = duration {
+                    let performance = self
+                        .dfg
+                        .performance
+                        .entry(previous.to_string())
+                        .or_default()
+                        .entry(activity.to_string())
+                        .or_default();
+                    performance.weight += increment;
+                    performance.seconds += increment * duration;
+                }
+            }
+            None => *self.dfg.start.entry(activity.to_string()).or_default() += increment,
+        }
+    }
+
+    /// Observe an event of a trace, following the `previous` event of the trace with an activity
+    fn on_trace_event(
+        &mut self,
+        previous: &mut Option<(String, Option<DateTime>)>,
+        event: &Event,
+    ) -> Result<()> {
+        if let Some(activity) = self.activity(event)? {
+            let timestamp = Self::timestamp(event)?;
+            let duration = previous
+                .as_ref()
+                .and_then(|(_, last)| Self::duration(*last, timestamp));
+            self.observe(
+                previous.as_ref().map(|(a, _)| a.as_str()),
+                &activity,
+                duration,
+            );
+            *previous = Some((activity, timestamp));
+        }
+
+        Ok(())
+    }
+
+    fn on_case_event(&mut self, event: &Event) -> Result<()> {
+        let case = match event.get_value(&self.case_key) {
+            Some(case) => case.try_string()?.to_string(),
+            None => return Ok(()),
+        };
+        let activity = match self.activity(event)? {
+            Some(activity) => activity,
+            None => return Ok(()),
+        };
+
+        let timestamp = Self::timestamp(event)?;
+
+        let (previous, duration) = match self.cases.get(&case) {
+            Some((previous, last, seen)) => {
+                self.seen.remove(seen);
+                (Some(previous.clone()), Self::duration(*last, timestamp))
+            }
+            None => (None, None),
+        };
+        self.observe(previous.as_deref(), &activity, duration);
+        self.seen.insert(self.ct_event, case.clone());
+        self.cases
+            .insert(case, (activity, timestamp, self.ct_event));
+
+        if self.cases.len() > self.max_cases {
+            let oldest = self.seen.keys().next().copied();
+            if let Some(case) = oldest.and_then(|seen| self.seen.remove(&seen)) {
+                self.cases.remove(&case);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_component(&mut self) -> Result<()> {
+        self.ct_component += 1;
+
+        if let Some((every, sender)) = &self.periodic {
+            if *every > 0 && self.ct_component.is_multiple_of(*every) {
+                sender.send(self.snapshot())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for OnlineDfg {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        self.open = None;
+        let mut previous = None;
+        for event in trace.events.iter() {
+            self.on_trace_event(&mut previous, event)?;
+        }
+
+        self.on_component()?;
+        Ok(Some(trace))
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        let mut open = self.open.take();
+        self.on_trace_event(&mut open, &event)?;
+        self.open = open;
+
+        self.on_component()?;
+        Ok(Some(event))
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if in_trace {
+            return Ok(Some(event));
+        }
+
+        self.on_case_event(&event)?;
+        self.on_component()?;
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.snapshot().into()])
+    }
+}
+
+impl PluginProvider for OnlineDfg {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "OnlineDfg",
+                "Maintain a directly-follows graph that forgets old observations",
+                Factory::new(
+                    Declaration::default().with(OnlineDfg::declare),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let dfg = OnlineDfg::from_parameters(parameters)?;
+                        Ok(Observer::from((parameters.acquire_stream("inner")?, dfg)).into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "IncrementalOnlineDfg",
+                "Maintain a directly-follows graph that forgets old observations and send snapshots while it is consumed",
+                Factory::new(
+                    Declaration::default()
+                        .with(OnlineDfg::declare)
+                        .data_sender::<Dfg, _, _>("snapshots", "Channel that receives interim graphs")
+                        .default_attr(
+                            "every",
+                            "Send a snapshot every n traces and events, 0 to disable",
+                            |k| (k, 1000).into(),
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let every = *parameters.acquire_attribute("every")?.value.try_int()?;
+                        let dfg = OnlineDfg::from_parameters(parameters)?.periodic(
+                            every.max(0) as usize,
+                            parameters.acquire_data_sender("snapshots")?,
+                        );
+                        Ok(Observer::from((parameters.acquire_stream("inner")?, dfg)).into_boxed())
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+impl OnlineDfg {
+    /// Declare the stream and attributes shared by the online DFG plugins
+    fn declare(declaration: Declaration) -> Declaration {
+        declaration
+            .stream("inner", "The stream to be observed")
+            .default_attr("factor", "Forgetting factor in (0, 1]", |k| (k, 1.0).into())
+            .default_attr("case", "Event attribute that identifies the case", |k| {
+                (k, "case:concept:name").into()
+            })
+            .optional_attr(
+                "max_cases",
+                "Maximal number of open cases, the least recently seen one is evicted",
+                AttributeType::Int,
+            )
+    }
+
+    fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        let factor = *parameters.acquire_attribute("factor")?.value.try_float()?;
+        if !(factor > 0.0 && factor <= 1.0) {
+            return Err(Error::AttributeError(format!(
+                "forgetting factor must be in (0, 1], got {}",
+                factor
+            )));
+        }
+
+        let mut dfg = OnlineDfg::new(factor)
+            .case_key(parameters.acquire_attribute("case")?.value.try_string()?);
+
+        if let Ok(max_cases) = parameters.acquire_attribute("max_cases") {
+            dfg = dfg.max_cases(*max_cases.value.try_int()? as usize);
+        }
+
+        Ok(dfg)
+    }
+}
+
+/// Where the activity of an event is taken from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActivitySource {
+    Attribute(String),
+    /// Name of a classifier declared in the meta data
+    Classifier(String),
+}
+
+/// Count the directly-follows relations of complete cases
+///
+/// Each trace is a case, including the unfolded traces of a `Flavor::Unfolded` stream. Events
+/// lacking any of the activity attributes are skipped, standalone events are ignored. If events carry a `time:timestamp`, the time passed between directly
+/// following events is recorded as well. In performance mode, all durations are kept to emit a
+/// `PerformanceDfg` in addition.
+///
+pub struct DfgGenerator {
+    source: ActivitySource,
+    classifier: Option<Classifier>,
+    dfg: Dfg,
+    durations: Option<BTreeMap<String, BTreeMap<String, Vec<f64>>>>,
+    percentiles: Vec<f64>,
+    /// Last event with an activity of the open unfolded trace
+    open: Option<(String, Option<DateTime>)>,
+}
+
+impl Default for DfgGenerator {
+    fn default() -> Self {
+        Self::new(ActivitySource::Attribute("concept:name".to_string()))
+    }
+}
+
+impl DfgGenerator {
+    pub fn new(source: ActivitySource) -> Self {
+        let classifier = match &source {
+            ActivitySource::Attribute(key) => Some(Classifier::attribute(key.as_str())),
+            ActivitySource::Classifier(_) => None,
+        };
+
+        DfgGenerator {
+            source,
+            classifier,
+            dfg: Dfg::default(),
+            durations: None,
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+            open: None,
+        }
+    }
+
+    /// Keep all durations to report their distribution per relation
+    pub fn performance(mut self, performance: bool) -> Self {
+        self.durations = if performance {
+            Some(BTreeMap::new())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Set the percentiles, between 0 and 100, reported in performance mode
+    pub fn percentiles<P: IntoIterator<Item = f64>>(mut self, percentiles: P) -> Self {
+        self.percentiles = percentiles.into_iter().collect();
+        self
+    }
+
+    /// Take activities from the given attribute
+    pub fn activity_key<K: Into<String>>(key: K) -> Self {
+        Self::new(ActivitySource::Attribute(key.into()))
+    }
+
+    /// Take activities from the classifier of the given name
+    pub fn classifier<N: Into<String>>(name: N) -> Self {
+        Self::new(ActivitySource::Classifier(name.into()))
+    }
+
+    /// Activity of an event, i.e. its class as of the classifier
+    pub fn activity(&self, event: &Event) -> Option<String> {
+        self.classifier.as_ref()?.class(event)
+    }
+
+    /// The graph counted so far
+    pub fn dfg(&self) -> &Dfg {
+        &self.dfg
+    }
+
+    /// Count an event of a trace, following the `previous` event of the trace with an activity
+    fn count(
+        &mut self,
+        previous: &mut Option<(String, Option<DateTime>)>,
+        event: &Event,
+    ) -> Result<()> {
+        let activity = match self.activity(event) {
+            Some(activity) => activity,
+            None => return Ok(()),
+        };
+        let timestamp = OnlineDfg::timestamp(event)?;
+
+        *self.dfg.activities.entry(activity.clone()).or_default() += 1.0;
+        match previous.as_ref() {
+            Some((from, last)) => {
+                *self
+                    .dfg
+                    .edges
+                    .entry(from.clone())
+                    .or_default()
+                    .entry(activity.clone())
+                    .or_default() += 1.0;
+
+                if let Some(duration) = OnlineDfg::duration(*last, timestamp) {
+                    let performance = self
+                        .dfg
+                        .performance
+                        .entry(from.clone())
+                        .or_default()
+                        .entry(activity.clone())
+                        .or_default();
+                    performance.weight += 1.0;
+                    performance.seconds += duration;
+
+                    if let Some(durations) = self.durations.as_mut() {
+                        durations
+                            .entry(from.clone())
+                            .or_default()
+                            .entry(activity.clone())
+                            .or_default()
+                            .push(duration);
+                    }
+                }
+            }
+            None => *self.dfg.start.entry(activity.clone()).or_default() += 1.0,
+        }
+
+        *previous = Some((activity, timestamp));
+        Ok(())
+    }
+
+    /// Count the end of a trace at its `last` event with an activity
+    fn close(&mut self, last: Option<(String, Option<DateTime>)>) {
+        if let Some((last, _)) = last {
+            *self.dfg.end.entry(last).or_default() += 1.0;
+        }
+    }
+
+    /// Distribution of the durations recorded so far, if in performance mode
+    pub fn performance_dfg(&self) -> Option<PerformanceDfg> {
+        let edges = self
+            .durations
+            .as_ref()?
+            .iter()
+            .map(|(from, targets)| {
+                let targets = targets
+                    .iter()
+                    .filter_map(|(to, durations)| {
+                        DurationStats::new(durations, &self.percentiles).map(|s| (to.clone(), s))
+                    })
+                    .collect();
+                (from.clone(), targets)
+            })
+            .collect();
+
+        Some(PerformanceDfg { edges })
+    }
+}
+
+impl Handler for DfgGenerator {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        if let ActivitySource::Classifier(name) = &self.source {
+            self.classifier = Some(meta.classifier(name)?);
+        }
+        Ok(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let open = self.open.take();
+        self.close(open);
+
+        let mut previous = None;
+        for event in trace.events.iter() {
+            self.count(&mut previous, event)?;
+        }
+        self.close(previous);
+
+        Ok(Some(trace))
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        let mut open = self.open.take();
+        self.count(&mut open, &event)?;
+        self.open = open;
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        let open = self.open.take();
+        self.close(open);
+
+        let mut artifacts = vec![self.dfg.clone().into()];
+        if let Some(performance) = self.performance_dfg() {
+            artifacts.push(performance.into());
+        }
+        Ok(artifacts)
+    }
+}
+
+impl PluginProvider for DfgGenerator {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "DfgGenerator",
+            "Count the directly-follows relations of all traces by activity attribute or classifier",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be observed")
+                    .default_attr("activity", "Event attribute that holds the activity", |k| {
+                        (k, "concept:name").into()
+                    })
+                    .default_attr("performance", "Report distributions of durations", |k| {
+                        (k, false).into()
+                    })
+                    .optional_attr(
+                        "classifier",
+                        "Classifier that takes precedence over the activity attribute",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "percentiles",
+                        "Percentiles of the durations to report, e.g. [50.0, 99.0]",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let activity = parameters
+                        .acquire_attribute("activity")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let performance = *parameters
+                        .acquire_attribute("performance")?
+                        .value
+                        .try_boolean()?;
+
+                    // a classifier takes precedence over the activity attribute
+                    let mut generator = match parameters.acquire_attribute("classifier") {
+                        Ok(classifier) => DfgGenerator::classifier(classifier.value.try_string()?),
+                        Err(_) => DfgGenerator::activity_key(activity),
+                    }
+                    .performance(performance);
+
+                    // optional list of percentiles, e.g. [50.0, 99.0]
+                    if let Ok(percentiles) = parameters.acquire_attribute("percentiles") {
+                        let percentiles = percentiles
+                            .value
+                            .try_list()?
+                            .iter()
+                            .map(|a| a.value.try_number())
+                            .collect::<Result<Vec<_>>>()?;
+                        generator = generator.percentiles(percentiles);
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, generator))
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+/// Render the directly-follows graph of a stream into an interactive HTML page
+///
+/// The page is written once the stream is closed. Additionally, the graph is emitted as an
+/// artifact.
+///
+pub struct HtmlProcessMap<W: io::Write> {
+    writer: W,
+    title: String,
+    dfg: OnlineDfg,
+    flavor: Flavor,
+    /// Whether events follow a trace of an unfolded stream
+    unfolded: bool,
+}
+
+impl<W: io::Write> HtmlProcessMap<W> {
+    pub fn new(writer: W) -> Self {
+        HtmlProcessMap {
+            writer,
+            title: "Process Map".to_string(),
+            dfg: OnlineDfg::default(),
+            flavor: Flavor::Log,
+            unfolded: false,
+        }
+    }
+
+    /// Set the title of the page
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write + Send> Sink for HtmlProcessMap<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        match component {
+            Component::Meta(meta) => {
+                self.flavor = meta.flavor;
+            }
+            Component::Trace(trace) => {
+                self.dfg.on_trace(trace)?;
+                self.unfolded = self.flavor == Flavor::Unfolded;
+            }
+            Component::Event(event) if self.unfolded => {
+                self.dfg.on_unfolded_event(event)?;
+            }
+            Component::Event(event) => {
+                self.dfg.on_event(event, false)?;
+            }
+        };
+
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        let html = self.dfg.snapshot().to_html(&self.title);
+        self.writer.write_all(html.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        self.dfg.release_artifacts()
+    }
+}
+
+impl PluginProvider for HtmlProcessMap<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "HtmlProcessMap",
+            "Render the directly-follows graph of the stream into a standalone HTML page",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the HTML file", AttributeType::String)
+                    .default_attr("title", "Title of the page", |k| (k, "Process Map").into()),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let path = parameters.acquire_attribute("path")?;
+                    let title = parameters.acquire_attribute("title")?;
+                    let file = File::create(path.value.try_string()?)?;
+                    Ok(HtmlProcessMap::new(file)
+                        .title(title.value.try_string()?)
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::buffer::Buffer;
+    use crate::stream::channel::{channel, Payload, TypedReceiver};
+    use crate::stream::flow::{Graph, Segment, SequentialExecutor};
+    use crate::stream::plugin::REGISTRY;
+    use crate::stream::void::consume;
+    use crate::stream::xes::XesReader;
+    use crate::stream::{
+        Attribute, AttributeMap, AttributeValue, ClassifierDecl, Component, Scope,
+    };
+
+    use super::*;
+
+    fn event(case: &str, activity: &str) -> Event {
+        let mut event = Event::default();
+        event.attributes.insert(("case:concept:name", case));
+        event.attributes.insert(("concept:name", activity));
+        event
+    }
+
+    fn timed_event(case: &str, activity: &str, minute: u32) -> Event {
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        let mut event = event(case, activity);
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    fn events(events: &[(&str, &str)]) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for (case, activity) in events.iter() {
+            buffer.push(Ok(Some(Component::Event(event(case, activity)))));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_online_dfg() {
+        let stream = events(&[("1", "a"), ("2", "a"), ("1", "b"), ("2", "c"), ("1", "c")]);
+        let mut observer = OnlineDfg::default().into_observer(stream);
+
+        let artifacts = consume(&mut observer).unwrap();
+        let dfg = AnyArtifact::find::<Dfg>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert!(is_close!(dfg.activities["a"], 2.0));
+        assert!(is_close!(dfg.start["a"], 2.0));
+        assert!(is_close!(dfg.weight("a", "b"), 1.0));
+        assert!(is_close!(dfg.weight("a", "c"), 1.0));
+        assert!(is_close!(dfg.weight("b", "c"), 1.0));
+        assert!(is_close!(dfg.weight("c", "a"), 0.0));
+
+        // traces are cases on their own
+        let mut buffer = Buffer::default();
+        let trace = Trace {
+            events: vec![event("x", "a"), event("y", "b")],
+            ..Trace::default()
+        };
+        buffer.push(Ok(Some(Component::Trace(trace.clone()))));
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let mut observer = OnlineDfg::default().into_observer(buffer);
+        consume(&mut observer).unwrap();
+        let dfg = observer.release().unwrap().snapshot();
+
+        assert!(is_close!(dfg.start["a"], 2.0));
+        assert!(is_close!(dfg.weight("a", "b"), 2.0));
+    }
+
+    #[test]
+    fn test_online_dfg_decay() {
+        let (sender, receiver) = channel::<Payload>(None);
+        let receiver = TypedReceiver::<Dfg>::from(receiver);
+        let stream = events(&[("1", "a"), ("1", "b"), ("2", "a"), ("2", "b")]);
+        let mut observer = OnlineDfg::new(0.5)
+            .max_cases(1)
+            .periodic(2, sender.into())
+            .into_observer(stream);
+
+        consume(&mut observer).unwrap();
+        let online_dfg = observer.release().unwrap();
+        assert_eq!(online_dfg.cases.len(), 1);
+        assert_eq!(online_dfg.seen.values().collect::<Vec<_>>(), ["2"]);
+        let dfg = online_dfg.snapshot();
+
+        // a: 1 * 0.5^3 + 1 * 0.5^1, a > b: 1 * 0.5^2 + 1
+        assert!(is_close!(dfg.activities["a"], 0.625));
+        assert!(is_close!(dfg.weight("a", "b"), 1.25));
+
+        let mut periodic = Vec::new();
+        while let Ok(Some(dfg)) = receiver.try_recv() {
+            periodic.push(dfg.weight("a", "b"));
+        }
+        assert_eq!(periodic.len(), 2);
+        assert!(is_close!(periodic[0], 1.0));
+
+        // decay keeps weights finite on long streams
+        let mut dfg = OnlineDfg::new(0.1);
+        for _ in 0..1000 {
+            dfg.observe(Some("a"), "b", None);
+        }
+        let snapshot = dfg.snapshot();
+        assert!(is_close!(snapshot.weight("a", "b"), 1.0 / 0.9));
+    }
+
+    #[test]
+    fn test_incremental_online_dfg() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut graph = Graph::default();
+        graph
+            .source(
+                "Producer",
+                Segment::new("XesReader").attribute(("path", path)),
+            )
+            .stream(
+                Segment::new("IncrementalOnlineDfg")
+                    .attribute(("every", 2))
+                    .emit_data("snapshots"),
+            )
+            .unwrap()
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        let snapshots = graph.subscribe::<Dfg>("snapshots");
+        graph.execute(&mut SequentialExecutor).unwrap();
+
+        // L1 holds six traces, each starting with activity a
+        let mut starts = Vec::new();
+        while let Some(dfg) = snapshots.recv().unwrap() {
+            starts.push(dfg.start["a"]);
+        }
+        assert_eq!(starts.len(), 3);
+        assert!(is_close!(starts[2], 6.0));
+    }
+
+    #[test]
+    fn test_performance() {
+        let mut buffer = Buffer::default();
+        for (case, activity, minute) in [("1", "a", 0), ("2", "a", 1), ("1", "b", 2), ("2", "b", 5)]
+        {
+            buffer.push(Ok(Some(Component::Event(timed_event(
+                case, activity, minute,
+            )))));
+        }
+        buffer.push(Ok(Some(Component::Event(event("1", "c")))));
+
+        let mut observer = OnlineDfg::default().into_observer(buffer);
+        consume(&mut observer).unwrap();
+        let dfg = observer.release().unwrap().snapshot();
+
+        assert!(is_close!(dfg.mean_duration("a", "b").unwrap(), 180.0));
+        assert!(is_close!(dfg.weight("b", "c"), 1.0));
+        assert!(dfg.mean_duration("b", "c").is_none());
+    }
+
+    #[test]
+    fn test_html_process_map() {
+        let mut buffer = Buffer::default();
+        let trace = Trace {
+            events: vec![
+                timed_event("x", "a</script>", 0),
+                timed_event("x", "b", 1),
+                timed_event("x", "b", 3),
+            ],
+            ..Trace::default()
+        };
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let mut sink = HtmlProcessMap::new(Vec::new()).title("<Foo>");
+        let artifacts = sink.consume(&mut buffer).unwrap();
+        assert!(AnyArtifact::find::<Dfg>(&mut artifacts.iter().flatten()).is_some());
+
+        let html = String::from_utf8(sink.into_inner()).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>&lt;Foo&gt;</title>"));
+        assert!(html.contains(r#"{"name":"a\u003c/script\u003e","weight":1,"start":1}"#));
+        assert!(html.contains(r#"{"from":"b","to":"b","weight":1,"mean":120}"#));
+        assert!(!html.contains("{{DATA}}"));
+        assert_eq!(html.matches("</script>").count(), 1);
+    }
+
+    #[test]
+    fn test_graphml() {
+        let stream = events(&[("1", "a"), ("1", "b"), ("1", "b")]);
+        let mut observer = OnlineDfg::default().into_observer(stream);
+        consume(&mut observer).unwrap();
+
+        let xml = observer
+            .release()
+            .unwrap()
+            .snapshot()
+            .to_graphml()
+            .unwrap()
+            .to_xml()
+            .unwrap();
+
+        assert!(xml.contains(r#"<data key="node_label">b</data>"#));
+        assert!(xml.contains(r#"<edge source="n0" target="n1">"#));
+        assert!(xml.contains(r#"<edge source="n1" target="n1">"#));
+        assert_eq!(xml.matches("<edge ").count(), 2);
+    }
+
+    #[test]
+    fn test_dfg_generator() {
+        let mut buffer = Buffer::default();
+        let mut meta = Meta::default();
+        meta.classifiers.push(ClassifierDecl {
+            name: "full".into(),
+            scope: Scope::Event,
+            keys: "concept:name 'life cycle'".into(),
+        });
+        buffer.push(Ok(Some(Component::Meta(meta))));
+        for activities in [vec!["a", "b", "c"], vec!["a", "c"], vec!["a", "b", "c"]] {
+            let mut trace = Trace::default();
+            for (i, activity) in activities.iter().enumerate() {
+                let mut event = timed_event("", activity, i as u32);
+                event.attributes.insert(("life cycle", "complete"));
+                trace.events.push(event);
+            }
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+        buffer.push(Ok(Some(Component::Event(event("1", "x")))));
+
+        let mut observer = DfgGenerator::default().into_observer(buffer.clone());
+        consume(&mut observer).unwrap();
+        let dfg = observer.release().unwrap().dfg().clone();
+
+        assert_eq!(dfg.activities["a"], 3.0);
+        assert!(!dfg.activities.contains_key("x"));
+        assert_eq!(dfg.start["a"], 3.0);
+        assert_eq!(dfg.end["c"], 3.0);
+        assert_eq!(dfg.weight("a", "b"), 2.0);
+        assert_eq!(dfg.weight("a", "c"), 1.0);
+        assert_eq!(dfg.mean_duration("a", "b"), Some(60.0));
+
+        let mut observer = DfgGenerator::classifier("full").into_observer(buffer.clone());
+        let artifacts = consume(&mut observer).unwrap();
+        let dfg = AnyArtifact::find::<Dfg>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(dfg.weight("a+complete", "b+complete"), 2.0);
+
+        let mut observer = DfgGenerator::classifier("missing").into_observer(buffer);
+        assert!(consume(&mut observer).is_err());
+    }
+
+    #[test]
+    fn test_dfg_unfolded() {
+        let unfolded =
+            || XesReader::from(join_static_reader!("xes", "book", "L1.xes")).streaming_events();
+
+        let mut observer = DfgGenerator::default().into_observer(load_example(&["book", "L1.xes"]));
+        consume(&mut observer).unwrap();
+        let expected = observer.release().unwrap().dfg().clone();
+
+        let mut observer = DfgGenerator::default().into_observer(unfolded());
+        consume(&mut observer).unwrap();
+        assert_eq!(observer.release().unwrap().dfg(), &expected);
+
+        let mut observer = OnlineDfg::default().into_observer(unfolded());
+        consume(&mut observer).unwrap();
+        let dfg = observer.release().unwrap().snapshot();
+        assert_eq!(dfg.activities, expected.activities);
+        assert_eq!(dfg.edges, expected.edges);
+        assert_eq!(dfg.start, expected.start);
+    }
+
+    #[test]
+    fn test_performance_dfg() {
+        let mut buffer = Buffer::default();
+        for minutes in [1, 2, 3, 10] {
+            let mut trace = Trace::default();
+            trace.events.push(timed_event("", "a", 0));
+            trace.events.push(timed_event("", "b", minutes));
+            trace.events.push(event("", "c"));
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+
+        let mut observer = DfgGenerator::default().into_observer(buffer.clone());
+        let artifacts = consume(&mut observer).unwrap();
+        assert!(AnyArtifact::find::<PerformanceDfg>(&mut artifacts.iter().flatten()).is_none());
+
+        let generator = DfgGenerator::default()
+            .performance(true)
+            .percentiles(vec![0.0, 90.0]);
+        let configured = buffer.clone();
+        let mut observer = generator.into_observer(buffer);
+        let artifacts = consume(&mut observer).unwrap();
+        let performance =
+            AnyArtifact::find::<PerformanceDfg>(&mut artifacts.iter().flatten()).unwrap();
+
+        let stats = performance.stats("a", "b").unwrap();
+        assert_eq!(stats.count, 4);
+        assert!(is_close!(stats.mean, 240.0));
+        assert!(is_close!(stats.median, 150.0));
+        assert_eq!((stats.min, stats.max), (60.0, 600.0));
+        assert_eq!(stats.percentile(0.0), Some(60.0));
+        assert!(is_close!(stats.percentile(90.0).unwrap(), 474.0));
+        assert!(performance.stats("b", "c").is_none());
+
+        // percentiles may be configured as integers as well as floats
+        let mut attributes = AttributeMap::new();
+        attributes.insert(("performance", true));
+        attributes.insert((
+            "percentiles",
+            AttributeValue::List(vec![Attribute::new("", 50), Attribute::new("", 99.5)]),
+        ));
+        let registry = REGISTRY.lock().unwrap();
+        let mut stream = registry
+            .get("DfgGenerator")
+            .unwrap()
+            .factory
+            .build_stream(attributes, &mut [], vec![Box::new(configured)], vec![])
+            .unwrap();
+        let artifacts = consume(&mut stream).unwrap();
+        let performance =
+            AnyArtifact::find::<PerformanceDfg>(&mut artifacts.iter().flatten()).unwrap();
+        let stats = performance.stats("a", "b").unwrap();
+        let percentiles: Vec<_> = stats.percentiles.iter().map(|(p, _)| *p).collect();
+        assert_eq!(percentiles, [50.0, 99.5]);
+    }
+}
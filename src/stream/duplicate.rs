@@ -0,0 +1,447 @@
+//! Detection of approximately duplicated traces
+//!
+//! Data that was exported twice, e.g. by overlapping extraction jobs, shows up as cases that share
+//! the same activity sequence and (nearly) the same timestamps. This module reports such pairs of
+//! traces as candidates for review instead of removing them, since genuinely distinct cases may
+//! look alike in high volume processes.
+//!
+//! Two traces are considered duplicates if their sequences of activities are identical and the
+//! timestamps of corresponding events differ by no more than a tolerance. Events without timestamp
+//! only match events without timestamp.
+//!
+//! Duplicated events within a trace, on the other hand, are removed by `Dedup`. Events duplicate
+//! an earlier event of their trace if they agree on all attributes of a key set, by default the
+//! activity, the timestamp and the resource. A missing attribute only agrees with a missing
+//! attribute.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeValue, Event, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// Pair of traces that are likely duplicates of each other
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicatePair {
+    /// Position of the earlier trace in the stream
+    pub first: usize,
+    /// Position of the later trace in the stream
+    pub second: usize,
+    /// `concept:name` of both traces, if present
+    pub names: (Option<String>, Option<String>),
+    /// Largest difference between the timestamps of corresponding events in seconds
+    pub deviation: f64,
+}
+
+/// Candidate duplicates of an event stream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateCandidates {
+    pub pairs: Vec<DuplicatePair>,
+    /// Number of traces compared
+    pub traces: usize,
+}
+
+impl DuplicateCandidates {
+    /// Positions of traces that duplicate an earlier trace
+    pub fn duplicates(&self) -> Vec<usize> {
+        let mut duplicates: Vec<_> = self.pairs.iter().map(|p| p.second).collect();
+        duplicates.sort_unstable();
+        duplicates.dedup();
+        duplicates
+    }
+}
+
+#[typetag::serde]
+impl Artifact for DuplicateCandidates {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Trace as far as it matters for the comparison
+struct Fingerprint {
+    position: usize,
+    name: Option<String>,
+    timestamps: Vec<Option<DateTime>>,
+}
+
+/// Find pairs of traces with identical activities and near-identical timestamps
+pub struct DuplicateDetector {
+    key: String,
+    tolerance: Duration,
+    seen: HashMap<Vec<String>, Vec<Fingerprint>>,
+    candidates: DuplicateCandidates,
+}
+
+impl Default for DuplicateDetector {
+    fn default() -> Self {
+        Self::new(Duration::seconds(1))
+    }
+}
+
+impl DuplicateDetector {
+    /// Create a new detector that accepts timestamps that deviate by up to `tolerance`
+    pub fn new(tolerance: Duration) -> Self {
+        DuplicateDetector {
+            key: "concept:name".to_string(),
+            tolerance,
+            seen: HashMap::new(),
+            candidates: DuplicateCandidates::default(),
+        }
+    }
+
+    /// Take activities from the given attribute
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+/// Largest deviation of corresponding timestamps, if all of them are within the tolerance
+fn deviation(
+    a: &[Option<DateTime>],
+    b: &[Option<DateTime>],
+    tolerance: Duration,
+) -> Option<Duration> {
+    let mut deviation = Duration::zero();
+    for pair in a.iter().zip(b.iter()) {
+        match pair {
+            (Some(a), Some(b)) => {
+                let delta = (*a - *b).abs();
+                if delta > tolerance {
+                    return None;
+                }
+                deviation = deviation.max(delta);
+            }
+            (None, None) => (),
+            _ => return None,
+        }
+    }
+    Some(deviation)
+}
+
+impl Handler for DuplicateDetector {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let mut activities = Vec::with_capacity(trace.events.len());
+        let mut timestamps = Vec::with_capacity(trace.events.len());
+        for event in trace.events.iter() {
+            activities.push(match event.get_value(&self.key) {
+                Some(AttributeValue::String(label)) | Some(AttributeValue::Id(label)) => {
+                    label.to_string()
+                }
+                _ => String::new(),
+            });
+            timestamps.push(match event.get_value("time:timestamp") {
+                Some(timestamp) => Some(*timestamp.try_date()?),
+                None => None,
+            });
+        }
+
+        let fingerprint = Fingerprint {
+            position: self.candidates.traces,
+            name: match trace.get_value("concept:name") {
+                Some(name) => Some(name.try_string()?.to_string()),
+                None => None,
+            },
+            timestamps,
+        };
+        self.candidates.traces += 1;
+
+        let earlier = self.seen.entry(activities).or_default();
+        for other in earlier.iter() {
+            if let Some(deviation) =
+                deviation(&other.timestamps, &fingerprint.timestamps, self.tolerance)
+            {
+                self.candidates.pairs.push(DuplicatePair {
+                    first: other.position,
+                    second: fingerprint.position,
+                    names: (other.name.clone(), fingerprint.name.clone()),
+                    deviation: deviation.num_milliseconds() as f64 / 1000.,
+                });
+            }
+        }
+        earlier.push(fingerprint);
+
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        self.seen.clear();
+        Ok(vec![mem::take(&mut self.candidates).into()])
+    }
+}
+
+/// Counts of events removed by `Dedup`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Deduplication {
+    /// Number of removed events
+    pub removed: usize,
+    /// Number of traces that contained duplicates
+    pub traces: usize,
+    /// Number of removed events by `concept:name`
+    pub activities: BTreeMap<String, usize>,
+}
+
+#[typetag::serde]
+impl Artifact for Deduplication {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Remove events that repeat an earlier event of their trace
+pub struct Dedup {
+    keys: Vec<String>,
+    deduplication: Deduplication,
+}
+
+impl Default for Dedup {
+    /// Compare events by activity, timestamp and resource
+    fn default() -> Self {
+        Self::new(vec!["concept:name", "time:timestamp", "org:resource"])
+    }
+}
+
+impl Dedup {
+    /// Create a new handler that compares events by the given attributes
+    pub fn new<I, K>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        Dedup {
+            keys: keys.into_iter().map(|k| k.into()).collect(),
+            deduplication: Deduplication::default(),
+        }
+    }
+
+    fn identity(&self, event: &Event) -> Vec<Option<String>> {
+        self.keys
+            .iter()
+            .map(|key| event.get_value(key).map(|value| format!("{:?}", value)))
+            .collect()
+    }
+}
+
+impl Handler for Dedup {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        let mut seen = HashSet::new();
+        let before = trace.events.len();
+
+        let events = mem::take(&mut trace.events);
+        for event in events {
+            if seen.insert(self.identity(&event)) {
+                trace.events.push(event);
+            } else {
+                let activity = match event.get_value("concept:name") {
+                    Some(AttributeValue::String(label)) | Some(AttributeValue::Id(label)) => {
+                        label.to_string()
+                    }
+                    _ => String::new(),
+                };
+                *self.deduplication.activities.entry(activity).or_default() += 1;
+            }
+        }
+
+        let removed = before - trace.events.len();
+        if removed > 0 {
+            self.deduplication.removed += removed;
+            self.deduplication.traces += 1;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![mem::take(&mut self.deduplication).into()])
+    }
+}
+
+impl PluginProvider for DuplicateDetector {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "DuplicateTraces",
+                "Find traces with identical activities and near-identical timestamps",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The stream to be analyzed")
+                        .default_attr("key", "Event attribute to compare", |k| {
+                            (k, "concept:name").into()
+                        })
+                        .default_attr(
+                            "tolerance",
+                            "Maximal deviation of corresponding timestamps in seconds",
+                            |k| (k, 1).into(),
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let tolerance =
+                            *parameters.acquire_attribute("tolerance")?.value.try_int()?;
+                        let detector = DuplicateDetector::new(Duration::seconds(tolerance))
+                            .key(parameters.acquire_attribute("key")?.value.try_string()?);
+
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, detector))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+            Entry::new(
+                "Dedup",
+                "Remove events that agree with an earlier event of their trace on all keys",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The stream to be cleaned")
+                        .default_attr(
+                            "keys",
+                            "Comma separated event attributes that identify duplicates",
+                            |k| (k, "concept:name,time:timestamp,org:resource").into(),
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let keys = parameters.acquire_attribute("keys")?;
+                        let keys: Vec<_> = keys
+                            .value
+                            .try_string()?
+                            .split(',')
+                            .map(|k| k.trim())
+                            .filter(|k| !k.is_empty())
+                            .collect();
+                        if keys.is_empty() {
+                            return Err(Error::AttributeError(
+                                "keys are expected to name at least one attribute".to_string(),
+                            ));
+                        }
+
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, Dedup::new(keys)))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Sink};
+
+    use super::*;
+
+    fn trace(name: &str, activities: &str, start: i64) -> Trace {
+        let origin = DateTime::parse_from_rfc3339("2020-01-01T00:00:00+00:00").unwrap();
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", name));
+
+        for (i, activity) in activities.chars().enumerate() {
+            let mut event = Event::default();
+            event
+                .attributes
+                .insert(("concept:name", activity.to_string()));
+            event.attributes.insert((
+                "time:timestamp",
+                origin + Duration::seconds(start + 60 * i as i64),
+            ));
+            trace.events.push(event);
+        }
+
+        trace
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut buffer = Buffer::default();
+        for (name, activities, start) in [
+            ("a", "abcd", 0),
+            ("b", "abcd", 1),
+            ("c", "abcd", 5),
+            ("d", "abdc", 0),
+            ("e", "abcd", 1),
+        ]
+        .iter()
+        {
+            buffer.push(Ok(Some(Component::Trace(trace(name, activities, *start)))));
+        }
+
+        let mut observer = DuplicateDetector::new(Duration::seconds(2)).into_observer(buffer);
+        let artifacts = consume(&mut observer).unwrap();
+        let candidates =
+            AnyArtifact::find::<DuplicateCandidates>(&mut artifacts.iter().flatten()).unwrap();
+
+        let pairs: Vec<_> = candidates
+            .pairs
+            .iter()
+            .map(|p| (p.first, p.second, p.deviation))
+            .collect();
+        assert_eq!(pairs, [(0, 1, 1.), (0, 4, 1.), (1, 4, 0.)]);
+        assert_eq!(candidates.duplicates(), [1, 4]);
+        assert_eq!(candidates.traces, 5);
+        assert_eq!(
+            candidates.pairs[0].names,
+            (Some("a".to_string()), Some("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut doubled = trace("a", "abcd", 0);
+        let mut resource = doubled.events[1].clone();
+        resource.attributes.insert(("org:resource", "Pete"));
+        doubled.events.insert(2, doubled.events[1].clone());
+        doubled.events.push(doubled.events[0].clone());
+        doubled.events.push(resource);
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(doubled))));
+        buffer.push(Ok(Some(Component::Trace(trace("b", "aaaa", 0)))));
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+
+        let mut observer = Dedup::default().into_observer(buffer.clone());
+        let mut log = Log::default();
+        let artifacts = log.consume(&mut observer).unwrap();
+        let deduplication =
+            AnyArtifact::find::<Deduplication>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(log.traces[0].events.len(), 5);
+        assert_eq!(log.traces[1].events.len(), 4);
+        assert_eq!(log.events.len(), 2);
+        assert_eq!(deduplication.removed, 2);
+        assert_eq!(deduplication.traces, 1);
+        assert_eq!(deduplication.activities.get("a"), Some(&1));
+        assert_eq!(deduplication.activities.get("b"), Some(&1));
+
+        // without timestamps, repeated activities are duplicates
+        let mut observer = Dedup::new(vec!["concept:name"]).into_observer(buffer);
+        let artifacts = consume(&mut observer).unwrap();
+        let deduplication =
+            AnyArtifact::find::<Deduplication>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(deduplication.removed, 6);
+        assert_eq!(deduplication.traces, 2);
+    }
+}
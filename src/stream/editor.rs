@@ -0,0 +1,272 @@
+//! Edit the meta data of event streams
+//!
+//! Many tools rely on declarations that logs in the wild lack, e.g. a classifier that tells which
+//! attribute identifies the activity. The `MetaEditor` handler applies a list of edits to the meta
+//! component of a stream. In flow graphs, each edit is an attribute whose key names the operation:
+//! - `add_classifier`: value is the classifier name, child `keys` the space separated attribute
+//!   keys and child `scope` optionally `trace` or `event` (default)
+//! - `remove_classifier`: value is the classifier name
+//! - `add_global`: value is the scope, children are the attributes with their default values
+//! - `remove_global`: value is the attribute key, child `scope` as above
+//! - `add_extension`: value is the prefix of a known extension or, with children `name` and `uri`,
+//!   of a custom one
+//! - `remove_extension`: value is the extension prefix
+//!
+//! Additions replace existing declarations of the same name, key or prefix.
+//!
+
+use std::convert::TryFrom;
+
+use crate::stream::extension::REGISTRY;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    Attribute, AttributeType, ClassifierDecl, ExtensionDecl, Global, Meta, Scope, Stream,
+};
+use crate::{Error, Result};
+
+/// A single modification of meta data
+#[derive(Debug, Clone)]
+pub enum MetaEdit {
+    AddClassifier(ClassifierDecl),
+    RemoveClassifier(String),
+    AddGlobal(Scope, Vec<Attribute>),
+    RemoveGlobal(Scope, String),
+    AddExtension(ExtensionDecl),
+    RemoveExtension(String),
+}
+
+impl MetaEdit {
+    /// Classifier of the given name that concatenates the given keys
+    pub fn classifier(name: &str, keys: &str, scope: Scope) -> Self {
+        MetaEdit::AddClassifier(ClassifierDecl {
+            name: name.to_string(),
+            scope,
+            keys: keys.to_string(),
+        })
+    }
+
+    /// One of the extensions known to the extension registry
+    pub fn extension(prefix: &str) -> Result<Self> {
+        let registry = REGISTRY.lock().map_err(|_| {
+            Error::ExtensionError("unable to acquire extension registry".to_string())
+        })?;
+
+        match registry.get(prefix) {
+            Some(entry) => Ok(MetaEdit::AddExtension(entry.declare())),
+            None => Err(Error::ExtensionError(format!(
+                "unknown extension {:?}",
+                prefix
+            ))),
+        }
+    }
+
+    /// Apply the edit to meta data
+    pub fn apply(&self, meta: &mut Meta) {
+        match self {
+            MetaEdit::AddClassifier(classifier) => {
+                meta.classifiers.retain(|c| c.name != classifier.name);
+                meta.classifiers.push(classifier.clone());
+            }
+            MetaEdit::RemoveClassifier(name) => meta.classifiers.retain(|c| &c.name != name),
+            MetaEdit::AddGlobal(scope, attributes) => {
+                let global = match meta.globals.iter_mut().position(|g| &g.scope == scope) {
+                    Some(index) => &mut meta.globals[index],
+                    None => {
+                        meta.globals.push(Global {
+                            scope: scope.clone(),
+                            attributes: Vec::new(),
+                        });
+                        meta.globals.last_mut().unwrap()
+                    }
+                };
+
+                for attribute in attributes.iter() {
+                    global.attributes.retain(|a| a.key != attribute.key);
+                    global.attributes.push(attribute.clone());
+                }
+            }
+            MetaEdit::RemoveGlobal(scope, key) => {
+                for global in meta.globals.iter_mut().filter(|g| &g.scope == scope) {
+                    global.attributes.retain(|a| a.key != key.as_str());
+                }
+                meta.globals.retain(|g| !g.attributes.is_empty());
+            }
+            MetaEdit::AddExtension(extension) => {
+                meta.extensions.retain(|e| e.prefix != extension.prefix);
+                meta.extensions.push(extension.clone());
+            }
+            MetaEdit::RemoveExtension(prefix) => meta.extensions.retain(|e| &e.prefix != prefix),
+        }
+    }
+}
+
+impl TryFrom<&Attribute> for MetaEdit {
+    type Error = Error;
+
+    fn try_from(attribute: &Attribute) -> Result<Self> {
+        let child = |key: &str| attribute.children.iter().find(|c| c.key == key);
+        let child_string = |key: &str| -> Result<Option<String>> {
+            child(key)
+                .map(|c| Ok(c.value.try_string()?.to_string()))
+                .transpose()
+        };
+        let value = attribute.value.try_string()?;
+
+        match attribute.key.as_str() {
+            "add_classifier" => {
+                let keys = child_string("keys")?.ok_or_else(|| {
+                    Error::AttributeError(format!("classifier {:?} requires keys", value))
+                })?;
+                let scope = Scope::try_from(child_string("scope")?)?;
+                Ok(MetaEdit::classifier(value, &keys, scope))
+            }
+            "remove_classifier" => Ok(MetaEdit::RemoveClassifier(value.to_string())),
+            "add_global" => Ok(MetaEdit::AddGlobal(
+                Scope::try_from(Some(value.to_string()))?,
+                attribute.children.clone(),
+            )),
+            "remove_global" => Ok(MetaEdit::RemoveGlobal(
+                Scope::try_from(child_string("scope")?)?,
+                value.to_string(),
+            )),
+            "add_extension" => match (child_string("name")?, child_string("uri")?) {
+                (Some(name), Some(uri)) => Ok(MetaEdit::AddExtension(ExtensionDecl {
+                    name,
+                    prefix: value.to_string(),
+                    uri,
+                })),
+                _ => MetaEdit::extension(value),
+            },
+            "remove_extension" => Ok(MetaEdit::RemoveExtension(value.to_string())),
+            other => Err(Error::AttributeError(format!(
+                "unknown meta edit {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Apply edits to the meta data of a stream
+#[derive(Debug, Clone, Default)]
+pub struct MetaEditor {
+    edits: Vec<MetaEdit>,
+}
+
+impl MetaEditor {
+    /// Add an edit, edits are applied in order of registration
+    pub fn edit(mut self, edit: MetaEdit) -> Self {
+        self.edits.push(edit);
+        self
+    }
+}
+
+impl Handler for MetaEditor {
+    fn on_meta(&mut self, mut meta: Meta) -> Result<Meta> {
+        for edit in self.edits.iter() {
+            edit.apply(&mut meta);
+        }
+
+        Ok(meta)
+    }
+}
+
+impl PluginProvider for MetaEditor {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "MetaEditor",
+            "Add or remove classifiers, globals and extensions",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream whose meta data is edited")
+                    .typed_attr(
+                        "edits",
+                        "List of edits, each keyed by its operation",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let editor = parameters
+                        .acquire_attribute("edits")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(MetaEdit::try_from)
+                        .collect::<Result<Vec<_>>>()?
+                        .into_iter()
+                        .fold(MetaEditor::default(), |editor, e| editor.edit(e));
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, editor)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::{Component, Sink};
+
+    use super::*;
+
+    #[test]
+    fn test_meta_editor() {
+        let edits = [
+            Attribute::with_children(
+                "add_classifier",
+                "Activity",
+                vec![Attribute::new("keys", "concept:name")],
+            ),
+            Attribute::with_children(
+                "add_global",
+                "event",
+                vec![
+                    Attribute::new("concept:name", "UNKNOWN"),
+                    Attribute::new("org:resource", "UNKNOWN"),
+                ],
+            ),
+            Attribute::new("remove_global", "concept:name"),
+            Attribute::new("add_extension", "concept"),
+            Attribute::with_children(
+                "add_extension",
+                "cost",
+                vec![
+                    Attribute::new("name", "Cost"),
+                    Attribute::new("uri", "http://www.xes-standard.org/cost.xesext"),
+                ],
+            ),
+            Attribute::new("remove_extension", "cost"),
+        ];
+        let editor = edits
+            .iter()
+            .map(|e| MetaEdit::try_from(e).unwrap())
+            .fold(MetaEditor::default(), |editor, e| editor.edit(e));
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        let mut result = Buffer::default();
+        result.consume(&mut editor.into_observer(buffer)).unwrap();
+
+        let meta = match result.next().unwrap() {
+            Some(Component::Meta(meta)) => meta,
+            other => panic!("expected meta, got {:?}", other),
+        };
+        assert_eq!(meta.classifiers.len(), 1);
+        assert_eq!(meta.classifiers[0].keys, "concept:name");
+        assert_eq!(meta.globals.len(), 1);
+        assert_eq!(meta.globals[0].scope, Scope::Event);
+        assert_eq!(
+            meta.globals[0].attributes,
+            vec![Attribute::new("org:resource", "UNKNOWN")]
+        );
+        let prefixes: Vec<_> = meta.extensions.iter().map(|e| e.prefix.as_str()).collect();
+        assert_eq!(prefixes, vec!["concept"]);
+
+        assert!(MetaEdit::try_from(&Attribute::new("add_extension", "unknown")).is_err());
+        assert!(MetaEdit::try_from(&Attribute::new("add_classifier", "Activity")).is_err());
+        assert!(MetaEdit::try_from(&Attribute::new("rename", "x")).is_err());
+    }
+}
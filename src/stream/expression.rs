@@ -0,0 +1,535 @@
+//! Evaluate expressions over attributes
+//!
+//! Expressions compute values from the attributes of a single trace or event. They are written
+//! in a small language that is easy to embed in flow definitions:
+//! - attribute references in double quotes, e.g. `"cost:total"`
+//! - string literals in single quotes, e.g. `'EUR'`
+//! - integer, float and boolean literals
+//! - arithmetic `+ - * / %` and comparisons `== != < <= > >=`, with the usual precedence
+//! - function calls:
+//!   - `duration_between(from, to)`: seconds between two dates
+//!   - `abs(x)` and `round(x)`
+//!   - `min(x, ...)` and `max(x, ...)`
+//!   - `coalesce(x, ...)`: the first argument that is not missing
+//!
+//! Integer arithmetic stays integral except for `/`, which always yields a float. `+` concatenates
+//! strings. If a referenced attribute is missing, so is the result, unless caught by `coalesce`.
+//!
+//! ```
+//! use promi::stream::expression::Expression;
+//! use promi::stream::{AttributeValue, Event};
+//!
+//! let mut event = Event::default();
+//! event.attributes.insert(("cost:total", 100));
+//!
+//! let gross = Expression::parse(r#""cost:total" * 1.19"#).unwrap();
+//! assert_eq!(gross.evaluate(&event).unwrap(), Some(AttributeValue::Float(119.0)));
+//! ```
+//!
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::stream::{AttributeContainer, AttributeValue};
+use crate::{Error, Result};
+
+/// Upper limit of nested parentheses, calls and negations, to bound the recursion of the parser
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Str(String),
+    Attribute(String),
+    Ident(String),
+    Op(String),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match chars.next() {
+                Some('\\') => text.extend(chars.next()),
+                Some(c) if c == quote => return Ok(text),
+                Some(c) => text.push(c),
+                None => {
+                    return Err(Error::ExpressionError(format!(
+                        "unterminated {}{}",
+                        quote, text
+                    )))
+                }
+            }
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '"' => Token::Attribute(quoted(&mut chars, '"')?),
+            '\'' => Token::Str(quoted(&mut chars, '\'')?),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '+' | '-' | '*' | '/' | '%' => Token::Op(c.to_string()),
+            '=' | '!' | '<' | '>' => {
+                let mut op = c.to_string();
+                if chars.peek() == Some(&'=') {
+                    op.push(chars.next().unwrap());
+                }
+                if op == "=" || op == "!" {
+                    return Err(Error::ExpressionError(format!("unknown operator {:?}", op)));
+                }
+                Token::Op(op)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(n) = chars.next_if(|n| n.is_ascii_digit() || *n == '.') {
+                    number.push(n);
+                }
+                Token::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(n) = chars.next_if(|n| n.is_alphanumeric() || *n == '_') {
+                    ident.push(n);
+                }
+                Token::Ident(ident)
+            }
+            other => {
+                return Err(Error::ExpressionError(format!(
+                    "unexpected character {:?}",
+                    other
+                )))
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(AttributeValue),
+    Attribute(String),
+    Negate(Box<Node>),
+    Binary(String, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(Error::ExpressionError(format!(
+                "expected {:?}, got {:?}",
+                expected, other
+            ))),
+        }
+    }
+
+    fn binary<F>(&mut self, ops: &[&str], mut operand: F) -> Result<Node>
+    where
+        F: FnMut(&mut Self) -> Result<Node>,
+    {
+        let mut node = operand(self)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            if !ops.contains(&op.as_str()) {
+                break;
+            }
+            let op = op.clone();
+            self.position += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(operand(self)?));
+        }
+        Ok(node)
+    }
+
+    fn comparison(&mut self) -> Result<Node> {
+        self.binary(&["==", "!=", "<", "<=", ">", ">="], Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Node> {
+        self.binary(&["+", "-"], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Node> {
+        self.binary(&["*", "/", "%"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::ExpressionError(format!(
+                "expression is nested deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+
+        self.depth += 1;
+        let node = if self.peek() == Some(&Token::Op("-".into())) {
+            self.position += 1;
+            self.unary().map(|node| Node::Negate(Box::new(node)))
+        } else {
+            self.primary()
+        };
+        self.depth -= 1;
+        node
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        match self.advance() {
+            Some(Token::Number(number)) if number.contains('.') => {
+                Ok(Node::Literal(AttributeValue::Float(number.parse::<f64>()?)))
+            }
+            Some(Token::Number(number)) => {
+                Ok(Node::Literal(AttributeValue::Int(number.parse::<i64>()?)))
+            }
+            Some(Token::Str(text)) => Ok(Node::Literal(AttributeValue::String(text.into()))),
+            Some(Token::Attribute(key)) => Ok(Node::Attribute(key)),
+            Some(Token::Ident(ident)) if ident == "true" || ident == "false" => {
+                Ok(Node::Literal(AttributeValue::Boolean(ident == "true")))
+            }
+            Some(Token::Ident(name)) => {
+                self.expect(Token::Open)?;
+                let mut arguments = Vec::new();
+                if self.peek() != Some(&Token::Close) {
+                    arguments.push(self.comparison()?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.position += 1;
+                        arguments.push(self.comparison()?);
+                    }
+                }
+                self.expect(Token::Close)?;
+                check_call(&name, arguments.len())?;
+                Ok(Node::Call(name, arguments))
+            }
+            Some(Token::Open) => {
+                let node = self.comparison()?;
+                self.expect(Token::Close)?;
+                Ok(node)
+            }
+            other => Err(Error::ExpressionError(format!(
+                "unexpected token {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+fn check_call(name: &str, arity: usize) -> Result<()> {
+    let valid = match name {
+        "duration_between" => arity == 2,
+        "abs" | "round" => arity == 1,
+        "min" | "max" | "coalesce" => arity >= 1,
+        other => {
+            return Err(Error::ExpressionError(format!(
+                "unknown function {:?}",
+                other
+            )))
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::ExpressionError(format!(
+            "{} does not take {} arguments",
+            name, arity
+        )))
+    }
+}
+
+fn type_error(op: &str, values: &[&AttributeValue]) -> Error {
+    Error::ExpressionError(format!("cannot apply {} to {:?}", op, values))
+}
+
+fn overflow(op: &str, values: &[&AttributeValue]) -> Error {
+    Error::ExpressionError(format!("integer overflow in {} of {:?}", op, values))
+}
+
+fn as_float(value: &AttributeValue) -> Option<f64> {
+    match value {
+        AttributeValue::Int(x) => Some(*x as f64),
+        AttributeValue::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn arithmetic(op: &str, a: &AttributeValue, b: &AttributeValue) -> Result<AttributeValue> {
+    use AttributeValue::*;
+
+    Ok(match (op, a, b) {
+        ("+", String(x), String(y)) => String(format!("{}{}", x, y).into()),
+        ("/", Int(_), Int(0)) | ("%", Int(_), Int(0)) => {
+            return Err(Error::ExpressionError("division by zero".into()))
+        }
+        ("+", Int(x), Int(y)) => Int(x.checked_add(*y).ok_or_else(|| overflow(op, &[a, b]))?),
+        ("-", Int(x), Int(y)) => Int(x.checked_sub(*y).ok_or_else(|| overflow(op, &[a, b]))?),
+        ("*", Int(x), Int(y)) => Int(x.checked_mul(*y).ok_or_else(|| overflow(op, &[a, b]))?),
+        ("%", Int(x), Int(y)) => Int(x.checked_rem(*y).ok_or_else(|| overflow(op, &[a, b]))?),
+        _ => match (as_float(a), as_float(b)) {
+            (Some(x), Some(y)) => Float(match op {
+                "+" => x + y,
+                "-" => x - y,
+                "*" => x * y,
+                "/" => x / y,
+                _ => x % y,
+            }),
+            _ => return Err(type_error(op, &[a, b])),
+        },
+    })
+}
+
+pub(crate) fn compare(op: &str, a: &AttributeValue, b: &AttributeValue) -> Result<AttributeValue> {
+    use std::cmp::Ordering;
+
+    let ordering = match (a, b) {
+        (AttributeValue::String(x), AttributeValue::String(y))
+        | (AttributeValue::Id(x), AttributeValue::Id(y)) => x.partial_cmp(y),
+        (AttributeValue::Date(x), AttributeValue::Date(y)) => x.partial_cmp(y),
+        (AttributeValue::Boolean(x), AttributeValue::Boolean(y)) => x.partial_cmp(y),
+        _ => match (as_float(a), as_float(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y),
+            _ => return Err(type_error(op, &[a, b])),
+        },
+    };
+
+    Ok(AttributeValue::Boolean(match (op, ordering) {
+        ("==", o) => o == Some(Ordering::Equal),
+        ("!=", o) => o != Some(Ordering::Equal),
+        (_, None) => false,
+        ("<", Some(o)) => o == Ordering::Less,
+        ("<=", Some(o)) => o != Ordering::Greater,
+        (">", Some(o)) => o == Ordering::Greater,
+        (_, Some(o)) => o != Ordering::Less,
+    }))
+}
+
+fn call(name: &str, arguments: &[AttributeValue]) -> Result<AttributeValue> {
+    match (name, arguments) {
+        ("duration_between", [AttributeValue::Date(from), AttributeValue::Date(to)]) => Ok(
+            AttributeValue::Float((*to - *from).num_milliseconds() as f64 / 1000.0),
+        ),
+        ("abs", [AttributeValue::Int(x)]) => x
+            .checked_abs()
+            .map(AttributeValue::Int)
+            .ok_or_else(|| overflow(name, &[&arguments[0]])),
+        ("abs", [AttributeValue::Float(x)]) => Ok(AttributeValue::Float(x.abs())),
+        ("round", [AttributeValue::Int(x)]) => Ok(AttributeValue::Int(*x)),
+        ("round", [AttributeValue::Float(x)]) => Ok(AttributeValue::Int(x.round() as i64)),
+        ("min", _) | ("max", _) => {
+            let mut result = arguments[0].clone();
+            for argument in arguments[1..].iter() {
+                let less = compare("<", argument, &result)? == AttributeValue::Boolean(true);
+                if less == (name == "min") && argument != &result {
+                    result = argument.clone();
+                }
+            }
+            Ok(result)
+        }
+        _ => Err(Error::ExpressionError(format!(
+            "cannot apply {} to {:?}",
+            name, arguments
+        ))),
+    }
+}
+
+impl Node {
+    fn evaluate(&self, container: &dyn AttributeContainer) -> Result<Option<AttributeValue>> {
+        Ok(match self {
+            Node::Literal(value) => Some(value.clone()),
+            Node::Attribute(key) => container.get_value(key).cloned(),
+            Node::Negate(node) => match node.evaluate(container)? {
+                Some(AttributeValue::Int(x)) => Some(AttributeValue::Int(
+                    x.checked_neg()
+                        .ok_or_else(|| overflow("-", &[&AttributeValue::Int(x)]))?,
+                )),
+                Some(AttributeValue::Float(x)) => Some(AttributeValue::Float(-x)),
+                Some(other) => return Err(type_error("-", &[&other])),
+                None => None,
+            },
+            Node::Binary(op, a, b) => match (a.evaluate(container)?, b.evaluate(container)?) {
+                (Some(a), Some(b)) if "+-*/%".contains(op.as_str()) => {
+                    Some(arithmetic(op, &a, &b)?)
+                }
+                (Some(a), Some(b)) => Some(compare(op, &a, &b)?),
+                _ => None,
+            },
+            Node::Call(name, arguments) if name == "coalesce" => {
+                for argument in arguments.iter() {
+                    if let Some(value) = argument.evaluate(container)? {
+                        return Ok(Some(value));
+                    }
+                }
+                None
+            }
+            Node::Call(name, arguments) => {
+                let values = arguments
+                    .iter()
+                    .map(|a| a.evaluate(container))
+                    .collect::<Result<Option<Vec<_>>>>()?;
+                match values {
+                    Some(values) => Some(call(name, &values)?),
+                    None => None,
+                }
+            }
+        })
+    }
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        };
+        let root = parser.comparison()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(Error::ExpressionError(format!(
+                "unexpected token {:?} in {:?}",
+                token, source
+            )));
+        }
+
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Evaluate the expression, `None` if it references a missing attribute
+    pub fn evaluate(&self, container: &dyn AttributeContainer) -> Result<Option<AttributeValue>> {
+        self.root.evaluate(container)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::Event;
+    use crate::DateTime;
+
+    use super::*;
+
+    fn evaluate(source: &str) -> Result<Option<AttributeValue>> {
+        let mut event = Event::default();
+        event.attributes.insert(("cost:total", 100));
+        event.attributes.insert(("org:resource", "Mike"));
+        event.attributes.insert((
+            "start",
+            DateTime::parse_from_rfc3339("2021-03-04T10:00:00+00:00").unwrap(),
+        ));
+        event.attributes.insert((
+            "end",
+            DateTime::parse_from_rfc3339("2021-03-04T11:30:00+01:00").unwrap(),
+        ));
+
+        Expression::parse(source)?.evaluate(&event)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let cases: Vec<(&str, AttributeValue)> = vec![
+            (r#""cost:total" * 1.19"#, 119.0.into()),
+            (r#""cost:total" + 2 * 3"#, 106.into()),
+            (r#"("cost:total" + 2) * -3"#, (-306).into()),
+            (r#""cost:total" / 8"#, 12.5.into()),
+            (r#""cost:total" % 7 == 2"#, true.into()),
+            (r#"duration_between("start", "end")"#, 1800.0.into()),
+            (r#"'Dr. ' + "org:resource""#, "Dr. Mike".into()),
+            (r#"max(1, "cost:total", 7.5)"#, 100.into()),
+            (r#"min(round(2.6), abs(-4))"#, 3.into()),
+            (r#"coalesce("cost:extra", 0) + 1"#, 1.into()),
+            (r#""start" < "end" == true"#, true.into()),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(evaluate(source).unwrap(), Some(expected), "{}", source);
+        }
+
+        assert_eq!(evaluate(r#""cost:extra" * 2"#).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for source in [
+            r#""cost:total" *"#,
+            r#""cost:total"#,
+            "unknown(1)",
+            "abs(1, 2)",
+            "1 = 2",
+            "(1",
+            "1 2",
+        ] {
+            assert!(Expression::parse(source).is_err(), "{}", source);
+        }
+
+        assert!(evaluate(r#""org:resource" * 2"#).is_err());
+        assert!(evaluate("1 / 0").is_err());
+        for source in [
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4611686018427387904 * 2",
+            "(-9223372036854775807 - 1) % -1",
+            "abs(-9223372036854775807 - 1)",
+            "-(-9223372036854775807 - 1)",
+        ] {
+            assert!(evaluate(source).is_err(), "{}", source);
+        }
+
+        let nested = format!("{}1{}", "(".repeat(200_000), ")".repeat(200_000));
+        assert!(Expression::parse(&nested)
+            .unwrap_err()
+            .to_string()
+            .contains("nested"));
+        assert!(Expression::parse(&format!("{}1", "- ".repeat(200_000)))
+            .unwrap_err()
+            .to_string()
+            .contains("nested"));
+        assert!(Expression::parse(&format!(
+            "{}1{}",
+            "abs(".repeat(200_000),
+            ")".repeat(200_000)
+        ))
+        .is_err());
+        assert!(evaluate(r#"duration_between("start", 1)"#).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+/// The standard cost extension
+///
+/// Covers the total cost of traces and events and its currency. Cost drivers, i.e. the nested
+/// break down of the total, are not interpreted.
+///
+use crate::error::{Error, Result};
+use crate::stream::extension::Extension;
+use crate::stream::validator::ValidatorFn;
+use crate::stream::{AttributeContainer, AttributeValue, ComponentType, Meta};
+
+pub struct Cost<'a> {
+    pub total: Option<f64>,
+    pub currency: Option<&'a str>,
+    origin: ComponentType,
+}
+
+impl<'a> Extension<'a> for Cost<'a> {
+    const NAME: &'static str = "Cost";
+    const PREFIX: &'static str = "cost";
+    const URI: &'static str = "http://www.xes-standard.org/cost.xesext";
+
+    fn view<T: AttributeContainer + ?Sized>(component: &'a T) -> Result<Self> {
+        let mut cost = Cost {
+            total: None,
+            currency: None,
+            origin: component.hint(),
+        };
+
+        // only traces and events are supported
+        if ComponentType::Trace == cost.origin || ComponentType::Event == cost.origin {
+            // extract total, integers are tolerated as they're common in the wild
+            cost.total = match component.get_value(Cost::TOTAL) {
+                Some(AttributeValue::Float(total)) => Some(*total),
+                Some(AttributeValue::Int(total)) => Some(*total as f64),
+                Some(other) => {
+                    return Err(Error::AttributeError(format!(
+                        "{} is expected to be numeric, got {:?}",
+                        Cost::TOTAL,
+                        other
+                    )))
+                }
+                None => None,
+            };
+
+            // extract currency
+            if let Some(currency) = component.get_value(Cost::CURRENCY) {
+                cost.currency = Some(currency.try_string()?)
+            }
+        }
+
+        Ok(cost)
+    }
+
+    fn validator(_meta: &Meta) -> ValidatorFn {
+        Box::new(|x| {
+            let _ = Cost::view(*x)?;
+            Ok(())
+        })
+    }
+}
+
+impl Cost<'_> {
+    pub const TOTAL: &'static str = "cost:total";
+    pub const CURRENCY: &'static str = "cost:currency";
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::stream::{Event, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_view() {
+        let mut event = Event::default();
+        event.attributes.insert((Cost::TOTAL, 12));
+        event.attributes.insert((Cost::CURRENCY, "EUR"));
+        let view = Cost::view(&event).unwrap();
+        assert_eq!(view.total, Some(12.0));
+        assert_eq!(view.currency, Some("EUR"));
+
+        let trace = Trace::default();
+        assert!(Cost::view(&trace).unwrap().total.is_none());
+
+        event.attributes.insert((Cost::TOTAL, "a lot"));
+        assert!(Cost::view(&event).is_err());
+    }
+}
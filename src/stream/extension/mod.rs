@@ -16,6 +16,7 @@ use std::sync::Mutex;
 
 // expose extensions
 pub use concept::Concept;
+pub use cost::Cost;
 pub use organizational::Org;
 pub use time::Time;
 
@@ -24,6 +25,7 @@ use crate::stream::{AttributeContainer, ExtensionDecl, Meta};
 use crate::{Error, Result};
 
 pub mod concept;
+pub mod cost;
 pub mod organizational;
 pub mod time;
 
@@ -108,6 +110,7 @@ lazy_static! {
     pub static ref REGISTRY: Mutex<Registry> = {
         Mutex::new(Registry::from(vec![
             Concept::registry_entry(),
+            Cost::registry_entry(),
             Org::registry_entry(),
             Time::registry_entry(),
         ]))
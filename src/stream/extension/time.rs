@@ -18,7 +18,8 @@ pub enum TimeType<'a> {
 }
 
 impl TimeType<'_> {
-    fn interval(&self) -> (&DateTime, &DateTime) {
+    /// Represent time as interval, a timestamp results in an interval of length zero
+    pub fn interval(&self) -> (&DateTime, &DateTime) {
         match self {
             TimeType::Timestamp(time) => (time, time),
             TimeType::Interval((t1, t2)) => (t1, t2),
@@ -1,8 +1,16 @@
 //! Filtering event streams.
+//!
+//! Filters are either composed from conditions in code or, e.g. in flow definitions, given as
+//! textual predicates to the `Filter` plugin, see `predicate` for their syntax.
+//!
 
-use crate::error::Result;
+use std::convert::TryFrom;
+
+use crate::error::{Error, Result};
 use crate::stream::observer::{Handler, Observer};
-use crate::stream::{AttributeContainer, Event, Stream, Trace};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::predicate::Predicate;
+use crate::stream::{AttributeContainer, AttributeType, Classifier, Event, Scope, Stream, Trace};
 
 /// A condition aka filter function maps any item to a boolean value
 pub type Condition<'a, T> = Box<dyn Fn(&T) -> Result<bool> + 'a + Send>;
@@ -52,6 +60,44 @@ impl<'a> Handler for Filter<'a> {
     }
 }
 
+impl PluginProvider for Filter<'static> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Filter",
+            "Keep traces or events that match a predicate, e.g. concept:name in [\"a\", \"b\"]",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be filtered")
+                    .typed_attr(
+                        "predicate",
+                        "Predicate that selects the components to keep",
+                        AttributeType::String,
+                    )
+                    .default_attr("scope", "Whether to filter traces or events", |k| {
+                        (k, "event").into()
+                    }),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let predicate = parameters.acquire_attribute("predicate")?;
+                    let predicate = Predicate::parse(predicate.value.try_string()?)?;
+                    let scope = parameters.acquire_attribute("scope")?;
+                    let stream = parameters.acquire_stream("inner")?;
+
+                    Ok(
+                        match Scope::try_from(Some(scope.value.try_string()?.to_string()))? {
+                            Scope::Trace => from_cnf(stream, predicate.cnf()?, Vec::new()),
+                            Scope::Event => from_cnf(stream, Vec::new(), predicate.cnf()?),
+                        }
+                        .into_boxed(),
+                    )
+                })),
+            ),
+        )]
+    }
+}
+
 /// Create pseudo filter function that returns always the same value
 pub fn pseudo_filter<'a, T: 'a + AttributeContainer>(value: bool) -> Condition<'a, T> {
     Box::new(move |_x: &T| Ok(value))
@@ -67,6 +113,20 @@ pub fn drop_err<'a, T: 'a + AttributeContainer>(function: Condition<'a, T>) -> C
     Box::new(move |x: &T| Ok(function(x).unwrap_or(false)))
 }
 
+/// Create a filter function that checks whether the class of an item is one of the given classes
+pub fn class_in<'a, T: 'a + AttributeContainer>(
+    classifier: Classifier,
+    classes: Vec<String>,
+) -> Condition<'a, T> {
+    Box::new(move |x: &T| match classifier.class(x) {
+        Some(class) => Ok(classes.contains(&class)),
+        None => Err(Error::AttributeError(format!(
+            "cannot classify by {:?}",
+            classifier.name
+        ))),
+    })
+}
+
 /// Create an observer based filter from filter functions given in conjunctive normal form
 ///
 /// Creates an instance of observer and populate it with filter handlers. The filter conditions are
@@ -113,7 +173,12 @@ pub fn from_cnf<'a, T: Stream>(
 
 #[cfg(test)]
 pub mod tests {
+    use std::io;
+
+    use crate::dev_util::load_example;
     use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::xes::{XesReader, XesWriter};
     use crate::stream::Component;
     use crate::stream::Sink;
 
@@ -188,4 +253,92 @@ pub mod tests {
 
         assert_eq!(sequence, result.as_string());
     }
+
+    #[test]
+    fn test_class_in() {
+        let buffer = load_example(&["book", "L1.xes"]);
+        let meta = match buffer.clone().next().unwrap() {
+            Some(Component::Meta(meta)) => meta,
+            other => panic!("expected meta data, got {:?}", other),
+        };
+        let classifier = meta.classifier("MXMLLegacyClassifier").unwrap();
+        assert!(meta.classifier("unknown").is_err());
+
+        let classes = vec!["a+complete".to_string(), "d+complete".to_string()];
+        test_filter(
+            buffer.clone(),
+            vec![],
+            vec![vec![class_in(classifier.clone(), classes)]],
+            "[ad][ad][ad][ad][ad][ad]",
+            None,
+        );
+
+        let classifier = Classifier {
+            keys: vec!["org:group".to_string()],
+            ..classifier
+        };
+        let mut filter = from_cnf(buffer, vec![], vec![vec![class_in(classifier, vec![])]]);
+        assert!(Sequencer::default().consume(&mut filter).is_err());
+    }
+
+    #[test]
+    fn test_predicate_filter() {
+        let predicate = Predicate::parse(r#"concept:name in ["a", "d"] or concept:name == 'e'"#);
+        let predicate = predicate.unwrap();
+        test_filter(
+            load_example(&["book", "L1.xes"]),
+            vec![],
+            predicate.cnf().unwrap(),
+            "[aed][ad][ad][ad][ad][ad]",
+            None,
+        );
+
+        let predicate = Predicate::parse("not concept:name == 'Case3.0'").unwrap();
+        let mut filter = from_cnf(
+            load_example(&["book", "L1.xes"]),
+            predicate.cnf().unwrap(),
+            vec![],
+        );
+        let mut log = Log::default();
+        log.consume(&mut filter).unwrap();
+        assert_eq!(log.traces.len(), 5);
+    }
+
+    #[test]
+    fn test_filter_unfolded() {
+        let predicate = Predicate::parse("not concept:name == 'Case3.0'").unwrap();
+        let reader = XesReader::from(join_static_reader!("xes", "book", "L1.xes"));
+        let mut filter = from_cnf(reader.streaming_events(), predicate.cnf().unwrap(), vec![]);
+
+        // the events of the dropped trace must not end up in the previous one
+        let mut writer = XesWriter::new(Vec::new());
+        writer.consume(&mut filter).unwrap();
+        let xes = writer.into_inner();
+
+        let mut log = Log::default();
+        log.consume(&mut XesReader::from(io::BufReader::new(&xes[..])))
+            .unwrap();
+        let mut expected = Log::default();
+        expected
+            .consume(&mut from_cnf(
+                load_example(&["book", "L1.xes"]),
+                predicate.cnf().unwrap(),
+                vec![],
+            ))
+            .unwrap();
+
+        assert_eq!(log.traces.len(), 5);
+        assert!(log.events.is_empty());
+        assert_eq!(
+            log.traces
+                .iter()
+                .map(|t| t.events.len())
+                .collect::<Vec<_>>(),
+            expected
+                .traces
+                .iter()
+                .map(|t| t.events.len())
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -1,9 +1,46 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
+use crate::stream::flow::topology::Plan;
 use crate::{Error, Result};
 
+/// Shared flag to stop the execution of a flow graph
+///
+/// Clones share the same state, i.e. a clone can be handed to a Ctrl-C handler or a GUI while the
+/// graph is executed with [`Graph::execute_cancellable`](crate::stream::flow::Graph::execute_cancellable).
+/// Pipes check the token between components and end their streams early once it is cancelled.
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request all pipes observing this token to stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
 /// The executor protocol
 pub trait Executor {
+    /// Prepare for the execution of a flow graph, called before its jobs are scheduled
+    fn prepare(&mut self, _plan: &Plan) -> Result<()> {
+        Ok(())
+    }
+
     /// Submit jobs for execution
     fn schedule<T, J>(&mut self, jobs: T)
     where
@@ -66,3 +103,306 @@ impl Executor for ThreadExecutor {
         })
     }
 }
+
+/// Run jobs on the blocking thread pool of a tokio runtime
+///
+/// `join` blocks the calling thread, so the executor is used from outside of async code, e.g. by
+/// `asynchronous::execute_graph`.
+///
+#[cfg(feature = "async")]
+pub struct AsyncExecutor {
+    handle: tokio::runtime::Handle,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncExecutor {
+    pub fn new(handle: tokio::runtime::Handle) -> Self {
+        AsyncExecutor {
+            handle,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Use the runtime of the current context, panics outside of a runtime
+    pub fn current() -> Self {
+        Self::new(tokio::runtime::Handle::current())
+    }
+}
+
+#[cfg(feature = "async")]
+impl Executor for AsyncExecutor {
+    fn schedule<T, J>(&mut self, jobs: T)
+    where
+        T: IntoIterator<Item = J>,
+        J: FnOnce() + Send + 'static,
+    {
+        let handle = &self.handle;
+        self.tasks
+            .extend(jobs.into_iter().map(|job| handle.spawn_blocking(job)))
+    }
+
+    fn join(&mut self) -> Result<()> {
+        let handle = &self.handle;
+        let results: Vec<_> = self
+            .tasks
+            .drain(..)
+            .map(|task| {
+                handle
+                    .block_on(task)
+                    .map_err(|e| Error::StreamError(format!("{:?}", e)))
+            })
+            .collect();
+        results.into_iter().collect()
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Sending and shared receiving end of the job queue
+type Queue = (Sender<Job>, Arc<Mutex<Receiver<Job>>>);
+
+/// Run jobs on a fixed number of worker threads
+///
+/// Jobs are started in the order they are scheduled, i.e. in the topological order of a flow
+/// graph, as soon as a worker is idle. Pipes connected by bounded stream channels block each other,
+/// so the pool grows to their number before a graph is executed, see
+/// [`Graph::channel_bound`](crate::stream::flow::Graph::channel_bound). A panicking job doesn't
+/// take down its worker, the panic is reported on `join`.
+///
+pub struct PoolExecutor {
+    size: usize,
+    queue: Option<Queue>,
+    workers: Vec<thread::JoinHandle<Result<()>>>,
+}
+
+impl Default for PoolExecutor {
+    /// One worker per available CPU
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl PoolExecutor {
+    /// Create a pool of `size` workers, at least one
+    pub fn new(size: usize) -> Self {
+        PoolExecutor {
+            size: size.max(1),
+            queue: None,
+            workers: Vec::new(),
+        }
+    }
+
+    /// Number of workers
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn worker(receiver: Arc<Mutex<Receiver<Job>>>) -> thread::JoinHandle<Result<()>> {
+        thread::spawn(move || {
+            let mut result = Ok(());
+            loop {
+                // the lock is released before the job is run
+                let job = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => break,
+                };
+                let job = match job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+
+                if let Err(e) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                    if result.is_ok() {
+                        result = Err(Error::StreamError(format!("{:?}", e)));
+                    }
+                }
+            }
+            result
+        })
+    }
+}
+
+impl Executor for PoolExecutor {
+    /// Grow the pool if there are fewer workers than pipes that have to run at the same time
+    fn prepare(&mut self, plan: &Plan) -> Result<()> {
+        let concurrent = plan.concurrent_pipes();
+        if concurrent > self.size {
+            warn!(
+                "grow pool from {} to {} workers for pipes connected by bounded channels",
+                self.size, concurrent
+            );
+            self.size = concurrent;
+        }
+        Ok(())
+    }
+
+    fn schedule<T, J>(&mut self, jobs: T)
+    where
+        T: IntoIterator<Item = J>,
+        J: FnOnce() + Send + 'static,
+    {
+        let (sender, receiver) = self.queue.get_or_insert_with(|| {
+            let (sender, receiver) = channel::<Job>();
+            (sender, Arc::new(Mutex::new(receiver)))
+        });
+
+        // the pool may have grown since the workers were started
+        while self.workers.len() < self.size {
+            self.workers.push(Self::worker(receiver.clone()));
+        }
+
+        for job in jobs {
+            // workers only quit once the sender is dropped on join
+            if sender.send(Box::new(job)).is_err() {
+                error!("unable to schedule job, workers are gone");
+            }
+        }
+    }
+
+    fn join(&mut self) -> Result<()> {
+        // workers quit once the queue is drained
+        self.queue.take();
+
+        let results: Vec<_> = self
+            .workers
+            .drain(..)
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|e| Error::StreamError(format!("{:?}", e)))
+                    .and_then(|result| result)
+            })
+            .collect();
+        results.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::stream::flow::{Graph, Segment};
+    use crate::stream::stats::Statistics;
+
+    use super::*;
+
+    #[test]
+    fn test_pool_executor() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let mut executor = PoolExecutor::new(2);
+        executor.schedule((0..8).map(|_| {
+            let (running, peak, done) = (running.clone(), peak.clone(), done.clone());
+            move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+        executor.join().unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), 8);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+
+        // the pool is reusable and reports panics
+        executor.schedule(vec![|| panic!("job failed")]);
+        assert!(executor.join().is_err());
+
+        // a single worker runs the pipes of a graph in topological order
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut graph = Graph::default();
+        graph
+            .source("Consumer", Segment::new("Receiver").acquire_stream("log"))
+            .stream(Segment::new("Statistics").emit_artifact("stats"))
+            .unwrap()
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        graph
+            .source(
+                "Producer",
+                Segment::new("XesReader").attribute(("path", path)),
+            )
+            .sink(Segment::new("Sender").emit_stream("log"))
+            .unwrap();
+        graph.execute(&mut PoolExecutor::new(1)).unwrap();
+
+        let stats = graph.artifacts["stats"].downcast_ref::<Statistics>();
+        assert_eq!(stats.unwrap().counts(), [6, 23, 23]);
+    }
+
+    #[test]
+    fn test_pool_executor_bounded() {
+        // the producer blocks once the channel is full, so both pipes need a worker of their own
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut graph = Graph::default();
+        graph
+            .channel_bound(1)
+            .source("Consumer", Segment::new("Receiver").acquire_stream("log"))
+            .stream(Segment::new("Statistics").emit_artifact("stats"))
+            .unwrap()
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        graph
+            .source(
+                "Producer",
+                Segment::new("XesReader").attribute(("path", path)),
+            )
+            .sink(Segment::new("Sender").emit_stream("log"))
+            .unwrap();
+        assert_eq!(graph.explain().unwrap().concurrent_pipes(), 2);
+
+        let mut executor = PoolExecutor::new(1);
+        graph.execute(&mut executor).unwrap();
+        assert_eq!(executor.size(), 2);
+
+        let stats = graph.artifacts["stats"].downcast_ref::<Statistics>();
+        assert_eq!(stats.unwrap().counts(), [6, 23, 23]);
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let build = || {
+            let mut graph = Graph::default();
+            graph
+                .source("Consumer", Segment::new("Receiver").acquire_stream("log"))
+                .stream(Segment::new("Statistics").emit_artifact("stats"))
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            graph
+                .source(
+                    "Producer",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .sink(Segment::new("Sender").emit_stream("log"))
+                .unwrap();
+            graph
+        };
+
+        // an untouched token doesn't interfere
+        let token = CancellationToken::new();
+        let mut graph = build();
+        graph
+            .execute_cancellable(&mut ThreadExecutor::default(), &token)
+            .unwrap();
+        let stats = graph.artifacts["stats"].downcast_ref::<Statistics>();
+        assert_eq!(stats.unwrap().counts(), [6, 23, 23]);
+
+        // pipes stop right away but partial artifacts are still collected
+        let cancelled = token.clone();
+        token.cancel();
+        assert!(cancelled.is_cancelled());
+        let mut graph = build();
+        graph
+            .execute_cancellable(&mut ThreadExecutor::default(), &cancelled)
+            .unwrap();
+        let stats = graph.artifacts["stats"].downcast_ref::<Statistics>();
+        assert_eq!(stats.unwrap().counts(), [0, 0, 0]);
+    }
+}
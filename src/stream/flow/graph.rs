@@ -1,17 +1,87 @@
+use std::any::Any;
 use std::collections::{HashMap, HashSet};
-use std::fmt::Debug;
-use std::sync::mpsc::channel;
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use std::sync::mpsc::{channel, Receiver};
 
 use serde::{Deserialize, Serialize};
 
-use crate::stream::flow::pipe::Pipe;
+use crate::stream::channel::{self as data, DataSender, Payload, TypedReceiver};
 use crate::stream::flow::pipe::PreparedPipe;
+use crate::stream::flow::pipe::{ErrorPolicy, Limits, Pipe, RetryPolicy};
+use crate::stream::flow::segment::ChannelKind;
 use crate::stream::flow::segment::Segment;
-use crate::stream::flow::util::{timeit, toposort, ACNS, SCNS};
-use crate::stream::flow::Executor;
-use crate::stream::AnyArtifact;
+use crate::stream::flow::topology::{ChannelPlan, Endpoint, Plan, Topology};
+use crate::stream::flow::util::{timeit, toposort, ACNS, DCNS, SCNS};
+use crate::stream::flow::{CancellationToken, Executor};
+use crate::stream::plugin::{DataChannelDescription, REGISTRY};
+use crate::stream::provenance::{fingerprint, PIPELINE_KEY, SOURCES_KEY};
+use crate::stream::{AnyArtifact, Artifact, Attribute, AttributeValue};
 use crate::{Error, Result};
 
+/// Job executing a pipe
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Name, error policy and outcome of an executed pipe
+type PipeResult = (String, ErrorPolicy, Result<Vec<(String, AnyArtifact)>>);
+
+/// Pipe that failed during a graph run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipeFailure {
+    pub pipe: String,
+    pub error: String,
+}
+
+/// Outcome of a graph run
+///
+/// A report is stored among the artifacts of the graph on each successful run, see
+/// [`Graph::run_report`]. Failures only show up here if the pipe's [`ErrorPolicy`] is to skip or
+/// the run was cancelled, otherwise the run fails right away.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Pipes that terminated successfully
+    pub completed: Vec<String>,
+    /// Pipes that failed and were skipped
+    pub failed: Vec<PipeFailure>,
+    /// Named artifacts that were never emitted due to failed pipes
+    pub missing: Vec<String>,
+    /// Whether the run was cancelled
+    pub cancelled: bool,
+}
+
+impl RunReport {
+    /// Whether all pipes completed and all artifacts arrived
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.missing.is_empty() && !self.cancelled
+    }
+}
+
+#[typetag::serde]
+impl Artifact for RunReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// External receiving end of a data channel, see [`Graph::subscribe`]
+struct Subscription {
+    type_name: &'static str,
+    sender: DataSender,
+}
+
+impl Debug for Subscription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
 /// Directed, acyclic event stream processing graph
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Graph {
@@ -19,6 +89,10 @@ pub struct Graph {
     pub artifacts: HashMap<String, AnyArtifact>,
     pub staging: Option<Pipe>,
     pub pipes: Vec<Pipe>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel_bound: Option<usize>,
+    #[serde(skip)]
+    subscriptions: HashMap<String, Subscription>,
 }
 
 impl Default for Graph {
@@ -28,6 +102,8 @@ impl Default for Graph {
             artifacts: HashMap::new(),
             staging: None,
             pipes: Vec::new(),
+            channel_bound: None,
+            subscriptions: HashMap::new(),
         }
     }
 }
@@ -59,6 +135,56 @@ impl Graph {
         }
     }
 
+    /// Retry the staging pipe on transient failures
+    ///
+    /// If a pipe is staging, the policy is applied to it. Otherwise, or if the policy is invalid,
+    /// an error occurs.
+    ///
+    pub fn retry(&mut self, policy: RetryPolicy) -> Result<&mut Self> {
+        policy.validate()?;
+        match &mut self.staging {
+            Some(pipe) => {
+                pipe.retry(policy);
+                Ok(self)
+            }
+            None => Err(Error::FlowError(
+                "nothing is staging, call `source` first".to_string(),
+            )),
+        }
+    }
+
+    /// Limit the resources of the staging pipe
+    ///
+    /// If a pipe is staging, the limits are applied to it. Otherwise, an error occurs.
+    ///
+    pub fn limits(&mut self, limits: Limits) -> Result<&mut Self> {
+        match &mut self.staging {
+            Some(pipe) => {
+                pipe.limits(limits);
+                Ok(self)
+            }
+            None => Err(Error::FlowError(
+                "nothing is staging, call `source` first".to_string(),
+            )),
+        }
+    }
+
+    /// Decide how failures of the staging pipe affect the graph run
+    ///
+    /// If a pipe is staging, the policy is applied to it. Otherwise, an error occurs.
+    ///
+    pub fn on_error(&mut self, policy: ErrorPolicy) -> Result<&mut Self> {
+        match &mut self.staging {
+            Some(pipe) => {
+                pipe.on_error(policy);
+                Ok(self)
+            }
+            None => Err(Error::FlowError(
+                "nothing is staging, call `source` first".to_string(),
+            )),
+        }
+    }
+
     /// Add a sink segment
     ///
     /// If a pipe is staging, the stream is added to it and the pipe is closed. Otherwise, an error
@@ -80,41 +206,364 @@ impl Graph {
         Ok(self)
     }
 
+    /// Bound the stream channels between pipes
+    ///
+    /// A sending pipe blocks once `bound` components are buffered, which keeps the memory of a run
+    /// low but requires the pipes on both ends of a channel to run at the same time. By default,
+    /// stream channels are unbounded.
+    ///
+    pub fn channel_bound(&mut self, bound: usize) -> &mut Self {
+        self.channel_bound = Some(bound);
+        self
+    }
+
+    /// Derive the topology of all pipes, including the staging one
+    pub fn topology(&self) -> Topology {
+        Topology::new(self.pipes.iter().chain(self.staging.iter()))
+    }
+
+    /// Render the topology as Graphviz DOT
+    pub fn to_dot(&self) -> String {
+        self.topology().to_dot()
+    }
+
+    /// Render the topology as Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        self.topology().to_mermaid()
+    }
+
+    /// Stable fingerprint of the pipe definitions, including the staging one
+    ///
+    /// Artifacts passed into the graph are not taken into account.
+    ///
+    pub fn fingerprint(&self) -> Result<String> {
+        let pipes: Vec<_> = self.pipes.iter().chain(self.staging.iter()).collect();
+        let definition = serde_json::to_vec(&pipes)
+            .map_err(|e| Error::FlowError(format!("unable to serialize pipes: {}", e)))?;
+        Ok(fingerprint(&definition))
+    }
+
+    /// Report of the most recent run, if any
+    pub fn run_report(&self) -> Option<&RunReport> {
+        let generation = self.generation.checked_sub(1)?;
+        self.artifacts
+            .get(&format!("__RUN_REPORT_GEN_{}__", generation))
+            .and_then(|a| a.downcast_ref::<RunReport>())
+    }
+
+    /// Receive the payloads sent through a data channel from outside the graph
+    ///
+    /// The receiver takes the place of a pipe acquiring the channel, e.g. to display the snapshots
+    /// of `IncrementalStatistics` while the graph is executed on another thread. Payloads are
+    /// buffered without bound. The subscription holds for the next execution only, the receiver
+    /// reports the end of the channel once the sending pipe terminates.
+    ///
+    pub fn subscribe<T: Send + 'static>(&mut self, channel: &str) -> TypedReceiver<T> {
+        let (sender, receiver) = data::channel::<Payload>(None);
+        self.subscriptions.insert(
+            channel.to_string(),
+            Subscription {
+                type_name: std::any::type_name::<T>(),
+                sender,
+            },
+        );
+        TypedReceiver::from(receiver)
+    }
+
     fn close(&mut self) {
         if let Some(pipe) = self.staging.take() {
             self.pipes.push(pipe);
         }
     }
 
-    /// Build and execute pipes
+    /// Resolve the execution plan without running anything
     ///
-    /// A number of things happen when the flow graph is executed:
-    /// 1. Pipes register stream/artifact acquisitions/emissions
-    /// 2. A dependency graph is built and checked for potential deadlocks
-    /// 3. Each pipe is turned into a job which is then scheduled for execution at the given executor
-    /// 4. After execution, artifacts are collected and the internal state is updated respectively
+    /// This performs the same acquisitions and deadlock checks as [`Graph::execute`] on a copy of
+    /// the configured pipes (including the staging one).
     ///
-    pub fn execute<E: Executor>(&mut self, executor: &mut E) -> Result<&mut Self> {
-        self.close();
+    pub fn explain(&self) -> Result<Plan> {
+        let pipes: Vec<_> = self
+            .pipes
+            .iter()
+            .chain(self.staging.iter())
+            .cloned()
+            .collect();
+        let topology = Topology::new(pipes.iter());
+
+        // stand-ins for the subscriptions, which are consumed by execution only
+        let subscriptions = self
+            .subscriptions
+            .iter()
+            .map(|(channel, subscription)| {
+                let subscription = Subscription {
+                    type_name: subscription.type_name,
+                    sender: data::channel(None).0,
+                };
+                (channel.clone(), subscription)
+            })
+            .collect();
+        let (prepared, mut scns, mut acns, dcns) =
+            Self::acquire(pipes, subscriptions, self.channel_bound)?;
+
+        // acquire remaining endpoints just like execute does to make dependencies complete
+        acns.set_generation(0);
+        let _artifact_senders: Vec<_> = acns.acquire_remaining_senders()?.collect();
+        acns.set_generation(usize::MAX);
+        let _artifact_receivers: Vec<_> = acns.acquire_remaining_receivers()?.collect();
+        scns.set_generation(0);
+        let _stream_senders: Vec<_> = scns.acquire_remaining_senders()?.collect();
+        scns.set_generation(usize::MAX);
+        let _stream_receivers: Vec<_> = scns.acquire_remaining_receivers()?.collect();
+
+        let schedule = Self::schedule(prepared.keys().copied(), &scns, &acns, &dcns)?;
 
-        let mut scns = SCNS::default();
+        Ok(self.plan(topology, schedule, &scns, &acns, &dcns))
+    }
+
+    /// Assemble the plan of acquired pipes
+    fn plan(
+        &self,
+        topology: Topology,
+        schedule: Vec<usize>,
+        scns: &SCNS,
+        acns: &ACNS,
+        dcns: &DCNS,
+    ) -> Plan {
+        let channels = topology
+            .edges
+            .iter()
+            .map(|edge| ChannelPlan {
+                edge: edge.clone(),
+                bound: match edge.kind {
+                    ChannelKind::Stream => scns.bound(),
+                    ChannelKind::Artifact => acns.bound(),
+                    ChannelKind::Data => dcns.bound(),
+                },
+            })
+            .collect();
+
+        let artifact_edges = || {
+            topology
+                .edges
+                .iter()
+                .filter(|e| e.kind == ChannelKind::Artifact)
+        };
+        let artifacts_in = artifact_edges()
+            .filter(|e| e.from == Endpoint::External)
+            .map(|e| (e.channel.clone(), self.artifacts.contains_key(&e.channel)))
+            .collect();
+        let artifacts_out = artifact_edges()
+            .filter(|e| e.to == Endpoint::External)
+            .map(|e| e.channel.clone())
+            .collect();
+
+        Plan {
+            topology,
+            schedule,
+            channels,
+            artifacts_in,
+            artifacts_out,
+        }
+    }
+
+    /// Provide segments recording provenance with the pipeline fingerprint and the source paths
+    ///
+    /// Sources are the `path` attributes of the first segment of all pipes. Both are passed as
+    /// reserved attributes, see [`PIPELINE_KEY`] and [`SOURCES_KEY`].
+    ///
+    fn provide_provenance(&mut self) -> Result<()> {
+        let records = |segment: &Segment| segment.attribute_value("provenance").is_some();
+        if !self.pipes.iter().flat_map(|p| p.segments()).any(records) {
+            return Ok(());
+        }
+
+        let pipeline = self.fingerprint()?;
+        let sources: Vec<_> = self
+            .pipes
+            .iter()
+            .filter_map(|p| p.segments().next()?.attribute_value("path"))
+            .filter_map(|path| path.try_string().ok())
+            .map(|path| Attribute::new("source", path))
+            .collect();
+
+        for segment in self.pipes.iter_mut().flat_map(|p| p.segments_mut()) {
+            if records(segment) {
+                segment.provide(Attribute::new(PIPELINE_KEY, pipeline.as_str()));
+                let sources = AttributeValue::List(sources.clone());
+                segment.provide(Attribute::new(SOURCES_KEY, sources));
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepare pipes, i.e. acquire channel endpoints in order of generation starting at 1
+    #[allow(clippy::type_complexity)]
+    fn acquire<I: IntoIterator<Item = Pipe>>(
+        pipes: I,
+        subscriptions: HashMap<String, Subscription>,
+        bound: Option<usize>,
+    ) -> Result<(HashMap<usize, PreparedPipe>, SCNS, ACNS, DCNS)> {
+        let pipes: Vec<_> = pipes.into_iter().collect();
+        Self::check_data_channels(&pipes, &subscriptions)?;
+
+        let mut scns = bound.map_or_else(SCNS::default, SCNS::sync);
         let mut acns = ACNS::default();
-        let mut pipes: HashMap<usize, PreparedPipe> = HashMap::new();
-        let mut artifacts: HashMap<_, _> = HashMap::new();
+        let mut dcns = DCNS::default();
+        let mut prepared = HashMap::new();
 
-        // store a copy of current configuration
-        artifacts.insert(
-            format!("__PIPES_GEN_{}__", &self.generation),
-            AnyArtifact::from(self.pipes.clone()),
-        );
+        // subscribers receive like a pipe that depends on all others
+        dcns.set_generation(usize::MAX);
+        for (channel, subscription) in subscriptions {
+            dcns.provide_sender(&channel, subscription.sender)?;
+        }
 
-        // prepare pipes, i.e. acquire artifacts and streams
-        for (generation, pipe) in (1..).zip(self.pipes.drain(..)) {
+        for (generation, pipe) in (1..).zip(pipes) {
             scns.set_generation(generation);
             acns.set_generation(generation);
-            pipes.insert(generation, pipe.acquire(&mut scns, &mut acns)?);
+            dcns.set_generation(generation);
+            prepared.insert(generation, pipe.acquire(&mut scns, &mut acns, &mut dcns)?);
         }
 
+        Ok((prepared, scns, acns, dcns))
+    }
+
+    /// Check that every data channel connects a sender and a receiver of the same payload type
+    ///
+    /// Payload types are taken from the plugin declarations, see
+    /// [`Declaration::data_sender`](crate::stream::plugin::Declaration::data_sender).
+    ///
+    fn check_data_channels(
+        pipes: &[Pipe],
+        subscriptions: &HashMap<String, Subscription>,
+    ) -> Result<()> {
+        let registry = REGISTRY.lock().map_err(|_| {
+            Error::StreamError("unable to acquire stream plugin registry".to_string())
+        })?;
+
+        // channel -> (endpoint, payload type if declared)
+        let mut senders: HashMap<&str, (String, Option<String>)> = HashMap::new();
+        let mut receivers: HashMap<&str, (String, Option<String>)> = HashMap::new();
+
+        for (channel, subscription) in subscriptions.iter() {
+            let type_name = Some(subscription.type_name.to_string());
+            receivers.insert(channel, ("a subscriber".to_string(), type_name));
+        }
+
+        for pipe in pipes.iter() {
+            let endpoint = format!("pipe {:?}", pipe.name());
+            for segment in pipe.segments() {
+                let (sent, expected) = match registry.get(segment.name()) {
+                    Some(entry) => {
+                        let declaration = entry.describe().declaration;
+                        (declaration.data_senders, declaration.data_receivers)
+                    }
+                    None => (vec![], vec![]),
+                };
+                let type_name = |declared: &[DataChannelDescription], i: usize| {
+                    declared.get(i).map(|d| d.type_name.clone())
+                };
+
+                let emissions = segment
+                    .emissions()
+                    .filter(|(kind, _)| *kind == ChannelKind::Data);
+                for (i, (_, channel)) in emissions.enumerate() {
+                    senders.insert(channel, (endpoint.clone(), type_name(&sent, i)));
+                }
+
+                let acquisitions = segment
+                    .acquisitions()
+                    .filter(|(kind, _)| *kind == ChannelKind::Data);
+                for (i, (_, channel)) in acquisitions.enumerate() {
+                    receivers.insert(channel, (endpoint.clone(), type_name(&expected, i)));
+                }
+            }
+        }
+
+        for (channel, (sending, sent)) in senders.iter() {
+            match receivers.get(channel) {
+                None => {
+                    return Err(Error::FlowError(format!(
+                        "data channel {:?} of {} has no receiver",
+                        channel, sending
+                    )))
+                }
+                Some((receiving, Some(expected))) => match sent {
+                    Some(sent) if sent != expected => {
+                        return Err(Error::FlowError(format!(
+                            "data channel {:?} from {} to {} carries {} but {} is expected",
+                            channel, sending, receiving, sent, expected
+                        )))
+                    }
+                    _ => (),
+                },
+                _ => (),
+            }
+        }
+
+        if let Some((channel, (receiving, _))) = receivers
+            .iter()
+            .find(|(channel, _)| !senders.contains_key(*channel))
+        {
+            return Err(Error::FlowError(format!(
+                "data channel {:?} of {} has no sender",
+                channel, receiving
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Order generations such that each pipe is scheduled after the ones it depends on
+    fn schedule<I: IntoIterator<Item = usize>>(
+        generations: I,
+        scns: &SCNS,
+        acns: &ACNS,
+        dcns: &DCNS,
+    ) -> Result<Vec<usize>> {
+        // extract dependencies
+        let dependencies: HashSet<_> = scns
+            .dependencies()?
+            .into_iter()
+            .chain(acns.dependencies()?)
+            .chain(dcns.dependencies()?)
+            .collect();
+        info!("pipe dependencies: {:?}", &dependencies);
+
+        // check for deadlocks
+        let ordering = toposort(dependencies)?;
+        let mut schedule: Vec<_> = generations.into_iter().collect();
+        schedule.sort_unstable();
+        schedule.sort_by_key(|i| ordering.iter().position(|j| j == i).unwrap_or(usize::MAX));
+        schedule.reverse();
+
+        Ok(schedule)
+    }
+
+    /// Acquire pipes and turn them into jobs
+    ///
+    /// Next to the jobs, the receivers of named artifacts and of the results of the pipes are
+    /// returned.
+    ///
+    #[allow(clippy::type_complexity)]
+    fn prepare<E: Executor>(
+        &mut self,
+        topology: Topology,
+        executor: &mut E,
+        token: &CancellationToken,
+    ) -> Result<(
+        Vec<Job>,
+        HashMap<String, Receiver<AnyArtifact>>,
+        Receiver<PipeResult>,
+    )> {
+        self.provide_provenance()?;
+
+        // prepare pipes, i.e. acquire artifacts and streams
+        let subscriptions = mem::take(&mut self.subscriptions);
+        let (mut pipes, mut scns, mut acns, dcns) =
+            Self::acquire(self.pipes.drain(..), subscriptions, self.channel_bound)?;
+        PreparedPipe::count_backlogs(pipes.values_mut());
+        PreparedPipe::trace_origins(pipes.values_mut());
+
         // collect remaining endpoints from partially acquired channels
         // artifact channels
         acns.set_generation(0);
@@ -137,28 +586,18 @@ impl Graph {
         let stream_receivers: HashMap<_, _> = scns.acquire_remaining_receivers()?.collect();
         info!("acquire stream receivers: {:?}", stream_receivers.keys());
 
-        // extract dependencies
-        let dependencies: HashSet<_> = scns
-            .dependencies()?
-            .into_iter()
-            .chain(acns.dependencies()?)
-            .collect();
-        info!("pipe dependencies: {:?}", &dependencies);
-
         // compute schedule and check for deadlocks
-        let ordering = toposort(dependencies)?;
-        let mut schedule: Vec<_> = pipes.keys().copied().collect();
-        schedule.sort_by_key(|i| ordering.iter().position(|j| j == i).unwrap_or(usize::MAX));
-        schedule.reverse();
+        let schedule = Self::schedule(pipes.keys().copied(), &scns, &acns, &dcns)?;
+        let plan = self.plan(topology, schedule, &scns, &acns, &dcns);
+        executor.prepare(&plan)?;
 
         // provide jobs with a channel endpoint to send back results
-        let (result_sender, result_receiver) =
-            channel::<(String, Result<Vec<(String, AnyArtifact)>>)>();
+        let (result_sender, result_receiver) = channel::<PipeResult>();
 
         // schedule jobs
-        info!("prepare {} jobs", schedule.len());
-        let mut jobs = Vec::new();
-        for (i, generation) in schedule.iter().enumerate() {
+        info!("prepare {} jobs", plan.schedule.len());
+        let mut jobs: Vec<Job> = Vec::new();
+        for (i, generation) in plan.schedule.iter().enumerate() {
             let pipe = pipes.remove(generation).ok_or_else(|| {
                 Error::FlowError(format!(
                     "There's no pipe associated with generation {}",
@@ -168,17 +607,19 @@ impl Graph {
 
             debug!("  {}. {} ({})", i + 1, &pipe.name, &generation);
             let name = pipe.name.clone();
+            let policy = pipe.error_policy();
+            let pipe = pipe.cancellation(token.clone());
             let local_sender = result_sender.clone();
 
             // create actual job
-            jobs.push(move || {
+            jobs.push(Box::new(move || {
                 let (duration, _) = timeit(|| {
                     local_sender
-                        .send((name.clone(), pipe.execute()))
+                        .send((name.clone(), policy, pipe.execute()))
                         .unwrap_or_else(|_| error!("{:?}: unable to send back results", name));
                 });
                 info!("pipe {:?} terminates after {:.2?}", name, duration)
-            })
+            }))
         }
 
         // as long as there's a copy of the sender the receiver will block, thus we drop it explicitly
@@ -187,12 +628,83 @@ impl Graph {
         info!("send {} artifacts out to jobs", artifact_senders.len());
         for (name, sender) in artifact_senders {
             debug!("  send: {}", &name);
-            let artifact = self.artifacts.remove(&name).expect(&name);
+            let artifact = self
+                .artifacts
+                .remove(&name)
+                .ok_or_else(|| Error::FlowError(format!("artifact {:?} is not available", name)))?;
             sender
                 .send(artifact)
                 .map_err(|_| Error::FlowError(format!("unable to send {:}", name)))?;
         }
 
+        Ok((jobs, artifact_receivers, result_receiver))
+    }
+
+    /// Build and execute pipes
+    ///
+    /// A number of things happen when the flow graph is executed:
+    /// 1. Pipes register stream/artifact/data acquisitions/emissions
+    /// 2. A dependency graph is built and checked for potential deadlocks
+    /// 3. Each pipe is turned into a job which is then scheduled for execution at the given executor
+    /// 4. After execution, artifacts are collected and the internal state is updated respectively
+    ///
+    /// Pipes that fail under [`ErrorPolicy::Skip`] don't abort the run, they are recorded in the
+    /// [`RunReport`] instead.
+    ///
+    pub fn execute<E: Executor>(&mut self, executor: &mut E) -> Result<&mut Self> {
+        self.execute_cancellable(executor, &CancellationToken::default())
+    }
+
+    /// Build and execute pipes until they terminate or the token is cancelled
+    ///
+    /// Each job observes a clone of the token. Once it is cancelled, pipes end their streams after
+    /// the current component, i.e. sinks complete and emit whatever they computed so far. Failures
+    /// of pipes that were torn down by cancellation (e.g. a sender whose receiver already quit) and
+    /// artifacts that were never emitted are logged and skipped, so the partial artifacts of all
+    /// other pipes are still collected.
+    ///
+    /// If the graph fails before any job is started, e.g. since a channel has no counterpart or a
+    /// segment can't be built, its pipes and artifacts are left untouched.
+    ///
+    pub fn execute_cancellable<E: Executor>(
+        &mut self,
+        executor: &mut E,
+        token: &CancellationToken,
+    ) -> Result<&mut Self> {
+        self.close();
+
+        // validate what can be checked without consuming pipes
+        Self::check_data_channels(&self.pipes, &self.subscriptions)?;
+        let topology = self.topology();
+        if let Some(edge) = topology.edges.iter().find(|e| {
+            e.kind == ChannelKind::Artifact
+                && e.from == Endpoint::External
+                && !self.artifacts.contains_key(&e.channel)
+        }) {
+            return Err(Error::FlowError(format!(
+                "artifact {:?} is not available",
+                edge.channel
+            )));
+        }
+
+        // pipes are consumed by acquisition, hence they're restored if anything else fails
+        let configured = self.pipes.clone();
+        let (jobs, artifact_receivers, result_receiver) =
+            match self.prepare(topology, executor, token) {
+                Ok(prepared) => prepared,
+                Err(e) => {
+                    self.pipes = configured;
+                    return Err(e);
+                }
+            };
+
+        // store a copy of current configuration
+        let mut artifacts: HashMap<_, _> = HashMap::new();
+        artifacts.insert(
+            format!("__PIPES_GEN_{}__", &self.generation),
+            AnyArtifact::from(configured),
+        );
+
         info!("start execution of {} jobs", jobs.len());
         executor.schedule(jobs);
 
@@ -200,27 +712,369 @@ impl Graph {
         executor.join()?;
 
         info!("collect anonymous artifacts");
-        while let Ok((t_name, result)) = result_receiver.recv() {
+        let mut report = RunReport {
+            cancelled: token.is_cancelled(),
+            ..RunReport::default()
+        };
+        while let Ok((t_name, policy, result)) = result_receiver.recv() {
             debug!("{}: {:?}", t_name, result);
-            for (key, artifact) in result? {
-                artifacts.insert(key, artifact);
+            match result {
+                Ok(emitted) => {
+                    artifacts.extend(emitted);
+                    report.completed.push(t_name);
+                }
+                Err(e) if report.cancelled || policy == ErrorPolicy::Skip => {
+                    warn!("skip {:?}: {:?}", t_name, e);
+                    report.failed.push(PipeFailure {
+                        pipe: t_name,
+                        error: e.to_string(),
+                    });
+                }
+                Err(e) => return Err(e),
             }
         }
 
         info!("collect {} named artifacts", artifact_receivers.len());
         for (name, receiver) in artifact_receivers {
             debug!("  receive: {}", &name);
-            artifacts.insert(
-                name.clone(),
-                receiver
-                    .recv()
-                    .map_err(|_| Error::FlowError(format!("unable to receive {:?}", name)))?,
-            );
+            match receiver.recv() {
+                Ok(artifact) => {
+                    artifacts.insert(name, artifact);
+                }
+                Err(_) if report.cancelled || !report.failed.is_empty() => {
+                    warn!("{:?} was not emitted by a failed pipe", name);
+                    report.missing.push(name);
+                }
+                Err(_) => {
+                    return Err(Error::FlowError(format!("unable to receive {:?}", name)));
+                }
+            }
         }
 
+        artifacts.insert(
+            format!("__RUN_REPORT_GEN_{}__", &self.generation),
+            AnyArtifact::from(report),
+        );
+
         // apply changes now that execution succeeded
         self.generation += 1;
         self.artifacts.extend(artifacts.into_iter());
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::stream::flow::SequentialExecutor;
+    use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType};
+    use crate::stream::provenance::{sidecar_path, Provenance};
+    use crate::stream::stats::Statistics;
+    use crate::stream::void::Void;
+    use crate::stream::Sink;
+
+    use super::*;
+
+    #[test]
+    fn test_error_policy() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let missing =
+            std::env::temp_dir().join(format!("promi_missing_{}.xes", std::process::id()));
+        let missing: String = missing.to_str().unwrap().into();
+
+        let build = |policy| {
+            let mut graph = Graph::default();
+            graph
+                .source(
+                    "Broken",
+                    Segment::new("XesReader").attribute(("path", missing.clone())),
+                )
+                .on_error(policy)
+                .unwrap()
+                .stream(Segment::new("Statistics").emit_artifact("lost"))
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            graph
+                .source(
+                    "Fine",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .stream(Segment::new("Statistics").emit_artifact("stats"))
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            graph
+        };
+
+        assert!(build(ErrorPolicy::FailFast)
+            .execute(&mut SequentialExecutor)
+            .is_err());
+
+        let mut graph = build(ErrorPolicy::Skip);
+        assert!(graph.run_report().is_none());
+        graph.execute(&mut SequentialExecutor).unwrap();
+
+        let stats = graph.artifacts["stats"].downcast_ref::<Statistics>();
+        assert_eq!(stats.unwrap().counts(), [6, 23, 23]);
+
+        let report = graph.run_report().unwrap();
+        assert!(!report.is_success());
+        assert_eq!(report.completed, vec!["Fine".to_string()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].pipe, "Broken");
+        assert_eq!(report.missing, vec!["lost".to_string()]);
+    }
+
+    #[test]
+    fn test_buffered_components() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let build = |max_buffered| {
+            let mut graph = Graph::default();
+            graph
+                .source(
+                    "Producer",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .sink(Segment::new("Sender").emit_stream("log"))
+                .unwrap()
+                .source("Consumer", Segment::new("Receiver").acquire_stream("log"))
+                .limits(Limits {
+                    max_buffered_components: Some(max_buffered),
+                    ..Limits::default()
+                })
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            graph
+        };
+
+        // the producer runs first, hence meta data and all six traces are buffered at once
+        build(7).execute(&mut SequentialExecutor).unwrap();
+        assert!(matches!(
+            build(6).execute(&mut SequentialExecutor).map(|_| ()),
+            Err(Error::ResourceLimit(_))
+        ));
+    }
+
+    #[test]
+    fn test_forward_context() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut graph = Graph::default();
+        graph
+            .source(
+                "Producer",
+                Segment::new("XesReader").attribute(("path", path)),
+            )
+            .sink(Segment::new("Sender").emit_stream("log"))
+            .unwrap()
+            .source("Consumer", Segment::new("Receiver").acquire_stream("log"))
+            .stream(Segment::new("ChaosStream").attribute(("fail_after", 3)))
+            .unwrap()
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+
+        // the failure is traced back to the component the producer read
+        let error = graph.execute(&mut SequentialExecutor).unwrap_err();
+        assert!(
+            error.to_string().contains(
+                r#"at component #4 of pipe "Consumer" from segment "Receiver", received as component #4 of pipe "Producer" from segment "XesReader""#
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_data_channel_check() {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .entry("TextReceiver".into())
+            .or_insert_with(|| {
+                Entry::new(
+                    "TextReceiver",
+                    "Receives text over a data channel",
+                    Factory::new(
+                        Declaration::default().data_receiver::<String, _, _>("text", "Some text"),
+                        FactoryType::Sink(Box::new(|_| -> Result<Box<dyn Sink>> {
+                            Ok(Box::new(Void))
+                        })),
+                    ),
+                )
+            });
+
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let snapshots = |graph: &mut Graph| {
+            graph
+                .source(
+                    "Producer",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .stream(Segment::new("IncrementalStatistics").emit_data("snapshots"))
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+        };
+        let text = |graph: &mut Graph| {
+            graph
+                .source(
+                    "Consumer",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .sink(Segment::new("TextReceiver").acquire_data("snapshots"))
+                .unwrap();
+        };
+        let message = |graph: &Graph| match graph.explain() {
+            Err(Error::FlowError(message)) => message,
+            other => panic!("unexpected result: {:?}", other),
+        };
+
+        let mut graph = Graph::default();
+        snapshots(&mut graph);
+        assert_eq!(
+            message(&graph),
+            r#"data channel "snapshots" of pipe "Producer" has no receiver"#
+        );
+
+        let mut graph = Graph::default();
+        text(&mut graph);
+        assert_eq!(
+            message(&graph),
+            r#"data channel "snapshots" of pipe "Consumer" has no sender"#
+        );
+
+        let mut graph = Graph::default();
+        snapshots(&mut graph);
+        text(&mut graph);
+        let message = message(&graph);
+        assert!(message.contains(r#"from pipe "Producer" to pipe "Consumer""#));
+        assert!(message.contains("Statistics"));
+        assert!(message.contains("String"));
+    }
+
+    #[test]
+    fn test_failed_preparation() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut graph = Graph::default();
+        graph
+            .source(
+                "Producer",
+                Segment::new("XesReader").attribute(("path", path)),
+            )
+            .stream(Segment::new("IncrementalStatistics").emit_data("snapshots"))
+            .unwrap()
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+
+        // the data channel is checked before pipes are consumed
+        assert!(graph.execute(&mut SequentialExecutor).is_err());
+        assert_eq!(graph.pipes.len(), 1);
+
+        // so are the artifacts the graph has to provide
+        let mut graph = Graph::default();
+        graph
+            .source(
+                "Consumer",
+                Segment::new("VoidStream").acquire_artifact("model"),
+            )
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        match graph.execute(&mut SequentialExecutor) {
+            Err(Error::FlowError(message)) => assert!(message.contains("\"model\"")),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(graph.pipes.len(), 1);
+
+        // pipes are restored if they can't be scheduled, e.g. due to a cycle
+        let mut graph = Graph::default();
+        for (from, to) in [("a", "b"), ("b", "a")].iter() {
+            graph
+                .source(*from, Segment::new("Receiver").acquire_stream(*from))
+                .sink(Segment::new("Sender").emit_stream(*to))
+                .unwrap();
+        }
+        assert!(graph.execute(&mut SequentialExecutor).is_err());
+        assert_eq!(graph.pipes.len(), 2);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let output = std::env::temp_dir()
+            .join(format!("promi_graph_provenance_{}.xes", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut graph = Graph::default();
+        graph
+            .source(
+                "Export",
+                Segment::new("XesReader").attribute(("path", path.as_str())),
+            )
+            .sink(
+                Segment::new("XesWriter")
+                    .attribute(("path", output.as_str()))
+                    .attribute(("provenance", true))
+                    .attribute((PIPELINE_KEY, "configured")),
+            )
+            .unwrap();
+        let fingerprint = graph.fingerprint().unwrap();
+        graph.execute(&mut SequentialExecutor).unwrap();
+
+        // fingerprint and sources are provided by the graph, not the configuration
+        let provenance = Provenance::load(&output).unwrap();
+        assert_eq!(provenance.pipeline, Some(fingerprint));
+        assert_eq!(provenance.sources, [path]);
+
+        std::fs::remove_file(&output).unwrap();
+        std::fs::remove_file(sidecar_path(&output)).unwrap();
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let build = || {
+            let mut graph = Graph::default();
+            graph
+                .source(
+                    "Producer",
+                    Segment::new("XesReader").attribute(("path", path.clone())),
+                )
+                .stream(
+                    Segment::new("IncrementalStatistics")
+                        .attribute(("every", 2))
+                        .emit_data("snapshots"),
+                )
+                .unwrap()
+                .sink(Segment::new("VoidSink"))
+                .unwrap();
+            graph
+        };
+
+        let mut graph = build();
+        let snapshots = graph.subscribe::<Statistics>("snapshots");
+        graph.explain().unwrap();
+        let worker = thread::spawn(move || {
+            graph.execute(&mut SequentialExecutor).unwrap();
+        });
+
+        let mut counts = Vec::new();
+        while let Some(statistics) = snapshots.recv().unwrap() {
+            counts.push(statistics.counts());
+        }
+        worker.join().unwrap();
+        assert_eq!(counts, vec![[2, 7, 7], [4, 15, 15], [6, 23, 23]]);
+
+        let mut graph = build();
+        let _ = graph.subscribe::<String>("snapshots");
+        match graph.explain() {
+            Err(Error::FlowError(message)) => {
+                assert!(message.contains(r#"from pipe "Producer" to a subscriber"#))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
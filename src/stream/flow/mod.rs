@@ -63,12 +63,16 @@
 //!# }
 //! ```
 //!
-pub use executor::{Executor, SequentialExecutor, ThreadExecutor};
-pub use graph::Graph;
-pub use segment::Segment;
+#[cfg(feature = "async")]
+pub use executor::AsyncExecutor;
+pub use executor::{CancellationToken, Executor, PoolExecutor, SequentialExecutor, ThreadExecutor};
+pub use graph::{Graph, PipeFailure, RunReport};
+pub use pipe::{ErrorPolicy, Limits, RetryPolicy};
+pub use segment::{ChannelKind, Segment};
 
 pub mod executor;
 pub mod graph;
 pub mod pipe;
 pub mod segment;
+pub mod topology;
 pub mod util;
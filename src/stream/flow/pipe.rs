@@ -1,14 +1,201 @@
 use std::any::Any;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
+use crate::stream::context::{self, Sequencer};
+use crate::stream::flow::executor::CancellationToken;
 use crate::stream::flow::segment::{PreparedSegment, Segment};
-use crate::stream::flow::util::{timeit, ACNS, SCNS};
-use crate::stream::{AnyArtifact, Artifact, Sink};
+use crate::stream::flow::util::{timeit, Backlog, Origins, ACNS, DCNS, SCNS};
+use crate::stream::{AnyArtifact, Artifact, ResOpt, Sink, Stream};
 use crate::{Error, Result};
 
+/// Tells how often and when a pipe is re-executed after a transient failure
+///
+/// Only pipes that neither send nor receive streams or data are retried as their channels cannot be
+/// rewound. Acquired artifacts are reused, emitted artifacts are only sent on success.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts including the first one
+    pub max_attempts: usize,
+    /// Delay before the first retry in milliseconds
+    pub backoff_ms: u64,
+    /// Factor the delay grows by with each retry
+    pub multiplier: f64,
+    /// Upper bound of the delay in milliseconds
+    #[serde(default = "RetryPolicy::default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_ms: 1000,
+            multiplier: 2.,
+            max_delay_ms: Self::default_max_delay_ms(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a validated policy whose delays are capped at one minute
+    pub fn new(max_attempts: usize, backoff_ms: u64, multiplier: f64) -> Result<Self> {
+        let policy = RetryPolicy {
+            max_attempts,
+            backoff_ms,
+            multiplier,
+            max_delay_ms: Self::default_max_delay_ms(),
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Cap the delay between attempts
+    pub fn max_delay_ms(mut self, max_delay_ms: u64) -> Self {
+        self.max_delay_ms = max_delay_ms;
+        self
+    }
+
+    fn default_max_delay_ms() -> u64 {
+        60_000
+    }
+
+    /// Check that there's at least one attempt and delays don't shrink
+    pub fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(Error::FlowError(
+                "a retry policy needs at least one attempt".to_string(),
+            ));
+        }
+        if !self.multiplier.is_finite() || self.multiplier < 1. {
+            return Err(Error::FlowError(format!(
+                "the multiplier of a retry policy has to be finite and at least 1, got {}",
+                self.multiplier
+            )));
+        }
+        Ok(())
+    }
+
+    /// Delay before the given attempt (starting at 2 for the first retry), at most `max_delay_ms`
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(2).min(i32::MAX as usize) as i32;
+        let max_delay = Duration::from_millis(self.max_delay_ms);
+        Duration::try_from_secs_f64(self.backoff_ms as f64 * self.multiplier.powi(exponent) / 1e3)
+            .map_or(max_delay, |delay| delay.min(max_delay))
+    }
+}
+
+/// Tells what happens to a graph run once a pipe failed for good, i.e. after all retries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// Abort the graph run with the error of the pipe
+    #[default]
+    FailFast,
+    /// Record the failure in the run report and keep collecting the artifacts of other pipes
+    Skip,
+}
+
+/// Soft resource limits of a pipe
+///
+/// Limits are checked whenever the sink of a pipe pulls the next component. Once a limit is
+/// exceeded, the pipe aborts with [`Error::ResourceLimit`].
+///
+/// The pulled component limit bounds the throughput of a pipe. The buffered component limit bounds
+/// the components that other pipes of the graph sent to the stream channels of the pipe but that
+/// it didn't receive yet, i.e. the backlog of a pipe that can't keep up with its producers.
+/// Components sent by external senders of the graph are not counted.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Maximum runtime in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runtime_ms: Option<u64>,
+    /// Maximum number of components pulled by the sink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pulled_components: Option<usize>,
+    /// Maximum number of components buffered in the receiving stream channels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_buffered_components: Option<usize>,
+}
+
+/// Stream wrapper that enforces limits and ends the stream early on cancellation
+struct Guard<'a> {
+    pipe: String,
+    inner: Box<dyn Stream + 'a>,
+    limits: Limits,
+    backlogs: Vec<Backlog>,
+    cancel: CancellationToken,
+    start: Instant,
+    ct_component: usize,
+}
+
+impl<'a> Stream for Guard<'a> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(self.inner.as_ref())
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(self.inner.as_mut())
+    }
+
+    fn next(&mut self) -> ResOpt {
+        // a cancelled pipe terminates like an exhausted one, so the sink still emits artifacts
+        if self.cancel.is_cancelled() {
+            info!(
+                "pipe {:?} cancelled after {} components",
+                self.pipe, self.ct_component
+            );
+            return Ok(None);
+        }
+
+        if let Some(max_runtime_ms) = self.limits.max_runtime_ms {
+            if self.start.elapsed() > Duration::from_millis(max_runtime_ms) {
+                return Err(Error::ResourceLimit(format!(
+                    "pipe {:?} exceeded its runtime of {}ms",
+                    self.pipe, max_runtime_ms
+                )));
+            }
+        }
+
+        if let Some(max_buffered) = self.limits.max_buffered_components {
+            let buffered: usize = self.backlogs.iter().map(|b| b.load(Ordering::SeqCst)).sum();
+            if buffered > max_buffered {
+                return Err(Error::ResourceLimit(format!(
+                    "pipe {:?} exceeded its limit of {} buffered components",
+                    self.pipe, max_buffered
+                )));
+            }
+        }
+
+        let component = self.inner.next()?;
+
+        if component.is_some() {
+            self.ct_component += 1;
+            if let Some(max_components) = self.limits.max_pulled_components {
+                if self.ct_component > max_components {
+                    return Err(Error::ResourceLimit(format!(
+                        "pipe {:?} exceeded its limit of {} pulled components",
+                        self.pipe, max_components
+                    )));
+                }
+            }
+        }
+
+        Ok(component)
+    }
+
+    // the guard is transparent, i.e. it must not add a level of artifacts
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        self.inner.emit_artifacts()
+    }
+}
+
 /// Pipe configuration
 ///
 /// A pipe is a container for arbitrarily many (but at least) one stream segment and an optional
@@ -20,6 +207,12 @@ pub struct Pipe {
     source: Segment,
     streams: Vec<Segment>,
     sink: Option<Segment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry: Option<RetryPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limits: Option<Limits>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_error: Option<ErrorPolicy>,
 }
 
 impl Pipe {
@@ -30,6 +223,9 @@ impl Pipe {
             source,
             streams: Vec::new(),
             sink: None,
+            retry: None,
+            limits: None,
+            on_error: None,
         }
     }
 
@@ -45,22 +241,68 @@ impl Pipe {
         self
     }
 
+    /// Retry the pipe on transient failures
+    pub fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Abort the pipe once it exceeds the given limits
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Decide whether a failure of the pipe aborts the graph run, see [`ErrorPolicy`]
+    pub fn on_error(&mut self, policy: ErrorPolicy) -> &mut Self {
+        self.on_error = Some(policy);
+        self
+    }
+
+    /// Name of the pipe
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All segments in order, starting with the source
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        std::iter::once(&self.source)
+            .chain(self.streams.iter())
+            .chain(self.sink.iter())
+    }
+
+    /// All segments in order, starting with the source
+    pub(in crate::stream::flow) fn segments_mut(&mut self) -> impl Iterator<Item = &mut Segment> {
+        std::iter::once(&mut self.source)
+            .chain(self.streams.iter_mut())
+            .chain(self.sink.iter_mut())
+    }
+
     /// Apply all acquisitions, turning this into a prepared pipe
     pub(in crate::stream::flow) fn acquire(
         self,
         scns: &mut SCNS,
         acns: &mut ACNS,
+        dcns: &mut DCNS,
     ) -> Result<PreparedPipe> {
+        if let Some(policy) = self.retry.as_ref() {
+            policy.validate()?;
+        }
+
         let sink = self.sink.unwrap_or_else(|| Segment::new("VoidSink"));
         Ok(PreparedPipe {
             name: self.name,
-            source_builder: self.source.acquire(scns, acns)?,
+            source_builder: self.source.acquire(scns, acns, dcns)?,
             stream_builder: self
                 .streams
                 .into_iter()
-                .map(|c| c.acquire(scns, acns))
+                .map(|c| c.acquire(scns, acns, dcns))
                 .collect::<Result<_>>()?,
-            sink_builder: sink.acquire(scns, acns)?,
+            sink_builder: sink.acquire(scns, acns, dcns)?,
+            retry: self.retry,
+            limits: self.limits,
+            on_error: self.on_error.unwrap_or_default(),
+            cancel: CancellationToken::default(),
         })
     }
 }
@@ -92,9 +334,99 @@ pub(in crate::stream::flow) struct PreparedPipe {
     source_builder: PreparedSegment,
     stream_builder: Vec<PreparedSegment>,
     sink_builder: PreparedSegment,
+    retry: Option<RetryPolicy>,
+    limits: Option<Limits>,
+    on_error: ErrorPolicy,
+    cancel: CancellationToken,
 }
 
 impl PreparedPipe {
+    /// Stop consuming the stream once the token is cancelled
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// What to do once the pipe failed
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.on_error
+    }
+
+    /// Count the components buffered in the stream channels of pipes that limit them
+    ///
+    /// Both endpoints of such a channel share a counter, see [`Limits::max_buffered_components`].
+    ///
+    pub fn count_backlogs<'p, I: IntoIterator<Item = &'p mut PreparedPipe>>(pipes: I) {
+        let mut pipes: Vec<_> = pipes.into_iter().collect();
+
+        let mut backlogs = HashMap::new();
+        let limited = pipes.iter().filter(|p| {
+            matches!(
+                p.limits,
+                Some(Limits {
+                    max_buffered_components: Some(_),
+                    ..
+                })
+            )
+        });
+        for segment in limited.flat_map(|p| p.segments()) {
+            for (channel, _) in segment.stream_receiver.iter() {
+                backlogs.insert(channel.clone(), Backlog::default());
+            }
+        }
+
+        for segment in pipes.iter_mut().flat_map(|p| p.segments_mut()) {
+            let channels = segment
+                .stream_sender
+                .iter()
+                .map(|(channel, _)| channel)
+                .chain(segment.stream_receiver.iter().map(|(channel, _)| channel));
+            let shared: Vec<_> = channels
+                .filter_map(|channel| Some((channel.clone(), backlogs.get(channel)?.clone())))
+                .collect();
+            segment.backlogs.extend(shared);
+        }
+    }
+
+    /// Forward the context of components sent over stream channels to the receiving pipes
+    ///
+    /// Only channels received by the source segment of a pipe are traced, as the context of a
+    /// received component is attached to the component that is sequenced next.
+    ///
+    pub fn trace_origins<'p, I: IntoIterator<Item = &'p mut PreparedPipe>>(pipes: I) {
+        let mut pipes: Vec<_> = pipes.into_iter().collect();
+
+        let mut origins = HashMap::new();
+        for pipe in pipes.iter() {
+            for (channel, _) in pipe.source_builder.stream_receiver.iter() {
+                origins.insert(channel.clone(), Origins::default());
+            }
+        }
+
+        for segment in pipes.iter_mut().flat_map(|p| p.segments_mut()) {
+            let shared: Vec<_> = segment
+                .stream_sender
+                .iter()
+                .map(|(channel, _)| channel)
+                .chain(segment.stream_receiver.iter().map(|(channel, _)| channel))
+                .filter_map(|channel| Some((channel.clone(), origins.get(channel)?.clone())))
+                .collect();
+            segment.origins.extend(shared);
+        }
+    }
+
+    fn segments(&self) -> impl Iterator<Item = &PreparedSegment> {
+        std::iter::once(&self.source_builder)
+            .chain(self.stream_builder.iter())
+            .chain(std::iter::once(&self.sink_builder))
+    }
+
+    fn segments_mut(&mut self) -> impl Iterator<Item = &mut PreparedSegment> {
+        std::iter::once(&mut self.source_builder)
+            .chain(self.stream_builder.iter_mut())
+            .chain(std::iter::once(&mut self.sink_builder))
+    }
+
     pub fn execute(self) -> Result<Vec<(String, AnyArtifact)>> {
         // concatenate all segments
         let mut segments: Vec<_> = vec![self.source_builder]
@@ -117,34 +449,62 @@ impl PreparedPipe {
         });
         let mut artifacts = artifacts?;
 
+        // counters of the components buffered in receiving stream channels
+        let backlogs: Vec<_> = segments
+            .iter()
+            .flat_map(|s| {
+                s.stream_receiver
+                    .iter()
+                    .filter_map(move |(channel, _)| s.backlogs.get(channel).cloned())
+            })
+            .collect();
+
         // prepare senders for all artifact emissions
         let artifact_senders = segments
             .iter_mut()
             .map(|cb| cb.artifact_sender.drain(..).collect::<BTreeMap<_, _>>())
             .collect::<Vec<_>>();
 
-        // assign artifact acquisitions to segments
-        let mut segments = segments
-            .into_iter()
-            .zip(artifacts.iter_mut().map(|(_, a)| a))
-            .peekable();
+        // consume stream, i.e. actual execution
+        let (name, limits, cancel) = (&self.name, self.limits, &self.cancel);
+        let mut attempt = 1;
+        let (drn_execution, emissions) = loop {
+            // keep a copy of the segments if the pipe may be retried
+            let spare = match self.retry {
+                Some(policy) if attempt < policy.max_attempts => segments
+                    .iter()
+                    .map(|s| s.try_clone())
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            };
 
-        // create stream/sink
-        let mut stream = None;
-        let mut sink = None;
-        while let Some((segment, artifacts)) = segments.next() {
-            if segments.peek().is_some() {
-                stream = Some(segment.into_stream(artifacts.as_mut_slice(), stream)?);
-            } else {
-                sink = Some(segment.into_sink(artifacts.as_mut_slice())?);
-            }
-        }
+            let (duration, emissions) = timeit(|| {
+                Self::consume(
+                    name,
+                    limits,
+                    &backlogs,
+                    cancel,
+                    segments,
+                    artifacts.iter_mut().map(|(_, a)| a),
+                )
+            });
 
-        // consume stream, i.e. actual execution
-        let (drn_execution, emissions) = timeit(|| match (stream, sink) {
-            (Some(mut stream), Some(mut sink)) => sink.consume(&mut stream),
-            _ => unreachable!(),
-        });
+            match (emissions, spare, self.retry) {
+                (Err(error), Some(spare), Some(policy))
+                    if error.is_transient() && !cancel.is_cancelled() =>
+                {
+                    attempt += 1;
+                    let delay = policy.delay(attempt);
+                    warn!(
+                        r#"retry "{}" in {:.3?} ({}/{}): {:?}"#,
+                        &self.name, delay, attempt, policy.max_attempts, error
+                    );
+                    thread::sleep(delay);
+                    segments = spare;
+                }
+                (emissions, _, _) => break (duration, emissions),
+            }
+        };
 
         // emit artifacts that where acquired somewhere else
         let (drn_emission, result) = timeit(|| -> Result<()> {
@@ -171,10 +531,71 @@ impl PreparedPipe {
             .flatten()
             .collect::<Vec<_>>())
     }
+
+    /// Create stream and sink from segments and consume
+    fn consume<'a, I>(
+        name: &str,
+        limits: Option<Limits>,
+        backlogs: &[Backlog],
+        cancel: &CancellationToken,
+        segments: Vec<PreparedSegment>,
+        artifacts: I,
+    ) -> Result<Vec<Vec<AnyArtifact>>>
+    where
+        I: Iterator<Item = &'a mut Vec<AnyArtifact>>,
+    {
+        // assign artifact acquisitions to segments
+        let mut segments = segments.into_iter().zip(artifacts).peekable();
+
+        // create stream/sink, components are sequenced right at the source
+        let mut stream: Option<Box<dyn Stream + 'a>> = None;
+        let mut sink = None;
+        while let Some((segment, artifacts)) = segments.next() {
+            if segments.peek().is_some() {
+                stream = Some(match stream {
+                    None => {
+                        let segment_name = segment.name().to_string();
+                        let source = segment.into_stream(artifacts.as_mut_slice(), None)?;
+                        Box::new(Sequencer::new(source).pipe(name).segment(segment_name))
+                    }
+                    inner => segment.into_stream(artifacts.as_mut_slice(), inner)?,
+                });
+            } else {
+                sink = Some(segment.into_sink(artifacts.as_mut_slice())?);
+            }
+        }
+
+        context::clear();
+        let result = match (stream, sink) {
+            (Some(stream), Some(mut sink)) => sink.consume(&mut Guard {
+                pipe: name.to_string(),
+                inner: stream,
+                limits: limits.unwrap_or_default(),
+                backlogs: backlogs.to_vec(),
+                cancel: cancel.clone(),
+                start: Instant::now(),
+                ct_component: 0,
+            }),
+            _ => unreachable!(),
+        };
+
+        // trace failures back to the last component that was pulled from the source, limits
+        // concern the pipe as a whole though
+        let result = result.map_err(|error| match (context::current(), error) {
+            (_, error @ Error::ResourceLimit(_)) => error,
+            (Some(context), error) => context.wrap(error),
+            (None, error) => error,
+        });
+        context::clear();
+
+        result
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::stream::stats::Statistics;
+
     use super::*;
 
     #[test]
@@ -182,16 +603,130 @@ mod tests {
     fn test_execute() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let mut pipe = Pipe::new("Foo", Segment::new("VoidStream"));
         pipe.stream(Segment::new("Statistics")).sink(Segment::new("VoidSink"));
 
-        let prepared_pipe = pipe.acquire(&mut scns, &mut acns).unwrap();
+        let prepared_pipe = pipe.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
         let artifacts = prepared_pipe.execute().unwrap();
 
         assert!(artifacts.into_iter().next().is_none())
     }
+
+    fn execute(pipe: Pipe) -> Result<Vec<(String, AnyArtifact)>> {
+        let mut scns = SCNS::default();
+        let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
+
+        scns.set_generation(0);
+        acns.set_generation(0);
+        dcns.set_generation(0);
+
+        pipe.acquire(&mut scns, &mut acns, &mut dcns)?.execute()
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::new(4, 20, 2.).unwrap();
+        assert_eq!(policy.delay(2), Duration::from_millis(20));
+        assert_eq!(policy.delay(4), Duration::from_millis(80));
+        assert_eq!(policy.delay(usize::MAX), Duration::from_secs(60));
+        assert_eq!(policy.max_delay_ms(50).delay(4), Duration::from_millis(50));
+        assert!(RetryPolicy::new(0, 20, 2.).is_err());
+        assert!(RetryPolicy::new(4, 20, 0.5).is_err());
+        assert!(RetryPolicy::new(4, 20, f64::NAN).is_err());
+
+        let path = std::env::temp_dir().join(format!("promi_retry_{}.xes", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut pipe = Pipe::new(
+            "Foo",
+            Segment::new("XesReader").attribute(("path", path.to_str().unwrap())),
+        );
+
+        // without retry, the missing file fails immediately
+        assert!(matches!(execute(pipe.clone()), Err(Error::IOError(_))));
+
+        // all attempts fail
+        pipe.retry(RetryPolicy {
+            max_attempts: 3,
+            ..policy
+        });
+        assert!(matches!(execute(pipe.clone()), Err(Error::IOError(_))));
+
+        // invalid policies are rejected before the pipe runs
+        pipe.retry(RetryPolicy {
+            max_attempts: 0,
+            ..policy
+        });
+        assert!(matches!(execute(pipe.clone()), Err(Error::FlowError(_))));
+
+        // the file shows up eventually
+        pipe.retry(policy);
+        let source = join_static!("xes", "book", "L1.xes");
+        let target = path.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            std::fs::copy(source, target).unwrap();
+        });
+        assert!(execute(pipe).is_ok());
+
+        handle.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_limits() {
+        let path: String = join_static_str!("xes", "book", "L1.xes");
+        let mut pipe = Pipe::new("Foo", Segment::new("XesReader").attribute(("path", path)));
+        pipe.stream(Segment::new("Statistics").emit_artifact("stats"));
+
+        let mut scns = SCNS::default();
+        let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
+        scns.set_generation(0);
+        acns.set_generation(0);
+        dcns.set_generation(0);
+
+        // limits do not interfere with artifact emission
+        let prepared = pipe
+            .clone()
+            .limits(Limits {
+                max_runtime_ms: Some(60_000),
+                max_pulled_components: Some(7),
+                max_buffered_components: None,
+            })
+            .clone()
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
+        let receiver = acns.acquire_receiver("stats").unwrap();
+        prepared.execute().unwrap();
+        assert!(receiver
+            .recv()
+            .unwrap()
+            .downcast_ref::<Statistics>()
+            .is_some());
+
+        pipe.limits(Limits {
+            max_runtime_ms: None,
+            max_pulled_components: Some(6),
+            max_buffered_components: None,
+        });
+        assert!(matches!(
+            execute(pipe.clone()),
+            Err(Error::ResourceLimit(_))
+        ));
+
+        pipe.limits(Limits {
+            max_runtime_ms: Some(0),
+            max_pulled_components: None,
+            max_buffered_components: None,
+        });
+        thread::sleep(Duration::from_millis(1));
+        assert!(matches!(execute(pipe), Err(Error::ResourceLimit(_))));
+    }
 }
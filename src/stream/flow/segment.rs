@@ -1,13 +1,30 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::atomic::Ordering;
 
 use serde::{Deserialize, Serialize};
 
-use crate::stream::channel::{StreamReceiver, StreamSender};
-use crate::stream::flow::util::{ArtifactReceiver, ArtifactSender, ACNS, SCNS};
+use crate::stream::channel::{
+    DataEndpoints, DataReceiver, DataSender, StreamReceiver, StreamSender,
+};
+use crate::stream::context;
+use crate::stream::flow::util::{
+    ArtifactReceiver, ArtifactSender, Backlog, Origins, ACNS, DCNS, SCNS,
+};
 use crate::stream::plugin::REGISTRY;
-use crate::stream::{AnyArtifact, Attribute, AttributeMap, Sink, Stream};
+use crate::stream::{
+    AnyArtifact, Attribute, AttributeMap, AttributeValue, Component, ResOpt, Sink, Stream,
+};
 use crate::{Error, Result};
 
+/// Kind of channel that connects segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChannelKind {
+    Stream,
+    Artifact,
+    Data,
+}
+
 /// Atomic unit of a pipe
 ///
 /// A segment describes the configuration of a stream source, intermediate stream or a sink. It does
@@ -30,6 +47,10 @@ pub struct Segment {
     artifact_sender: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     artifact_receiver: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    data_sender: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    data_receiver: Vec<String>,
 }
 
 impl Segment {
@@ -42,6 +63,8 @@ impl Segment {
             stream_receiver: Vec::new(),
             artifact_sender: Vec::new(),
             artifact_receiver: Vec::new(),
+            data_sender: Vec::new(),
+            data_receiver: Vec::new(),
         }
     }
 
@@ -101,11 +124,79 @@ impl Segment {
         self
     }
 
+    /// Acquire sending data channel endpoint
+    pub fn emit_data<S>(mut self, sender: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.data_sender.push(sender.into());
+        self
+    }
+
+    /// Acquire receiving data channel endpoint
+    pub fn acquire_data<S>(mut self, receiver: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.data_receiver.push(receiver.into());
+        self
+    }
+
+    /// Value of an attribute of the segment, if present
+    pub fn attribute_value(&self, key: &str) -> Option<&AttributeValue> {
+        self.attributes_.get_value(key)
+    }
+
+    /// Provide an attribute that isn't part of the configuration, replacing any present one
+    pub(in crate::stream::flow) fn provide(&mut self, attribute: Attribute) {
+        self.attributes_.insert(attribute);
+    }
+
+    /// Name of the plugin this segment is an instance of
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Channels the segment sends to
+    pub fn emissions(&self) -> impl Iterator<Item = (ChannelKind, &str)> {
+        Self::channels(
+            &self.stream_sender,
+            &self.artifact_sender,
+            &self.data_sender,
+        )
+    }
+
+    /// Channels the segment receives from
+    pub fn acquisitions(&self) -> impl Iterator<Item = (ChannelKind, &str)> {
+        Self::channels(
+            &self.stream_receiver,
+            &self.artifact_receiver,
+            &self.data_receiver,
+        )
+    }
+
+    fn channels<'a>(
+        streams: &'a [String],
+        artifacts: &'a [String],
+        data: &'a [String],
+    ) -> impl Iterator<Item = (ChannelKind, &'a str)> {
+        streams
+            .iter()
+            .map(|c| (ChannelKind::Stream, c.as_str()))
+            .chain(
+                artifacts
+                    .iter()
+                    .map(|c| (ChannelKind::Artifact, c.as_str())),
+            )
+            .chain(data.iter().map(|c| (ChannelKind::Data, c.as_str())))
+    }
+
     /// Acquire all channel endpoints, turning this into a prepared segment
     pub(in crate::stream::flow) fn acquire(
         self,
         scns: &mut SCNS,
         acns: &mut ACNS,
+        dcns: &mut DCNS,
     ) -> Result<PreparedSegment> {
         Ok(PreparedSegment {
             name: self.name,
@@ -142,10 +233,129 @@ impl Segment {
                     Ok((k, r))
                 })
                 .collect::<Result<_>>()?,
+            data_sender: self
+                .data_sender
+                .into_iter()
+                .map(|k| {
+                    let s = dcns.acquire_sender(&k)?;
+                    Ok((k, s))
+                })
+                .collect::<Result<_>>()?,
+            data_receiver: self
+                .data_receiver
+                .into_iter()
+                .map(|k| {
+                    let r = dcns.acquire_receiver(&k)?;
+                    Ok((k, r))
+                })
+                .collect::<Result<_>>()?,
+            backlogs: HashMap::new(),
+            origins: HashMap::new(),
         })
     }
 }
 
+/// Stream channel endpoint that keeps track of the components buffered in its channel
+struct Counted<T> {
+    endpoint: T,
+    backlog: Backlog,
+}
+
+impl<T: Stream> Stream for Counted<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        let component = self.endpoint.next()?;
+        if component.is_some() {
+            self.backlog.fetch_sub(1, Ordering::SeqCst);
+        }
+        Ok(component)
+    }
+}
+
+impl<T: Sink> Sink for Counted<T> {
+    fn on_open(&mut self) -> Result<()> {
+        self.endpoint.on_open()
+    }
+
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        // count before sending, such that the receiver never sees a negative backlog
+        self.backlog.fetch_add(1, Ordering::SeqCst);
+        self.endpoint.on_component(component)
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.endpoint.on_close()
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.endpoint.on_error(error)
+    }
+}
+
+/// Stream channel endpoint that passes the context of components on to the receiving pipe
+struct Traced<T> {
+    endpoint: T,
+    origins: Origins,
+}
+
+impl<T: Stream> Stream for Traced<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        let component = self.endpoint.next()?;
+        if component.is_some() {
+            let origin = self
+                .origins
+                .lock()
+                .map_err(|_| Error::FlowError("unable to acquire component origins".into()))?
+                .pop_front();
+            context::forward(origin.flatten());
+        }
+        Ok(component)
+    }
+}
+
+impl<T: Sink> Sink for Traced<T> {
+    fn on_open(&mut self) -> Result<()> {
+        self.endpoint.on_open()
+    }
+
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        // senders of the same channel must not interleave between recording and sending
+        let mut origins = self
+            .origins
+            .lock()
+            .map_err(|_| Error::FlowError("unable to acquire component origins".into()))?;
+        origins.push_back(context::current());
+        let result = self.endpoint.on_component(component);
+        if result.is_err() {
+            origins.pop_back();
+        }
+        result
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.endpoint.on_close()
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.endpoint.on_error(error)
+    }
+}
+
 pub(in crate::stream::flow) struct PreparedSegment {
     name: String,
     attributes: AttributeMap,
@@ -153,9 +363,19 @@ pub(in crate::stream::flow) struct PreparedSegment {
     pub stream_receiver: Vec<(String, StreamReceiver)>,
     pub artifact_sender: Vec<(String, ArtifactSender)>,
     pub artifact_receiver: Vec<(String, ArtifactReceiver)>,
+    pub data_sender: Vec<(String, DataSender)>,
+    pub data_receiver: Vec<(String, DataReceiver)>,
+    /// Counters of the stream channels whose buffered components are limited
+    pub backlogs: HashMap<String, Backlog>,
+    /// Origins of the components in stream channels that connect pipes
+    pub origins: HashMap<String, Origins>,
 }
 
 impl PreparedSegment {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn receive_artifacts(&mut self) -> Result<Vec<(String, AnyArtifact)>> {
         self.artifact_receiver
             .drain(..)
@@ -168,8 +388,90 @@ impl PreparedSegment {
             .collect::<Result<_>>()
     }
 
+    /// Copy the segment if it holds no stream or data channel endpoints
+    pub fn try_clone(&self) -> Option<Self> {
+        let endpoints = self.stream_sender.len()
+            + self.stream_receiver.len()
+            + self.artifact_sender.len()
+            + self.artifact_receiver.len()
+            + self.data_sender.len()
+            + self.data_receiver.len();
+
+        if endpoints > 0 {
+            return None;
+        }
+
+        Some(PreparedSegment {
+            name: self.name.clone(),
+            attributes: self.attributes.clone(),
+            stream_sender: Vec::new(),
+            stream_receiver: Vec::new(),
+            artifact_sender: Vec::new(),
+            artifact_receiver: Vec::new(),
+            data_sender: Vec::new(),
+            data_receiver: Vec::new(),
+            backlogs: HashMap::new(),
+            origins: HashMap::new(),
+        })
+    }
+
+    /// Stream channel endpoints, counting the components of channels with a backlog and tracing
+    /// those of channels with origins
+    #[allow(clippy::type_complexity)]
+    fn stream_endpoints<'a>(&mut self) -> (Vec<Box<dyn Stream + 'a>>, Vec<Box<dyn Sink + 'a>>) {
+        let (backlogs, origins) = (&self.backlogs, &self.origins);
+        let streams = self
+            .stream_receiver
+            .drain(..)
+            .map(|(k, endpoint)| {
+                let mut stream = endpoint.into_boxed();
+                if let Some(backlog) = backlogs.get(&k) {
+                    stream = Box::new(Counted {
+                        endpoint: stream,
+                        backlog: backlog.clone(),
+                    });
+                }
+                if let Some(origins) = origins.get(&k) {
+                    stream = Box::new(Traced {
+                        endpoint: stream,
+                        origins: origins.clone(),
+                    });
+                }
+                stream
+            })
+            .collect();
+        let sinks = self
+            .stream_sender
+            .drain(..)
+            .map(|(k, endpoint)| {
+                let mut sink = endpoint.into_boxed();
+                if let Some(backlog) = backlogs.get(&k) {
+                    sink = Box::new(Counted {
+                        endpoint: sink,
+                        backlog: backlog.clone(),
+                    });
+                }
+                if let Some(origins) = origins.get(&k) {
+                    sink = Box::new(Traced {
+                        endpoint: sink,
+                        origins: origins.clone(),
+                    });
+                }
+                sink
+            })
+            .collect();
+        (streams, sinks)
+    }
+
+    fn data_endpoints(&mut self) -> DataEndpoints {
+        DataEndpoints {
+            senders: self.data_sender.drain(..).map(|(_, s)| s).collect(),
+            receivers: self.data_receiver.drain(..).map(|(_, r)| r).collect(),
+        }
+    }
+
     pub fn into_stream<'a>(
-        self,
+        mut self,
         artifacts: &'a mut [AnyArtifact],
         inner: Option<Box<dyn Stream + 'a>>,
     ) -> Result<Box<dyn Stream + 'a>> {
@@ -181,25 +483,18 @@ impl PreparedSegment {
             .get(&self.name)
             .ok_or_else(|| Error::FlowError(format!("no such stream plugin: {:?}", &self.name)))?;
 
-        entry.factory.build_stream(
+        let data = self.data_endpoints();
+        let (streams, sinks) = self.stream_endpoints();
+        entry.factory.build_stream_with_data(
             self.attributes,
             artifacts,
-            inner
-                .into_iter()
-                .chain(
-                    self.stream_receiver
-                        .into_iter()
-                        .map(|(_, s)| -> Box<dyn Stream + 'a> { s.into_boxed() }),
-                )
-                .collect(),
-            self.stream_sender
-                .into_iter()
-                .map(|(_, r)| -> Box<dyn Sink + 'a> { r.into_boxed() })
-                .collect(),
+            inner.into_iter().chain(streams).collect(),
+            sinks,
+            data,
         )
     }
 
-    pub fn into_sink<'a>(self, artifacts: &'a mut [AnyArtifact]) -> Result<Box<dyn Sink + 'a>> {
+    pub fn into_sink<'a>(mut self, artifacts: &'a mut [AnyArtifact]) -> Result<Box<dyn Sink + 'a>> {
         let registry = REGISTRY.lock().map_err(|_| {
             Error::StreamError("unable to acquire stream plugin registry".to_string())
         })?;
@@ -208,18 +503,11 @@ impl PreparedSegment {
             .get(&self.name)
             .ok_or_else(|| Error::FlowError(format!("no such sink plugin: {:?}", &self.name)))?;
 
-        entry.factory.build_sink(
-            self.attributes,
-            artifacts,
-            self.stream_receiver
-                .into_iter()
-                .map(|(_, s)| -> Box<dyn Stream> { s.into_boxed() })
-                .collect(),
-            self.stream_sender
-                .into_iter()
-                .map(|(_, r)| -> Box<dyn Sink> { r.into_boxed() })
-                .collect(),
-        )
+        let data = self.data_endpoints();
+        let (streams, sinks) = self.stream_endpoints();
+        entry
+            .factory
+            .build_sink_with_data(self.attributes, artifacts, streams, sinks, data)
     }
 }
 
@@ -233,9 +521,11 @@ mod tests {
     fn test_segment_acquire() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .acquire_artifact("Foo")
@@ -243,7 +533,7 @@ mod tests {
             .acquire_stream("Foo")
             .emit_stream("Bar");
 
-        segment.acquire(&mut scns, &mut acns).unwrap();
+        segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
 
         let a_snd: Vec<_> = acns
             .acquire_remaining_senders()
@@ -272,20 +562,59 @@ mod tests {
         assert_eq!(s_rcv, ["Bar"]);
     }
 
+    #[test]
+    fn test_segment_acquire_data() {
+        let mut scns = SCNS::default();
+        let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
+
+        scns.set_generation(0);
+        acns.set_generation(0);
+        dcns.set_generation(1);
+
+        let mut prepared = Segment::new("Foo")
+            .emit_data("Foo")
+            .acquire_data("Bar")
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
+
+        dcns.set_generation(2);
+        let sender = dcns.acquire_sender("Bar").unwrap();
+        let receiver = dcns.acquire_receiver("Foo").unwrap();
+
+        let data = prepared.data_endpoints();
+        assert_eq!(data.senders.len(), 1);
+        assert_eq!(data.receivers.len(), 1);
+
+        sender.send(Box::new(42usize)).unwrap();
+        let payload = data.receivers[0].recv().unwrap();
+        assert_eq!(payload.downcast_ref::<usize>(), Some(&42));
+
+        data.senders[0].send(Box::new("foo")).unwrap();
+        assert!(receiver.recv().is_ok());
+
+        let dependencies: Vec<_> = dcns.dependencies().unwrap().into_iter().collect();
+        assert_eq!(dependencies.len(), 2);
+        assert!(dependencies.contains(&(1, 2)));
+        assert!(dependencies.contains(&(2, 1)));
+    }
+
     #[test]
     #[should_panic(expected = r#"ChannelError("receiver \"Foo\" was already acquired")"#)]
     fn test_segment_acquire_artifact_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .acquire_artifact("Foo")
             .acquire_artifact("Foo");
 
-        segment.acquire(&mut scns, &mut acns).unwrap();
+        segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
     }
 
     #[test]
@@ -293,15 +622,17 @@ mod tests {
     fn test_segment_acquire_stream_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .acquire_stream("Foo")
             .acquire_stream("Foo");
 
-        segment.acquire(&mut scns, &mut acns).unwrap();
+        segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
     }
 
     #[test]
@@ -309,15 +640,17 @@ mod tests {
     fn test_segment_emit_artifact_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .emit_artifact("Foo")
             .emit_artifact("Foo");
 
-        segment.acquire(&mut scns, &mut acns).unwrap();
+        segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
     }
 
     #[test]
@@ -325,29 +658,33 @@ mod tests {
     fn test_segment_emit_stream_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo").emit_stream("Foo").emit_stream("Foo");
 
-        segment.acquire(&mut scns, &mut acns).unwrap();
+        segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
     }
 
     #[test]
     fn test_prepared_segment_receive() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .acquire_artifact("Foo")
             .emit_artifact("Bar")
             .acquire_stream("Foo")
             .emit_stream("Bar");
-        let mut prepared_segment = segment.acquire(&mut scns, &mut acns).unwrap();
+        let mut prepared_segment = segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
         let sender = acns
             .acquire_remaining_senders()
             .unwrap()
@@ -378,16 +715,18 @@ mod tests {
     fn test_prepared_segment_receive_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let segment = Segment::new("Foo")
             .acquire_artifact("Foo")
             .emit_artifact("Bar")
             .acquire_stream("Foo")
             .emit_stream("Bar");
-        let mut prepared_segment = segment.acquire(&mut scns, &mut acns).unwrap();
+        let mut prepared_segment = segment.acquire(&mut scns, &mut acns, &mut dcns).unwrap();
 
         acns.acquire_remaining_senders().unwrap().for_each(drop);
         prepared_segment.receive_artifacts().unwrap();
@@ -397,14 +736,20 @@ mod tests {
     fn test_prepared_segment_into_stream() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let source_segment = Segment::new("VoidStream");
         let stream_segment = Segment::new("Statistics");
-        let source_prepared = source_segment.acquire(&mut scns, &mut acns).unwrap();
-        let stream_prepared = stream_segment.acquire(&mut scns, &mut acns).unwrap();
+        let source_prepared = source_segment
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
+        let stream_prepared = stream_segment
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
 
         let source = source_prepared.into_stream(&mut [], None).unwrap();
         stream_prepared.into_stream(&mut [], Some(source)).unwrap();
@@ -415,12 +760,16 @@ mod tests {
     fn test_prepared_segment_into_stream_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let source_segment = Segment::new("Foo");
-        let source_prepared = source_segment.acquire(&mut scns, &mut acns).unwrap();
+        let source_prepared = source_segment
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
 
         source_prepared.into_stream(&mut [], None).unwrap();
     }
@@ -429,12 +778,16 @@ mod tests {
     fn test_prepared_segment_into_sink() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let source_segment = Segment::new("VoidSink");
-        let source_prepared = source_segment.acquire(&mut scns, &mut acns).unwrap();
+        let source_prepared = source_segment
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
 
         source_prepared.into_sink(&mut []).unwrap();
     }
@@ -444,12 +797,16 @@ mod tests {
     fn test_prepared_segment_into_sink_error() {
         let mut scns = SCNS::default();
         let mut acns = ACNS::default();
+        let mut dcns = DCNS::default();
 
         scns.set_generation(0);
         acns.set_generation(0);
+        dcns.set_generation(0);
 
         let source_segment = Segment::new("Foo");
-        let source_prepared = source_segment.acquire(&mut scns, &mut acns).unwrap();
+        let source_prepared = source_segment
+            .acquire(&mut scns, &mut acns, &mut dcns)
+            .unwrap();
 
         source_prepared.into_sink(&mut []).unwrap();
     }
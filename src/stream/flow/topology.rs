@@ -0,0 +1,474 @@
+//! Static view on the topology of a flow graph
+//!
+//! The topology is derived from the configuration only, i.e. nothing is acquired or executed. It
+//! may be rendered as [Graphviz](https://graphviz.org/) or [Mermaid](https://mermaid-js.github.io)
+//! diagram to inspect and document pipelines before running them. A [`Plan`] additionally
+//! resolves the execution order as [`Graph::execute`](crate::stream::flow::Graph::execute) would.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fmt::Write;
+
+use crate::stream::flow::pipe::Pipe;
+use crate::stream::flow::segment::ChannelKind;
+
+/// End of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Endpoint {
+    /// Segment `.1` of the pipe of generation `.0`
+    Segment(usize, usize),
+    /// The graph itself, i.e. an artifact that is provided or collected by the graph
+    External,
+}
+
+/// Connection between two endpoints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: ChannelKind,
+    pub channel: String,
+    pub from: Endpoint,
+    pub to: Endpoint,
+}
+
+/// A pipe and its segments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub generation: usize,
+    pub name: String,
+    pub segments: Vec<String>,
+}
+
+/// Pipes and channels of a flow graph
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Topology {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Topology {
+    /// Derive the topology of the given pipes, generations are assigned in order starting at 1
+    pub fn new<'a, I: IntoIterator<Item = &'a Pipe>>(pipes: I) -> Self {
+        let mut nodes = Vec::new();
+        let mut channels: BTreeMap<_, (Vec<_>, Vec<_>)> = BTreeMap::new();
+
+        for (generation, pipe) in (1..).zip(pipes) {
+            let mut segments = Vec::new();
+
+            for (i, segment) in pipe.segments().enumerate() {
+                segments.push(segment.name().to_string());
+
+                for (kind, channel) in segment.emissions() {
+                    let entry = channels.entry((kind, channel.to_string())).or_default();
+                    entry.0.push(Endpoint::Segment(generation, i));
+                }
+
+                for (kind, channel) in segment.acquisitions() {
+                    let entry = channels.entry((kind, channel.to_string())).or_default();
+                    entry.1.push(Endpoint::Segment(generation, i));
+                }
+            }
+
+            nodes.push(Node {
+                generation,
+                name: pipe.name().to_string(),
+                segments,
+            });
+        }
+
+        let mut edges = Vec::new();
+        for ((kind, channel), (mut senders, mut receivers)) in channels {
+            if senders.is_empty() {
+                senders.push(Endpoint::External);
+            }
+            if receivers.is_empty() {
+                receivers.push(Endpoint::External);
+            }
+
+            for from in senders.iter() {
+                for to in receivers.iter() {
+                    edges.push(Edge {
+                        kind,
+                        channel: channel.clone(),
+                        from: *from,
+                        to: *to,
+                    });
+                }
+            }
+        }
+
+        Self { nodes, edges }
+    }
+
+    /// Channels that are connected to the graph itself
+    fn externals(&self) -> Vec<(ChannelKind, &str)> {
+        let mut externals: Vec<_> = self
+            .edges
+            .iter()
+            .filter(|e| e.from == Endpoint::External || e.to == Endpoint::External)
+            .map(|e| (e.kind, e.channel.as_str()))
+            .collect();
+        externals.dedup();
+        externals
+    }
+
+    fn node_id(&self, endpoint: &Endpoint, kind: ChannelKind, channel: &str) -> String {
+        match endpoint {
+            Endpoint::Segment(generation, segment) => format!("p{}_s{}", generation, segment),
+            Endpoint::External => {
+                let index = self
+                    .externals()
+                    .iter()
+                    .position(|e| *e == (kind, channel))
+                    .unwrap_or_default();
+                format!("x{}", index)
+            }
+        }
+    }
+
+    /// Render as Graphviz DOT
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let quote = |s: &str| format!("{:?}", s);
+
+        writeln!(dot, "digraph flow {{").unwrap();
+        writeln!(dot, "    rankdir=LR;").unwrap();
+
+        for node in self.nodes.iter() {
+            writeln!(dot, "    subgraph cluster_p{} {{", node.generation).unwrap();
+            writeln!(
+                dot,
+                "        label={};",
+                quote(&format!("{} (generation {})", node.name, node.generation))
+            )
+            .unwrap();
+
+            for (i, segment) in node.segments.iter().enumerate() {
+                writeln!(
+                    dot,
+                    "        p{}_s{} [label={}, shape=box];",
+                    node.generation,
+                    i,
+                    quote(segment)
+                )
+                .unwrap();
+            }
+
+            for i in 1..node.segments.len() {
+                writeln!(
+                    dot,
+                    "        p{0}_s{1} -> p{0}_s{2};",
+                    node.generation,
+                    i - 1,
+                    i
+                )
+                .unwrap();
+            }
+
+            writeln!(dot, "    }}").unwrap();
+        }
+
+        for (i, (_, channel)) in self.externals().iter().enumerate() {
+            writeln!(dot, "    x{} [label={}, shape=note];", i, quote(channel)).unwrap();
+        }
+
+        for edge in self.edges.iter() {
+            let style = match edge.kind {
+                ChannelKind::Stream => "bold",
+                ChannelKind::Artifact => "dashed",
+                ChannelKind::Data => "dotted",
+            };
+            writeln!(
+                dot,
+                "    {} -> {} [label={}, style={}];",
+                self.node_id(&edge.from, edge.kind, &edge.channel),
+                self.node_id(&edge.to, edge.kind, &edge.channel),
+                quote(&edge.channel),
+                style
+            )
+            .unwrap();
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// Render as Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::new();
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "#quot;"));
+
+        writeln!(mermaid, "flowchart LR").unwrap();
+
+        for node in self.nodes.iter() {
+            writeln!(
+                mermaid,
+                "    subgraph p{}[{}]",
+                node.generation,
+                quote(&format!("{} (generation {})", node.name, node.generation))
+            )
+            .unwrap();
+
+            for (i, segment) in node.segments.iter().enumerate() {
+                writeln!(
+                    mermaid,
+                    "        p{}_s{}[{}]",
+                    node.generation,
+                    i,
+                    quote(segment)
+                )
+                .unwrap();
+            }
+
+            for i in 1..node.segments.len() {
+                writeln!(
+                    mermaid,
+                    "        p{0}_s{1} --> p{0}_s{2}",
+                    node.generation,
+                    i - 1,
+                    i
+                )
+                .unwrap();
+            }
+
+            writeln!(mermaid, "    end").unwrap();
+        }
+
+        for (i, (_, channel)) in self.externals().iter().enumerate() {
+            writeln!(mermaid, "    x{}[({})]", i, quote(channel)).unwrap();
+        }
+
+        for edge in self.edges.iter() {
+            let arrow = match edge.kind {
+                ChannelKind::Stream => "==>",
+                ChannelKind::Artifact => "-.->",
+                ChannelKind::Data => "-->",
+            };
+            writeln!(
+                mermaid,
+                "    {} {}|{}| {}",
+                self.node_id(&edge.from, edge.kind, &edge.channel),
+                arrow,
+                quote(&edge.channel),
+                self.node_id(&edge.to, edge.kind, &edge.channel)
+            )
+            .unwrap();
+        }
+
+        mermaid
+    }
+}
+
+/// A channel of a plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelPlan {
+    pub edge: Edge,
+    /// Bound of the channel, `None` if unbounded
+    pub bound: Option<usize>,
+}
+
+/// Resolved execution plan of a flow graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub topology: Topology,
+    /// Generations in order of execution
+    pub schedule: Vec<usize>,
+    pub channels: Vec<ChannelPlan>,
+    /// Artifacts the graph has to provide and whether they are available
+    pub artifacts_in: Vec<(String, bool)>,
+    /// Artifacts that are collected by the graph
+    pub artifacts_out: Vec<String>,
+}
+
+impl Plan {
+    /// Number of pipes that have to run at the same time since they are connected by a bounded
+    /// stream channel
+    pub fn concurrent_pipes(&self) -> usize {
+        let generations: BTreeSet<_> = self
+            .channels
+            .iter()
+            .filter(|c| c.edge.kind == ChannelKind::Stream && c.bound.is_some())
+            .filter_map(|c| match (c.edge.from, c.edge.to) {
+                (Endpoint::Segment(from, _), Endpoint::Segment(to, _)) => Some([from, to]),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        generations.len()
+    }
+
+    fn endpoint(&self, endpoint: &Endpoint) -> String {
+        match endpoint {
+            Endpoint::Segment(generation, segment) => {
+                let node = &self.topology.nodes[generation - 1];
+                format!("{}[{}] {}", node.name, segment, node.segments[*segment])
+            }
+            Endpoint::External => "graph".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "schedule:")?;
+        for (i, generation) in self.schedule.iter().enumerate() {
+            let node = &self.topology.nodes[generation - 1];
+            writeln!(
+                f,
+                "  {}. {} (generation {}): {}",
+                i + 1,
+                node.name,
+                generation,
+                node.segments.join(" > ")
+            )?;
+        }
+
+        writeln!(f, "channels:")?;
+        for channel in self.channels.iter() {
+            let bound = match channel.bound {
+                Some(bound) => format!("bound {}", bound),
+                None => "unbounded".to_string(),
+            };
+            writeln!(
+                f,
+                "  {:?} {:?}: {} -> {} ({})",
+                channel.edge.kind,
+                channel.edge.channel,
+                self.endpoint(&channel.edge.from),
+                self.endpoint(&channel.edge.to),
+                bound
+            )?;
+        }
+
+        writeln!(f, "artifacts:")?;
+        for (name, available) in self.artifacts_in.iter() {
+            let state = if *available { "available" } else { "missing" };
+            writeln!(f, "  in  {:?} ({})", name, state)?;
+        }
+        for name in self.artifacts_out.iter() {
+            writeln!(f, "  out {:?}", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::flow::{Graph, Segment};
+
+    use super::*;
+
+    fn graph() -> Graph {
+        let mut graph = Graph::default();
+        graph
+            .source("Train", Segment::new("XesReader"))
+            .stream(Segment::new("Statistics").emit_artifact("stats"))
+            .unwrap()
+            .stream(Segment::new("Split").emit_stream("test"))
+            .unwrap()
+            .sink(Segment::new("XesWriter"))
+            .unwrap();
+        graph
+            .source("Test", Segment::new("Receiver").acquire_stream("test"))
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_topology() {
+        let topology = graph().topology();
+
+        assert_eq!(topology.nodes.len(), 2);
+        assert_eq!(
+            topology.nodes[0].segments,
+            ["XesReader", "Statistics", "Split", "XesWriter"]
+        );
+        assert_eq!(
+            topology.edges,
+            [
+                Edge {
+                    kind: ChannelKind::Stream,
+                    channel: "test".into(),
+                    from: Endpoint::Segment(1, 2),
+                    to: Endpoint::Segment(2, 0)
+                },
+                Edge {
+                    kind: ChannelKind::Artifact,
+                    channel: "stats".into(),
+                    from: Endpoint::Segment(1, 1),
+                    to: Endpoint::External
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let graph = graph();
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph flow {"));
+        assert!(dot.contains(r#"subgraph cluster_p2 {"#));
+        assert!(dot.contains(r#"p1_s2 -> p2_s0 [label="test", style=bold];"#));
+        assert!(dot.contains(r#"p1_s1 -> x0 [label="stats", style=dashed];"#));
+        assert!(dot.contains(r#"x0 [label="stats", shape=note];"#));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains(r#"subgraph p1["Train (generation 1)"]"#));
+        assert!(mermaid.contains(r#"p1_s2 ==>|"test"| p2_s0"#));
+        assert!(mermaid.contains(r#"p1_s1 -.->|"stats"| x0"#));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut graph = graph();
+        let fingerprint = graph.fingerprint().unwrap();
+        assert_eq!(fingerprint, self::graph().fingerprint().unwrap());
+
+        graph
+            .source("Other", Segment::new("VoidStream"))
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+        assert_ne!(fingerprint, graph.fingerprint().unwrap());
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut graph = graph();
+        graph
+            .source(
+                "Model",
+                Segment::new("VoidStream").acquire_artifact("model"),
+            )
+            .sink(Segment::new("VoidSink"))
+            .unwrap();
+
+        let plan = graph.explain().unwrap();
+
+        let position = |g| plan.schedule.iter().position(|i| *i == g).unwrap();
+        assert_eq!(plan.schedule.len(), 3);
+        assert!(position(1) < position(2));
+        assert_eq!(plan.channels.len(), 3);
+        assert!(plan.channels.iter().all(|c| c.bound.is_none()));
+        assert_eq!(plan.artifacts_in, [("model".to_string(), false)]);
+        assert_eq!(plan.artifacts_out, ["stats"]);
+
+        let explanation = plan.to_string();
+        assert!(explanation.contains("Test (generation 2): Receiver > VoidSink\n"));
+        assert!(explanation
+            .contains("  Stream \"test\": Train[2] Split -> Test[0] Receiver (unbounded)\n"));
+        assert!(explanation.contains("  in  \"model\" (missing)\n"));
+
+        // explaining does not alter the graph
+        assert_eq!(graph.pipes.len(), 3);
+
+        graph
+            .source("Loop", Segment::new("Receiver").acquire_stream("loop"))
+            .sink(Segment::new("Sender").emit_stream("loop"))
+            .unwrap();
+        assert!(graph.explain().is_err());
+    }
+}
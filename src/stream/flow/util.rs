@@ -1,13 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::sync::atomic::AtomicUsize;
 use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use petgraph::algo::toposort as pg_toposort;
 use petgraph::prelude::DiGraph;
 
-use crate::stream::channel::{ChannelNameSpace, Sender};
+use crate::stream::channel::{ChannelNameSpace, Payload, Sender};
+use crate::stream::context::ComponentContext;
 use crate::stream::{AnyArtifact, ResOpt};
 use crate::{Error, Result};
 
@@ -17,6 +20,12 @@ pub(in crate::stream::flow) type ArtifactSender = Sender<AnyArtifact>;
 /// Receiving endpoint of an artifact channel
 pub(in crate::stream::flow) type ArtifactReceiver = Receiver<AnyArtifact>;
 
+/// Number of components sent to a stream channel but not received yet
+pub(in crate::stream::flow) type Backlog = Arc<AtomicUsize>;
+
+/// Contexts of the components sent to a stream channel but not received yet, in order
+pub(in crate::stream::flow) type Origins = Arc<Mutex<VecDeque<Option<ComponentContext>>>>;
+
 /// Stream channel name space
 #[allow(clippy::upper_case_acronyms)]
 pub(in crate::stream::flow) type SCNS = ChannelNameSpace<ResOpt, usize>;
@@ -25,6 +34,10 @@ pub(in crate::stream::flow) type SCNS = ChannelNameSpace<ResOpt, usize>;
 #[allow(clippy::upper_case_acronyms)]
 pub(in crate::stream::flow) type ACNS = ChannelNameSpace<AnyArtifact, usize>;
 
+/// Data channel name space
+#[allow(clippy::upper_case_acronyms)]
+pub(in crate::stream::flow) type DCNS = ChannelNameSpace<Payload, usize>;
+
 /// Measure execution time of given closure
 pub(in crate::stream::flow) fn timeit<T, F>(function: F) -> (Duration, T)
 where
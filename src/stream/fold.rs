@@ -0,0 +1,486 @@
+//! Fold standalone events into traces and flatten traces into standalone events
+//!
+//! Event streams of `Flavor::Stream` don't group events by traces, instead each event refers to
+//! its case by an attribute, usually `case:concept:name`. `Correlate` folds such a stream into
+//! traces and `Flatten` does the opposite, such that both operators are inverse to each other:
+//! * `Flatten` copies the attributes of a trace into each of its events, prefixed with `case:`,
+//!   and emits the events as standalone events.
+//! * `Correlate` groups events by the value of the case attribute into traces named after it.
+//!   Event attributes prefixed with `case:` move to the trace without their prefix.
+//!
+//! Since a stream may be unbounded, `Correlate` can't wait for its end to release traces. A case
+//! is considered complete once no event of it occurred for longer than a timeout, measured in
+//! event time, i.e. by the latest timestamp seen so far. Additionally, the number of open cases
+//! can be limited, in which case the least recently active case is released first. Events of a
+//! released case that arrive later start a new trace of the same name.
+//!
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use chrono::Duration;
+
+use crate::stream::merge::join_value;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    Attribute, AttributeContainer, AttributeType, Component, Event, Flavor, Key, ResOpt, Stream,
+    Trace,
+};
+use crate::{DateTime, Error, Result};
+
+fn timestamp(event: &Event) -> Result<Option<DateTime>> {
+    match event.get_value("time:timestamp") {
+        Some(value) => Ok(Some(*value.try_date()?)),
+        None => Ok(None),
+    }
+}
+
+/// Case that still receives events
+struct OpenCase {
+    trace: Trace,
+    touched: u64,
+    last: Option<DateTime>,
+}
+
+/// Group standalone events into traces by a case attribute
+///
+/// Released traces are emitted as soon as they are complete, all others once the inner stream is
+/// exhausted, in order of their latest event. Events without case attribute are released after
+/// all traces. Traces of the inner stream are passed through and meta data is passed through as
+/// `Flavor::Log`, as the stream holds traces now.
+///
+pub struct Correlate<T: Stream> {
+    stream: T,
+    key: String,
+    prefix: String,
+    timeout: Option<Duration>,
+    max_open: Option<usize>,
+    open: HashMap<String, OpenCase>,
+    touched: BTreeMap<u64, String>,
+    deadlines: BTreeSet<(DateTime, String)>,
+    clock: Option<DateTime>,
+    counter: u64,
+    ready: VecDeque<Trace>,
+    orphans: VecDeque<Event>,
+    exhausted: bool,
+}
+
+impl<T: Stream> Correlate<T> {
+    /// Group events by `case:concept:name`, releasing traces at the end of the stream only
+    pub fn new(stream: T) -> Self {
+        Correlate {
+            stream,
+            key: "case:concept:name".to_string(),
+            prefix: "case:".to_string(),
+            timeout: None,
+            max_open: None,
+            open: HashMap::new(),
+            touched: BTreeMap::new(),
+            deadlines: BTreeSet::new(),
+            clock: None,
+            counter: 0,
+            ready: VecDeque::new(),
+            orphans: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    /// Take the case of events from the given attribute
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Move event attributes with the given prefix to the trace
+    pub fn prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Release a case once no event of it occurred for longer than `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Release the least recently active case once more than `max_open` cases are open
+    pub fn max_open(mut self, max_open: usize) -> Self {
+        self.max_open = Some(max_open);
+        self
+    }
+
+    fn open(&self, id: &str) -> OpenCase {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", id));
+        OpenCase {
+            trace,
+            touched: 0,
+            last: None,
+        }
+    }
+
+    fn close(&mut self, id: &str) {
+        if let Some(case) = self.open.remove(id) {
+            self.touched.remove(&case.touched);
+            if let Some(last) = case.last {
+                self.deadlines.remove(&(last, id.to_string()));
+            }
+            self.ready.push_back(case.trace);
+        }
+    }
+
+    fn add(&mut self, id: String, mut event: Event) -> Result<()> {
+        let timestamp = timestamp(&event)?;
+        if let Some(timestamp) = timestamp {
+            self.clock = Some(self.clock.map_or(timestamp, |clock| clock.max(timestamp)));
+        }
+        self.expire();
+
+        let mut case = match self.open.remove(&id) {
+            Some(case) => {
+                self.touched.remove(&case.touched);
+                if let Some(last) = case.last {
+                    self.deadlines.remove(&(last, id.clone()));
+                }
+                case
+            }
+            None => self.open(&id),
+        };
+
+        // case attributes move to the trace, the first event that carries them wins
+        let keys: Vec<String> = event
+            .attributes
+            .iter()
+            .map(|(key, _, _)| key)
+            .filter(|key| key.starts_with(&self.prefix) && *key != self.key)
+            .map(|key| key.to_string())
+            .collect();
+        for key in keys {
+            let attribute = event.attributes.remove(&key).unwrap();
+            let key = Key::from(&key[self.prefix.len()..]);
+            if case.trace.attributes.get_value(&key).is_none() {
+                case.trace.attributes.insert(Attribute { key, ..attribute });
+            }
+        }
+        if self.key.starts_with(&self.prefix) {
+            event.attributes.remove(&self.key);
+        }
+
+        case.trace.events.push(event);
+        case.touched = self.counter;
+        self.counter += 1;
+        case.last = timestamp.or(case.last);
+
+        self.touched.insert(case.touched, id.clone());
+        if let Some(last) = case.last {
+            self.deadlines.insert((last, id.clone()));
+        }
+        self.open.insert(id, case);
+
+        if let Some(max_open) = self.max_open {
+            while self.open.len() > max_open {
+                let id = self.touched.values().next().unwrap().clone();
+                self.close(&id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Release cases that timed out before the latest event
+    fn expire(&mut self) {
+        if let (Some(timeout), Some(clock)) = (self.timeout, self.clock) {
+            while let Some((last, id)) = self.deadlines.iter().next().cloned() {
+                if clock.signed_duration_since(last) <= timeout {
+                    break;
+                }
+                self.close(&id);
+            }
+        }
+    }
+
+    fn release(&mut self) {
+        let ids: Vec<String> = self.touched.values().cloned().collect();
+        for id in ids {
+            self.close(&id);
+        }
+    }
+}
+
+impl<T: Stream> Stream for Correlate<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        loop {
+            if let Some(trace) = self.ready.pop_front() {
+                return Ok(Some(Component::Trace(trace)));
+            }
+            if self.exhausted {
+                return Ok(self.orphans.pop_front().map(Component::Event));
+            }
+
+            match self.stream.next()? {
+                Some(Component::Event(event)) => {
+                    match event.get_value(&self.key).and_then(join_value) {
+                        Some(id) => self.add(id, event)?,
+                        None => self.orphans.push_back(event),
+                    }
+                }
+                Some(Component::Meta(mut meta)) => {
+                    meta.flavor = Flavor::Log;
+                    return Ok(Some(Component::Meta(meta)));
+                }
+                Some(component) => return Ok(Some(component)),
+                None => {
+                    self.exhausted = true;
+                    self.release();
+                }
+            }
+        }
+    }
+}
+
+/// Explode traces into standalone events
+///
+/// Each event inherits the attributes of its trace, prefixed with `case:`, unless it carries an
+/// attribute of the prefixed key already. Traces without events vanish. Standalone events are
+/// passed through and meta data is passed through as `Flavor::Stream`.
+///
+pub struct Flatten<T: Stream> {
+    stream: T,
+    prefix: String,
+    events: VecDeque<Event>,
+}
+
+impl<T: Stream> Flatten<T> {
+    pub fn new(stream: T) -> Self {
+        Flatten {
+            stream,
+            prefix: "case:".to_string(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Prefix the keys of trace attributes with the given prefix
+    pub fn prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn flatten(&mut self, trace: Trace) {
+        let attributes: Vec<Attribute> = trace.attributes.into_iter().collect();
+        for mut event in trace.events {
+            for attribute in attributes.iter() {
+                let key = Key::from(format!("{}{}", self.prefix, attribute.key));
+                if event.attributes.get_value(&key).is_none() {
+                    event.attributes.insert(Attribute {
+                        key,
+                        ..attribute.clone()
+                    });
+                }
+            }
+            self.events.push_back(event);
+        }
+    }
+}
+
+impl<T: Stream> Stream for Flatten<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(Component::Event(event)));
+            }
+
+            match self.stream.next()? {
+                Some(Component::Trace(trace)) => self.flatten(trace),
+                Some(Component::Meta(mut meta)) => {
+                    meta.flavor = Flavor::Stream;
+                    return Ok(Some(Component::Meta(meta)));
+                }
+                other => return Ok(other),
+            }
+        }
+    }
+}
+
+impl PluginProvider for Correlate<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "Correlate",
+                "Group standalone events into traces by a case attribute",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The event stream")
+                        .default_attr("key", "Event attribute that identifies the case", |k| {
+                            (k, "case:concept:name").into()
+                        })
+                        .default_attr(
+                            "prefix",
+                            "Prefix of event attributes that move to the trace",
+                            |k| (k, "case:").into(),
+                        )
+                        .optional_attr(
+                            "timeout",
+                            "Seconds of event time after which an idle case is released",
+                            AttributeType::Int,
+                        )
+                        .optional_attr(
+                            "max_open",
+                            "Maximal number of open cases, the oldest one is released first",
+                            AttributeType::Int,
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let mut correlate = Correlate::new(parameters.acquire_stream("inner")?)
+                            .key(parameters.acquire_attribute("key")?.value.try_string()?)
+                            .prefix(parameters.acquire_attribute("prefix")?.value.try_string()?);
+
+                        // optional release policies
+                        if let Ok(timeout) = parameters.acquire_attribute("timeout") {
+                            let timeout = *timeout.value.try_int()?;
+                            if timeout < 0 {
+                                return Err(Error::AttributeError(format!(
+                                    "timeout is expected to be non-negative, got {}",
+                                    timeout
+                                )));
+                            }
+                            correlate = correlate.timeout(Duration::seconds(timeout));
+                        }
+                        if let Ok(max_open) = parameters.acquire_attribute("max_open") {
+                            let max_open = *max_open.value.try_int()?;
+                            if max_open < 1 {
+                                return Err(Error::AttributeError(format!(
+                                    "max_open is expected to be positive, got {}",
+                                    max_open
+                                )));
+                            }
+                            correlate = correlate.max_open(max_open as usize);
+                        }
+
+                        Ok(correlate.into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "Flatten",
+                "Explode traces into standalone events that carry the prefixed trace attributes",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The stream to be flattened")
+                        .default_attr("prefix", "Prefix of inherited trace attributes", |k| {
+                            (k, "case:").into()
+                        }),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        Ok(Flatten::new(parameters.acquire_stream("inner")?)
+                            .prefix(parameters.acquire_attribute("prefix")?.value.try_string()?)
+                            .into_boxed())
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::order::OrderGuard;
+    use crate::stream::Sink;
+
+    use super::*;
+
+    fn event(case: &str, activity: &str, minute: u32) -> Event {
+        let mut event = Event::default();
+        event.attributes.insert(("concept:name", activity));
+        event.attributes.insert(("case:concept:name", case));
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    fn names(log: &Log) -> Vec<String> {
+        log.traces
+            .iter()
+            .map(|t| t.get_value("concept:name").unwrap().try_string().unwrap())
+            .map(|n| n.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_fold() {
+        // flattening and correlating restores the log
+        let mut original = Log::default();
+        original
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+
+        let mut flat = Log::default();
+        flat.consume(&mut Flatten::new(load_example(&["book", "L1.xes"])))
+            .unwrap();
+        assert_eq!(flat.meta.flavor, Flavor::Stream);
+        assert!(flat.traces.is_empty());
+        assert_eq!(flat.events.len(), 23);
+
+        let mut log = Log::default();
+        log.consume(&mut OrderGuard::new(Correlate::new(Flatten::new(
+            load_example(&["book", "L1.xes"]),
+        ))))
+        .unwrap();
+        assert_eq!(log.meta.flavor, Flavor::Log);
+        assert_eq!(names(&log), names(&original));
+        for (restored, trace) in log.traces.iter().zip(original.traces.iter()) {
+            assert_eq!(restored.attributes, trace.attributes);
+            assert_eq!(restored.events.len(), trace.events.len());
+            for (a, b) in restored.events.iter().zip(trace.events.iter()) {
+                assert_eq!(a.attributes, b.attributes);
+            }
+        }
+
+        // timeouts and the limit of open cases release traces early
+        let mut buffer = Buffer::default();
+        for event in [
+            event("c1", "a", 0),
+            event("c2", "a", 1),
+            event("c1", "b", 2),
+            event("c3", "a", 3),
+            event("c2", "b", 30),
+            event("c3", "b", 31),
+        ]
+        .iter()
+        .cloned()
+        {
+            buffer.push(Ok(Some(Component::Event(event))));
+        }
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+
+        let mut log = Log::default();
+        log.consume(&mut Correlate::new(buffer.clone()).timeout(Duration::minutes(10)))
+            .unwrap();
+        assert_eq!(names(&log), vec!["c2", "c1", "c3", "c2", "c3"]);
+        assert_eq!(log.events.len(), 1);
+
+        let mut log = Log::default();
+        log.consume(&mut Correlate::new(buffer).max_open(2))
+            .unwrap();
+        assert_eq!(names(&log), vec!["c2", "c1", "c2", "c3"]);
+        assert_eq!(log.traces[1].events.len(), 2);
+    }
+}
@@ -0,0 +1,316 @@
+//! Infer global declarations from data
+//!
+//! Globals declare attributes that every trace or event holds, along with a default value. Many
+//! tools rely on them, but logs often lack them. `InferGlobals` profiles the attributes of a
+//! stream and proposes a global for each attribute that is present on at least a given share of
+//! traces or events and always has the same type.
+//!
+//! Proposals are released as `InferredGlobals` artifact. Optionally, they are written into the
+//! meta data of the stream itself. As the meta component precedes all data, this requires to
+//! buffer the whole stream in memory.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::TimeZone;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::editor::MetaEdit;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::preview::{AttributeProfile, AttributeStats};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeType, AttributeValue, Component, Global, Meta,
+    ResOpt, Scope, Stream,
+};
+use crate::{DateTime, Result};
+
+/// Default value of a global attribute of the given type
+pub fn default_value(hint: &AttributeType) -> Option<AttributeValue> {
+    Some(match hint {
+        AttributeType::String => AttributeValue::String("UNKNOWN".into()),
+        AttributeType::Id => AttributeValue::Id("UNKNOWN".into()),
+        AttributeType::Int => AttributeValue::Int(0),
+        AttributeType::Float => AttributeValue::Float(0.0),
+        AttributeType::Boolean => AttributeValue::Boolean(false),
+        AttributeType::Date => {
+            let epoch: DateTime = chrono::FixedOffset::east_opt(0)
+                .unwrap()
+                .timestamp_opt(0, 0)
+                .unwrap();
+            AttributeValue::Date(epoch)
+        }
+        AttributeType::List => return None,
+    })
+}
+
+/// Proposed global declarations
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InferredGlobals {
+    pub globals: Vec<Global>,
+}
+
+impl InferredGlobals {
+    /// Edits that add the proposals to meta data
+    pub fn edits(&self) -> Vec<MetaEdit> {
+        self.globals
+            .iter()
+            .map(|g| MetaEdit::AddGlobal(g.scope.clone(), g.attributes.clone()))
+            .collect()
+    }
+}
+
+#[typetag::serde]
+impl Artifact for InferredGlobals {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Propose globals for attributes present on at least `threshold` of traces or events
+///
+/// The default threshold is 1.0. Lower ones are useful to find near-complete attributes, but a
+/// global that is not present on each component renders the log invalid.
+///
+pub struct InferGlobals<T: Stream> {
+    stream: T,
+    threshold: f64,
+    apply: bool,
+    profile: AttributeProfile,
+    meta: Option<Meta>,
+    buffer: Option<VecDeque<Component>>,
+    released: bool,
+}
+
+impl<T: Stream> InferGlobals<T> {
+    pub fn new(stream: T) -> Self {
+        InferGlobals {
+            stream,
+            threshold: 1.0,
+            apply: false,
+            profile: AttributeProfile::default(),
+            meta: None,
+            buffer: None,
+            released: false,
+        }
+    }
+
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Write the proposals into the meta data of the stream
+    pub fn apply(mut self, apply: bool) -> Self {
+        self.apply = apply;
+        self
+    }
+
+    /// Proposals based on the data seen so far
+    pub fn inferred(&self) -> InferredGlobals {
+        let propose = |scope: Scope, total: usize, stats: &BTreeMap<String, AttributeStats>| {
+            let attributes: Vec<Attribute> = stats
+                .iter()
+                .filter(|(_, s)| total > 0 && s.count as f64 >= self.threshold * total as f64)
+                .filter(|(_, s)| s.types.len() == 1)
+                .filter_map(|(key, s)| {
+                    default_value(&s.types[0].0).map(|value| Attribute::new(key.as_str(), value))
+                })
+                .collect();
+
+            if attributes.is_empty() {
+                None
+            } else {
+                Some(Global { scope, attributes })
+            }
+        };
+
+        let profile = &self.profile;
+        InferredGlobals {
+            globals: vec![
+                propose(Scope::Trace, profile.traces, &profile.trace_attributes),
+                propose(Scope::Event, profile.events, &profile.event_attributes),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        }
+    }
+
+    fn observe(&mut self, component: &Component) {
+        match component {
+            Component::Trace(trace) => self.profile.observe_trace(trace),
+            Component::Event(event) => self.profile.observe_event(event),
+            Component::Meta(_) => (),
+        }
+    }
+
+    /// Read the whole stream, then release the edited meta data followed by the buffered rest
+    fn next_applied(&mut self) -> ResOpt {
+        if self.buffer.is_none() {
+            let mut buffer = VecDeque::new();
+            while let Some(component) = self.stream.next()? {
+                match component {
+                    Component::Meta(meta) => self.meta = Some(meta),
+                    component => {
+                        self.observe(&component);
+                        buffer.push_back(component);
+                    }
+                }
+            }
+            self.buffer = Some(buffer);
+        }
+
+        if let Some(mut meta) = self.meta.take() {
+            for edit in self.inferred().edits() {
+                edit.apply(&mut meta);
+            }
+            return Ok(Some(Component::Meta(meta)));
+        }
+
+        Ok(self.buffer.as_mut().and_then(|b| b.pop_front()))
+    }
+}
+
+impl<T: Stream> Stream for InferGlobals<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.apply {
+            return self.next_applied();
+        }
+
+        let component = self.stream.next()?;
+        if let Some(component) = &component {
+            self.observe(component);
+        }
+        Ok(component)
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        if self.released {
+            return Ok(vec![]);
+        }
+        self.released = true;
+        Ok(vec![self.inferred().into()])
+    }
+}
+
+impl PluginProvider for InferGlobals<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "InferGlobals",
+            "Propose global declarations for attributes present on most traces or events",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be analyzed")
+                    .default_attr(
+                        "threshold",
+                        "Minimal share of traces or events holding an attribute",
+                        |k| (k, 1.0).into(),
+                    )
+                    .default_attr(
+                        "apply",
+                        "Write proposals into meta data, requires to buffer the stream",
+                        |k| (k, false).into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    Ok(InferGlobals::new(parameters.acquire_stream("inner")?)
+                        .threshold(
+                            *parameters
+                                .acquire_attribute("threshold")?
+                                .value
+                                .try_float()?,
+                        )
+                        .apply(*parameters.acquire_attribute("apply")?.value.try_boolean()?)
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::{Event, Sink, Trace};
+
+    use super::*;
+
+    fn buffer() -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for i in 0..4 {
+            let mut trace = Trace::default();
+            trace
+                .attributes
+                .insert(("concept:name", format!("case {}", i)));
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", "a"));
+            event.attributes.insert((
+                "cost",
+                if i == 0 {
+                    1.5.into()
+                } else {
+                    AttributeValue::Int(1)
+                },
+            ));
+            if i > 0 {
+                event.attributes.insert(("org:resource", "r"));
+            }
+            trace.events.push(event);
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_infer_globals() {
+        let mut stream = InferGlobals::new(buffer());
+        let artifacts = consume(&mut stream).unwrap();
+        let inferred =
+            AnyArtifact::find::<InferredGlobals>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(inferred.globals.len(), 2);
+        assert_eq!(inferred.globals[0].scope, Scope::Trace);
+        assert_eq!(inferred.globals[1].scope, Scope::Event);
+        // the cost has inconsistent types, a resource is not always present
+        assert_eq!(
+            inferred.globals[1].attributes,
+            vec![Attribute::new("concept:name", "UNKNOWN")]
+        );
+
+        let inferred = InferGlobals::new(buffer()).threshold(0.75);
+        let mut stream = inferred;
+        consume(&mut stream).unwrap();
+        assert_eq!(stream.inferred().globals[1].attributes.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_globals() {
+        let mut result = Buffer::default();
+        result
+            .consume(&mut InferGlobals::new(buffer()).apply(true))
+            .unwrap();
+
+        match result.next().unwrap() {
+            Some(Component::Meta(meta)) => assert_eq!(meta.globals.len(), 2),
+            other => panic!("expected meta, got {:?}", other),
+        }
+        assert_eq!(result.len(), 4);
+    }
+}
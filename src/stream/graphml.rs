@@ -0,0 +1,348 @@
+//! GraphML export of graph artifacts
+//!
+//! [GraphML](http://graphml.graphdrawing.org) is an XML based format for graphs that is
+//! understood by common graph editors such as Gephi or yEd. Artifacts that represent a graph
+//! implement `ToGraphMl` to be converted into a `GraphMl` document. The `GraphMlWriter` sink
+//! writes such an artifact to a file.
+//!
+//! Directly-follows graphs, heuristic nets, Petri nets and process trees are supported. Social
+//! networks and transition systems aren't artifacts of promi yet and hence can't be exported.
+//!
+
+use std::fs::File;
+use std::io;
+
+use quick_xml::events::{
+    BytesDecl as QxBytesDecl, BytesEnd as QxBytesEnd, BytesStart as QxBytesStart,
+    BytesText as QxBytesText, Event as QxEvent,
+};
+use quick_xml::Writer as QxWriter;
+
+use crate::model::heuristic::HeuristicNet;
+use crate::model::petri::PetriNet;
+use crate::model::process_tree::ProcessTree;
+use crate::stream::dfg::Dfg;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, AttributeType, Sink};
+use crate::{Error, Result};
+
+/// Type of the values associated with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Boolean,
+    Int,
+    Double,
+    String,
+}
+
+impl KeyType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::Boolean => "boolean",
+            KeyType::Int => "int",
+            KeyType::Double => "double",
+            KeyType::String => "string",
+        }
+    }
+}
+
+/// Scope of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyScope {
+    Node,
+    Edge,
+}
+
+impl KeyScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            KeyScope::Node => "node",
+            KeyScope::Edge => "edge",
+        }
+    }
+}
+
+type Data = Vec<(String, String)>;
+
+/// A GraphML document holding a single graph
+///
+/// Data is attached to nodes and edges by name of previously declared keys. Nodes are referred to
+/// by their id.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphMl {
+    directed: bool,
+    keys: Vec<(String, KeyScope, KeyType)>,
+    nodes: Vec<(String, Data)>,
+    edges: Vec<(String, String, Data)>,
+}
+
+impl GraphMl {
+    pub fn new(directed: bool) -> Self {
+        GraphMl {
+            directed,
+            keys: Vec::new(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Declare a key
+    pub fn key<N: Into<String>>(mut self, name: N, scope: KeyScope, key_type: KeyType) -> Self {
+        self.keys.push((name.into(), scope, key_type));
+        self
+    }
+
+    /// Add a node with data
+    pub fn node<I, K, V>(&mut self, id: &str, data: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: ToString,
+    {
+        let data = self.data(KeyScope::Node, data)?;
+        self.nodes.push((id.to_string(), data));
+        Ok(())
+    }
+
+    /// Add an edge between two nodes with data
+    pub fn edge<I, K, V>(&mut self, source: &str, target: &str, data: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: ToString,
+    {
+        let data = self.data(KeyScope::Edge, data)?;
+        self.edges
+            .push((source.to_string(), target.to_string(), data));
+        Ok(())
+    }
+
+    fn data<I, K, V>(&self, scope: KeyScope, data: I) -> Result<Data>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: ToString,
+    {
+        data.into_iter()
+            .map(|(key, value)| {
+                let key = key.into();
+                if self.keys.iter().any(|(n, s, _)| n == &key && *s == scope) {
+                    Ok((key, value.to_string()))
+                } else {
+                    Err(Error::KeyError(format!("{} key {:?}", scope.as_str(), key)))
+                }
+            })
+            .collect()
+    }
+
+    fn key_id(scope: KeyScope, name: &str) -> String {
+        format!("{}_{}", scope.as_str(), name)
+    }
+
+    fn write_data<W: io::Write>(
+        writer: &mut QxWriter<W>,
+        scope: KeyScope,
+        data: &[(String, String)],
+    ) -> Result<()> {
+        for (key, value) in data.iter() {
+            let mut event = QxBytesStart::owned_name(b"data".to_vec());
+            event.push_attribute(("key", Self::key_id(scope, key).as_str()));
+            writer.write_event(QxEvent::Start(event))?;
+            writer.write_event(QxEvent::Text(QxBytesText::from_plain_str(value)))?;
+            writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"data")))?;
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the document
+    pub fn write<W: io::Write>(&self, writer: W) -> Result<()> {
+        let mut writer = QxWriter::new_with_indent(writer, b' ', 2);
+
+        let declaration = QxBytesDecl::new(b"1.0", Some(b"UTF-8"), None);
+        writer.write_event(QxEvent::Decl(declaration))?;
+
+        let mut event = QxBytesStart::owned_name(b"graphml".to_vec());
+        event.push_attribute(("xmlns", "http://graphml.graphdrawing.org/xmlns"));
+        writer.write_event(QxEvent::Start(event))?;
+
+        for (name, scope, key_type) in self.keys.iter() {
+            let mut event = QxBytesStart::owned_name(b"key".to_vec());
+            event.push_attribute(("id", Self::key_id(*scope, name).as_str()));
+            event.push_attribute(("for", scope.as_str()));
+            event.push_attribute(("attr.name", name.as_str()));
+            event.push_attribute(("attr.type", key_type.as_str()));
+            writer.write_event(QxEvent::Empty(event))?;
+        }
+
+        let mut event = QxBytesStart::owned_name(b"graph".to_vec());
+        let edge_default = if self.directed {
+            "directed"
+        } else {
+            "undirected"
+        };
+        event.push_attribute(("edgedefault", edge_default));
+        writer.write_event(QxEvent::Start(event))?;
+
+        for (id, data) in self.nodes.iter() {
+            let mut event = QxBytesStart::owned_name(b"node".to_vec());
+            event.push_attribute(("id", id.as_str()));
+            writer.write_event(QxEvent::Start(event))?;
+            Self::write_data(&mut writer, KeyScope::Node, data)?;
+            writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"node")))?;
+        }
+
+        for (source, target, data) in self.edges.iter() {
+            let mut event = QxBytesStart::owned_name(b"edge".to_vec());
+            event.push_attribute(("source", source.as_str()));
+            event.push_attribute(("target", target.as_str()));
+            writer.write_event(QxEvent::Start(event))?;
+            Self::write_data(&mut writer, KeyScope::Edge, data)?;
+            writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"edge")))?;
+        }
+
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"graph")))?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"graphml")))?;
+
+        Ok(())
+    }
+
+    /// Serialize the document into a string
+    pub fn to_xml(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        self.write(&mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Artifacts that can be represented as GraphML
+pub trait ToGraphMl {
+    fn to_graphml(&self) -> Result<GraphMl>;
+}
+
+/// Convert an artifact into GraphML, if it's a graph
+pub fn artifact_to_graphml(artifact: &AnyArtifact) -> Result<GraphMl> {
+    if let Some(dfg) = artifact.downcast_ref::<Dfg>() {
+        dfg.to_graphml()
+    } else if let Some(net) = artifact.downcast_ref::<HeuristicNet>() {
+        net.to_graphml()
+    } else if let Some(net) = artifact.downcast_ref::<PetriNet>() {
+        net.to_graphml()
+    } else if let Some(tree) = artifact.downcast_ref::<ProcessTree>() {
+        tree.to_graphml()
+    } else {
+        Err(Error::ArtifactError(format!(
+            "expected graph artifact, got {:?}",
+            artifact
+        )))
+    }
+}
+
+/// Write a graph artifact as GraphML once the stream is closed
+pub struct GraphMlWriter<W: io::Write> {
+    writer: W,
+    graph: GraphMl,
+}
+
+impl<W: io::Write> GraphMlWriter<W> {
+    pub fn new(writer: W, graph: GraphMl) -> Self {
+        GraphMlWriter { writer, graph }
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write + Send> Sink for GraphMlWriter<W> {
+    fn on_close(&mut self) -> Result<()> {
+        self.graph.write(&mut self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl PluginProvider for GraphMlWriter<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "GraphMlWriter",
+            "Write a graph artifact (e.g. a directly-follows graph or Petri net) as GraphML",
+            Factory::new(
+                Declaration::default()
+                    .artifact("graph", "The graph to be written")
+                    .typed_attr(
+                        "path",
+                        "Location of the GraphML file",
+                        AttributeType::String,
+                    ),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let graph = artifact_to_graphml(parameters.acquire_artifact("graph")?)?;
+                    let path = parameters.acquire_attribute("path")?;
+                    let file = File::create(path.value.try_string()?)?;
+                    Ok(GraphMlWriter::new(file, graph).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::plugin::REGISTRY;
+    use crate::stream::AttributeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_graphml() {
+        let mut graph = GraphMl::new(true)
+            .key("label", KeyScope::Node, KeyType::String)
+            .key("weight", KeyScope::Edge, KeyType::Double);
+
+        graph.node("n0", vec![("label", "a & b")]).unwrap();
+        graph.node("n1", vec![("label", "c")]).unwrap();
+        graph.edge("n0", "n1", vec![("weight", 2.5)]).unwrap();
+        assert!(graph.edge("n0", "n1", vec![("label", "x")]).is_err());
+
+        let xml = graph.to_xml().unwrap();
+        for line in [
+            r#"<key id="node_label" for="node" attr.name="label" attr.type="string"/>"#,
+            r#"<graph edgedefault="directed">"#,
+            r#"<data key="node_label">a &amp; b</data>"#,
+            r#"<edge source="n0" target="n1">"#,
+            r#"<data key="edge_weight">2.5</data>"#,
+        ]
+        .iter()
+        {
+            assert!(xml.contains(line), "{} not in {}", line, xml);
+        }
+    }
+
+    #[test]
+    fn test_graphml_writer() {
+        let registry = REGISTRY.lock().unwrap();
+        let factory = &registry.get("GraphMlWriter").unwrap().factory;
+
+        // there's no graphml representation of a buffer
+        let mut artifacts: Vec<AnyArtifact> = vec![Buffer::default().into()];
+        let result = factory.build_sink(
+            AttributeMap::from(vec![("path", "/dev/null")].into_iter()),
+            &mut artifacts,
+            vec![],
+            vec![],
+        );
+        assert!(matches!(result, Err(Error::ArtifactError(_))));
+
+        let mut sink = GraphMlWriter::new(Vec::new(), GraphMl::new(false));
+        sink.consume(&mut Buffer::default()).unwrap();
+        let xml = String::from_utf8(sink.into_inner()).unwrap();
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains(r#"<graph edgedefault="undirected">"#));
+    }
+}
@@ -0,0 +1,244 @@
+//! Combinators for handlers
+//!
+//! Complex per-component logic is often a variation of existing handlers: two handlers that
+//! always go together, a handler that should only see some components or only traces. Instead of
+//! writing a new struct for each variation, handlers can be composed:
+//! * `chain(a, b)` invokes `a` and then `b` on each component,
+//! * `when(condition, h)` invokes `h` on traces and events that satisfy the condition only and
+//! * `scoped(Scope::Trace, h)` invokes `h` on traces (or events) only.
+//!
+//! Meta data is always passed to all handlers since they may depend on it, e.g. on declared
+//! classifiers. All combinators produce handlers again and can thus be nested arbitrarily.
+//!
+
+use crate::stream::observer::Handler;
+use crate::stream::{AnyArtifact, AttributeContainer, Event, Meta, Scope, Trace};
+use crate::Result;
+
+/// Invoke two handlers one after another
+pub struct Chain<A: Handler, B: Handler> {
+    first: A,
+    second: B,
+}
+
+impl<A: Handler, B: Handler> Chain<A, B> {
+    /// Release both handlers
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// Invoke `first` and then `second`, components filtered by `first` don't reach `second`
+pub fn chain<A: Handler, B: Handler>(first: A, second: B) -> Chain<A, B> {
+    Chain { first, second }
+}
+
+impl<A: Handler, B: Handler> Handler for Chain<A, B> {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.second.on_meta(self.first.on_meta(meta)?)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        match self.first.on_trace(trace)? {
+            Some(trace) => self.second.on_trace(trace),
+            None => Ok(None),
+        }
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        match self.first.on_event(event, in_trace)? {
+            Some(event) => self.second.on_event(event, in_trace),
+            None => Ok(None),
+        }
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        match self.first.on_unfolded_event(event)? {
+            Some(event) => self.second.on_unfolded_event(event),
+            None => Ok(None),
+        }
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        let mut artifacts = self.first.release_artifacts()?;
+        artifacts.extend(self.second.release_artifacts()?);
+        Ok(artifacts)
+    }
+}
+
+/// Invoke a handler on components that satisfy a condition
+pub struct When<F, H: Handler>
+where
+    F: Fn(&dyn AttributeContainer) -> bool + Send,
+{
+    condition: F,
+    handler: H,
+}
+
+impl<F, H: Handler> When<F, H>
+where
+    F: Fn(&dyn AttributeContainer) -> bool + Send,
+{
+    /// Release the wrapped handler
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+/// Invoke `handler` on traces and events that satisfy `condition`, pass all others through
+///
+/// Traces and the events within are checked separately, i.e. events of a trace that doesn't
+/// satisfy the condition may still be handled.
+///
+pub fn when<F, H: Handler>(condition: F, handler: H) -> When<F, H>
+where
+    F: Fn(&dyn AttributeContainer) -> bool + Send,
+{
+    When { condition, handler }
+}
+
+impl<F, H: Handler> Handler for When<F, H>
+where
+    F: Fn(&dyn AttributeContainer) -> bool + Send,
+{
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.handler.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        if (self.condition)(&trace) {
+            self.handler.on_trace(trace)
+        } else {
+            Ok(Some(trace))
+        }
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if (self.condition)(&event) {
+            self.handler.on_event(event, in_trace)
+        } else {
+            Ok(Some(event))
+        }
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        if (self.condition)(&event) {
+            self.handler.on_unfolded_event(event)
+        } else {
+            Ok(Some(event))
+        }
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        self.handler.release_artifacts()
+    }
+}
+
+/// Invoke a handler on traces or events only
+pub struct Scoped<H: Handler> {
+    scope: Scope,
+    handler: H,
+}
+
+impl<H: Handler> Scoped<H> {
+    /// Release the wrapped handler
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+/// Invoke `handler` on either traces or events, whether in traces or standalone
+pub fn scoped<H: Handler>(scope: Scope, handler: H) -> Scoped<H> {
+    Scoped { scope, handler }
+}
+
+impl<H: Handler> Handler for Scoped<H> {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.handler.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        match self.scope {
+            Scope::Trace => self.handler.on_trace(trace),
+            Scope::Event => Ok(Some(trace)),
+        }
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        match self.scope {
+            Scope::Event => self.handler.on_event(event, in_trace),
+            Scope::Trace => Ok(Some(event)),
+        }
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        match self.scope {
+            Scope::Event => self.handler.on_unfolded_event(event),
+            Scope::Trace => Ok(Some(event)),
+        }
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        self.handler.release_artifacts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::void::consume;
+    use crate::stream::AttributeValue;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        meta: usize,
+        traces: usize,
+        events: usize,
+    }
+
+    impl Handler for Counter {
+        fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+            self.meta += 1;
+            Ok(meta)
+        }
+
+        fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+            self.traces += 1;
+            Ok(Some(trace))
+        }
+
+        fn on_event(&mut self, event: Event, _in_trace: bool) -> Result<Option<Event>> {
+            self.events += 1;
+            Ok(Some(event))
+        }
+    }
+
+    fn counts(counter: &Counter) -> [usize; 3] {
+        [counter.meta, counter.traces, counter.events]
+    }
+
+    #[test]
+    fn test_combinators() {
+        let is_a = |c: &dyn AttributeContainer| {
+            c.get_value("concept:name") == Some(&AttributeValue::String("a".into()))
+        };
+        let handler = chain(
+            when(is_a, Counter::default()),
+            chain(
+                scoped(Scope::Trace, Counter::default()),
+                scoped(Scope::Event, Counter::default()),
+            ),
+        );
+
+        let mut observer = handler.into_observer(load_example(&["book", "L1.xes"]));
+        consume(&mut observer).unwrap();
+
+        let (conditional, scoped) = observer.release().unwrap().into_inner();
+        let (traces, events) = scoped.into_inner();
+        assert_eq!(counts(&conditional.into_inner()), [1, 0, 6]);
+        assert_eq!(counts(&traces.into_inner()), [1, 6, 0]);
+        assert_eq!(counts(&events.into_inner()), [1, 0, 23]);
+    }
+}
@@ -0,0 +1,369 @@
+//! Fill in missing event attributes
+//!
+//! Machine learning on event data usually requires complete feature vectors. The `Impute` handler
+//! fills in missing event attributes according to an ordered list of imputations, each naming the
+//! attribute and the strategy to derive a value from:
+//! - `constant`: a fixed value, given by the child `value`
+//! - `most_frequent`: the value observed most often so far for the event's activity
+//! - `forward_fill`: the last value observed within the same trace
+//! - `derive`: the value of another attribute of the same event, given by the child `from`
+//!
+//! If no value can be derived, the attribute remains missing. The number of imputed and remaining
+//! missing values is reported per attribute.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeContainer, AttributeType, AttributeValue, Event,
+    Stream, Trace,
+};
+use crate::{Error, Result};
+
+/// How to derive a missing value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    Constant(AttributeValue),
+    MostFrequent,
+    ForwardFill,
+    Derive(String),
+}
+
+/// Fill in a single attribute using the given strategy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Imputation {
+    pub key: String,
+    pub strategy: Strategy,
+}
+
+impl Imputation {
+    pub fn new<K: Into<String>>(key: K, strategy: Strategy) -> Self {
+        Self {
+            key: key.into(),
+            strategy,
+        }
+    }
+}
+
+impl TryFrom<&Attribute> for Imputation {
+    type Error = Error;
+
+    fn try_from(attribute: &Attribute) -> Result<Self> {
+        let child = |key: &str| {
+            attribute
+                .children
+                .iter()
+                .find(|c| c.key == key)
+                .ok_or_else(|| {
+                    Error::AttributeError(format!("{:?} requires {:?}", attribute.key, key))
+                })
+        };
+
+        let strategy = match attribute.value.try_string()? {
+            "constant" => Strategy::Constant(child("value")?.value.clone()),
+            "most_frequent" => Strategy::MostFrequent,
+            "forward_fill" => Strategy::ForwardFill,
+            "derive" => Strategy::Derive(child("from")?.value.try_string()?.to_string()),
+            other => {
+                return Err(Error::AttributeError(format!(
+                    "unknown imputation strategy {:?}",
+                    other
+                )))
+            }
+        };
+
+        Ok(Imputation::new(attribute.key.as_str(), strategy))
+    }
+}
+
+/// Number of imputed and remaining missing values per attribute
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImputationReport {
+    pub imputed: BTreeMap<String, usize>,
+    pub missing: BTreeMap<String, usize>,
+}
+
+#[typetag::serde]
+impl Artifact for ImputationReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+type Frequencies = HashMap<(String, String), Vec<(AttributeValue, usize)>>;
+
+/// Fill in missing event attributes
+///
+/// The most frequent value is determined online, i.e. it's the most frequent one among the
+/// values observed so far. Imputed values are not counted.
+///
+pub struct Impute {
+    activity_key: String,
+    imputations: Vec<Imputation>,
+    frequencies: Frequencies,
+    report: ImputationReport,
+}
+
+impl Default for Impute {
+    fn default() -> Self {
+        Self {
+            activity_key: "concept:name".to_string(),
+            imputations: Vec::new(),
+            frequencies: HashMap::new(),
+            report: ImputationReport::default(),
+        }
+    }
+}
+
+impl Impute {
+    /// Add an imputation, imputations are applied in order of registration
+    pub fn imputation(mut self, imputation: Imputation) -> Self {
+        self.imputations.push(imputation);
+        self
+    }
+
+    fn activity(&self, event: &Event) -> String {
+        match event.get_value(&self.activity_key) {
+            Some(AttributeValue::String(activity)) => activity.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn observe(&mut self, activity: &str, event: &Event) {
+        for imputation in self.imputations.iter() {
+            if imputation.strategy != Strategy::MostFrequent {
+                continue;
+            }
+
+            if let Some(value) = event.get_value(&imputation.key) {
+                let counts = self
+                    .frequencies
+                    .entry((imputation.key.clone(), activity.to_string()))
+                    .or_default();
+                match counts.iter_mut().find(|(v, _)| v == value) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((value.clone(), 1)),
+                }
+            }
+        }
+    }
+
+    fn most_frequent(&self, key: &str, activity: &str) -> Option<&AttributeValue> {
+        self.frequencies
+            .get(&(key.to_string(), activity.to_string()))
+            .and_then(|counts| {
+                // prefer the value seen first on ties
+                counts
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(value, _)| value)
+            })
+    }
+
+    /// Impute missing attributes of a single event, `previous` holds the last values of the trace
+    fn impute(
+        &mut self,
+        event: &mut Event,
+        previous: &mut HashMap<String, AttributeValue>,
+    ) -> Result<()> {
+        let activity = self.activity(event);
+        self.observe(&activity, event);
+
+        for imputation in self.imputations.iter() {
+            let key = imputation.key.as_str();
+            if let Some(value) = event.get_value(key) {
+                previous.insert(key.to_string(), value.clone());
+                continue;
+            }
+
+            let value = match &imputation.strategy {
+                Strategy::Constant(value) => Some(value.clone()),
+                Strategy::MostFrequent => self.most_frequent(key, &activity).cloned(),
+                Strategy::ForwardFill => previous.get(key).cloned(),
+                Strategy::Derive(from) => event.get_value(from).cloned(),
+            };
+
+            match value {
+                Some(value) => {
+                    event.attributes.insert(Attribute::new(key, value));
+                    *self.report.imputed.entry(key.to_string()).or_default() += 1;
+                }
+                None => *self.report.missing.entry(key.to_string()).or_default() += 1,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for Impute {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        let mut previous = HashMap::new();
+        for event in trace.events.iter_mut() {
+            self.impute(event, &mut previous)?;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.impute(&mut event, &mut HashMap::new())?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.report.clone().into()])
+    }
+}
+
+impl PluginProvider for Impute {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Impute",
+            "Fill in missing event attributes",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be completed")
+                    .typed_attr(
+                        "imputations",
+                        "List of strategies, each keyed by the attribute to fill in",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let imputations = parameters
+                        .acquire_attribute("imputations")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(Imputation::try_from)
+                        .collect::<Result<Vec<_>>>()?;
+
+                    let impute = imputations
+                        .into_iter()
+                        .fold(Impute::default(), |impute, i| impute.imputation(i));
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, impute)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Sink};
+
+    use super::*;
+
+    fn event(attributes: &[(&str, &str)]) -> Event {
+        let mut event = Event::default();
+        for (key, value) in attributes.iter() {
+            event.attributes.insert((*key, *value));
+        }
+        event
+    }
+
+    fn values<'a>(log: &'a Log, key: &str) -> Vec<Option<&'a str>> {
+        log.traces
+            .iter()
+            .flat_map(|t| t.events.iter())
+            .map(|e| e.get_value(key).map(|v| v.try_string().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_impute() {
+        let trace = |events| Trace {
+            events,
+            ..Trace::default()
+        };
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(trace(vec![
+            event(&[
+                ("concept:name", "a"),
+                ("org:resource", "x"),
+                ("org:role", "r"),
+            ]),
+            event(&[("concept:name", "a"), ("org:resource", "y")]),
+            event(&[("concept:name", "a"), ("org:resource", "x"), ("id", "1")]),
+            event(&[("concept:name", "b")]),
+        ])))));
+        buffer.push(Ok(Some(Component::Trace(trace(vec![
+            event(&[("concept:name", "a")]),
+            event(&[("concept:name", "c")]),
+        ])))));
+
+        let impute = Impute::default()
+            .imputation(Imputation::new("org:resource", Strategy::MostFrequent))
+            .imputation(Imputation::new("org:role", Strategy::ForwardFill))
+            .imputation(Imputation::new("label", Strategy::Derive("id".into())))
+            .imputation(Imputation::new(
+                "cost",
+                Strategy::Constant(AttributeValue::from("0")),
+            ));
+        let mut observer = impute.into_observer(buffer);
+        let mut log = Log::default();
+        let artifacts = log.consume(&mut observer).unwrap();
+
+        assert_eq!(
+            values(&log, "org:resource"),
+            vec![Some("x"), Some("y"), Some("x"), None, Some("x"), None]
+        );
+        assert_eq!(
+            values(&log, "org:role"),
+            vec![Some("r"), Some("r"), Some("r"), Some("r"), None, None]
+        );
+        assert_eq!(
+            values(&log, "label"),
+            vec![None, None, Some("1"), None, None, None]
+        );
+        assert!(values(&log, "cost").iter().all(|v| *v == Some("0")));
+
+        let report =
+            AnyArtifact::find::<ImputationReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.imputed["org:resource"], 1);
+        assert_eq!(report.missing["org:resource"], 2);
+        assert_eq!(report.imputed["org:role"], 3);
+        assert_eq!(report.imputed["cost"], 6);
+    }
+
+    #[test]
+    fn test_imputation_from_attribute() {
+        let attribute = Attribute::with_children(
+            "org:resource",
+            "constant",
+            vec![Attribute::new("value", "unknown")],
+        );
+        assert_eq!(
+            Imputation::try_from(&attribute).unwrap().strategy,
+            Strategy::Constant("unknown".into())
+        );
+
+        assert!(Imputation::try_from(&Attribute::new("org:resource", "constant")).is_err());
+        assert!(Imputation::try_from(&Attribute::new("org:resource", "magic")).is_err());
+
+        let mut observer = Impute::default().into_observer(Buffer::default());
+        assert!(consume(&mut observer).is_ok());
+    }
+}
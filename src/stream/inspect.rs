@@ -0,0 +1,99 @@
+//! Quick summaries of event logs
+//!
+//! Often, all that's needed of a log is an overview: how many traces and events it holds, which
+//! period it covers and which attributes to expect. `quick_stats` answers this by scanning the
+//! structure of a XES file rather than reading it as stream, see `XesReader::scan`.
+//!
+//! ```
+//! use promi::stream::inspect::quick_stats;
+//!
+//! let stats = quick_stats("static/xes/book/L1.xes").unwrap();
+//! assert_eq!(stats.traces, 6);
+//! assert!(stats.event_keys.contains("concept:name"));
+//! ```
+//!
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::stream::xes::XesReader;
+use crate::{DateTime, Result};
+
+/// Structural information of a log
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuickStats {
+    pub traces: usize,
+    /// All events, whether in traces or standalone
+    pub events: usize,
+    /// Earliest event timestamp
+    pub start: Option<DateTime>,
+    /// Latest event timestamp
+    pub end: Option<DateTime>,
+    pub trace_keys: BTreeSet<String>,
+    pub event_keys: BTreeSet<String>,
+}
+
+impl QuickStats {
+    /// Extend the time range by a timestamp
+    pub fn update_range(&mut self, timestamp: DateTime) {
+        if self.start.is_none_or(|start| timestamp < start) {
+            self.start = Some(timestamp);
+        }
+        if self.end.is_none_or(|end| timestamp > end) {
+            self.end = Some(timestamp);
+        }
+    }
+}
+
+/// Summarize a XES file without reading it as stream
+pub fn quick_stats<P: AsRef<Path>>(path: P) -> Result<QuickStats> {
+    XesReader::from(BufReader::new(File::open(path)?)).scan()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeContainer, Sink};
+
+    use super::*;
+
+    // the scan is expected to agree with reading the whole log
+    #[test]
+    fn test_quick_stats() {
+        for name in ["L1.xes", "L4.xes", "bigger-example.xes"] {
+            let stats = quick_stats(join_static!("xes", "book", name)).unwrap();
+
+            let mut log = Log::default();
+            log.consume(&mut XesReader::from(join_static_reader!(
+                "xes", "book", name
+            )))
+            .unwrap();
+
+            let events = log.traces.iter().flat_map(|t| t.events.iter());
+            let mut expected = QuickStats {
+                traces: log.traces.len(),
+                events: events.clone().count() + log.events.len(),
+                ..QuickStats::default()
+            };
+            for trace in log.traces.iter() {
+                for (key, _, _) in trace.attributes.iter() {
+                    expected.trace_keys.insert(key.to_string());
+                }
+            }
+            for event in events.chain(log.events.iter()) {
+                for (key, _, _) in event.attributes.iter() {
+                    expected.event_keys.insert(key.to_string());
+                }
+                if let Some(timestamp) = event.get_value("time:timestamp") {
+                    expected.update_range(*timestamp.try_date().unwrap());
+                }
+            }
+
+            assert_eq!(stats, expected, "{}", name);
+        }
+
+        assert!(quick_stats(join_static!("xes", "book", "missing.xes")).is_err());
+    }
+}
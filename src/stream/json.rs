@@ -0,0 +1,534 @@
+//! Event data in JSON formats
+//!
+//! `JsonWriter` serializes a stream as JSON lines, i.e. one component of the promi component model
+//! per line, and `JsonReader` reads it back. Unlike XES, the format is lossless with respect to
+//! the component model and can be processed by any JSON tooling, line by line.
+//!
+//! The [OCEL 1.0](http://www.ocel-standard.org/) JSON format for object-centric event logs is
+//! supported as well:
+//! - `OcelWriter` exports traces as objects of a single type, by default `case`, whose events
+//!   refer to them. The activity and timestamp of each event are required.
+//! - `OcelReader` flattens an object-centric log on one object type. Each object becomes a trace
+//!   with all events that refer to it, ordered by time. Events that refer to several objects of
+//!   that type occur in several traces, events that refer to none of them are dropped and
+//!   reported as `OrphanEvent` in a `QualityReport` artifact.
+//!
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+use serde_json::{json, Map, Number, Value};
+
+use crate::stream::output::FileOptions;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::provenance::ProvenanceWriter;
+use crate::stream::quality::{Finding, Issue, QualityReport};
+use crate::stream::{
+    AnyArtifact, AttributeContainer, AttributeMap, AttributeType, AttributeValue, Component, Event,
+    Meta, ResOpt, Sink, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+fn json_error(error: serde_json::Error) -> Error {
+    Error::JsonError(error.to_string())
+}
+
+/// Write a stream as JSON lines, one component per line
+pub struct JsonWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonWriter { writer }
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> Sink for JsonWriter<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &component).map_err(json_error)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read a stream from JSON lines, one component per line
+pub struct JsonReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    pub fn new(reader: R) -> Self {
+        JsonReader {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead + Send> Stream for JsonReader<R> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        for line in &mut self.lines {
+            let line = line?;
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return serde_json::from_str(&line)
+                .map(Some)
+                .map_err(|e| Error::JsonError(format!("line {}: {}", self.line, e)));
+        }
+        Ok(None)
+    }
+}
+
+/// Convert an attribute value into an OCEL value
+fn ocel_value(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::String(string) | AttributeValue::Id(string) => Value::from(string.as_str()),
+        AttributeValue::Date(date) => Value::from(date.to_rfc3339()),
+        AttributeValue::Int(int) => Value::from(*int),
+        AttributeValue::Float(float) => Number::from_f64(*float).map_or(Value::Null, Value::Number),
+        AttributeValue::Boolean(boolean) => Value::from(*boolean),
+        AttributeValue::List(list) => list.iter().map(|a| ocel_value(&a.value)).collect(),
+    }
+}
+
+/// Convert an OCEL value into an attribute value, if it has a counterpart
+fn attribute_value(value: &Value) -> Option<AttributeValue> {
+    match value {
+        Value::String(string) => Some(AttributeValue::String(string.into())),
+        Value::Number(number) => Some(match number.as_i64() {
+            Some(int) => AttributeValue::Int(int),
+            None => AttributeValue::Float(number.as_f64()?),
+        }),
+        Value::Bool(boolean) => Some(AttributeValue::Boolean(*boolean)),
+        _ => None,
+    }
+}
+
+/// Export traces and events to the OCEL 1.0 JSON format
+///
+/// Since the format is a single document, the log is kept in memory until the stream ends.
+///
+pub struct OcelWriter<W: Write> {
+    writer: W,
+    object_type: String,
+    events: Map<String, Value>,
+    objects: Map<String, Value>,
+    attribute_names: BTreeSet<String>,
+}
+
+impl<W: Write> OcelWriter<W> {
+    pub fn new(writer: W) -> Self {
+        OcelWriter {
+            writer,
+            object_type: "case".to_string(),
+            events: Map::new(),
+            objects: Map::new(),
+            attribute_names: BTreeSet::new(),
+        }
+    }
+
+    /// Type of the objects that traces are exported as
+    pub fn object_type<T: Into<String>>(mut self, object_type: T) -> Self {
+        self.object_type = object_type.into();
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn add_event(&mut self, event: &Event, object: Option<&str>) -> Result<()> {
+        let activity = match event.get_value("concept:name") {
+            Some(activity) => activity.try_string()?,
+            None => return Err(Error::JsonError("OCEL events require an activity".into())),
+        };
+        let timestamp = match event.get_value("time:timestamp") {
+            Some(timestamp) => timestamp.try_date()?,
+            None => return Err(Error::JsonError("OCEL events require a timestamp".into())),
+        };
+
+        let mut vmap = Map::new();
+        for (key, value, _) in event.attributes.iter() {
+            if key != "concept:name" && key != "time:timestamp" {
+                self.attribute_names.insert(key.to_string());
+                vmap.insert(key.to_string(), ocel_value(value));
+            }
+        }
+
+        let id = format!("e{}", self.events.len() + 1);
+        self.events.insert(
+            id,
+            json!({
+                "ocel:activity": activity,
+                "ocel:timestamp": timestamp.to_rfc3339(),
+                "ocel:omap": object.into_iter().collect::<Vec<_>>(),
+                "ocel:vmap": vmap,
+            }),
+        );
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> Sink for OcelWriter<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        match component {
+            Component::Meta(_) => Ok(()),
+            Component::Trace(trace) => {
+                let id = match trace.get_value("concept:name") {
+                    Some(name) => name.try_string()?.to_string(),
+                    None => format!("{}{}", self.object_type, self.objects.len() + 1),
+                };
+
+                let mut ovmap = Map::new();
+                for (key, value, _) in trace.attributes.iter() {
+                    if key != "concept:name" {
+                        ovmap.insert(key.to_string(), ocel_value(value));
+                    }
+                }
+                self.objects.insert(
+                    id.clone(),
+                    json!({"ocel:type": self.object_type, "ocel:ovmap": ovmap}),
+                );
+
+                trace
+                    .events
+                    .iter()
+                    .try_for_each(|event| self.add_event(event, Some(&id)))
+            }
+            Component::Event(event) => self.add_event(&event, None),
+        }
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        let document = json!({
+            "ocel:global-event": {"ocel:activity": "__INVALID__"},
+            "ocel:global-object": {"ocel:type": "__INVALID__"},
+            "ocel:global-log": {
+                "ocel:version": "1.0",
+                "ocel:ordering": "timestamp",
+                "ocel:attribute-names": self.attribute_names,
+                "ocel:object-types": [self.object_type],
+            },
+            "ocel:events": std::mem::take(&mut self.events),
+            "ocel:objects": std::mem::take(&mut self.objects),
+        });
+
+        serde_json::to_writer_pretty(&mut self.writer, &document).map_err(json_error)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read an OCEL 1.0 JSON document, flattened on one object type
+pub struct OcelReader<R: Read> {
+    reader: Option<R>,
+    object_type: String,
+    pending: VecDeque<Component>,
+    quality: QualityReport,
+}
+
+impl<R: Read> OcelReader<R> {
+    pub fn new<T: Into<String>>(reader: R, object_type: T) -> Self {
+        OcelReader {
+            reader: Some(reader),
+            object_type: object_type.into(),
+            pending: VecDeque::new(),
+            quality: QualityReport::default().max_findings(1000),
+        }
+    }
+
+    fn parse(&mut self, reader: R) -> Result<VecDeque<Component>> {
+        let document: Value = serde_json::from_reader(reader).map_err(json_error)?;
+        let empty = Map::new();
+        let section = |key: &str| match &document[key] {
+            Value::Object(section) => Ok(section),
+            Value::Null => Ok(&empty),
+            _ => Err(Error::JsonError(format!("{} is no object", key))),
+        };
+
+        let mut traces: BTreeMap<&str, Trace> = BTreeMap::new();
+        for (id, object) in section("ocel:objects")?.iter() {
+            if object["ocel:type"].as_str() != Some(self.object_type.as_str()) {
+                continue;
+            }
+
+            let mut trace = Trace::default();
+            trace.attributes.insert(("concept:name", id.as_str()));
+            if let Some(ovmap) = object["ocel:ovmap"].as_object() {
+                for (key, value) in ovmap.iter() {
+                    if let Some(value) = attribute_value(value) {
+                        trace.attributes.insert((key.as_str(), value));
+                    }
+                }
+            }
+            traces.insert(id, trace);
+        }
+
+        for (id, entry) in section("ocel:events")?.iter() {
+            let (activity, timestamp) = match (
+                entry["ocel:activity"].as_str(),
+                entry["ocel:timestamp"].as_str(),
+            ) {
+                (Some(activity), Some(timestamp)) => (activity, timestamp),
+                _ => {
+                    return Err(Error::JsonError(format!(
+                        "event {} lacks activity or timestamp",
+                        id
+                    )))
+                }
+            };
+
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", activity));
+            event
+                .attributes
+                .insert(("time:timestamp", DateTime::parse_from_rfc3339(timestamp)?));
+            if let Some(vmap) = entry["ocel:vmap"].as_object() {
+                for (key, value) in vmap.iter() {
+                    if let Some(value) = attribute_value(value) {
+                        event.attributes.insert((key.as_str(), value));
+                    }
+                }
+            }
+
+            let mut orphan = true;
+            for object in entry["ocel:omap"].as_array().into_iter().flatten() {
+                if let Some(trace) = object.as_str().and_then(|o| traces.get_mut(o)) {
+                    trace.events.push(event.clone());
+                    orphan = false;
+                }
+            }
+            if orphan {
+                self.quality.record(Finding::new(
+                    Issue::OrphanEvent,
+                    format!("event {}", id),
+                    format!("event refers to no object of type {:?}", self.object_type),
+                ));
+            }
+        }
+
+        Ok(traces
+            .into_values()
+            .map(|mut trace| {
+                trace.events.sort_by_key(|e| {
+                    e.get_value("time:timestamp")
+                        .and_then(|t| t.try_date().ok().copied())
+                });
+                trace
+            })
+            .filter(|trace| !trace.events.is_empty())
+            .map(Component::Trace)
+            .collect())
+    }
+}
+
+impl<R: Read + Send> Stream for OcelReader<R> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if let Some(reader) = self.reader.take() {
+            self.pending = self.parse(reader)?;
+            let meta = Meta {
+                attributes: AttributeMap::from(
+                    vec![("ocel:object-type", self.object_type.as_str())].into_iter(),
+                ),
+                ..Meta::default()
+            };
+            return Ok(Some(Component::Meta(meta)));
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        if self.quality.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![std::mem::take(&mut self.quality).into()])
+    }
+}
+
+/// Dummy struct for JSON Plugins
+pub struct JsonPluginProvider;
+
+impl PluginProvider for JsonPluginProvider {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        fn path(parameters: &mut Parameters) -> Result<String> {
+            Ok(parameters
+                .acquire_attribute("path")?
+                .value
+                .try_string()?
+                .to_string())
+        }
+
+        vec![
+            Entry::new(
+                "JsonReader",
+                "Read a stream from JSON lines, one component per line",
+                Factory::new(
+                    Declaration::default().attribute("path", "Location of the JSON lines file"),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let file = File::open(path(parameters)?)?;
+                        Ok(JsonReader::new(io::BufReader::new(file)).into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "JsonWriter",
+                "Write the stream as JSON lines, one component per line, optionally tuning file output and recording provenance",
+                Factory::new(
+                    Declaration::default()
+                        .attribute("path", "Location of the JSON lines file")
+                        .with(FileOptions::declare)
+                        .with(ProvenanceWriter::declare),
+                    FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                        let path = path(parameters)?;
+                        let file = FileOptions::from_parameters(parameters)?.create(&path)?;
+                        ProvenanceWriter::from_parameters(
+                            JsonWriter::new(file).into_boxed(),
+                            &path,
+                            parameters,
+                        )
+                    })),
+                ),
+            ),
+            Entry::new(
+                "OcelReader",
+                "Read an OCEL 1.0 JSON log with one trace per object of the given type",
+                Factory::new(
+                    Declaration::default()
+                        .typed_attr("path", "Location of the OCEL file", AttributeType::String)
+                        .typed_attr("object_type", "Object type that traces are built from", AttributeType::String),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let file = io::BufReader::new(File::open(path(parameters)?)?);
+                        let object_type = parameters.acquire_attribute("object_type")?;
+                        Ok(OcelReader::new(file, object_type.value.try_string()?).into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "OcelWriter",
+                "Export the stream to OCEL 1.0 JSON with traces as objects of the given type, optionally tuning file output and recording provenance",
+                Factory::new(
+                    Declaration::default()
+                        .typed_attr("path", "Location of the OCEL file", AttributeType::String)
+                        .default_attr("object_type", "Object type of traces", |k| {
+                            (k, "case").into()
+                        })
+                        .with(FileOptions::declare)
+                        .with(ProvenanceWriter::declare),
+                    FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                        let path = path(parameters)?;
+                        let object_type = parameters.acquire_attribute("object_type")?;
+                        let file = FileOptions::from_parameters(parameters)?.create(&path)?;
+                        let writer =
+                            OcelWriter::new(file).object_type(object_type.value.try_string()?);
+                        ProvenanceWriter::from_parameters(writer.into_boxed(), &path, parameters)
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::log::Log;
+
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut writer = JsonWriter::new(Vec::new());
+        writer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let lines = writer.into_inner();
+        assert_eq!(lines.iter().filter(|b| **b == b'\n').count(), 1 + 6);
+
+        let mut expected = Log::default();
+        expected
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let mut log = Log::default();
+        log.consume(&mut JsonReader::new(&lines[..])).unwrap();
+        assert_eq!(
+            format!("{:?}", log.traces),
+            format!("{:?}", expected.traces)
+        );
+
+        // traces become objects, events refer to them
+        let mut writer = OcelWriter::new(Vec::new()).object_type("order");
+        writer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let document = writer.into_inner();
+        let value: Value = serde_json::from_slice(&document).unwrap();
+        assert_eq!(value["ocel:events"].as_object().unwrap().len(), 23);
+        assert_eq!(value["ocel:objects"].as_object().unwrap().len(), 6);
+        assert_eq!(value["ocel:global-log"]["ocel:object-types"][0], "order");
+
+        let mut log = Log::default();
+        log.consume(&mut OcelReader::new(&document[..], "order"))
+            .unwrap();
+        let activities = |log: &Log| -> Vec<Vec<String>> {
+            let mut activities: Vec<_> = log
+                .traces
+                .iter()
+                .map(|t| {
+                    t.events
+                        .iter()
+                        .map(|e| e.get_value("concept:name").unwrap().try_string().unwrap())
+                        .map(|a| a.to_string())
+                        .collect()
+                })
+                .collect();
+            activities.sort();
+            activities
+        };
+        assert_eq!(activities(&log), activities(&expected));
+
+        let mut log = Log::default();
+        let artifacts = log
+            .consume(&mut OcelReader::new(&document[..], "item"))
+            .unwrap();
+        assert!(log.traces.is_empty());
+        let report = AnyArtifact::find::<QualityReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.count(Issue::OrphanEvent), 23);
+    }
+}
@@ -0,0 +1,288 @@
+//! Locale-aware parsing of numbers and timestamps
+//!
+//! Exports of spreadsheets and business software follow the conventions of their region, e.g.
+//! `1.234,5` for a number or `03. März 2021` for a date in German. A `Locale` normalizes such
+//! values before they are parsed. Locales are identified by a tag, see `Locale::from_tag`.
+//!
+
+use crate::stream::csv::{parse_timestamp, TIMESTAMP_FORMATS};
+use crate::{DateTime, Error, Result};
+
+const MONTHS_EN: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const MONTHS_DE: [&str; 12] = [
+    "januar",
+    "februar",
+    "märz",
+    "april",
+    "mai",
+    "juni",
+    "juli",
+    "august",
+    "september",
+    "oktober",
+    "november",
+    "dezember",
+];
+
+const MONTHS_FR: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+
+/// Regional conventions for numbers and dates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    /// Whether `,` separates decimals and `.` groups digits
+    pub decimal_comma: bool,
+    /// Whether ambiguous dates like `03/04/2021` start with the day
+    pub day_first: bool,
+    /// Lower case month names, January first
+    pub month_names: Vec<String>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            decimal_comma: false,
+            day_first: false,
+            month_names: MONTHS_EN.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+impl Locale {
+    /// Locale of a language tag, one of `en`/`en-US`, `en-GB`, `de` or `fr` (and regional variants)
+    pub fn from_tag(tag: &str) -> Result<Self> {
+        let tag = tag.to_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or("");
+        let names = |months: &[&str; 12]| months.iter().map(|m| m.to_string()).collect();
+
+        match (language, tag.as_str()) {
+            (_, "en-gb") => Ok(Locale::default().day_first(true)),
+            ("en", _) => Ok(Locale::default()),
+            ("de", _) => Ok(Locale::default()
+                .decimal_comma(true)
+                .day_first(true)
+                .month_names(names(&MONTHS_DE))),
+            ("fr", _) => Ok(Locale::default()
+                .decimal_comma(true)
+                .day_first(true)
+                .month_names(names(&MONTHS_FR))),
+            _ => Err(Error::AttributeError(format!("unknown locale {:?}", tag))),
+        }
+    }
+
+    pub fn decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.decimal_comma = decimal_comma;
+        self
+    }
+
+    pub fn day_first(mut self, day_first: bool) -> Self {
+        self.day_first = day_first;
+        self
+    }
+
+    pub fn month_names(mut self, month_names: Vec<String>) -> Self {
+        self.month_names = month_names.into_iter().map(|m| m.to_lowercase()).collect();
+        self
+    }
+
+    /// Bring a number into the format understood by Rust, i.e. without grouping and decimal dot
+    ///
+    /// Grouping characters (the locale's one, `'` and spaces) are only accepted between groups of
+    /// three digits in the integer part, e.g. `1,234,567`. Otherwise the value is returned as is,
+    /// so that `1,2` does not become `12`.
+    ///
+    pub fn normalize_number(&self, value: &str) -> String {
+        let (group, decimal) = if self.decimal_comma {
+            ('.', ',')
+        } else {
+            (',', '.')
+        };
+        let value = value.trim();
+        let integer = value.split(decimal).next().unwrap_or("");
+        let groups: Vec<&str> = integer
+            .split(|c: char| c == group || c == '\'' || c.is_whitespace())
+            .collect();
+        let grouped = groups.len() == 1
+            || groups.iter().enumerate().all(|(i, g)| {
+                let digits = if i == 0 {
+                    g.trim_start_matches(['+', '-'])
+                } else {
+                    g
+                };
+                let valid_length = if i == 0 {
+                    (1..=3).contains(&digits.len())
+                } else {
+                    digits.len() == 3
+                };
+                valid_length && digits.chars().all(|c| c.is_ascii_digit())
+            });
+        if !grouped {
+            return value.to_string();
+        }
+
+        value
+            .chars()
+            .filter(|c| *c != group && !c.is_whitespace() && *c != '\'')
+            .map(|c| if c == decimal { '.' } else { c })
+            .collect()
+    }
+
+    pub fn parse_float(&self, value: &str) -> Result<f64> {
+        Ok(self.normalize_number(value).parse::<f64>()?)
+    }
+
+    pub fn parse_int(&self, value: &str) -> Result<i64> {
+        Ok(self.normalize_number(value).parse::<i64>()?)
+    }
+
+    /// Number of the month with the given name or abbreviation of at least three letters
+    fn month(&self, word: &str) -> Option<usize> {
+        let word = word.to_lowercase();
+        self.month_names
+            .iter()
+            .position(|m| *m == word || (word.chars().count() >= 3 && m.starts_with(&word)))
+            .map(|m| m + 1)
+    }
+
+    /// Replace month names by their number
+    pub fn normalize_date(&self, value: &str) -> String {
+        let mut result = String::with_capacity(value.len());
+        let mut word = String::new();
+        let mut chars = value.trim().chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c.is_alphabetic() {
+                word.push(c);
+                if chars.peek().is_some_and(|n| n.is_alphabetic()) {
+                    continue;
+                }
+
+                match self.month(&word) {
+                    Some(month) => {
+                        result.push_str(&format!("{:02}", month));
+                        // abbreviations are often followed by a dot, e.g. `4. Mär. 2021`
+                        if chars.peek() == Some(&'.') {
+                            chars.next();
+                        }
+                    }
+                    None => result.push_str(&word),
+                }
+                word.clear();
+            } else {
+                result.push(c);
+            }
+        }
+
+        result
+    }
+
+    /// Parse a timestamp, trying common formats if none is given
+    ///
+    /// Of the formats that differ only in the order of day and month, the one matching the
+    /// locale is tried.
+    ///
+    pub fn parse_timestamp(&self, value: &str, format: Option<&str>) -> Result<DateTime> {
+        let normalized = self.normalize_date(value);
+        if let Some(format) = format {
+            return parse_timestamp(&normalized, format)
+                .or_else(|_| parse_timestamp(value, format));
+        }
+
+        let skip = if self.day_first {
+            "%m/%d/%Y %H:%M:%S"
+        } else {
+            "%d/%m/%Y %H:%M:%S"
+        };
+        TIMESTAMP_FORMATS
+            .iter()
+            .chain(["%d. %m %Y", "%d %m %Y", "%d. %m %Y %H:%M", "%d %m %Y %H:%M"].iter())
+            .filter(|f| **f != skip)
+            .find_map(|f| parse_timestamp(&normalized, f).ok())
+            .ok_or_else(|| Error::AttributeError(format!("cannot parse {:?} as timestamp", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(rfc3339: &str) -> DateTime {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap()
+    }
+
+    #[test]
+    fn test_numbers() {
+        let de = Locale::from_tag("de-AT").unwrap();
+        assert_eq!(de.parse_float("1.234,5").unwrap(), 1234.5);
+        assert_eq!(de.parse_int("1.234").unwrap(), 1234);
+        assert_eq!(Locale::default().parse_float("1,234.5").unwrap(), 1234.5);
+        assert!(de.parse_float("n/a").is_err());
+        assert_eq!(Locale::default().parse_int("1'234'567").unwrap(), 1234567);
+        assert_eq!(
+            Locale::default().parse_float("-12 345.5").unwrap(),
+            -12345.5
+        );
+        assert!(Locale::default().parse_float("1,2").is_err());
+        assert!(Locale::default().parse_float("1,2,3").is_err());
+        assert!(Locale::default().parse_float("1234,567").is_err());
+        assert!(de.parse_int("1.23").is_err());
+        assert_eq!(de.parse_float("1,2").unwrap(), 1.2);
+        assert!(Locale::from_tag("xx").is_err());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let midnight = timestamp("2021-03-04T00:00:00+00:00");
+        let de = Locale::from_tag("de").unwrap();
+        assert_eq!(de.parse_timestamp("04. März 2021", None).unwrap(), midnight);
+        assert_eq!(de.parse_timestamp("4. Mär. 2021", None).unwrap(), midnight);
+        assert_eq!(
+            de.parse_timestamp("04/03/2021 10:30:00", None).unwrap(),
+            timestamp("2021-03-04T10:30:00+00:00")
+        );
+        assert_eq!(
+            Locale::default()
+                .parse_timestamp("03/04/2021 10:30:00", None)
+                .unwrap(),
+            timestamp("2021-03-04T10:30:00+00:00")
+        );
+        assert_eq!(
+            Locale::from_tag("fr")
+                .unwrap()
+                .parse_timestamp("4 mars 2021", Some("%d %m %Y"))
+                .unwrap(),
+            midnight
+        );
+        assert_eq!(
+            de.parse_timestamp("2021-03-04T00:00:00+00:00", None)
+                .unwrap(),
+            midnight
+        );
+    }
+}
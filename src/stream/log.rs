@@ -1,13 +1,49 @@
 //! A static representation of an event stream.
 //!
 
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
 use serde::{Deserialize, Serialize};
 
+use crate::stream::buffer::Buffer;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
 use crate::stream::{
-    Attribute, AttributeContainer, AttributeValue, Component, ComponentType, Event, Meta, Sink,
-    Trace,
+    Artifact, Attribute, AttributeContainer, AttributeValue, Component, ComponentType, Event, Meta,
+    Sink, Stream, Trace,
 };
-use crate::Result;
+use crate::{Error, Result};
+
+/// Tells how traces sharing the same case id are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// Keep the left hand side trace as is
+    Left,
+    /// Replace the left hand side trace by the right hand side one
+    Right,
+    /// Keep the left hand side trace, add attributes only present on the right hand side and
+    /// append the events of the right hand side
+    Combine,
+}
+
+impl MergePolicy {
+    fn merge(&self, left: Trace, right: Trace) -> Trace {
+        match self {
+            MergePolicy::Left => left,
+            MergePolicy::Right => right,
+            MergePolicy::Combine => {
+                let mut merged = left;
+                for attribute in right.attributes {
+                    if merged.attributes.get_value(&attribute.key).is_none() {
+                        merged.attributes.insert(attribute);
+                    }
+                }
+                merged.events.extend(right.events);
+                merged
+            }
+        }
+    }
+}
 
 /// Represents information that is related to a specific process
 ///
@@ -58,6 +94,116 @@ impl AttributeContainer for Log {
     }
 }
 
+impl Log {
+    /// Case id of a trace, i.e. its `concept:name` attribute
+    fn case_id(trace: &Trace) -> Result<String> {
+        Ok(trace
+            .get_value_or("concept:name")?
+            .try_string()?
+            .to_string())
+    }
+
+    /// Merge traces sharing a case id into the first of them, in order of occurrence
+    fn fold<I: IntoIterator<Item = Trace>>(
+        traces: I,
+        policy: MergePolicy,
+    ) -> Result<(Vec<Trace>, HashMap<String, usize>)> {
+        let mut index = HashMap::new();
+        let mut folded: Vec<Trace> = Vec::new();
+
+        for trace in traces {
+            match index.get(&Self::case_id(&trace)?) {
+                Some(&i) => {
+                    let left = std::mem::take(&mut folded[i]);
+                    folded[i] = policy.merge(left, trace);
+                }
+                None => {
+                    index.insert(Self::case_id(&trace)?, folded.len());
+                    folded.push(trace);
+                }
+            }
+        }
+
+        Ok((folded, index))
+    }
+
+    /// Combine traces of both logs
+    ///
+    /// Traces are identified by their case id. Those that share a case id, be it within one log
+    /// or across both, are merged in order according to the given policy, the remaining ones are
+    /// appended. Standalone events are concatenated.
+    ///
+    pub fn union(self, other: Log, policy: MergePolicy) -> Result<Log> {
+        let (traces, _) = Self::fold(self.traces.into_iter().chain(other.traces), policy)?;
+
+        let mut meta = self.meta;
+        if policy != MergePolicy::Left {
+            for attribute in other.meta.attributes {
+                if policy == MergePolicy::Right || meta.get_value(&attribute.key).is_none() {
+                    meta.attributes.insert(attribute);
+                }
+            }
+        }
+
+        Ok(Log {
+            meta,
+            traces,
+            events: self.events.into_iter().chain(other.events).collect(),
+        })
+    }
+
+    /// Keep traces whose case id does not occur in the other log
+    ///
+    /// Standalone events are not keyed and therefore kept as is.
+    ///
+    pub fn difference(self, other: &Log) -> Result<Log> {
+        let keys = other
+            .traces
+            .iter()
+            .map(Self::case_id)
+            .collect::<Result<HashSet<_>>>()?;
+
+        let mut traces = Vec::new();
+        for trace in self.traces {
+            if !keys.contains(&Self::case_id(&trace)?) {
+                traces.push(trace);
+            }
+        }
+
+        Ok(Log {
+            meta: self.meta,
+            traces,
+            events: self.events,
+        })
+    }
+
+    /// Keep traces whose case id occurs in both logs, merged according to the given policy
+    ///
+    /// Traces sharing a case id within one log are merged first. Standalone events are not keyed
+    /// and therefore kept as is.
+    ///
+    pub fn intersect(self, other: Log, policy: MergePolicy) -> Result<Log> {
+        let (left, _) = Self::fold(self.traces, policy)?;
+        let (right, index) = Self::fold(other.traces, policy)?;
+        let mut right: Vec<Option<Trace>> = right.into_iter().map(Some).collect();
+
+        let mut traces = Vec::new();
+        for trace in left {
+            if let Some(&i) = index.get(&Self::case_id(&trace)?) {
+                if let Some(other) = right[i].take() {
+                    traces.push(policy.merge(trace, other));
+                }
+            }
+        }
+
+        Ok(Log {
+            meta: self.meta,
+            traces,
+            events: self.events,
+        })
+    }
+}
+
 impl Sink for Log {
     fn on_component(&mut self, component: Component) -> Result<()> {
         match component {
@@ -69,3 +215,254 @@ impl Sink for Log {
         Ok(())
     }
 }
+
+#[typetag::serde]
+impl Artifact for Log {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Replays a materialized log or buffer artifact as stream
+///
+/// This enables multi-stage flow graphs where a later stage streams over an intermediate result
+/// of a previous one. The artifact is copied, i.e. it remains available to other pipes.
+///
+pub struct LogArtifactSource;
+
+impl PluginProvider for LogArtifactSource {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "LogArtifactSource",
+            "Replay a log or buffer artifact as stream",
+            Factory::new(
+                Declaration::default().artifact("log", "The log to be replayed"),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let artifact = parameters.acquire_artifact("log")?;
+
+                    if let Some(log) = artifact.downcast_ref::<Log>() {
+                        Ok(Box::new(Buffer::from(log.clone())))
+                    } else if let Some(buffer) = artifact.downcast_ref::<Buffer>() {
+                        Ok(Box::new(buffer.clone()))
+                    } else {
+                        Err(Error::ArtifactError(format!(
+                            "expected log or buffer artifact, got {:?}",
+                            artifact
+                        )))
+                    }
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::plugin::REGISTRY;
+    use crate::stream::AnyArtifact;
+
+    use super::*;
+
+    fn load_log(path: &[&str]) -> Log {
+        let mut log = Log::default();
+        log.consume(&mut load_example(path)).unwrap();
+        log
+    }
+
+    fn case_ids(log: &Log) -> Vec<String> {
+        log.traces
+            .iter()
+            .map(|t| Log::case_id(t).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let l1 = load_log(&["book", "L1.xes"]);
+        let l2 = load_log(&["book", "L2.xes"]);
+
+        let union = l1.clone().union(l2.clone(), MergePolicy::Left).unwrap();
+        assert_eq!(union.traces.len(), 6 + 13 - 6);
+        assert_eq!(&case_ids(&union)[..6], &case_ids(&l1)[..]);
+
+        let difference = l2.clone().difference(&l1).unwrap();
+        assert_eq!(difference.traces.len(), 13 - 6);
+        assert!(case_ids(&difference)
+            .iter()
+            .all(|k| !case_ids(&l1).contains(k)));
+
+        let intersection = l1
+            .clone()
+            .intersect(l2.clone(), MergePolicy::Right)
+            .unwrap();
+        assert_eq!(case_ids(&intersection), case_ids(&l1));
+        assert_eq!(
+            intersection
+                .traces
+                .iter()
+                .map(|t| t.events.len())
+                .collect::<Vec<_>>(),
+            intersection
+                .traces
+                .iter()
+                .map(|t| {
+                    let key = Log::case_id(t).unwrap();
+                    l2.traces
+                        .iter()
+                        .find(|o| Log::case_id(o).unwrap() == key)
+                        .unwrap()
+                        .events
+                        .len()
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_merge_policy() {
+        let mut left = Trace::default();
+        left.attributes.insert(("concept:name", "Case1"));
+        left.attributes.insert(("foo", 1));
+
+        let mut right = Trace::default();
+        right.attributes.insert(("concept:name", "Case1"));
+        right.attributes.insert(("foo", 2));
+        right.attributes.insert(("bar", 3));
+
+        let merged = MergePolicy::Left.merge(left.clone(), right.clone());
+        assert_eq!(merged.attributes.len(), 2);
+        assert_eq!(merged.get_value("foo"), Some(&AttributeValue::Int(1)));
+
+        let merged = MergePolicy::Right.merge(left.clone(), right.clone());
+        assert_eq!(merged.attributes.len(), 3);
+        assert_eq!(merged.get_value("foo"), Some(&AttributeValue::Int(2)));
+
+        let merged = MergePolicy::Combine.merge(left, right);
+        assert_eq!(merged.attributes.len(), 3);
+        assert_eq!(merged.get_value("foo"), Some(&AttributeValue::Int(1)));
+        assert_eq!(merged.get_value("bar"), Some(&AttributeValue::Int(3)));
+
+        let mut anonymous = Log::default();
+        anonymous.traces.push(Trace::default());
+        assert!(anonymous.difference(&Log::default()).is_err());
+    }
+
+    fn case(id: &str, foo: i64, activities: &[&str]) -> Trace {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", id));
+        trace.attributes.insert(("foo", foo));
+        for activity in activities {
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", *activity));
+            trace.events.push(event);
+        }
+        trace
+    }
+
+    fn activities(trace: &Trace) -> Vec<&str> {
+        trace.events.iter().map(|e| e.name().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_duplicate_case_ids() {
+        let left = Log {
+            traces: vec![
+                case("1", 1, &["a"]),
+                case("2", 2, &["b"]),
+                case("1", 3, &["c"]),
+            ],
+            ..Log::default()
+        };
+        let right = Log {
+            traces: vec![
+                case("1", 4, &["d"]),
+                case("3", 5, &["e"]),
+                case("3", 6, &["f"]),
+            ],
+            ..Log::default()
+        };
+
+        let union = left
+            .clone()
+            .union(right.clone(), MergePolicy::Left)
+            .unwrap();
+        assert_eq!(case_ids(&union), vec!["1", "2", "3"]);
+        assert_eq!(activities(&union.traces[0]), vec!["a"]);
+        assert_eq!(activities(&union.traces[2]), vec!["e"]);
+
+        let union = left
+            .clone()
+            .union(right.clone(), MergePolicy::Right)
+            .unwrap();
+        assert_eq!(case_ids(&union), vec!["1", "2", "3"]);
+        assert_eq!(
+            union.traces[0].get_value("foo"),
+            Some(&AttributeValue::Int(4))
+        );
+        assert_eq!(activities(&union.traces[0]), vec!["d"]);
+        assert_eq!(activities(&union.traces[2]), vec!["f"]);
+
+        let union = left
+            .clone()
+            .union(right.clone(), MergePolicy::Combine)
+            .unwrap();
+        assert_eq!(
+            union.traces[0].get_value("foo"),
+            Some(&AttributeValue::Int(1))
+        );
+        assert_eq!(activities(&union.traces[0]), vec!["a", "c", "d"]);
+        assert_eq!(activities(&union.traces[2]), vec!["e", "f"]);
+
+        let intersection = left
+            .clone()
+            .intersect(right.clone(), MergePolicy::Combine)
+            .unwrap();
+        assert_eq!(case_ids(&intersection), vec!["1"]);
+        assert_eq!(activities(&intersection.traces[0]), vec!["a", "c", "d"]);
+
+        let intersection = right.intersect(left, MergePolicy::Right).unwrap();
+        assert_eq!(case_ids(&intersection), vec!["1"]);
+        assert_eq!(activities(&intersection.traces[0]), vec!["c"]);
+    }
+
+    #[test]
+    fn test_log_artifact_source() {
+        let registry = REGISTRY.lock().unwrap();
+        let factory = &registry.get("LogArtifactSource").unwrap().factory;
+
+        let mut artifacts: Vec<AnyArtifact> = vec![load_log(&["book", "L1.xes"]).into()];
+        let mut stream = factory
+            .build_stream(Default::default(), &mut artifacts, vec![], vec![])
+            .unwrap();
+
+        let mut log = Log::default();
+        log.consume(&mut stream).unwrap();
+        drop(stream);
+
+        assert_eq!(case_ids(&log), case_ids(&load_log(&["book", "L1.xes"])));
+        assert!(artifacts[0].downcast_ref::<Log>().is_some());
+
+        let mut artifacts: Vec<AnyArtifact> = vec![load_example(&["book", "L1.xes"]).into()];
+        let mut stream = factory
+            .build_stream(Default::default(), &mut artifacts, vec![], vec![])
+            .unwrap();
+
+        let mut log = Log::default();
+        log.consume(&mut stream).unwrap();
+        assert_eq!(log.traces.len(), 6);
+        drop(stream);
+
+        let mut artifacts: Vec<AnyArtifact> = vec![AttributeValue::Int(42).into()];
+        assert!(factory
+            .build_stream(Default::default(), &mut artifacts, vec![], vec![],)
+            .is_err());
+    }
+}
@@ -0,0 +1,664 @@
+//! Merge fragmented cases and whole streams
+//!
+//! A real-world case is often split across system boundaries, e.g. an order appears as one trace
+//! in the ERP system and as another one in the shop system. If the fragments share an attribute
+//! such as an order number, `MergeCases` joins them into a single trace that is identified by the
+//! join value. Events of the merged trace are ordered chronologically.
+//!
+//! As fragments may appear anywhere in the stream, merged traces are released once the inner
+//! stream is exhausted. Traces without join value and events outside of traces are passed
+//! through immediately.
+//!
+//! Logs are often spread over several files, e.g. one per month or per system. `Merge` combines
+//! multiple streams into one, either by concatenation or by interleaving their components by
+//! `time:timestamp` of the time extension. Traces of all streams precede their standalone events,
+//! such that the merged stream keeps a valid order. The meta data of all streams is merged into
+//! one: extensions, classifiers and log attributes are united, whereas globals are only kept if
+//! all streams declare them. Contradicting declarations are resolved in favor of the first stream
+//! and listed in a `MergeReport`.
+//!
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeContainer, AttributeType, AttributeValue, Component,
+    Flavor, Global, Meta, ResOpt, Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// String representation of attribute values that are suitable to identify a case
+pub fn join_value(value: &AttributeValue) -> Option<String> {
+    match value {
+        AttributeValue::String(value) | AttributeValue::Id(value) => Some(value.to_string()),
+        AttributeValue::Int(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Join fragments of cases by a shared attribute
+///
+/// The join value is taken from the trace attributes or, if missing there, from the first event
+/// that carries the join attribute. The merged trace takes the join value as `concept:name`,
+/// lists the names of the merged fragments in `case:fragments` and inherits all further
+/// attributes from the first fragment that holds them. Events are sorted by `time:timestamp` if
+/// all of them carry one, otherwise they are concatenated in order of arrival.
+///
+pub struct MergeCases<T: Stream> {
+    stream: T,
+    key: String,
+    order: Vec<String>,
+    fragments: HashMap<String, Vec<Trace>>,
+    merged: Option<VecDeque<Trace>>,
+}
+
+impl<T: Stream> MergeCases<T> {
+    pub fn new<K: Into<String>>(stream: T, key: K) -> Self {
+        MergeCases {
+            stream,
+            key: key.into(),
+            order: Vec::new(),
+            fragments: HashMap::new(),
+            merged: None,
+        }
+    }
+
+    fn join_value(&self, trace: &Trace) -> Option<String> {
+        trace
+            .get_value(&self.key)
+            .or_else(|| trace.events.iter().find_map(|e| e.get_value(&self.key)))
+            .and_then(join_value)
+    }
+
+    fn merge(id: String, fragments: Vec<Trace>) -> Trace {
+        let mut merged = Trace::default();
+        let mut names = Vec::new();
+
+        for fragment in fragments {
+            if let Some(name) = fragment.get_value("concept:name").and_then(join_value) {
+                names.push(Attribute::new("name", name));
+            }
+            for attribute in fragment.attributes {
+                if merged.get_value(&attribute.key).is_none() {
+                    merged.attributes.insert(attribute);
+                }
+            }
+            merged.events.extend(fragment.events);
+        }
+
+        if merged
+            .events
+            .iter()
+            .all(|e| matches!(e.get_value("time:timestamp"), Some(AttributeValue::Date(_))))
+        {
+            // stable, i.e. the order of arrival is kept for events at the same time
+            merged
+                .events
+                .sort_by_key(|e| match e.get_value("time:timestamp") {
+                    Some(AttributeValue::Date(timestamp)) => Some(*timestamp),
+                    _ => None,
+                });
+        }
+
+        merged.attributes.insert(("concept:name", id));
+        merged
+            .attributes
+            .insert(("case:fragments", AttributeValue::List(names)));
+        merged
+    }
+
+    fn release(&mut self) -> Option<Trace> {
+        if self.merged.is_none() {
+            let mut fragments = std::mem::take(&mut self.fragments);
+            self.merged = Some(
+                self.order
+                    .drain(..)
+                    .map(|id| {
+                        let traces = fragments.remove(&id).unwrap_or_default();
+                        Self::merge(id, traces)
+                    })
+                    .collect(),
+            );
+        }
+
+        self.merged.as_mut().and_then(|merged| merged.pop_front())
+    }
+}
+
+impl<T: Stream> Stream for MergeCases<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.merged.is_some() {
+            return Ok(self.release().map(Component::Trace));
+        }
+
+        loop {
+            match self.stream.next()? {
+                Some(Component::Trace(trace)) => match self.join_value(&trace) {
+                    Some(id) => {
+                        if !self.fragments.contains_key(&id) {
+                            self.order.push(id.clone());
+                        }
+                        self.fragments.entry(id).or_default().push(trace);
+                    }
+                    None => return Ok(Some(Component::Trace(trace))),
+                },
+                Some(component) => return Ok(Some(component)),
+                None => return Ok(self.release().map(Component::Trace)),
+            }
+        }
+    }
+}
+
+impl PluginProvider for MergeCases<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "MergeCases",
+            "Merge traces that share the value of a join attribute, e.g. an order number",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream holding case fragments")
+                    .typed_attr(
+                        "key",
+                        "Attribute that identifies the real case",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    Ok(MergeCases::new(
+                        parameters.acquire_stream("inner")?,
+                        parameters.acquire_attribute("key")?.value.try_string()?,
+                    )
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+/// How `Merge` orders the components of its streams
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum MergeMode {
+    /// Emit the traces of one stream after the other, followed by their events likewise
+    #[default]
+    Concatenate,
+    /// Emit traces by the timestamp of their first event and events by their timestamp
+    Interleave,
+}
+
+impl FromStr for MergeMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "concatenate" => Ok(MergeMode::Concatenate),
+            "interleave" => Ok(MergeMode::Interleave),
+            other => Err(Error::AttributeError(format!(
+                "invalid merge mode {:?}, expected \"concatenate\" or \"interleave\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Contradicting declaration in the meta data of merged streams
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetaConflict {
+    /// Index of the stream whose declaration was dropped
+    pub stream: usize,
+    /// Kind of declaration, e.g. extension, classifier, global or attribute
+    pub kind: String,
+    pub key: String,
+}
+
+/// Outcome of merging the meta data of multiple streams
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub streams: usize,
+    pub conflicts: Vec<MetaConflict>,
+}
+
+impl MergeReport {
+    fn conflict(&mut self, stream: usize, kind: &str, key: &str) {
+        warn!(
+            "{} {:?} of stream {} contradicts an earlier stream and is dropped",
+            kind, key, stream
+        );
+        self.conflicts.push(MetaConflict {
+            stream,
+            kind: kind.to_string(),
+            key: key.to_string(),
+        });
+    }
+}
+
+#[typetag::serde]
+impl Artifact for MergeReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Combine multiple streams into one
+///
+/// Unfolded streams are concatenated as they are, one after the other, since their events belong
+/// to the preceding trace. Interleaving them fails.
+///
+pub struct Merge<'a> {
+    streams: Vec<Box<dyn Stream + 'a>>,
+    mode: MergeMode,
+    /// Next payload component of each stream, `None` once a stream is exhausted
+    heads: Vec<Option<Component>>,
+    flavor: Flavor,
+    started: bool,
+    report: MergeReport,
+}
+
+impl<'a> Merge<'a> {
+    pub fn new(streams: Vec<Box<dyn Stream + 'a>>, mode: MergeMode) -> Self {
+        Merge {
+            report: MergeReport {
+                streams: streams.len(),
+                conflicts: Vec::new(),
+            },
+            streams,
+            mode,
+            heads: Vec::new(),
+            flavor: Flavor::default(),
+            started: false,
+        }
+    }
+
+    /// Merge the meta data of another stream into the meta data of the streams before
+    fn merge_meta(&mut self, merged: &mut Meta, meta: Meta, stream: usize) {
+        for extension in meta.extensions {
+            match merged
+                .extensions
+                .iter()
+                .find(|e| e.prefix == extension.prefix)
+            {
+                Some(e) if e.name != extension.name || e.uri != extension.uri => {
+                    self.report.conflict(stream, "extension", &extension.prefix)
+                }
+                Some(_) => (),
+                None => merged.extensions.push(extension),
+            }
+        }
+
+        for classifier in meta.classifiers {
+            match merged
+                .classifiers
+                .iter()
+                .find(|c| c.name == classifier.name)
+            {
+                Some(c) if c.scope != classifier.scope || c.keys != classifier.keys => {
+                    self.report.conflict(stream, "classifier", &classifier.name)
+                }
+                Some(_) => (),
+                None => merged.classifiers.push(classifier),
+            }
+        }
+
+        // only attributes that are global in all streams stay global
+        let mut globals = Vec::new();
+        for global in std::mem::take(&mut merged.globals) {
+            let others: Vec<&Attribute> = meta
+                .globals
+                .iter()
+                .filter(|g| g.scope == global.scope)
+                .flat_map(|g| g.attributes.iter())
+                .collect();
+            let mut attributes = Vec::new();
+            for attribute in global.attributes {
+                match others.iter().find(|a| a.key == attribute.key) {
+                    Some(other) if other.value.type_hint() != attribute.value.type_hint() => {
+                        self.report.conflict(stream, "global", &attribute.key)
+                    }
+                    Some(_) => attributes.push(attribute),
+                    None => (),
+                }
+            }
+            if !attributes.is_empty() {
+                globals.push(Global {
+                    scope: global.scope,
+                    attributes,
+                });
+            }
+        }
+        merged.globals = globals;
+
+        for attribute in meta.attributes.into_iter() {
+            match merged.attributes.get_value(&attribute.key) {
+                Some(value) if *value != attribute.value => {
+                    self.report.conflict(stream, "attribute", &attribute.key)
+                }
+                Some(_) => (),
+                None => merged.attributes.insert(attribute),
+            }
+        }
+
+        if merged.flavor != meta.flavor {
+            merged.flavor = match (merged.flavor, meta.flavor) {
+                (Flavor::Unfolded, _) | (_, Flavor::Unfolded) => Flavor::Unfolded,
+                _ => Flavor::Log,
+            };
+        }
+    }
+
+    /// Read the meta data of all streams and the first payload component of each
+    fn start(&mut self) -> Result<Meta> {
+        let mut merged: Option<Meta> = None;
+        for i in 0..self.streams.len() {
+            let (meta, head) = match self.streams[i].next()? {
+                Some(Component::Meta(meta)) => (meta, self.streams[i].next()?),
+                head => (Meta::default(), head),
+            };
+            match merged.as_mut() {
+                Some(merged) => self.merge_meta(merged, meta, i),
+                None => merged = Some(meta),
+            }
+            self.heads.push(head);
+        }
+
+        let merged = merged.unwrap_or_default();
+        if merged.flavor == Flavor::Unfolded && self.mode == MergeMode::Interleave {
+            return Err(Error::StreamError(
+                "unfolded streams can't be interleaved".to_string(),
+            ));
+        }
+        self.flavor = merged.flavor;
+        Ok(merged)
+    }
+
+    fn timestamp(component: &Component) -> Option<DateTime> {
+        let value = match component {
+            Component::Trace(trace) => trace.events.first()?.get_value("time:timestamp"),
+            Component::Event(event) => event.get_value("time:timestamp"),
+            Component::Meta(_) => None,
+        };
+        match value {
+            Some(AttributeValue::Date(timestamp)) => Some(*timestamp),
+            _ => None,
+        }
+    }
+
+    /// Stream whose head is to be emitted next
+    fn select(&self) -> Option<usize> {
+        // traces of all streams come first, unless unfolded streams are concatenated
+        let candidates: Vec<usize> = if self.flavor == Flavor::Unfolded {
+            (0..self.heads.len())
+                .find(|i| self.heads[*i].is_some())
+                .into_iter()
+                .collect()
+        } else {
+            let traces: Vec<usize> = (0..self.heads.len())
+                .filter(|i| matches!(self.heads[*i], Some(Component::Trace(_))))
+                .collect();
+            if traces.is_empty() {
+                (0..self.heads.len())
+                    .filter(|i| self.heads[*i].is_some())
+                    .collect()
+            } else {
+                traces
+            }
+        };
+
+        match self.mode {
+            MergeMode::Concatenate => candidates.first().copied(),
+            // components without timestamp don't wait for others
+            MergeMode::Interleave => candidates
+                .into_iter()
+                .min_by_key(|i| self.heads[*i].as_ref().and_then(Self::timestamp)),
+        }
+    }
+}
+
+impl<'a> Stream for Merge<'a> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if !self.started {
+            self.started = true;
+            return Ok(Some(Component::Meta(self.start()?)));
+        }
+
+        match self.select() {
+            Some(i) => {
+                let next = self.streams[i].next()?;
+                if let Some(Component::Meta(_)) = next {
+                    return Err(Error::StateError(format!(
+                        "unexpected meta data in stream {}",
+                        i
+                    )));
+                }
+                Ok(std::mem::replace(&mut self.heads[i], next))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![std::mem::take(&mut self.report).into()])
+    }
+
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        let mut artifacts = Vec::new();
+        for stream in self.streams.iter_mut() {
+            artifacts.extend(stream.emit_artifacts()?);
+        }
+        artifacts.push(self.on_emit_artifacts()?);
+        Ok(artifacts)
+    }
+}
+
+impl PluginProvider for Merge<'static> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Merge",
+            "Concatenate or interleave multiple streams, any further streams follow the first one",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The first stream")
+                    .default_attr(
+                        "mode",
+                        "Either concatenate streams or interleave them by timestamp",
+                        |k| (k, "concatenate").into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mode = parameters
+                        .acquire_attribute("mode")?
+                        .value
+                        .try_string()?
+                        .parse()?;
+                    let mut streams = vec![parameters.acquire_stream("inner")?];
+                    streams.extend(parameters.acquire_streams_anon());
+                    Ok(Merge::new(streams, mode).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::order::OrderGuard;
+    use crate::stream::{ClassifierDecl, Event, Scope, Sink};
+    use crate::DateTime;
+
+    use super::*;
+
+    fn trace(name: &str, order: Option<&str>, events: &[(&str, u32)]) -> Component {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", name));
+        if let Some(order) = order {
+            trace.attributes.insert(("order", order));
+        }
+        for (activity, second) in events.iter() {
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", *activity));
+            let timestamp =
+                DateTime::parse_from_rfc3339(&format!("2020-01-01T00:00:{:02}+00:00", second))
+                    .unwrap();
+            event.attributes.insert(("time:timestamp", timestamp));
+            trace.events.push(event);
+        }
+        Component::Trace(trace)
+    }
+
+    #[test]
+    fn test_merge_cases() {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(trace("erp-1", Some("o1"), &[("a", 0), ("c", 20)]))));
+        buffer.push(Ok(Some(trace("erp-2", Some("o2"), &[("a", 5)]))));
+        buffer.push(Ok(Some(trace("misc", None, &[("x", 0)]))));
+        buffer.push(Ok(Some(trace(
+            "shop-1",
+            Some("o1"),
+            &[("b", 10), ("d", 30)],
+        ))));
+
+        let mut log = Log::default();
+        log.consume(&mut MergeCases::new(buffer, "order")).unwrap();
+
+        let names: Vec<_> = log
+            .traces
+            .iter()
+            .map(|t| t.get_value("concept:name").unwrap().try_string().unwrap())
+            .collect();
+        assert_eq!(names, vec!["misc", "o1", "o2"]);
+
+        let merged = &log.traces[1];
+        let activities: Vec<_> = merged
+            .events
+            .iter()
+            .map(|e| e.get_value("concept:name").unwrap().try_string().unwrap())
+            .collect();
+        assert_eq!(activities, vec!["a", "b", "c", "d"]);
+
+        let fragments = merged
+            .get_value("case:fragments")
+            .unwrap()
+            .try_list()
+            .unwrap();
+        assert_eq!(
+            fragments,
+            &[
+                Attribute::new("name", "erp-1"),
+                Attribute::new("name", "shop-1")
+            ]
+        );
+    }
+
+    fn names(log: &Log) -> Vec<String> {
+        log.traces
+            .iter()
+            .map(|t| t.get_value("concept:name").unwrap().try_string().unwrap())
+            .chain(
+                log.events
+                    .iter()
+                    .map(|e| e.get_value("concept:name").unwrap().try_string().unwrap()),
+            )
+            .map(|n| n.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let origin = DateTime::parse_from_rfc3339("2020-01-01T00:00:00+00:00").unwrap();
+        let event = |name: &str, second: i64| {
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", name));
+            event
+                .attributes
+                .insert(("time:timestamp", origin + chrono::Duration::seconds(second)));
+            Component::Event(event)
+        };
+        let meta = |source: &str, keys: &str| {
+            let mut meta = Meta::default();
+            meta.attributes.insert(("source", source));
+            meta.classifiers.push(ClassifierDecl {
+                name: "activity".to_string(),
+                scope: Scope::Event,
+                keys: keys.to_string(),
+            });
+            Component::Meta(meta)
+        };
+
+        let mut first = Buffer::default();
+        first.push(Ok(Some(meta("erp", "concept:name"))));
+        first.push(Ok(Some(trace("t1", None, &[("a", 0)]))));
+        first.push(Ok(Some(trace("t2", None, &[("a", 20)]))));
+        first.push(Ok(Some(event("e1", 5))));
+        let mut second = Buffer::default();
+        second.push(Ok(Some(meta("shop", "concept:name org:resource"))));
+        second.push(Ok(Some(trace("t3", None, &[("a", 10)]))));
+        second.push(Ok(Some(event("e2", 1))));
+
+        let streams =
+            || -> Vec<Box<dyn Stream>> { vec![Box::new(first.clone()), Box::new(second.clone())] };
+
+        let mut log = Log::default();
+        let artifacts = log
+            .consume(&mut OrderGuard::new(Merge::new(
+                streams(),
+                MergeMode::Concatenate,
+            )))
+            .unwrap();
+        assert_eq!(names(&log), vec!["t1", "t2", "t3", "e1", "e2"]);
+        assert_eq!(
+            log.meta
+                .attributes
+                .get_value("source")
+                .unwrap()
+                .try_string()
+                .unwrap(),
+            "erp"
+        );
+        assert_eq!(log.meta.classifiers.len(), 1);
+
+        let report = AnyArtifact::find::<MergeReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.streams, 2);
+        let conflicts: Vec<_> = report
+            .conflicts
+            .iter()
+            .map(|c| (c.stream, c.kind.as_str(), c.key.as_str()))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![(1, "classifier", "activity"), (1, "attribute", "source")]
+        );
+
+        let mut log = Log::default();
+        log.consume(&mut Merge::new(streams(), "interleave".parse().unwrap()))
+            .unwrap();
+        assert_eq!(names(&log), vec!["t1", "t3", "t2", "e2", "e1"]);
+    }
+}
@@ -14,6 +14,7 @@
 pub use self::core::artifact::*;
 pub use self::core::attribute::*;
 pub use self::core::component::*;
+pub use self::core::iter::*;
 pub use self::core::sink::*;
 pub use self::core::stream::*;
 #[cfg(test)]
@@ -21,19 +22,75 @@ pub use self::core::tests;
 
 pub mod core;
 // modules
+pub mod anonymize;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod bottleneck;
 pub mod buffer;
+pub mod canonical;
+pub mod certify;
 pub mod channel;
+pub mod chaos;
+pub mod clock;
+pub mod cohort;
+pub mod compare;
+pub mod context;
+pub mod correlate;
+pub mod csv;
+pub mod currency;
+pub mod derive;
+pub mod dfg;
+pub mod duplicate;
 pub mod duplicator;
+pub mod editor;
+pub mod expression;
 pub mod extension;
 pub mod filter;
 pub mod flow;
+pub mod fold;
+pub mod globals;
+pub mod graphml;
+pub mod handler;
+pub mod impute;
+pub mod inspect;
+pub mod json;
+pub mod locale;
 pub mod log;
+pub mod merge;
+pub mod monitor;
+pub mod mxml;
+pub mod net;
+pub mod noise;
+pub mod notify;
 pub mod observer;
+pub mod order;
+pub mod outcome;
+pub mod output;
+pub mod partition;
 pub mod plugin;
+pub mod predicate;
+pub mod preview;
+pub mod privacy;
+pub mod progress;
+pub mod project;
+pub mod provenance;
+pub mod pseudonymize;
+pub mod quality;
 pub mod repair;
+pub mod report;
+pub mod rework;
+pub mod rotate;
+pub mod sequence;
+pub mod session;
+pub mod simulate;
 pub mod split;
 pub mod stats;
+pub mod taskmining;
+pub mod unit;
 pub mod validator;
+pub mod variant;
 pub mod void;
 pub mod xes;
 pub mod xml_util;
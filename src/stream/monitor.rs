@@ -0,0 +1,427 @@
+//! Operational monitoring of live event streams
+//!
+//! In an event-only stream, cases are not materialized as traces. Instead, each event refers to
+//! its case by an attribute. The `OpenCases` handler keeps track of cases that have been started
+//! but not ended yet. A case is opened by one of the configured start activities (or any event if
+//! none are given) and closed by one of the end activities or when it was idle for too long. Idle
+//! time is measured by a `Clock`, event time by default. The `IncrementalOpenCases` plugin
+//! additionally sends snapshots to a data channel every n events.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::channel::TypedSender;
+use crate::stream::clock::{EventClock, SharedClock};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeContainer, AttributeType, Event, Stream};
+use crate::{DateTime, Result};
+
+/// State of a single open case
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseState {
+    pub started: Option<DateTime>,
+    pub last_seen: Option<DateTime>,
+    pub events: usize,
+}
+
+/// Snapshot of currently open cases
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenCasesSnapshot {
+    pub open: BTreeMap<String, CaseState>,
+    pub closed: usize,
+    pub timed_out: usize,
+}
+
+#[typetag::serde]
+impl Artifact for OpenCasesSnapshot {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Track open cases of an event-only stream
+///
+/// Events that are part of a trace are ignored as their case is already known to be complete.
+///
+pub struct OpenCases {
+    case_key: String,
+    activity_key: String,
+    start: HashSet<String>,
+    end: HashSet<String>,
+    timeout: Option<Duration>,
+    clock: SharedClock,
+    periodic: Option<(usize, TypedSender<OpenCasesSnapshot>)>,
+    ct_event: usize,
+    snapshot: OpenCasesSnapshot,
+}
+
+impl Default for OpenCases {
+    fn default() -> Self {
+        Self::new("case:concept:name")
+    }
+}
+
+impl OpenCases {
+    /// Create a new tracker that identifies cases by the given event attribute
+    pub fn new<K: Into<String>>(case_key: K) -> Self {
+        Self {
+            case_key: case_key.into(),
+            activity_key: "concept:name".to_string(),
+            start: HashSet::new(),
+            end: HashSet::new(),
+            timeout: None,
+            clock: EventClock::shared(),
+            periodic: None,
+            ct_event: 0,
+            snapshot: OpenCasesSnapshot::default(),
+        }
+    }
+
+    /// Only open cases on the given activities
+    pub fn start<I: IntoIterator<Item = S>, S: Into<String>>(mut self, activities: I) -> Self {
+        self.start.extend(activities.into_iter().map(|a| a.into()));
+        self
+    }
+
+    /// Close cases on the given activities
+    pub fn end<I: IntoIterator<Item = S>, S: Into<String>>(mut self, activities: I) -> Self {
+        self.end.extend(activities.into_iter().map(|a| a.into()));
+        self
+    }
+
+    /// Close cases that did not receive an event for the given duration
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Measure the timeout by the given clock instead of event time
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Send a snapshot to the given channel every `every` events
+    pub fn periodic(mut self, every: usize, sender: TypedSender<OpenCasesSnapshot>) -> Self {
+        self.periodic = Some((every, sender));
+        self
+    }
+
+    /// Access the current state
+    pub fn snapshot(&self) -> &OpenCasesSnapshot {
+        &self.snapshot
+    }
+
+    fn expire(&mut self, now: &DateTime) {
+        if let Some(timeout) = self.timeout {
+            let before = self.snapshot.open.len();
+            self.snapshot.open.retain(|_, state| match state.last_seen {
+                Some(last_seen) => now.signed_duration_since(last_seen) <= timeout,
+                None => true,
+            });
+            self.snapshot.timed_out += before - self.snapshot.open.len();
+        }
+    }
+
+    fn update(&mut self, event: &Event) -> Result<()> {
+        let case = match event.get_value(&self.case_key) {
+            Some(case) => case.try_string()?.to_string(),
+            None => return Ok(()),
+        };
+        let activity = match event.get_value(&self.activity_key) {
+            Some(activity) => Some(activity.try_string()?),
+            None => None,
+        };
+        let timestamp = match event.get_value("time:timestamp") {
+            Some(timestamp) => Some(*timestamp.try_date()?),
+            None => None,
+        };
+
+        self.clock.observe(event)?;
+        if let Some(now) = self.clock.now() {
+            self.expire(&now);
+        }
+
+        let is_start = self.start.is_empty() || activity.is_some_and(|a| self.start.contains(a));
+        if !self.snapshot.open.contains_key(&case) && is_start {
+            self.snapshot.open.insert(
+                case.clone(),
+                CaseState {
+                    started: timestamp,
+                    last_seen: timestamp,
+                    events: 0,
+                },
+            );
+        }
+
+        if let Some(state) = self.snapshot.open.get_mut(&case) {
+            state.events += 1;
+            if timestamp.is_some() {
+                state.last_seen = timestamp;
+            }
+
+            if activity.is_some_and(|a| self.end.contains(a)) {
+                self.snapshot.open.remove(&case);
+                self.snapshot.closed += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for OpenCases {
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if in_trace {
+            return Ok(Some(event));
+        }
+
+        self.update(&event)?;
+        self.ct_event += 1;
+
+        if let Some((every, sender)) = &self.periodic {
+            if *every > 0 && self.ct_event.is_multiple_of(*every) {
+                sender.send(self.snapshot.clone())?;
+            }
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.snapshot.clone().into()])
+    }
+}
+
+impl PluginProvider for OpenCases {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "OpenCases",
+                "Track cases of an event-only stream that were started but not ended yet",
+                Factory::new(
+                    Declaration::default().with(OpenCases::declare),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let open_cases = OpenCases::from_parameters(parameters)?;
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, open_cases))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+            Entry::new(
+                "IncrementalOpenCases",
+                "Track open cases of an event-only stream and send snapshots while it is consumed",
+                Factory::new(
+                    Declaration::default()
+                        .with(OpenCases::declare)
+                        .data_sender::<OpenCasesSnapshot, _, _>(
+                            "snapshots",
+                            "Channel that receives interim snapshots",
+                        )
+                        .default_attr(
+                            "every",
+                            "Send a snapshot every n events, 0 to disable",
+                            |k| (k, 1000).into(),
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let every = *parameters.acquire_attribute("every")?.value.try_int()?;
+                        let open_cases = OpenCases::from_parameters(parameters)?.periodic(
+                            every.max(0) as usize,
+                            parameters.acquire_data_sender("snapshots")?,
+                        );
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, open_cases))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+impl OpenCases {
+    /// Declare the stream and attributes shared by the open case plugins
+    fn declare(declaration: Declaration) -> Declaration {
+        declaration
+            .stream("inner", "The stream to be monitored")
+            .default_attr("case", "Event attribute that identifies the case", |k| {
+                (k, "case:concept:name").into()
+            })
+            .optional_attr("start", "Activities that start a case", AttributeType::List)
+            .optional_attr("end", "Activities that end a case", AttributeType::List)
+            .optional_attr(
+                "timeout",
+                "Seconds of event time after which an idle case counts as stale",
+                AttributeType::Int,
+            )
+    }
+
+    fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        let mut open_cases =
+            OpenCases::new(parameters.acquire_attribute("case")?.value.try_string()?);
+
+        if let Ok(start) = parameters.acquire_attribute("start") {
+            open_cases = open_cases.start(
+                start
+                    .value
+                    .try_list()?
+                    .iter()
+                    .map(|a| Ok(a.value.try_string()?.to_string()))
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+
+        if let Ok(end) = parameters.acquire_attribute("end") {
+            open_cases = open_cases.end(
+                end.value
+                    .try_list()?
+                    .iter()
+                    .map(|a| Ok(a.value.try_string()?.to_string()))
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+
+        if let Ok(timeout) = parameters.acquire_attribute("timeout") {
+            open_cases = open_cases.timeout(Duration::seconds(*timeout.value.try_int()?));
+        }
+
+        Ok(open_cases)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::channel::{channel, DataEndpoints, Payload, TypedReceiver};
+    use std::sync::Arc;
+
+    use crate::stream::clock::MockClock;
+    use crate::stream::plugin::REGISTRY;
+    use crate::stream::void::consume;
+    use crate::stream::{AttributeMap, Component, Meta};
+
+    use super::*;
+
+    fn event(case: &str, activity: &str, minute: u32) -> Component {
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        let mut event = Event::default();
+        event.attributes.insert(("case:concept:name", case));
+        event.attributes.insert(("concept:name", activity));
+        event.attributes.insert(("time:timestamp", timestamp));
+        Component::Event(event)
+    }
+
+    fn stream() -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for (case, activity, minute) in [
+            ("1", "a", 0),
+            ("2", "a", 1),
+            ("1", "b", 2),
+            ("3", "b", 3),
+            ("2", "z", 4),
+            ("4", "a", 5),
+            ("1", "b", 30),
+        ]
+        .iter()
+        {
+            buffer.push(Ok(Some(event(case, activity, *minute))));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_open_cases() {
+        let (sender, receiver) = channel::<Payload>(None);
+        let receiver = TypedReceiver::<OpenCasesSnapshot>::from(receiver);
+        let mut observer = OpenCases::default()
+            .start(vec!["a"])
+            .end(vec!["z"])
+            .timeout(Duration::minutes(10))
+            .periodic(3, sender.into())
+            .into_observer(stream());
+
+        let artifacts = consume(&mut observer).unwrap();
+        let snapshot =
+            AnyArtifact::find::<OpenCasesSnapshot>(&mut artifacts.iter().flatten()).unwrap();
+
+        // cases 1 and 4 time out, the late "b" does not reopen case 1
+        assert!(snapshot.open.is_empty());
+        assert_eq!(snapshot.closed, 1);
+        assert_eq!(snapshot.timed_out, 2);
+
+        let mut periodic = Vec::new();
+        while let Ok(Some(snapshot)) = receiver.try_recv() {
+            periodic.push(snapshot.open.len());
+        }
+        assert_eq!(periodic, [2, 2]);
+
+        let mut observer = OpenCases::default().into_observer(stream());
+        let artifacts = consume(&mut observer).unwrap();
+        let snapshot =
+            AnyArtifact::find::<OpenCasesSnapshot>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(snapshot.open.len(), 4);
+        assert_eq!(snapshot.open["1"].events, 3);
+
+        // a clock that is far ahead times out each case on the next event
+        let now = DateTime::parse_from_rfc3339("2020-01-01T00:30:00+00:00").unwrap();
+        let mut observer = OpenCases::default()
+            .start(vec!["a"])
+            .timeout(Duration::minutes(10))
+            .clock(Arc::new(MockClock::new(now)))
+            .into_observer(stream());
+        let artifacts = consume(&mut observer).unwrap();
+        let snapshot =
+            AnyArtifact::find::<OpenCasesSnapshot>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert!(snapshot.open.is_empty());
+        assert_eq!(snapshot.timed_out, 3);
+    }
+
+    #[test]
+    fn test_incremental_open_cases() {
+        let (sender, receiver) = channel::<Payload>(None);
+        let receiver = TypedReceiver::<OpenCasesSnapshot>::from(receiver);
+
+        let registry = REGISTRY.lock().unwrap();
+        let mut stream = registry
+            .get("IncrementalOpenCases")
+            .unwrap()
+            .factory
+            .build_stream_with_data(
+                AttributeMap::from(vec![("every", 2)].into_iter()),
+                &mut [],
+                vec![Box::new(stream())],
+                vec![],
+                DataEndpoints {
+                    senders: vec![sender],
+                    receivers: vec![],
+                },
+            )
+            .unwrap();
+        consume(&mut stream).unwrap();
+
+        let mut periodic = Vec::new();
+        while let Ok(Some(snapshot)) = receiver.try_recv() {
+            periodic.push(snapshot.open.len());
+        }
+        assert_eq!(periodic, [2, 3, 4]);
+    }
+}
@@ -0,0 +1,452 @@
+//! Legacy MXML event logs
+//!
+//! MXML is the log format of ProM 5 and earlier, and many archived logs are only available in it.
+//! The `MxmlReader` streams such logs: each `ProcessInstance` becomes a trace and each
+//! `AuditTrailEntry` an event. The standard elements of an entry are mapped onto the standard
+//! extensions:
+//!
+//! | MXML                   | promi                  |
+//! |------------------------|------------------------|
+//! | `WorkflowModelElement` | `concept:name`         |
+//! | `EventType`            | `lifecycle:transition` |
+//! | `Timestamp`            | `time:timestamp`       |
+//! | `Originator`           | `org:resource`         |
+//!
+//! Instance identifiers become the `concept:name` of traces, the identifier of the (first)
+//! process the `concept:name` of the log. Data attributes are read as strings, since MXML doesn't
+//! declare their types. Timestamps without time zone are taken as UTC.
+//!
+//! ```
+//! use promi::stream::mxml::MxmlReader;
+//! use promi::stream::Stream;
+//!
+//! let s = r#"<WorkflowLog>
+//!              <Process id="orders">
+//!                <ProcessInstance id="1">
+//!                  <AuditTrailEntry>
+//!                    <WorkflowModelElement>register</WorkflowModelElement>
+//!                    <EventType>complete</EventType>
+//!                    <Originator>Alice</Originator>
+//!                  </AuditTrailEntry>
+//!                </ProcessInstance>
+//!              </Process>
+//!            </WorkflowLog>"#;
+//!
+//! let trace = MxmlReader::new(s.as_bytes()).traces().next().unwrap().unwrap();
+//! assert_eq!(trace.case_id(), Some("1"));
+//! assert_eq!(trace.events[0].resource(), Some("Alice"));
+//! ```
+//!
+
+use std::fs::File;
+use std::io;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use quick_xml::events::{BytesStart as QxBytesStart, Event as QxEvent};
+use quick_xml::Reader as QxReader;
+
+use crate::stream::extension::{Concept, Extension, Org, Time};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AttributeType, Component, Event, ExtensionDecl, Meta, ResOpt, Stream, Trace};
+use crate::{DateTime, Error, Result};
+
+/// Read MXML logs as event stream
+pub struct MxmlReader<R: io::BufRead> {
+    reader: QxReader<R>,
+    buffer: Vec<u8>,
+    path: Vec<Vec<u8>>,
+    meta: Option<Meta>,
+    trace: Option<Trace>,
+    event: Option<Event>,
+    // key of the data attribute or unknown event type that is read
+    key: Option<String>,
+    text: String,
+    // empty element whose end is yet to be handled
+    pending: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<R: io::BufRead> MxmlReader<R> {
+    pub fn new(reader: R) -> Self {
+        let mut reader = QxReader::from_reader(reader);
+        reader.trim_text(true);
+
+        let meta = Meta {
+            extensions: vec![
+                Concept::declare(),
+                Time::declare(),
+                Org::declare(),
+                ExtensionDecl {
+                    name: "Lifecycle".to_string(),
+                    prefix: "lifecycle".to_string(),
+                    uri: "http://www.xes-standard.org/lifecycle.xesext".to_string(),
+                },
+            ],
+            ..Meta::default()
+        };
+
+        MxmlReader {
+            reader,
+            buffer: Vec::new(),
+            path: Vec::new(),
+            meta: Some(meta),
+            trace: None,
+            event: None,
+            key: None,
+            text: String::new(),
+            pending: None,
+            done: false,
+        }
+    }
+
+    fn attribute(element: &QxBytesStart, key: &[u8]) -> Result<Option<String>> {
+        for attribute in element.attributes() {
+            let attribute = attribute?;
+            if attribute.key == key {
+                return Ok(Some(String::from_utf8(
+                    attribute.unescaped_value()?.to_vec(),
+                )?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse_timestamp(text: &str) -> Result<DateTime> {
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+            return Ok(timestamp);
+        }
+
+        // ProM writes time zones without colon, older tools none at all
+        for format in ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%dT%H:%M:%S%z"].iter() {
+            if let Ok(timestamp) = DateTime::parse_from_str(text, format) {
+                return Ok(timestamp);
+            }
+        }
+        for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"].iter() {
+            if let Ok(timestamp) = NaiveDateTime::parse_from_str(text, format) {
+                return Ok(Utc.from_utc_datetime(&timestamp).into());
+            }
+        }
+
+        Err(Error::XMLError(format!(
+            "invalid MXML timestamp {:?}",
+            text
+        )))
+    }
+
+    fn on_start(&mut self, element: &QxBytesStart) -> Result<Option<Component>> {
+        match element.name() {
+            b"Source" => {
+                if let (Some(meta), Some(program)) =
+                    (self.meta.as_mut(), Self::attribute(element, b"program")?)
+                {
+                    meta.attributes.insert(("source", program));
+                }
+            }
+            b"Process" => {
+                if let (Some(meta), Some(id)) =
+                    (self.meta.as_mut(), Self::attribute(element, b"id")?)
+                {
+                    if meta.attributes.get_value("concept:name").is_none() {
+                        meta.attributes.insert(("concept:name", id));
+                    }
+                }
+            }
+            b"ProcessInstance" => {
+                let mut trace = Trace::default();
+                if let Some(id) = Self::attribute(element, b"id")? {
+                    trace.attributes.insert(("concept:name", id));
+                }
+                self.trace = Some(trace);
+
+                // meta data is complete with the first instance
+                if let Some(meta) = self.meta.take() {
+                    return Ok(Some(Component::Meta(meta)));
+                }
+            }
+            b"AuditTrailEntry" => self.event = Some(Event::default()),
+            b"Attribute" => self.key = Self::attribute(element, b"name")?,
+            b"EventType" => self.key = Self::attribute(element, b"unknowntype")?,
+            _ => (),
+        }
+
+        Ok(None)
+    }
+
+    fn on_end(&mut self, name: &[u8]) -> Result<Option<Component>> {
+        let text = std::mem::take(&mut self.text);
+
+        match name {
+            b"ProcessInstance" => return Ok(self.trace.take().map(Component::Trace)),
+            b"AuditTrailEntry" => {
+                if let (Some(trace), Some(event)) = (self.trace.as_mut(), self.event.take()) {
+                    trace.events.push(event);
+                }
+            }
+            b"Attribute" => {
+                if let Some(key) = self.key.take() {
+                    let attribute = (key, text);
+                    match (self.event.as_mut(), self.trace.as_mut(), self.meta.as_mut()) {
+                        (Some(event), _, _) => event.attributes.insert(attribute),
+                        (None, Some(trace), _) => trace.attributes.insert(attribute),
+                        (None, None, Some(meta)) => meta.attributes.insert(attribute),
+                        _ => (),
+                    }
+                }
+            }
+            _ => {
+                let event = match self.event.as_mut() {
+                    Some(event) => event,
+                    None => return Ok(None),
+                };
+
+                match name {
+                    b"WorkflowModelElement" => event.attributes.insert(("concept:name", text)),
+                    b"EventType" => {
+                        // the actual type of `unknown` event types is given as attribute
+                        let transition = match self.key.take() {
+                            Some(unknown) if text == "unknown" || text.is_empty() => unknown,
+                            _ => text,
+                        };
+                        event
+                            .attributes
+                            .insert(("lifecycle:transition", transition))
+                    }
+                    b"Timestamp" => event
+                        .attributes
+                        .insert(("time:timestamp", Self::parse_timestamp(&text)?)),
+                    b"Originator" => event.attributes.insert(("org:resource", text)),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn read(&mut self) -> ResOpt {
+        if let Some(name) = self.pending.take() {
+            if let Some(component) = self.on_end(&name)? {
+                return Ok(Some(component));
+            }
+        }
+
+        loop {
+            let event = self.reader.read_event(&mut self.buffer)?;
+            let component = match event {
+                QxEvent::Start(element) => {
+                    let element = element.into_owned();
+                    self.buffer.clear();
+                    self.path.push(element.name().to_vec());
+                    self.on_start(&element)?
+                }
+                QxEvent::Empty(element) => {
+                    let element = element.into_owned();
+                    self.buffer.clear();
+                    match self.on_start(&element)? {
+                        Some(component) => {
+                            // the end of an empty element is handled once the start was emitted
+                            self.pending = Some(element.name().to_vec());
+                            Some(component)
+                        }
+                        None => self.on_end(element.name())?,
+                    }
+                }
+                QxEvent::Text(text) => {
+                    let text = String::from_utf8(text.unescaped()?.to_vec())?;
+                    self.buffer.clear();
+                    self.text.push_str(&text);
+                    None
+                }
+                QxEvent::End(element) => {
+                    let name = element.name().to_vec();
+                    self.buffer.clear();
+                    match self.path.pop() {
+                        Some(open) if open == name => self.on_end(&name)?,
+                        open => {
+                            return Err(Error::XMLError(format!(
+                                "unexpected closing tag {:?}, expected {:?}",
+                                String::from_utf8_lossy(&name),
+                                open.map(|o| String::from_utf8_lossy(&o).to_string())
+                            )))
+                        }
+                    }
+                }
+                QxEvent::Eof => {
+                    self.done = true;
+                    return Ok(self.meta.take().map(Component::Meta));
+                }
+                _ => {
+                    self.buffer.clear();
+                    None
+                }
+            };
+
+            if component.is_some() {
+                return Ok(component);
+            }
+        }
+    }
+}
+
+impl MxmlReader<io::BufReader<File>> {
+    /// Open an MXML file
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(io::BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: io::BufRead + Send> Stream for MxmlReader<R> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.done {
+            return Ok(None);
+        }
+
+        let component = self.read();
+        if component.is_err() {
+            self.done = true;
+        }
+        component
+    }
+}
+
+impl PluginProvider for MxmlReader<io::BufReader<File>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "MxmlReader",
+            "Read a legacy MXML log as used by ProM 5",
+            Factory::new(
+                Declaration::default().typed_attr(
+                    "path",
+                    "Location of the MXML file",
+                    AttributeType::String,
+                ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    Ok(MxmlReader::open(path)?.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeContainer, AttributeValue, Sink};
+
+    use super::*;
+
+    const LOG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <WorkflowLog xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+          <Data><Attribute name="app.name">ProM</Attribute></Data>
+          <Source program="staffware"/>
+          <Process id="claims" description="claim handling">
+            <ProcessInstance id="case 1">
+              <Data><Attribute name="priority">high</Attribute></Data>
+              <AuditTrailEntry>
+                <Data><Attribute name="cost">12.5</Attribute></Data>
+                <WorkflowModelElement>register</WorkflowModelElement>
+                <EventType>start</EventType>
+                <Timestamp>2005-12-01T09:00:00.000+01:00</Timestamp>
+                <Originator>Alice</Originator>
+              </AuditTrailEntry>
+              <AuditTrailEntry>
+                <WorkflowModelElement>register</WorkflowModelElement>
+                <EventType unknowntype="review">unknown</EventType>
+                <Timestamp>2005-12-01T09:30:00.000+0100</Timestamp>
+              </AuditTrailEntry>
+            </ProcessInstance>
+            <ProcessInstance id="case 2">
+              <AuditTrailEntry>
+                <WorkflowModelElement>check &amp; pay</WorkflowModelElement>
+                <EventType>complete</EventType>
+                <Timestamp>2005-12-02T10:00:00</Timestamp>
+              </AuditTrailEntry>
+            </ProcessInstance>
+            <ProcessInstance id="empty"/>
+          </Process>
+          <Process id="other">
+            <Data><Attribute name="ignored">x</Attribute></Data>
+            <ProcessInstance id="case 3"><Data/></ProcessInstance>
+          </Process>
+        </WorkflowLog>"#;
+
+    #[test]
+    fn test_mxml_reader() {
+        let mut log = Log::default();
+        log.consume(&mut MxmlReader::new(LOG.as_bytes())).unwrap();
+
+        let string =
+            |value: Option<&AttributeValue>| value.unwrap().try_string().unwrap().to_string();
+        assert_eq!(
+            string(log.meta.attributes.get_value("concept:name")),
+            "claims"
+        );
+        assert_eq!(string(log.meta.attributes.get_value("source")), "staffware");
+        assert_eq!(string(log.meta.attributes.get_value("app.name")), "ProM");
+        assert_eq!(log.meta.extensions.len(), 4);
+
+        assert_eq!(log.traces.len(), 4);
+        let trace = &log.traces[0];
+        assert_eq!(trace.case_id(), Some("case 1"));
+        assert_eq!(string(trace.get_value("priority")), "high");
+
+        let event = &trace.events[0];
+        assert_eq!(
+            (event.name(), event.transition(), event.resource()),
+            (Some("register"), Some("start"), Some("Alice"))
+        );
+        assert_eq!(string(event.get_value("cost")), "12.5");
+        assert_eq!(trace.events[1].transition(), Some("review"));
+        assert_eq!(
+            *trace.events[1].timestamp().unwrap() - *event.timestamp().unwrap(),
+            chrono::Duration::minutes(30)
+        );
+
+        let event = &log.traces[1].events[0];
+        assert_eq!(event.name(), Some("check & pay"));
+        assert_eq!(
+            event.timestamp().unwrap().to_rfc3339(),
+            "2005-12-02T10:00:00+00:00"
+        );
+        assert_eq!(log.traces[2].case_id(), Some("empty"));
+        assert!(log.traces[2].events.is_empty());
+
+        assert_eq!(log.traces[3].case_id(), Some("case 3"));
+        assert!(log.meta.attributes.get_value("ignored").is_none());
+
+        // empty instances, logs without instances and broken documents
+        let empty = r#"<WorkflowLog><Process><ProcessInstance id="1"/></Process></WorkflowLog>"#;
+        let traces: Vec<_> = MxmlReader::new(empty.as_bytes())
+            .traces()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(traces.len(), 1);
+        let components: Vec<_> =
+            MxmlReader::new(r#"<WorkflowLog><Process id="p"/></WorkflowLog>"#.as_bytes())
+                .iter()
+                .collect::<Result<_>>()
+                .unwrap();
+        assert!(matches!(components[..], [Component::Meta(_)]));
+        let broken = r#"<WorkflowLog><Process><ProcessInstance></Process>"#;
+        assert!(Log::default()
+            .consume(&mut MxmlReader::new(broken.as_bytes()))
+            .is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! Stream endpoints that communicate over TCP
+//!
+//! A `TcpStreamSender` is a sink that connects to a `TcpStreamReceiver`, which is a stream, e.g.
+//! in another promi process on a different host. Components, errors and the end of stream are sent
+//! as length prefixed frames. The sender announces its codec when it connects, so the receiver
+//! decodes either of them:
+//! - `Codec::Binary` encodes frames as MessagePack, which is compact and fast and
+//! - `Codec::Json` encodes frames as JSON, which is convenient for debugging.
+//!
+//! Errors of the sending stream are passed on as `ChannelError`. A connection that closes before
+//! the end of stream is reported as well.
+//!
+
+use std::fmt;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AttributeType, Component, ResOpt, Sink, Stream};
+use crate::{Error, Result};
+
+/// Magic bytes that precede the codec announced by a sender
+const MAGIC: &[u8; 4] = b"PRMI";
+
+/// Upper limit of the size of a single frame
+const MAX_FRAME: usize = 1 << 28;
+
+/// Serialization of stream frames
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Codec {
+    #[default]
+    Binary,
+    Json,
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "binary" | "msgpack" => Ok(Codec::Binary),
+            "json" => Ok(Codec::Json),
+            other => Err(Error::ChannelError(format!("unknown codec: {:?}", other))),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::Binary => write!(f, "binary"),
+            Codec::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl Codec {
+    fn tag(&self) -> u8 {
+        match self {
+            Codec::Binary => 0,
+            Codec::Json => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(Codec::Binary),
+            1 => Ok(Codec::Json),
+            other => Err(Error::ChannelError(format!("unknown codec tag: {}", other))),
+        }
+    }
+
+    fn encode(&self, frame: &Frame) -> Result<Vec<u8>> {
+        match self {
+            Codec::Binary => rmp_serde::to_vec_named(frame).map_err(|e| e.to_string()),
+            Codec::Json => serde_json::to_vec(frame).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| Error::ChannelError(format!("unable to encode frame: {}", e)))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Frame> {
+        match self {
+            Codec::Binary => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            Codec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| Error::ChannelError(format!("unable to decode frame: {}", e)))
+    }
+
+    /// Write a length prefixed frame, returns the number of bytes written
+    pub(crate) fn write_frame<W: Write>(&self, writer: &mut W, frame: &Frame) -> Result<usize> {
+        let bytes = self.encode(frame)?;
+        if bytes.len() > MAX_FRAME {
+            return Err(Error::ResourceLimit(format!(
+                "frame of {} bytes exceeds the limit of {} bytes",
+                bytes.len(),
+                MAX_FRAME
+            )));
+        }
+        writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        writer.write_all(&bytes)?;
+        Ok(bytes.len() + 4)
+    }
+
+    /// Read a length prefixed frame
+    pub(crate) fn read_frame<R: Read>(&self, reader: &mut R) -> Result<Frame> {
+        let mut length = [0; 4];
+        reader.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_FRAME {
+            return Err(Error::ResourceLimit(format!(
+                "frame of {} bytes exceeds the limit of {} bytes",
+                length, MAX_FRAME
+            )));
+        }
+
+        let mut bytes = vec![0; length];
+        reader.read_exact(&mut bytes)?;
+        self.decode(&bytes)
+    }
+}
+
+/// Unit of transmission
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Frame {
+    Component(Component),
+    Error(String),
+    End,
+}
+
+/// Sending endpoint of a TCP stream connection
+pub struct TcpStreamSender {
+    writer: BufWriter<TcpStream>,
+    codec: Codec,
+}
+
+impl TcpStreamSender {
+    /// Connect to a receiver and announce the codec
+    pub fn connect<A: ToSocketAddrs>(address: A, codec: Codec) -> Result<Self> {
+        let mut writer = BufWriter::new(TcpStream::connect(address)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[codec.tag()])?;
+
+        Ok(TcpStreamSender { writer, codec })
+    }
+
+    fn send(&mut self, frame: &Frame) -> Result<()> {
+        self.codec.write_frame(&mut self.writer, frame)?;
+        Ok(())
+    }
+}
+
+impl Sink for TcpStreamSender {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.send(&Frame::Component(component))
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.send(&Frame::End)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.send(&Frame::Error(error.to_string()))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl PluginProvider for TcpStreamSender {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "TcpStreamSender",
+            "Send a stream to a TCP stream receiver",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr(
+                        "address",
+                        "Address of the receiver, e.g. \"host:port\"",
+                        AttributeType::String,
+                    )
+                    .default_attr("codec", "Serialization of stream frames", |k| {
+                        (k, "binary").into()
+                    }),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let codec = parameters
+                        .acquire_attribute("codec")?
+                        .value
+                        .try_string()?
+                        .parse()?;
+                    let address = parameters.acquire_attribute("address")?;
+
+                    Ok(TcpStreamSender::connect(address.value.try_string()?, codec)?.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+/// Receiving endpoint of a TCP stream connection
+///
+/// The receiver listens on its address and accepts a single sender once the stream is read.
+///
+pub struct TcpStreamReceiver {
+    listener: TcpListener,
+    connection: Option<(BufReader<TcpStream>, Codec)>,
+    done: bool,
+}
+
+impl TcpStreamReceiver {
+    /// Listen on the given address
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<Self> {
+        Ok(TcpStreamReceiver {
+            listener: TcpListener::bind(address)?,
+            connection: None,
+            done: false,
+        })
+    }
+
+    /// Address the receiver listens on, e.g. to find out the port assigned by the system
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    fn accept(&mut self) -> Result<(BufReader<TcpStream>, Codec)> {
+        let (stream, _) = self.listener.accept()?;
+        let mut reader = BufReader::new(stream);
+
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(Error::ChannelError(
+                "peer is not a promi stream sender".to_string(),
+            ));
+        }
+
+        Ok((reader, Codec::from_tag(header[4])?))
+    }
+
+    fn receive(&mut self) -> Result<Frame> {
+        if self.connection.is_none() {
+            self.connection = Some(self.accept()?);
+        }
+        let (reader, codec) = self.connection.as_mut().unwrap();
+
+        codec.read_frame(reader).map_err(|error| match error {
+            Error::IOError(_) => {
+                Error::ChannelError("connection closed before the end of stream".to_string())
+            }
+            error => error,
+        })
+    }
+}
+
+impl Stream for TcpStreamReceiver {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.done {
+            return Ok(None);
+        }
+
+        match self.receive() {
+            Ok(Frame::Component(component)) => Ok(Some(component)),
+            Ok(Frame::End) => {
+                self.done = true;
+                Ok(None)
+            }
+            Ok(Frame::Error(error)) => {
+                self.done = true;
+                Err(Error::ChannelError(format!("sender failed: {}", error)))
+            }
+            Err(error) => {
+                self.done = true;
+                Err(error)
+            }
+        }
+    }
+}
+
+impl PluginProvider for TcpStreamReceiver {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "TcpStreamReceiver",
+            "Receive a stream from a TCP stream sender",
+            Factory::new(
+                Declaration::default().typed_attr(
+                    "address",
+                    "Address to listen on, e.g. \"0.0.0.0:port\"",
+                    AttributeType::String,
+                ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let address = parameters.acquire_attribute("address")?;
+                    Ok(TcpStreamReceiver::bind(address.value.try_string()?)?.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::dev_util::load_example;
+    use crate::stream::observer::Handler;
+    use crate::stream::stats::{Statistics, StatsCollector};
+    use crate::stream::void::consume;
+    use crate::stream::AnyArtifact;
+
+    use super::*;
+
+    #[test]
+    fn test_tcp_stream() {
+        for codec in [Codec::Binary, Codec::Json].iter() {
+            let receiver = TcpStreamReceiver::bind("127.0.0.1:0").unwrap();
+            let address = receiver.local_addr().unwrap();
+            let codec = *codec;
+            let sender = thread::spawn(move || {
+                let mut sender = TcpStreamSender::connect(address, codec).unwrap();
+                sender.consume(&mut load_example(&["book", "L1.xes"]))
+            });
+
+            let mut observer = StatsCollector::default().into_observer(receiver);
+            let artifacts = consume(&mut observer).unwrap();
+            assert!(sender.join().unwrap().is_ok());
+
+            let statistics =
+                AnyArtifact::find::<Statistics>(&mut artifacts.iter().flatten()).unwrap();
+            assert_eq!(statistics.counts(), [6, 23, 23], "codec {}", codec);
+        }
+
+        // errors of the sending stream are passed on
+        let mut receiver = TcpStreamReceiver::bind("127.0.0.1:0").unwrap();
+        let address = receiver.local_addr().unwrap();
+        let sender = thread::spawn(move || {
+            let mut sender = TcpStreamSender::connect(address, Codec::Json).unwrap();
+            sender.on_error(Error::StreamError("broken".to_string()))
+        });
+        match receiver.next() {
+            Err(Error::ChannelError(message)) => assert!(message.contains("broken")),
+            other => panic!("expected channel error, got {:?}", other),
+        }
+        assert!(receiver.next().unwrap().is_none());
+        assert!(sender.join().unwrap().is_ok());
+
+        // so are connections that close early
+        let mut receiver = TcpStreamReceiver::bind("127.0.0.1:0").unwrap();
+        let address = receiver.local_addr().unwrap();
+        thread::spawn(move || TcpStreamSender::connect(address, Codec::Binary).map(drop))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(receiver.next(), Err(Error::ChannelError(_))));
+        assert_eq!("msgpack".parse::<Codec>().unwrap(), Codec::Binary);
+    }
+}
@@ -0,0 +1,357 @@
+//! Inject noise into event streams
+//!
+//! Discovery and conformance techniques are evaluated on how they cope with imperfect data. The
+//! `Noise` handler creates controlled noisy variants of clean logs: each event is removed,
+//! duplicated, swapped with its successor or has its attributes corrupted with a given
+//! probability.
+//!
+//! Corrupted attributes take another value observed for the same key earlier in the stream, or are
+//! removed if there's none. Standalone events can only be removed or corrupted. Seeded noise is
+//! reproducible.
+//!
+
+use std::collections::HashMap;
+
+use rand::{random, Rng};
+use rand_pcg::Pcg64;
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AttributeType, AttributeValue, Event, Stream, Trace};
+use crate::{Error, Result};
+
+// number of values per key that corrupted attributes are drawn from
+const POOL_SIZE: usize = 64;
+
+/// Randomly perturb events
+pub struct Noise {
+    remove: f64,
+    duplicate: f64,
+    swap: f64,
+    corrupt: f64,
+    keys: Vec<String>,
+    pool: HashMap<String, (usize, Vec<AttributeValue>)>,
+    rng: Pcg64,
+}
+
+fn checked(name: &str, probability: f64) -> Result<f64> {
+    if (0.0..=1.0).contains(&probability) {
+        Ok(probability)
+    } else {
+        Err(Error::AttributeError(format!(
+            "{} is expected to be within [0, 1], got {}",
+            name, probability
+        )))
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise {
+            remove: 0.0,
+            duplicate: 0.0,
+            swap: 0.0,
+            corrupt: 0.0,
+            keys: vec!["concept:name".to_string()],
+            pool: HashMap::new(),
+            rng: Pcg64::new(random(), 0),
+        }
+    }
+}
+
+impl Noise {
+    /// Probability of an event to be removed, fails if it's not within [0, 1]
+    pub fn remove(mut self, probability: f64) -> Result<Self> {
+        self.remove = checked("remove", probability)?;
+        Ok(self)
+    }
+
+    /// Probability of an event to occur twice in a row, fails if it's not within [0, 1]
+    pub fn duplicate(mut self, probability: f64) -> Result<Self> {
+        self.duplicate = checked("duplicate", probability)?;
+        Ok(self)
+    }
+
+    /// Probability of an event to be swapped with its successor, fails if it's not within [0, 1]
+    pub fn swap(mut self, probability: f64) -> Result<Self> {
+        self.swap = checked("swap", probability)?;
+        Ok(self)
+    }
+
+    /// Probability of an event to have its attributes corrupted, fails if it's not within [0, 1]
+    pub fn corrupt(mut self, probability: f64) -> Result<Self> {
+        self.corrupt = checked("corrupt", probability)?;
+        Ok(self)
+    }
+
+    /// Set the attributes subject to corruption, `concept:name` by default
+    pub fn keys<K: Into<String>, I: IntoIterator<Item = K>>(mut self, keys: I) -> Self {
+        self.keys = keys.into_iter().map(|k| k.into()).collect();
+        self
+    }
+
+    /// Make noise reproducible
+    pub fn random_state(mut self, random_state: u128) -> Self {
+        self.rng = Pcg64::new(random_state, 0);
+        self
+    }
+
+    fn happens(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.gen_bool(probability)
+    }
+
+    // reservoir sample of the values observed per key
+    fn observe(&mut self, event: &Event) {
+        for key in self.keys.iter() {
+            let value = match event.attributes.get_value(key) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let (seen, values) = self.pool.entry(key.clone()).or_default();
+            *seen += 1;
+            if values.len() < POOL_SIZE {
+                values.push(value.clone());
+            } else {
+                let i = self.rng.gen_range(0..*seen);
+                if i < POOL_SIZE {
+                    values[i] = value.clone();
+                }
+            }
+        }
+    }
+
+    fn perturb(&mut self, mut event: Event) -> Event {
+        self.observe(&event);
+        if !self.happens(self.corrupt) {
+            return event;
+        }
+
+        for key in self.keys.iter() {
+            let value = match event.attributes.get_value(key) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let candidates: Vec<&AttributeValue> = self
+                .pool
+                .get(key)
+                .map(|(_, values)| values.iter().filter(|v| *v != value).collect())
+                .unwrap_or_default();
+            if candidates.is_empty() {
+                event.attributes.remove(key);
+            } else {
+                let replacement = candidates[self.rng.gen_range(0..candidates.len())].clone();
+                *event.attributes.get_value_mut(key).unwrap() = replacement;
+            }
+        }
+
+        event
+    }
+}
+
+impl Handler for Noise {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        let mut events = Vec::with_capacity(trace.events.len());
+        for event in trace.events.drain(..) {
+            if self.happens(self.remove) {
+                continue;
+            }
+            if self.happens(self.duplicate) {
+                events.push(event.clone());
+            }
+            events.push(event);
+        }
+
+        // swapped pairs are skipped, such that events move by one position at most
+        let mut i = 0;
+        while i + 1 < events.len() {
+            if self.happens(self.swap) {
+                events.swap(i, i + 1);
+                i += 1;
+            }
+            i += 1;
+        }
+
+        trace.events = events.into_iter().map(|e| self.perturb(e)).collect();
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if in_trace {
+            return Ok(Some(event));
+        }
+
+        if self.happens(self.remove) {
+            Ok(None)
+        } else {
+            Ok(Some(self.perturb(event)))
+        }
+    }
+}
+
+impl PluginProvider for Noise {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Noise",
+            "Randomly remove, duplicate, swap or corrupt events",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to perturb")
+                    .default_attr("remove", "Probability of an event to be removed", |k| {
+                        (k, 0.0).into()
+                    })
+                    .default_attr(
+                        "duplicate",
+                        "Probability of an event to occur twice in a row",
+                        |k| (k, 0.0).into(),
+                    )
+                    .default_attr(
+                        "swap",
+                        "Probability of an event to be swapped with its successor",
+                        |k| (k, 0.0).into(),
+                    )
+                    .default_attr(
+                        "corrupt",
+                        "Probability of an event to have its attributes corrupted",
+                        |k| (k, 0.0).into(),
+                    )
+                    .optional_attr(
+                        "keys",
+                        "Attributes to corrupt, all string attributes if omitted",
+                        AttributeType::List,
+                    )
+                    .optional_attr("seed", "Seed of the random generator", AttributeType::Int),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut noise = Noise::default()
+                        .remove(parameters.acquire_attribute("remove")?.value.try_number()?)?
+                        .duplicate(
+                            parameters
+                                .acquire_attribute("duplicate")?
+                                .value
+                                .try_number()?,
+                        )?
+                        .swap(parameters.acquire_attribute("swap")?.value.try_number()?)?
+                        .corrupt(
+                            parameters
+                                .acquire_attribute("corrupt")?
+                                .value
+                                .try_number()?,
+                        )?;
+
+                    // optional list of attributes to corrupt and seed
+                    if let Ok(keys) = parameters.acquire_attribute("keys") {
+                        noise = noise.keys(
+                            keys.value
+                                .try_list()?
+                                .iter()
+                                .map(|a| Ok(a.value.try_string()?.to_string()))
+                                .collect::<Result<Vec<_>>>()?,
+                        );
+                    }
+                    if let Ok(seed) = parameters.acquire_attribute("seed") {
+                        noise = noise.random_state(*seed.value.try_int()? as u128);
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, noise)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::{Attribute, AttributeMap};
+
+    use super::*;
+
+    fn activities(noise: Noise) -> Vec<Vec<String>> {
+        noise
+            .into_observer(load_example(&["book", "L1.xes"]))
+            .traces()
+            .map(|t| {
+                Ok(t?
+                    .events
+                    .iter()
+                    .map(|e| e.name().unwrap_or("").to_string())
+                    .collect())
+            })
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_noise() {
+        let clean = activities(Noise::default());
+        let length = |log: &Vec<Vec<String>>| log.iter().map(|t| t.len()).sum::<usize>();
+        assert_eq!(length(&clean), 23);
+
+        assert_eq!(
+            length(&activities(Noise::default().remove(1.0).unwrap())),
+            0
+        );
+        let duplicated = activities(Noise::default().duplicate(1.0).unwrap());
+        assert_eq!(length(&duplicated), 46);
+        assert_eq!(duplicated[0][..4], ["a", "a", "e", "e"]);
+
+        // events are swapped pairwise
+        let swapped = activities(Noise::default().swap(1.0).unwrap());
+        for (s, c) in swapped.iter().zip(clean.iter()) {
+            assert_eq!((&s[0], &s[1]), (&c[1], &c[0]));
+            let (mut s, mut c) = (s.clone(), c.clone());
+            s.sort();
+            c.sort();
+            assert_eq!(s, c);
+        }
+
+        // corrupted activities are removed or replaced by other activities
+        let corrupted = activities(Noise::default().corrupt(1.0).unwrap().random_state(7));
+        assert_eq!(corrupted[0][0], "");
+        for (s, c) in corrupted
+            .iter()
+            .flatten()
+            .zip(clean.iter().flatten())
+            .skip(1)
+        {
+            assert_ne!(s, c);
+        }
+
+        // seeded noise is reproducible
+        let noisy = || {
+            Noise::default()
+                .remove(0.2)
+                .and_then(|n| n.duplicate(0.2))
+                .and_then(|n| n.swap(0.2))
+                .and_then(|n| n.corrupt(0.2))
+                .unwrap()
+                .random_state(42)
+        };
+        assert_eq!(activities(noisy()), activities(noisy()));
+        assert_ne!(activities(noisy()), clean);
+
+        assert!(matches!(
+            Noise::default().swap(1.5),
+            Err(Error::AttributeError(_))
+        ));
+
+        // integer probabilities are accepted by the plugin
+        let registry = crate::stream::plugin::REGISTRY.lock().unwrap();
+        let stream = registry
+            .get("Noise")
+            .unwrap()
+            .factory
+            .build_stream(
+                AttributeMap::from(vec![Attribute::new("remove", 1)].into_iter()),
+                &mut [],
+                vec![Box::new(load_example(&["book", "L1.xes"]))],
+                vec![],
+            )
+            .unwrap();
+        assert_eq!(stream.traces().flat_map(|t| t.unwrap().events).count(), 0);
+    }
+}
@@ -0,0 +1,519 @@
+//! Drive alerting from within a pipeline
+//!
+//! A `NotificationSink` evaluates alarms on the components of a stream and, optionally, on
+//! artifacts that are sent periodically by another pipe (e.g. snapshots of open cases). Whenever an
+//! alarm fires, a `Notification` is dispatched to all registered channels, such as the log, an
+//! external program or a webhook.
+//!
+
+use std::any::Any;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::outcome::Rule;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeContainer, AttributeType, Component, Sink};
+use crate::{Error, Result};
+
+/// Message that is sent when an alarm fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub alarm: String,
+    pub message: String,
+}
+
+/// All notifications that were dispatched by a sink
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Notifications {
+    pub notifications: Vec<Notification>,
+}
+
+#[typetag::serde]
+impl Artifact for Notifications {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Condition on stream components, returns a message if the alarm should fire
+pub type ComponentCondition = Box<dyn Fn(&Component) -> Result<Option<String>> + Send>;
+
+/// Condition on artifacts, returns a message if the alarm should fire
+pub type ArtifactCondition = Box<dyn Fn(&AnyArtifact) -> Result<Option<String>> + Send>;
+
+/// What an alarm is evaluated on
+pub enum AlarmCondition {
+    Component(ComponentCondition),
+    Artifact(ArtifactCondition),
+}
+
+impl From<Rule> for AlarmCondition {
+    /// Fire on each trace that satisfies the rule
+    fn from(rule: Rule) -> Self {
+        AlarmCondition::Component(Box::new(move |component| match component {
+            Component::Trace(trace) if rule.check(trace)? => {
+                Ok(Some(match trace.get_value("concept:name") {
+                    Some(name) => format!("trace {} matches {}", name.try_string()?, rule.label),
+                    None => format!("trace matches {}", rule.label),
+                }))
+            }
+            _ => Ok(None),
+        }))
+    }
+}
+
+/// Destination of notifications
+pub trait NotificationChannel: Send {
+    /// Deliver a notification
+    fn notify(&mut self, notification: &Notification) -> Result<()>;
+}
+
+/// Write notifications to the log
+pub struct LogChannel {
+    pub level: logging::Level,
+}
+
+impl Default for LogChannel {
+    fn default() -> Self {
+        Self {
+            level: logging::Level::Warn,
+        }
+    }
+}
+
+impl NotificationChannel for LogChannel {
+    fn notify(&mut self, notification: &Notification) -> Result<()> {
+        log!(
+            self.level,
+            "alarm {:?}: {}",
+            notification.alarm,
+            notification.message
+        );
+        Ok(())
+    }
+}
+
+/// Run an external program for each notification
+///
+/// The notification is passed by the environment variables `PROMI_ALARM` and `PROMI_MESSAGE`.
+///
+pub struct ExecChannel {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl NotificationChannel for ExecChannel {
+    fn notify(&mut self, notification: &Notification) -> Result<()> {
+        let status = Command::new(&self.program)
+            .args(&self.args)
+            .env("PROMI_ALARM", &notification.alarm)
+            .env("PROMI_MESSAGE", &notification.message)
+            .status()
+            .map_err(|e| Error::NotificationError(format!("{}: {}", self.program, e)))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::NotificationError(format!(
+                "{} exited with {}",
+                self.program, status
+            )))
+        }
+    }
+}
+
+/// Post notifications to a webhook
+///
+/// Only plain `http://` URLs are supported. The body is of type `text/plain` and has the form
+/// `<alarm>: <message>`.
+///
+pub struct WebhookChannel {
+    host: String,
+    port: u16,
+    path: String,
+    timeout: Duration,
+}
+
+impl WebhookChannel {
+    /// Create a new webhook channel from an URL like `http://localhost:8080/hook`
+    pub fn new(url: &str) -> Result<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            Error::NotificationError(format!("unsupported webhook url {:?}", url))
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(i) => (&authority[..i], authority[i + 1..].parse::<u16>()?),
+            None => (authority, 80),
+        };
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    /// Give up connecting, sending or awaiting the response after the given duration
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connect to the first address of the host that accepts within the timeout
+    fn connect(&self) -> std::io::Result<TcpStream> {
+        let mut last_error = None;
+        for address in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no address found for {:?}", self.host),
+            )
+        }))
+    }
+}
+
+impl NotificationChannel for WebhookChannel {
+    fn notify(&mut self, notification: &Notification) -> Result<()> {
+        let error = |e: std::io::Error| Error::NotificationError(format!("webhook: {}", e));
+        let body = format!("{}: {}", notification.alarm, notification.message);
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        );
+
+        let mut stream = self.connect().map_err(error)?;
+        stream.write_all(request.as_bytes()).map_err(error)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(error)?;
+
+        match response.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            status => Err(Error::NotificationError(format!(
+                "webhook responded with status {:?}",
+                status
+            ))),
+        }
+    }
+}
+
+/// Closure that is invoked for each notification
+pub type Callback = Box<dyn FnMut(&Notification) -> Result<()> + Send>;
+
+/// Invoke a closure for each notification
+pub struct CallbackChannel(pub Callback);
+
+impl NotificationChannel for CallbackChannel {
+    fn notify(&mut self, notification: &Notification) -> Result<()> {
+        (self.0)(notification)
+    }
+}
+
+/// Sink that evaluates alarms and dispatches notifications
+#[derive(Default)]
+pub struct NotificationSink {
+    alarms: Vec<(String, AlarmCondition)>,
+    channels: Vec<Box<dyn NotificationChannel>>,
+    artifacts: Option<Receiver<AnyArtifact>>,
+    notifications: Notifications,
+}
+
+impl NotificationSink {
+    /// Register an alarm
+    pub fn alarm<N: Into<String>, C: Into<AlarmCondition>>(
+        mut self,
+        name: N,
+        condition: C,
+    ) -> Self {
+        self.alarms.push((name.into(), condition.into()));
+        self
+    }
+
+    /// Register a channel notifications are dispatched to
+    pub fn channel<C: NotificationChannel + 'static>(mut self, channel: C) -> Self {
+        self.channels.push(Box::new(channel));
+        self
+    }
+
+    /// Receive artifacts to evaluate artifact conditions on
+    ///
+    /// The receiver is polled on each component and once the stream is closed.
+    ///
+    pub fn artifacts(mut self, receiver: Receiver<AnyArtifact>) -> Self {
+        self.artifacts = Some(receiver);
+        self
+    }
+
+    fn dispatch(&mut self, alarm: String, message: String) -> Result<()> {
+        let notification = Notification { alarm, message };
+
+        for channel in self.channels.iter_mut() {
+            channel.notify(&notification)?;
+        }
+
+        self.notifications.notifications.push(notification);
+        Ok(())
+    }
+
+    /// Evaluate artifact conditions on the given artifact
+    pub fn check_artifact(&mut self, artifact: &AnyArtifact) -> Result<()> {
+        let mut fired = Vec::new();
+
+        for (name, condition) in self.alarms.iter() {
+            if let AlarmCondition::Artifact(condition) = condition {
+                if let Some(message) = condition(artifact)? {
+                    fired.push((name.clone(), message));
+                }
+            }
+        }
+
+        for (name, message) in fired {
+            self.dispatch(name, message)?;
+        }
+
+        Ok(())
+    }
+
+    fn poll_artifacts(&mut self) -> Result<()> {
+        let artifacts: Vec<_> = match &self.artifacts {
+            Some(receiver) => receiver.try_iter().collect(),
+            None => return Ok(()),
+        };
+
+        for artifact in artifacts.iter() {
+            self.check_artifact(artifact)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for NotificationSink {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        let mut fired = Vec::new();
+
+        for (name, condition) in self.alarms.iter() {
+            if let AlarmCondition::Component(condition) = condition {
+                if let Some(message) = condition(&component)? {
+                    fired.push((name.clone(), message));
+                }
+            }
+        }
+
+        for (name, message) in fired {
+            self.dispatch(name, message)?;
+        }
+
+        self.poll_artifacts()
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.poll_artifacts()
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![std::mem::take(&mut self.notifications).into()])
+    }
+}
+
+impl PluginProvider for NotificationSink {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "NotificationSink",
+            "Dispatch notifications for traces that satisfy alarm rules",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr(
+                        "alarms",
+                        "List of rules, each keyed by the alarm's name",
+                        AttributeType::List,
+                    )
+                    .default_attr("log", "Log level of notifications", |k| (k, "warn").into())
+                    .optional_attr(
+                        "exec",
+                        "Program that is run per notification, with PROMI_ALARM and PROMI_MESSAGE set",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "webhook",
+                        "HTTP URL notifications are posted to",
+                        AttributeType::String,
+                    ),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let mut sink = NotificationSink::default();
+
+                    for alarm in parameters
+                        .acquire_attribute("alarms")?
+                        .value
+                        .try_list()?
+                        .iter()
+                    {
+                        let rule = Rule::try_from(alarm)?;
+                        sink = sink.alarm(rule.label.clone(), rule);
+                    }
+
+                    let level = parameters.acquire_attribute("log")?;
+                    let level = level.value.try_string()?;
+                    if level != "off" {
+                        sink = sink.channel(LogChannel {
+                            level: level.parse().map_err(|_| {
+                                Error::AttributeError(format!("invalid log level {:?}", level))
+                            })?,
+                        });
+                    }
+
+                    if let Ok(program) = parameters.acquire_attribute("exec") {
+                        sink = sink.channel(ExecChannel {
+                            program: program.value.try_string()?.to_string(),
+                            args: Vec::new(),
+                        });
+                    }
+
+                    if let Ok(url) = parameters.acquire_attribute("webhook") {
+                        sink = sink.channel(WebhookChannel::new(url.value.try_string()?)?);
+                    }
+
+                    Ok(sink.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::dev_util::load_example;
+    use crate::stream::channel::channel;
+    use crate::stream::monitor::OpenCasesSnapshot;
+    use crate::stream::outcome::Predicate;
+
+    use super::*;
+
+    #[test]
+    fn test_notification_sink() {
+        let (sender, receiver) = channel(None);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_ = received.clone();
+
+        let mut sink = NotificationSink::default()
+            .alarm(
+                "rejected",
+                Rule::new("rejected").predicate(Predicate::Contains("e".into())),
+            )
+            .alarm(
+                "backlog",
+                AlarmCondition::Artifact(Box::new(|artifact| {
+                    Ok(artifact
+                        .downcast_ref::<OpenCasesSnapshot>()
+                        .filter(|s| s.open.len() > 2)
+                        .map(|s| format!("{} open cases", s.open.len())))
+                })),
+            )
+            .channel(LogChannel::default())
+            .channel(CallbackChannel(Box::new(move |n| {
+                received_.lock().unwrap().push(n.alarm.clone());
+                Ok(())
+            })))
+            .artifacts(receiver);
+
+        let mut snapshot = OpenCasesSnapshot::default();
+        sender.send(snapshot.clone().into()).unwrap();
+        for case in 0..3 {
+            snapshot.open.insert(
+                case.to_string(),
+                crate::stream::monitor::CaseState {
+                    started: None,
+                    last_seen: None,
+                    events: 1,
+                },
+            );
+        }
+        sender.send(snapshot.into()).unwrap();
+
+        let artifacts = sink
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let notifications =
+            AnyArtifact::find::<Notifications>(&mut artifacts.iter().flatten()).unwrap();
+
+        assert_eq!(
+            notifications.notifications,
+            [
+                Notification {
+                    alarm: "backlog".into(),
+                    message: "3 open cases".into()
+                },
+                Notification {
+                    alarm: "rejected".into(),
+                    message: "trace Case3.0 matches rejected".into()
+                }
+            ]
+        );
+        assert_eq!(*received.lock().unwrap(), ["backlog", "rejected"]);
+    }
+
+    #[test]
+    fn test_webhook_url() {
+        let webhook = WebhookChannel::new("http://localhost:8080/hooks/promi").unwrap();
+        assert_eq!(webhook.host, "localhost");
+        assert_eq!(webhook.port, 8080);
+        assert_eq!(webhook.path, "/hooks/promi");
+
+        let webhook = WebhookChannel::new("http://example.com").unwrap();
+        assert_eq!(webhook.port, 80);
+        assert_eq!(webhook.path, "/");
+
+        assert!(WebhookChannel::new("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_webhook_timeout() {
+        // the server accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let mut webhook = WebhookChannel::new(&url)
+            .unwrap()
+            .timeout(Duration::from_millis(50));
+
+        let notification = Notification {
+            alarm: "backlog".into(),
+            message: "too many open cases".into(),
+        };
+        assert!(matches!(
+            webhook.notify(&notification),
+            Err(Error::NotificationError(_))
+        ));
+    }
+}
@@ -1,7 +1,10 @@
 //! A stateful observer that allows for registering callbacks to handle stream components
 
-use crate::error::{Error, Result};
-use crate::stream::{AnyArtifact, Component, ComponentType, Event, Meta, ResOpt, Stream, Trace};
+use crate::error::Result;
+use crate::stream::order::OrderState;
+use crate::stream::{
+    AnyArtifact, Component, ComponentType, Event, Flavor, Meta, ResOpt, Stream, Trace,
+};
 
 /// Gets registered with an observer while providing callbacks
 ///
@@ -36,6 +39,16 @@ pub trait Handler: Send {
         Ok(Some(event))
     }
 
+    /// Handle an event of an unfolded trace
+    ///
+    /// Invoked on each event that follows a trace in a stream of `Flavor::Unfolded`, i.e. on the
+    /// events of the trace last passed to `on_trace` without events. By default, such an event is
+    /// handled like a standalone event. Handlers that group events by trace override this.
+    ///
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        self.on_event(event, false)
+    }
+
     /// Release artifacts of handler
     ///
     /// A handler may aggregate data over an event stream that is released by calling this method.
@@ -58,13 +71,25 @@ pub trait Handler: Send {
 ///
 /// An observer preserves a state with copies of meta data components. It manages an arbitrary
 /// number of registered handlers and invokes their callbacks. Further, it checks if components of
-/// the stream occur in a valid order.
+/// the stream occur in a valid order, see `OrderGuard`.
+///
+/// Handlers are invoked in descending order of their priority, handlers of equal priority in the
+/// order they were registered. `register` assigns priority zero, so a handler that others depend
+/// on, e.g. one that repairs events before they are validated, is registered with a higher
+/// priority. A handler that filters a component hides it from all handlers invoked later.
+///
+/// In a stream of `Flavor::Unfolded`, the events following a trace belong to that trace. They are
+/// passed to `on_unfolded_event` and are dropped along with the trace if a handler filters it.
 ///
 #[derive(Debug, Clone)]
 pub struct Observer<I: Stream, H: Handler> {
     stream: I,
-    state: ComponentType,
+    state: OrderState,
+    flavor: Flavor,
+    /// Whether the open trace of an unfolded stream passed all handlers, if a trace is open
+    open_trace: Option<bool>,
     handler: Vec<H>,
+    priorities: Vec<i32>,
 }
 
 impl<'a, I: Stream, H: Handler> Observer<I, H> {
@@ -72,38 +97,41 @@ impl<'a, I: Stream, H: Handler> Observer<I, H> {
     pub fn new(stream: I) -> Self {
         Observer {
             stream,
-            state: ComponentType::Meta,
+            state: OrderState::default(),
+            flavor: Flavor::Log,
+            open_trace: None,
             handler: Vec::new(),
+            priorities: Vec::new(),
         }
     }
 
-    /// Register a new handler
+    /// Register a new handler with priority zero
     pub fn register(&'a mut self, handler: H) {
-        self.handler.push(handler)
+        self.register_with_priority(handler, 0)
     }
 
-    /// Release handler (reverse registering order)
-    pub fn release(&mut self) -> Option<H> {
-        self.handler.pop()
+    /// Register a new handler that is invoked before all handlers of lower priority
+    pub fn register_with_priority(&'a mut self, handler: H, priority: i32) {
+        let index = self
+            .priorities
+            .iter()
+            .position(|p| *p < priority)
+            .unwrap_or(self.priorities.len());
+        self.handler.insert(index, handler);
+        self.priorities.insert(index, priority);
     }
 
-    fn update_state(&mut self, state: ComponentType) -> Result<()> {
-        if self.state > state {
-            Err(Error::StateError(format!(
-                "invalid transition: {:?} --> {:?}",
-                self.state, state
-            )))
-        } else {
-            self.state = state;
-            Ok(())
-        }
+    /// Release handler (reverse invocation order)
+    pub fn release(&mut self) -> Option<H> {
+        self.priorities.pop();
+        self.handler.pop()
     }
 
     fn on_component(&mut self, component: Component) -> ResOpt {
         let component_ = match component {
             Component::Meta(meta) => {
-                // Since there's only one meta component allowed, we can directly jump to trace state
-                self.update_state(ComponentType::Trace)?;
+                self.state.transition_meta(&meta)?;
+                self.flavor = meta.flavor;
 
                 // call all the handlers
                 let mut meta = meta;
@@ -114,16 +142,22 @@ impl<'a, I: Stream, H: Handler> Observer<I, H> {
                 Component::Meta(meta)
             }
             Component::Trace(trace) => {
-                self.update_state(ComponentType::Trace)?;
+                self.state.transition(ComponentType::Trace)?;
 
                 // apply all handlers on trace
                 let mut trace = trace;
+                if self.flavor == Flavor::Unfolded {
+                    self.open_trace = Some(false);
+                }
                 for handler in self.handler.iter_mut() {
                     trace = match handler.on_trace(trace)? {
                         Some(trace) => trace,
                         None => return Ok(None),
                     };
                 }
+                if self.flavor == Flavor::Unfolded {
+                    self.open_trace = Some(true);
+                }
 
                 // apply all handlers on events within trace
                 let mut events: Vec<Event> = Vec::new();
@@ -146,12 +180,24 @@ impl<'a, I: Stream, H: Handler> Observer<I, H> {
                 Component::Trace(trace)
             }
             Component::Event(event) => {
-                self.update_state(ComponentType::Event)?;
+                self.state.transition(ComponentType::Event)?;
+
+                // events of a filtered trace are dropped as well
+                let unfolded = match self.open_trace {
+                    Some(false) => return Ok(None),
+                    Some(true) => true,
+                    None => false,
+                };
 
                 // apply all handlers on the event
                 let mut event = event;
                 for handler in self.handler.iter_mut() {
-                    event = match handler.on_event(event, false)? {
+                    let handled = if unfolded {
+                        handler.on_unfolded_event(event)?
+                    } else {
+                        handler.on_event(event, false)?
+                    };
+                    event = match handled {
                         Some(event) => event,
                         None => return Ok(None),
                     };
@@ -222,7 +268,9 @@ impl<I: Stream, H: Handler> Stream for Observer<I, H> {
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
 
+    use crate::dev_util::load_example;
     use crate::stream::{void::consume, xes::XesReader};
 
     use super::*;
@@ -252,6 +300,10 @@ mod tests {
             }
         }
 
+        fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+            self.on_event(event, true)
+        }
+
         fn on_event(&mut self, event: Event, _in_trace: bool) -> Result<Option<Event>> {
             self.ct_event += 1;
 
@@ -324,6 +376,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_observer_unfolded() {
+        let reader = XesReader::from(join_static_reader!("xes", "book", "L1.xes"));
+        let mut observer = Observer::new(reader.streaming_events());
+        observer.register(TestHandler::new(true));
+        observer.register(TestHandler::new(false));
+        consume(&mut observer).unwrap();
+
+        // events of filtered traces are dropped along with their trace
+        assert_eq!(observer.release().unwrap().counts(), [1, 3, 6, 6]);
+    }
+
     #[test]
     fn test_observer_filtering() {
         let param = vec![
@@ -347,6 +411,38 @@ mod tests {
         }
     }
 
+    struct OrderHandler {
+        name: &'static str,
+        invoked: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Handler for OrderHandler {
+        fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+            self.invoked.lock().unwrap().push(self.name);
+            Ok(meta)
+        }
+    }
+
+    #[test]
+    fn test_observer_priorities() {
+        let invoked = Arc::new(Mutex::new(Vec::new()));
+        let handler = |name| OrderHandler {
+            name,
+            invoked: invoked.clone(),
+        };
+
+        let mut observer = Observer::new(load_example(&["book", "L1.xes"]));
+        observer.register(handler("c"));
+        observer.register_with_priority(handler("a"), 10);
+        observer.register_with_priority(handler("d"), -5);
+        observer.register_with_priority(handler("b"), 10);
+        consume(&mut observer).unwrap();
+
+        assert_eq!(*invoked.lock().unwrap(), ["a", "b", "c", "d"]);
+        assert_eq!(observer.release().unwrap().name, "d");
+        assert_eq!(observer.release().unwrap().name, "c");
+    }
+
     #[test]
     fn test_observer_order_validation() {
         let paths = vec![
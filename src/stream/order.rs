@@ -0,0 +1,289 @@
+//! Validation of the order of stream components
+//!
+//! By default, a stream starts with at most one meta component, followed by traces, followed by
+//! standalone events. The `OrderGuard` enforces these transitions on any stream, e.g. directly
+//! after a network receiver, and fails with a `StateError` on the first violation. Streams that
+//! don't follow the XES layout, e.g. event-only streams, are checked by adjusting the
+//! `OrderRules`. Meta data of `Flavor::Stream` rules out traces regardless of the configured
+//! rules, meta data of `Flavor::Unfolded` allows traces to follow events. The `Observer` applies
+//! the same state machine with default rules.
+//!
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, AttributeContainer, Component, ComponentType, Flavor, Meta, ResOpt, Stream,
+};
+use crate::{Error, Result};
+
+/// Transition rules for stream components
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRules {
+    /// Whether the stream has to start with a meta component
+    pub require_meta: bool,
+    /// Whether traces may occur at all
+    pub traces: bool,
+    /// Whether traces may follow standalone events
+    pub interleaved: bool,
+}
+
+impl Default for OrderRules {
+    fn default() -> Self {
+        OrderRules {
+            require_meta: false,
+            traces: true,
+            interleaved: false,
+        }
+    }
+}
+
+impl OrderRules {
+    /// Rules for streams of standalone events, optionally preceded by meta data
+    pub fn events_only() -> Self {
+        OrderRules {
+            traces: false,
+            ..OrderRules::default()
+        }
+    }
+}
+
+/// State machine that checks the transitions between stream components
+#[derive(Debug, Clone)]
+pub struct OrderState {
+    rules: OrderRules,
+    state: Option<ComponentType>,
+}
+
+impl Default for OrderState {
+    fn default() -> Self {
+        Self::new(OrderRules::default())
+    }
+}
+
+impl OrderState {
+    pub fn new(rules: OrderRules) -> Self {
+        OrderState { rules, state: None }
+    }
+
+    /// Type of the last component passed, if any
+    pub fn state(&self) -> Option<&ComponentType> {
+        self.state.as_ref()
+    }
+
+    /// Check the transition to the next component and update the state
+    pub fn transition(&mut self, next: ComponentType) -> Result<()> {
+        let valid = match (&self.state, &next) {
+            (None, ComponentType::Meta) => true,
+            (Some(_), ComponentType::Meta) => false,
+            (None, _) if self.rules.require_meta => {
+                return Err(Error::StateError(format!(
+                    "invalid transition: stream starts with {:?} instead of meta data",
+                    next
+                )))
+            }
+            (_, ComponentType::Trace) if !self.rules.traces => {
+                return Err(Error::StateError(
+                    "invalid transition: unexpected trace in event-only stream".to_string(),
+                ))
+            }
+            (Some(ComponentType::Event), ComponentType::Trace) => self.rules.interleaved,
+            _ => true,
+        };
+
+        if !valid {
+            return Err(Error::StateError(format!(
+                "invalid transition: {:?} --> {:?}",
+                self.state.as_ref().unwrap(),
+                next
+            )));
+        }
+
+        self.state = Some(next);
+        Ok(())
+    }
+
+    /// Check the transition to meta data, which may restrict the stream to standalone events or
+    /// allow unfolded traces
+    pub fn transition_meta(&mut self, meta: &Meta) -> Result<()> {
+        self.transition(ComponentType::Meta)?;
+        match meta.flavor {
+            Flavor::Log => (),
+            Flavor::Stream => self.rules.traces = false,
+            Flavor::Unfolded => self.rules.interleaved = true,
+        }
+        Ok(())
+    }
+}
+
+/// Fails on components that occur in invalid order
+pub struct OrderGuard<T: Stream> {
+    stream: T,
+    state: OrderState,
+}
+
+impl<T: Stream> OrderGuard<T> {
+    /// Guard the stream with default rules
+    pub fn new(stream: T) -> Self {
+        OrderGuard {
+            stream,
+            state: OrderState::default(),
+        }
+    }
+
+    pub fn rules(mut self, rules: OrderRules) -> Self {
+        self.state = OrderState::new(rules);
+        self
+    }
+}
+
+impl<T: Stream> Stream for OrderGuard<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        let component = match self.stream.next()? {
+            Some(component) => component,
+            None => return Ok(None),
+        };
+
+        match &component {
+            Component::Meta(meta) => self.state.transition_meta(meta)?,
+            other => self.state.transition(other.hint())?,
+        }
+        Ok(Some(component))
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![])
+    }
+}
+
+impl PluginProvider for OrderGuard<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "OrderGuard",
+            "Fail on stream components that occur in invalid order",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be checked")
+                    .default_attr(
+                        "require_meta",
+                        "Whether the stream has to start with meta data",
+                        |k| (k, false).into(),
+                    )
+                    .default_attr(
+                        "events_only",
+                        "Whether the stream must not contain traces",
+                        |k| (k, false).into(),
+                    )
+                    .default_attr(
+                        "interleaved",
+                        "Whether traces may follow standalone events",
+                        |k| (k, false).into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut flag = |key: &str| -> Result<bool> {
+                        Ok(*parameters.acquire_attribute(key)?.value.try_boolean()?)
+                    };
+                    let rules = OrderRules {
+                        require_meta: flag("require_meta")?,
+                        traces: !flag("events_only")?,
+                        interleaved: flag("interleaved")?,
+                    };
+
+                    Ok(OrderGuard::new(parameters.acquire_stream("inner")?)
+                        .rules(rules)
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Event, Meta, Trace};
+
+    use super::*;
+
+    fn buffer(components: &[ComponentType]) -> Buffer {
+        let mut buffer = Buffer::default();
+        for component in components {
+            buffer.push(Ok(Some(match component {
+                ComponentType::Meta => Component::Meta(Meta::default()),
+                ComponentType::Trace => Component::Trace(Trace::default()),
+                ComponentType::Event => Component::Event(Event::default()),
+            })));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_order_guard() {
+        use ComponentType::*;
+
+        let param = vec![
+            (vec![Meta, Trace, Trace, Event], OrderRules::default(), true),
+            (vec![Trace, Event], OrderRules::default(), true),
+            (vec![Meta, Event, Trace], OrderRules::default(), false),
+            (vec![Meta, Trace, Meta], OrderRules::default(), false),
+            (vec![Meta, Meta], OrderRules::default(), false),
+            (vec![Event, Trace, Event], OrderRules::default(), false),
+            (
+                vec![Meta, Event, Trace, Event],
+                OrderRules {
+                    interleaved: true,
+                    ..OrderRules::default()
+                },
+                true,
+            ),
+            (vec![Meta, Event, Event], OrderRules::events_only(), true),
+            (vec![Meta, Trace], OrderRules::events_only(), false),
+            (
+                vec![Event],
+                OrderRules {
+                    require_meta: true,
+                    ..OrderRules::default()
+                },
+                false,
+            ),
+        ];
+
+        for (components, rules, valid) in param {
+            let mut guard = OrderGuard::new(buffer(&components)).rules(rules.clone());
+            match consume(&mut guard) {
+                Ok(_) => assert!(valid, "expected error on {:?} / {:?}", components, rules),
+                Err(Error::StateError(_)) => {
+                    assert!(!valid, "unexpected error on {:?} / {:?}", components, rules)
+                }
+                Err(error) => panic!("expected state error, got {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_order_guard_flavor() {
+        let meta = Meta {
+            flavor: Flavor::Stream,
+            ..Meta::default()
+        };
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(meta))));
+        buffer.push(Ok(Some(Component::Trace(Trace::default()))));
+
+        match consume(&mut OrderGuard::new(buffer)) {
+            Err(Error::StateError(_)) => (),
+            other => panic!("expected state error, got {:?}", other),
+        }
+    }
+}
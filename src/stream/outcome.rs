@@ -0,0 +1,297 @@
+//! Derive case level outcomes from traces
+//!
+//! Outcome oriented analyses require each case to be labeled, e.g. as _rejected_ if a certain
+//! activity was executed or as _late_ if it took too long. The `Labeler` evaluates an ordered list
+//! of rules on each trace and stamps the label of the first matching rule as trace attribute.
+//!
+//! Rules can either be deserialized (e.g. from YAML) or be derived from attributes. In the latter
+//! case, an attribute's key is used as label while its children make up the predicates:
+//! - `contains` (string): the trace contains an event with the given `concept:name`
+//! - `longer_than` (int): the trace spans more than the given number of seconds
+//! - any other key: the trace has an attribute of equal key and value
+//!
+
+use std::convert::TryFrom;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::extension::{Extension, Time};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{Attribute, AttributeContainer, AttributeType, AttributeValue, Stream, Trace};
+use crate::Result;
+
+/// A single condition a trace is checked against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Predicate {
+    /// Trace contains an event with the given activity (`concept:name`)
+    Contains(String),
+    /// Trace spans more than the given number of seconds
+    LongerThan(i64),
+    /// Trace has an attribute with the given key and value
+    Equals(String, AttributeValue),
+}
+
+impl Predicate {
+    /// Check whether the trace satisfies the predicate
+    pub fn check(&self, trace: &Trace) -> Result<bool> {
+        Ok(match self {
+            Predicate::Contains(activity) => {
+                for event in trace.events.iter() {
+                    if let Some(name) = event.get_value("concept:name") {
+                        if name.try_string()? == activity {
+                            return Ok(true);
+                        }
+                    }
+                }
+                false
+            }
+            Predicate::LongerThan(seconds) => match Time::view(trace) {
+                Ok(time) => {
+                    let (start, end) = time.time.interval();
+                    end.signed_duration_since(*start) > Duration::seconds(*seconds)
+                }
+                Err(_) => false,
+            },
+            Predicate::Equals(key, value) => trace.get_value(key) == Some(value),
+        })
+    }
+}
+
+/// A label that applies iff all predicates hold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub label: String,
+    pub predicates: Vec<Predicate>,
+}
+
+impl Rule {
+    /// Create a new rule without any predicates
+    pub fn new<L: Into<String>>(label: L) -> Self {
+        Self {
+            label: label.into(),
+            predicates: Vec::new(),
+        }
+    }
+
+    /// Add a predicate to the rule
+    pub fn predicate(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Check whether all predicates hold for the given trace
+    pub fn check(&self, trace: &Trace) -> Result<bool> {
+        for predicate in self.predicates.iter() {
+            if !predicate.check(trace)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl TryFrom<&Attribute> for Rule {
+    type Error = crate::Error;
+
+    fn try_from(attribute: &Attribute) -> Result<Self> {
+        let mut rule = Rule::new(attribute.key.as_str());
+
+        for child in attribute.children.iter() {
+            rule = rule.predicate(match child.key.as_str() {
+                "contains" => Predicate::Contains(child.value.try_string()?.to_string()),
+                "longer_than" => Predicate::LongerThan(*child.value.try_int()?),
+                other => Predicate::Equals(other.to_string(), child.value.clone()),
+            });
+        }
+
+        Ok(rule)
+    }
+}
+
+/// Stamps an outcome label on each trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Labeler {
+    pub key: String,
+    pub rules: Vec<Rule>,
+    pub default: Option<String>,
+}
+
+impl Default for Labeler {
+    fn default() -> Self {
+        Self {
+            key: "outcome".to_string(),
+            rules: Vec::new(),
+            default: None,
+        }
+    }
+}
+
+impl Labeler {
+    /// Add a rule, rules are evaluated in order of registration
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Find the label of the first rule the trace satisfies
+    pub fn label(&self, trace: &Trace) -> Result<Option<&str>> {
+        for rule in self.rules.iter() {
+            if rule.check(trace)? {
+                return Ok(Some(rule.label.as_str()));
+            }
+        }
+
+        Ok(self.default.as_deref())
+    }
+}
+
+impl Handler for Labeler {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        if let Some(label) = self.label(&trace)? {
+            let attribute = Attribute::new(self.key.as_str(), label);
+            trace.attributes.insert(attribute);
+        }
+
+        Ok(Some(trace))
+    }
+}
+
+impl PluginProvider for Labeler {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "OutcomeLabeler",
+            "Derive a case level outcome attribute from rules",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be labeled")
+                    .typed_attr(
+                        "rules",
+                        "List of rules, each keyed by its label",
+                        AttributeType::List,
+                    )
+                    .default_attr("key", "Key of the outcome attribute", |k| {
+                        (k, "outcome").into()
+                    })
+                    .optional_attr(
+                        "default",
+                        "Label of traces that match no rule",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let rules = parameters
+                        .acquire_attribute("rules")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(Rule::try_from)
+                        .collect::<Result<_>>()?;
+
+                    let labeler = Labeler {
+                        key: parameters
+                            .acquire_attribute("key")?
+                            .value
+                            .try_string()?
+                            .to_string(),
+                        rules,
+                        default: match parameters.acquire_attribute("default") {
+                            Ok(default) => Some(default.value.try_string()?.to_string()),
+                            Err(_) => None,
+                        },
+                    };
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, labeler)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeMap, Sink};
+
+    use super::*;
+
+    fn labels(log: &Log) -> Vec<Option<&str>> {
+        log.traces
+            .iter()
+            .map(|t| t.get_value("outcome").map(|v| v.try_string().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_labeler() {
+        let labeler = Labeler::default()
+            .rule(Rule::new("rejected").predicate(Predicate::Contains("e".into())))
+            .rule(Rule::new("late").predicate(Predicate::LongerThan(150)));
+
+        let mut log = Log::default();
+        log.consume(&mut labeler.into_observer(load_example(&["book", "L1.xes"])))
+            .unwrap();
+
+        assert_eq!(
+            labels(&log),
+            [
+                Some("rejected"),
+                Some("late"),
+                Some("late"),
+                Some("late"),
+                Some("late"),
+                Some("late")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labeler_plugin() {
+        let rules = Attribute::new(
+            "rules",
+            vec![
+                Attribute::with_children(
+                    "first",
+                    "",
+                    vec![Attribute::new("concept:name", "Case1.0")],
+                ),
+                Attribute::with_children("long", "", vec![Attribute::new("longer_than", 180)]),
+            ],
+        );
+        let attributes =
+            AttributeMap::from(vec![rules, Attribute::new("default", "ok")].into_iter());
+
+        let registry = crate::stream::plugin::REGISTRY.lock().unwrap();
+        let mut stream = registry
+            .get("OutcomeLabeler")
+            .unwrap()
+            .factory
+            .build_stream(
+                attributes,
+                &mut [],
+                vec![Box::new(load_example(&["book", "L1.xes"]))],
+                vec![],
+            )
+            .unwrap();
+
+        let mut log = Log::default();
+        log.consume(&mut stream).unwrap();
+
+        assert_eq!(
+            labels(&log),
+            [
+                Some("ok"),
+                Some("ok"),
+                Some("ok"),
+                Some("ok"),
+                Some("first"),
+                Some("ok")
+            ]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Tuning of the files written by file based sinks
+//!
+//! The defaults of the standard library, i.e. a small write buffer and leaving it to the operating
+//! system when data hits the disk, are suboptimal for multi-GB outputs, especially on network file
+//! systems. `FileOptions` configure
+//! - the size of the write buffer,
+//! - when data is synchronized to disk (`SyncPolicy`) and
+//! - whether the file is written through a memory map instead of write calls.
+//!
+//! Memory mapped files grow in chunks and are truncated to the written size whenever they are
+//! flushed or dropped.
+//!
+//! File writing plugins accept the optional attributes `buffer_kilobytes`, `fsync` (`never` or
+//! `flush`), `fsync_megabytes` and `mmap` to set these options.
+//!
+
+use std::cmp;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::stream::plugin::{Declaration, Parameters};
+use crate::stream::AttributeType;
+use crate::{Error, Result};
+
+/// Size by which memory mapped files grow
+const MAP_CHUNK: u64 = 64 << 20;
+
+/// When written data is synchronized to disk
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SyncPolicy {
+    /// Leave it to the operating system
+    #[default]
+    Never,
+    /// Whenever the sink flushes its writer and when the file is dropped
+    Flush,
+    /// Whenever the given number of bytes was written since the last synchronization
+    Bytes(u64),
+}
+
+impl FromStr for SyncPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "never" | "none" => Ok(SyncPolicy::Never),
+            "flush" | "close" => Ok(SyncPolicy::Flush),
+            other => Err(Error::AttributeError(format!(
+                "unknown fsync policy: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Options of files written by sinks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileOptions {
+    pub buffer_size: usize,
+    pub sync: SyncPolicy,
+    pub mmap: bool,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        FileOptions {
+            buffer_size: 8 * 1024,
+            sync: SyncPolicy::Never,
+            mmap: false,
+        }
+    }
+}
+
+impl FileOptions {
+    /// Size of the write buffer in bytes, ignored by memory mapped files
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    pub fn sync(mut self, sync: SyncPolicy) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Whether to write through a memory map
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Declare the optional plugin attributes `buffer_kilobytes`, `fsync`, `fsync_megabytes` and
+    /// `mmap`
+    pub fn declare(declaration: Declaration) -> Declaration {
+        declaration
+            .optional_attr(
+                "buffer_kilobytes",
+                "Size of the write buffer",
+                AttributeType::Int,
+            )
+            .optional_attr(
+                "fsync",
+                "When to sync to disk (never or flush)",
+                AttributeType::String,
+            )
+            .optional_attr(
+                "fsync_megabytes",
+                "Sync to disk after this many megabytes were written",
+                AttributeType::Float,
+            )
+            .optional_attr(
+                "mmap",
+                "Write through a memory mapping",
+                AttributeType::Boolean,
+            )
+    }
+
+    /// Read the optional plugin attributes `buffer_kilobytes`, `fsync`, `fsync_megabytes` and
+    /// `mmap`
+    pub fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        let mut options = FileOptions::default();
+
+        if let Ok(attribute) = parameters.acquire_attribute("buffer_kilobytes") {
+            let kilobytes = *attribute.value.try_int()?;
+            if kilobytes < 1 {
+                return Err(Error::AttributeError(format!(
+                    "buffer_kilobytes is expected to be positive, got {}",
+                    kilobytes
+                )));
+            }
+            options = options.buffer_size(kilobytes as usize * 1024);
+        }
+
+        if let Ok(attribute) = parameters.acquire_attribute("fsync") {
+            options = options.sync(attribute.value.try_string()?.parse()?);
+        }
+
+        if let Ok(attribute) = parameters.acquire_attribute("fsync_megabytes") {
+            let megabytes = *attribute.value.try_float()?;
+            if megabytes <= 0.0 {
+                return Err(Error::AttributeError(format!(
+                    "fsync_megabytes is expected to be positive, got {}",
+                    megabytes
+                )));
+            }
+            options = options.sync(SyncPolicy::Bytes((megabytes * 1_000_000.0) as u64));
+        }
+
+        if let Ok(attribute) = parameters.acquire_attribute("mmap") {
+            options = options.mmap(*attribute.value.try_boolean()?);
+        }
+
+        Ok(options)
+    }
+
+    /// Create or truncate the file at the given path
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> Result<OutputFile> {
+        let target = if self.mmap {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?;
+            Target::Mapped(MappedFile::new(file))
+        } else {
+            Target::Buffered(BufWriter::with_capacity(
+                self.buffer_size,
+                File::create(path)?,
+            ))
+        };
+
+        Ok(OutputFile {
+            target,
+            sync: self.sync,
+            unsynced: 0,
+        })
+    }
+}
+
+/// File that grows through a sequence of memory mapped windows
+struct MappedFile {
+    file: File,
+    map: Option<MmapMut>,
+    /// Offset of the current window
+    offset: u64,
+    /// Position within the current window
+    position: usize,
+}
+
+impl MappedFile {
+    /// Wrap the file, the first window is mapped on the first write
+    fn new(file: File) -> Self {
+        MappedFile {
+            file,
+            map: None,
+            offset: 0,
+            position: 0,
+        }
+    }
+
+    fn map_window(&mut self) -> io::Result<()> {
+        self.file.set_len(self.offset + MAP_CHUNK)?;
+        // the file is owned exclusively, hence nobody truncates it while it's mapped
+        let map = unsafe {
+            MmapOptions::new()
+                .offset(self.offset)
+                .len(MAP_CHUNK as usize)
+                .map_mut(&self.file)?
+        };
+        self.map = Some(map);
+        Ok(())
+    }
+
+    /// Unmap the current window and truncate the file to the written size
+    fn truncate(&mut self) -> io::Result<()> {
+        if self.map.take().is_some() {
+            self.file.set_len(self.offset + self.position as u64)?;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        match &self.map {
+            Some(map) => map.flush(),
+            None => self.file.sync_data(),
+        }
+    }
+}
+
+impl Write for MappedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.position == MAP_CHUNK as usize {
+            self.truncate()?;
+            self.offset += MAP_CHUNK;
+            self.position = 0;
+        }
+        if self.map.is_none() {
+            self.map_window()?;
+        }
+
+        let map = self.map.as_mut().expect("window is mapped");
+        let written = cmp::min(buf.len(), map.len() - self.position);
+        map[self.position..self.position + written].copy_from_slice(&buf[..written]);
+        self.position += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.truncate()
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        let _ = self.truncate();
+    }
+}
+
+enum Target {
+    Buffered(BufWriter<File>),
+    Mapped(MappedFile),
+}
+
+/// File created according to `FileOptions`
+pub struct OutputFile {
+    target: Target,
+    sync: SyncPolicy,
+    unsynced: u64,
+}
+
+impl OutputFile {
+    /// Synchronize all data written so far to disk
+    pub fn sync_data(&mut self) -> io::Result<()> {
+        self.unsynced = 0;
+        match &mut self.target {
+            Target::Buffered(writer) => {
+                writer.flush()?;
+                writer.get_ref().sync_data()
+            }
+            Target::Mapped(mapped) => mapped.sync(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match &mut self.target {
+            Target::Buffered(writer) => writer.write(buf)?,
+            Target::Mapped(mapped) => mapped.write(buf)?,
+        };
+
+        self.unsynced += written as u64;
+        if let SyncPolicy::Bytes(bytes) = self.sync {
+            if self.unsynced >= bytes {
+                self.sync_data()?;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            Target::Buffered(writer) => writer.flush()?,
+            Target::Mapped(mapped) => mapped.flush()?,
+        }
+
+        if self.sync == SyncPolicy::Flush {
+            self.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if self.sync == SyncPolicy::Flush {
+            let _ = self.sync_data();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_output_file() {
+        let path = std::env::temp_dir().join(format!("promi_output_{}.txt", std::process::id()));
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        for options in [
+            FileOptions::default().buffer_size(1024),
+            FileOptions::default().sync(SyncPolicy::Flush),
+            FileOptions::default().sync(SyncPolicy::Bytes(10_000)),
+            FileOptions::default().mmap(true),
+            FileOptions::default().mmap(true).sync(SyncPolicy::Flush),
+        ]
+        .iter()
+        {
+            let mut file = options.create(&path).unwrap();
+            for chunk in content.chunks(777) {
+                file.write_all(chunk).unwrap();
+            }
+            file.flush().unwrap();
+            assert_eq!(fs::read(&path).unwrap(), content, "{:?}", options);
+            file.write_all(b"!").unwrap();
+            drop(file);
+
+            assert_eq!(fs::read(&path).unwrap().len(), content.len() + 1);
+        }
+
+        // windows of memory mapped files are remapped once full
+        let mut mapped = FileOptions::default().mmap(true).create(&path).unwrap();
+        let block = vec![7u8; MAP_CHUNK as usize / 2 + 1];
+        for _ in 0..3 {
+            mapped.write_all(&block).unwrap();
+        }
+        drop(mapped);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 3 * block.len() as u64);
+
+        assert_eq!("flush".parse::<SyncPolicy>().unwrap(), SyncPolicy::Flush);
+        assert!("sometimes".parse::<SyncPolicy>().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
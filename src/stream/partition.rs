@@ -0,0 +1,394 @@
+//! Write event streams into files per calendar period
+//!
+//! Archival pipelines usually store logs per month, week or day. The `PartitionedXesWriter` sink
+//! assigns each trace to the period of its start, i.e. its earliest `time:timestamp`, and each
+//! standalone event to the period of its own timestamp. Components are written into XES files whose
+//! path is given by a template, in which `{period}` is replaced by e.g. `2021-03` (month),
+//! `2021-W09` (ISO week) or `2021-03-04` (day). Components without timestamp go into the `undated`
+//! partition. Each file starts with the meta data of the stream.
+//!
+//! Periods are determined in the offset of the respective timestamp. Files are kept open until the
+//! stream ends, but at most `max_open` at once: the least recently written file is closed and
+//! reopened in append mode once it receives another component. The number of components written
+//! per file is released as `Partitions` artifact.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::xes::XesWriter;
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, Component, Meta, Sink,
+};
+use crate::{DateTime, Error, Result};
+
+/// Calendar period that partitions a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Month,
+    Week,
+    Day,
+}
+
+impl Period {
+    /// Label of the period a timestamp falls into
+    pub fn label(&self, timestamp: &DateTime) -> String {
+        let format = match self {
+            Period::Month => "%Y-%m",
+            Period::Week => "%G-W%V",
+            Period::Day => "%Y-%m-%d",
+        };
+        timestamp.format(format).to_string()
+    }
+}
+
+impl TryFrom<&str> for Period {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "month" => Ok(Period::Month),
+            "week" => Ok(Period::Week),
+            "day" => Ok(Period::Day),
+            other => Err(Error::AttributeError(format!(
+                "unknown period {:?}, expected month, week or day",
+                other
+            ))),
+        }
+    }
+}
+
+/// Label of the partition a component without timestamp is written to
+pub const UNDATED: &str = "undated";
+
+/// Number of components written per file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Partitions {
+    pub files: BTreeMap<String, usize>,
+}
+
+#[typetag::serde]
+impl Artifact for Partitions {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// File of a partition that is closed while idle and reopened in append mode on the next write
+struct PartitionFile {
+    path: String,
+    file: Option<BufWriter<File>>,
+}
+
+impl PartitionFile {
+    /// Create or truncate the file
+    fn create(path: &str) -> Result<Self> {
+        Ok(PartitionFile {
+            path: path.to_string(),
+            file: Some(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    fn open(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.file.is_none() {
+            let file = OpenOptions::new().append(true).open(&self.path)?;
+            self.file = Some(BufWriter::new(file));
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    /// Flush and close the file until it is written again
+    fn release(&mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(mut file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for PartitionFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.open()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write components into one XES file per calendar period
+pub struct PartitionedXesWriter {
+    template: String,
+    period: Period,
+    max_open: usize,
+    meta: Option<Meta>,
+    writers: BTreeMap<String, XesWriter<PartitionFile>>,
+    /// Paths of the open files, least recently written first
+    open: VecDeque<String>,
+    partitions: Partitions,
+}
+
+impl PartitionedXesWriter {
+    /// Create a writer, `template` is a path containing `{period}`
+    pub fn new<T: Into<String>>(template: T, period: Period) -> Result<Self> {
+        let template = template.into();
+        if !template.contains("{period}") {
+            return Err(Error::AttributeError(format!(
+                "path template {:?} lacks {{period}}",
+                template
+            )));
+        }
+
+        Ok(PartitionedXesWriter {
+            template,
+            period,
+            max_open: 64,
+            meta: None,
+            writers: BTreeMap::new(),
+            open: VecDeque::new(),
+            partitions: Partitions::default(),
+        })
+    }
+
+    /// Keep at most `max_open` files open at once, at least one
+    pub fn max_open(mut self, max_open: usize) -> Self {
+        self.max_open = max_open.max(1);
+        self
+    }
+
+    fn timestamp(component: &dyn AttributeContainer) -> Option<DateTime> {
+        component
+            .get_value("time:timestamp")
+            .and_then(|t| t.try_date().ok())
+            .cloned()
+    }
+
+    /// Label of the partition a trace or event belongs to
+    fn partition(&self, component: &Component) -> String {
+        let start = match component {
+            Component::Trace(trace) => trace
+                .events
+                .iter()
+                .filter_map(|e| Self::timestamp(e))
+                .min()
+                .or_else(|| Self::timestamp(trace)),
+            Component::Event(event) => Self::timestamp(event),
+            Component::Meta(_) => None,
+        };
+
+        match start {
+            Some(start) => self.period.label(&start),
+            None => UNDATED.to_string(),
+        }
+    }
+
+    /// Writer of a partition, opened on first use
+    fn writer(&mut self, partition: &str) -> Result<&mut XesWriter<PartitionFile>> {
+        let path = self.template.replace("{period}", partition);
+        self.touch(&path)?;
+
+        if !self.writers.contains_key(&path) {
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut writer = XesWriter::new(PartitionFile::create(&path)?);
+            writer.on_open()?;
+            if let Some(meta) = &self.meta {
+                writer.on_component(Component::Meta(meta.clone()))?;
+            }
+            self.writers.insert(path.clone(), writer);
+            self.partitions.files.insert(path.clone(), 0);
+        }
+
+        *self.partitions.files.get_mut(&path).unwrap() += 1;
+        Ok(self.writers.get_mut(&path).unwrap())
+    }
+
+    /// Mark a file as most recently written, closing the least recently written one if too many
+    /// files are open
+    fn touch(&mut self, path: &str) -> Result<()> {
+        match self.open.iter().position(|p| p == path) {
+            Some(i) => {
+                let path = self.open.remove(i).unwrap();
+                self.open.push_back(path);
+            }
+            None => self.open.push_back(path.to_string()),
+        }
+
+        while self.open.len() > self.max_open {
+            let idle = self.open.pop_front().unwrap();
+            if let Some(writer) = self.writers.get_mut(&idle) {
+                writer.inner_mut().release()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sink for PartitionedXesWriter {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        if let Component::Meta(meta) = component {
+            self.meta = Some(meta);
+            return Ok(());
+        }
+
+        let partition = self.partition(&component);
+        self.writer(&partition)?.on_component(component)
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        for writer in self.writers.values_mut() {
+            writer.on_close()?;
+            writer.inner_mut().release()?;
+        }
+        self.writers.clear();
+        self.open.clear();
+        Ok(())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.partitions.clone().into()])
+    }
+}
+
+impl PluginProvider for PartitionedXesWriter {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "PartitionedXesWriter",
+            "Write the stream into XES files per month, week or day of trace start",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr(
+                        "path",
+                        "Path template, {period} is replaced by the period",
+                        AttributeType::String,
+                    )
+                    .default_attr("period", "One of month, week or day", |k| {
+                        (k, "month").into()
+                    })
+                    .default_attr("max_open", "Maximal number of files open at once", |k| {
+                        (k, 64).into()
+                    }),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let template = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let period = Period::try_from(
+                        parameters.acquire_attribute("period")?.value.try_string()?,
+                    )?;
+
+                    let max_open = *parameters.acquire_attribute("max_open")?.value.try_int()?;
+
+                    Ok(PartitionedXesWriter::new(template, period)?
+                        .max_open(max_open.max(1) as usize)
+                        .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::xes::XesReader;
+    use crate::stream::{Event, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_period() {
+        let timestamp = DateTime::parse_from_rfc3339("2021-03-04T10:30:00+01:00").unwrap();
+        assert_eq!(Period::Month.label(&timestamp), "2021-03");
+        assert_eq!(Period::Week.label(&timestamp), "2021-W09");
+        assert_eq!(Period::Day.label(&timestamp), "2021-03-04");
+        assert!(Period::try_from("year").is_err());
+    }
+
+    fn trace(timestamps: &[&str]) -> Trace {
+        let mut trace = Trace::default();
+        for timestamp in timestamps {
+            let mut event = Event::default();
+            event.attributes.insert((
+                "time:timestamp",
+                DateTime::parse_from_rfc3339(timestamp).unwrap(),
+            ));
+            trace.events.push(event);
+        }
+        trace
+    }
+
+    #[test]
+    fn test_partitioned_writer() {
+        let directory =
+            std::env::temp_dir().join(format!("promi_partitions_{}", std::process::id()));
+        let path = |period: &str| {
+            directory
+                .join(format!("log_{}.xes", period))
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for timestamps in [
+            vec!["2021-03-05T08:00:00+00:00", "2021-03-04T23:00:00+00:00"],
+            vec!["2021-03-05T10:00:00+00:00"],
+            vec!["2021-03-04T12:00:00+00:00"],
+        ] {
+            buffer.push(Ok(Some(Component::Trace(trace(&timestamps)))));
+        }
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+
+        // partitions alternate, hence a single open file is reopened time and again
+        for max_open in [64, 1] {
+            let mut writer = PartitionedXesWriter::new(path("{period}"), Period::Day)
+                .unwrap()
+                .max_open(max_open);
+            let artifacts = writer.consume(&mut buffer.clone()).unwrap();
+            let partitions =
+                AnyArtifact::find::<Partitions>(&mut artifacts.iter().flatten()).unwrap();
+
+            let expected: BTreeMap<_, _> = vec![
+                (path("2021-03-04"), 2),
+                (path("2021-03-05"), 1),
+                (path(UNDATED), 1),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(partitions.files, expected);
+
+            let mut reader =
+                XesReader::from(BufReader::new(File::open(path("2021-03-04")).unwrap()));
+            let mut log = Log::default();
+            log.consume(&mut reader).unwrap();
+            assert_eq!(log.traces.len(), 2);
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(PartitionedXesWriter::new("log.xes", Period::Day).is_err());
+    }
+}
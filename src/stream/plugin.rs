@@ -2,17 +2,75 @@
 //!
 
 use std::collections::HashMap;
+use std::fs::File;
 use std::sync::Mutex;
 
-use crate::stream::channel::{StreamReceiver, StreamSender};
+use serde::{Deserialize, Serialize};
+
+use crate::model::alpha::AlphaMiner;
+use crate::model::footprint::FootprintGenerator;
+use crate::model::heuristic::HeuristicMiner;
+use crate::model::petri::PnmlWriter;
+use crate::model::replay::TokenReplay;
+use crate::render::dot::DotWriter;
+use crate::stream::anonymize::Anonymize;
+#[cfg(feature = "arrow")]
+use crate::stream::arrow::{ParquetReader, ParquetWriter};
+use crate::stream::bottleneck::BottleneckReport;
+use crate::stream::buffer::BufferSink;
+use crate::stream::canonical::Canonicalize;
+use crate::stream::channel::{
+    DataEndpoints, DataReceiver, DataSender, StreamReceiver, StreamSender, TypedReceiver,
+    TypedSender,
+};
+use crate::stream::chaos::ChaosStream;
+use crate::stream::cohort::Cohort;
+use crate::stream::correlate::Correlator;
+use crate::stream::csv::{CsvReader, CsvWriter};
+use crate::stream::currency::CurrencyConverter;
+use crate::stream::derive::Derive;
+use crate::stream::dfg::{DfgGenerator, HtmlProcessMap, OnlineDfg};
+use crate::stream::duplicate::DuplicateDetector;
 use crate::stream::duplicator::Duplicator;
+use crate::stream::editor::MetaEditor;
+use crate::stream::filter::Filter;
+use crate::stream::fold::Correlate;
+use crate::stream::globals::InferGlobals;
+use crate::stream::graphml::GraphMlWriter;
+use crate::stream::impute::Impute;
+use crate::stream::json::JsonPluginProvider;
+use crate::stream::log::LogArtifactSource;
+use crate::stream::merge::{Merge, MergeCases};
+use crate::stream::monitor::OpenCases;
+use crate::stream::mxml::MxmlReader;
+use crate::stream::net::{TcpStreamReceiver, TcpStreamSender};
+use crate::stream::noise::Noise;
+use crate::stream::notify::NotificationSink;
+use crate::stream::order::OrderGuard;
+use crate::stream::outcome::Labeler;
+use crate::stream::partition::PartitionedXesWriter;
+use crate::stream::privacy::DpNoise;
+use crate::stream::progress::Progress;
+use crate::stream::project::Project;
+use crate::stream::pseudonymize::Pseudonymize;
+use crate::stream::quality::QualityChecker;
 use crate::stream::repair::Repair;
+use crate::stream::report::ReportSink;
+use crate::stream::rework::ReworkCollector;
+use crate::stream::sequence::CanonicalOrder;
+use crate::stream::session::Sessionize;
+use crate::stream::simulate::PlayOut;
 use crate::stream::split::Split;
 use crate::stream::stats::StatsCollector;
+use crate::stream::taskmining::TaskMiningPluginProvider;
+use crate::stream::unit::UnitChecker;
 use crate::stream::validator::Validator;
+use crate::stream::variant::VariantCollector;
 use crate::stream::void::Void;
 use crate::stream::xes::XesPluginProvider;
-use crate::stream::{AnyArtifact, Attribute, AttributeMap, Sink, Stream};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeMap, AttributeType, AttributeValue, Sink, Stream,
+};
 use crate::{Error, Result};
 
 /// Parametrisation for [`StreamFactory`] and ['SinkFactory']
@@ -24,6 +82,8 @@ pub struct Parameters<'a> {
     streams_anon: Vec<Box<dyn Stream + 'a>>,
     sinks: HashMap<String, Box<dyn Sink + 'a>>,
     sinks_anon: Vec<Box<dyn Sink + 'a>>,
+    data_senders: HashMap<String, DataSender>,
+    data_receivers: HashMap<String, DataReceiver>,
 }
 
 impl<'a> Parameters<'a> {
@@ -70,6 +130,22 @@ impl<'a> Parameters<'a> {
         self.sinks_anon.drain(..).collect()
     }
 
+    /// Try to acquire a typed data channel sender by name
+    pub fn acquire_data_sender<T>(&mut self, key: &str) -> Result<TypedSender<T>> {
+        self.data_senders
+            .remove(key)
+            .map(TypedSender::from)
+            .ok_or_else(|| Error::StreamError(format!("no data sender {:?}", key)))
+    }
+
+    /// Try to acquire a typed data channel receiver by name
+    pub fn acquire_data_receiver<T>(&mut self, key: &str) -> Result<TypedReceiver<T>> {
+        self.data_receivers
+            .remove(key)
+            .map(TypedReceiver::from)
+            .ok_or_else(|| Error::StreamError(format!("no data receiver {:?}", key)))
+    }
+
     fn warn_non_empty(&self) {
         let remaining_attributes = self.attributes.len();
         if remaining_attributes > 0 {
@@ -90,9 +166,23 @@ impl<'a> Parameters<'a> {
         if remaining_sinks > 0 {
             warn!("{} sinks remain unused", remaining_sinks)
         }
+
+        let remaining_data = self.data_senders.len() + self.data_receivers.len();
+        if remaining_data > 0 {
+            warn!("{} data channel endpoints remain unused", remaining_data)
+        }
     }
 }
 
+/// Name, description, expected type, default value and whether an attribute may be omitted
+type AttributeDeclaration = (
+    String,
+    String,
+    Option<AttributeType>,
+    Option<Attribute>,
+    bool,
+);
+
 /// Parameter declaration
 ///
 /// A parameter declaration holds information about which parameters a [`StreamFactory`] or
@@ -100,10 +190,12 @@ impl<'a> Parameters<'a> {
 ///
 #[derive(Debug, Clone)]
 pub struct Declaration {
-    attributes: Vec<(String, String, Option<Attribute>)>,
-    artifacts: Vec<(String, String)>,
+    attributes: Vec<AttributeDeclaration>,
+    artifacts: Vec<(String, String, Option<String>)>,
     streams: Vec<(String, String)>,
     sinks: Vec<(String, String)>,
+    data_senders: Vec<(String, String, String)>,
+    data_receivers: Vec<(String, String, String)>,
 }
 
 impl Default for Declaration {
@@ -113,6 +205,8 @@ impl Default for Declaration {
             artifacts: vec![],
             streams: vec![],
             sinks: vec![],
+            data_senders: vec![],
+            data_receivers: vec![],
         }
     }
 }
@@ -121,11 +215,37 @@ impl Declaration {
     /// Register attribute
     pub fn attribute<S: Into<String>, D: Into<String>>(mut self, name: S, description: D) -> Self {
         self.attributes
-            .push((name.into(), description.into(), None));
+            .push((name.into(), description.into(), None, None, false));
         self
     }
 
-    /// Register attribute with default value
+    /// Register attribute of the given type
+    pub fn typed_attr<S, D>(mut self, name: S, description: D, expected: AttributeType) -> Self
+    where
+        S: Into<String>,
+        D: Into<String>,
+    {
+        self.attributes
+            .push((name.into(), description.into(), Some(expected), None, false));
+        self
+    }
+
+    /// Register attribute of the given type that may be omitted
+    ///
+    /// Unlike [`Declaration::default_attr`], there is no default value: the plugin reads the
+    /// attribute only if it is given.
+    ///
+    pub fn optional_attr<S, D>(mut self, name: S, description: D, expected: AttributeType) -> Self
+    where
+        S: Into<String>,
+        D: Into<String>,
+    {
+        self.attributes
+            .push((name.into(), description.into(), Some(expected), None, true));
+        self
+    }
+
+    /// Register attribute with default value, the expected type is the one of the default
     pub fn default_attr<S, D, V>(mut self, name: S, description: D, default: V) -> Self
     where
         S: Into<String>,
@@ -133,14 +253,41 @@ impl Declaration {
         V: Fn(String) -> Attribute,
     {
         let name = name.into();
-        self.attributes
-            .push((name.clone(), description.into(), Some(default(name))));
+        let default = default(name.clone());
+        self.attributes.push((
+            name,
+            description.into(),
+            Some(default.hint()),
+            Some(default),
+            false,
+        ));
         self
     }
 
+    /// Register parameters that are shared between plugins, e.g. by
+    /// [`FileOptions::declare`](crate::stream::output::FileOptions::declare)
+    pub fn with<F: FnOnce(Self) -> Self>(self, declare: F) -> Self {
+        declare(self)
+    }
+
     /// Register artifact
     pub fn artifact<S: Into<String>, D: Into<String>>(mut self, name: S, description: D) -> Self {
-        self.artifacts.push((name.into(), description.into()));
+        self.artifacts.push((name.into(), description.into(), None));
+        self
+    }
+
+    /// Register artifact of the given type
+    pub fn typed_artifact<T, S, D>(mut self, name: S, description: D) -> Self
+    where
+        T: Artifact,
+        S: Into<String>,
+        D: Into<String>,
+    {
+        // strip the module path, which leaves the name artifacts are tagged with on serialization
+        let type_name = std::any::type_name::<T>();
+        let type_name = type_name.rsplit("::").next().unwrap_or(type_name);
+        self.artifacts
+            .push((name.into(), description.into(), Some(type_name.into())));
         self
     }
 
@@ -156,34 +303,73 @@ impl Declaration {
         self
     }
 
+    /// Register sending data channel endpoint for payloads of type `T`
+    pub fn data_sender<T, S, D>(mut self, name: S, description: D) -> Self
+    where
+        T: Send + 'static,
+        S: Into<String>,
+        D: Into<String>,
+    {
+        self.data_senders.push((
+            name.into(),
+            description.into(),
+            std::any::type_name::<T>().into(),
+        ));
+        self
+    }
+
+    /// Register receiving data channel endpoint for payloads of type `T`
+    pub fn data_receiver<T, S, D>(mut self, name: S, description: D) -> Self
+    where
+        T: Send + 'static,
+        S: Into<String>,
+        D: Into<String>,
+    {
+        self.data_receivers.push((
+            name.into(),
+            description.into(),
+            std::any::type_name::<T>().into(),
+        ));
+        self
+    }
+
     fn make<'a>(
         &self,
         mut attributes: AttributeMap,
         artifacts: &'a mut [AnyArtifact],
         streams: Vec<Box<dyn Stream + 'a>>,
         sinks: Vec<Box<dyn Sink + 'a>>,
+        data: DataEndpoints,
     ) -> Result<Parameters<'a>> {
         let mut artifacts = artifacts.iter_mut();
         let mut streams = streams.into_iter();
         let mut sinks = sinks.into_iter();
+        let mut data_senders = data.senders.into_iter();
+        let mut data_receivers = data.receivers.into_iter();
 
         let mut attribute_map = AttributeMap::new();
         let mut artifact_map = HashMap::new();
         let mut stream_map = HashMap::new();
         let mut sink_map = HashMap::new();
 
-        for (name, _, default) in self.attributes.iter() {
-            let mut attribute = attributes
-                .remove(name)
-                .or_else(|| default.clone())
-                .ok_or_else(|| Error::StreamError(format!("attribute {:?} is missing", &name)))?;
+        for (name, _, _, default, optional) in self.attributes.iter() {
+            let mut attribute = match attributes.remove(name).or_else(|| default.clone()) {
+                Some(attribute) => attribute,
+                None if *optional => continue,
+                None => {
+                    return Err(Error::StreamError(format!(
+                        "attribute {:?} is missing",
+                        &name
+                    )))
+                }
+            };
             attribute.key = name.into();
             attribute_map.insert(attribute);
         }
 
         attribute_map.extend(attributes.into_iter());
 
-        for (i, (name, _)) in self.artifacts.iter().enumerate() {
+        for (i, (name, _, _)) in self.artifacts.iter().enumerate() {
             artifact_map.insert(
                 name.clone(),
                 artifacts.next().ok_or_else(|| {
@@ -210,6 +396,30 @@ impl Declaration {
             );
         }
 
+        let data_sender_map = self
+            .data_senders
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _, _))| {
+                let sender = data_senders.next().ok_or_else(|| {
+                    Error::StreamError(format!("{}. data sender {:?} is missing", i, name))
+                })?;
+                Ok((name.clone(), sender))
+            })
+            .collect::<Result<_>>()?;
+
+        let data_receiver_map = self
+            .data_receivers
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _, _))| {
+                let receiver = data_receivers.next().ok_or_else(|| {
+                    Error::StreamError(format!("{}. data receiver {:?} is missing", i, name))
+                })?;
+                Ok((name.clone(), receiver))
+            })
+            .collect::<Result<_>>()?;
+
         Ok(Parameters {
             attributes: attribute_map,
             artifacts: artifact_map,
@@ -218,8 +428,127 @@ impl Declaration {
             streams_anon: streams.collect(),
             sinks: sink_map,
             sinks_anon: sinks.collect(),
+            data_senders: data_sender_map,
+            data_receivers: data_receiver_map,
         })
     }
+
+    fn describe(&self) -> DeclarationDescription {
+        let parameters = |declared: &[(String, String)]| {
+            declared
+                .iter()
+                .map(|(name, description)| ParameterDescription {
+                    name: name.clone(),
+                    description: description.clone(),
+                })
+                .collect()
+        };
+        let data_channels = |declared: &[(String, String, String)]| {
+            declared
+                .iter()
+                .map(|(name, description, type_name)| DataChannelDescription {
+                    name: name.clone(),
+                    description: description.clone(),
+                    type_name: type_name.clone(),
+                })
+                .collect()
+        };
+
+        DeclarationDescription {
+            attributes: self
+                .attributes
+                .iter()
+                .map(
+                    |(name, description, expected, default, optional)| AttributeDescription {
+                        name: name.clone(),
+                        description: description.clone(),
+                        expected: expected.clone(),
+                        default: default.as_ref().map(|a| a.value.clone()),
+                        optional: *optional,
+                    },
+                )
+                .collect(),
+            artifacts: self
+                .artifacts
+                .iter()
+                .map(|(name, description, type_name)| ArtifactDescription {
+                    name: name.clone(),
+                    description: description.clone(),
+                    type_name: type_name.clone(),
+                })
+                .collect(),
+            streams: parameters(&self.streams),
+            sinks: parameters(&self.sinks),
+            data_senders: data_channels(&self.data_senders),
+            data_receivers: data_channels(&self.data_receivers),
+        }
+    }
+}
+
+/// Declared attribute of a plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDescription {
+    pub name: String,
+    pub description: String,
+    /// Type the value is expected to be of, if declared
+    pub expected: Option<AttributeType>,
+    /// Value used if the attribute is not given, required otherwise
+    pub default: Option<AttributeValue>,
+    /// Whether the attribute may be omitted even though there is no default
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// Declared artifact of a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactDescription {
+    pub name: String,
+    pub description: String,
+    /// Name of the expected artifact type, any artifact is accepted if `None`
+    pub type_name: Option<String>,
+}
+
+/// Declared stream or sink of a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterDescription {
+    pub name: String,
+    pub description: String,
+}
+
+/// Declared data channel endpoint of a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataChannelDescription {
+    pub name: String,
+    pub description: String,
+    /// Name of the payload type, both endpoints of a channel have to agree on it
+    pub type_name: String,
+}
+
+/// Parameters a plugin expects, see [`Declaration`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeclarationDescription {
+    pub attributes: Vec<AttributeDescription>,
+    pub artifacts: Vec<ArtifactDescription>,
+    pub streams: Vec<ParameterDescription>,
+    pub sinks: Vec<ParameterDescription>,
+    pub data_senders: Vec<DataChannelDescription>,
+    pub data_receivers: Vec<DataChannelDescription>,
+}
+
+/// Whether a plugin builds a stream or a sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluginKind {
+    Stream,
+    Sink,
+}
+
+/// Structured metadata of a registered plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginDescription {
+    pub name: String,
+    pub description: String,
+    pub kind: PluginKind,
+    pub declaration: DeclarationDescription,
 }
 
 /// Function that turns [`Parameters`] into a [`Stream`] object
@@ -257,12 +586,24 @@ impl Factory {
         artifacts: &'a mut [AnyArtifact],
         streams: Vec<Box<dyn Stream + 'a>>,
         sinks: Vec<Box<dyn Sink + 'a>>,
+    ) -> Result<Box<dyn Stream + 'a>> {
+        self.build_stream_with_data(attributes, artifacts, streams, sinks, Default::default())
+    }
+
+    /// Try to build a [`Stream`] object that is connected to data channels
+    pub fn build_stream_with_data<'a>(
+        &self,
+        attributes: AttributeMap,
+        artifacts: &'a mut [AnyArtifact],
+        streams: Vec<Box<dyn Stream + 'a>>,
+        sinks: Vec<Box<dyn Sink + 'a>>,
+        data: DataEndpoints,
     ) -> Result<Box<dyn Stream + 'a>> {
         match &self.factory {
             FactoryType::Stream(factory) => {
                 let mut parameters = self
                     .declaration
-                    .make(attributes, artifacts, streams, sinks)?;
+                    .make(attributes, artifacts, streams, sinks, data)?;
                 let stream = factory(&mut parameters);
                 parameters.warn_non_empty();
                 stream
@@ -278,12 +619,24 @@ impl Factory {
         artifacts: &'a mut [AnyArtifact],
         streams: Vec<Box<dyn Stream + 'a>>,
         sinks: Vec<Box<dyn Sink + 'a>>,
+    ) -> Result<Box<dyn Sink + 'a>> {
+        self.build_sink_with_data(attributes, artifacts, streams, sinks, Default::default())
+    }
+
+    /// Try to build a [`Sink`] object that is connected to data channels
+    pub fn build_sink_with_data<'a>(
+        &self,
+        attributes: AttributeMap,
+        artifacts: &'a mut [AnyArtifact],
+        streams: Vec<Box<dyn Stream + 'a>>,
+        sinks: Vec<Box<dyn Sink + 'a>>,
+        data: DataEndpoints,
     ) -> Result<Box<dyn Sink + 'a>> {
         match &self.factory {
             FactoryType::Sink(factory) => {
                 let mut parameters = self
                     .declaration
-                    .make(attributes, artifacts, streams, sinks)?;
+                    .make(attributes, artifacts, streams, sinks, data)?;
                 let sink = factory(&mut parameters);
                 parameters.warn_non_empty();
                 sink
@@ -335,6 +688,19 @@ pub struct Entry {
 }
 
 impl Entry {
+    /// Structured metadata of this entry
+    pub fn describe(&self) -> PluginDescription {
+        PluginDescription {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            kind: match self.factory.factory {
+                FactoryType::Stream(_) => PluginKind::Stream,
+                FactoryType::Sink(_) => PluginKind::Sink,
+            },
+            declaration: self.factory.declaration.describe(),
+        }
+    }
+
     pub fn new<N: Into<String>, D: Into<String>>(
         name: N,
         description: D,
@@ -360,50 +726,147 @@ lazy_static! {
         Duplicator::register_at(&mut registry);
         StatsCollector::register_at(&mut registry);
         Validator::register_at(&mut registry);
+        Filter::register_at(&mut registry);
+        QualityChecker::register_at(&mut registry);
         Repair::register_at(&mut registry);
+        Canonicalize::register_at(&mut registry);
+        MetaEditor::register_at(&mut registry);
+        InferGlobals::<Box<dyn Stream>>::register_at(&mut registry);
+        Impute::register_at(&mut registry);
+        Derive::register_at(&mut registry);
+        UnitChecker::register_at(&mut registry);
+        CurrencyConverter::register_at(&mut registry);
+        Project::register_at(&mut registry);
+        Pseudonymize::register_at(&mut registry);
+        Anonymize::register_at(&mut registry);
         Split::register_at(&mut registry);
+        MergeCases::<Box<dyn Stream>>::register_at(&mut registry);
+        Merge::register_at(&mut registry);
+        Sessionize::<Box<dyn Stream>>::register_at(&mut registry);
+        Correlate::<Box<dyn Stream>>::register_at(&mut registry);
+        TaskMiningPluginProvider::register_at(&mut registry);
         StreamSender::register_at(&mut registry);
         StreamReceiver::register_at(&mut registry);
+        TcpStreamSender::register_at(&mut registry);
+        TcpStreamReceiver::register_at(&mut registry);
+        ChaosStream::<Box<dyn Stream>>::register_at(&mut registry);
+        Noise::register_at(&mut registry);
+        OrderGuard::<Box<dyn Stream>>::register_at(&mut registry);
+        CanonicalOrder::<Box<dyn Stream>>::register_at(&mut registry);
         XesPluginProvider::register_at(&mut registry);
+        MxmlReader::<std::io::BufReader<File>>::register_at(&mut registry);
+        CsvReader::<std::io::BufReader<File>>::register_at(&mut registry);
+        CsvWriter::<std::io::BufWriter<File>>::register_at(&mut registry);
+        #[cfg(feature = "arrow")]
+        ParquetReader::register_at(&mut registry);
+        #[cfg(feature = "arrow")]
+        ParquetWriter::<File>::register_at(&mut registry);
+        JsonPluginProvider::register_at(&mut registry);
+        Labeler::register_at(&mut registry);
+        Cohort::register_at(&mut registry);
+        Correlator::register_at(&mut registry);
+        ReworkCollector::register_at(&mut registry);
+        DuplicateDetector::register_at(&mut registry);
+        OpenCases::register_at(&mut registry);
+        Progress::<Box<dyn Stream>>::register_at(&mut registry);
+        OnlineDfg::register_at(&mut registry);
+        DfgGenerator::register_at(&mut registry);
+        VariantCollector::register_at(&mut registry);
+        FootprintGenerator::register_at(&mut registry);
+        AlphaMiner::register_at(&mut registry);
+        PnmlWriter::register_at(&mut registry);
+        HeuristicMiner::register_at(&mut registry);
+        TokenReplay::register_at(&mut registry);
+        DpNoise::<Box<dyn Stream>>::register_at(&mut registry);
+        HtmlProcessMap::<File>::register_at(&mut registry);
+        BottleneckReport::<File>::register_at(&mut registry);
+        ReportSink::<File>::register_at(&mut registry);
+        GraphMlWriter::<File>::register_at(&mut registry);
+        DotWriter::<File>::register_at(&mut registry);
+        PartitionedXesWriter::register_at(&mut registry);
+        NotificationSink::register_at(&mut registry);
+        LogArtifactSource::register_at(&mut registry);
+        PlayOut::register_at(&mut registry);
+        BufferSink::register_at(&mut registry);
 
         Mutex::new(registry)
     };
 }
 
-/// List all installed plugins via logger
-pub fn log_plugins() -> Result<()> {
+/// Describe all installed plugins, sorted by name
+///
+/// This is meant for user interfaces and configuration validators that need to know which plugins
+/// exist and what parameters they take.
+///
+pub fn describe() -> Result<Vec<PluginDescription>> {
     let registry = REGISTRY
         .lock()
         .map_err(|_| Error::StreamError("unable to acquire stream plugin registry".to_string()))?;
 
-    let mut entries: Vec<_> = registry.iter().collect();
-    entries.sort_by_key(|e| e.0);
+    let mut descriptions: Vec<_> = registry.values().map(Entry::describe).collect();
+    descriptions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(descriptions)
+}
 
+/// List all installed plugins via logger
+pub fn log_plugins() -> Result<()> {
     info!("Installed Plugins:");
-    for (i, (_, entry)) in entries.into_iter().enumerate() {
-        let declaration = &entry.factory.declaration;
+    for (i, plugin) in describe()?.into_iter().enumerate() {
+        let declaration = &plugin.declaration;
 
-        info!("{:>2}. {}", i + 1, entry.name);
-        info!("    {:?}", entry.description);
+        info!("{:>2}. {} ({:?})", i + 1, plugin.name, plugin.kind);
+        info!("    {:?}", plugin.description);
 
-        for (name, description, default) in declaration.attributes.iter() {
-            let default_str = default
+        for attribute in declaration.attributes.iter() {
+            let type_str = attribute
+                .expected
                 .as_ref()
-                .map(|v| format!("[{:?}]", v))
+                .map(|t| format!("<{:?}>", t))
                 .unwrap_or_else(|| "".into());
-            info!("    ATR: {:>8}: {:?} {}", name, description, default_str)
+            let default_str = attribute
+                .default
+                .as_ref()
+                .map(|v| format!("[{:?}]", v))
+                .unwrap_or_else(|| if attribute.optional { "[optional]" } else { "" }.into());
+            info!(
+                "    ATR: {:>8}: {:?} {}{}",
+                attribute.name, attribute.description, type_str, default_str
+            )
         }
 
-        for (name, description) in declaration.artifacts.iter() {
-            info!("    ART: {:>8}: {:?}", name, description)
+        for artifact in declaration.artifacts.iter() {
+            let type_str = artifact
+                .type_name
+                .as_ref()
+                .map(|t| format!("<{}>", t))
+                .unwrap_or_else(|| "".into());
+            info!(
+                "    ART: {:>8}: {:?} {}",
+                artifact.name, artifact.description, type_str
+            )
         }
 
-        for (name, description) in declaration.streams.iter() {
-            info!("    STR: {:>8}: {:?}", name, description)
+        let parameters = [("STR", &declaration.streams), ("SNK", &declaration.sinks)];
+        for (tag, parameters) in parameters.iter() {
+            for parameter in parameters.iter() {
+                info!(
+                    "    {}: {:>8}: {:?}",
+                    tag, parameter.name, parameter.description
+                )
+            }
         }
 
-        for (name, description) in declaration.sinks.iter() {
-            info!("    SNK: {:>8}: {:?}", name, description)
+        let channels = [
+            ("DTS", &declaration.data_senders),
+            ("DTR", &declaration.data_receivers),
+        ];
+        for (tag, channels) in channels.iter() {
+            for channel in channels.iter() {
+                info!(
+                    "    {}: {:>8}: {:?} <{}>",
+                    tag, channel.name, channel.description, channel.type_name
+                )
+            }
         }
     }
 
@@ -453,7 +916,15 @@ mod tests {
             .stream("foo", "some description")
             .stream("bar", "some description")
             .sink("foo", "some description")
-            .sink("bar", "some description");
+            .sink("bar", "some description")
+            .data_sender::<usize, _, _>("foo", "some description")
+            .data_receiver::<usize, _, _>("foo", "some description");
+
+        let (data_sender, data_receiver) = crate::stream::channel::channel(None);
+        let data = DataEndpoints {
+            senders: vec![data_sender],
+            receivers: vec![data_receiver],
+        };
 
         let atr_extra: AttributeMap = vec![("bar", 13), ("baz", 37)].into_iter().into();
         let art_extra: &mut [AnyArtifact] = &mut [
@@ -472,7 +943,7 @@ mod tests {
             Box::new(Void::default()),
         ];
         let mut parameters = declaration
-            .make(atr_extra, art_extra, str_extra, snk_extra)
+            .make(atr_extra, art_extra, str_extra, snk_extra, data)
             .unwrap();
 
         assert_eq!(
@@ -515,6 +986,12 @@ mod tests {
         assert_eq!(parameters.acquire_streams_anon().len(), 1);
         assert_eq!(parameters.acquire_sinks_anon().len(), 1);
 
+        let data_sender = parameters.acquire_data_sender::<usize>("foo").unwrap();
+        let data_receiver = parameters.acquire_data_receiver::<usize>("foo").unwrap();
+        data_sender.send(42).unwrap();
+        assert_eq!(data_receiver.recv().unwrap(), Some(42));
+        assert!(parameters.acquire_data_sender::<usize>("foo").is_err());
+
         parameters.warn_non_empty();
 
         assert!(parameters.acquire_attribute("foo").is_err());
@@ -531,6 +1008,95 @@ mod tests {
         assert_eq!(parameters.acquire_sinks_anon().len(), 0);
     }
 
+    #[test]
+    fn test_describe() {
+        logging();
+        log_plugins().unwrap();
+
+        let plugins = describe().unwrap();
+        assert!(plugins.windows(2).all(|w| w[0].name < w[1].name));
+        let plugin = |name: &str| plugins.iter().find(|p| p.name == name).unwrap();
+
+        let pnml = plugin("PnmlWriter");
+        assert_eq!(pnml.kind, PluginKind::Sink);
+        assert_eq!(
+            pnml.declaration.artifacts[0].type_name.as_deref(),
+            Some("PetriNet")
+        );
+        let path = &pnml.declaration.attributes[0];
+        assert_eq!(
+            (path.name.as_str(), &path.expected, &path.default),
+            ("path", &Some(AttributeType::String), &None)
+        );
+
+        let progress = plugin("Progress");
+        assert_eq!(progress.kind, PluginKind::Stream);
+        assert_eq!(progress.declaration.streams[0].name, "inner");
+        let interval = &progress.declaration.attributes[0];
+        assert_eq!(interval.expected, Some(AttributeType::Int));
+        assert_eq!(interval.default, Some(AttributeValue::Int(10)));
+
+        let xes = plugin("XesWriter");
+        let optional: Vec<_> = xes
+            .declaration
+            .attributes
+            .iter()
+            .filter(|a| a.optional)
+            .map(|a| a.name.as_str())
+            .collect();
+        for name in ["flavor", "max_traces", "fsync", "mmap", "provenance"].iter() {
+            assert!(optional.contains(name), "{} is not declared", name);
+        }
+        let json = serde_json::to_string(&plugins).unwrap();
+        let restored: Vec<PluginDescription> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, plugins);
+    }
+
+    #[test]
+    fn test_optional_attr() {
+        let declaration = Declaration::default()
+            .optional_attr("foo", "some description", AttributeType::Int)
+            .attribute("bar", "some description");
+
+        let attributes: AttributeMap = vec![("bar", 1)].into_iter().into();
+        let mut parameters = declaration
+            .make(
+                attributes,
+                &mut [],
+                vec![],
+                vec![],
+                DataEndpoints::default(),
+            )
+            .unwrap();
+        assert!(parameters.acquire_attribute("foo").is_err());
+
+        let attributes: AttributeMap = vec![("foo", 2)].into_iter().into();
+        assert!(declaration
+            .make(
+                attributes,
+                &mut [],
+                vec![],
+                vec![],
+                DataEndpoints::default()
+            )
+            .is_err());
+
+        let attributes: AttributeMap = vec![("foo", 2), ("bar", 1)].into_iter().into();
+        let mut parameters = declaration
+            .make(
+                attributes,
+                &mut [],
+                vec![],
+                vec![],
+                DataEndpoints::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            parameters.acquire_attribute("foo").unwrap().value,
+            AttributeValue::Int(2)
+        );
+    }
+
     #[test]
     fn test_parameters_warning() {
         logging();
@@ -552,21 +1118,93 @@ mod tests {
             .sink("bar", "some description");
 
         let atr_err: AttributeMap = AttributeMap::new();
-        let mut art_err: &mut [AnyArtifact] = &mut [TestArtifact::default().into()];
+        let art_err: &mut [AnyArtifact] = &mut [TestArtifact::default().into()];
         let str_err: Vec<Box<dyn Stream>> = vec![Box::new(Void::default())];
         let snk_err: Vec<Box<dyn Sink>> = vec![Box::new(Void::default())];
 
         assert!(decl_atr
-            .make(atr_err.clone(), &mut [], vec![], vec![],)
+            .make(
+                atr_err.clone(),
+                &mut [],
+                vec![],
+                vec![],
+                DataEndpoints::default()
+            )
             .is_err());
         assert!(decl_art
-            .make(atr_err.clone(), &mut art_err, vec![], vec![],)
+            .make(
+                atr_err.clone(),
+                art_err,
+                vec![],
+                vec![],
+                DataEndpoints::default()
+            )
             .is_err());
         assert!(decl_str
-            .make(atr_err.clone(), &mut [], str_err, vec![],)
+            .make(
+                atr_err.clone(),
+                &mut [],
+                str_err,
+                vec![],
+                DataEndpoints::default()
+            )
             .is_err());
         assert!(decl_snk
-            .make(atr_err.clone(), &mut [], vec![], snk_err,)
+            .make(
+                atr_err.clone(),
+                &mut [],
+                vec![],
+                snk_err,
+                DataEndpoints::default()
+            )
             .is_err());
     }
+
+    /// Collect the Rust sources below the given directory
+    fn sources(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, files);
+            } else if path.extension().is_some_and(|e| e == "rs") {
+                files.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_declared_attributes() {
+        let mut files = Vec::new();
+        sources(
+            std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src")),
+            &mut files,
+        );
+
+        // every attribute read by a factory has to be declared by its plugin
+        let registry = REGISTRY.lock().unwrap();
+        let mut undeclared = Vec::new();
+        for file in files.iter() {
+            let source = std::fs::read_to_string(file).unwrap();
+            let source = source.split("#[cfg(test)]").next().unwrap();
+            for block in source.split("Entry::new(").skip(1) {
+                let entry = match block.split('"').nth(1).and_then(|n| registry.get(n)) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let description = entry.describe();
+                let declared = description.declaration.attributes;
+                for read in block.split("acquire_attribute(\"").skip(1) {
+                    let key = read.split('"').next().unwrap();
+                    if !declared.iter().any(|a| a.name == key) {
+                        undeclared.push(format!("{}: {}", description.name, key));
+                    }
+                }
+            }
+        }
+        assert!(
+            undeclared.is_empty(),
+            "undeclared attributes: {:#?}",
+            undeclared
+        );
+    }
 }
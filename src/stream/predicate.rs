@@ -0,0 +1,520 @@
+//! Textual predicates over attributes
+//!
+//! Predicates make filters available to flow definitions that can't hold Rust closures. They are
+//! written as comparisons of attributes with literals, combined by `and`, `or`, `not` and
+//! parentheses:
+//!
+//! ```text
+//! concept:name in ["a", "b"] and (time:timestamp > 2020-01-01 or not cost:total <= 100)
+//! ```
+//!
+//! - attribute keys are written as is, or quoted if they contain other characters than
+//!   alphanumerics and `_ : . -`, e.g. `"order id"`
+//! - literals are strings in single or double quotes, integers, floats, `true`/`false` and dates,
+//!   either as `2020-01-01`, as `2020-01-01T10:00:00` (both UTC) or as RFC 3339 with offset
+//! - comparisons are `==` (or `=`), `!=`, `<`, `<=`, `>`, `>=`, `in [...]` and `not in [...]`
+//!
+//! A comparison with a missing attribute is false. Predicates are compiled into conditions in
+//! conjunctive normal form, such that they plug into `filter::from_cnf`.
+//!
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::stream::csv::parse_timestamp;
+use crate::stream::expression::compare;
+use crate::stream::filter::{Condition, CNF};
+use crate::stream::{AttributeContainer, AttributeValue};
+use crate::{DateTime, Error, Result};
+
+/// Upper limit of clauses of a predicate in conjunctive normal form
+const MAX_CLAUSES: usize = 1024;
+
+/// Upper limit of nested parentheses and negations, to bound the recursion of the parser
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Literal(AttributeValue),
+    Op(String),
+    Open,
+    Close,
+    OpenList,
+    CloseList,
+    Comma,
+}
+
+fn literal(word: &str) -> Result<AttributeValue> {
+    if let Ok(value) = word.parse::<i64>() {
+        return Ok(AttributeValue::Int(value));
+    }
+    if let Ok(value) = word.parse::<f64>() {
+        return Ok(AttributeValue::Float(value));
+    }
+    if let Ok(value) = DateTime::parse_from_rfc3339(word) {
+        return Ok(AttributeValue::Date(value));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d"].iter() {
+        if let Ok(value) = parse_timestamp(word, format) {
+            return Ok(AttributeValue::Date(value));
+        }
+    }
+
+    Err(Error::ExpressionError(format!(
+        "invalid literal {:?}",
+        word
+    )))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match chars.next() {
+                Some('\\') => text.extend(chars.next()),
+                Some(c) if c == quote => return Ok(text),
+                Some(c) => text.push(c),
+                None => {
+                    return Err(Error::ExpressionError(format!(
+                        "unterminated {}{}",
+                        quote, text
+                    )))
+                }
+            }
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '"' | '\'' => Token::Quoted(quoted(&mut chars, c)?),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => Token::OpenList,
+            ']' => Token::CloseList,
+            ',' => Token::Comma,
+            '=' | '!' | '<' | '>' => {
+                let mut op = c.to_string();
+                if chars.peek() == Some(&'=') {
+                    op.push(chars.next().unwrap());
+                }
+                match op.as_str() {
+                    "=" => Token::Op("==".to_string()),
+                    "!" => return Err(Error::ExpressionError("unknown operator \"!\"".into())),
+                    _ => Token::Op(op),
+                }
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut word = c.to_string();
+                while let Some(n) = chars.next_if(|n| n.is_alphanumeric() || ":.+-".contains(*n)) {
+                    word.push(n);
+                }
+                Token::Literal(literal(&word)?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(n) = chars.next_if(|n| n.is_alphanumeric() || "_:.-".contains(*n)) {
+                    word.push(n);
+                }
+                Token::Word(word)
+            }
+            other => {
+                return Err(Error::ExpressionError(format!(
+                    "unexpected character {:?}",
+                    other
+                )))
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Comparison of an attribute with a literal
+#[derive(Debug, Clone, PartialEq)]
+struct Comparison {
+    key: String,
+    op: String,
+    values: Vec<AttributeValue>,
+}
+
+impl Comparison {
+    fn test(&self, container: &dyn AttributeContainer) -> Result<bool> {
+        let value = match container.get_value(&self.key) {
+            Some(AttributeValue::Id(id)) => AttributeValue::String(id.clone()),
+            Some(value) => value.clone(),
+            None => return Ok(false),
+        };
+
+        let op = if self.op == "in" { "==" } else { &self.op };
+        for other in self.values.iter() {
+            if compare(op, &value, other)? == AttributeValue::Boolean(true) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Comparison(Comparison),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+/// Comparison that is possibly negated
+type Literal = (Comparison, bool);
+
+impl Node {
+    fn evaluate(&self, container: &dyn AttributeContainer) -> Result<bool> {
+        Ok(match self {
+            Node::Comparison(comparison) => comparison.test(container)?,
+            Node::Not(node) => !node.evaluate(container)?,
+            Node::And(a, b) => a.evaluate(container)? && b.evaluate(container)?,
+            Node::Or(a, b) => a.evaluate(container)? || b.evaluate(container)?,
+        })
+    }
+
+    /// Clauses of the (possibly negated) node in conjunctive normal form
+    fn clauses(&self, negated: bool) -> Result<Vec<Vec<Literal>>> {
+        let (a, b, conjunction) = match self {
+            Node::Comparison(comparison) => return Ok(vec![vec![(comparison.clone(), negated)]]),
+            Node::Not(node) => return node.clauses(!negated),
+            // De Morgan: negation swaps conjunction and disjunction
+            Node::And(a, b) => (a, b, !negated),
+            Node::Or(a, b) => (a, b, negated),
+        };
+
+        let (a, b) = (a.clauses(negated)?, b.clauses(negated)?);
+        if conjunction {
+            return Ok(a.into_iter().chain(b).collect());
+        }
+
+        if a.len() * b.len() > MAX_CLAUSES {
+            return Err(Error::ExpressionError(format!(
+                "predicate exceeds {} clauses in conjunctive normal form",
+                MAX_CLAUSES
+            )));
+        }
+        let mut clauses = Vec::with_capacity(a.len() * b.len());
+        for x in a.iter() {
+            for y in b.iter() {
+                clauses.push(x.iter().chain(y.iter()).cloned().collect());
+            }
+        }
+        Ok(clauses)
+    }
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(Error::ExpressionError(format!(
+                "expected {:?}, got {:?}",
+                expected, other
+            ))),
+        }
+    }
+
+    fn disjunction(&mut self) -> Result<Node> {
+        let mut node = self.conjunction()?;
+        while self.keyword("or") {
+            node = Node::Or(Box::new(node), Box::new(self.conjunction()?));
+        }
+        Ok(node)
+    }
+
+    fn conjunction(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        while self.keyword("and") {
+            node = Node::And(Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::ExpressionError(format!(
+                "predicate is nested deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+
+        self.depth += 1;
+        let node = if self.keyword("not") {
+            self.unary().map(|node| Node::Not(Box::new(node)))
+        } else if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            self.disjunction()
+                .and_then(|node| self.expect(Token::Close).map(|_| node))
+        } else {
+            self.comparison()
+        };
+        self.depth -= 1;
+        node
+    }
+
+    fn value(&mut self) -> Result<AttributeValue> {
+        match self.advance() {
+            Some(Token::Literal(value)) => Ok(value),
+            Some(Token::Quoted(text)) => Ok(AttributeValue::String(text.into())),
+            Some(Token::Word(word)) if word == "true" || word == "false" => {
+                Ok(AttributeValue::Boolean(word == "true"))
+            }
+            other => Err(Error::ExpressionError(format!(
+                "expected value, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<AttributeValue>> {
+        self.expect(Token::OpenList)?;
+        let mut values = Vec::new();
+        if self.peek() != Some(&Token::CloseList) {
+            values.push(self.value()?);
+            while self.peek() == Some(&Token::Comma) {
+                self.position += 1;
+                values.push(self.value()?);
+            }
+        }
+        self.expect(Token::CloseList)?;
+        Ok(values)
+    }
+
+    fn comparison(&mut self) -> Result<Node> {
+        let key = match self.advance() {
+            Some(Token::Word(key)) | Some(Token::Quoted(key)) => key,
+            other => {
+                return Err(Error::ExpressionError(format!(
+                    "expected attribute, got {:?}",
+                    other
+                )))
+            }
+        };
+
+        if self.keyword("in") {
+            let values = self.list()?;
+            return Ok(Node::Comparison(Comparison {
+                key,
+                op: "in".to_string(),
+                values,
+            }));
+        }
+        if self.keyword("not") {
+            if !self.keyword("in") {
+                return Err(Error::ExpressionError(format!(
+                    "expected \"in\" after {} not",
+                    key
+                )));
+            }
+            let values = self.list()?;
+            return Ok(Node::Not(Box::new(Node::Comparison(Comparison {
+                key,
+                op: "in".to_string(),
+                values,
+            }))));
+        }
+
+        match self.advance() {
+            Some(Token::Op(op)) => Ok(Node::Comparison(Comparison {
+                key,
+                op,
+                values: vec![self.value()?],
+            })),
+            other => Err(Error::ExpressionError(format!(
+                "expected comparison after {}, got {:?}",
+                key, other
+            ))),
+        }
+    }
+}
+
+/// A parsed predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    source: String,
+    root: Node,
+}
+
+impl Predicate {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        };
+        let root = parser.disjunction()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(Error::ExpressionError(format!(
+                "unexpected token {:?} in {:?}",
+                token, source
+            )));
+        }
+
+        Ok(Predicate {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Evaluate the predicate on a trace or event
+    pub fn matches(&self, container: &dyn AttributeContainer) -> Result<bool> {
+        self.root.evaluate(container)
+    }
+
+    /// Compile the predicate into conditions in conjunctive normal form
+    pub fn cnf<'a, T: AttributeContainer + 'a>(&self) -> Result<CNF<'a, T>> {
+        Ok(self
+            .root
+            .clauses(false)?
+            .into_iter()
+            .map(|clause| {
+                clause
+                    .into_iter()
+                    .map(|(comparison, negated)| -> Condition<'a, T> {
+                        Box::new(move |x: &T| Ok(comparison.test(x)? != negated))
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::Event;
+
+    use super::*;
+
+    fn event(name: &str, cost: i64, timestamp: &str) -> Event {
+        let mut event = Event::default();
+        event.attributes.insert(("concept:name", name));
+        event.attributes.insert(("cost:total", cost));
+        event.attributes.insert((
+            "time:timestamp",
+            DateTime::parse_from_rfc3339(timestamp).unwrap(),
+        ));
+        event
+    }
+
+    #[test]
+    fn test_predicate() {
+        let events = [
+            event("a", 50, "2019-06-01T00:00:00+00:00"),
+            event("b", 150, "2020-06-01T00:00:00+00:00"),
+            event("c", 50, "2020-06-01T00:00:00+00:00"),
+            event("a", 150, "2021-06-01T00:00:00+02:00"),
+        ];
+        let cases = [
+            (
+                r#"concept:name in ["a","b"] and time:timestamp > 2020-01-01"#,
+                "ba",
+            ),
+            ("concept:name == 'a' or cost:total >= 100", "aba"),
+            ("not (concept:name = 'a' or cost:total < 100)", "b"),
+            ("concept:name not in ['a'] and not cost:total != 50", "c"),
+            ("time:timestamp <= 2020-06-01T00:00:00", "abc"),
+            (
+                "time:timestamp < 2021-06-01T01:00:00+02:00 and unknown == 1",
+                "",
+            ),
+            (
+                "not unknown == 1 and (cost:total > 100 or concept:name == 'c')",
+                "bca",
+            ),
+        ];
+
+        for (source, expected) in cases.iter() {
+            let predicate = Predicate::parse(source).unwrap();
+            let cnf = predicate.cnf::<Event>().unwrap();
+            let mut matched = String::new();
+            for event in events.iter() {
+                let cnf_match = cnf
+                    .iter()
+                    .all(|clause| clause.iter().any(|condition| condition(event).unwrap()));
+                assert_eq!(cnf_match, predicate.matches(event).unwrap(), "{}", source);
+                if cnf_match {
+                    matched += event
+                        .get_value("concept:name")
+                        .unwrap()
+                        .try_string()
+                        .unwrap();
+                }
+            }
+            assert_eq!(matched, *expected, "{}", source);
+        }
+
+        for source in [
+            "concept:name",
+            "concept:name == a",
+            "concept:name in 'a'",
+            "concept:name not 'a'",
+            "(cost:total > 1",
+            "cost:total > 1 cost:total",
+            "time:timestamp > 2020-13-01",
+        ]
+        .iter()
+        {
+            assert!(Predicate::parse(source).is_err(), "{}", source);
+        }
+        let nested = format!(
+            "{}cost:total > 1{}",
+            "(".repeat(200_000),
+            ")".repeat(200_000)
+        );
+        assert!(Predicate::parse(&nested)
+            .unwrap_err()
+            .to_string()
+            .contains("nested"));
+        assert!(Predicate::parse(&format!("{}cost:total > 1", "not ".repeat(200_000))).is_err());
+        assert!(Predicate::parse("concept:name > 1")
+            .unwrap()
+            .matches(&events[0])
+            .is_err());
+    }
+}
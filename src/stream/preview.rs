@@ -0,0 +1,207 @@
+//! Peek into event streams
+//!
+//! Interactive tools need to show what a log looks like before processing it as a whole. Any
+//! stream, most notably readers such as `XesReader`, can be previewed: `preview(n)` pulls only
+//! the first `n` traces or standalone events and profiles their attributes, i.e. which keys occur
+//! how often with which types.
+//!
+//! ```
+//! use std::io;
+//! use promi::stream::preview::StreamPreview;
+//! use promi::stream::xes::XesReader;
+//!
+//! let s = r#"<?xml version="1.0" encoding="UTF-8"?>
+//!            <log xes.version="1.0" xes.features="">
+//!                <trace><event><string key="concept:name" value="a"/></event></trace>
+//!                <trace><event><string key="concept:name" value="b"/></event></trace>
+//!            </log>"#;
+//!
+//! let mut reader = XesReader::from(io::BufReader::new(s.as_bytes()));
+//! let preview = reader.preview(1).unwrap();
+//!
+//! assert_eq!(preview.traces.len(), 1);
+//! assert!(!preview.exhausted);
+//! println!("{}", preview.profile);
+//! ```
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::stream::{
+    AttributeMap, AttributeType, AttributeValue, Component, Event, Meta, Stream, Trace,
+};
+use crate::Result;
+
+/// Occurrences of an attribute key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributeStats {
+    /// Number of components holding the attribute
+    pub count: usize,
+    /// Number of occurrences per type
+    pub types: Vec<(AttributeType, usize)>,
+    /// Up to `EXAMPLES` distinct values
+    pub examples: Vec<AttributeValue>,
+}
+
+impl AttributeStats {
+    pub const EXAMPLES: usize = 3;
+
+    fn observe(&mut self, value: &AttributeValue) {
+        self.count += 1;
+
+        let hint = value.type_hint();
+        match self.types.iter_mut().find(|(t, _)| *t == hint) {
+            Some((_, count)) => *count += 1,
+            None => self.types.push((hint, 1)),
+        }
+
+        if self.examples.len() < Self::EXAMPLES && !self.examples.contains(value) {
+            self.examples.push(value.clone());
+        }
+    }
+
+    /// The type observed most often
+    pub fn dominant_type(&self) -> Option<&AttributeType> {
+        self.types
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(t, _)| t)
+    }
+}
+
+/// Attribute keys of traces and events with their occurrences
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributeProfile {
+    pub traces: usize,
+    /// Number of events, both within traces and standalone
+    pub events: usize,
+    pub trace_attributes: BTreeMap<String, AttributeStats>,
+    pub event_attributes: BTreeMap<String, AttributeStats>,
+}
+
+impl AttributeProfile {
+    fn observe(stats: &mut BTreeMap<String, AttributeStats>, attributes: &AttributeMap) {
+        for (key, value, _) in attributes.iter() {
+            stats.entry(key.to_string()).or_default().observe(value);
+        }
+    }
+
+    /// Profile a trace and its events
+    pub fn observe_trace(&mut self, trace: &Trace) {
+        self.traces += 1;
+        Self::observe(&mut self.trace_attributes, &trace.attributes);
+        for event in trace.events.iter() {
+            self.observe_event(event);
+        }
+    }
+
+    pub fn observe_event(&mut self, event: &Event) {
+        self.events += 1;
+        Self::observe(&mut self.event_attributes, &event.attributes);
+    }
+}
+
+impl fmt::Display for AttributeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            ("trace", self.traces, &self.trace_attributes),
+            ("event", self.events, &self.event_attributes),
+        ];
+
+        for (scope, total, attributes) in sections.iter() {
+            writeln!(f, "{} attributes ({} {}s)", scope, total, scope)?;
+            for (key, stats) in attributes.iter() {
+                let types: Vec<_> = stats
+                    .types
+                    .iter()
+                    .map(|(t, n)| format!("{:?}: {}", t, n))
+                    .collect();
+                writeln!(
+                    f,
+                    "   {:<24} {:>6.1}%  [{}]  e.g. {:?}",
+                    key,
+                    100.0 * stats.count as f64 / (*total).max(1) as f64,
+                    types.join(", "),
+                    stats.examples
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The beginning of a stream
+#[derive(Debug, Clone, Default)]
+pub struct Preview {
+    pub meta: Option<Meta>,
+    pub traces: Vec<Trace>,
+    /// Events outside of traces
+    pub events: Vec<Event>,
+    pub profile: AttributeProfile,
+    /// Whether the stream ended within the preview
+    pub exhausted: bool,
+}
+
+/// Materialize the beginning of a stream
+pub trait StreamPreview: Stream {
+    /// Pull components until `n` traces or standalone events are read or the stream ends
+    ///
+    /// Previewed components are taken from the stream, i.e. later consumers only see the rest.
+    ///
+    fn preview(&mut self, n: usize) -> Result<Preview> {
+        let mut preview = Preview::default();
+
+        while preview.traces.len() + preview.events.len() < n {
+            match self.next()? {
+                Some(Component::Meta(meta)) => preview.meta = Some(meta),
+                Some(Component::Trace(trace)) => {
+                    preview.profile.observe_trace(&trace);
+                    preview.traces.push(trace);
+                }
+                Some(Component::Event(event)) => {
+                    preview.profile.observe_event(&event);
+                    preview.events.push(event);
+                }
+                None => {
+                    preview.exhausted = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(preview)
+    }
+}
+
+impl<T: Stream + ?Sized> StreamPreview for T {}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        let mut reader = load_example(&["book", "L1.xes"]);
+        let preview = reader.preview(3).unwrap();
+
+        assert!(preview.meta.is_some());
+        assert_eq!(preview.traces.len(), 3);
+        assert!(!preview.exhausted);
+
+        let profile = &preview.profile;
+        let activities = &profile.event_attributes["concept:name"];
+        assert_eq!(activities.count, profile.events);
+        assert_eq!(activities.dominant_type(), Some(&AttributeType::String));
+        assert!(activities.examples.len() <= AttributeStats::EXAMPLES);
+        assert!(profile.to_string().contains("concept:name"));
+
+        // the remainder of the stream is still available
+        let rest = reader.preview(usize::MAX).unwrap();
+        assert!(rest.exhausted);
+        assert!(rest.meta.is_none());
+    }
+}
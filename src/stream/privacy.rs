@@ -0,0 +1,340 @@
+//! Differential privacy for aggregate artifacts
+//!
+//! Aggregates such as directly-follows graphs may reveal individual cases, e.g. through rare
+//! paths. The `DpNoise` stream perturbs artifacts emitted by its inner stream with Laplace noise,
+//! scaled by `sensitivity / epsilon`, so that they can be shared with differential privacy.
+//!
+//! The sensitivity is the L1 sensitivity of all released counts, i.e. the maximum sum of the
+//! changes to all counts that a single case can cause. A case of `n` events adds to `n`
+//! activity counts, `n - 1` relations and one start and end each, see `dfg_sensitivity`. It has
+//! to be chosen by the user, as it depends on the log at hand.
+//!
+//! The keys, i.e. activities and relations, are released according to a `KeySet`. Only a public
+//! set of activities, known without looking at the log, gives ε-differential privacy: noise is
+//! added to all relations among them, observed or not, and observed activities that are not
+//! public are dropped. Otherwise, observed keys are released if their noisy count reaches a
+//! threshold. A key that a single case contributes to survives with a probability of at most
+//! `exp(-(threshold - sensitivity) * epsilon / sensitivity) / 2`, which is the δ of
+//! (ε, δ)-differential privacy. Durations are not protected and hence removed.
+//!
+
+use std::collections::BTreeMap;
+
+use rand::{distributions::Open01, random, Rng};
+use rand_pcg::Pcg64;
+
+use crate::stream::dfg::Dfg;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, AttributeType, ResOpt, Stream};
+use crate::{Error, Result};
+
+/// Laplace mechanism
+pub struct Laplace {
+    scale: f64,
+    rng: Pcg64,
+}
+
+impl Laplace {
+    pub fn new(epsilon: f64, sensitivity: f64, random_state: Option<u128>) -> Result<Self> {
+        if !(epsilon > 0.0 && sensitivity > 0.0) {
+            return Err(Error::AttributeError(format!(
+                "epsilon and sensitivity must be positive, got {} and {}",
+                epsilon, sensitivity
+            )));
+        }
+
+        Ok(Laplace {
+            scale: sensitivity / epsilon,
+            rng: Pcg64::new(random_state.unwrap_or_else(random), 0),
+        })
+    }
+
+    /// Draw a sample by inverting the cumulative distribution function
+    pub fn sample(&mut self) -> f64 {
+        let u: f64 = self.rng.sample::<f64, _>(Open01) - 0.5;
+        -self.scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+    }
+
+    /// Perturb a count, the result is not negative
+    pub fn perturb(&mut self, value: f64) -> f64 {
+        (value + self.sample()).max(0.0)
+    }
+}
+
+/// L1 sensitivity of a directly-follows graph for cases of at most `max_events` events
+pub fn dfg_sensitivity(max_events: usize) -> f64 {
+    (2 * max_events + 1) as f64
+}
+
+/// Keys of an aggregate that are released
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySet {
+    /// Keys known in advance, e.g. the activities of a process model
+    Public(Vec<String>),
+    /// Observed keys whose noisy count reaches the threshold
+    Threshold(f64),
+}
+
+/// Perturb observed counts, drop those that don't reach the threshold
+fn release_observed(counts: &mut BTreeMap<String, f64>, threshold: f64, noise: &mut Laplace) {
+    for weight in counts.values_mut() {
+        *weight = noise.perturb(*weight);
+    }
+    counts.retain(|_, weight| *weight > 0.0 && *weight >= threshold);
+}
+
+/// Perturb the counts of all public keys, observed or not, drop all others
+fn release_public(counts: &mut BTreeMap<String, f64>, keys: &[String], noise: &mut Laplace) {
+    let mut released = BTreeMap::new();
+    for key in keys.iter() {
+        let weight = noise.perturb(counts.get(key).copied().unwrap_or(0.0));
+        if weight > 0.0 {
+            released.insert(key.clone(), weight);
+        }
+    }
+    *counts = released;
+}
+
+/// Artifacts that can be released with differential privacy
+pub trait Privatize {
+    fn privatize(&mut self, noise: &mut Laplace, keys: &KeySet);
+}
+
+impl Privatize for Dfg {
+    fn privatize(&mut self, noise: &mut Laplace, keys: &KeySet) {
+        match keys {
+            KeySet::Public(activities) => {
+                release_public(&mut self.activities, activities, noise);
+                release_public(&mut self.start, activities, noise);
+                release_public(&mut self.end, activities, noise);
+
+                let mut edges = BTreeMap::new();
+                for activity in activities.iter() {
+                    let mut targets = self.edges.remove(activity).unwrap_or_default();
+                    release_public(&mut targets, activities, noise);
+                    if !targets.is_empty() {
+                        edges.insert(activity.clone(), targets);
+                    }
+                }
+                self.edges = edges;
+            }
+            KeySet::Threshold(threshold) => {
+                release_observed(&mut self.activities, *threshold, noise);
+                release_observed(&mut self.start, *threshold, noise);
+                release_observed(&mut self.end, *threshold, noise);
+                for targets in self.edges.values_mut() {
+                    release_observed(targets, *threshold, noise);
+                }
+                self.edges.retain(|_, targets| !targets.is_empty());
+            }
+        }
+
+        self.performance.clear();
+    }
+}
+
+/// Apply noise to an artifact, returns false if the artifact is not supported
+pub fn privatize_artifact(artifact: &mut AnyArtifact, noise: &mut Laplace, keys: &KeySet) -> bool {
+    if let Some(dfg) = artifact.downcast_mut::<Dfg>() {
+        dfg.privatize(noise, keys);
+        true
+    } else {
+        false
+    }
+}
+
+/// Perturb supported artifacts of the inner stream, others are passed unchanged
+///
+/// Only artifacts emitted at the end of the stream are covered, i.e. periodic snapshots sent
+/// through channels are not.
+///
+pub struct DpNoise<T: Stream> {
+    stream: T,
+    noise: Laplace,
+    keys: KeySet,
+}
+
+impl<T: Stream> DpNoise<T> {
+    pub fn new(stream: T, noise: Laplace, keys: KeySet) -> Self {
+        DpNoise {
+            stream,
+            noise,
+            keys,
+        }
+    }
+}
+
+impl<T: Stream> Stream for DpNoise<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        self.stream.next()
+    }
+
+    // the noise layer is transparent, i.e. it must not add a level of artifacts
+    fn emit_artifacts(&mut self) -> Result<Vec<Vec<AnyArtifact>>> {
+        let mut artifacts = self.stream.emit_artifacts()?;
+        for artifact in artifacts.iter_mut().flatten() {
+            privatize_artifact(artifact, &mut self.noise, &self.keys);
+        }
+        Ok(artifacts)
+    }
+}
+
+impl PluginProvider for DpNoise<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "DpNoise",
+            "Perturb aggregate artifacts (e.g. directly-follows graphs) with Laplace noise",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream whose artifacts are perturbed")
+                    .typed_attr(
+                        "epsilon",
+                        "Privacy budget, smaller values add more noise",
+                        AttributeType::Float,
+                    )
+                    .typed_attr(
+                        "sensitivity",
+                        "Maximum sum of the contributions of a single case to all counts",
+                        AttributeType::Float,
+                    )
+                    .optional_attr(
+                        "activities",
+                        "Public list of activities, the only ones released",
+                        AttributeType::List,
+                    )
+                    .default_attr(
+                        "threshold",
+                        "Minimum noisy count of observed keys without public activities",
+                        |k| (k, 10.0).into(),
+                    )
+                    .optional_attr(
+                        "seed",
+                        "Seed of the random number generator",
+                        AttributeType::Int,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let noise = Laplace::new(
+                        *parameters.acquire_attribute("epsilon")?.value.try_float()?,
+                        *parameters
+                            .acquire_attribute("sensitivity")?
+                            .value
+                            .try_float()?,
+                        match parameters.acquire_attribute("seed") {
+                            Ok(s) => Some(*(s.value.try_int()?) as u128),
+                            _ => None,
+                        },
+                    )?;
+                    let keys = match parameters.acquire_attribute("activities") {
+                        Ok(activities) => KeySet::Public(
+                            activities
+                                .value
+                                .try_list()?
+                                .iter()
+                                .map(|a| Ok(a.value.try_string()?.to_string()))
+                                .collect::<Result<Vec<_>>>()?,
+                        ),
+                        Err(_) => KeySet::Threshold(
+                            *parameters
+                                .acquire_attribute("threshold")?
+                                .value
+                                .try_float()?,
+                        ),
+                    };
+
+                    Ok(DpNoise::new(parameters.acquire_stream("inner")?, noise, keys).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::dfg::OnlineDfg;
+    use crate::stream::observer::Handler;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Event, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_laplace() {
+        assert!(Laplace::new(0.0, 1.0, None).is_err());
+
+        let mut noise = Laplace::new(0.5, 1.0, Some(42)).unwrap();
+        let samples: Vec<f64> = (0..20000).map(|_| noise.sample()).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / 20000.0;
+
+        // Laplace(0, b) has mean 0 and variance 2b², b = 2
+        assert!(mean.abs() < 0.1, "{}", mean);
+        assert!((variance - 8.0).abs() < 0.5, "{}", variance);
+    }
+
+    fn dfg_of(traces: &[(&[&str], usize)]) -> Buffer {
+        let mut buffer = Buffer::default();
+        for (activities, count) in traces.iter() {
+            for _ in 0..*count {
+                let mut trace = Trace::default();
+                for activity in activities.iter() {
+                    let mut event = Event::default();
+                    event.attributes.insert(("concept:name", *activity));
+                    trace.events.push(event);
+                }
+                buffer.push(Ok(Some(Component::Trace(trace))));
+            }
+        }
+        buffer
+    }
+
+    fn release(buffer: Buffer, keys: KeySet) -> Dfg {
+        let noise = Laplace::new(1.0, dfg_sensitivity(2), Some(0)).unwrap();
+        let mut stream = DpNoise::new(OnlineDfg::default().into_observer(buffer), noise, keys);
+        let artifacts = consume(&mut stream).unwrap();
+        AnyArtifact::find::<Dfg>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_dp_noise() {
+        assert_eq!(dfg_sensitivity(2), 5.0);
+        assert!(matches!(
+            Laplace::new(-1.0, 1.0, None),
+            Err(Error::AttributeError(_))
+        ));
+
+        let traces: &[(&[&str], usize)] = &[(&["a", "b"], 1000), (&["rare"], 1)];
+
+        // observed keys below the threshold are dropped
+        let dfg = release(dfg_of(traces), KeySet::Threshold(50.0));
+        assert!((dfg.weight("a", "b") - 1000.0).abs() < 100.0);
+        assert!(!dfg.activities.contains_key("rare"));
+        assert!(!dfg.start.contains_key("rare"));
+        assert!(dfg.activities.values().all(|w| *w >= 50.0));
+        assert!(dfg.performance.is_empty());
+
+        // only public activities are released, whether observed or not
+        let public = KeySet::Public(vec!["a".into(), "b".into(), "c".into()]);
+        let dfg = release(dfg_of(traces), public);
+        assert!(!dfg.activities.contains_key("rare"));
+        assert!(dfg
+            .edges
+            .iter()
+            .all(|(from, targets)| from != "rare" && !targets.contains_key("rare")));
+        assert!((dfg.activities["a"] - 1000.0).abs() < 100.0);
+        assert!(dfg.weight("b", "a") < 100.0);
+    }
+}
@@ -0,0 +1,301 @@
+//! Progress reporting of long-running pipelines
+//!
+//! The `Progress` stream forwards its inner stream and periodically reports how far it got: the
+//! number of components, traces and events, the throughput and, if the source knows its position,
+//! the bytes read so far (see `Stream::read_position`). Reports are passed to a callback or logged
+//! at info level. A final report is made at the end of the stream and emitted as artifact.
+//!
+//! `Sink::consume_with_progress` is a shortcut to consume a stream with a progress callback.
+//!
+
+use std::any::Any;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AnyArtifact, Artifact, AttributeType, Component, ResOpt, Stream};
+use crate::{Error, Result};
+
+/// Snapshot of the progress of a stream
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    pub components: usize,
+    pub traces: usize,
+    /// Events, in traces or standalone
+    pub events: usize,
+    /// Bytes read from the source, if known
+    pub bytes: Option<u64>,
+    /// Size of the source in bytes, if known
+    pub total: Option<u64>,
+    /// Time passed since the first component was requested
+    pub seconds: f64,
+    /// Whether the stream is exhausted
+    pub finished: bool,
+}
+
+impl ProgressReport {
+    /// Components per second
+    pub fn throughput(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.components as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Share of the source that has been read, if its size is known
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.bytes, self.total) {
+            (Some(bytes), Some(total)) if total > 0 => Some(bytes as f64 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} components ({} traces, {} events) in {:.1}s, {:.1}/s",
+            self.components,
+            self.traces,
+            self.events,
+            self.seconds,
+            self.throughput()
+        )?;
+        match (self.bytes, self.fraction()) {
+            (_, Some(fraction)) => write!(f, ", {:.1}% read", 100.0 * fraction)?,
+            (Some(bytes), None) => write!(f, ", {} bytes read", bytes)?,
+            _ => (),
+        }
+        if self.finished {
+            write!(f, ", finished")?;
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ProgressReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+type Callback<'a> = Box<dyn FnMut(&ProgressReport) + Send + 'a>;
+
+/// Report the progress of a stream periodically
+///
+/// A report is made whenever the interval has passed, every given number of components if set, and
+/// once at the end of the stream.
+///
+pub struct Progress<'a, T: Stream> {
+    stream: T,
+    report: ProgressReport,
+    interval: Duration,
+    every: Option<usize>,
+    callback: Option<Callback<'a>>,
+    start: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl<'a, T: Stream> Progress<'a, T> {
+    pub fn new(stream: T) -> Self {
+        Progress {
+            stream,
+            report: ProgressReport::default(),
+            interval: Duration::from_secs(10),
+            every: None,
+            callback: None,
+            start: None,
+            last: None,
+        }
+    }
+
+    /// Set the time between reports
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Report every given number of components in addition
+    pub fn every(mut self, every: usize) -> Self {
+        self.every = Some(every);
+        self
+    }
+
+    /// Pass reports to a callback instead of logging them
+    pub fn callback<F: FnMut(&ProgressReport) + Send + 'a>(mut self, callback: F) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// The progress made so far
+    pub fn report(&self) -> &ProgressReport {
+        &self.report
+    }
+
+    fn emit(&mut self, now: Instant) {
+        self.last = Some(now);
+        self.report.seconds = self
+            .start
+            .map(|start| (now - start).as_secs_f64())
+            .unwrap_or_default();
+        if let Some(position) = self.stream.read_position() {
+            self.report.bytes = Some(position.bytes);
+            self.report.total = position.total;
+        }
+
+        match self.callback.as_mut() {
+            Some(callback) => callback(&self.report),
+            None => info!("{}", self.report),
+        }
+    }
+}
+
+impl<'a, T: Stream> Stream for Progress<'a, T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if self.start.is_none() {
+            self.start = Some(Instant::now());
+            self.last = self.start;
+        }
+
+        let component = self.stream.next()?;
+        let now = Instant::now();
+        match &component {
+            Some(component) => {
+                self.report.components += 1;
+                match component {
+                    Component::Trace(trace) => {
+                        self.report.traces += 1;
+                        self.report.events += trace.events.len();
+                    }
+                    Component::Event(_) => self.report.events += 1,
+                    Component::Meta(_) => (),
+                }
+
+                let due = self
+                    .every
+                    .is_some_and(|every| self.report.components.is_multiple_of(every))
+                    || self.last.is_some_and(|last| now - last >= self.interval);
+                if due {
+                    self.emit(now);
+                }
+            }
+            None if !self.report.finished => {
+                self.report.finished = true;
+                self.emit(now);
+            }
+            None => (),
+        }
+
+        Ok(component)
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.report.clone().into()])
+    }
+}
+
+impl PluginProvider for Progress<'_, Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Progress",
+            "Log the progress of a stream periodically",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be observed")
+                    .default_attr("interval", "Seconds between reports", |k| (k, 10).into())
+                    .optional_attr(
+                        "every",
+                        "Number of components between reports",
+                        AttributeType::Int,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let interval = *parameters.acquire_attribute("interval")?.value.try_int()?;
+                    if interval <= 0 {
+                        return Err(Error::AttributeError(format!(
+                            "interval is expected to be positive, got {}",
+                            interval
+                        )));
+                    }
+                    let mut progress = Progress::new(parameters.acquire_stream("inner")?)
+                        .interval(Duration::from_secs(interval as u64));
+
+                    // optional number of components between reports
+                    if let Ok(every) = parameters.acquire_attribute("every") {
+                        progress = progress.every((*every.value.try_int()?).max(1) as usize);
+                    }
+
+                    Ok(progress.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::stream::xes::XesReaderOptions;
+    use crate::stream::Sink;
+
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let path = join_static!("xes", "book", "L1.xes");
+        let mut reports = Vec::new();
+        let mut stream = Progress::new(XesReaderOptions::default().open(&path).unwrap())
+            .every(3)
+            .callback(|r: &ProgressReport| reports.push(r.clone()));
+        let artifacts = crate::stream::void::consume(&mut stream).unwrap();
+        drop(stream);
+
+        // meta data and six traces, reported after three and six components and at the end
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[1].components, 6);
+        let last = reports.last().unwrap();
+        assert_eq!((last.components, last.traces, last.events), (7, 6, 23));
+        assert!(last.finished);
+        assert_eq!(last.fraction(), Some(1.0));
+        assert!(last
+            .to_string()
+            .starts_with("7 components (6 traces, 23 events) in "));
+        assert_eq!(
+            AnyArtifact::find::<ProgressReport>(&mut artifacts.iter().flatten()),
+            Some(last)
+        );
+
+        // sources of unknown size
+        let file = std::fs::File::open(&path).unwrap();
+        let mut reader = crate::stream::xes::XesReader::from(BufReader::new(file));
+        let mut sink = crate::stream::log::Log::default();
+        let mut last = None;
+        sink.consume_with_progress(&mut reader, Duration::from_secs(60), |r| {
+            last = Some(r.clone())
+        })
+        .unwrap();
+        let last = last.unwrap();
+        assert!(last.finished && last.total.is_none() && last.bytes.unwrap() > 0);
+        assert_eq!(sink.traces.len(), 6);
+    }
+}
@@ -0,0 +1,337 @@
+//! Keep, drop and rename attributes by key patterns
+//!
+//! Streams often carry more attributes than the next stage of a pipeline should see, e.g. personal
+//! data that must not be written to disk or sent to another host. The `Project` handler strips
+//! and renames attributes of meta data, traces and events on the fly. Keys are selected by
+//! `KeyPattern`s, either globs (`org:*`, `cost:?`) or, prefixed with `re:`, regular expressions
+//! (`re:^custom\.\d+$`). Patterns always match the whole key.
+//!
+//! Rules are applied in order of
+//! 1. keeping, i.e. if any keep patterns are given, attributes that match none of them are dropped,
+//! 2. dropping attributes that match any drop pattern and
+//! 3. renaming attributes by the first matching rename rule. Targets may refer to the wildcards of
+//!    a glob or to the groups of a regular expression as `$1`, `$2`, ..., e.g. `org:*` to
+//!    `resource:$1`. A renamed attribute replaces an existing attribute of the same key.
+//!
+//! Only top-level attributes are projected, nested attributes move along with their parent.
+//! Globals of meta data are projected along with the scope they declare attributes for, such that
+//! streams keep validating. Classifiers are left as is.
+//!
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    Attribute, AttributeMap, AttributeType, ComponentType, Event, Meta, Scope, Stream, Trace,
+};
+use crate::{Error, Result};
+
+/// Pattern that selects attributes by their key
+#[derive(Debug, Clone)]
+pub struct KeyPattern {
+    source: String,
+    regex: Regex,
+}
+
+impl KeyPattern {
+    /// Glob pattern, `*` matches any sequence of characters and `?` a single character
+    pub fn glob(glob: &str) -> Result<Self> {
+        let mut pattern = String::from("^");
+        for c in glob.chars() {
+            match c {
+                '*' => pattern.push_str("(.*)"),
+                '?' => pattern.push_str("(.)"),
+                c => pattern.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        pattern.push('$');
+
+        Ok(KeyPattern {
+            source: glob.to_string(),
+            regex: Regex::new(&pattern).map_err(|e| Error::AttributeError(e.to_string()))?,
+        })
+    }
+
+    /// Regular expression that has to match the whole key
+    pub fn regex(regex: &str) -> Result<Self> {
+        Ok(KeyPattern {
+            source: format!("re:{}", regex),
+            regex: Regex::new(&format!("^(?:{})$", regex))
+                .map_err(|e| Error::AttributeError(e.to_string()))?,
+        })
+    }
+
+    pub fn is_match(&self, key: &str) -> bool {
+        self.regex.is_match(key)
+    }
+
+    /// Replace the key by the target if it matches, expanding `$1`, `$2`, ...
+    pub fn rename(&self, key: &str, target: &str) -> Option<String> {
+        let captures = self.regex.captures(key)?;
+        let mut renamed = String::new();
+        captures.expand(target, &mut renamed);
+        Some(renamed)
+    }
+}
+
+impl FromStr for KeyPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix("re:") {
+            Some(regex) => KeyPattern::regex(regex),
+            None => KeyPattern::glob(s),
+        }
+    }
+}
+
+impl fmt::Display for KeyPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Keep, drop and rename attributes of stream components
+pub struct Project {
+    keep: Vec<KeyPattern>,
+    drop: Vec<KeyPattern>,
+    rename: Vec<(KeyPattern, String)>,
+    components: Vec<ComponentType>,
+}
+
+impl Default for Project {
+    /// Leave all attributes of all components as they are
+    fn default() -> Self {
+        Project {
+            keep: Vec::new(),
+            drop: Vec::new(),
+            rename: Vec::new(),
+            components: vec![
+                ComponentType::Meta,
+                ComponentType::Trace,
+                ComponentType::Event,
+            ],
+        }
+    }
+}
+
+impl Project {
+    /// Keep attributes that match the pattern, drop all others that match no keep pattern
+    pub fn keep(mut self, pattern: KeyPattern) -> Self {
+        self.keep.push(pattern);
+        self
+    }
+
+    pub fn drop(mut self, pattern: KeyPattern) -> Self {
+        self.drop.push(pattern);
+        self
+    }
+
+    /// Rename attributes that match the pattern to the target
+    pub fn rename<T: Into<String>>(mut self, pattern: KeyPattern, target: T) -> Self {
+        self.rename.push((pattern, target.into()));
+        self
+    }
+
+    /// Project attributes of the given kinds of components only
+    pub fn components<I: IntoIterator<Item = ComponentType>>(mut self, components: I) -> Self {
+        self.components = components.into_iter().collect();
+        self
+    }
+
+    /// New key of an attribute, if it's not dropped
+    fn project_key(&self, key: &str) -> Option<String> {
+        if !self.keep.is_empty() && !self.keep.iter().any(|p| p.is_match(key)) {
+            return None;
+        }
+        if self.drop.iter().any(|p| p.is_match(key)) {
+            return None;
+        }
+
+        Some(
+            self.rename
+                .iter()
+                .find_map(|(pattern, target)| pattern.rename(key, target))
+                .unwrap_or_else(|| key.to_string()),
+        )
+    }
+
+    fn project_all(&self, attributes: Vec<Attribute>) -> Vec<Attribute> {
+        attributes
+            .into_iter()
+            .filter_map(|attribute| {
+                self.project_key(&attribute.key).map(|key| Attribute {
+                    key: key.into(),
+                    ..attribute
+                })
+            })
+            .collect()
+    }
+
+    fn project(&self, component: ComponentType, attributes: AttributeMap) -> AttributeMap {
+        if !self.components.contains(&component) {
+            return attributes;
+        }
+        AttributeMap::from(
+            self.project_all(attributes.into_iter().collect())
+                .into_iter(),
+        )
+    }
+}
+
+impl Handler for Project {
+    fn on_meta(&mut self, mut meta: Meta) -> Result<Meta> {
+        for global in meta.globals.iter_mut() {
+            let component = match global.scope {
+                Scope::Trace => ComponentType::Trace,
+                Scope::Event => ComponentType::Event,
+            };
+            if self.components.contains(&component) {
+                global.attributes = self.project_all(std::mem::take(&mut global.attributes));
+            }
+        }
+
+        meta.attributes = self.project(ComponentType::Meta, meta.attributes);
+        Ok(meta)
+    }
+
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        trace.attributes = self.project(ComponentType::Trace, trace.attributes);
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, _in_trace: bool) -> Result<Option<Event>> {
+        event.attributes = self.project(ComponentType::Event, event.attributes);
+        Ok(Some(event))
+    }
+}
+
+impl PluginProvider for Project {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Project",
+            "Keep, drop and rename attributes by glob or regex (prefixed with re:) key patterns",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be projected")
+                    .default_attr(
+                        "components",
+                        "Comma separated kinds of components to project, i.e. meta, trace, event",
+                        |k| (k, "meta,trace,event").into(),
+                    )
+                    .optional_attr("keep", "Patterns of keys to keep", AttributeType::List)
+                    .optional_attr("drop", "Patterns of keys to drop", AttributeType::List)
+                    .optional_attr(
+                        "rename",
+                        "Attributes keyed by pattern with the new key as value",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut project = Project::default();
+
+                    let components = parameters.acquire_attribute("components")?;
+                    let components = components
+                        .value
+                        .try_string()?
+                        .split(',')
+                        .map(|c| match c.trim() {
+                            "meta" => Ok(ComponentType::Meta),
+                            "trace" => Ok(ComponentType::Trace),
+                            "event" => Ok(ComponentType::Event),
+                            other => Err(Error::AttributeError(format!(
+                                "unknown component type {:?}",
+                                other
+                            ))),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    project = project.components(components);
+
+                    // lists of patterns
+                    for key in ["keep", "drop"].iter() {
+                        if let Ok(patterns) = parameters.acquire_attribute(key) {
+                            for pattern in patterns.value.try_list()?.iter() {
+                                let pattern = pattern.value.try_string()?.parse()?;
+                                project = match *key {
+                                    "keep" => project.keep(pattern),
+                                    _ => project.drop(pattern),
+                                };
+                            }
+                        }
+                    }
+
+                    // list of attributes, keyed by pattern with the target as value
+                    if let Ok(rules) = parameters.acquire_attribute("rename") {
+                        for rule in rules.value.try_list()?.iter() {
+                            project = project.rename(rule.key.parse()?, rule.value.try_string()?);
+                        }
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, project)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::log::Log;
+    use crate::stream::validator::Validator;
+    use crate::stream::{AttributeContainer, Sink};
+
+    use super::*;
+
+    #[test]
+    fn test_project() {
+        let project = Project::default()
+            .drop("lifecycle:*".parse().unwrap())
+            .drop("re:org:(role|group)".parse().unwrap())
+            .rename("org:*".parse().unwrap(), "resource:$1")
+            .rename("concept:name".parse().unwrap(), "activity")
+            .components(vec![ComponentType::Event]);
+        let mut log = Log::default();
+        log.consume(&mut project.into_observer(load_example(&["book", "L1.xes"])))
+            .unwrap();
+
+        let event = &log.traces[0].events[0];
+        let keys: Vec<_> = event.attributes.iter().map(|(k, _, _)| k).collect();
+        assert!(keys.contains(&"activity"));
+        assert!(!keys.iter().any(|k| k.starts_with("lifecycle:")));
+        assert!(!keys.iter().any(|k| k.starts_with("org:")));
+        assert!(log.traces[0].get_value("concept:name").is_some());
+
+        // keep patterns drop all other attributes
+        let project = Project::default().keep("concept:?ame".parse().unwrap());
+        let mut log = Log::default();
+        log.consume(&mut project.into_observer(load_example(&["book", "L1.xes"])))
+            .unwrap();
+        let event = &log.traces[0].events[0];
+        assert_eq!(event.attributes.len(), 1);
+        assert_eq!(log.traces[0].attributes.len(), 1);
+
+        // globals follow, so the stream keeps validating
+        let project = Project::default().rename("re:org:(.*)".parse().unwrap(), "staff:$1");
+        let observer = project.into_observer(load_example(&["test", "extension_full.xes"]));
+        let mut log = Log::default();
+        log.consume(&mut Validator::default().into_observer(observer))
+            .unwrap();
+        assert!(log.traces[0].events[0]
+            .get_value("staff:resource")
+            .is_some());
+
+        assert!("re:(".parse::<KeyPattern>().is_err());
+        assert_eq!(
+            KeyPattern::glob("a.*").unwrap().rename("a.b.c", "$1"),
+            Some("b.c".to_string())
+        );
+        assert!(!KeyPattern::glob("a.*").unwrap().is_match("abc"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Provenance records for exported logs
+//!
+//! Logs exported by a pipeline are often consumed by people who didn't run it. A
+//! `ProvenanceWriter` wraps an exporting sink and, once the stream is closed, writes a JSON sidecar
+//! next to the output, e.g. `log.xes.provenance.json`:
+//!
+//! ```json
+//! {
+//!   "promi_version": "0.0.0",
+//!   "pipeline": "a3f1c2e4b5d6e7f8",
+//!   "sources": ["raw/orders.csv"],
+//!   "output": "log.xes",
+//!   "counts": { "meta": 1, "traces": 120, "events": 1843 },
+//!   "exported_at": "2021-03-04T10:30:00+00:00"
+//! }
+//! ```
+//!
+//! The pipeline is identified by the fingerprint of its definition, see `Graph::fingerprint`.
+//! Exporting plugins write a sidecar if the optional attribute `provenance` is set. Sources and
+//! pipeline fingerprint aren't configured but provided by the graph when building the plugin, see
+//! `PIPELINE_KEY` and `SOURCES_KEY`.
+//!
+
+use std::any::Any;
+use std::fs::File;
+use std::io::BufWriter;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::clock::{SharedClock, SystemClock};
+use crate::stream::plugin::{Declaration, Parameters};
+use crate::stream::void::ComponentCounts;
+use crate::stream::{AnyArtifact, Artifact, AttributeType, Component, Sink};
+use crate::{DateTime, Error, Result};
+
+/// Reserved attribute the graph provides exporting plugins with the pipeline fingerprint by
+pub const PIPELINE_KEY: &str = "__pipeline__";
+
+/// Reserved attribute the graph provides exporting plugins with the paths read by sources by
+pub const SOURCES_KEY: &str = "__sources__";
+
+/// Path of the sidecar of an output file
+pub fn sidecar_path(output: &str) -> String {
+    format!("{}.provenance.json", output)
+}
+
+/// Stable 64 bit FNV-1a hash, rendered as hex string
+pub fn fingerprint(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Where an exported log came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub promi_version: String,
+    pub pipeline: Option<String>,
+    pub sources: Vec<String>,
+    pub output: String,
+    pub counts: ComponentCounts,
+    pub exported_at: Option<DateTime>,
+}
+
+impl Provenance {
+    pub fn new<T: Into<String>>(output: T) -> Self {
+        Provenance {
+            promi_version: crate::VERSION.to_string(),
+            pipeline: None,
+            sources: Vec::new(),
+            output: output.into(),
+            counts: ComponentCounts::default(),
+            exported_at: None,
+        }
+    }
+
+    /// Write the record as JSON sidecar of the output
+    pub fn save(&self) -> Result<()> {
+        let path = sidecar_path(&self.output);
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), self)
+            .map_err(|e| Error::StreamError(format!("unable to write {:?}: {}", path, e)))
+    }
+
+    /// Read the sidecar of an output
+    pub fn load(output: &str) -> Result<Self> {
+        let path = sidecar_path(output);
+        serde_json::from_reader(std::io::BufReader::new(File::open(&path)?))
+            .map_err(|e| Error::StreamError(format!("unable to read {:?}: {}", path, e)))
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Provenance {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Write a provenance sidecar once the wrapped sink is closed
+pub struct ProvenanceWriter<S: Sink> {
+    inner: S,
+    provenance: Provenance,
+    clock: SharedClock,
+}
+
+impl<S: Sink> ProvenanceWriter<S> {
+    pub fn new<T: Into<String>>(inner: S, output: T) -> Self {
+        ProvenanceWriter {
+            inner,
+            provenance: Provenance::new(output),
+            clock: SystemClock::shared(),
+        }
+    }
+
+    /// Take the export time from the given clock instead of the system
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record a source file of the exported log
+    pub fn source<T: Into<String>>(mut self, source: T) -> Self {
+        self.provenance.sources.push(source.into());
+        self
+    }
+
+    /// Record the fingerprint of the pipeline definition
+    pub fn pipeline<T: Into<String>>(mut self, fingerprint: T) -> Self {
+        self.provenance.pipeline = Some(fingerprint.into());
+        self
+    }
+}
+
+impl<'a> ProvenanceWriter<Box<dyn Sink + 'a>> {
+    /// Declare the optional plugin attribute `provenance`
+    pub fn declare(declaration: Declaration) -> Declaration {
+        declaration.optional_attr(
+            "provenance",
+            "Record a provenance sidecar next to the output",
+            AttributeType::Boolean,
+        )
+    }
+
+    /// Wrap an exporting plugin's sink if its attribute `provenance` is set
+    pub fn from_parameters(
+        sink: Box<dyn Sink + 'a>,
+        output: &str,
+        parameters: &mut Parameters,
+    ) -> Result<Box<dyn Sink + 'a>> {
+        match parameters.acquire_attribute("provenance") {
+            Ok(attribute) if *attribute.value.try_boolean()? => (),
+            Ok(_) | Err(_) => return Ok(sink),
+        }
+
+        let mut writer = ProvenanceWriter::new(sink, output);
+        if let Ok(attribute) = parameters.acquire_attribute(SOURCES_KEY) {
+            for source in attribute.value.try_list()? {
+                writer = writer.source(source.value.try_string()?);
+            }
+        }
+        if let Ok(attribute) = parameters.acquire_attribute(PIPELINE_KEY) {
+            writer = writer.pipeline(attribute.value.try_string()?);
+        }
+
+        Ok(writer.into_boxed())
+    }
+}
+
+impl<S: Sink> Sink for ProvenanceWriter<S> {
+    fn on_open(&mut self) -> Result<()> {
+        self.inner.on_open()
+    }
+
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.provenance.counts.add(&component);
+        self.inner.on_component(component)
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.inner.on_close()?;
+
+        self.provenance.exported_at = self.clock.now();
+        self.provenance.save()
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        self.inner.on_error(error)
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        let mut artifacts = self.inner.on_emit_artifacts()?;
+        artifacts.push(self.provenance.clone().into());
+        Ok(artifacts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use crate::stream::buffer::Buffer;
+    use crate::stream::clock::MockClock;
+    use crate::stream::xes::XesWriter;
+    use crate::stream::{Event, Meta, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint(b""), "cbf29ce484222325");
+        assert_eq!(fingerprint(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_provenance_writer() {
+        let path =
+            std::env::temp_dir().join(format!("promi_provenance_{}.xes", std::process::id()));
+        let output = path.to_str().unwrap();
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        let mut trace = Trace::default();
+        trace.events.push(Event::default());
+        trace.events.push(Event::default());
+        buffer.push(Ok(Some(Component::Trace(trace))));
+        buffer.push(Ok(Some(Component::Event(Event::default()))));
+
+        let sink = XesWriter::new(File::create(&path).unwrap());
+        let now = DateTime::parse_from_rfc3339("2021-03-04T10:30:00+00:00").unwrap();
+        let mut writer = ProvenanceWriter::new(sink, output)
+            .source("orders.csv")
+            .pipeline("0123456789abcdef")
+            .clock(Arc::new(MockClock::new(now)));
+        let artifacts = writer.consume(&mut buffer).unwrap();
+
+        let provenance = Provenance::load(output).unwrap();
+        assert_eq!(
+            Some(&provenance),
+            AnyArtifact::find::<Provenance>(&mut artifacts.iter().flatten())
+        );
+        assert_eq!(provenance.sources, ["orders.csv"]);
+        assert_eq!(
+            provenance.counts,
+            ComponentCounts {
+                meta: 1,
+                traces: 1,
+                events: 3
+            }
+        );
+        assert_eq!(provenance.exported_at, Some(now));
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(sidecar_path(output)).unwrap();
+    }
+}
@@ -0,0 +1,613 @@
+//! Consistent pseudonymization of attribute values
+//!
+//! The `Pseudonymize` handler replaces string values of selected attributes, e.g. `org:resource`,
+//! by random pseudonyms. The mapping from original values to pseudonyms is kept in a `Vault` that
+//! may be persisted to an encrypted file. Loading the vault in a later run re-applies the same
+//! pseudonyms to values seen before, e.g. for monthly exports of a log, and allows to reverse the
+//! pseudonymization for whoever holds the key.
+//!
+//! Vault files are encrypted with XChaCha20-Poly1305 under a 256 bit key, given as 64 hex digits.
+//! Each file is encrypted with a fresh random nonce. The encryption is authenticated, i.e. reading
+//! a vault with the wrong key or after it was tampered with fails. The plugin reads the key from a
+//! key file or an environment variable (`PROMI_VAULT_KEY` by default), never from the pipeline
+//! configuration.
+//!
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::{random, Rng};
+use rand_pcg::Pcg64;
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, AttributeMap, AttributeType, AttributeValue, Event, Stream, Trace,
+};
+use crate::{Error, Result};
+
+const MAGIC: &[u8] = b"PROMIVAULT";
+const HEADER: &str = "promi-vault 1";
+const NONCE_SIZE: usize = 24;
+
+/// Secret key of a vault
+#[derive(Clone, PartialEq, Eq)]
+pub struct VaultKey([u8; 32]);
+
+impl VaultKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        VaultKey(key)
+    }
+
+    /// Parse a key from 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(Error::VaultError(
+                "key must consist of 64 hex digits".into(),
+            ));
+        }
+
+        let mut key = [0; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+        }
+        Ok(VaultKey(key))
+    }
+
+    /// Read a key from the environment variable of the given name
+    pub fn from_env(name: &str) -> Result<Self> {
+        let hex = env::var(name)
+            .map_err(|_| Error::VaultError(format!("no key in environment variable {:?}", name)))?;
+        Self::from_hex(&hex)
+    }
+
+    /// Read a key from a file that contains nothing but the 64 hex digits
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_hex(&fs::read_to_string(path)?)
+    }
+
+    /// Generate a random key
+    pub fn generate() -> Self {
+        VaultKey(random())
+    }
+
+    /// Hex representation of the key
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+// Keep the key out of debug output
+impl std::fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VaultKey(..)")
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Bidirectional mapping between original values and pseudonyms, per attribute key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vault {
+    pseudonyms: BTreeMap<String, BTreeMap<String, String>>,
+    originals: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Vault {
+    /// Pseudonym of an original value
+    pub fn pseudonym(&self, key: &str, original: &str) -> Option<&str> {
+        self.pseudonyms
+            .get(key)
+            .and_then(|m| m.get(original))
+            .map(|p| p.as_str())
+    }
+
+    /// Original value of a pseudonym
+    pub fn original(&self, key: &str, pseudonym: &str) -> Option<&str> {
+        self.originals
+            .get(key)
+            .and_then(|m| m.get(pseudonym))
+            .map(|o| o.as_str())
+    }
+
+    /// Add a mapping, fails if either the original value or the pseudonym is already mapped
+    pub fn insert(&mut self, key: &str, original: &str, pseudonym: &str) -> Result<()> {
+        if self.pseudonym(key, original).is_some() || self.original(key, pseudonym).is_some() {
+            return Err(Error::VaultError(format!(
+                "conflicting mapping for {:?} of {:?}",
+                original, key
+            )));
+        }
+
+        self.pseudonyms
+            .entry(key.to_string())
+            .or_default()
+            .insert(original.to_string(), pseudonym.to_string());
+        self.originals
+            .entry(key.to_string())
+            .or_default()
+            .insert(pseudonym.to_string(), original.to_string());
+        Ok(())
+    }
+
+    /// Total number of mappings
+    pub fn len(&self) -> usize {
+        self.pseudonyms.values().map(|m| m.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Encrypt the vault and write it
+    pub fn write<W: Write>(&self, mut writer: W, key: &VaultKey) -> Result<()> {
+        let mut plain = format!("{}\n", HEADER);
+        for (attribute, mapping) in self.pseudonyms.iter() {
+            for (original, pseudonym) in mapping.iter() {
+                plain.push_str(&format!(
+                    "{}\t{}\t{}\n",
+                    escape(attribute),
+                    escape(original),
+                    escape(pseudonym)
+                ));
+            }
+        }
+
+        // the magic number is authenticated along with the content
+        let nonce: [u8; NONCE_SIZE] = random();
+        let payload = Payload {
+            msg: plain.as_bytes(),
+            aad: MAGIC,
+        };
+        let data = key
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| Error::VaultError("unable to encrypt vault".into()))?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&nonce)?;
+        writer.write_all(&data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read and decrypt a vault
+    pub fn read<R: Read>(mut reader: R, key: &VaultKey) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        if data.len() < MAGIC.len() + NONCE_SIZE || !data.starts_with(MAGIC) {
+            return Err(Error::VaultError("not a vault file".into()));
+        }
+        let (nonce, data) = data[MAGIC.len()..].split_at(NONCE_SIZE);
+        let payload = Payload {
+            msg: data,
+            aad: MAGIC,
+        };
+        let plain = key
+            .cipher()
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| Error::VaultError("wrong key or tampered vault".into()))?;
+
+        let plain = match String::from_utf8(plain) {
+            Ok(plain) if plain.starts_with(HEADER) => plain,
+            _ => return Err(Error::VaultError("malformed vault".into())),
+        };
+
+        let mut vault = Vault::default();
+        for line in plain.lines().skip(1) {
+            let fields: Vec<_> = line.split('\t').map(unescape).collect();
+            if fields.len() != 3 {
+                return Err(Error::VaultError(format!("malformed entry {:?}", line)));
+            }
+            vault.insert(&fields[0], &fields[1], &fields[2])?;
+        }
+
+        Ok(vault)
+    }
+
+    /// Encrypt the vault and write it to a file
+    ///
+    /// The vault is written to a temporary file next to the target, synced and renamed, such that
+    /// a crash never leaves a truncated vault behind.
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P, key: &VaultKey) -> Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let result = (|| -> Result<()> {
+            let mut writer = io::BufWriter::new(File::create(&temporary)?);
+            self.write(&mut writer, key)?;
+            writer
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .sync_all()?;
+            fs::rename(&temporary, path)?;
+            Ok(())
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// Read and decrypt a vault from a file
+    pub fn load<P: AsRef<Path>>(path: P, key: &VaultKey) -> Result<Self> {
+        Self::read(io::BufReader::new(File::open(path)?), key)
+    }
+}
+
+/// Replace attribute values by pseudonyms or, if reversing, pseudonyms by original values
+///
+/// Only string values are affected. When reversing, encountering an unknown pseudonym is an
+/// error. If a location is given, the vault is saved once the stream is exhausted.
+///
+pub struct Pseudonymize {
+    keys: Vec<String>,
+    vault: Vault,
+    reverse: bool,
+    persist: Option<(PathBuf, VaultKey)>,
+    rng: Pcg64,
+}
+
+impl Pseudonymize {
+    pub fn new<K: Into<String>, I: IntoIterator<Item = K>>(keys: I) -> Self {
+        Pseudonymize {
+            keys: keys.into_iter().map(|k| k.into()).collect(),
+            vault: Vault::default(),
+            reverse: false,
+            persist: None,
+            rng: Pcg64::new(random(), 0),
+        }
+    }
+
+    /// Start from existing mappings
+    pub fn vault(mut self, vault: Vault) -> Self {
+        self.vault = vault;
+        self
+    }
+
+    /// Map pseudonyms back to original values
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Save the vault to the given location once the stream is exhausted
+    pub fn persist<P: Into<PathBuf>>(mut self, path: P, key: VaultKey) -> Self {
+        self.persist = Some((path.into(), key));
+        self
+    }
+
+    /// Make generated pseudonyms reproducible
+    pub fn random_state(mut self, random_state: u128) -> Self {
+        self.rng = Pcg64::new(random_state, 0);
+        self
+    }
+
+    /// Release the vault
+    pub fn into_vault(self) -> Vault {
+        self.vault
+    }
+
+    fn substitute(
+        vault: &mut Vault,
+        rng: &mut Pcg64,
+        reverse: bool,
+        key: &str,
+        value: &str,
+    ) -> Result<String> {
+        if reverse {
+            return match vault.original(key, value) {
+                Some(original) => Ok(original.to_string()),
+                None => Err(Error::VaultError(format!(
+                    "unknown pseudonym {:?} of {:?}",
+                    value, key
+                ))),
+            };
+        }
+
+        if let Some(pseudonym) = vault.pseudonym(key, value) {
+            return Ok(pseudonym.to_string());
+        }
+
+        let pseudonym = loop {
+            let candidate = format!("{:016x}", rng.gen::<u64>());
+            if vault.original(key, &candidate).is_none() {
+                break candidate;
+            }
+        };
+        vault.insert(key, value, &pseudonym)?;
+        Ok(pseudonym)
+    }
+
+    fn apply(&mut self, attributes: &mut AttributeMap) -> Result<()> {
+        for key in self.keys.iter() {
+            if let Some(AttributeValue::String(value)) = attributes.get_value_mut(key) {
+                let substitute =
+                    Self::substitute(&mut self.vault, &mut self.rng, self.reverse, key, value)?;
+                *value = substitute.into();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler for Pseudonymize {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        self.apply(&mut trace.attributes)?;
+        for event in trace.events.iter_mut() {
+            self.apply(&mut event.attributes)?;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.apply(&mut event.attributes)?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        if let Some((path, key)) = &self.persist {
+            self.vault.save(path, key)?;
+        }
+
+        Ok(vec![])
+    }
+}
+
+impl PluginProvider for Pseudonymize {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Pseudonymize",
+            "Replace attribute values by pseudonyms that are consistent across runs",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be pseudonymized")
+                    .typed_attr(
+                        "keys",
+                        "List of attributes to be pseudonymized",
+                        AttributeType::List,
+                    )
+                    .default_attr("reverse", "Map pseudonyms back to original values", |k| {
+                        (k, false).into()
+                    })
+                    .optional_attr(
+                        "vault",
+                        "Location of the vault, e.g. \"/secure/vault.bin\"",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "key_file",
+                        "File that holds the key of the vault as 64 hex digits",
+                        AttributeType::String,
+                    )
+                    .default_attr(
+                        "key_env",
+                        "Environment variable that holds the key of the vault, unless a key file is given",
+                        |k| (k, "PROMI_VAULT_KEY").into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let keys = parameters
+                        .acquire_attribute("keys")?
+                        .value
+                        .try_list()?
+                        .iter()
+                        .map(|a| Ok(a.value.try_string()?.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+                    let reverse = *parameters
+                        .acquire_attribute("reverse")?
+                        .value
+                        .try_boolean()?;
+                    let mut pseudonymize = Pseudonymize::new(keys).reverse(reverse);
+
+                    if let Ok(path) = parameters.acquire_attribute("vault") {
+                        let path = path.value.try_string()?.to_string();
+                        let key = match parameters.acquire_attribute("key_file") {
+                            Ok(file) => VaultKey::from_file(file.value.try_string()?)?,
+                            Err(_) => VaultKey::from_env(
+                                parameters.acquire_attribute("key_env")?.value.try_string()?,
+                            )?,
+                        };
+
+                        if Path::new(&path).exists() {
+                            pseudonymize = pseudonymize.vault(Vault::load(&path, &key)?);
+                        } else if reverse {
+                            return Err(Error::VaultError(format!("no vault at {:?}", path)));
+                        }
+                        if !reverse {
+                            pseudonymize = pseudonymize.persist(path, key);
+                        }
+                    }
+
+                    Ok(
+                        Observer::from((parameters.acquire_stream("inner")?, pseudonymize))
+                            .into_boxed(),
+                    )
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeContainer, Component, Sink};
+
+    use super::*;
+
+    fn buffer(resources: &[&str]) -> Buffer {
+        let mut trace = Trace::default();
+        for resource in resources.iter() {
+            let mut event = Event::default();
+            event.attributes.insert(("org:resource", *resource));
+            event.attributes.insert(("cost", 42));
+            trace.events.push(event);
+        }
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(trace))));
+        buffer
+    }
+
+    fn resources(pseudonymize: Pseudonymize, buffer: Buffer) -> (Vec<String>, Pseudonymize) {
+        let mut observer = pseudonymize.into_observer(buffer);
+        let mut log = Log::default();
+        log.consume(&mut observer).unwrap();
+
+        let resources = log.traces[0]
+            .events
+            .iter()
+            .map(|e| e.get_value("org:resource").unwrap())
+            .map(|v| v.try_string().unwrap().to_string())
+            .collect();
+        (resources, observer.release().unwrap())
+    }
+
+    #[test]
+    fn test_pseudonymize() {
+        let keys = vec!["org:resource", "cost"];
+        let (first, pseudonymize) = resources(
+            Pseudonymize::new(keys.clone()),
+            buffer(&["alice", "bob", "alice"]),
+        );
+        assert_eq!(first[0], first[2]);
+        assert_ne!(first[0], first[1]);
+        assert!(!first.contains(&"alice".to_string()));
+
+        // a later run re-applies known pseudonyms
+        let vault = pseudonymize.into_vault();
+        assert_eq!(vault.len(), 2);
+        let (second, pseudonymize) = resources(
+            Pseudonymize::new(keys.clone()).vault(vault),
+            buffer(&["carol", "alice"]),
+        );
+        assert_eq!(second[1], first[0]);
+        assert_eq!(pseudonymize.vault.len(), 3);
+
+        let mut pseudonyms = Buffer::default();
+        let mut trace = Trace::default();
+        trace.events.push(Event::default());
+        trace.events[0]
+            .attributes
+            .insert(("org:resource", second[0].as_str()));
+        pseudonyms.push(Ok(Some(Component::Trace(trace))));
+        let (original, _) = resources(
+            Pseudonymize::new(keys)
+                .vault(pseudonymize.into_vault())
+                .reverse(true),
+            pseudonyms,
+        );
+        assert_eq!(original, vec!["carol"]);
+    }
+
+    #[test]
+    fn test_vault() {
+        let mut vault = Vault::default();
+        vault
+            .insert("org:resource", "tab\tnew\nline\\", "p0")
+            .unwrap();
+        vault.insert("org:group", "sales", "p0").unwrap();
+        assert!(vault.insert("org:resource", "other", "p0").is_err());
+
+        let key = VaultKey::generate();
+        let mut data = Vec::new();
+        vault.write(&mut data, &key).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("sales"));
+        assert_eq!(Vault::read(data.as_slice(), &key).unwrap(), vault);
+
+        let other = VaultKey::from_hex(&"ab".repeat(32)).unwrap();
+        assert_eq!(other.to_hex(), "ab".repeat(32));
+        assert!(matches!(
+            Vault::read(data.as_slice(), &other),
+            Err(Error::VaultError(_))
+        ));
+
+        // any modification fails authentication
+        for i in [
+            MAGIC.len() + 1,
+            MAGIC.len() + NONCE_SIZE + 1,
+            data.len() - 1,
+        ]
+        .iter()
+        {
+            let mut tampered = data.clone();
+            tampered[*i] ^= 1;
+            assert!(matches!(
+                Vault::read(tampered.as_slice(), &key),
+                Err(Error::VaultError(_))
+            ));
+        }
+
+        // the nonce is drawn per write
+        let mut again = Vec::new();
+        vault.write(&mut again, &key).unwrap();
+        assert_ne!(again, data);
+        assert!(VaultKey::from_hex("abc").is_err());
+
+        // files are replaced as a whole
+        let dir = std::env::temp_dir().join(format!("promi_vault_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vault.bin");
+        vault.save(&path, &key).unwrap();
+        Vault::default().save(&path, &key).unwrap();
+        assert!(Vault::load(&path, &key).unwrap().is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let key_file = dir.join("key");
+        std::fs::write(&key_file, format!("{}\n", key.to_hex())).unwrap();
+        assert_eq!(VaultKey::from_file(&key_file).unwrap(), key);
+        assert!(VaultKey::from_env("PROMI_TEST_NO_SUCH_KEY").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,448 @@
+//! Typed findings on the quality of event data
+//!
+//! Readers, validators and transformers come across the same kinds of flaws in real-world data
+//! over and over again. Instead of describing them in free-form messages only, they are reported
+//! as `Finding`s of a fixed `Issue` category, such that pipelines and user interfaces can
+//! aggregate them by category:
+//! - `MissingCaseId`: a trace without `concept:name` or a row without case,
+//! - `NonMonotonicTimestamps`: an event of a trace that is older than its predecessor,
+//! - `UnknownActivity`: an event without activity or with an activity that is not expected and
+//! - `OrphanEvent`: an event that belongs to no case although the stream consists of cases.
+//!
+//! Findings are either collected in a `QualityReport` artifact as warnings or, if their category
+//! is rejected, raised as `Error::QualityError`. `Error::finding` recovers the finding of an error,
+//! even if it was wrapped in context on its way through a pipeline.
+//!
+//! `QualityChecker` checks a stream for all categories. `CsvReader` and `OcelReader` report the
+//! issues they come across while reading.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, AttributeValue, Event, Flavor, Meta,
+    Stream, Trace,
+};
+use crate::{DateTime, Error, Result};
+
+/// Category of data quality issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Issue {
+    MissingCaseId,
+    NonMonotonicTimestamps,
+    UnknownActivity,
+    OrphanEvent,
+}
+
+impl Issue {
+    pub const ALL: [Issue; 4] = [
+        Issue::MissingCaseId,
+        Issue::NonMonotonicTimestamps,
+        Issue::UnknownActivity,
+        Issue::OrphanEvent,
+    ];
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{:?}", self))
+    }
+}
+
+impl FromStr for Issue {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Issue::ALL
+            .iter()
+            .find(|issue| issue.to_string().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| Error::AttributeError(format!("unknown data quality issue: {:?}", s)))
+    }
+}
+
+/// Whether a finding stops the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Single occurrence of a data quality issue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub issue: Issue,
+    pub severity: Severity,
+    /// Where the issue occurred, e.g. a case id or line number
+    pub location: String,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new<L: Into<String>, M: Into<String>>(issue: Issue, location: L, message: M) -> Self {
+        Finding {
+            issue,
+            severity: Severity::Warning,
+            location: location.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}: {}", self.issue, self.location, self.message)
+    }
+}
+
+impl From<Finding> for Error {
+    fn from(finding: Finding) -> Self {
+        Error::QualityError(Box::new(finding.severity(Severity::Error)))
+    }
+}
+
+/// Data quality findings of a stream, aggregated by category
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QualityReport {
+    /// Number of findings per category, including those beyond `max_findings`
+    pub counts: BTreeMap<Issue, usize>,
+    pub findings: Vec<Finding>,
+    /// Upper limit of findings kept in detail, unlimited if `None`
+    pub max_findings: Option<usize>,
+}
+
+impl QualityReport {
+    /// Keep up to the given number of findings in detail, while still counting all of them
+    pub fn max_findings(mut self, max_findings: usize) -> Self {
+        self.max_findings = Some(max_findings);
+        self
+    }
+
+    pub fn record(&mut self, finding: Finding) {
+        *self.counts.entry(finding.issue).or_default() += 1;
+        if self
+            .max_findings
+            .is_none_or(|max| self.findings.len() < max)
+        {
+            self.findings.push(finding);
+        }
+    }
+
+    /// Number of findings of the given category
+    pub fn count(&self, issue: Issue) -> usize {
+        self.counts.get(&issue).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Findings of the given category that were kept in detail
+    pub fn findings_of(&self, issue: Issue) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.issue == issue)
+    }
+
+    /// Merge the findings of another report, e.g. of a different stage of a pipeline
+    pub fn merge(&mut self, other: QualityReport) {
+        for (issue, count) in other.counts.into_iter() {
+            *self.counts.entry(issue).or_default() += count;
+        }
+        for finding in other.findings.into_iter() {
+            if self
+                .max_findings
+                .is_none_or(|max| self.findings.len() < max)
+            {
+                self.findings.push(finding);
+            }
+        }
+    }
+}
+
+#[typetag::serde]
+impl Artifact for QualityReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Check a stream for all categories of data quality issues
+///
+/// Activities are taken from `concept:name`. Unless a set of expected activities is given, only
+/// events without activity are reported as `UnknownActivity`.
+///
+pub struct QualityChecker {
+    activities: Option<BTreeSet<String>>,
+    reject: BTreeSet<Issue>,
+    flavor: Flavor,
+    report: QualityReport,
+    events: usize,
+}
+
+impl Default for QualityChecker {
+    fn default() -> Self {
+        QualityChecker {
+            activities: None,
+            reject: BTreeSet::new(),
+            flavor: Flavor::default(),
+            report: QualityReport::default().max_findings(1000),
+            events: 0,
+        }
+    }
+}
+
+impl QualityChecker {
+    /// Report activities that are not in the given set
+    pub fn activities<I: IntoIterator<Item = S>, S: Into<String>>(mut self, activities: I) -> Self {
+        self.activities = Some(activities.into_iter().map(|a| a.into()).collect());
+        self
+    }
+
+    /// Fail on the first finding of the given category instead of reporting it
+    pub fn reject(mut self, issue: Issue) -> Self {
+        self.reject.insert(issue);
+        self
+    }
+
+    pub fn max_findings(mut self, max_findings: usize) -> Self {
+        self.report.max_findings = Some(max_findings);
+        self
+    }
+
+    fn found(&mut self, finding: Finding) -> Result<()> {
+        if self.reject.contains(&finding.issue) {
+            return Err(finding.into());
+        }
+        self.report.record(finding);
+        Ok(())
+    }
+
+    fn check_activity(&mut self, event: &Event, location: &str) -> Result<()> {
+        let message = match (event.get_value("concept:name"), &self.activities) {
+            (Some(AttributeValue::String(activity)), Some(activities))
+                if !activities.contains(activity.as_str()) =>
+            {
+                format!("unexpected activity {:?}", activity)
+            }
+            (Some(AttributeValue::String(_)), _) => return Ok(()),
+            (Some(other), _) => format!("activity {:?} is no string", other),
+            (None, _) => "event has no activity".to_string(),
+        };
+        self.found(Finding::new(Issue::UnknownActivity, location, message))
+    }
+}
+
+impl Handler for QualityChecker {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.flavor = meta.flavor;
+        Ok(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let location = match trace.get_value("concept:name") {
+            Some(AttributeValue::String(id)) | Some(AttributeValue::Id(id)) if !id.is_empty() => {
+                id.to_string()
+            }
+            _ => {
+                let location = format!("event {}", self.events);
+                self.found(Finding::new(
+                    Issue::MissingCaseId,
+                    location.as_str(),
+                    "trace has no case id",
+                ))?;
+                location
+            }
+        };
+
+        let mut previous: Option<DateTime> = None;
+        for (i, event) in trace.events.iter().enumerate() {
+            let event_location = format!("{}, event {}", location, i);
+            self.check_activity(event, &event_location)?;
+
+            if let Some(AttributeValue::Date(timestamp)) = event.get_value("time:timestamp") {
+                if previous.is_some_and(|p| *timestamp < p) {
+                    self.found(Finding::new(
+                        Issue::NonMonotonicTimestamps,
+                        event_location,
+                        format!("{} precedes {}", timestamp, previous.unwrap()),
+                    ))?;
+                }
+                previous = Some(*timestamp);
+            }
+        }
+        self.events += trace.events.len();
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if in_trace {
+            return Ok(Some(event));
+        }
+
+        let location = format!("event {}", self.events);
+        self.events += 1;
+        self.check_activity(&event, &location)?;
+        if self.flavor != Flavor::Stream {
+            self.found(Finding::new(
+                Issue::OrphanEvent,
+                location,
+                "event belongs to no trace",
+            ))?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        let location = format!("event {}", self.events);
+        self.events += 1;
+        self.check_activity(&event, &location)?;
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        let max_findings = self.report.max_findings;
+        let report = std::mem::take(&mut self.report);
+        self.report.max_findings = max_findings;
+        Ok(vec![report.into()])
+    }
+}
+
+impl PluginProvider for QualityChecker {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "QualityChecker",
+            "Report data quality issues by category, optionally failing on selected categories",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be checked")
+                    .default_attr(
+                        "reject",
+                        "Comma separated categories that fail the stream, e.g. \"MissingCaseId\"",
+                        |k| (k, "").into(),
+                    )
+                    .optional_attr(
+                        "activities",
+                        "Known activities, others are reported",
+                        AttributeType::List,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut checker = QualityChecker::default();
+                    let reject = parameters.acquire_attribute("reject")?;
+                    for issue in reject.value.try_string()?.split(',') {
+                        if !issue.trim().is_empty() {
+                            checker = checker.reject(issue.trim().parse()?);
+                        }
+                    }
+                    if let Ok(activities) = parameters.acquire_attribute("activities") {
+                        checker = checker.activities(
+                            activities
+                                .value
+                                .try_list()?
+                                .iter()
+                                .map(|a| Ok(a.value.try_string()?.to_string()))
+                                .collect::<Result<Vec<_>>>()?,
+                        );
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, checker)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::Component;
+
+    use super::*;
+
+    fn event(activity: Option<&str>, second: u32) -> Event {
+        let mut event = Event::default();
+        if let Some(activity) = activity {
+            event.attributes.insert(("concept:name", activity));
+        }
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:00:{:02}+00:00", second)).unwrap();
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    fn buffer() -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", "case 1"));
+        trace.events.push(event(Some("a"), 1));
+        trace.events.push(event(Some("b"), 3));
+        trace.events.push(event(Some("x"), 2));
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let mut trace = Trace::default();
+        trace.events.push(event(None, 1));
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        buffer.push(Ok(Some(Component::Event(event(Some("a"), 5)))));
+        buffer
+    }
+
+    #[test]
+    fn test_quality_checker() {
+        let mut observer = QualityChecker::default()
+            .activities(vec!["a", "b"])
+            .into_observer(buffer());
+        let artifacts = consume(&mut observer).unwrap();
+        let report = AnyArtifact::find::<QualityReport>(&mut artifacts.iter().flatten()).unwrap();
+
+        let counts: Vec<_> = Issue::ALL.iter().map(|i| report.count(*i)).collect();
+        assert_eq!(counts, [1, 1, 2, 1]);
+        let finding = report
+            .findings_of(Issue::NonMonotonicTimestamps)
+            .next()
+            .unwrap();
+        assert_eq!(
+            (finding.location.as_str(), finding.severity),
+            ("case 1, event 2", Severity::Warning)
+        );
+
+        let mut observer = QualityChecker::default()
+            .reject(Issue::MissingCaseId)
+            .into_observer(buffer());
+        let error = Error::ComponentError(
+            "component #3".to_string(),
+            Box::new(consume(&mut observer).unwrap_err()),
+        );
+        let finding = error.finding().unwrap();
+        assert_eq!(
+            (finding.issue, finding.severity),
+            (Issue::MissingCaseId, Severity::Error)
+        );
+
+        assert_eq!("orphanevent".parse::<Issue>().unwrap(), Issue::OrphanEvent);
+        assert!("typo".parse::<Issue>().is_err());
+    }
+}
@@ -2,20 +2,160 @@
 //!
 //! By now, the following error classes are covered:
 //! - fix invalid classifier names
+//! - fix unparsable or missing `time:timestamp` attributes of events
 //!
+//! Timestamps are repaired by a selection of `TimestampStrategy`s, which are applied in order of
+//! 1. coercing strings in alternative formats, i.e. `%Y-%m-%d %H:%M:%S` or epoch milliseconds, and
+//!    integers, taken as epoch milliseconds, into dates,
+//! 2. interpolating missing timestamps linearly between the neighboring events of a trace and
+//! 3. assigning a trace-level default, i.e. the date of a trace attribute, to events whose
+//!    timestamp is still missing.
+//!
+//! Timestamps that can't be coerced are considered missing by the latter strategies. Without any
+//! strategy, timestamps are left as they are.
+//!
+
+use std::str::FromStr;
 
+use chrono::{FixedOffset, TimeZone};
+
+use crate::stream::csv::parse_timestamp;
 use crate::stream::observer::{Handler, Observer};
 use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
 use crate::stream::xml_util::CRE_NCNAME;
-use crate::stream::{Meta, Stream};
-use crate::Result;
+use crate::stream::{AttributeContainer, AttributeValue, Event, Meta, Stream, Trace};
+use crate::{DateTime, Error, Result};
+
+/// Way to repair `time:timestamp` attributes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimestampStrategy {
+    Coerce,
+    Interpolate,
+    TraceDefault,
+}
+
+impl FromStr for TimestampStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "coerce" => Ok(TimestampStrategy::Coerce),
+            "interpolate" => Ok(TimestampStrategy::Interpolate),
+            "default" => Ok(TimestampStrategy::TraceDefault),
+            other => Err(Error::AttributeError(format!(
+                "invalid timestamp strategy {:?}, expected \"coerce\", \"interpolate\" or \"default\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Interpret a value in one of the alternative timestamp formats
+fn coerce(value: &AttributeValue) -> Option<DateTime> {
+    let utc = FixedOffset::east_opt(0).unwrap();
+    match value {
+        AttributeValue::Date(timestamp) => Some(*timestamp),
+        AttributeValue::Int(millis) => utc.timestamp_millis_opt(*millis).single(),
+        AttributeValue::String(value) => {
+            let value = value.trim();
+            if let Ok(millis) = value.parse::<i64>() {
+                return utc.timestamp_millis_opt(millis).single();
+            }
+            chrono::DateTime::parse_from_rfc3339(value)
+                .ok()
+                .or_else(|| parse_timestamp(value, "%Y-%m-%d %H:%M:%S").ok())
+                .or_else(|| parse_timestamp(value, "%Y-%m-%dT%H:%M:%S").ok())
+        }
+        _ => None,
+    }
+}
 
 /// Collection of stream repair strategies
-pub struct Repair;
+pub struct Repair {
+    timestamps: Vec<TimestampStrategy>,
+    default_key: String,
+}
 
 impl Default for Repair {
+    /// Fix classifier names only
     fn default() -> Self {
-        Repair {}
+        Repair {
+            timestamps: Vec::new(),
+            default_key: "time:timestamp".to_string(),
+        }
+    }
+}
+
+impl Repair {
+    /// Repair timestamps by the given strategy, in addition to the ones selected before
+    pub fn timestamps(mut self, strategy: TimestampStrategy) -> Self {
+        if !self.timestamps.contains(&strategy) {
+            self.timestamps.push(strategy);
+        }
+        self
+    }
+
+    /// Take trace-level default timestamps from the given trace attribute
+    pub fn default_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.default_key = key.into();
+        self
+    }
+
+    fn coerce_event(&self, event: &mut Event) {
+        if let Some(value) = event.attributes.get_value_mut("time:timestamp") {
+            if let Some(timestamp) = coerce(value) {
+                *value = AttributeValue::Date(timestamp);
+            }
+        }
+    }
+
+    /// Fill gaps between events with valid timestamps by linear interpolation
+    fn interpolate(timestamps: &mut [Option<DateTime>]) {
+        let mut previous: Option<usize> = None;
+        for i in 0..timestamps.len() {
+            if timestamps[i].is_none() {
+                continue;
+            }
+            if let Some(p) = previous {
+                if i - p > 1 {
+                    let (start, end) = (timestamps[p].unwrap(), timestamps[i].unwrap());
+                    let step = (end - start) / (i - p) as i32;
+                    for (n, j) in (p + 1..i).enumerate() {
+                        timestamps[j] = Some(start + step * (n as i32 + 1));
+                    }
+                }
+            }
+            previous = Some(i);
+        }
+    }
+
+    fn repair_timestamps(&self, trace: &mut Trace) {
+        let mut timestamps: Vec<Option<DateTime>> = trace
+            .events
+            .iter()
+            .map(|e| match e.get_value("time:timestamp") {
+                Some(AttributeValue::Date(timestamp)) => Some(*timestamp),
+                _ => None,
+            })
+            .collect();
+        let valid: Vec<bool> = timestamps.iter().map(|t| t.is_some()).collect();
+
+        if self.timestamps.contains(&TimestampStrategy::Interpolate) {
+            Self::interpolate(&mut timestamps);
+        }
+        if self.timestamps.contains(&TimestampStrategy::TraceDefault) {
+            if let Some(default) = trace.get_value(&self.default_key).and_then(coerce) {
+                for timestamp in timestamps.iter_mut().filter(|t| t.is_none()) {
+                    *timestamp = Some(default);
+                }
+            }
+        }
+
+        for ((event, timestamp), valid) in trace.events.iter_mut().zip(timestamps).zip(valid) {
+            if let (Some(timestamp), false) = (timestamp, valid) {
+                event.attributes.insert(("time:timestamp", timestamp));
+            }
+        }
     }
 }
 
@@ -35,6 +175,26 @@ impl Handler for Repair {
 
         Ok(meta)
     }
+
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        if self.timestamps.contains(&TimestampStrategy::Coerce) {
+            for event in trace.events.iter_mut() {
+                self.coerce_event(event);
+            }
+        }
+        self.repair_timestamps(&mut trace);
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        // events of traces are repaired along with their trace
+        if !in_trace && self.timestamps.contains(&TimestampStrategy::Coerce) {
+            self.coerce_event(&mut event);
+        }
+
+        Ok(Some(event))
+    }
 }
 
 impl PluginProvider for Repair {
@@ -46,12 +206,34 @@ impl PluginProvider for Repair {
             "Repair",
             "Applies a number of methods in order to fix broken items such as invalid names",
             Factory::new(
-                Declaration::default().stream("inner", "The stream to be repaired"),
-                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
-                    Ok(
-                        Observer::from((parameters.acquire_stream("inner")?, Repair::default()))
-                            .into_boxed(),
+                Declaration::default()
+                    .stream("inner", "The stream to be repaired")
+                    .default_attr(
+                        "timestamps",
+                        "Comma separated timestamp strategies, i.e. coerce, interpolate, default",
+                        |k| (k, "").into(),
                     )
+                    .default_attr(
+                        "default_key",
+                        "Trace attribute that holds the default timestamp of its events",
+                        |k| (k, "time:timestamp").into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut repair = Repair::default().default_key(
+                        parameters
+                            .acquire_attribute("default_key")?
+                            .value
+                            .try_string()?,
+                    );
+
+                    let strategies = parameters.acquire_attribute("timestamps")?;
+                    for strategy in strategies.value.try_string()?.split(',') {
+                        if !strategy.trim().is_empty() {
+                            repair = repair.timestamps(strategy.trim().parse()?);
+                        }
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, repair)).into_boxed())
                 })),
             ),
         )]
@@ -81,4 +263,71 @@ mod test {
             assert!(consume(&mut repaired).is_ok());
         }
     }
+
+    #[test]
+    fn test_repair_timestamps() {
+        let at = |time: &str| DateTime::parse_from_rfc3339(&format!("2020-01-01T{}Z", time));
+        let values: Vec<Option<AttributeValue>> = vec![
+            Some(at("10:00:00").unwrap().into()),
+            Some("2020-01-01 10:30:00".into()),
+            None,
+            Some(at("11:00:00").unwrap().timestamp_millis().into()),
+            Some("yesterday".into()),
+            None,
+        ];
+        let mut trace = Trace::default();
+        trace
+            .attributes
+            .insert(("time:timestamp", at("09:00:00").unwrap()));
+        for value in values {
+            let mut event = Event::default();
+            if let Some(value) = value {
+                event.attributes.insert(("time:timestamp", value));
+            }
+            trace.events.push(event);
+        }
+
+        let repair = |repair: Repair| -> Vec<Option<DateTime>> {
+            let mut repair = repair;
+            repair
+                .on_trace(trace.clone())
+                .unwrap()
+                .unwrap()
+                .events
+                .iter()
+                .map(|e| {
+                    e.get_value("time:timestamp")
+                        .and_then(|t| t.try_date().ok().copied())
+                })
+                .collect()
+        };
+
+        let none = repair(Repair::default());
+        assert_eq!(none.iter().filter(|t| t.is_some()).count(), 1);
+
+        let coerced = repair(Repair::default().timestamps(TimestampStrategy::Coerce));
+        assert_eq!(coerced[1], at("10:30:00").ok());
+        assert_eq!(coerced[3], at("11:00:00").ok());
+        assert_eq!(coerced[4], None);
+
+        let repaired = repair(
+            Repair::default()
+                .timestamps(TimestampStrategy::Coerce)
+                .timestamps(TimestampStrategy::Interpolate)
+                .timestamps(TimestampStrategy::TraceDefault),
+        );
+        assert_eq!(
+            repaired,
+            vec![
+                at("10:00:00").ok(),
+                at("10:30:00").ok(),
+                at("10:45:00").ok(),
+                at("11:00:00").ok(),
+                at("09:00:00").ok(),
+                at("09:00:00").ok(),
+            ]
+        );
+
+        assert!("guess".parse::<TimestampStrategy>().is_err());
+    }
 }
@@ -0,0 +1,715 @@
+//! Render a summary document of a stream
+//!
+//! `ReportSink` produces a one-command report per log. While the stream passes, it collects the
+//! designated artifacts of an analysis: the `Statistics`, the `Variants`, the `Dfg` and a
+//! `DataQuality` tally. Once the stream is closed, they are rendered into a Markdown or HTML
+//! document. Artifacts produced elsewhere, e.g. a soundness report of a discovered model, may be
+//! added to the report; an added artifact of a designated type takes the place of the collected
+//! one, all others are appended as JSON.
+//!
+//! The layout of the document is given by a template with the placeholders `{{title}}`,
+//! `{{statistics}}`, `{{variants}}`, `{{dfg}}`, `{{quality}}` and `{{artifacts}}`. Each section
+//! placeholder is replaced by a heading and its content, or by nothing if there is no data.
+//! Unknown placeholders are kept as they are. In Markdown, the directly-follows graph is drawn as
+//! a Mermaid flowchart, in HTML the interactive process map is embedded.
+//!
+
+use std::any::Any;
+use std::fs::{self, File};
+use std::io;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::dfg::{escape_html, ActivitySource, Dfg, DfgGenerator};
+use crate::stream::observer::Handler;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::stats::{Statistics, StatsCollector};
+use crate::stream::variant::{VariantCollector, Variants};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, Component, Event, Flavor, Sink, Trace,
+};
+use crate::{Error, Result};
+
+/// Default layout of a Markdown report
+pub const MARKDOWN_TEMPLATE: &str = "# {{title}}
+
+{{statistics}}{{variants}}{{dfg}}{{quality}}{{artifacts}}";
+
+/// Default layout of an HTML report
+pub const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+<style>
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+td.number { text-align: right; }
+iframe { width: 100%; height: 40em; border: 1px solid #ccc; }
+pre { background: #f6f6f6; padding: 1em; overflow: auto; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+{{statistics}}{{variants}}{{dfg}}{{quality}}{{artifacts}}</body>
+</html>
+"#;
+
+/// Completeness and consistency of the events of a stream
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DataQuality {
+    pub events: usize,
+    pub missing_activity: usize,
+    pub missing_timestamp: usize,
+    /// Traces whose events are not in chronological order
+    pub unordered_traces: usize,
+}
+
+#[typetag::serde]
+impl Artifact for DataQuality {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Output format of the report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentFormat {
+    Markdown,
+    Html,
+}
+
+impl DocumentFormat {
+    /// Default layout of a report in this format
+    pub fn template(&self) -> &'static str {
+        match self {
+            DocumentFormat::Markdown => MARKDOWN_TEMPLATE,
+            DocumentFormat::Html => HTML_TEMPLATE,
+        }
+    }
+
+    fn escape(&self, text: &str) -> String {
+        match self {
+            DocumentFormat::Markdown => text.replace('|', "\\|"),
+            DocumentFormat::Html => escape_html(text),
+        }
+    }
+
+    fn section(&self, heading: &str, body: &str) -> String {
+        match self {
+            DocumentFormat::Markdown => format!("## {}\n\n{}", heading, body),
+            DocumentFormat::Html => {
+                format!("<section>\n<h2>{}</h2>\n{}</section>\n", heading, body)
+            }
+        }
+    }
+
+    fn paragraph(&self, text: &str) -> String {
+        match self {
+            DocumentFormat::Markdown => format!("{}\n\n", text),
+            DocumentFormat::Html => format!("<p>{}</p>\n", escape_html(text)),
+        }
+    }
+
+    fn code(&self, language: &str, code: &str) -> String {
+        match self {
+            DocumentFormat::Markdown => format!("```{}\n{}\n```\n\n", language, code),
+            DocumentFormat::Html => format!("<pre><code>{}</code></pre>\n", escape_html(code)),
+        }
+    }
+
+    /// Render a table, columns named in `numeric` are aligned to the right
+    fn table(&self, header: &[&str], numeric: &[&str], rows: &[Vec<String>]) -> String {
+        let mut table = String::new();
+        match self {
+            DocumentFormat::Markdown => {
+                table.push_str(&format!("| {} |\n|", header.join(" | ")));
+                for column in header {
+                    table.push_str(if numeric.contains(column) {
+                        "---:|"
+                    } else {
+                        "---|"
+                    });
+                }
+                table.push('\n');
+                for row in rows {
+                    let cells: Vec<_> = row.iter().map(|c| self.escape(c)).collect();
+                    table.push_str(&format!("| {} |\n", cells.join(" | ")));
+                }
+                table.push('\n');
+            }
+            DocumentFormat::Html => {
+                table.push_str("<table>\n<thead><tr>");
+                for column in header {
+                    table.push_str(&format!("<th>{}</th>", column));
+                }
+                table.push_str("</tr></thead>\n<tbody>\n");
+                for row in rows {
+                    table.push_str("<tr>");
+                    for (column, cell) in header.iter().zip(row.iter()) {
+                        let class = if numeric.contains(column) {
+                            " class=\"number\""
+                        } else {
+                            ""
+                        };
+                        table.push_str(&format!("<td{}>{}</td>", class, escape_html(cell)));
+                    }
+                    table.push_str("</tr>\n");
+                }
+                table.push_str("</tbody>\n</table>\n");
+            }
+        }
+        table
+    }
+}
+
+impl std::str::FromStr for DocumentFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown" | "md" => Ok(DocumentFormat::Markdown),
+            "html" | "htm" => Ok(DocumentFormat::Html),
+            other => Err(Error::AttributeError(format!(
+                "invalid document format {:?}, expected \"markdown\" or \"html\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Replace the `{{key}}` placeholders of a template, unknown keys are kept
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut document = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        document.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = placeholder.find("}}").and_then(|end| {
+            let key = placeholder[2..end].trim();
+            values
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| (v, end + 2))
+        });
+
+        match value {
+            Some((value, length)) => {
+                document.push_str(value);
+                rest = &placeholder[length..];
+            }
+            None => {
+                document.push_str("{{");
+                rest = &placeholder[2..];
+            }
+        }
+    }
+
+    document.push_str(rest);
+    document
+}
+
+fn render_statistics(format: DocumentFormat, statistics: &Statistics) -> String {
+    let [traces, in_traces, events] = statistics.counts();
+    let mut body = format.table(
+        &["Traces", "Events in traces", "Standalone events"],
+        &["Traces", "Events in traces", "Standalone events"],
+        &[vec![
+            traces.to_string(),
+            in_traces.to_string(),
+            (events - in_traces).to_string(),
+        ]],
+    );
+
+    if !statistics.classes().is_empty() {
+        let rows: Vec<_> = statistics
+            .classes()
+            .iter()
+            .map(|(class, count)| vec![class.clone(), count.to_string()])
+            .collect();
+        body.push_str(&format.table(&["Class", "Events"], &["Events"], &rows));
+    }
+
+    format.section("Statistics", &body)
+}
+
+fn render_variants(format: DocumentFormat, variants: &Variants, max_variants: usize) -> String {
+    if variants.variants.is_empty() {
+        return String::new();
+    }
+
+    let page = variants.page(0, max_variants);
+    let rows: Vec<_> = page
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(rank, variant)| {
+            vec![
+                (rank + 1).to_string(),
+                variant.count.to_string(),
+                format!(
+                    "{:.1}%",
+                    100.0 * variant.count as f64 / variants.traces.max(1) as f64
+                ),
+                variant.activities.join(" → "),
+            ]
+        })
+        .collect();
+
+    let mut body = format.table(
+        &["Rank", "Traces", "Share", "Activities"],
+        &["Rank", "Traces", "Share"],
+        &rows,
+    );
+    if page.has_next() {
+        body.push_str(&format.paragraph(&format!(
+            "{} of {} variants shown.",
+            page.variants.len(),
+            variants.variants.len()
+        )));
+    }
+
+    format.section("Variants", &body)
+}
+
+fn render_dfg(format: DocumentFormat, dfg: &Dfg, title: &str) -> String {
+    if dfg.activities.is_empty() {
+        return String::new();
+    }
+
+    let body = match format {
+        DocumentFormat::Markdown => {
+            let ids: Vec<&String> = dfg.activities.keys().collect();
+            let id = |activity: &str| ids.iter().position(|a| *a == activity).unwrap_or(0);
+
+            let mut chart = "flowchart LR".to_string();
+            for (i, (activity, weight)) in dfg.activities.iter().enumerate() {
+                chart.push_str(&format!(
+                    "\n    n{}[\"{} ({})\"]",
+                    i,
+                    activity.replace('"', "#quot;"),
+                    weight
+                ));
+            }
+            for (from, targets) in dfg.edges.iter() {
+                for (to, weight) in targets.iter() {
+                    chart.push_str(&format!("\n    n{} -->|{}| n{}", id(from), weight, id(to)));
+                }
+            }
+            format.code("mermaid", &chart)
+        }
+        DocumentFormat::Html => format!(
+            "<iframe title=\"{}\" srcdoc=\"{}\"></iframe>\n",
+            escape_html(title),
+            escape_html(&dfg.to_html(title))
+        ),
+    };
+
+    format.section("Directly-Follows Graph", &body)
+}
+
+fn render_quality(format: DocumentFormat, quality: &DataQuality) -> String {
+    let rows = [
+        ("Events", quality.events),
+        ("Events without activity", quality.missing_activity),
+        ("Events without timestamp", quality.missing_timestamp),
+        (
+            "Traces out of chronological order",
+            quality.unordered_traces,
+        ),
+    ]
+    .iter()
+    .map(|(check, count)| vec![check.to_string(), count.to_string()])
+    .collect::<Vec<_>>();
+
+    format.section(
+        "Data Quality",
+        &format.table(&["Check", "Count"], &["Count"], &rows),
+    )
+}
+
+/// Collect the artifacts of a stream and render them into a report once it is closed
+///
+/// Additionally, the collected artifacts are emitted.
+///
+pub struct ReportSink<W: io::Write> {
+    writer: W,
+    format: DocumentFormat,
+    title: String,
+    template: Option<String>,
+    max_variants: usize,
+    statistics: StatsCollector,
+    variants: VariantCollector,
+    dfg: DfgGenerator,
+    quality: DataQuality,
+    added: Vec<AnyArtifact>,
+    collected: Vec<AnyArtifact>,
+    flavor: Flavor,
+    /// Whether events follow a trace of an unfolded stream
+    unfolded: bool,
+}
+
+impl<W: io::Write> ReportSink<W> {
+    pub fn new(writer: W, format: DocumentFormat) -> Self {
+        ReportSink {
+            writer,
+            format,
+            title: "Process Report".to_string(),
+            template: None,
+            max_variants: 10,
+            statistics: StatsCollector::default(),
+            variants: VariantCollector::default(),
+            dfg: DfgGenerator::default(),
+            quality: DataQuality::default(),
+            added: Vec::new(),
+            collected: Vec::new(),
+            flavor: Flavor::Log,
+            unfolded: false,
+        }
+    }
+
+    /// Set the title of the report
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Replace the default layout of the format
+    pub fn template<T: Into<String>>(mut self, template: T) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Take activities from the given source, a classifier also counts the events per class
+    pub fn source(mut self, source: ActivitySource) -> Self {
+        self.statistics = match &source {
+            ActivitySource::Classifier(name) => StatsCollector::default().classifier(name.clone()),
+            ActivitySource::Attribute(_) => StatsCollector::default(),
+        };
+        self.variants = VariantCollector::new(source.clone()).max_examples(0);
+        self.dfg = DfgGenerator::new(source);
+        self
+    }
+
+    /// List the `max_variants` most frequent variants only
+    pub fn max_variants(mut self, max_variants: usize) -> Self {
+        self.max_variants = max_variants;
+        self
+    }
+
+    /// Add an artifact to the report
+    pub fn artifact(mut self, artifact: AnyArtifact) -> Self {
+        self.added.push(artifact);
+        self
+    }
+
+    /// Release the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn handlers(&mut self) -> [&mut dyn Handler; 3] {
+        [&mut self.statistics, &mut self.variants, &mut self.dfg]
+    }
+
+    fn inspect(&mut self, event: &Event) {
+        self.quality.events += 1;
+        if self.dfg.activity(event).is_none() {
+            self.quality.missing_activity += 1;
+        }
+        if event.get_value("time:timestamp").is_none() {
+            self.quality.missing_timestamp += 1;
+        }
+    }
+
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<()> {
+        self.inspect(&event);
+
+        let mut event = event;
+        for handler in self.handlers().iter_mut() {
+            event = match handler.on_event(event, in_trace)? {
+                Some(event) => event,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<()> {
+        self.inspect(&event);
+
+        let mut event = event;
+        for handler in self.handlers().iter_mut() {
+            event = match handler.on_unfolded_event(event)? {
+                Some(event) => event,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<()> {
+        let mut last = None;
+        for event in trace.events.iter() {
+            if let Some(timestamp) = event.get_value("time:timestamp") {
+                let timestamp = *timestamp.try_date()?;
+                if last.is_some_and(|last| timestamp < last) {
+                    self.quality.unordered_traces += 1;
+                    break;
+                }
+                last = Some(timestamp);
+            }
+        }
+
+        let mut trace = trace;
+        for handler in self.handlers().iter_mut() {
+            trace = match handler.on_trace(trace)? {
+                Some(trace) => trace,
+                None => return Ok(()),
+            };
+        }
+        for event in mem::take(&mut trace.events) {
+            self.on_event(event, true)?;
+        }
+        Ok(())
+    }
+
+    /// Render the report from the added and collected artifacts
+    fn render(&self) -> Result<String> {
+        let format = self.format;
+        let artifacts = || self.added.iter().chain(self.collected.iter());
+
+        let mut further = String::new();
+        for artifact in self.added.iter() {
+            if artifact.downcast_ref::<Statistics>().is_none()
+                && artifact.downcast_ref::<Variants>().is_none()
+                && artifact.downcast_ref::<Dfg>().is_none()
+                && artifact.downcast_ref::<DataQuality>().is_none()
+            {
+                let json = serde_json::to_string_pretty(artifact)
+                    .map_err(|e| Error::ArtifactError(e.to_string()))?;
+                further.push_str(&format.code("json", &json));
+            }
+        }
+        if !further.is_empty() {
+            further = format.section("Further Artifacts", &further);
+        }
+
+        let title = match format {
+            DocumentFormat::Markdown => self.title.clone(),
+            DocumentFormat::Html => escape_html(&self.title),
+        };
+        let values = [
+            ("title", title),
+            (
+                "statistics",
+                AnyArtifact::find::<Statistics>(&mut artifacts())
+                    .map(|s| render_statistics(format, s))
+                    .unwrap_or_default(),
+            ),
+            (
+                "variants",
+                AnyArtifact::find::<Variants>(&mut artifacts())
+                    .map(|v| render_variants(format, v, self.max_variants))
+                    .unwrap_or_default(),
+            ),
+            (
+                "dfg",
+                AnyArtifact::find::<Dfg>(&mut artifacts())
+                    .map(|d| render_dfg(format, d, &self.title))
+                    .unwrap_or_default(),
+            ),
+            (
+                "quality",
+                AnyArtifact::find::<DataQuality>(&mut artifacts())
+                    .map(|q| render_quality(format, q))
+                    .unwrap_or_default(),
+            ),
+            ("artifacts", further),
+        ];
+
+        Ok(fill(
+            self.template
+                .as_deref()
+                .unwrap_or_else(|| format.template()),
+            &values,
+        ))
+    }
+}
+
+impl<W: io::Write + Send> Sink for ReportSink<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        match component {
+            Component::Meta(meta) => {
+                self.flavor = meta.flavor;
+                let mut meta = meta;
+                for handler in self.handlers().iter_mut() {
+                    meta = handler.on_meta(meta)?;
+                }
+            }
+            Component::Trace(trace) => {
+                self.on_trace(trace)?;
+                self.unfolded = self.flavor == Flavor::Unfolded;
+            }
+            Component::Event(event) if self.unfolded => self.on_unfolded_event(event)?,
+            Component::Event(event) => self.on_event(event, false)?,
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        let mut collected = Vec::new();
+        for handler in self.handlers().iter_mut() {
+            collected.extend(handler.release_artifacts()?);
+        }
+        collected.push(mem::take(&mut self.quality).into());
+        self.collected = collected;
+
+        let report = self.render()?;
+        self.writer.write_all(report.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(mem::take(&mut self.collected))
+    }
+}
+
+impl PluginProvider for ReportSink<File> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "ReportSink",
+            "Render statistics, variants, directly-follows graph, data quality and given artifacts into a Markdown or HTML report",
+            Factory::new(
+                Declaration::default()
+                    .typed_attr("path", "Location of the report, HTML if it ends with .html", AttributeType::String)
+                    .default_attr("title", "Title of the report", |k| {
+                        (k, "Process Report").into()
+                    })
+                    .default_attr("max_variants", "Number of variants listed", |k| {
+                        (k, 10).into()
+                    })
+                    .optional_attr(
+                        "format",
+                        "Either \"markdown\" or \"html\", derived from the path if omitted",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "template",
+                        "HTML file the report is rendered into",
+                        AttributeType::String,
+                    )
+                    .optional_attr(
+                        "classifier",
+                        "Classifier that identifies the activities",
+                        AttributeType::String,
+                    ),
+                FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                    let path = parameters
+                        .acquire_attribute("path")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let format = match parameters.acquire_attribute("format") {
+                        Ok(format) => format.value.try_string()?.parse()?,
+                        Err(_) if path.ends_with(".html") || path.ends_with(".htm") => {
+                            DocumentFormat::Html
+                        }
+                        Err(_) => DocumentFormat::Markdown,
+                    };
+                    let title = parameters
+                        .acquire_attribute("title")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let max_variants =
+                        *parameters.acquire_attribute("max_variants")?.value.try_int()?;
+                    if max_variants < 1 {
+                        return Err(Error::AttributeError(format!(
+                            "max_variants is expected to be positive, got {}",
+                            max_variants
+                        )));
+                    }
+
+                    let mut sink = ReportSink::new(File::create(&path)?, format)
+                        .title(title)
+                        .max_variants(max_variants as usize);
+                    if let Ok(template) = parameters.acquire_attribute("template") {
+                        sink = sink.template(fs::read_to_string(template.value.try_string()?)?);
+                    }
+                    if let Ok(classifier) = parameters.acquire_attribute("classifier") {
+                        sink = sink.source(ActivitySource::Classifier(
+                            classifier.value.try_string()?.to_string(),
+                        ));
+                    }
+
+                    // artifacts are borrowed, a copy is made by a serialization round trip
+                    for artifact in parameters.acquire_artifacts_anon() {
+                        let copy = serde_json::to_value(&*artifact)
+                            .and_then(serde_json::from_value)
+                            .map_err(|e| Error::ArtifactError(e.to_string()))?;
+                        sink = sink.artifact(copy);
+                    }
+
+                    Ok(sink.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::bottleneck::Bottlenecks;
+
+    use super::*;
+
+    #[test]
+    fn test_report_sink() {
+        let mut sink = ReportSink::new(Vec::new(), DocumentFormat::Markdown)
+            .title("L1")
+            .max_variants(2)
+            .source(ActivitySource::Classifier("EventName".to_string()))
+            .artifact(Bottlenecks::default().into());
+        let artifacts = sink
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let markdown = String::from_utf8(sink.into_inner()).unwrap();
+
+        assert!(markdown.starts_with("# L1\n\n## Statistics\n"));
+        assert!(markdown.contains("| 6 | 23 | 0 |"));
+        assert!(markdown.contains("| 1 | 3 | 50.0% | a → b → c → d |"));
+        assert!(markdown.contains("2 of 3 variants shown."));
+        assert!(markdown.contains("```mermaid\nflowchart LR\n    n0[\"a (6)\"]"));
+        assert!(markdown.contains("| Events without timestamp | 0 |"));
+        assert!(markdown.contains("## Further Artifacts"));
+
+        let quality = AnyArtifact::find::<DataQuality>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!((quality.events, quality.missing_activity), (23, 0));
+        let statistics = AnyArtifact::find::<Statistics>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(statistics.classes()["a"], 6);
+
+        let mut sink = ReportSink::new(Vec::new(), DocumentFormat::Html)
+            .title("<L1>")
+            .template("<h1>{{title}}</h1>{{dfg}}{{unknown}}");
+        sink.consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let html = String::from_utf8(sink.into_inner()).unwrap();
+
+        assert!(html.starts_with("<h1>&lt;L1&gt;</h1><section>\n<h2>Directly-Follows Graph</h2>"));
+        assert!(html.contains("<iframe title=\"&lt;L1&gt;\" srcdoc=\"&lt;!DOCTYPE html&gt;"));
+        assert!(html.ends_with("</section>\n{{unknown}}"));
+    }
+}
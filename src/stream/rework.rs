@@ -0,0 +1,255 @@
+//! Measure repetitions within traces
+//!
+//! Rework is commonly considered waste. This module counts, per trace and per activity, how often
+//! - an activity is repeated at all (_rework_),
+//! - an activity directly follows itself (_self-loop_) and
+//! - two activities alternate, i.e. `a b a` (_ping-pong_).
+//!
+//! Instead of activities, any other event attribute such as `org:resource` may be used. Optionally,
+//! the cost of repeated executions is accumulated from a numeric event attribute.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, AttributeValue, Stream, Trace,
+};
+use crate::Result;
+
+/// Repetition counts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReworkCounts {
+    pub rework: usize,
+    pub self_loops: usize,
+    pub ping_pongs: usize,
+    pub cost: f64,
+}
+
+impl ReworkCounts {
+    fn add(&mut self, other: &ReworkCounts) {
+        self.rework += other.rework;
+        self.self_loops += other.self_loops;
+        self.ping_pongs += other.ping_pongs;
+        self.cost += other.cost;
+    }
+}
+
+/// Repetition metrics of an event stream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rework {
+    /// Counts per trace, identified by its `concept:name` if present
+    pub traces: Vec<(Option<String>, ReworkCounts)>,
+    /// Counts per activity (or whatever attribute was chosen)
+    pub activities: BTreeMap<String, ReworkCounts>,
+}
+
+impl Rework {
+    /// Aggregate counts over all traces
+    pub fn total(&self) -> ReworkCounts {
+        let mut total = ReworkCounts::default();
+        for (_, counts) in self.traces.iter() {
+            total.add(counts);
+        }
+        total
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Rework {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Collect repetition metrics from traces
+#[derive(Debug)]
+pub struct ReworkCollector {
+    key: String,
+    cost_key: Option<String>,
+    rework: Rework,
+}
+
+impl Default for ReworkCollector {
+    fn default() -> Self {
+        Self::new("concept:name", None)
+    }
+}
+
+impl ReworkCollector {
+    /// Create a new collector that identifies events by `key` and optionally accumulates costs
+    pub fn new<K: Into<String>>(key: K, cost_key: Option<String>) -> Self {
+        Self {
+            key: key.into(),
+            cost_key,
+            rework: Rework::default(),
+        }
+    }
+
+    fn cost(&self, event: &dyn AttributeContainer) -> f64 {
+        match self.cost_key.as_ref().and_then(|k| event.get_value(k)) {
+            Some(AttributeValue::Float(cost)) => *cost,
+            Some(AttributeValue::Int(cost)) => *cost as f64,
+            _ => 0.,
+        }
+    }
+}
+
+impl Handler for ReworkCollector {
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let mut counts = ReworkCounts::default();
+        let mut seen = HashSet::new();
+        let mut history: [Option<String>; 2] = [None, None];
+
+        for event in trace.events.iter() {
+            let label = match event.get_value(&self.key) {
+                Some(AttributeValue::String(label)) | Some(AttributeValue::Id(label)) => {
+                    label.to_string()
+                }
+                _ => {
+                    history = [None, None];
+                    continue;
+                }
+            };
+
+            let mut delta = ReworkCounts::default();
+
+            if !seen.insert(label.clone()) {
+                delta.rework += 1;
+                delta.cost += self.cost(event);
+            }
+
+            match &history {
+                [_, Some(previous)] if *previous == label => delta.self_loops += 1,
+                [Some(before), Some(_)] if *before == label => delta.ping_pongs += 1,
+                _ => (),
+            }
+
+            counts.add(&delta);
+            self.rework
+                .activities
+                .entry(label.clone())
+                .or_default()
+                .add(&delta);
+
+            history = [history[1].take(), Some(label)];
+        }
+
+        let name = match trace.get_value("concept:name") {
+            Some(name) => Some(name.try_string()?.to_string()),
+            None => None,
+        };
+        self.rework.traces.push((name, counts));
+
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![mem::take(&mut self.rework).into()])
+    }
+}
+
+impl PluginProvider for ReworkCollector {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Rework",
+            "Count repetitions, self-loops and ping-pong patterns within traces",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be analyzed")
+                    .default_attr("key", "Event attribute to compare", |k| {
+                        (k, "concept:name").into()
+                    })
+                    .optional_attr(
+                        "cost",
+                        "Event attribute that holds the cost of rework",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let key = parameters
+                        .acquire_attribute("key")?
+                        .value
+                        .try_string()?
+                        .to_string();
+                    let cost_key = match parameters.acquire_attribute("cost") {
+                        Ok(cost) => Some(cost.value.try_string()?.to_string()),
+                        Err(_) => None,
+                    };
+
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        ReworkCollector::new(key, cost_key),
+                    ))
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::{Component, Event};
+
+    use super::*;
+
+    fn trace(activities: &str) -> Trace {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", activities));
+
+        for (i, activity) in activities.chars().enumerate() {
+            let mut event = Event::default();
+            event
+                .attributes
+                .insert(("concept:name", activity.to_string()));
+            event.attributes.insert(("cost", i as i64));
+            trace.events.push(event);
+        }
+
+        trace
+    }
+
+    #[test]
+    fn test_rework() {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Default::default()))));
+        for activities in ["abcd", "abbc", "abab", "aaa"].iter() {
+            buffer.push(Ok(Some(Component::Trace(trace(activities)))));
+        }
+
+        let mut observer =
+            ReworkCollector::new("concept:name", Some("cost".into())).into_observer(buffer);
+        let artifacts = consume(&mut observer).unwrap();
+        let rework = AnyArtifact::find::<Rework>(&mut artifacts.iter().flatten()).unwrap();
+
+        let counts: Vec<_> = rework
+            .traces
+            .iter()
+            .map(|(_, c)| [c.rework, c.self_loops, c.ping_pongs])
+            .collect();
+        assert_eq!(counts, [[0, 0, 0], [1, 1, 0], [2, 0, 2], [2, 2, 0]]);
+
+        let total = rework.total();
+        assert_eq!(total.rework, 5);
+        assert!(is_close!(total.cost, 2. + 2. + 3. + 1. + 2.));
+
+        assert_eq!(rework.activities["a"].rework, 3);
+        assert_eq!(rework.activities["b"].self_loops, 1);
+        assert_eq!(rework.activities["b"].ping_pongs, 1);
+        assert_eq!(rework.traces[0].0.as_deref(), Some("abcd"));
+    }
+}
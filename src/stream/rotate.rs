@@ -0,0 +1,360 @@
+//! Split the output of file writing sinks into several files
+//!
+//! Long running pipelines produce files that quickly become too large to handle. A
+//! `RotatingWriter` wraps a file writing sink and starts a new file once the current one holds a
+//! given number of traces or has grown beyond a given size. Files are numbered in sequence, either
+//! by replacing `{sequence}` in the path or by inserting the number in front of the file extension,
+//! e.g. `log.xes` becomes `log.0001.xes`, `log.0002.xes` and so on. Each file starts with the meta
+//! data of the stream. The produced files are released as `Manifest` artifact.
+//!
+//! File writing plugins accept the optional attributes `max_traces` and `max_megabytes` to enable
+//! rotation. Files are created according to the `FileOptions` of the plugin.
+//!
+
+use std::any::Any;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::output::{FileOptions, OutputFile};
+use crate::stream::plugin::{Declaration, Parameters};
+use crate::stream::{AnyArtifact, Artifact, AttributeType, Component, Meta, Sink};
+use crate::{Error, Result};
+
+/// Writer that counts the bytes written through it
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    count: Arc<AtomicU64>,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CountingWriter {
+            inner,
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Shared counter of written bytes
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.count.clone()
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// File writer handed to the wrapped sinks
+pub type RotatingFile = CountingWriter<OutputFile>;
+
+/// Creates a sink that writes into the given file
+pub type SinkBuilder = Box<dyn Fn(RotatingFile) -> Result<Box<dyn Sink>> + Send>;
+
+/// Limits that trigger the start of a new file
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rotation {
+    pub traces: Option<usize>,
+    pub bytes: Option<u64>,
+}
+
+impl Rotation {
+    /// Start a new file after `traces` traces
+    pub fn traces(mut self, traces: usize) -> Self {
+        self.traces = Some(traces);
+        self
+    }
+
+    /// Start a new file once the current one exceeds `megabytes`
+    pub fn megabytes(mut self, megabytes: f64) -> Self {
+        self.bytes = Some((megabytes * 1_000_000.0) as u64);
+        self
+    }
+
+    /// Declare the optional plugin attributes `max_traces` and `max_megabytes`
+    pub fn declare(declaration: Declaration) -> Declaration {
+        declaration
+            .optional_attr(
+                "max_traces",
+                "Start a new file after this many traces",
+                AttributeType::Int,
+            )
+            .optional_attr(
+                "max_megabytes",
+                "Start a new file once the current one exceeds this size",
+                AttributeType::Float,
+            )
+    }
+
+    /// Read the optional plugin attributes `max_traces` and `max_megabytes`
+    pub fn from_parameters(parameters: &mut Parameters) -> Result<Option<Self>> {
+        let mut rotation = Rotation::default();
+
+        if let Ok(attribute) = parameters.acquire_attribute("max_traces") {
+            let traces = *attribute.value.try_int()?;
+            if traces < 1 {
+                return Err(Error::AttributeError(format!(
+                    "max_traces is expected to be positive, got {}",
+                    traces
+                )));
+            }
+            rotation = rotation.traces(traces as usize);
+        }
+
+        if let Ok(attribute) = parameters.acquire_attribute("max_megabytes") {
+            let megabytes = *attribute.value.try_float()?;
+            if megabytes <= 0.0 {
+                return Err(Error::AttributeError(format!(
+                    "max_megabytes is expected to be positive, got {}",
+                    megabytes
+                )));
+            }
+            rotation = rotation.megabytes(megabytes);
+        }
+
+        Ok(if rotation == Rotation::default() {
+            None
+        } else {
+            Some(rotation)
+        })
+    }
+}
+
+/// File produced by a rotating writer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub traces: usize,
+    pub events: usize,
+    pub bytes: u64,
+}
+
+/// Files produced by a rotating writer, in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+#[typetag::serde]
+impl Artifact for Manifest {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Path of the file with the given sequence number
+pub fn sequence_path(path: &str, sequence: usize) -> String {
+    let number = format!("{:04}", sequence);
+    if path.contains("{sequence}") {
+        return path.replace("{sequence}", &number);
+    }
+
+    let path = Path::new(path);
+    let file_name = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{}.{}.{}",
+            stem.to_string_lossy(),
+            number,
+            extension.to_string_lossy()
+        ),
+        _ => format!("{}.{}", path.to_string_lossy(), number),
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+/// Distribute the stream over a sequence of files
+pub struct RotatingWriter {
+    path: String,
+    rotation: Rotation,
+    builder: SinkBuilder,
+    options: FileOptions,
+    meta: Option<Meta>,
+    current: Option<(Box<dyn Sink>, Arc<AtomicU64>)>,
+    manifest: Manifest,
+}
+
+impl RotatingWriter {
+    pub fn new<T: Into<String>>(path: T, rotation: Rotation, builder: SinkBuilder) -> Self {
+        RotatingWriter {
+            path: path.into(),
+            rotation,
+            builder,
+            options: FileOptions::default(),
+            meta: None,
+            current: None,
+            manifest: Manifest::default(),
+        }
+    }
+
+    /// Create files with the given options
+    pub fn options(mut self, options: FileOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Current sink, a new file is started if there is none
+    fn sink(&mut self) -> Result<&mut Box<dyn Sink>> {
+        if self.current.is_none() {
+            let path = sequence_path(&self.path, self.manifest.files.len() + 1);
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let file = CountingWriter::new(self.options.create(&path)?);
+            let counter = file.counter();
+            let mut sink = (self.builder)(file)?;
+            sink.on_open()?;
+            if let Some(meta) = &self.meta {
+                sink.on_component(Component::Meta(meta.clone()))?;
+            }
+
+            self.manifest.files.push(ManifestEntry {
+                path,
+                traces: 0,
+                events: 0,
+                bytes: 0,
+            });
+            self.current = Some((sink, counter));
+        }
+
+        Ok(&mut self.current.as_mut().unwrap().0)
+    }
+
+    /// Close the current file, if any
+    fn rotate(&mut self) -> Result<()> {
+        if let Some((mut sink, counter)) = self.current.take() {
+            sink.on_close()?;
+            drop(sink);
+            self.manifest.files.last_mut().unwrap().bytes = counter.load(Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn exhausted(&self) -> bool {
+        let (entry, counter) = match (self.manifest.files.last(), &self.current) {
+            (Some(entry), Some((_, counter))) => (entry, counter),
+            _ => return false,
+        };
+
+        self.rotation.traces.is_some_and(|t| entry.traces >= t)
+            || self
+                .rotation
+                .bytes
+                .is_some_and(|b| counter.load(Ordering::Relaxed) >= b)
+    }
+}
+
+impl Sink for RotatingWriter {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        let (traces, events) = match &component {
+            Component::Meta(meta) => {
+                self.meta = Some(meta.clone());
+                return match &mut self.current {
+                    Some((sink, _)) => sink.on_component(component),
+                    None => Ok(()),
+                };
+            }
+            Component::Trace(trace) => (1, trace.events.len()),
+            Component::Event(_) => (0, 1),
+        };
+
+        self.sink()?.on_component(component)?;
+        let entry = self.manifest.files.last_mut().unwrap();
+        entry.traces += traces;
+        entry.events += events;
+
+        if self.exhausted() {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.rotate()
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.manifest.clone().into()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::xes::{XesReader, XesWriter};
+    use crate::stream::{Event, Trace};
+
+    use super::*;
+
+    #[test]
+    fn test_sequence_path() {
+        assert_eq!(sequence_path("out/log.xes", 1), "out/log.0001.xes");
+        assert_eq!(sequence_path("log_{sequence}.csv", 12), "log_0012.csv");
+        assert_eq!(sequence_path("log", 3), "log.0003");
+    }
+
+    fn buffer(traces: usize) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for _ in 0..traces {
+            let mut trace = Trace::default();
+            trace.events.push(Event::default());
+            buffer.push(Ok(Some(Component::Trace(trace))));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_rotating_writer() {
+        let directory = std::env::temp_dir().join(format!("promi_rotate_{}", std::process::id()));
+        let path = directory.join("log.xes").to_str().unwrap().to_string();
+        let builder: fn() -> SinkBuilder =
+            || Box::new(|file| Ok(XesWriter::new(file).into_boxed()));
+
+        let mut writer = RotatingWriter::new(&path, Rotation::default().traces(2), builder());
+        let artifacts = writer.consume(&mut buffer(5)).unwrap();
+        let manifest = AnyArtifact::find::<Manifest>(&mut artifacts.iter().flatten()).unwrap();
+
+        let traces: Vec<_> = manifest.files.iter().map(|f| f.traces).collect();
+        assert_eq!(traces, [2, 2, 1]);
+        assert_eq!(manifest.files[2].path, sequence_path(&path, 3));
+
+        for entry in manifest.files.iter() {
+            let file = File::open(&entry.path).unwrap();
+            assert_eq!(file.metadata().unwrap().len(), entry.bytes);
+
+            let mut log = Log::default();
+            log.consume(&mut XesReader::from(BufReader::new(file)))
+                .unwrap();
+            assert_eq!(log.traces.len(), entry.traces);
+        }
+
+        // any non-empty file exceeds a single byte
+        let mut writer = RotatingWriter::new(&path, Rotation::default().megabytes(1e-6), builder());
+        let artifacts = writer.consume(&mut buffer(3)).unwrap();
+        let manifest = AnyArtifact::find::<Manifest>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(manifest.files.len(), 3);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
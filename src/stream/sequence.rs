@@ -0,0 +1,314 @@
+//! Deterministic order of stream components
+//!
+//! Operators that merge or parallelize streams may emit the same components in a different order
+//! from run to run. Features that rely on an identical replay, e.g. checkpoints that resume a
+//! stream at a given position or comparisons of repeated runs, need a well-defined order instead.
+//! This module defines the canonical order of components:
+//! 1. meta data comes first,
+//! 2. traces precede standalone events,
+//! 3. traces are ordered by the timestamp of their first event and standalone events by their
+//!    timestamp, components without timestamp come last,
+//! 4. ties are broken by `concept:name` and
+//! 5. finally by a fingerprint of the content, such that only identical components are equal.
+//!
+//! `CanonicalOrder` either establishes this order by sorting or verifies that a stream adheres to
+//! it. Sorting buffers the whole stream, unless a window is set which bounds the memory at the cost
+//! of only correcting disorder within that window.
+//!
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AttributeContainer, AttributeType, AttributeValue, Component, ResOpt, Stream};
+use crate::{DateTime, Error, Result};
+
+/// Position of a component in the canonical order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SequenceKey {
+    event: bool,
+    untimed: bool,
+    timestamp: Option<DateTime>,
+    name: String,
+    fingerprint: u64,
+}
+
+/// FNV-1a hash, stable across platforms and releases
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn timestamp(component: &dyn AttributeContainer) -> Option<DateTime> {
+    match component.get_value("time:timestamp") {
+        Some(AttributeValue::Date(timestamp)) => Some(*timestamp),
+        _ => None,
+    }
+}
+
+impl SequenceKey {
+    /// Key of a trace or event, meta data has no key as it always comes first
+    pub fn of(component: &Component) -> Result<Option<Self>> {
+        let (event, timestamp, name) = match component {
+            Component::Meta(_) => return Ok(None),
+            Component::Trace(trace) => (
+                false,
+                trace.events.first().and_then(|e| timestamp(e)),
+                trace.get_value("concept:name"),
+            ),
+            Component::Event(event) => (true, timestamp(event), event.get_value("concept:name")),
+        };
+
+        let content = serde_json::to_vec(component)
+            .map_err(|e| Error::StreamError(format!("unable to fingerprint component: {}", e)))?;
+
+        Ok(Some(SequenceKey {
+            event,
+            untimed: timestamp.is_none(),
+            timestamp,
+            name: match name {
+                Some(AttributeValue::String(name)) | Some(AttributeValue::Id(name)) => {
+                    name.to_string()
+                }
+                _ => String::new(),
+            },
+            fingerprint: fingerprint(&content),
+        }))
+    }
+}
+
+/// What a sequencer does about the order of components
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SequenceMode {
+    /// Sort components, optionally within a window of the given number of components
+    Sort(Option<usize>),
+    /// Fail on the first component that is out of order
+    Verify,
+}
+
+impl FromStr for SequenceMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sort" => Ok(SequenceMode::Sort(None)),
+            "verify" => Ok(SequenceMode::Verify),
+            other => Err(Error::AttributeError(format!(
+                "unknown sequence mode: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Establish or verify the canonical order of stream components
+pub struct CanonicalOrder<T: Stream> {
+    stream: T,
+    mode: SequenceMode,
+    /// Components by key and arrival, as identical components share a key
+    buffer: BTreeMap<(SequenceKey, usize), Component>,
+    arrivals: usize,
+    last: Option<SequenceKey>,
+    exhausted: bool,
+}
+
+impl<T: Stream> CanonicalOrder<T> {
+    pub fn new(stream: T, mode: SequenceMode) -> Self {
+        CanonicalOrder {
+            stream,
+            mode,
+            buffer: BTreeMap::new(),
+            arrivals: 0,
+            last: None,
+            exhausted: false,
+        }
+    }
+
+    fn pop_first(&mut self) -> Option<Component> {
+        let key = self.buffer.keys().next().cloned()?;
+        self.buffer.remove(&key)
+    }
+
+    fn sort(&mut self, window: Option<usize>) -> ResOpt {
+        while !self.exhausted && window.is_none_or(|w| self.buffer.len() <= w) {
+            match self.stream.next()? {
+                Some(component) => match SequenceKey::of(&component)? {
+                    Some(key) => {
+                        self.buffer.insert((key, self.arrivals), component);
+                        self.arrivals += 1;
+                    }
+                    None => return Ok(Some(component)),
+                },
+                None => self.exhausted = true,
+            }
+        }
+
+        Ok(self.pop_first())
+    }
+
+    fn verify(&mut self) -> ResOpt {
+        let component = match self.stream.next()? {
+            Some(component) => component,
+            None => return Ok(None),
+        };
+
+        if let Some(key) = SequenceKey::of(&component)? {
+            if self.last.as_ref().is_some_and(|last| key < *last) {
+                return Err(Error::StateError(format!(
+                    "{:?} is out of canonical order",
+                    component.hint()
+                )));
+            }
+            self.last = Some(key);
+        }
+        Ok(Some(component))
+    }
+}
+
+impl<T: Stream> Stream for CanonicalOrder<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        match self.mode {
+            SequenceMode::Sort(window) => self.sort(window),
+            SequenceMode::Verify => self.verify(),
+        }
+    }
+}
+
+impl PluginProvider for CanonicalOrder<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "CanonicalOrder",
+            "Sort stream components into their canonical order, optionally within a window, or verify that order",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be ordered")
+                    .default_attr("mode", "Either sort or verify", |k| (k, "sort").into())
+                    .optional_attr(
+                        "window",
+                        "Number of components sorted at a time",
+                        AttributeType::Int,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let mut mode = parameters
+                        .acquire_attribute("mode")?
+                        .value
+                        .try_string()?
+                        .parse()?;
+                    if let Ok(window) = parameters.acquire_attribute("window") {
+                        let window = *window.value.try_int()?;
+                        if window < 1 {
+                            return Err(Error::AttributeError(format!(
+                                "window is expected to be positive, got {}",
+                                window
+                            )));
+                        }
+                        if let SequenceMode::Sort(_) = mode {
+                            mode = SequenceMode::Sort(Some(window as usize));
+                        }
+                    }
+
+                    Ok(CanonicalOrder::new(parameters.acquire_stream("inner")?, mode).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::void::consume;
+    use crate::stream::{Event, Meta, Sink, Trace};
+
+    use super::*;
+
+    fn component(name: &str, second: Option<u32>, trace: bool) -> Component {
+        let mut event = Event::default();
+        event.attributes.insert(("concept:name", name));
+        if let Some(second) = second {
+            let timestamp =
+                DateTime::parse_from_rfc3339(&format!("2020-01-01T00:00:{:02}+00:00", second))
+                    .unwrap();
+            event.attributes.insert(("time:timestamp", timestamp));
+        }
+
+        if trace {
+            let mut trace = Trace::default();
+            trace.attributes.insert(("concept:name", name));
+            trace.events.push(event);
+            Component::Trace(trace)
+        } else {
+            Component::Event(event)
+        }
+    }
+
+    fn buffer(components: &[Component]) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        for component in components.iter() {
+            buffer.push(Ok(Some(component.clone())));
+        }
+        buffer
+    }
+
+    fn names(mut stream: impl Stream) -> Vec<String> {
+        let mut log = Log::default();
+        log.consume(&mut stream).unwrap();
+        log.traces
+            .iter()
+            .map(|t| t.get_value("concept:name").unwrap())
+            .chain(
+                log.events
+                    .iter()
+                    .map(|e| e.get_value("concept:name").unwrap()),
+            )
+            .map(|n| n.try_string().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sequencer() {
+        let components = [
+            component("x", Some(1), false),
+            component("c", None, true),
+            component("b", Some(2), true),
+            component("a", Some(2), true),
+            component("d", Some(1), true),
+        ];
+        let mut shuffled = components.to_vec();
+        shuffled.reverse();
+
+        let expected = ["d", "a", "b", "c", "x"];
+        for input in [&components[..], &shuffled[..]].iter() {
+            let sorted = CanonicalOrder::new(buffer(input), SequenceMode::Sort(None));
+            assert_eq!(names(sorted), expected);
+        }
+
+        // a window only corrects local disorder
+        let windowed = CanonicalOrder::new(buffer(&components[1..]), SequenceMode::Sort(Some(1)));
+        assert_eq!(names(windowed), ["b", "a", "d", "c"]);
+
+        let sorted = CanonicalOrder::new(buffer(&components), SequenceMode::Sort(None));
+        assert!(consume(&mut CanonicalOrder::new(sorted, SequenceMode::Verify)).is_ok());
+        match consume(&mut CanonicalOrder::new(
+            buffer(&components),
+            SequenceMode::Verify,
+        )) {
+            Err(Error::StateError(_)) => (),
+            other => panic!("expected state error, got {:?}", other),
+        }
+    }
+}
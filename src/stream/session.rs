@@ -0,0 +1,204 @@
+//! Segment traces into sessions
+//!
+//! Logs of user interactions often hold all events of a user in a single trace, although the
+//! user came back several times. `Sessionize` splits each trace into sessions whenever the time
+//! passed between two consecutive events exceeds a given gap. Each session becomes a trace of its
+//! own that inherits all attributes of the original trace and carries its session index, starting
+//! at zero, in the attribute `session`.
+//!
+//! Events without `time:timestamp` stay in the current session. Standalone events and meta data are
+//! passed through.
+//!
+
+use std::collections::VecDeque;
+
+use chrono::Duration;
+
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{AttributeContainer, AttributeValue, Component, ResOpt, Stream, Trace};
+use crate::{DateTime, Error, Result};
+
+/// Split traces by inactivity gaps
+pub struct Sessionize<T: Stream> {
+    stream: T,
+    gap: Duration,
+    key: String,
+    sessions: VecDeque<Trace>,
+}
+
+impl<T: Stream> Sessionize<T> {
+    /// Start a new session once no event occurred for longer than `gap`
+    pub fn new(stream: T, gap: Duration) -> Self {
+        Sessionize {
+            stream,
+            gap,
+            key: "session".to_string(),
+            sessions: VecDeque::new(),
+        }
+    }
+
+    /// Stamp the session index into the given trace attribute
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    fn timestamp(event: &dyn AttributeContainer) -> Result<Option<DateTime>> {
+        match event.get_value("time:timestamp") {
+            Some(value) => Ok(Some(*value.try_date()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn split(&mut self, mut trace: Trace) -> Result<()> {
+        let events = std::mem::take(&mut trace.events);
+        let mut session = trace.clone();
+        let mut last: Option<DateTime> = None;
+
+        for event in events {
+            let timestamp = Self::timestamp(&event)?;
+            if let (Some(last), Some(timestamp)) = (last, timestamp) {
+                if timestamp.signed_duration_since(last) > self.gap {
+                    self.sessions
+                        .push_back(std::mem::replace(&mut session, trace.clone()));
+                }
+            }
+
+            last = timestamp.or(last);
+            session.events.push(event);
+        }
+        self.sessions.push_back(session);
+
+        for (i, session) in self.sessions.iter_mut().enumerate() {
+            session
+                .attributes
+                .insert((self.key.as_str(), AttributeValue::Int(i as i64)));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Stream> Stream for Sessionize<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        if let Some(session) = self.sessions.pop_front() {
+            return Ok(Some(Component::Trace(session)));
+        }
+
+        match self.stream.next()? {
+            Some(Component::Trace(trace)) => {
+                self.split(trace)?;
+                Ok(self.sessions.pop_front().map(Component::Trace))
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+impl PluginProvider for Sessionize<Box<dyn Stream>> {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "Sessionize",
+            "Split traces into sessions whenever consecutive events are more than gap seconds apart",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be segmented")
+                    .default_attr("gap", "Maximal inactivity within a session in seconds", |k| {
+                        (k, 1800).into()
+                    })
+                    .default_attr("key", "Trace attribute that holds the session index", |k| {
+                        (k, "session").into()
+                    }),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let gap = *parameters.acquire_attribute("gap")?.value.try_int()?;
+                    if gap < 0 {
+                        return Err(Error::AttributeError(format!(
+                            "gap is expected to be non-negative, got {}",
+                            gap
+                        )));
+                    }
+
+                    Ok(Sessionize::new(
+                        parameters.acquire_stream("inner")?,
+                        Duration::seconds(gap),
+                    )
+                    .key(parameters.acquire_attribute("key")?.value.try_string()?)
+                    .into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{Event, Sink};
+
+    use super::*;
+
+    fn trace(name: &str, events: &[(&str, Option<u32>)]) -> Component {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", name));
+        for (activity, minute) in events.iter() {
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", *activity));
+            if let Some(minute) = minute {
+                let timestamp =
+                    DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute))
+                        .unwrap();
+                event.attributes.insert(("time:timestamp", timestamp));
+            }
+            trace.events.push(event);
+        }
+        Component::Trace(trace)
+    }
+
+    #[test]
+    fn test_sessionize() {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(trace(
+            "u1",
+            &[
+                ("login", Some(0)),
+                ("view", Some(5)),
+                ("login", Some(40)),
+                ("help", None),
+                ("buy", Some(45)),
+                ("login", Some(59)),
+            ],
+        ))));
+        buffer.push(Ok(Some(trace("u2", &[("login", Some(0))]))));
+
+        let mut log = Log::default();
+        log.consume(&mut Sessionize::new(buffer, Duration::minutes(8)))
+            .unwrap();
+
+        let sessions: Vec<_> = log
+            .traces
+            .iter()
+            .map(|t| {
+                (
+                    t.get_value("concept:name").unwrap().try_string().unwrap(),
+                    *t.get_value("session").unwrap().try_int().unwrap(),
+                    t.events.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![("u1", 0, 2), ("u1", 1, 3), ("u1", 2, 1), ("u2", 0, 1)]
+        );
+    }
+}
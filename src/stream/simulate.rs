@@ -0,0 +1,431 @@
+//! Generate synthetic event logs from process models
+//!
+//! The `PlayOut` source randomly fires enabled transitions of a Petri net (or of the workflow net
+//! of a process tree) until the final marking is reached, emitting a trace per run. Labelled
+//! transitions become events, silent ones leave no trace in the log. This is meant for testing
+//! discovery and conformance algorithms with logs of known origin and for benchmarking pipelines
+//! end to end.
+//!
+//! Cases arrive one after another according to an arrival process. The events of a case take
+//! place sequentially, each one completing after a randomly drawn duration. Noise replaces the
+//! activity of an event by a random activity of the model.
+//!
+//! Runs that deadlock or exceed the maximum length end prematurely, such that play out always
+//! terminates.
+//!
+
+use std::collections::HashMap;
+
+use rand::{random, Rng};
+use rand_pcg::Pcg64;
+
+use crate::model::petri::{Marking, PetriNet};
+use crate::model::process_tree::ProcessTree;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::{AttributeType, Component, Event, Meta, ResOpt, Stream, Trace};
+use crate::{DateTime, Error, Result};
+
+/// Random number of seconds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    Fixed(f64),
+    /// Uniformly distributed between a minimum and a maximum
+    Uniform(f64, f64),
+    /// Exponentially distributed with the given mean
+    Exponential(f64),
+}
+
+impl Distribution {
+    /// Draw a non-negative sample
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        let sample = match self {
+            Distribution::Fixed(value) => *value,
+            Distribution::Uniform(min, max) if min < max => rng.gen_range(*min..*max),
+            Distribution::Uniform(min, _) => *min,
+            Distribution::Exponential(mean) => -mean * (1.0 - rng.gen::<f64>()).ln(),
+        };
+        sample.max(0.0)
+    }
+}
+
+/// Stream source that plays out a process model
+pub struct PlayOut {
+    net: PetriNet,
+    initial: Marking,
+    final_marking: Option<Marking>,
+    activities: Vec<String>,
+    traces: usize,
+    max_length: usize,
+    noise: f64,
+    arrival: Distribution,
+    durations: HashMap<String, Distribution>,
+    default_duration: Distribution,
+    rng: Pcg64,
+    clock: DateTime,
+    emitted: Option<usize>,
+}
+
+impl PlayOut {
+    /// Play out a net from its initial marking until it reaches the final one
+    ///
+    /// Without a final marking, a run ends once no transition is enabled anymore.
+    ///
+    pub fn new(net: PetriNet, initial: Marking, final_marking: Option<Marking>) -> Result<Self> {
+        for marking in std::iter::once(&initial).chain(final_marking.iter()) {
+            if marking.0.len() != net.places.len() {
+                return Err(Error::ModelError(format!(
+                    "marking of {} places doesn't match net of {} places",
+                    marking.0.len(),
+                    net.places.len()
+                )));
+            }
+        }
+
+        let mut activities: Vec<String> = net
+            .transitions
+            .iter()
+            .filter_map(|t| t.label.clone())
+            .collect();
+        activities.sort();
+        activities.dedup();
+
+        Ok(PlayOut {
+            net,
+            initial,
+            final_marking,
+            activities,
+            traces: 100,
+            max_length: 100,
+            noise: 0.0,
+            arrival: Distribution::Exponential(3600.0),
+            durations: HashMap::new(),
+            default_duration: Distribution::Exponential(600.0),
+            rng: Pcg64::new(random(), 0),
+            clock: DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap(),
+            emitted: None,
+        })
+    }
+
+    /// Play out a workflow net with a single token in its source and sink places respectively
+    pub fn from_net(net: PetriNet) -> Result<Self> {
+        let (sources, sinks) = (net.source_places(), net.sink_places());
+        if sources.is_empty() || sinks.is_empty() {
+            return Err(Error::ModelError(
+                "workflow net requires source and sink places".to_string(),
+            ));
+        }
+
+        let tokens = |places: Vec<usize>| places.into_iter().map(|p| (p, 1)).collect::<Vec<_>>();
+        let initial = net.marking(&tokens(sources));
+        let final_marking = net.marking(&tokens(sinks));
+        Self::new(net, initial, Some(final_marking))
+    }
+
+    /// Play out a process tree via its workflow net
+    pub fn from_tree(tree: &ProcessTree) -> Result<Self> {
+        Self::from_net(tree.to_petri_net()?)
+    }
+
+    /// Number of traces to generate, 100 by default
+    pub fn trace_count(mut self, traces: usize) -> Self {
+        self.traces = traces;
+        self
+    }
+
+    /// Maximum number of transitions fired per trace, 100 by default
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Probability of an event to carry a random activity instead of the one of its transition
+    ///
+    /// # Panics
+    ///
+    /// Panics if `noise` is not within [0, 1].
+    pub fn noise(mut self, noise: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&noise),
+            "noise is expected to be within [0, 1], got {}",
+            noise
+        );
+        self.noise = noise;
+        self
+    }
+
+    /// Seconds between the starts of subsequent cases, exponential with a mean of 1h by default
+    pub fn arrival(mut self, arrival: Distribution) -> Self {
+        self.arrival = arrival;
+        self
+    }
+
+    /// Seconds an activity takes
+    pub fn duration<S: Into<String>>(mut self, activity: S, duration: Distribution) -> Self {
+        self.durations.insert(activity.into(), duration);
+        self
+    }
+
+    /// Seconds any other activity takes, exponential with a mean of 10min by default
+    pub fn default_duration(mut self, duration: Distribution) -> Self {
+        self.default_duration = duration;
+        self
+    }
+
+    /// Time at which the first case starts, 2020-01-01 UTC by default
+    pub fn start(mut self, start: DateTime) -> Self {
+        self.clock = start;
+        self
+    }
+
+    /// Seed the random number generator for reproducible logs
+    pub fn seed(mut self, seed: u128) -> Self {
+        self.rng = Pcg64::new(seed, 0);
+        self
+    }
+
+    fn seconds(seconds: f64) -> chrono::Duration {
+        chrono::Duration::milliseconds((seconds * 1000.0) as i64)
+    }
+
+    // sequence of activities of a single run
+    fn run(&mut self) -> Vec<String> {
+        let mut marking = self.initial.clone();
+        let mut activities = Vec::new();
+
+        for _ in 0..self.max_length {
+            if self.final_marking.as_ref() == Some(&marking) {
+                break;
+            }
+
+            let enabled: Vec<usize> = (0..self.net.transitions.len())
+                .filter(|t| self.net.is_enabled(&marking, *t))
+                .collect();
+            if enabled.is_empty() {
+                break;
+            }
+
+            let transition = enabled[self.rng.gen_range(0..enabled.len())];
+            marking = self.net.fire(&marking, transition).unwrap();
+            if let Some(label) = &self.net.transitions[transition].label {
+                activities.push(label.clone());
+            }
+        }
+
+        activities
+    }
+
+    fn generate(&mut self, case: usize) -> Trace {
+        let mut time = self.clock;
+        let mut events = Vec::new();
+
+        for mut activity in self.run() {
+            if self.noise > 0.0 && self.rng.gen_bool(self.noise) {
+                activity = self.activities[self.rng.gen_range(0..self.activities.len())].clone();
+            }
+
+            let duration = self
+                .durations
+                .get(&activity)
+                .unwrap_or(&self.default_duration)
+                .sample(&mut self.rng);
+            time += Self::seconds(duration);
+
+            events.push(
+                Event::builder()
+                    .name(activity)
+                    .timestamp(time)
+                    .transition("complete"),
+            );
+        }
+
+        self.clock += Self::seconds(self.arrival.sample(&mut self.rng));
+
+        Trace::builder()
+            .case_id(format!("case_{}", case + 1))
+            .events(events)
+            .build()
+    }
+}
+
+impl Stream for PlayOut {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        None
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        None
+    }
+
+    fn next(&mut self) -> ResOpt {
+        match self.emitted {
+            None => {
+                self.emitted = Some(0);
+                Ok(Some(Component::Meta(Meta::default())))
+            }
+            Some(n) if n < self.traces => {
+                self.emitted = Some(n + 1);
+                Ok(Some(Component::Trace(self.generate(n))))
+            }
+            Some(_) => Ok(None),
+        }
+    }
+}
+
+impl PluginProvider for PlayOut {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "PlayOut",
+            "Generate a random log from a Petri net or process tree",
+            Factory::new(
+                Declaration::default()
+                    .artifact("model", "The Petri net (workflow net) or process tree")
+                    .default_attr("traces", "Number of traces", |k| (k, 100).into())
+                    .default_attr(
+                        "max_length",
+                        "Maximum number of transitions per trace",
+                        |k| (k, 100).into(),
+                    )
+                    .default_attr(
+                        "noise",
+                        "Probability of an event to carry a random activity",
+                        |k| (k, 0.0).into(),
+                    )
+                    .default_attr("arrival", "Mean seconds between the starts of cases", |k| {
+                        (k, 3600.0).into()
+                    })
+                    .default_attr("duration", "Mean seconds an activity takes", |k| {
+                        (k, 600.0).into()
+                    })
+                    .optional_attr("seed", "Seed of the random generator", AttributeType::Int),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let artifact = parameters.acquire_artifact("model")?;
+                    let play_out = if let Some(net) = artifact.downcast_ref::<PetriNet>() {
+                        PlayOut::from_net(net.clone())?
+                    } else if let Some(tree) = artifact.downcast_ref::<ProcessTree>() {
+                        PlayOut::from_tree(tree)?
+                    } else {
+                        return Err(Error::ArtifactError(format!(
+                            "expected Petri net or process tree artifact, got {:?}",
+                            artifact
+                        )));
+                    };
+
+                    let count = |parameters: &mut Parameters, key| {
+                        let value = *parameters.acquire_attribute(key)?.value.try_int()?;
+                        Ok::<_, Error>(value.max(0) as usize)
+                    };
+                    let traces = count(parameters, "traces")?;
+                    let max_length = count(parameters, "max_length")?;
+
+                    let noise = parameters.acquire_attribute("noise")?.value.try_number()?;
+                    if !(0.0..=1.0).contains(&noise) {
+                        return Err(Error::AttributeError(format!(
+                            "noise is expected to be within [0, 1], got {}",
+                            noise
+                        )));
+                    }
+                    let arrival = parameters
+                        .acquire_attribute("arrival")?
+                        .value
+                        .try_number()?;
+                    let duration = parameters
+                        .acquire_attribute("duration")?
+                        .value
+                        .try_number()?;
+
+                    let mut play_out = play_out
+                        .trace_count(traces)
+                        .max_length(max_length)
+                        .noise(noise)
+                        .arrival(Distribution::Exponential(arrival))
+                        .default_duration(Distribution::Exponential(duration));
+                    if let Ok(seed) = parameters.acquire_attribute("seed") {
+                        play_out = play_out.seed(*seed.value.try_int()? as u128);
+                    }
+
+                    Ok(play_out.into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::void::consume;
+    use crate::stream::Sink;
+
+    use super::*;
+
+    fn variants(play_out: PlayOut) -> Vec<Vec<String>> {
+        play_out
+            .traces()
+            .map(|t| {
+                Ok(t?
+                    .events
+                    .iter()
+                    .map(|e| e.name().unwrap().to_string())
+                    .collect())
+            })
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_play_out() {
+        let tree = ProcessTree::sequence(vec![
+            ProcessTree::activity("a"),
+            ProcessTree::xor(vec![ProcessTree::activity("b"), ProcessTree::activity("c")]),
+            ProcessTree::parallel(vec![ProcessTree::activity("d"), ProcessTree::activity("e")]),
+        ]);
+
+        let play_out = || PlayOut::from_tree(&tree).unwrap().trace_count(50).seed(42);
+        let log = variants(play_out());
+        assert_eq!(log.len(), 50);
+        assert!(log.iter().all(|t| t.len() == 4 && t[0] == "a"));
+        assert!(log.iter().all(|t| ["b", "c"].contains(&t[1].as_str())));
+        assert!(log.iter().any(|t| t[2] == "d") && log.iter().any(|t| t[2] == "e"));
+
+        // seeded logs are reproducible
+        assert_eq!(log, variants(play_out()));
+
+        // fixed arrival and durations
+        let mut buffer = Buffer::default();
+        buffer
+            .consume(
+                &mut play_out()
+                    .trace_count(2)
+                    .arrival(Distribution::Fixed(60.0))
+                    .default_duration(Distribution::Fixed(10.0)),
+            )
+            .unwrap();
+        let traces: Vec<_> = buffer.traces().collect::<Result<_>>().unwrap();
+        assert_eq!(traces[1].case_id(), Some("case_2"));
+        let (first, last) = traces[1].interval().unwrap();
+        assert_eq!(first.to_rfc3339(), "2020-01-01T00:01:10+00:00");
+        assert_eq!(*last - *first, chrono::Duration::seconds(30));
+
+        // noise only introduces activities of the model
+        let noisy = variants(play_out().noise(1.0));
+        assert!(noisy
+            .iter()
+            .flatten()
+            .all(|a| tree.activities().contains(&a.as_str())));
+        assert_ne!(noisy, log);
+        assert!(
+            std::panic::catch_unwind(|| PlayOut::from_tree(&tree).unwrap().noise(1.5)).is_err()
+        );
+
+        // loops are cut off at the maximum length
+        let looped = ProcessTree::looped(vec![ProcessTree::activity("a"), ProcessTree::Silent]);
+        let log = variants(PlayOut::from_tree(&looped).unwrap().max_length(10).seed(1));
+        assert!(log.iter().all(|t| !t.is_empty() && t.len() <= 5));
+
+        assert!(PlayOut::from_net(PetriNet::default()).is_err());
+        assert!(consume(&mut PlayOut::from_tree(&tree).unwrap().trace_count(0)).is_ok());
+    }
+}
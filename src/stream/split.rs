@@ -6,7 +6,7 @@ use rand_pcg::Pcg64;
 
 use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
 use crate::stream::void::Void;
-use crate::stream::{AnyArtifact, Component, ResOpt, Sink, Stream};
+use crate::stream::{AnyArtifact, AttributeType, Component, ResOpt, Sink, Stream};
 use crate::Result;
 
 /// Train-Test split
@@ -100,7 +100,11 @@ impl PluginProvider for Split<Box<dyn Stream>, Box<dyn Sink>> {
                     Declaration::default()
                         .stream("inner", "The stream to be split")
                         .sink("sink", "The sink that consumes one part of the stream")
-                        .attribute("ratio", "Share of events/traces that are kept")
+                        .typed_attr(
+                            "ratio",
+                            "Share of events/traces that are kept",
+                            AttributeType::Float,
+                        )
                         .default_attr("seed", "Optional seed", |k| {
                             (k, Utc::now().timestamp_nanos()).into()
                         }),
@@ -124,7 +128,11 @@ impl PluginProvider for Split<Box<dyn Stream>, Box<dyn Sink>> {
                 Factory::new(
                     Declaration::default()
                         .stream("inner", "The stream to be sampled from")
-                        .attribute("ratio", "Share of events/traces that are sampled")
+                        .typed_attr(
+                            "ratio",
+                            "Share of events/traces that are sampled",
+                            AttributeType::Float,
+                        )
                         .default_attr("seed", "Optional seed", |k| {
                             (k, Utc::now().timestamp_nanos()).into()
                         }),
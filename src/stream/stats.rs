@@ -46,24 +46,39 @@
 //! println!("{}", statistics);
 //! ```
 //!
+//! # Incremental statistics
+//! Long-running streams only release their artifacts at the very end. In incremental mode, the
+//! `StatsCollector` additionally sends snapshots of its statistics through a data channel every
+//! N components (i.e. traces and standalone events) and/or every T seconds, such that live counts
+//! can be displayed while the stream is consumed. In a flow graph, the snapshots of the
+//! `IncrementalStatistics` plugin are received outside the graph via `Graph::subscribe`.
+//!
 
 use std::any::Any;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::mem;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
+use crate::stream::channel::TypedSender;
 use crate::stream::observer::Observer;
 use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
-use crate::stream::{observer::Handler, AnyArtifact, Artifact, Event, Stream, Trace};
+use crate::stream::{
+    observer::Handler, AnyArtifact, Artifact, AttributeType, Classifier, Event, Meta, Stream, Trace,
+};
 
 /// Container for statistical data of an event stream
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Statistics {
     ct_trace: Vec<usize>,
     ct_event: usize,
+    /// Number of events per class, if a classifier was given
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    ct_class: BTreeMap<String, usize>,
 }
 
 impl Statistics {
@@ -77,6 +92,11 @@ impl Statistics {
             self.ct_event,
         ]
     }
+
+    /// Number of events per class
+    pub fn classes(&self) -> &BTreeMap<String, usize> {
+        &self.ct_class
+    }
 }
 
 impl Default for Statistics {
@@ -84,6 +104,7 @@ impl Default for Statistics {
         Statistics {
             ct_trace: Vec::new(),
             ct_event: 0,
+            ct_class: BTreeMap::new(),
         }
     }
 }
@@ -106,35 +127,149 @@ impl fmt::Display for Statistics {
         writeln!(f, "   traces:              {:?}", self.ct_trace.len())?;
         writeln!(f, "   events:              {:?}", self.ct_event)?;
         writeln!(f, "   events (standalone): {:?}", sa_events)?;
+        if !self.ct_class.is_empty() {
+            writeln!(f, "   classes:             {:?}", self.ct_class.len())?;
+        }
         Ok(())
     }
 }
 
+// sends interim statistics through a data channel
+struct Snapshots {
+    sender: Option<TypedSender<Statistics>>,
+    every: Option<usize>,
+    interval: Option<Duration>,
+    // since the last snapshot
+    components: usize,
+    last: Instant,
+}
+
+impl Snapshots {
+    fn due(&mut self) -> bool {
+        self.components += 1;
+        let by_count = self.every.is_some_and(|n| n > 0 && self.components >= n);
+        let by_time = self.interval.is_some_and(|t| self.last.elapsed() >= t);
+        by_count || by_time
+    }
+
+    fn send(&mut self, statistics: &Statistics) {
+        self.components = 0;
+        self.last = Instant::now();
+        if let Some(sender) = &self.sender {
+            // a receiver that is gone must not interrupt the stream
+            if sender.send(statistics.clone()).is_err() {
+                warn!("statistics snapshot receiver is gone, stopped sending snapshots");
+                self.sender = None;
+            }
+        }
+    }
+}
+
+impl Debug for Snapshots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshots")
+            .field("every", &self.every)
+            .field("interval", &self.interval)
+            .field("components", &self.components)
+            .finish()
+    }
+}
+
 /// Generate statistics from event stream
 #[derive(Debug)]
 pub struct StatsCollector {
     pub statistics: Statistics,
+    classifier_name: Option<String>,
+    classifier: Option<Classifier>,
+    snapshots: Option<Snapshots>,
+    pending: usize,
 }
 
 impl Default for StatsCollector {
     fn default() -> Self {
         Self {
             statistics: Statistics::default(),
+            classifier_name: None,
+            classifier: None,
+            snapshots: None,
+            pending: 0,
+        }
+    }
+}
+
+impl StatsCollector {
+    /// Count events per class of the classifier declared under the given name
+    pub fn classifier<N: Into<String>>(mut self, name: N) -> Self {
+        self.classifier_name = Some(name.into());
+        self
+    }
+
+    /// Send snapshots every `every` components and/or every `interval`, whatever comes first
+    pub fn incremental(
+        mut self,
+        sender: TypedSender<Statistics>,
+        every: Option<usize>,
+        interval: Option<Duration>,
+    ) -> Self {
+        self.snapshots = Some(Snapshots {
+            sender: Some(sender),
+            every,
+            interval,
+            components: 0,
+            last: Instant::now(),
+        });
+        self
+    }
+
+    fn on_component(&mut self) {
+        if let Some(snapshots) = self.snapshots.as_mut() {
+            if snapshots.due() {
+                snapshots.send(&self.statistics);
+            }
         }
     }
 }
 
 impl Handler for StatsCollector {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        if let Some(name) = &self.classifier_name {
+            self.classifier = Some(meta.classifier(name)?);
+        }
+        Ok(meta)
+    }
+
     fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
         self.statistics.ct_trace.push(trace.events.len());
+        // the events of a trace are handled afterwards, snapshots are due after the last one
+        self.pending = trace.events.len();
+        if self.pending == 0 {
+            self.on_component();
+        }
         Ok(Some(trace))
     }
 
-    fn on_event(&mut self, event: Event, _in_trace: bool) -> Result<Option<Event>> {
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
         self.statistics.ct_event += 1;
+        if let Some(class) = self.classifier.as_ref().and_then(|c| c.class(&event)) {
+            *self.statistics.ct_class.entry(class).or_default() += 1;
+        }
+
+        if in_trace {
+            self.pending = self.pending.saturating_sub(1);
+        }
+        if !in_trace || self.pending == 0 {
+            self.on_component();
+        }
         Ok(Some(event))
     }
 
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        if let Some(ct_event) = self.statistics.ct_trace.last_mut() {
+            *ct_event += 1;
+        }
+        self.on_event(event, false)
+    }
+
     fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
         Ok(vec![mem::take(&mut self.statistics).into()])
     }
@@ -145,17 +280,72 @@ impl PluginProvider for StatsCollector {
     where
         Self: Sized,
     {
-        vec![Entry::new(
+        vec![
+            Entry::new(
             "Statistics",
-            "Compute basic statistics of an event stream",
+            "Compute basic statistics of an event stream, optionally counting events per class of a classifier",
             Factory::new(
-                Declaration::default().stream("inner", "The stream to be analyzed"),
+                Declaration::default()
+                    .stream("inner", "The stream to be analyzed")
+                    .optional_attr(
+                        "classifier",
+                        "Classifier to count events per class by",
+                        AttributeType::String,
+                    ),
                 FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
-                    Ok(Observer::from((
-                        parameters.acquire_stream("inner")?,
-                        StatsCollector::default(),
-                    ))
-                    .into_boxed())
+                    let mut collector = StatsCollector::default();
+                    if let Ok(classifier) = parameters.acquire_attribute("classifier") {
+                        collector = collector.classifier(classifier.value.try_string()?);
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, collector))
+                        .into_boxed())
+                })),
+            ),
+        ),
+        Entry::new(
+            "IncrementalStatistics",
+            "Compute basic statistics of an event stream and send snapshots while it is consumed",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be analyzed")
+                    .data_sender::<Statistics, _, _>("snapshots", "Channel that receives interim statistics")
+                    .default_attr(
+                        "every",
+                        "Send a snapshot every n traces and standalone events, 0 to disable",
+                        |k| (k, 1000).into(),
+                    )
+                    .optional_attr(
+                        "interval",
+                        "Seconds between snapshots",
+                        AttributeType::Float,
+                    )
+                    .optional_attr(
+                        "classifier",
+                        "Classifier to count events per class by",
+                        AttributeType::String,
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let every = *parameters.acquire_attribute("every")?.value.try_int()?;
+                    // optional interval in seconds
+                    let interval = match parameters.acquire_attribute("interval") {
+                        Ok(interval) => Some(Duration::from_secs_f64(
+                            interval.value.try_float()?.max(0.0),
+                        )),
+                        Err(_) => None,
+                    };
+
+                    let mut collector = StatsCollector::default().incremental(
+                        parameters.acquire_data_sender("snapshots")?,
+                        Some(every.max(0) as usize),
+                        interval,
+                    );
+                    if let Ok(classifier) = parameters.acquire_attribute("classifier") {
+                        collector = collector.classifier(classifier.value.try_string()?);
+                    }
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, collector))
+                        .into_boxed())
                 })),
             ),
         )]
@@ -169,6 +359,7 @@ mod tests {
     use serde::Serialize;
 
     use crate::dev_util::load_example;
+    use crate::stream::channel::{channel, Payload, TypedReceiver};
     use crate::stream::{observer::Observer, void::consume};
 
     use super::*;
@@ -199,6 +390,17 @@ mod tests {
             );
         }
 
+        let mut observer = StatsCollector::default()
+            .classifier("MXMLLegacyClassifier")
+            .into_observer(load_example(&["book", "L1.xes"]));
+        let artifacts = consume(&mut observer).unwrap();
+        let classes = AnyArtifact::find::<Statistics>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .classes();
+        assert_eq!(classes.len(), 5);
+        assert_eq!(classes["a+complete"], 6);
+        assert_eq!(classes["e+complete"], 1);
+
         let buffer = load_example(&["book", "L1.xes"]);
         let mut observer = Observer::new(buffer);
         observer.register(StatsCollector::default());
@@ -220,4 +422,39 @@ mod tests {
             String::from_utf8(buffer.into_inner().unwrap()).unwrap()
         );
     }
+
+    #[test]
+    fn test_incremental() {
+        let snapshots = |every, interval| {
+            let (sender, receiver) = channel::<Payload>(None);
+            let receiver = TypedReceiver::<Statistics>::from(receiver);
+            let mut observer = StatsCollector::default()
+                .incremental(sender.into(), every, interval)
+                .into_observer(load_example(&["book", "L1.xes"]));
+            consume(&mut observer).unwrap();
+            drop(observer);
+
+            let mut counts = Vec::new();
+            while let Some(statistics) = receiver.recv().unwrap() {
+                counts.push(statistics.counts());
+            }
+            counts
+        };
+
+        // snapshots are taken once all events of a trace are counted
+        assert_eq!(
+            snapshots(Some(2), None),
+            vec![[2, 7, 7], [4, 15, 15], [6, 23, 23]]
+        );
+        assert_eq!(snapshots(None, Some(Duration::ZERO)).len(), 6);
+        assert!(snapshots(Some(0), Some(Duration::from_secs(3600))).is_empty());
+
+        // a receiver that is gone doesn't interrupt the stream
+        let (sender, receiver) = channel::<Payload>(None);
+        drop(receiver);
+        let mut observer = StatsCollector::default()
+            .incremental(sender.into(), Some(1), None)
+            .into_observer(load_example(&["book", "L1.xes"]));
+        assert!(consume(&mut observer).is_ok());
+    }
 }
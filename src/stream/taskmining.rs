@@ -0,0 +1,570 @@
+//! Preprocessing of user interaction logs for task mining
+//!
+//! Recorders of desktop activity produce streams of low-level UI events: each key press, click and
+//! focus change is a standalone event that carries the user, the title of the active window and
+//! the type of interaction. Before such a click stream can be mined, it needs to be lifted to the
+//! level of tasks:
+//! * `MergeMicroEvents` merges runs of micro-events, e.g. key presses, into single actions,
+//! * `WindowActivities` names activities after window titles by means of regex rules and
+//! * `UserSessions` aggregates standalone events into one trace per user session.
+//!
+//! By default, the interaction type is read from `ui:type`, the window title from `ui:window` and
+//! the user from `org:resource`.
+//!
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::Duration;
+use regex::Regex;
+
+use crate::stream::merge::join_value;
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AttributeContainer, AttributeType, AttributeValue, Component, Event, Flavor, ResOpt, Stream,
+    Trace,
+};
+use crate::{DateTime, Error, Result};
+
+fn timestamp(event: &Event) -> Result<Option<DateTime>> {
+    match event.get_value("time:timestamp") {
+        Some(value) => Ok(Some(*value.try_date()?)),
+        None => Ok(None),
+    }
+}
+
+/// Merge runs of micro-events into actions
+///
+/// A run consists of micro-events of the same user in the same window that follow each other
+/// immediately in the stream, i.e. any other event ends the run. It is replaced by its first
+/// event, renamed to the action and extended by the number of merged events in `ui:count`. Runs
+/// are merged within traces as well as among standalone events.
+///
+pub struct MergeMicroEvents<T: Stream> {
+    stream: T,
+    type_key: String,
+    user_key: String,
+    window_key: String,
+    micro: Vec<String>,
+    action: String,
+    run: Option<(Event, i64)>,
+    queue: VecDeque<Component>,
+}
+
+impl<T: Stream> MergeMicroEvents<T> {
+    /// Merge key presses into `type` actions
+    pub fn new(stream: T) -> Self {
+        MergeMicroEvents {
+            stream,
+            type_key: "ui:type".to_string(),
+            user_key: "org:resource".to_string(),
+            window_key: "ui:window".to_string(),
+            micro: vec!["keypress".to_string()],
+            action: "type".to_string(),
+            run: None,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Interaction types that are considered micro-events
+    pub fn micro<S: Into<String>>(mut self, types: Vec<S>) -> Self {
+        self.micro = types.into_iter().map(|t| t.into()).collect();
+        self
+    }
+
+    /// Activity name of merged actions
+    pub fn action<S: Into<String>>(mut self, action: S) -> Self {
+        self.action = action.into();
+        self
+    }
+
+    /// Take interaction types from the given event attribute
+    pub fn type_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.type_key = key.into();
+        self
+    }
+
+    fn is_micro(&self, event: &Event) -> bool {
+        event
+            .get_value(&self.type_key)
+            .and_then(|v| v.try_string().ok())
+            .is_some_and(|t| self.micro.iter().any(|m| m == t))
+    }
+
+    fn continues(user_key: &str, window_key: &str, run: &Event, event: &Event) -> bool {
+        run.get_value(user_key) == event.get_value(user_key)
+            && run.get_value(window_key) == event.get_value(window_key)
+    }
+
+    /// Add an event to the current run, returns what is complete
+    fn push(&mut self, event: Event) -> Vec<Event> {
+        let micro = self.is_micro(&event);
+        if micro {
+            if let Some((run, count)) = &mut self.run {
+                if Self::continues(&self.user_key, &self.window_key, run, &event) {
+                    *count += 1;
+                    return vec![];
+                }
+            }
+        }
+
+        let mut complete: Vec<Event> = self.finish().into_iter().collect();
+        if micro {
+            self.run = Some((event, 1));
+        } else {
+            complete.push(event);
+        }
+        complete
+    }
+
+    /// Close the current run
+    fn finish(&mut self) -> Option<Event> {
+        self.run.take().map(|(mut event, count)| {
+            event
+                .attributes
+                .insert(("concept:name", self.action.as_str()));
+            event.attributes.insert(("ui:count", count));
+            event
+        })
+    }
+
+    fn merge(&mut self, mut trace: Trace) -> Trace {
+        let mut events = Vec::with_capacity(trace.events.len());
+        for event in std::mem::take(&mut trace.events) {
+            events.extend(self.push(event));
+        }
+        events.extend(self.finish());
+        trace.events = events;
+        trace
+    }
+}
+
+impl<T: Stream> Stream for MergeMicroEvents<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        loop {
+            if let Some(component) = self.queue.pop_front() {
+                return Ok(Some(component));
+            }
+
+            match self.stream.next()? {
+                Some(Component::Event(event)) => {
+                    let complete = self.push(event);
+                    self.queue
+                        .extend(complete.into_iter().map(Component::Event));
+                }
+                Some(Component::Trace(trace)) => {
+                    let trace = self.merge(trace);
+                    return Ok(Some(Component::Trace(trace)));
+                }
+                Some(Component::Meta(meta)) => return Ok(Some(Component::Meta(meta))),
+                None => return Ok(self.finish().map(Component::Event)),
+            }
+        }
+    }
+}
+
+/// Name activities after the titles of the windows they occurred in
+///
+/// Rules are tried in order of registration, the first one whose pattern matches the window title
+/// determines the activity. Templates may refer to capture groups, e.g. `Edit $1` or
+/// `Edit ${file}`. Events whose window title matches no rule keep their name.
+///
+#[derive(Debug, Clone)]
+pub struct WindowActivities {
+    window_key: String,
+    rules: Vec<(Regex, String)>,
+}
+
+impl Default for WindowActivities {
+    fn default() -> Self {
+        WindowActivities {
+            window_key: "ui:window".to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl WindowActivities {
+    /// Add a rule that names the activity by expanding `template` if `pattern` matches
+    pub fn rule<T: Into<String>>(mut self, pattern: &str, template: T) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| Error::AttributeError(format!("invalid pattern {:?}: {}", pattern, e)))?;
+        self.rules.push((regex, template.into()));
+        Ok(self)
+    }
+
+    /// Take window titles from the given event attribute
+    pub fn window_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.window_key = key.into();
+        self
+    }
+
+    /// Activity of the given window title, if any rule matches
+    pub fn activity(&self, title: &str) -> Option<String> {
+        self.rules.iter().find_map(|(regex, template)| {
+            regex.captures(title).map(|captures| {
+                let mut activity = String::new();
+                captures.expand(template, &mut activity);
+                activity
+            })
+        })
+    }
+}
+
+impl Handler for WindowActivities {
+    fn on_event(&mut self, mut event: Event, _in_trace: bool) -> Result<Option<Event>> {
+        let activity = match event.get_value(&self.window_key) {
+            Some(title) => self.activity(title.try_string()?),
+            None => None,
+        };
+        if let Some(activity) = activity {
+            event.attributes.insert(("concept:name", activity));
+        }
+        Ok(Some(event))
+    }
+}
+
+/// Aggregate standalone events into traces per user session
+///
+/// A session of a user ends once no event of that user occurred for longer than the gap. Finished
+/// sessions are released immediately, all others once the inner stream is exhausted. Each session
+/// trace is named after the user and carries its session index, starting at zero, in the
+/// attribute `session`. Events without timestamp stay in the current session of their user.
+///
+/// Events without user are released after all traces. Traces of the inner stream are passed
+/// through and meta data is passed through as `Flavor::Log`, as the stream holds traces now.
+///
+pub struct UserSessions<T: Stream> {
+    stream: T,
+    user_key: String,
+    gap: Duration,
+    order: Vec<String>,
+    open: HashMap<String, (Trace, Option<DateTime>)>,
+    sessions: HashMap<String, i64>,
+    ready: VecDeque<Trace>,
+    orphans: VecDeque<Event>,
+    exhausted: bool,
+}
+
+impl<T: Stream> UserSessions<T> {
+    /// Start a new session once a user was inactive for longer than `gap`
+    pub fn new(stream: T, gap: Duration) -> Self {
+        UserSessions {
+            stream,
+            user_key: "org:resource".to_string(),
+            gap,
+            order: Vec::new(),
+            open: HashMap::new(),
+            sessions: HashMap::new(),
+            ready: VecDeque::new(),
+            orphans: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    /// Take users from the given event attribute
+    pub fn user_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.user_key = key.into();
+        self
+    }
+
+    fn open(&mut self, user: &str) -> Trace {
+        let index = self.sessions.entry(user.to_string()).or_insert(0);
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", user));
+        trace
+            .attributes
+            .insert((self.user_key.as_str(), AttributeValue::String(user.into())));
+        trace.attributes.insert(("session", *index));
+        *index += 1;
+        trace
+    }
+
+    fn add(&mut self, user: String, event: Event) -> Result<()> {
+        let timestamp = timestamp(&event)?;
+
+        let expired = match (self.open.get(&user), timestamp) {
+            (Some((_, Some(last))), Some(timestamp)) => {
+                timestamp.signed_duration_since(*last) > self.gap
+            }
+            _ => false,
+        };
+        if expired {
+            if let Some((trace, _)) = self.open.remove(&user) {
+                self.ready.push_back(trace);
+            }
+        }
+
+        if !self.open.contains_key(&user) {
+            if !self.order.contains(&user) {
+                self.order.push(user.clone());
+            }
+            let trace = self.open(&user);
+            self.open.insert(user.clone(), (trace, None));
+        }
+
+        let (trace, last) = self.open.get_mut(&user).unwrap();
+        *last = timestamp.or(*last);
+        trace.events.push(event);
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        for user in std::mem::take(&mut self.order) {
+            if let Some((trace, _)) = self.open.remove(&user) {
+                self.ready.push_back(trace);
+            }
+        }
+    }
+}
+
+impl<T: Stream> Stream for UserSessions<T> {
+    fn inner_ref(&self) -> Option<&dyn Stream> {
+        Some(&self.stream)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Stream> {
+        Some(&mut self.stream)
+    }
+
+    fn next(&mut self) -> ResOpt {
+        loop {
+            if let Some(trace) = self.ready.pop_front() {
+                return Ok(Some(Component::Trace(trace)));
+            }
+            if self.exhausted {
+                return Ok(self.orphans.pop_front().map(Component::Event));
+            }
+
+            match self.stream.next()? {
+                Some(Component::Event(event)) => {
+                    match event.get_value(&self.user_key).and_then(join_value) {
+                        Some(user) => self.add(user, event)?,
+                        None => self.orphans.push_back(event),
+                    }
+                }
+                Some(Component::Meta(mut meta)) => {
+                    meta.flavor = Flavor::Log;
+                    return Ok(Some(Component::Meta(meta)));
+                }
+                Some(component) => return Ok(Some(component)),
+                None => {
+                    self.exhausted = true;
+                    self.release();
+                }
+            }
+        }
+    }
+}
+
+/// Dummy struct for task mining plugins
+pub struct TaskMiningPluginProvider;
+
+impl PluginProvider for TaskMiningPluginProvider {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "MergeMicroEvents",
+                "Merge runs of micro-events, e.g. key presses, of the same user and window into single actions",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The click stream")
+                        .default_attr("action", "Activity name of merged actions", |k| {
+                            (k, "type").into()
+                        })
+                        .default_attr("type_key", "Event attribute that holds the interaction type", |k| {
+                            (k, "ui:type").into()
+                        })
+                        .optional_attr(
+                            "micro",
+                            "Interaction types that count as micro-events",
+                            AttributeType::List,
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let mut merge = MergeMicroEvents::new(parameters.acquire_stream("inner")?)
+                            .action(parameters.acquire_attribute("action")?.value.try_string()?)
+                            .type_key(parameters.acquire_attribute("type_key")?.value.try_string()?);
+
+                        if let Ok(micro) = parameters.acquire_attribute("micro") {
+                            merge = merge.micro(
+                                micro
+                                    .value
+                                    .try_list()?
+                                    .iter()
+                                    .map(|a| Ok(a.value.try_string()?.to_string()))
+                                    .collect::<Result<Vec<_>>>()?,
+                            );
+                        }
+
+                        Ok(merge.into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "WindowActivities",
+                "Name activities after window titles, each rule is a regex keyed template",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The click stream")
+                        .typed_attr("rules", "List of activity templates, each keyed by a regex on the window title", AttributeType::List)
+                        .default_attr("window_key", "Event attribute that holds the window title", |k| {
+                            (k, "ui:window").into()
+                        }),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let mut activities = WindowActivities::default().window_key(
+                            parameters.acquire_attribute("window_key")?.value.try_string()?,
+                        );
+                        for rule in parameters.acquire_attribute("rules")?.value.try_list()? {
+                            activities = activities.rule(&rule.key, rule.value.try_string()?)?;
+                        }
+
+                        Ok(Observer::from((parameters.acquire_stream("inner")?, activities))
+                            .into_boxed())
+                    })),
+                ),
+            ),
+            Entry::new(
+                "UserSessions",
+                "Aggregate standalone events into traces per user, starting a new session after gap seconds of inactivity",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The click stream")
+                        .default_attr("gap", "Maximal inactivity within a session in seconds", |k| {
+                            (k, 1800).into()
+                        })
+                        .default_attr("user_key", "Event attribute that identifies the user", |k| {
+                            (k, "org:resource").into()
+                        }),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let gap = *parameters.acquire_attribute("gap")?.value.try_int()?;
+                        if gap < 0 {
+                            return Err(Error::AttributeError(format!(
+                                "gap is expected to be non-negative, got {}",
+                                gap
+                            )));
+                        }
+
+                        Ok(UserSessions::new(
+                            parameters.acquire_stream("inner")?,
+                            Duration::seconds(gap),
+                        )
+                        .user_key(parameters.acquire_attribute("user_key")?.value.try_string()?)
+                        .into_boxed())
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::order::OrderGuard;
+    use crate::stream::{Meta, Sink};
+
+    use super::*;
+
+    fn event(user: &str, kind: &str, window: &str, minute: u32) -> Event {
+        let mut event = Event::default();
+        event.attributes.insert(("concept:name", kind));
+        event.attributes.insert(("org:resource", user));
+        event.attributes.insert(("ui:type", kind));
+        event.attributes.insert(("ui:window", window));
+        let timestamp =
+            DateTime::parse_from_rfc3339(&format!("2020-01-01T00:{:02}:00+00:00", minute)).unwrap();
+        event.attributes.insert(("time:timestamp", timestamp));
+        event
+    }
+
+    #[test]
+    fn test_task_mining() {
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta {
+            flavor: Flavor::Stream,
+            ..Meta::default()
+        }))));
+        for event in [
+            event("ann", "click", "Inbox - Outlook", 0),
+            event("ann", "keypress", "report.docx - Word", 1),
+            event("ann", "keypress", "report.docx - Word", 1),
+            event("ann", "keypress", "notes.txt - Editor", 2),
+            event("bob", "click", "Inbox - Outlook", 2),
+            event("ann", "keypress", "notes.txt - Editor", 3),
+            event("ann", "click", "Inbox - Outlook", 50),
+        ]
+        .iter()
+        .cloned()
+        {
+            buffer.push(Ok(Some(Component::Event(event))));
+        }
+        let mut orphan = Event::default();
+        orphan.attributes.insert(("concept:name", "boot"));
+        buffer.push(Ok(Some(Component::Event(orphan))));
+
+        let activities = WindowActivities::default()
+            .rule(r"^Inbox - Outlook$", "Read mail")
+            .unwrap()
+            .rule(r"^(?P<file>.+)\.docx - Word$", "Edit ${file}")
+            .unwrap();
+        assert!(WindowActivities::default().rule("(", "x").is_err());
+
+        // micro-events are merged before the windows determine the activity
+        let stream = UserSessions::new(
+            activities.into_observer(MergeMicroEvents::new(buffer)),
+            Duration::minutes(30),
+        );
+        let mut log = Log::default();
+        log.consume(&mut OrderGuard::new(stream)).unwrap();
+
+        assert_eq!(log.meta.flavor, Flavor::Log);
+        let sessions: Vec<_> = log
+            .traces
+            .iter()
+            .map(|t| {
+                (
+                    t.get_value("concept:name").unwrap().try_string().unwrap(),
+                    *t.get_value("session").unwrap().try_int().unwrap(),
+                    t.events
+                        .iter()
+                        .map(|e| {
+                            let name = e.get_value("concept:name").unwrap().try_string().unwrap();
+                            let count = e.get_value("ui:count").map(|c| *c.try_int().unwrap());
+                            (name, count)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![
+                (
+                    "ann",
+                    0,
+                    vec![
+                        ("Read mail", None),
+                        ("Edit report", Some(2)),
+                        ("type", Some(1)),
+                        ("type", Some(1))
+                    ]
+                ),
+                ("ann", 1, vec![("Read mail", None)]),
+                ("bob", 0, vec![("Read mail", None)]),
+            ]
+        );
+        assert_eq!(log.events.len(), 1);
+    }
+}
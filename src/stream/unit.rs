@@ -0,0 +1,477 @@
+//! Units of measure for numeric attributes
+//!
+//! A cost of `12` means little without knowing whether it's EUR or USD, and summing up durations
+//! in seconds and minutes yields garbage without any warning. Numeric attributes may carry their
+//! unit as child attribute with key `unit`, e.g. `Attribute("cost:total", 12.0, [("unit", "EUR")])`.
+//! Alternatively, a `UnitMap` declares units per attribute key as sidecar. Units of children take
+//! precedence over the sidecar.
+//!
+//! Known units are grouped by dimension and convert into each other: time (`ms`, `s`, `min`, `h`,
+//! `d`), mass (`g`, `kg`, `t`) and length (`mm`, `cm`, `m`, `km`). Any three letter upper case
+//! code is considered a currency. Currencies don't convert into each other without a rate table.
+//!
+//! `Aggregate` sums up quantities and refuses to mix incompatible units. The `UnitChecker` handler
+//! reports the units seen per attribute, optionally converts values to target units and fails on
+//! attributes that mix incompatible units.
+//!
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, Attribute, AttributeMap, AttributeValue, Event, Stream, Trace,
+};
+use crate::{Error, Result};
+
+/// Key of the child attribute that holds the unit
+pub const UNIT_KEY: &str = "unit";
+
+const KNOWN_UNITS: [(&str, Dimension, f64); 12] = [
+    ("ms", Dimension::Time, 0.001),
+    ("s", Dimension::Time, 1.0),
+    ("min", Dimension::Time, 60.0),
+    ("h", Dimension::Time, 3600.0),
+    ("d", Dimension::Time, 86400.0),
+    ("g", Dimension::Mass, 0.001),
+    ("kg", Dimension::Mass, 1.0),
+    ("t", Dimension::Mass, 1000.0),
+    ("mm", Dimension::Length, 0.001),
+    ("cm", Dimension::Length, 0.01),
+    ("m", Dimension::Length, 1.0),
+    ("km", Dimension::Length, 1000.0),
+];
+
+/// Physical dimension of a unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dimension {
+    Time,
+    Mass,
+    Length,
+    Currency,
+}
+
+/// A unit of measure, i.e. its symbol and its factor with respect to the base unit of its dimension
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unit {
+    pub symbol: String,
+    pub dimension: Dimension,
+    pub factor: f64,
+}
+
+impl Unit {
+    /// Look up a unit by its symbol
+    pub fn parse(symbol: &str) -> Result<Self> {
+        let symbol = symbol.trim();
+
+        if let Some((_, dimension, factor)) = KNOWN_UNITS.iter().find(|(s, _, _)| *s == symbol) {
+            return Ok(Unit {
+                symbol: symbol.to_string(),
+                dimension: *dimension,
+                factor: *factor,
+            });
+        }
+
+        if symbol.len() == 3 && symbol.chars().all(|c| c.is_ascii_uppercase()) {
+            return Ok(Unit {
+                symbol: symbol.to_string(),
+                dimension: Dimension::Currency,
+                factor: 1.0,
+            });
+        }
+
+        Err(Error::UnitError(format!("unknown unit {:?}", symbol)))
+    }
+
+    /// Whether values of this unit can be converted into the other
+    pub fn is_compatible(&self, other: &Unit) -> bool {
+        self.dimension == other.dimension
+            && (self.dimension != Dimension::Currency || self.symbol == other.symbol)
+    }
+
+    /// Convert a value of this unit into the other
+    pub fn convert(&self, value: f64, to: &Unit) -> Result<f64> {
+        if self.is_compatible(to) {
+            Ok(value * self.factor / to.factor)
+        } else {
+            Err(Error::UnitError(format!(
+                "cannot convert {} to {}",
+                self, to
+            )))
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol)
+    }
+}
+
+/// Numeric value of an attribute
+fn numeric(value: &AttributeValue) -> Option<f64> {
+    match value {
+        AttributeValue::Int(x) => Some(*x as f64),
+        AttributeValue::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Unit declared by the children of an attribute
+pub fn unit_of(children: &[Attribute]) -> Result<Option<Unit>> {
+    children
+        .iter()
+        .find(|c| c.key == UNIT_KEY)
+        .map(|c| Unit::parse(c.value.try_string()?))
+        .transpose()
+}
+
+/// Sidecar declaration of units per attribute key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnitMap {
+    units: BTreeMap<String, Unit>,
+}
+
+impl UnitMap {
+    pub fn declare<K: Into<String>>(mut self, key: K, unit: Unit) -> Self {
+        self.units.insert(key.into(), unit);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Unit> {
+        self.units.get(key)
+    }
+
+    /// Unit of an attribute, given by its children or the sidecar declaration
+    pub fn resolve(&self, key: &str, children: &[Attribute]) -> Result<Option<Unit>> {
+        Ok(unit_of(children)?.or_else(|| self.get(key).cloned()))
+    }
+}
+
+impl TryFrom<&[Attribute]> for UnitMap {
+    type Error = Error;
+
+    /// Read `(key, symbol)` pairs
+    fn try_from(attributes: &[Attribute]) -> Result<Self> {
+        attributes.iter().try_fold(UnitMap::default(), |map, a| {
+            Ok(map.declare(a.key.as_str(), Unit::parse(a.value.try_string()?)?))
+        })
+    }
+}
+
+/// Sum of quantities, expressed in the unit of the first one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Aggregate {
+    pub sum: f64,
+    pub count: usize,
+    pub unit: Option<Unit>,
+}
+
+impl Aggregate {
+    /// Add a quantity, failing if its unit is incompatible with the ones seen so far
+    pub fn add(&mut self, value: f64, unit: &Unit) -> Result<()> {
+        let value = match &self.unit {
+            Some(target) => unit.convert(value, target)?,
+            None => {
+                self.unit = Some(unit.clone());
+                value
+            }
+        };
+
+        self.sum += value;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Units observed per attribute key
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnitReport {
+    pub units: BTreeMap<String, BTreeSet<String>>,
+    /// Number of numeric values without unit per attribute key
+    pub missing: BTreeMap<String, usize>,
+}
+
+impl UnitReport {
+    /// Keys of attributes that occur with different units
+    pub fn mixed(&self) -> Vec<&str> {
+        self.units
+            .iter()
+            .filter(|(_, units)| units.len() > 1)
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+}
+
+#[typetag::serde]
+impl Artifact for UnitReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Check and normalize units of numeric attributes
+///
+/// Attributes listed as target are converted into the target unit. Afterwards, the values of an
+/// attribute key are expected to share a unit, or at least compatible ones, unless `strict` is
+/// disabled. Numeric attributes without any unit are only counted.
+///
+#[derive(Debug, Clone)]
+pub struct UnitChecker {
+    sidecar: UnitMap,
+    targets: UnitMap,
+    strict: bool,
+    first: BTreeMap<String, Unit>,
+    report: UnitReport,
+}
+
+impl Default for UnitChecker {
+    fn default() -> Self {
+        UnitChecker {
+            sidecar: UnitMap::default(),
+            targets: UnitMap::default(),
+            strict: true,
+            first: BTreeMap::new(),
+            report: UnitReport::default(),
+        }
+    }
+}
+
+impl UnitChecker {
+    /// Units of attributes without `unit` child
+    pub fn sidecar(mut self, sidecar: UnitMap) -> Self {
+        self.sidecar = sidecar;
+        self
+    }
+
+    /// Units to convert attributes into
+    pub fn targets(mut self, targets: UnitMap) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Whether incompatible units of the same attribute are an error
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    fn check(&mut self, attributes: &mut AttributeMap) -> Result<()> {
+        let mut converted = Vec::new();
+
+        for (key, value, children) in attributes.iter() {
+            let value = match numeric(value) {
+                Some(value) => value,
+                None => continue,
+            };
+            let mut unit = match self.sidecar.resolve(key, children)? {
+                Some(unit) => unit,
+                None => {
+                    *self.report.missing.entry(key.to_string()).or_default() += 1;
+                    continue;
+                }
+            };
+
+            if let Some(target) = self.targets.get(key) {
+                if unit != *target {
+                    let value = unit.convert(value, target)?;
+                    let children = children
+                        .iter()
+                        .filter(|c| c.key != UNIT_KEY)
+                        .cloned()
+                        .chain(vec![Attribute::new(UNIT_KEY, target.symbol.as_str())]);
+                    converted.push(Attribute::with_children(key, value, children));
+                    unit = target.clone();
+                }
+            }
+
+            let first = self
+                .first
+                .entry(key.to_string())
+                .or_insert_with(|| unit.clone());
+            if self.strict && !first.is_compatible(&unit) {
+                return Err(Error::UnitError(format!(
+                    "attribute {:?} mixes units {} and {}",
+                    key, first, unit
+                )));
+            }
+            self.report
+                .units
+                .entry(key.to_string())
+                .or_default()
+                .insert(unit.symbol);
+        }
+
+        for attribute in converted {
+            attributes.insert(attribute);
+        }
+        Ok(())
+    }
+}
+
+impl Handler for UnitChecker {
+    fn on_trace(&mut self, mut trace: Trace) -> Result<Option<Trace>> {
+        self.check(&mut trace.attributes)?;
+        for event in trace.events.iter_mut() {
+            self.check(&mut event.attributes)?;
+        }
+
+        Ok(Some(trace))
+    }
+
+    fn on_event(&mut self, mut event: Event, in_trace: bool) -> Result<Option<Event>> {
+        if !in_trace {
+            self.check(&mut event.attributes)?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.report.clone().into()])
+    }
+}
+
+impl PluginProvider for UnitChecker {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![Entry::new(
+            "UnitChecker",
+            "Report, convert and validate units of numeric attributes",
+            Factory::new(
+                Declaration::default()
+                    .stream("inner", "The stream to be checked")
+                    .default_attr(
+                        "units",
+                        "Units of attributes without unit child, keyed by attribute",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    )
+                    .default_attr(
+                        "targets",
+                        "Units to convert attributes into, keyed by attribute",
+                        |k| (k, Vec::<Attribute>::new()).into(),
+                    )
+                    .default_attr(
+                        "strict",
+                        "Fail on attributes that mix incompatible units",
+                        |k| (k, true).into(),
+                    ),
+                FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                    let checker = UnitChecker::default()
+                        .sidecar(UnitMap::try_from(
+                            parameters.acquire_attribute("units")?.value.try_list()?,
+                        )?)
+                        .targets(UnitMap::try_from(
+                            parameters.acquire_attribute("targets")?.value.try_list()?,
+                        )?)
+                        .strict(
+                            *parameters
+                                .acquire_attribute("strict")?
+                                .value
+                                .try_boolean()?,
+                        );
+
+                    Ok(Observer::from((parameters.acquire_stream("inner")?, checker)).into_boxed())
+                })),
+            ),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::buffer::Buffer;
+    use crate::stream::log::Log;
+    use crate::stream::{AttributeContainer, Component, Sink};
+
+    use super::*;
+
+    fn unit(symbol: &str) -> Unit {
+        Unit::parse(symbol).unwrap()
+    }
+
+    #[test]
+    fn test_units() {
+        assert!(is_close!(
+            unit("h").convert(1.5, &unit("min")).unwrap(),
+            90.0
+        ));
+        assert!(is_close!(
+            unit("g").convert(250.0, &unit("kg")).unwrap(),
+            0.25
+        ));
+        assert!(unit("EUR").convert(1.0, &unit("USD")).is_err());
+        assert!(unit("s").convert(1.0, &unit("kg")).is_err());
+        assert!(Unit::parse("parsec").is_err());
+
+        let mut aggregate = Aggregate::default();
+        aggregate.add(1.0, &unit("h")).unwrap();
+        aggregate.add(30.0, &unit("min")).unwrap();
+        assert!(is_close!(aggregate.sum, 1.5));
+        assert!(aggregate.add(1.0, &unit("EUR")).is_err());
+        assert_eq!(aggregate.count, 2);
+    }
+
+    fn buffer(units: &[&str]) -> Buffer {
+        let mut trace = Trace::default();
+        for u in units {
+            let mut event = Event::default();
+            event
+                .attributes
+                .insert(("duration", 30, vec![Attribute::new(UNIT_KEY, *u)]));
+            event.attributes.insert(("cost", 10.0));
+            trace.events.push(event);
+        }
+
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(trace))));
+        buffer
+    }
+
+    #[test]
+    fn test_unit_checker() {
+        let checker = UnitChecker::default()
+            .sidecar(UnitMap::default().declare("cost", unit("EUR")))
+            .targets(UnitMap::default().declare("duration", unit("h")));
+        let mut observer = checker.into_observer(buffer(&["min", "h"]));
+        let mut log = Log::default();
+        let artifacts = log.consume(&mut observer).unwrap();
+
+        let durations: Vec<_> = log.traces[0]
+            .events
+            .iter()
+            .map(|e| e.get_value("duration").cloned().unwrap())
+            .collect();
+        assert_eq!(durations, vec![0.5.into(), AttributeValue::Int(30)]);
+        assert_eq!(
+            unit_of(log.traces[0].events[0].get_children("duration").unwrap()).unwrap(),
+            Some(unit("h"))
+        );
+
+        let report = AnyArtifact::find::<UnitReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert!(report.mixed().is_empty());
+        assert_eq!(report.units["cost"].len(), 1);
+
+        let mut observer = UnitChecker::default().into_observer(buffer(&["min", "EUR"]));
+        assert!(Log::default().consume(&mut observer).is_err());
+
+        let mut observer = UnitChecker::default()
+            .strict(false)
+            .into_observer(buffer(&["min", "s"]));
+        let artifacts = Log::default().consume(&mut observer).unwrap();
+        let report = AnyArtifact::find::<UnitReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(report.mixed(), vec!["duration"]);
+        assert_eq!(report.missing["cost"], 2);
+    }
+}
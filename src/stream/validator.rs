@@ -4,22 +4,144 @@
 //! for doing the remaining checks. For now, that includes
 //! - checking for attributes enforced by globals
 //! - semantic validation via extensions
+//! - rejecting traces in streams of `Flavor::Stream`
 //!
+//! By default, validation aborts the stream on the first error. Auditing large third-party logs
+//! rather calls for a list of all flaws, so in `ValidationMode::Collect`, errors are recorded
+//! along with their position and the stream continues. Extensions that can't be validated are
+//! recorded as warnings. The `ValidationReport` is released as artifact at the end of the stream.
+//!
+
+use std::any::Any;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
 
 use crate::stream::extension::REGISTRY;
 use crate::stream::observer::{Handler, Observer};
 use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::quality::Severity;
 use crate::stream::xml_util::CRE_NCNAME;
-use crate::stream::{AttributeContainer, Event, Meta, Scope, Stream, Trace};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, ComponentType, Event, Flavor, Meta, Scope, Stream,
+    Trace,
+};
 use crate::{Error, Result};
 
 pub type ValidatorFn = Box<dyn Fn(Box<&dyn AttributeContainer>) -> Result<()> + Send>;
 
+/// What to do about validation errors
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ValidationMode {
+    /// Fail on the first error
+    #[default]
+    Abort,
+    /// Record all errors in a `ValidationReport` and continue
+    Collect,
+}
+
+impl FromStr for ValidationMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "abort" => Ok(ValidationMode::Abort),
+            "collect" => Ok(ValidationMode::Collect),
+            other => Err(Error::AttributeError(format!(
+                "invalid validation mode {:?}, expected \"abort\" or \"collect\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Position of a component in a stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    /// Index of the trace, if the component is or belongs to a trace
+    pub trace: Option<usize>,
+    /// Index of the event within its trace or among standalone events
+    pub event: Option<usize>,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.trace, self.event) {
+            (Some(trace), Some(event)) => write!(f, "trace {}, event {}", trace, event),
+            (Some(trace), None) => write!(f, "trace {}", trace),
+            (None, Some(event)) => write!(f, "event {}", event),
+            (None, None) => write!(f, "meta"),
+        }
+    }
+}
+
+/// Single validation error or warning
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationEntry {
+    pub severity: Severity,
+    pub component: ComponentType,
+    pub position: Position,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at {}: {}",
+            self.severity, self.position, self.message
+        )
+    }
+}
+
+/// All errors and warnings of a stream that was validated in `ValidationMode::Collect`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub entries: Vec<ValidationEntry>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.severity == Severity::Warning)
+    }
+
+    /// Whether the stream passed validation, i.e. there are no errors
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ValidationReport {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 /// Container for validator functions
 pub struct Validator {
     validators: Vec<ValidatorFn>,
     trace_only: Vec<ValidatorFn>,
     event_only: Vec<ValidatorFn>,
+    flavor: Flavor,
+    mode: ValidationMode,
+    report: ValidationReport,
+    traces: usize,
+    events: usize,
+    standalone: usize,
 }
 
 impl Default for Validator {
@@ -28,10 +150,78 @@ impl Default for Validator {
             validators: Vec::new(),
             trace_only: Vec::new(),
             event_only: Vec::new(),
+            flavor: Flavor::default(),
+            mode: ValidationMode::default(),
+            report: ValidationReport::default(),
+            traces: 0,
+            events: 0,
+            standalone: 0,
         }
     }
 }
 
+impl Validator {
+    pub fn mode(mut self, mode: ValidationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Record errors instead of failing, short for `mode(ValidationMode::Collect)`
+    pub fn collect(self) -> Self {
+        self.mode(ValidationMode::Collect)
+    }
+
+    /// Fail or record the outcome of a check, depending on the mode
+    fn check(
+        &mut self,
+        component: ComponentType,
+        position: Position,
+        result: Result<()>,
+    ) -> Result<()> {
+        match (result, self.mode) {
+            (Err(error), ValidationMode::Collect) => {
+                self.report.entries.push(ValidationEntry {
+                    severity: Severity::Error,
+                    component,
+                    position,
+                    message: match error {
+                        Error::ValidationError(message) => message,
+                        error => error.to_string(),
+                    },
+                });
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Run the validators of the given component type on the component
+    fn validate(
+        &mut self,
+        component_type: ComponentType,
+        position: Position,
+        component: &dyn AttributeContainer,
+    ) -> Result<()> {
+        let scoped = match component_type {
+            ComponentType::Trace => self.trace_only.len(),
+            ComponentType::Event => self.event_only.len(),
+            _ => 0,
+        };
+
+        for i in 0..scoped + self.validators.len() {
+            let validator = match (i < scoped, &component_type) {
+                (true, ComponentType::Trace) => &self.trace_only[i],
+                (true, _) => &self.event_only[i],
+                (false, _) => &self.validators[i - scoped],
+            };
+            let result = validator(Box::new(component));
+            self.check(component_type.clone(), position, result)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl PluginProvider for Validator {
     fn entries() -> Vec<Entry>
     where
@@ -41,12 +231,24 @@ impl PluginProvider for Validator {
             "Validator",
             "Validate stream semantics",
             Factory::new(
-                Declaration::default().stream("inner", "The stream to be validated"),
+                Declaration::default()
+                    .stream("inner", "The stream to be validated")
+                    .default_attr(
+                        "mode",
+                        "Either abort on the first error or collect all errors in a report",
+                        |k| (k, "abort").into(),
+                    ),
                 FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
-                    Ok(
-                        Observer::from((parameters.acquire_stream("inner")?, Validator::default()))
-                            .into_boxed(),
-                    )
+                    let mode = parameters
+                        .acquire_attribute("mode")?
+                        .value
+                        .try_string()?
+                        .parse()?;
+                    Ok(Observer::from((
+                        parameters.acquire_stream("inner")?,
+                        Validator::default().mode(mode),
+                    ))
+                    .into_boxed())
                 })),
             ),
         )]
@@ -67,7 +269,18 @@ impl Handler for Validator {
                 warn!(
                     "{:?} extension is not supported and therefore not validated",
                     extension_decl.name
-                )
+                );
+                if self.mode == ValidationMode::Collect {
+                    self.report.entries.push(ValidationEntry {
+                        severity: Severity::Warning,
+                        component: ComponentType::Meta,
+                        position: Position::default(),
+                        message: format!(
+                            "{:?} extension is not supported and therefore not validated",
+                            extension_decl.name
+                        ),
+                    });
+                }
             }
         }
 
@@ -83,42 +296,85 @@ impl Handler for Validator {
         // validate classifiers
         for classifier_decl in meta.classifiers.iter() {
             if !CRE_NCNAME.is_match(&classifier_decl.name) {
-                return Err(Error::ValidationError(format!(
-                    "classifier name {:?} is no valid xs:NCName",
-                    &classifier_decl.name
-                )));
+                self.check(
+                    ComponentType::Meta,
+                    Position::default(),
+                    Err(Error::ValidationError(format!(
+                        "classifier name {:?} is no valid xs:NCName",
+                        &classifier_decl.name
+                    ))),
+                )?;
             }
         }
 
         // validate meta against extensions
-        for validator in self.validators.iter() {
-            validator(Box::new(&meta))?;
-        }
+        self.validate(ComponentType::Meta, Position::default(), &meta)?;
 
+        self.flavor = meta.flavor;
         Ok(meta)
     }
 
     fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
-        for validator in self.trace_only.iter().chain(self.validators.iter()) {
-            validator(Box::new(&trace))?
+        let position = Position {
+            trace: Some(self.traces),
+            event: None,
+        };
+        self.traces += 1;
+        self.events = 0;
+
+        if self.flavor == Flavor::Stream {
+            self.check(
+                ComponentType::Trace,
+                position,
+                Err(Error::ValidationError(
+                    "unexpected trace in event stream".to_string(),
+                )),
+            )?;
         }
 
+        self.validate(ComponentType::Trace, position, &trace)?;
+
         Ok(Some(trace))
     }
 
-    fn on_event(&mut self, event: Event, _in_trace: bool) -> Result<Option<Event>> {
-        for validator in self.event_only.iter().chain(self.validators.iter()) {
-            validator(Box::new(&event))?;
-        }
+    fn on_event(&mut self, event: Event, in_trace: bool) -> Result<Option<Event>> {
+        let position = if in_trace {
+            self.events += 1;
+            Position {
+                trace: Some(self.traces - 1),
+                event: Some(self.events - 1),
+            }
+        } else {
+            self.standalone += 1;
+            Position {
+                trace: None,
+                event: Some(self.standalone - 1),
+            }
+        };
+
+        self.validate(ComponentType::Event, position, &event)?;
 
         Ok(Some(event))
     }
+
+    fn on_unfolded_event(&mut self, event: Event) -> Result<Option<Event>> {
+        self.on_event(event, true)
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        match self.mode {
+            ValidationMode::Abort => Ok(vec![]),
+            ValidationMode::Collect => Ok(vec![std::mem::take(&mut self.report).into()]),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::dev_util::load_example;
+    use crate::stream::log::Log;
     use crate::stream::void::consume;
+    use crate::stream::Sink;
 
     use super::*;
 
@@ -147,4 +403,42 @@ mod tests {
             panic!("expected validation error")
         }
     }
+
+    #[test]
+    fn test_collect_validation() {
+        let buffer = load_example(&["non_validating", "event_incorrect_type.xes"]);
+        let mut log = Log::default();
+        let artifacts = log
+            .consume(&mut Validator::default().collect().into_observer(buffer))
+            .expect("collecting validation errors is expected to succeed");
+        assert_eq!(log.traces.len(), 1);
+        assert_eq!(log.events.len(), 2);
+
+        let report =
+            AnyArtifact::find::<ValidationReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert!(!report.is_valid());
+        let errors: Vec<_> = report.errors().collect();
+        // the global as well as the extension reject the same event
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.position == errors[0].position));
+        assert_eq!(errors[0].component, ComponentType::Event);
+        assert_eq!(
+            errors[0].position,
+            Position {
+                trace: Some(0),
+                event: Some(1)
+            }
+        );
+        assert_eq!(errors[0].position.to_string(), "trace 0, event 1");
+
+        // valid streams yield an empty report
+        let buffer = load_example(&["test", "extension_full.xes"]);
+        let mut validator = Validator::default()
+            .mode("collect".parse().unwrap())
+            .into_observer(buffer);
+        let artifacts = consume(&mut validator).unwrap();
+        let report =
+            AnyArtifact::find::<ValidationReport>(&mut artifacts.iter().flatten()).unwrap();
+        assert!(report.is_valid());
+    }
 }
@@ -0,0 +1,525 @@
+//! Trace variants and their exploration
+//!
+//! A variant is a sequence of activities shared by one or more traces. `VariantCollector` counts
+//! the variants of a stream and releases them as `Variants` artifact, ordered by frequency. The
+//! artifact is the backend of interactive exploration:
+//! * `Variants::page` pages through variants, e.g. twenty at a time,
+//! * `Variants::coverage` and `Variants::covering` relate variants to the share of traces they
+//!   account for,
+//! * `Variants::examples` fetches example traces of a variant from a fresh stream of the same
+//!   source, reading no further than the last example required and
+//! * `VariantFilter` restricts a stream to selected variants.
+//!
+//! Example traces are referenced by their position among the traces of the stream and by their
+//! `concept:name`, since buffering whole traces for thousands of variants quickly exhausts memory.
+//!
+
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::dfg::{ActivitySource, DfgGenerator};
+use crate::stream::observer::{Handler, Observer};
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::{
+    AnyArtifact, Artifact, AttributeContainer, AttributeType, Component, Meta, Stream, Trace,
+};
+use crate::{Error, Result};
+
+/// Sequence of activities shared by a number of traces
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub activities: Vec<String>,
+    pub count: usize,
+    /// Positions of example traces among the traces of the stream
+    pub examples: Vec<usize>,
+    /// `concept:name` of the example traces that have one
+    #[serde(default)]
+    pub example_ids: Vec<String>,
+}
+
+/// Slice of variants
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPage<'a> {
+    pub variants: &'a [Variant],
+    /// Index of the page, starting at zero
+    pub page: usize,
+    pub pages: usize,
+    /// Rank of the first variant on the page
+    pub offset: usize,
+}
+
+impl<'a> VariantPage<'a> {
+    pub fn has_next(&self) -> bool {
+        self.page + 1 < self.pages
+    }
+}
+
+/// Variants of a stream, most frequent first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Variants {
+    pub variants: Vec<Variant>,
+    /// Number of traces observed
+    pub traces: usize,
+}
+
+impl Variants {
+    /// Variants on the given page of `size` variants, empty if the page is out of range
+    pub fn page(&self, page: usize, size: usize) -> VariantPage<'_> {
+        let size = size.max(1);
+        let offset = (page * size).min(self.variants.len());
+        let end = (offset + size).min(self.variants.len());
+
+        VariantPage {
+            variants: &self.variants[offset..end],
+            page,
+            pages: self.variants.len().div_ceil(size),
+            offset,
+        }
+    }
+
+    /// Percentage of traces that follow the variant of the given rank
+    pub fn coverage(&self, rank: usize) -> Option<f64> {
+        let variant = self.variants.get(rank)?;
+        Some(percentage(variant.count, self.traces))
+    }
+
+    /// Percentage of traces that follow one of the variants up to the given rank, inclusive
+    pub fn cumulative_coverage(&self, rank: usize) -> f64 {
+        let count = self.variants.iter().take(rank + 1).map(|v| v.count).sum();
+        percentage(count, self.traces)
+    }
+
+    /// Number of most frequent variants that cover at least the given percentage of traces
+    pub fn covering(&self, percent: f64) -> usize {
+        let mut count = 0;
+        for (rank, variant) in self.variants.iter().enumerate() {
+            if percentage(count, self.traces) >= percent {
+                return rank;
+            }
+            count += variant.count;
+        }
+        self.variants.len()
+    }
+
+    /// Rank of the variant of the given activities, if observed
+    pub fn position<S: AsRef<str>>(&self, activities: &[S]) -> Option<usize> {
+        self.variants.iter().position(|v| {
+            v.activities.len() == activities.len()
+                && v.activities
+                    .iter()
+                    .zip(activities.iter())
+                    .all(|(a, b)| a == b.as_ref())
+        })
+    }
+
+    /// Read the example traces of the variant of given rank from a fresh stream of the source
+    ///
+    /// The stream is consumed up to the last example only.
+    ///
+    pub fn examples(&self, rank: usize, stream: &mut dyn Stream) -> Result<Vec<Trace>> {
+        let variant = self.variants.get(rank).ok_or_else(|| {
+            Error::KeyError(format!("variant {} of {}", rank, self.variants.len()))
+        })?;
+
+        let mut positions = variant.examples.iter().copied().peekable();
+        let mut examples = Vec::with_capacity(variant.examples.len());
+        let mut position = 0;
+        while positions.peek().is_some() {
+            match stream.next()? {
+                Some(Component::Trace(trace)) => {
+                    if positions.peek() == Some(&position) {
+                        positions.next();
+                        examples.push(trace);
+                    }
+                    position += 1;
+                }
+                Some(_) => (),
+                None => {
+                    return Err(Error::StreamError(format!(
+                        "stream ended after {} traces, expected at least {}",
+                        position,
+                        variant.examples.last().unwrap() + 1
+                    )))
+                }
+            }
+        }
+
+        Ok(examples)
+    }
+}
+
+#[typetag::serde]
+impl Artifact for Variants {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        count as f64 * 100. / total as f64
+    }
+}
+
+fn variant(generator: &DfgGenerator, trace: &Trace) -> Vec<String> {
+    trace
+        .events
+        .iter()
+        .filter_map(|e| generator.activity(e))
+        .collect()
+}
+
+/// Count the variants of a stream
+///
+/// Activities are taken like by the `DfgGenerator`, events without activity are skipped.
+///
+pub struct VariantCollector {
+    generator: DfgGenerator,
+    max_examples: usize,
+    index: HashMap<Vec<String>, usize>,
+    variants: Vec<Variant>,
+    traces: usize,
+}
+
+impl Default for VariantCollector {
+    fn default() -> Self {
+        Self::new(ActivitySource::Attribute("concept:name".to_string()))
+    }
+}
+
+impl VariantCollector {
+    pub fn new(source: ActivitySource) -> Self {
+        VariantCollector {
+            generator: DfgGenerator::new(source),
+            max_examples: 10,
+            index: HashMap::new(),
+            variants: Vec::new(),
+            traces: 0,
+        }
+    }
+
+    /// Record the positions and ids of up to `max_examples` traces per variant
+    pub fn max_examples(mut self, max_examples: usize) -> Self {
+        self.max_examples = max_examples;
+        self
+    }
+
+    /// Variants observed so far, most frequent first and in order of appearance otherwise
+    pub fn variants(&self) -> Variants {
+        let mut variants = self.variants.clone();
+        // stable, i.e. the order of appearance is kept among equally frequent variants
+        variants.sort_by_key(|v| Reverse(v.count));
+
+        Variants {
+            variants,
+            traces: self.traces,
+        }
+    }
+}
+
+impl Handler for VariantCollector {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.generator.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let activities = variant(&self.generator, &trace);
+        let variants = &mut self.variants;
+        let i = *self
+            .index
+            .entry(activities)
+            .or_insert_with_key(|activities| {
+                variants.push(Variant {
+                    activities: activities.clone(),
+                    count: 0,
+                    examples: Vec::new(),
+                    example_ids: Vec::new(),
+                });
+                variants.len() - 1
+            });
+
+        let variant = &mut self.variants[i];
+        variant.count += 1;
+        if variant.examples.len() < self.max_examples {
+            variant.examples.push(self.traces);
+            if let Some(id) = trace.get_value("concept:name") {
+                variant.example_ids.push(id.try_string()?.to_string());
+            }
+        }
+        self.traces += 1;
+
+        Ok(Some(trace))
+    }
+
+    fn release_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.variants().into()])
+    }
+}
+
+/// Pass traces of selected variants only
+///
+/// Standalone events are passed through.
+///
+pub struct VariantFilter {
+    generator: DfgGenerator,
+    selection: BTreeSet<Vec<String>>,
+    invert: bool,
+}
+
+impl VariantFilter {
+    /// Select the given activity sequences, activities are taken from `concept:name`
+    pub fn new(selection: Vec<Vec<String>>) -> Self {
+        VariantFilter {
+            generator: DfgGenerator::default(),
+            selection: selection.into_iter().collect(),
+            invert: false,
+        }
+    }
+
+    /// Take activities as given instead
+    pub fn source(mut self, source: ActivitySource) -> Self {
+        self.generator = DfgGenerator::new(source);
+        self
+    }
+
+    /// Drop the selected variants instead of all others
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+}
+
+impl Handler for VariantFilter {
+    fn on_meta(&mut self, meta: Meta) -> Result<Meta> {
+        self.generator.on_meta(meta)
+    }
+
+    fn on_trace(&mut self, trace: Trace) -> Result<Option<Trace>> {
+        let selected = self.selection.contains(&variant(&self.generator, &trace));
+        Ok(if selected != self.invert {
+            Some(trace)
+        } else {
+            None
+        })
+    }
+}
+
+fn activity_source(parameters: &mut Parameters) -> Result<ActivitySource> {
+    // a classifier takes precedence over the activity attribute
+    Ok(match parameters.acquire_attribute("classifier") {
+        Ok(classifier) => ActivitySource::Classifier(classifier.value.try_string()?.to_string()),
+        Err(_) => ActivitySource::Attribute(
+            parameters
+                .acquire_attribute("activity")?
+                .value
+                .try_string()?
+                .to_string(),
+        ),
+    })
+}
+
+impl PluginProvider for VariantCollector {
+    fn entries() -> Vec<Entry>
+    where
+        Self: Sized,
+    {
+        vec![
+            Entry::new(
+                "VariantCollector",
+                "Count the variants of all traces by activity attribute or classifier",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The stream to be observed")
+                        .default_attr("activity", "Event attribute that holds the activity", |k| {
+                            (k, "concept:name").into()
+                        })
+                        .default_attr(
+                            "max_examples",
+                            "Number of example traces recorded per variant",
+                            |k| (k, 10).into(),
+                        )
+                        .optional_attr(
+                            "classifier",
+                            "Classifier that takes precedence over the activity attribute",
+                            AttributeType::String,
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let max_examples = *parameters
+                            .acquire_attribute("max_examples")?
+                            .value
+                            .try_int()?;
+                        let collector = VariantCollector::new(activity_source(parameters)?)
+                            .max_examples(max_examples.max(0) as usize);
+
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, collector))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+            Entry::new(
+                "VariantFilter",
+                "Pass traces of the variants of given ranks only, or drop them if inverted",
+                Factory::new(
+                    Declaration::default()
+                        .stream("inner", "The stream to be filtered")
+                        .typed_artifact::<Variants, _, _>("variants", "The variants to select from")
+                        .typed_attr(
+                            "selection",
+                            "List of ranks of the selected variants",
+                            AttributeType::List,
+                        )
+                        .default_attr("activity", "Event attribute that holds the activity", |k| {
+                            (k, "concept:name").into()
+                        })
+                        .default_attr("invert", "Whether to drop the selected variants", |k| {
+                            (k, false).into()
+                        })
+                        .optional_attr(
+                            "classifier",
+                            "Classifier that takes precedence over the activity attribute",
+                            AttributeType::String,
+                        ),
+                    FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
+                        let artifact = parameters.acquire_artifact("variants")?;
+                        let variants = match artifact.downcast_ref::<Variants>() {
+                            Some(variants) => variants.clone(),
+                            None => {
+                                return Err(Error::ArtifactError(format!(
+                                    "expected variants artifact, got {:?}",
+                                    artifact
+                                )))
+                            }
+                        };
+
+                        let mut selection = Vec::new();
+                        for rank in parameters
+                            .acquire_attribute("selection")?
+                            .value
+                            .try_list()?
+                        {
+                            let rank = *rank.value.try_int()?;
+                            match variants.variants.get(rank as usize) {
+                                Some(variant) if rank >= 0 => {
+                                    selection.push(variant.activities.clone())
+                                }
+                                _ => {
+                                    return Err(Error::AttributeError(format!(
+                                        "no variant of rank {}, got {} variants",
+                                        rank,
+                                        variants.variants.len()
+                                    )))
+                                }
+                            }
+                        }
+
+                        let filter = VariantFilter::new(selection)
+                            .source(activity_source(parameters)?)
+                            .invert(
+                                *parameters
+                                    .acquire_attribute("invert")?
+                                    .value
+                                    .try_boolean()?,
+                            );
+
+                        Ok(
+                            Observer::from((parameters.acquire_stream("inner")?, filter))
+                                .into_boxed(),
+                        )
+                    })),
+                ),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+    use crate::stream::log::Log;
+    use crate::stream::void::consume;
+    use crate::stream::Sink;
+
+    use super::*;
+
+    #[test]
+    fn test_variants() {
+        let mut observer = VariantCollector::default()
+            .max_examples(2)
+            .into_observer(load_example(&["book", "L1.xes"]));
+        let artifacts = consume(&mut observer).unwrap();
+        let variants = AnyArtifact::find::<Variants>(&mut artifacts.iter().flatten())
+            .unwrap()
+            .clone();
+
+        let summary: Vec<_> = variants
+            .variants
+            .iter()
+            .map(|v| (v.activities.concat(), v.count, v.examples.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("abcd".to_string(), 3, 2),
+                ("acbd".to_string(), 2, 2),
+                ("aed".to_string(), 1, 1)
+            ]
+        );
+        assert_eq!(variants.traces, 6);
+        assert_eq!(variants.position(&["a", "e", "d"]), Some(2));
+        assert_eq!(variants.coverage(0), Some(50.));
+        assert_eq!(variants.coverage(3), None);
+        assert!((variants.cumulative_coverage(1) - 500. / 6.).abs() < 1e-9);
+        assert_eq!(variants.covering(50.), 1);
+        assert_eq!(variants.covering(60.), 2);
+        assert_eq!(variants.covering(100.), 3);
+        assert_eq!(variants.variants[2].example_ids.len(), 1);
+
+        let page = variants.page(1, 2);
+        assert_eq!((page.offset, page.pages, page.variants.len()), (2, 2, 1));
+        assert!(!page.has_next());
+        assert!(variants.page(5, 2).variants.is_empty());
+
+        let examples = variants
+            .examples(2, &mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        assert_eq!(examples.len(), 1);
+        let activities: Vec<_> = examples[0]
+            .events
+            .iter()
+            .map(|e| e.get_value("concept:name").unwrap().try_string().unwrap())
+            .collect();
+        assert_eq!(activities, ["a", "e", "d"]);
+        assert!(variants
+            .examples(3, &mut load_example(&["book", "L1.xes"]))
+            .is_err());
+
+        let selection = vec![variants.variants[1].activities.clone()];
+        let mut log = Log::default();
+        log.consume(
+            &mut VariantFilter::new(selection.clone())
+                .into_observer(load_example(&["book", "L1.xes"])),
+        )
+        .unwrap();
+        assert_eq!(log.traces.len(), 2);
+
+        let mut log = Log::default();
+        log.consume(
+            &mut VariantFilter::new(selection)
+                .invert(true)
+                .into_observer(load_example(&["book", "L1.xes"])),
+        )
+        .unwrap();
+        assert_eq!(log.traces.len(), 4);
+    }
+}
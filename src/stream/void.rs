@@ -1,9 +1,17 @@
 //! Dummy stream and / or sink.
 //!
+//! Besides the plain `Void`, the `CountingSink` discards all components but counts them and
+//! optionally asserts the expected numbers of meta, trace and event components once the stream is
+//! closed. That makes it a handy terminal segment for pipelines that only validate their input.
+//!
+
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
 
 use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
-use crate::stream::{AnyArtifact, ResOpt, Sink, Stream};
-use crate::Result;
+use crate::stream::{AnyArtifact, Artifact, Component, ResOpt, Sink, Stream};
+use crate::{Error, Result};
 
 /// A dummy stream / sink that does nothing but producing an empty or consuming a given stream
 pub struct Void;
@@ -52,11 +60,187 @@ impl PluginProvider for Void {
                     FactoryType::Sink(Box::new(|_| Ok(Box::new(Void::default())))),
                 ),
             ),
+            Entry::new(
+                "CountingSink",
+                "A sink that discards all items but counts them and optionally asserts the expected counts (expect_meta, expect_traces, expect_events)",
+                Factory::new(
+                    Declaration::default(),
+                    FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
+                        let mut expect = |key: &str| -> Result<Option<usize>> {
+                            match parameters.acquire_attribute(key) {
+                                Ok(attribute) => {
+                                    let count = *attribute.value.try_int()?;
+                                    if count < 0 {
+                                        return Err(Error::AttributeError(format!(
+                                            "{} is expected to be non-negative, got {}",
+                                            key, count
+                                        )));
+                                    }
+                                    Ok(Some(count as usize))
+                                }
+                                Err(_) => Ok(None),
+                            }
+                        };
+
+                        Ok(CountingSink {
+                            counts: ComponentCounts::default(),
+                            expected: ExpectedCounts {
+                                meta: expect("expect_meta")?,
+                                traces: expect("expect_traces")?,
+                                events: expect("expect_events")?,
+                            },
+                        }
+                        .into_boxed())
+                    })),
+                ),
+            ),
         ]
     }
 }
 
+/// Number of components per type
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComponentCounts {
+    pub meta: usize,
+    pub traces: usize,
+    /// All events, whether in traces or standalone
+    pub events: usize,
+}
+
+impl ComponentCounts {
+    /// Count a component
+    pub fn add(&mut self, component: &Component) {
+        match component {
+            Component::Meta(_) => self.meta += 1,
+            Component::Trace(trace) => {
+                self.traces += 1;
+                self.events += trace.events.len();
+            }
+            Component::Event(_) => self.events += 1,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Artifact for ComponentCounts {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Expected number of components per type, unset counts are not checked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpectedCounts {
+    pub meta: Option<usize>,
+    pub traces: Option<usize>,
+    pub events: Option<usize>,
+}
+
+/// A sink that discards all components but counts them
+///
+/// The counts are released as `ComponentCounts` artifact. If expected counts are given, closing
+/// the stream fails with a `ValidationError` that lists all mismatches.
+///
+#[derive(Debug, Default)]
+pub struct CountingSink {
+    counts: ComponentCounts,
+    expected: ExpectedCounts,
+}
+
+impl CountingSink {
+    /// Expect the given number of meta components
+    pub fn expect_meta(mut self, n: usize) -> Self {
+        self.expected.meta = Some(n);
+        self
+    }
+
+    /// Expect the given number of traces
+    pub fn expect_traces(mut self, n: usize) -> Self {
+        self.expected.traces = Some(n);
+        self
+    }
+
+    /// Expect the given number of events, whether in traces or standalone
+    pub fn expect_events(mut self, n: usize) -> Self {
+        self.expected.events = Some(n);
+        self
+    }
+
+    /// Counts so far
+    pub fn counts(&self) -> &ComponentCounts {
+        &self.counts
+    }
+}
+
+impl Sink for CountingSink {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.counts.add(&component);
+        Ok(())
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        let checks = [
+            ("meta components", self.expected.meta, self.counts.meta),
+            ("traces", self.expected.traces, self.counts.traces),
+            ("events", self.expected.events, self.counts.events),
+        ];
+        let mismatches: Vec<_> = checks
+            .iter()
+            .filter_map(|(name, expected, actual)| match expected {
+                Some(expected) if expected != actual => {
+                    Some(format!("expected {} {}, got {}", expected, name, actual))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ValidationError(mismatches.join(", ")))
+        }
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(vec![self.counts.clone().into()])
+    }
+}
+
 /// Creates a dummy sink and consumes the given stream
 pub fn consume<T: Stream>(stream: &mut T) -> Result<Vec<Vec<AnyArtifact>>> {
     Void::default().consume(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::dev_util::load_example;
+
+    use super::*;
+
+    #[test]
+    fn test_counting_sink() {
+        let mut sink = CountingSink::default().expect_meta(1).expect_traces(6);
+        let artifacts = sink
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let counts = AnyArtifact::find::<ComponentCounts>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!(
+            counts,
+            &ComponentCounts {
+                meta: 1,
+                traces: 6,
+                events: 23
+            }
+        );
+
+        let mut sink = CountingSink::default().expect_traces(5).expect_events(23);
+        match sink.consume(&mut load_example(&["book", "L1.xes"])) {
+            Err(Error::ValidationError(message)) => assert_eq!(message, "expected 5 traces, got 6"),
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+}
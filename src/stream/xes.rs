@@ -6,6 +6,31 @@
 //! 100% compliant (assuming validation is turned on). Hence, if you run into a case where invalid
 //! XES XML is produced consider it a bug and feel invited to report an issue.
 //!
+//! Event streams without traces (`Flavor::Stream`) are marked by the `event-stream` token in the
+//! `xes.features` of the log element. The reader takes that over into the meta data and rejects
+//! traces in such documents. The writer marks streams of that flavor likewise and writes the events
+//! of any traces as standalone events that carry the trace attributes with the prefix `case:`.
+//!
+//! Logs with huge traces can be read in constant memory by `XesReader::streaming_events`, which
+//! emits the traces unfolded (`Flavor::Unfolded`). The writer folds such streams into traces again.
+//!
+//! Serialization is CPU-heavy due to escaping and formatting. `XesWriter::background` moves it to
+//! a worker thread that is fed through a bounded queue, such that the calling thread continues to
+//! pull the stream meanwhile.
+//!
+//! Errors of the reader are reported as `Error::ParseError` with a `ParseDiagnostic` that locates
+//! them by line and column, such that tools can point at the offending element.
+//!
+//! Malformed traces and events fail the whole stream by default. With `XesReader::skip_invalid`,
+//! a trace or event whose attributes can't be parsed is skipped instead, i.e. the innermost trace
+//! or event around the error. Skipped components are logged along with the location of the error
+//! and summarized in a `SkippedComponents` artifact. Broken meta data and XML syntax errors
+//! remain fatal, since the structure of the document can't be recovered from them.
+//!
+//! Files whose path ends with `.gz` are transparently decompressed while reading and compressed
+//! while writing by the plugins, the optional attribute `compression` (`gzip` or `none`) overrides
+//! that choice.
+//!
 //! For further information see [xes-standard.org](http://www.xes-standard.org/) and for other than
 //! the shipped example files see [processmining.org](http://www.processmining.org/logs/start).
 //!
@@ -46,31 +71,115 @@
 //! ```
 //!
 
+use std::any::Any;
 use std::collections::{HashMap, VecDeque};
 use std::convert::{From, TryFrom};
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use quick_xml::events::{
     BytesDecl as QxBytesDecl, BytesEnd as QxBytesEnd, BytesStart as QxBytesStart,
     BytesText as QxBytesText, Event as QxEvent,
 };
 use quick_xml::{Reader as QxReader, Writer as QxWriter};
+use serde::{Deserialize, Serialize};
 
+use crate::stream::channel::{stream_channel, StreamSender};
+use crate::stream::csv::CASE_PREFIX;
+use crate::stream::inspect::QuickStats;
 use crate::stream::log::Log;
-use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, PluginProvider};
+use crate::stream::output::FileOptions;
+use crate::stream::plugin::{Declaration, Entry, Factory, FactoryType, Parameters, PluginProvider};
+use crate::stream::provenance::ProvenanceWriter;
+use crate::stream::rotate::{RotatingWriter, Rotation, SinkBuilder};
 use crate::stream::xml_util::{
     parse_bool, validate_name, validate_ncname, validate_token, validate_uri,
 };
 use crate::stream::{
-    Attribute, AttributeMap, AttributeValue, ClassifierDecl, Component, Event, ExtensionDecl,
-    Global, Meta, ResOpt, Scope, Sink, Stream, Trace,
+    AnyArtifact, Artifact, Attribute, AttributeMap, AttributeType, AttributeValue, ClassifierDecl,
+    Component, ComponentType, Event, ExtensionDecl, Flavor, Global, KeyTable, Meta, ReadPosition,
+    ResOpt, Scope, Sink, Stream, Trace,
 };
 use crate::{DateTime, Error, Result};
 
+/// Token of `xes.features` that marks event streams without traces
+pub const EVENT_STREAM_FEATURE: &str = "event-stream";
+
+/// Compression of XES files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl std::str::FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            other => Err(Error::AttributeError(format!(
+                "invalid compression {:?}, expected \"none\" or \"gzip\"",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+        })
+    }
+}
+
+impl Compression {
+    /// Guess the compression from the file extension
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Read the optional plugin attribute `compression`, fall back to the file extension
+    pub fn from_parameters(path: &str, parameters: &mut Parameters) -> Result<Self> {
+        match parameters.acquire_attribute("compression") {
+            Ok(attribute) => attribute.value.try_string()?.parse(),
+            Err(_) => Ok(Compression::from_path(path)),
+        }
+    }
+
+    /// Wrap a reader such that it decompresses
+    pub fn reader<R: io::Read + Send + 'static>(self, reader: R) -> Box<dyn io::Read + Send> {
+        match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(GzDecoder::new(reader)),
+        }
+    }
+
+    /// Wrap a writer such that it compresses
+    pub fn writer<W: io::Write + Send + 'static>(self, writer: W) -> Box<dyn io::Write + Send> {
+        match self {
+            Compression::None => Box::new(writer),
+            Compression::Gzip => Box::new(GzEncoder::new(writer, flate2::Compression::default())),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum XesComponent {
     Attribute(Attribute),
@@ -88,21 +197,20 @@ struct XesValue {
     attributes: Vec<Attribute>,
 }
 
-impl TryFrom<XesIntermediate> for Attribute {
-    type Error = Error;
-
-    fn try_from(mut intermediate: XesIntermediate) -> Result<Self> {
-        let key_str = intermediate.pop("key")?;
+impl Attribute {
+    /// Build an attribute from its XES element, looking up its key in the reader's key table
+    fn from_intermediate(mut intermediate: XesIntermediate, keys: &mut KeyTable) -> Result<Self> {
+        let key = keys.intern(&intermediate.pop("key")?);
         let val_str = intermediate.pop("value");
         let mut children = Vec::new();
 
         let value = match intermediate.type_name.as_str() {
-            "string" => AttributeValue::String(val_str?),
+            "string" => AttributeValue::String(val_str?.into()),
             "date" => AttributeValue::Date(DateTime::parse_from_rfc3339(val_str?.as_str())?),
             "int" => AttributeValue::Int(val_str?.parse::<i64>()?),
             "float" => AttributeValue::Float(val_str?.parse::<f64>()?),
             "boolean" => AttributeValue::Boolean(parse_bool(&val_str?.as_str())?),
-            "id" => AttributeValue::Id(val_str?),
+            "id" => AttributeValue::Id(val_str?.into()),
             "list" => {
                 let mut attributes: Vec<Attribute> = Vec::new();
 
@@ -137,9 +245,9 @@ impl TryFrom<XesIntermediate> for Attribute {
         }
 
         Ok(if children.is_empty() {
-            Attribute::new(key_str, value)
+            Attribute::new(key, value)
         } else {
-            Attribute::with_children(key_str, value, children)
+            Attribute::with_children(key, value, children)
         })
     }
 }
@@ -439,7 +547,8 @@ impl TryFrom<XesIntermediate> for Trace {
 }
 
 impl Trace {
-    fn write_xes<W>(&self, writer: &mut QxWriter<W>) -> Result<()>
+    /// Write the trace element without closing it, such that further events may follow
+    fn write_xes_open<W>(&self, writer: &mut QxWriter<W>) -> Result<()>
     where
         W: io::Write,
     {
@@ -451,7 +560,16 @@ impl Trace {
             .iter()
             .try_for_each(|(k, v, c)| Attribute::components_write_xes(k, v, c, writer))?;
         self.events.iter().try_for_each(|e| e.write_xes(writer))?;
-        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(tag)))?;
+
+        Ok(())
+    }
+
+    fn write_xes<W>(&self, writer: &mut QxWriter<W>) -> Result<()>
+    where
+        W: io::Write,
+    {
+        self.write_xes_open(writer)?;
+        writer.write_event(QxEvent::End(QxBytesEnd::borrowed(b"trace")))?;
 
         Ok(())
     }
@@ -491,14 +609,12 @@ impl TryFrom<XesIntermediate> for Log {
     }
 }
 
-impl TryFrom<XesIntermediate> for XesComponent {
-    type Error = Error;
-
-    fn try_from(intermediate: XesIntermediate) -> Result<Self> {
+impl XesComponent {
+    fn from_intermediate(intermediate: XesIntermediate, keys: &mut KeyTable) -> Result<Self> {
         match intermediate.type_name.as_str() {
-            "string" | "date" | "int" | "float" | "boolean" | "id" | "list" => {
-                Ok(XesComponent::Attribute(Attribute::try_from(intermediate)?))
-            }
+            "string" | "date" | "int" | "float" | "boolean" | "id" | "list" => Ok(
+                XesComponent::Attribute(Attribute::from_intermediate(intermediate, keys)?),
+            ),
             "values" => Ok(XesComponent::Values(XesValue::try_from(intermediate)?)),
             "extension" => Ok(XesComponent::ExtensionDecl(ExtensionDecl::try_from(
                 intermediate,
@@ -558,25 +674,237 @@ impl XesIntermediate {
     }
 }
 
+/// Location and cause of an error in a XES document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    /// Byte offset of the reader when the error occurred, i.e. right after the offending markup
+    pub position: usize,
+    /// Line of the position, starting at 1
+    pub line: usize,
+    /// Column of the position in bytes, starting at 1
+    pub column: usize,
+    /// Name of the element that was read last
+    pub element: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)?;
+        if let Some(element) = &self.element {
+            write!(f, " in <{}>", element)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Line of the position a `LineReader` consumed up to
+#[derive(Debug, Default)]
+struct LineCounter {
+    line: AtomicUsize,
+    line_start: AtomicUsize,
+}
+
+/// Buffered reader that counts the lines it passes
+struct LineReader<R: io::BufRead> {
+    inner: R,
+    position: usize,
+    counter: Arc<LineCounter>,
+}
+
+impl<R: io::BufRead> io::Read for LineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = io::BufRead::fill_buf(self)?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
+
+impl<R: io::BufRead> io::BufRead for LineReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // consumed bytes have been filled before, so no reading takes place here
+        if let Ok(buf) = self.inner.fill_buf() {
+            let consumed = &buf[..amt.min(buf.len())];
+            let newlines = consumed.iter().filter(|b| **b == b'\n').count();
+            if newlines > 0 {
+                let last = consumed.iter().rposition(|b| *b == b'\n').unwrap();
+                self.counter.line.fetch_add(newlines, Ordering::Relaxed);
+                self.counter
+                    .line_start
+                    .store(self.position + last + 1, Ordering::Relaxed);
+            }
+        }
+        self.position += amt;
+        self.inner.consume(amt)
+    }
+}
+
+/// Trace or event that was skipped by a lenient `XesReader`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedComponent {
+    pub component: ComponentType,
+    pub diagnostic: ParseDiagnostic,
+}
+
+/// Summary of the components a lenient `XesReader` skipped
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkippedComponents {
+    pub traces: usize,
+    pub events: usize,
+    pub components: Vec<SkippedComponent>,
+}
+
+impl SkippedComponents {
+    fn record(&mut self, skipped: SkippedComponent) {
+        warn!(
+            "skipped invalid {:?} at {}",
+            skipped.component, skipped.diagnostic
+        );
+        match skipped.component {
+            ComponentType::Trace => self.traces += 1,
+            _ => self.events += 1,
+        }
+        self.components.push(skipped);
+    }
+}
+
+#[typetag::serde]
+impl Artifact for SkippedComponents {
+    fn upcast_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn upcast_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 /// XML deserialization of XES
 pub struct XesReader<R: io::BufRead> {
-    reader: QxReader<R>,
+    reader: QxReader<LineReader<R>>,
+    lines: Arc<LineCounter>,
+    /// Name of the element that was read last
+    element: Vec<u8>,
     buffer: Vec<u8>,
     stack: Vec<XesIntermediate>,
-    cache: Option<Component>,
+    cache: VecDeque<Component>,
     meta: Option<Meta>,
     empty: bool,
+    flavor: Flavor,
+    /// Whether the attributes of the open trace have been emitted already
+    unfolded: bool,
+    /// Skip invalid traces and events instead of failing, collecting them here
+    skipped: Option<SkippedComponents>,
+    /// Stack depth of the invalid component that is being skipped
+    skipping: Option<(usize, SkippedComponent)>,
+    /// Size of the document in bytes, if known
+    size: Option<u64>,
+    /// Attribute keys read so far, shared by all attributes of the same key
+    keys: KeyTable,
 }
 
 impl<R: io::BufRead> XesReader<R> {
     pub fn new(reader: R) -> Self {
+        let lines = Arc::new(LineCounter::default());
         XesReader {
-            reader: QxReader::from_reader(reader),
+            reader: QxReader::from_reader(LineReader {
+                inner: reader,
+                position: 0,
+                counter: lines.clone(),
+            }),
+            lines,
+            element: Vec::new(),
             buffer: Vec::new(),
             stack: Vec::new(),
-            cache: None,
+            cache: VecDeque::new(),
             meta: Some(Meta::default()),
             empty: true,
+            flavor: Flavor::Log,
+            unfolded: false,
+            skipped: None,
+            skipping: None,
+            size: None,
+            keys: KeyTable::default(),
+        }
+    }
+
+    /// Set the size of the document in bytes to report the progress of reading it
+    pub fn size_hint(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Read the document as given flavor, regardless of its declared features
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.set_flavor(flavor);
+        self
+    }
+
+    /// Emit traces unfolded, i.e. without holding a whole trace in memory
+    ///
+    /// Each trace is emitted as a trace without events that carries the trace attributes, followed
+    /// by its events one by one, see `Flavor::Unfolded`. Trace attributes that occur after the
+    /// first event of a trace are dropped with a warning.
+    ///
+    pub fn streaming_events(self) -> Self {
+        self.flavor(Flavor::Unfolded)
+    }
+
+    /// Locate an error at the current position of the reader
+    fn diagnostic(&self, error: &Error) -> ParseDiagnostic {
+        let position = self.reader.buffer_position();
+        let line_start = self.lines.line_start.load(Ordering::Relaxed);
+        ParseDiagnostic {
+            position,
+            line: self.lines.line.load(Ordering::Relaxed) + 1,
+            column: position.saturating_sub(line_start) + 1,
+            element: if self.element.is_empty() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&self.element).to_string())
+            },
+            message: error.to_string(),
+        }
+    }
+
+    /// Attach the location to an error, unless it's caused by I/O
+    fn diagnose(&self, error: Error) -> Error {
+        match error {
+            Error::IOError(_) | Error::ParseError(_) => error,
+            error => Error::ParseError(Box::new(self.diagnostic(&error))),
+        }
+    }
+
+    /// Skip traces and events that can't be parsed instead of failing
+    pub fn skip_invalid(mut self) -> Self {
+        self.skipped = Some(SkippedComponents::default());
+        self
+    }
+
+    fn set_flavor(&mut self, flavor: Flavor) {
+        self.flavor = flavor;
+        if let Some(meta) = &mut self.meta {
+            meta.flavor = flavor;
+        }
+    }
+
+    /// Take over the flavor declared by the log element
+    fn detect_flavor(&mut self, intermediate: &XesIntermediate) {
+        if intermediate.type_name != "log" {
+            return;
+        }
+        let features = intermediate.attributes.get("xes.features");
+        if features.is_some_and(|f| f.split_whitespace().any(|t| t == EVENT_STREAM_FEATURE)) {
+            self.set_flavor(Flavor::Stream);
         }
     }
 }
@@ -587,9 +915,232 @@ impl<R: io::BufRead> From<R> for XesReader<R> {
     }
 }
 
+/// Options of a `XesReader`
+///
+/// Options are set by builder methods, deserialized from a configuration file or read from the
+/// attributes of a plugin. `attributes` turns them into attributes of a flow `Segment`, such that
+/// readers are configured the same way in code and in flows. Unset options keep the defaults of
+/// the reader.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct XesReaderOptions {
+    /// Read the document as this flavor, regardless of its declared features
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flavor: Option<Flavor>,
+    /// Compression of the file, guessed from its extension otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    /// Skip traces and events that can't be parsed, see `XesReader::skip_invalid`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skip_invalid: bool,
+}
+
+impl XesReaderOptions {
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = Some(flavor);
+        self
+    }
+
+    /// Emit traces unfolded, see `XesReader::streaming_events`
+    pub fn streaming_events(self) -> Self {
+        self.flavor(Flavor::Unfolded)
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn skip_invalid(mut self) -> Self {
+        self.skip_invalid = true;
+        self
+    }
+
+    /// Declare the optional plugin attributes `flavor`, `compression` and `skip_invalid`
+    pub fn declare(declaration: Declaration) -> Declaration {
+        declaration
+            .optional_attr(
+                "flavor",
+                "Read as log, stream or unfolded",
+                AttributeType::String,
+            )
+            .optional_attr(
+                "compression",
+                "Compression of the file (none or gzip), derived from the extension by default",
+                AttributeType::String,
+            )
+            .optional_attr(
+                "skip_invalid",
+                "Skip invalid traces and events instead of failing",
+                AttributeType::Boolean,
+            )
+    }
+
+    /// Read the optional plugin attributes `flavor`, `compression` and `skip_invalid`
+    pub fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        let mut options = XesReaderOptions::default();
+        if let Ok(flavor) = parameters.acquire_attribute("flavor") {
+            options.flavor = Some(flavor.value.try_string()?.parse()?);
+        }
+        if let Ok(compression) = parameters.acquire_attribute("compression") {
+            options.compression = Some(compression.value.try_string()?.parse()?);
+        }
+        if let Ok(skip_invalid) = parameters.acquire_attribute("skip_invalid") {
+            options.skip_invalid = *skip_invalid.value.try_boolean()?;
+        }
+        Ok(options)
+    }
+
+    /// Plugin attributes of the options that are set
+    pub fn attributes(&self) -> Vec<Attribute> {
+        let mut attributes = Vec::new();
+        if let Some(flavor) = self.flavor {
+            attributes.push(("flavor", flavor.to_string()).into());
+        }
+        if let Some(compression) = self.compression {
+            attributes.push(("compression", compression.to_string()).into());
+        }
+        if self.skip_invalid {
+            attributes.push(("skip_invalid", true).into());
+        }
+        attributes
+    }
+
+    /// Create a reader with these options
+    pub fn reader<R: io::BufRead>(&self, reader: R) -> XesReader<R> {
+        let mut reader = XesReader::new(reader);
+        if let Some(flavor) = self.flavor {
+            reader = reader.flavor(flavor);
+        }
+        if self.skip_invalid {
+            reader = reader.skip_invalid();
+        }
+        reader
+    }
+
+    /// Open a file with these options
+    pub fn open<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<XesReader<BufReader<Box<dyn io::Read + Send>>>> {
+        let path = path.as_ref();
+        let compression = self
+            .compression
+            .unwrap_or_else(|| Compression::from_path(&path.to_string_lossy()));
+        let file = File::open(path)?;
+
+        // the size of compressed files doesn't tell how much there is to read
+        let size = match compression {
+            Compression::None => Some(file.metadata()?.len()),
+            _ => None,
+        };
+        let reader = self.reader(BufReader::new(compression.reader(file)));
+        Ok(match size {
+            Some(size) => reader.size_hint(size),
+            None => reader,
+        })
+    }
+}
+
 impl<R: io::BufRead> XesReader<R> {
+    /// Emit a payload component, preceded by the meta data on the first call
+    fn emit(&mut self, component: Component) -> ResOpt {
+        Ok(Some(if let Some(meta) = self.meta.take() {
+            self.cache.push_back(component);
+            Component::Meta(meta)
+        } else {
+            component
+        }))
+    }
+
+    /// Take the attributes parsed so far from the open trace into a trace without events
+    fn unfold_trace(&mut self) -> Result<Trace> {
+        let intermediate = self.stack.last_mut().unwrap();
+        Trace::try_from(XesIntermediate {
+            type_name: intermediate.type_name.clone(),
+            attributes: HashMap::new(),
+            components: std::mem::take(&mut intermediate.components),
+        })
+    }
+
+    /// Skip the innermost trace or event around an error, if the reader is lenient
+    ///
+    /// `closed` names the element that failed after it was popped from the stack already.
+    ///
+    fn recover(&mut self, error: Error, closed: Option<&str>) -> Result<()> {
+        if self.skipped.is_none() {
+            return Err(error);
+        }
+
+        let component = |name: &str| match name {
+            "trace" => Some(ComponentType::Trace),
+            "event" => Some(ComponentType::Event),
+            _ => None,
+        };
+        let skipped = |component| SkippedComponent {
+            component,
+            diagnostic: self.diagnostic(&error),
+        };
+
+        // components directly below the log element, or events of traces
+        if let Some(closed) = closed
+            .filter(|_| !self.stack.is_empty())
+            .and_then(component)
+        {
+            let skipped = skipped(closed);
+            self.skipped.as_mut().unwrap().record(skipped);
+            return Ok(());
+        }
+        let open = self
+            .stack
+            .iter()
+            .enumerate()
+            .skip(1)
+            .rev()
+            .find_map(|(i, e)| component(&e.type_name).map(|c| (i, c)));
+        match open {
+            Some((depth, component)) => {
+                self.skipping = Some((depth + 1, skipped(component)));
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Whether the element that was just closed ends the component being skipped
+    fn skip_end(&mut self) -> bool {
+        match &self.skipping {
+            Some((depth, _)) if self.stack.len() + 1 > *depth => true,
+            Some((depth, _)) if self.stack.len() + 1 == *depth => {
+                let (_, skipped) = self.skipping.take().unwrap();
+                if skipped.component == ComponentType::Trace {
+                    self.unfolded = false;
+                }
+                self.skipped.as_mut().unwrap().record(skipped);
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn update(&mut self, intermediate: XesIntermediate) -> ResOpt {
-        let component = XesComponent::try_from(intermediate)?;
+        let component = XesComponent::from_intermediate(intermediate, &mut self.keys)?;
+
+        // events of an unfolded trace are emitted immediately, preceded by the trace attributes
+        let in_trace = self.stack.len() == 2 && self.stack[1].type_name == "trace";
+        if self.flavor == Flavor::Unfolded && in_trace {
+            if let XesComponent::Event(event) = component {
+                if self.unfolded {
+                    return Ok(Some(Component::Event(event)));
+                }
+                self.unfolded = true;
+                let trace = self.unfold_trace()?;
+                let first = self.emit(Component::Trace(trace))?;
+                self.cache.push_back(Component::Event(event));
+                return Ok(first);
+            }
+        }
 
         if self.stack.len() <= 1 {
             match component {
@@ -625,20 +1176,21 @@ impl<R: io::BufRead> XesReader<R> {
                     return Err(Error::StateError(format!("unexpected: {:?}", value)));
                 }
                 XesComponent::Trace(trace) => {
-                    return if let Some(meta) = self.meta.take() {
-                        self.cache = Some(Component::Trace(trace));
-                        Ok(Some(Component::Meta(meta)))
-                    } else {
-                        Ok(Some(Component::Trace(trace)))
-                    };
+                    if self.flavor == Flavor::Stream {
+                        return Err(Error::XesError(
+                            "unexpected trace in event stream".to_string(),
+                        ));
+                    }
+                    if self.flavor == Flavor::Unfolded && std::mem::take(&mut self.unfolded) {
+                        if !trace.attributes.is_empty() {
+                            warn!("trace attributes after the first event of an unfolded trace are dropped");
+                        }
+                        return Ok(None);
+                    }
+                    return self.emit(Component::Trace(trace));
                 }
                 XesComponent::Event(event) => {
-                    return if let Some(meta) = self.meta.take() {
-                        self.cache = Some(Component::Event(event));
-                        Ok(Some(Component::Meta(meta)))
-                    } else {
-                        Ok(Some(Component::Event(event)))
-                    };
+                    return self.emit(Component::Event(event));
                 }
                 XesComponent::Log(_) => {
                     self.empty = false;
@@ -655,6 +1207,111 @@ impl<R: io::BufRead> XesReader<R> {
     }
 }
 
+/// Element levels the structural scan distinguishes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScanLevel {
+    Log,
+    Trace,
+    Event,
+    Other,
+}
+
+impl<R: io::BufRead> XesReader<R> {
+    /// Scan the structure of the document without building components
+    ///
+    /// This counts traces and events, collects the keys of top level trace and event attributes and
+    /// the range of event timestamps. No other values are parsed and nothing is validated, which
+    /// makes it considerably faster than reading the stream.
+    ///
+    pub fn scan(mut self) -> Result<QuickStats> {
+        let mut stats = QuickStats::default();
+        match self.scan_into(&mut stats) {
+            Ok(()) => Ok(stats),
+            Err(error) => Err(self.diagnose(error)),
+        }
+    }
+
+    fn scan_into(&mut self, stats: &mut QuickStats) -> Result<()> {
+        let mut levels: Vec<ScanLevel> = Vec::new();
+
+        loop {
+            let (element, open) = match self.reader.read_event(&mut self.buffer) {
+                Ok(QxEvent::Start(element)) => (element, true),
+                Ok(QxEvent::Empty(element)) => (element, false),
+                Ok(QxEvent::End(_)) => {
+                    levels.pop();
+                    self.buffer.clear();
+                    continue;
+                }
+                Ok(QxEvent::Eof) => break,
+                Err(error) => return Err(error.into()),
+                _ => {
+                    self.buffer.clear();
+                    continue;
+                }
+            };
+
+            self.element.clear();
+            self.element.extend_from_slice(element.name());
+
+            let parent = levels.last().copied();
+            let level = match (element.name(), parent) {
+                (b"log", None) => ScanLevel::Log,
+                (b"trace", Some(ScanLevel::Log)) => {
+                    stats.traces += 1;
+                    ScanLevel::Trace
+                }
+                (b"event", Some(ScanLevel::Log)) | (b"event", Some(ScanLevel::Trace)) => {
+                    stats.events += 1;
+                    ScanLevel::Event
+                }
+                (name, Some(ScanLevel::Trace)) | (name, Some(ScanLevel::Event)) => {
+                    let in_event = parent == Some(ScanLevel::Event);
+                    let mut key = None;
+                    let mut value = None;
+                    for attribute in element.attributes() {
+                        let attribute = attribute?;
+                        match attribute.key {
+                            b"key" => key = Some(attribute.value),
+                            b"value" => value = Some(attribute.value),
+                            _ => (),
+                        }
+                    }
+
+                    if let Some(key) = key {
+                        let key = std::str::from_utf8(&key)?;
+                        let keys = if in_event {
+                            &mut stats.event_keys
+                        } else {
+                            &mut stats.trace_keys
+                        };
+                        if !keys.contains(key) {
+                            keys.insert(key.to_string());
+                        }
+
+                        if in_event && name == b"date" && key == "time:timestamp" {
+                            if let Some(value) = value {
+                                let timestamp =
+                                    DateTime::parse_from_rfc3339(std::str::from_utf8(&value)?)?;
+                                stats.update_range(timestamp);
+                            }
+                        }
+                    }
+                    ScanLevel::Other
+                }
+                _ => ScanLevel::Other,
+            };
+
+            if open {
+                levels.push(level);
+            }
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+}
+
 impl<T: io::BufRead + Send> Stream for XesReader<T> {
     fn inner_ref(&self) -> Option<&dyn Stream> {
         None
@@ -666,37 +1323,86 @@ impl<T: io::BufRead + Send> Stream for XesReader<T> {
 
     fn next(&mut self) -> ResOpt {
         // At the transition of the meta data fields to actual stream data the first trace/event
-        // will be cached and emitted in the next iteration. This is supposed to happen once per
-        // stream at max.
-        if let Some(component) = self.cache.take() {
+        // will be cached and emitted in the next iteration. Unfolded traces cache their first
+        // event likewise.
+        if let Some(component) = self.cache.pop_front() {
             return Ok(Some(component));
         }
 
+        self.read_next().map_err(|error| self.diagnose(error))
+    }
+
+    fn read_position(&self) -> Option<ReadPosition> {
+        Some(ReadPosition {
+            bytes: self.reader.buffer_position() as u64,
+            total: self.size,
+        })
+    }
+
+    fn on_emit_artifacts(&mut self) -> Result<Vec<AnyArtifact>> {
+        Ok(self
+            .skipped
+            .take()
+            .map(|skipped| vec![skipped.into()])
+            .unwrap_or_default())
+    }
+}
+
+impl<R: io::BufRead> XesReader<R> {
+    fn read_next(&mut self) -> ResOpt {
         loop {
             match self.reader.read_event(&mut self.buffer) {
                 Ok(QxEvent::Start(event)) => {
-                    let intermediate = XesIntermediate::from_event(event)?;
-                    self.stack.push(intermediate);
+                    self.element.clear();
+                    self.element.extend_from_slice(event.name());
+                    let name = String::from_utf8_lossy(event.name()).to_string();
+                    match XesIntermediate::from_event(event) {
+                        Ok(intermediate) if self.skipping.is_none() => {
+                            self.detect_flavor(&intermediate);
+                            self.stack.push(intermediate);
+                        }
+                        result => {
+                            // placeholder that keeps track of the depth
+                            self.stack.push(XesIntermediate {
+                                type_name: name,
+                                attributes: HashMap::new(),
+                                components: Vec::new(),
+                            });
+                            if let (Err(error), None) = (result, &self.skipping) {
+                                self.recover(error, None)?;
+                            }
+                        }
+                    }
                 }
-                Ok(QxEvent::End(_event)) => {
+                Ok(QxEvent::End(event)) => {
+                    self.element.clear();
+                    self.element.extend_from_slice(event.name());
                     let intermediate = self.stack.pop().unwrap();
-                    if let Some(component) = self.update(intermediate)? {
-                        return Ok(Some(component));
+                    if !self.skip_end() {
+                        let name = intermediate.type_name.clone();
+                        match self.update(intermediate) {
+                            Ok(Some(component)) => return Ok(Some(component)),
+                            Ok(None) => (),
+                            Err(error) => self.recover(error, Some(&name))?,
+                        }
                     }
                 }
+                Ok(QxEvent::Empty(_)) if self.skipping.is_some() => (),
                 Ok(QxEvent::Empty(event)) => {
-                    let intermediate = XesIntermediate::from_event(event)?;
-                    if let Some(component) = self.update(intermediate)? {
-                        return Ok(Some(component));
+                    self.element.clear();
+                    self.element.extend_from_slice(event.name());
+                    let name = String::from_utf8_lossy(event.name()).to_string();
+                    let component = XesIntermediate::from_event(event).and_then(|intermediate| {
+                        self.detect_flavor(&intermediate);
+                        self.update(intermediate)
+                    });
+                    match component {
+                        Ok(Some(component)) => return Ok(Some(component)),
+                        Ok(None) => (),
+                        Err(error) => self.recover(error, Some(&name))?,
                     }
                 }
-                Err(error) => {
-                    return Err(Error::XesError(format!(
-                        "Error at position {}: {:?}",
-                        self.reader.buffer_position(),
-                        error
-                    )));
-                }
+                Err(error) => return Err(error.into()),
                 Ok(QxEvent::Eof) => {
                     if self.empty {
                         return Err(Error::XesError(String::from("No root component found")));
@@ -714,21 +1420,76 @@ impl<T: io::BufRead + Send> Stream for XesReader<T> {
 }
 
 /// XML serialization of XES
+///
+/// The log element is written along with the first component, such that the flavor declared by
+/// the meta data is taken into account. Unfolded traces are folded again, i.e. events are written
+/// into the element of the preceding trace.
+///
 pub struct XesWriter<W: io::Write> {
     writer: QxWriter<W>,
+    flavor: Flavor,
+    started: bool,
+    unfolded: bool,
+    /// Attributes of the unfolded trace whose events are being written
+    open_trace: Option<AttributeMap>,
 }
 
 impl<W: io::Write> XesWriter<W> {
     pub fn new(writer: W) -> Self {
         XesWriter {
             writer: QxWriter::new(writer),
+            flavor: Flavor::Log,
+            started: false,
+            unfolded: false,
+            open_trace: None,
         }
     }
 
     pub fn with_indent(writer: W, indent_char: u8, indent_size: usize) -> Self {
         XesWriter {
             writer: QxWriter::new_with_indent(writer, indent_char, indent_size),
+            flavor: Flavor::Log,
+            started: false,
+            unfolded: false,
+            open_trace: None,
+        }
+    }
+
+    /// Write an event stream, regardless of the flavor declared by the meta data
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = flavor;
+        self.unfolded |= flavor == Flavor::Unfolded;
+        self
+    }
+
+    /// Close the element of the unfolded trace, if any
+    fn close_trace(&mut self) -> Result<()> {
+        if self.open_trace.take().is_some() && self.flavor != Flavor::Stream {
+            self.writer
+                .write_event(QxEvent::End(QxBytesEnd::borrowed(b"trace")))?;
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.started {
+            return Ok(());
         }
+
+        let tag = b"log";
+        let mut event = QxBytesStart::owned(tag.to_vec(), tag.len());
+
+        let features = match self.flavor {
+            Flavor::Log | Flavor::Unfolded => "nested-attributes".to_string(),
+            Flavor::Stream => format!("nested-attributes {}", EVENT_STREAM_FEATURE),
+        };
+        event.push_attribute(("xes.version", "1849.2016"));
+        event.push_attribute(("xes.features", features.as_str()));
+
+        self.writer.write_event(QxEvent::Start(event))?;
+        self.started = true;
+
+        Ok(())
     }
 }
 
@@ -751,29 +1512,51 @@ impl<W: io::Write + Send> Sink for XesWriter<W> {
                 .write_event(QxEvent::Comment(QxBytesText::from_plain_str(s)))
         })?;
 
-        // write contents
-        let tag = b"log";
-        let mut event = QxBytesStart::owned(tag.to_vec(), tag.len());
-
-        event.push_attribute(("xes.version", "1849.2016"));
-        event.push_attribute(("xes.features", "nested-attributes"));
-
-        self.writer.write_event(QxEvent::Start(event))?;
-
         Ok(())
     }
 
     fn on_component(&mut self, component: Component) -> Result<()> {
+        if let Component::Meta(meta) = &component {
+            match meta.flavor {
+                Flavor::Stream => self.flavor = Flavor::Stream,
+                Flavor::Unfolded => self.unfolded = true,
+                Flavor::Log => (),
+            }
+        }
+        self.start()?;
+
         match component {
             Component::Meta(meta) => meta.write_xes(&mut self.writer)?,
+            Component::Trace(trace) if self.flavor == Flavor::Stream => {
+                for mut event in trace.events {
+                    add_case_attributes(&mut event, &trace.attributes);
+                    event.write_xes(&mut self.writer)?;
+                }
+                if self.unfolded {
+                    self.open_trace = Some(trace.attributes);
+                }
+            }
+            Component::Trace(trace) if self.unfolded => {
+                self.close_trace()?;
+                trace.write_xes_open(&mut self.writer)?;
+                self.open_trace = Some(trace.attributes);
+            }
             Component::Trace(trace) => trace.write_xes(&mut self.writer)?,
-            Component::Event(event) => event.write_xes(&mut self.writer)?,
+            Component::Event(mut event) => {
+                if let (Flavor::Stream, Some(attributes)) = (self.flavor, &self.open_trace) {
+                    add_case_attributes(&mut event, attributes);
+                }
+                event.write_xes(&mut self.writer)?
+            }
         };
 
         Ok(())
     }
 
     fn on_close(&mut self) -> Result<()> {
+        self.start()?;
+        self.close_trace()?;
+
         let event = QxEvent::End(QxBytesEnd::borrowed(b"log"));
 
         self.writer.write_event(event)?;
@@ -783,18 +1566,124 @@ impl<W: io::Write + Send> Sink for XesWriter<W> {
     }
 }
 
+/// Add the trace attributes to an event, prefixed by `case:`
+fn add_case_attributes(event: &mut Event, attributes: &AttributeMap) {
+    for (key, value, children) in attributes.iter() {
+        event.attributes.insert(Attribute {
+            key: format!("{}{}", CASE_PREFIX, key).into(),
+            value: value.clone(),
+            children: children.to_vec(),
+        });
+    }
+}
+
 impl<W: io::Write> XesWriter<W> {
     /// Get a reference of the underlying writer
     pub fn inner(&mut self) -> &W {
         self.writer.inner()
     }
 
+    /// Get a mutable reference of the underlying writer
+    pub fn inner_mut(&mut self) -> &mut W {
+        self.writer.inner()
+    }
+
     /// Release the underlying writer
     pub fn into_inner(self) -> W {
         self.writer.into_inner()
     }
 }
 
+impl<W: io::Write + Send + 'static> XesWriter<W> {
+    /// Serialize on a worker thread that buffers up to `bound` components
+    pub fn background(self, bound: usize) -> BackgroundXesWriter<W> {
+        let (sender, mut receiver) = stream_channel(Some(bound.max(1)));
+        let mut writer = self;
+        let worker = thread::spawn(move || {
+            writer.consume(&mut receiver)?;
+            Ok(writer)
+        });
+
+        BackgroundXesWriter {
+            sender: Some(sender),
+            worker: Some(worker),
+            writer: None,
+        }
+    }
+}
+
+/// XES serialization on a worker thread, see `XesWriter::background`
+///
+/// Errors of the worker surface with the next component sent or when the stream is closed.
+///
+pub struct BackgroundXesWriter<W: io::Write> {
+    sender: Option<StreamSender>,
+    worker: Option<JoinHandle<Result<XesWriter<W>>>>,
+    writer: Option<XesWriter<W>>,
+}
+
+impl<W: io::Write> BackgroundXesWriter<W> {
+    /// Wait for the worker to terminate
+    fn join(&mut self) -> Result<()> {
+        if let Some(worker) = self.worker.take() {
+            let writer = worker
+                .join()
+                .map_err(|_| Error::XesError("XES writer thread panicked".to_string()))??;
+            self.writer = Some(writer);
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, component: ResOpt) -> Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| Error::XesError("XES writer is released".to_string()))?;
+        match sender.send(component) {
+            Ok(()) => Ok(()),
+            // the worker only hangs up on failure, which is the more relevant error
+            Err(error) => self.join().and(Err(error)),
+        }
+    }
+
+    /// Release the underlying writer, once the stream is closed
+    ///
+    /// If the stream isn't closed yet, the worker is stopped and an error is returned as the
+    /// output is incomplete.
+    ///
+    pub fn into_inner(mut self) -> Result<W> {
+        if self.worker.is_some() {
+            // hang up first, otherwise the worker waits for further components forever
+            self.sender = None;
+            self.join().ok();
+            return Err(Error::XesError(
+                "XES writer released before the stream was closed".to_string(),
+            ));
+        }
+        match self.writer {
+            Some(writer) => Ok(writer.into_inner()),
+            None => Err(Error::XesError("XES writer thread failed".to_string())),
+        }
+    }
+}
+
+impl<W: io::Write + Send> Sink for BackgroundXesWriter<W> {
+    fn on_component(&mut self, component: Component) -> Result<()> {
+        self.send(Ok(Some(component)))
+    }
+
+    fn on_close(&mut self) -> Result<()> {
+        self.send(Ok(None))?;
+        self.join()
+    }
+
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        // the worker passes on the error it was sent
+        self.send(Err(error))?;
+        self.join().or(Ok(()))
+    }
+}
+
 /// Dummy struct for XES Plugins
 pub struct XesPluginProvider;
 
@@ -806,48 +1695,97 @@ impl PluginProvider for XesPluginProvider {
         vec![
             Entry::new(
                 "XesReader",
-                "Parse the XES format from a file",
+                "Parse the XES format from a file, optionally gzip compressed, as given flavor (log, stream or unfolded) and skipping invalid traces and events (skip_invalid)",
                 Factory::new(
-                    Declaration::default().attribute("path", "Location of the XES file"),
+                    Declaration::default()
+                        .attribute("path", "Location of the XES file")
+                        .with(XesReaderOptions::declare),
                     FactoryType::Stream(Box::new(|parameters| -> Result<Box<dyn Stream>> {
                         let path = parameters
                             .acquire_attribute("path")?
                             .value
                             .try_string()?
                             .to_string();
-                        let file = File::open(&Path::new(&path))
-                            .map_err(|e| Error::StreamError(format!("{:?}", e)))?;
-                        let reader = BufReader::new(file);
-                        Ok(XesReader::from(reader).into_boxed())
+                        Ok(XesReaderOptions::from_parameters(parameters)?
+                            .open(&path)?
+                            .into_boxed())
                     })),
                 ),
             ),
             Entry::new(
                 "XesWriter",
-                "Render the stream into the XES format",
+                "Render the stream into the XES format, optionally compressed and rotated",
                 Factory::new(
                     Declaration::default()
-                        .attribute("path", "Location of the XES file")
-                        .default_attr("indent", "Indentation", |n| (n, 0).into()),
+                        .typed_attr("path", "Location of the XES file", AttributeType::String)
+                        .default_attr("indent", "Indentation", |n| (n, 0).into())
+                        .default_attr(
+                            "queue",
+                            "Serialize on a worker thread that buffers the given number of components, 0 to serialize in place",
+                            |n| (n, 0).into(),
+                        )
+                        .optional_attr(
+                            "flavor",
+                            "Write as log or stream, log by default",
+                            AttributeType::String,
+                        )
+                        .optional_attr(
+                            "compression",
+                            "Compression of the file (none or gzip), derived from the extension by default",
+                            AttributeType::String,
+                        )
+                        .with(FileOptions::declare)
+                        .with(Rotation::declare)
+                        .with(ProvenanceWriter::declare),
                     FactoryType::Sink(Box::new(|parameters| -> Result<Box<dyn Sink>> {
                         let path = parameters
                             .acquire_attribute("path")?
                             .value
                             .try_string()?
                             .to_string();
-                        let file = File::create(&Path::new(&path))
-                            .map_err(|e| Error::StreamError(format!("{:?}", e)))?;
-                        let writer = BufWriter::new(file);
                         let indent = parameters
                             .acquire_attribute("indent")?
                             .value
                             .try_int()
                             .map(|v| *v as usize)?;
-                        Ok(Box::new(if indent > 0 {
-                            XesWriter::with_indent(writer, b'\t', indent)
+                        let queue = parameters
+                            .acquire_attribute("queue")?
+                            .value
+                            .try_int()
+                            .map(|v| *v as usize)?;
+
+                        let flavor = match parameters.acquire_attribute("flavor") {
+                            Ok(flavor) => flavor.value.try_string()?.parse()?,
+                            Err(_) => Flavor::Log,
+                        };
+
+                        let compression = Compression::from_parameters(&path, parameters)?;
+                        let options = FileOptions::from_parameters(parameters)?;
+
+                        // shared by all files if rotated
+                        let build = move |file: Box<dyn io::Write + Send>| -> Box<dyn Sink> {
+                            let writer = if indent > 0 {
+                                XesWriter::with_indent(file, b'\t', indent)
+                            } else {
+                                XesWriter::new(file)
+                            }
+                            .flavor(flavor);
+                            match queue {
+                                0 => writer.into_boxed(),
+                                queue => writer.background(queue).into_boxed(),
+                            }
+                        };
+
+                        let sink = if let Some(rotation) = Rotation::from_parameters(parameters)? {
+                            let builder: SinkBuilder =
+                                Box::new(move |file| Ok(build(compression.writer(file))));
+                            RotatingWriter::new(&path, rotation, builder)
+                                .options(options)
+                                .into_boxed()
                         } else {
-                            XesWriter::new(writer)
-                        }))
+                            build(compression.writer(options.create(&path)?))
+                        };
+                        ProvenanceWriter::from_parameters(sink, &path, parameters)
                     })),
                 ),
             ),
@@ -863,8 +1801,14 @@ mod tests {
     use std::path::PathBuf;
     use std::process::{Command, Output, Stdio};
 
+    use chrono::Duration;
+
+    use crate::dev_util::load_example;
     use crate::stream::buffer::Buffer;
+    use crate::stream::compare::{assert_stream_eq, CompareOptions};
+    use crate::stream::order::OrderGuard;
     use crate::stream::void::consume;
+    use crate::stream::AttributeContainer;
 
     use super::*;
 
@@ -963,36 +1907,30 @@ mod tests {
         validate_directory(join_static!("xes", "recoverable"));
     }
 
-    // deserialize-serialize-deserialize-serialize XES, check whether outputs are consistent
+    // deserialize-serialize-deserialize XES, check whether the result equals the original stream
     fn serialize_deserialize_identity(path: PathBuf) {
         for p in fs::read_dir(path).unwrap().map(|p| p.unwrap()) {
-            let mut buffer = Buffer::default();
-            let mut snapshots: Vec<Vec<u8>> = Vec::new();
-
-            buffer
+            let mut original = Buffer::default();
+            original
                 .consume(&mut XesReader::from(join_static_reader!(&p.path())))
                 .unwrap();
 
-            for _ in 0..2 {
-                // serialize to XML
-                let bytes: Vec<u8> = Vec::new();
-                let mut writer = XesWriter::with_indent(bytes, b'1', 1);
-                writer.consume(&mut buffer).unwrap();
-
-                // make snapshot
-                let bytes = writer.into_inner();
-                snapshots.push(bytes.clone());
-
-                // deserialize from XML
-                let mut reader = XesReader::from(io::Cursor::new(bytes));
-                buffer
-                    .consume(&mut reader)
-                    .unwrap_or_else(|_| panic!("{:?}", p.path()));
-            }
-
-            for (s1, s2) in snapshots.iter().zip(&snapshots[1..]) {
-                assert_eq!(s1, s2)
-            }
+            // serialize to XML
+            let bytes: Vec<u8> = Vec::new();
+            let mut writer = XesWriter::with_indent(bytes, b'1', 1);
+            writer.consume(&mut original.clone()).unwrap();
+
+            // deserialize from XML
+            let mut deserialized = Buffer::default();
+            deserialized
+                .consume(&mut XesReader::from(io::Cursor::new(writer.into_inner())))
+                .unwrap_or_else(|_| panic!("{:?}", p.path()));
+
+            assert_stream_eq(
+                &mut original,
+                &mut deserialized,
+                &CompareOptions::default().timestamp_precision(Duration::milliseconds(1)),
+            );
         }
     }
 
@@ -1003,4 +1941,291 @@ mod tests {
         serialize_deserialize_identity(join_static!("xes", "correct"));
         serialize_deserialize_identity(join_static!("xes", "recoverable"));
     }
+
+    #[test]
+    fn test_event_stream() {
+        let mut trace = Trace::default();
+        trace.attributes.insert(("concept:name", "case 1"));
+        for activity in ["a", "b"].iter() {
+            let mut event = Event::default();
+            event.attributes.insert(("concept:name", *activity));
+            trace.events.push(event);
+        }
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Meta(Meta::default()))));
+        buffer.push(Ok(Some(Component::Trace(trace))));
+
+        let mut writer = XesWriter::new(Vec::new()).flavor(Flavor::Stream);
+        writer.consume(&mut buffer).unwrap();
+        let xes = writer.into_inner();
+        assert!(String::from_utf8_lossy(&xes).contains("nested-attributes event-stream"));
+
+        let mut reader = XesReader::from(io::BufReader::new(&xes[..]));
+        match reader.next().unwrap() {
+            Some(Component::Meta(meta)) => assert_eq!(meta.flavor, Flavor::Stream),
+            other => panic!("expected meta data, got {:?}", other),
+        }
+        for activity in ["a", "b"].iter() {
+            match reader.next().unwrap() {
+                Some(Component::Event(event)) => {
+                    assert_eq!(
+                        event
+                            .get_value("concept:name")
+                            .unwrap()
+                            .try_string()
+                            .unwrap(),
+                        *activity
+                    );
+                    assert_eq!(
+                        event
+                            .get_value("case:concept:name")
+                            .unwrap()
+                            .try_string()
+                            .unwrap(),
+                        "case 1"
+                    );
+                }
+                other => panic!("expected event, got {:?}", other),
+            }
+        }
+        assert!(reader.next().unwrap().is_none());
+
+        // traces are rejected in event streams
+        let mut reader =
+            XesReader::from(join_static_reader!("xes", "book", "L1.xes")).flavor(Flavor::Stream);
+        assert!(consume(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_streaming_events() {
+        let mut reader = OrderGuard::new(
+            XesReader::from(join_static_reader!("xes", "book", "L1.xes")).streaming_events(),
+        );
+        let mut components = Vec::new();
+        while let Some(component) = reader.next().unwrap() {
+            components.push(component);
+        }
+
+        assert_eq!(components.len(), 1 + 6 + 23);
+        match &components[0] {
+            Component::Meta(meta) => assert_eq!(meta.flavor, Flavor::Unfolded),
+            other => panic!("expected meta data, got {:?}", other),
+        }
+        match &components[1] {
+            Component::Trace(trace) => {
+                assert!(trace.events.is_empty());
+                assert!(trace.get_value("concept:name").is_some());
+            }
+            other => panic!("expected trace, got {:?}", other),
+        }
+        let kinds: String = components[1..6]
+            .iter()
+            .map(|c| match c {
+                Component::Trace(_) => 't',
+                _ => 'e',
+            })
+            .collect();
+        assert_eq!(kinds, "teeet");
+
+        // the writer folds the traces again
+        let mut writer = XesWriter::new(Vec::new());
+        writer
+            .consume(
+                &mut XesReader::from(join_static_reader!("xes", "book", "L1.xes"))
+                    .streaming_events(),
+            )
+            .unwrap();
+        let xes = writer.into_inner();
+        assert_stream_eq(
+            &mut load_example(&["book", "L1.xes"]),
+            &mut XesReader::from(io::BufReader::new(&xes[..])),
+            &CompareOptions::default(),
+        );
+    }
+
+    #[test]
+    fn test_shared_keys() {
+        let mut reader =
+            XesReader::from(join_static_reader!("xes", "book", "L1.xes")).flavor(Flavor::Unfolded);
+        let mut keys = Vec::new();
+        while let Some(component) = reader.next().unwrap() {
+            if let Component::Event(event) = component {
+                let (key, _, _) = event
+                    .attributes
+                    .iter()
+                    .find(|(key, _, _)| *key == "concept:name")
+                    .unwrap();
+                keys.push(key.as_ptr());
+            }
+        }
+
+        assert_eq!(keys.len(), 23);
+        assert!(keys.iter().all(|key| *key == keys[0]));
+    }
+
+    #[test]
+    fn test_skip_invalid() {
+        let s = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <log xes.version="1.0" xes.features="">
+                <string key="concept:name" value="log"/>
+                <trace>
+                    <string key="concept:name" value="1"/>
+                    <event><string key="concept:name" value="a"/></event>
+                    <event><date key="time:timestamp" value="yesterday"/></event>
+                    <event><string key="concept:name" value="b"/></event>
+                </trace>
+                <trace>
+                    <int key="cost" value="many"/>
+                    <event><string key="concept:name" value="c"/></event>
+                </trace>
+                <trace>
+                    <string key="concept:name" value="3"/>
+                </trace>
+                <event><float key="cost" value="1.5"/></event>
+                <event><duration key="cost" value="1.5"/></event>
+            </log>"#;
+
+        // errors are located by line and column
+        let mut reader = XesReader::from(io::BufReader::new(s.as_bytes()));
+        let error = consume(&mut reader).unwrap_err();
+        let diagnostic = error.diagnostic().unwrap();
+        let line = s.lines().nth(6).unwrap();
+        assert_eq!(diagnostic.line, 7);
+        assert_eq!(diagnostic.column, line.find("/>").unwrap() + 3);
+        assert_eq!(diagnostic.element.as_deref(), Some("date"));
+        assert!(error
+            .to_string()
+            .starts_with("Parse Error: line 7, column 74 in <date>:"));
+
+        let mut reader = XesReaderOptions::default()
+            .skip_invalid()
+            .reader(io::BufReader::new(s.as_bytes()));
+        let mut log = Log::default();
+        let artifacts = log.consume(&mut reader).unwrap();
+
+        assert_eq!(log.traces.len(), 2);
+        assert_eq!(log.traces[0].events.len(), 2);
+        assert_eq!(log.traces[1].events.len(), 0);
+        assert_eq!(log.events.len(), 1);
+
+        let skipped =
+            AnyArtifact::find::<SkippedComponents>(&mut artifacts.iter().flatten()).unwrap();
+        assert_eq!((skipped.traces, skipped.events), (1, 2));
+        let components: Vec<_> = skipped.components.iter().map(|c| &c.component).collect();
+        assert_eq!(
+            components,
+            [
+                &ComponentType::Event,
+                &ComponentType::Trace,
+                &ComponentType::Event
+            ]
+        );
+        assert_eq!(skipped.components[0].diagnostic, *diagnostic);
+        assert_eq!(skipped.components[1].diagnostic.line, 11);
+        assert!(skipped.components[2]
+            .diagnostic
+            .message
+            .contains("duration"));
+
+        // broken meta data can't be skipped
+        let s = r#"<log><int key="cost" value="many"/><trace/></log>"#;
+        let mut reader = XesReader::from(io::BufReader::new(s.as_bytes())).skip_invalid();
+        assert!(consume(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_reader_options() {
+        let options = XesReaderOptions::default()
+            .streaming_events()
+            .compression(Compression::None);
+
+        let yaml = serde_yaml::to_string(&options).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<XesReaderOptions>(&yaml).unwrap(),
+            options
+        );
+        assert_eq!(
+            serde_yaml::from_str::<XesReaderOptions>("{}").unwrap(),
+            XesReaderOptions::default()
+        );
+
+        let attributes: Vec<_> = options
+            .attributes()
+            .iter()
+            .map(|a| (a.key.to_string(), a.value.try_string().unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                ("flavor".to_string(), "unfolded".to_string()),
+                ("compression".to_string(), "none".to_string())
+            ]
+        );
+
+        let mut reader = options.open(join_static!("xes", "book", "L1.xes")).unwrap();
+        match reader.next().unwrap() {
+            Some(Component::Meta(meta)) => assert_eq!(meta.flavor, Flavor::Unfolded),
+            other => panic!("expected meta data, got {:?}", other),
+        }
+        match reader.next().unwrap() {
+            Some(Component::Trace(trace)) => assert!(trace.events.is_empty()),
+            other => panic!("expected trace, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_background_writer() {
+        let mut writer = XesWriter::new(Vec::new()).background(4);
+        writer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        let xes = writer.into_inner().unwrap();
+        assert_stream_eq(
+            &mut load_example(&["book", "L1.xes"]),
+            &mut XesReader::from(io::BufReader::new(&xes[..])),
+            &CompareOptions::default(),
+        );
+
+        // errors of the stream terminate the worker
+        let mut buffer = Buffer::default();
+        buffer.push(Ok(Some(Component::Trace(Trace::default()))));
+        buffer.push(Err(Error::StreamError("broken".to_string())));
+        let mut writer = XesWriter::new(Vec::new()).background(1);
+        assert!(writer.consume(&mut buffer).is_err());
+        assert!(writer.into_inner().is_err());
+
+        // releasing the writer of an open stream fails instead of waiting for the worker
+        let mut writer = XesWriter::new(Vec::new()).background(4);
+        writer.on_open().unwrap();
+        writer
+            .on_component(Component::Trace(Trace::default()))
+            .unwrap();
+        assert!(writer.into_inner().is_err());
+    }
+
+    #[test]
+    fn test_gzip() {
+        let path = std::env::temp_dir().join(format!("promi_gzip_{}.xes.gz", std::process::id()));
+        let compression = Compression::from_path(path.to_str().unwrap());
+        assert_eq!(compression, Compression::Gzip);
+        assert_eq!(Compression::from_path("log.xes"), Compression::None);
+        assert!("zip".parse::<Compression>().is_err());
+
+        let mut writer = XesWriter::new(compression.writer(File::create(&path).unwrap()));
+        writer
+            .consume(&mut load_example(&["book", "L1.xes"]))
+            .unwrap();
+        drop(writer);
+
+        assert_eq!(&fs::read(&path).unwrap()[..2], &[0x1f, 0x8b]);
+
+        let file = File::open(&path).unwrap();
+        let mut reader = XesReader::from(BufReader::new(compression.reader(file)));
+        assert_stream_eq(
+            &mut load_example(&["book", "L1.xes"]),
+            &mut reader,
+            &CompareOptions::default(),
+        );
+        fs::remove_file(&path).unwrap();
+    }
 }